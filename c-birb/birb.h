@@ -0,0 +1,71 @@
+#ifndef BIRB_H
+#define BIRB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/// An embedded birb view tree, which is rendered by sending backend calls to a renderer (usually
+/// a birb IPC renderer in another process).
+typedef struct BirbHost BirbHost;
+
+typedef enum BirbStatus {
+    BirbOk = 0,
+    BirbError = 1,
+} BirbStatus;
+
+/// Returns the description of the root view as a JSON string, which must stay valid until the
+/// next call.
+///
+/// A description looks like `{"component": "Layer", "key": 1, "props": {...}, "subviews": [...]}`;
+/// `key`, `props`, and `subviews` are optional. Built-in components:
+///
+/// - `Layer`, with the props `bounds` ([x, y, width, height]), `background` ([r, g, b, a]),
+///   `background_gradient` ([r, g, b, a, r, g, b, a], from top to bottom), `corner_radius`,
+///   `border_width`, `border_color`, `border_alignment` ("inside", "center", or "outside"),
+///   `border_dash` ([dash, gap]), `clip_contents`, `opacity`, `group_opacity`,
+///   `should_rasterize`, and `material`.
+typedef const char *(*BirbDescribeFn)(void *user_data);
+
+/// Sends bytes to the renderer. The bytes form a stream and must be forwarded in order.
+typedef void (*BirbSendFn)(void *user_data, const uint8_t *data, size_t len);
+
+/// Creates a host. The root view will be described and rendered on the first call to
+/// birb_host_pump. The callbacks are only called from birb_host_pump.
+///
+/// Returns NULL if the host could not be created.
+BirbHost *birb_host_new(BirbDescribeFn describe, BirbSendFn send, void *user_data);
+
+/// Frees a host.
+void birb_host_free(BirbHost *host);
+
+/// Passes bytes received from the renderer to the host.
+///
+/// Returns BirbError if host is NULL, or if data is NULL while len isn't zero.
+BirbStatus birb_host_receive(BirbHost *host, const uint8_t *data, size_t len);
+
+/// Describes and renders the root view again on the next call to birb_host_pump.
+///
+/// Returns BirbError if host is NULL.
+BirbStatus birb_host_invalidate(BirbHost *host);
+
+/// Dispatches events from the renderer, renders if needed, and sends changes to the renderer.
+/// Should be called once per frame.
+///
+/// Returns BirbError if host is NULL.
+BirbStatus birb_host_pump(BirbHost *host);
+
+/// Returns a description of the last error, which stays valid until the next call to another
+/// host function, or NULL. Also returns NULL if host is NULL.
+///
+/// Host functions never unwind into the caller; panics are reported as errors.
+const char *birb_host_last_error(const BirbHost *host);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,455 @@
+//! C API for embedding birb in applications that aren’t written in Rust; see `birb.h`.
+//!
+//! A host runs a view tree that is built from [declarative descriptions](birb::declarative)
+//! supplied by the application, and renders it through an [IPC backend](birb::ipc). The
+//! application forwards the bytes it is given to a renderer (usually an `IpcRenderer` in a
+//! renderer process) and passes the renderer’s replies back in, so it never has to deal with
+//! native views itself.
+
+use birb::backend::Backend;
+use birb::declarative::{Description, Registry};
+use birb::dispatch::EventDispatcher;
+use birb::ipc::{IpcBackend, IpcError, IpcViewRef};
+use birb::{NVTree, ViewTree};
+use crossbeam::channel::{self, Receiver, Sender};
+use std::cell::Cell;
+use std::ffi::{c_void, CStr, CString};
+use std::io::{self, Read, Write};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::Instant;
+use std::{fmt, ptr, slice};
+
+/// Returns the description of the root view as a JSON string, which must stay valid until the
+/// next call.
+pub type BirbDescribeFn = extern "C" fn(user_data: *mut c_void) -> *const c_char;
+
+/// Sends bytes to the renderer.
+pub type BirbSendFn = extern "C" fn(user_data: *mut c_void, data: *const u8, len: usize);
+
+/// Result of a host function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BirbStatus {
+    BirbOk = 0,
+    BirbError = 1,
+}
+
+/// Writes to the application’s send callback while the host is being pumped.
+///
+/// Writes at any other time are discarded, so that the buffer that is flushed when the host is
+/// freed never reaches the callback.
+struct CallbackWriter {
+    send: BirbSendFn,
+    user_data: *mut c_void,
+    is_pumping: Rc<Cell<bool>>,
+}
+
+impl Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_pumping.get() {
+            (self.send)(self.user_data, buf.as_ptr(), buf.len());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads bytes passed to [`birb_host_receive`]; ends when the host is freed.
+struct ChannelReader {
+    bytes: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buffer.len() {
+            match self.bytes.recv() {
+                Ok(bytes) => {
+                    self.buffer = bytes;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// An embedded birb view tree.
+pub struct BirbHost {
+    views: ViewTree<()>,
+    native_views: NVTree<Box<IpcBackend<CallbackWriter>>, IpcViewRef>,
+    dispatcher: EventDispatcher,
+    registry: Registry<()>,
+    describe: BirbDescribeFn,
+    user_data: *mut c_void,
+    received: Sender<Vec<u8>>,
+    is_pumping: Rc<Cell<bool>>,
+    needs_render: bool,
+    last_error: Option<CString>,
+}
+
+impl fmt::Debug for BirbHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BirbHost")
+            .field("needs_render", &self.needs_render)
+            .field("last_error", &self.last_error)
+            .finish()
+    }
+}
+
+impl BirbHost {
+    fn render(&mut self) -> Result<(), String> {
+        let json = (self.describe)(self.user_data);
+        if json.is_null() {
+            return Err("no root view description".into());
+        }
+        let json = unsafe { CStr::from_ptr(json) }.to_string_lossy();
+        let description: Description =
+            serde_json::from_str(&json).map_err(|err| format!("invalid description: {}", err))?;
+        let view = self
+            .registry
+            .load(&description)
+            .map_err(|err| format!("invalid description: {}", err))?;
+        self.views.render_root(view, ());
+        Ok(())
+    }
+
+    fn pump(&mut self) -> Result<(), String> {
+        let result = self.update();
+        // flush even if updating failed, so that nothing is left to send when the host is freed
+        let flushed = self
+            .native_views
+            .backend_mut()
+            .flush()
+            .map_err(|err| err.to_string());
+        result.and(flushed)
+    }
+
+    fn update(&mut self) -> Result<(), String> {
+        let now = Instant::now();
+        loop {
+            match self.native_views.backend_mut().poll() {
+                Ok(Some(event)) => {
+                    self.dispatcher
+                        .dispatch(&event, now, &self.views, &mut self.native_views);
+                }
+                Ok(None) => break,
+                // the renderer may have lost some views
+                Err(IpcError::Renderer(_)) => self.native_views.resync().map_err(debug)?,
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+
+        if self.needs_render {
+            self.render()?;
+            self.needs_render = false;
+        }
+        self.views.tick(now);
+        self.native_views
+            .patch_frame(self.views.patches())
+            .map_err(debug)?;
+        self.dispatcher.update(&self.views);
+        Ok(())
+    }
+}
+
+fn debug<T: fmt::Debug>(value: T) -> String {
+    format!("{:?}", value)
+}
+
+/// Records the result of a host function as the last error and returns its status.
+fn status(host: &mut BirbHost, result: Result<(), String>) -> BirbStatus {
+    match result {
+        Ok(()) => {
+            host.last_error = None;
+            BirbStatus::BirbOk
+        }
+        Err(err) => {
+            host.last_error = CString::new(err).ok();
+            BirbStatus::BirbError
+        }
+    }
+}
+
+/// Runs the body of a host function, turning panics into errors so that they don’t unwind into
+/// the application.
+fn catch_panic<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message,
+            None => payload
+                .downcast_ref::<String>()
+                .map_or("unknown panic", |message| message),
+        };
+        Err(format!("panicked: {}", message))
+    })
+}
+
+/// Creates a host. The root view will be described and rendered on the first call to
+/// [`birb_host_pump`].
+///
+/// Returns null if the host could not be created.
+#[no_mangle]
+pub extern "C" fn birb_host_new(
+    describe: BirbDescribeFn,
+    send: BirbSendFn,
+    user_data: *mut c_void,
+) -> *mut BirbHost {
+    catch_panic(|| {
+        let (received, bytes) = channel::unbounded();
+        let reader = ChannelReader {
+            bytes,
+            buffer: Vec::new(),
+            pos: 0,
+        };
+        let is_pumping = Rc::new(Cell::new(false));
+        let writer = CallbackWriter {
+            send,
+            user_data,
+            is_pumping: Rc::clone(&is_pumping),
+        };
+        let host = BirbHost {
+            views: ViewTree::new(),
+            native_views: NVTree::new(Box::new(IpcBackend::new(reader, writer))),
+            dispatcher: EventDispatcher::new(),
+            registry: Registry::with_builtins(),
+            describe,
+            user_data,
+            received,
+            is_pumping,
+            needs_render: true,
+            last_error: None,
+        };
+        Ok(Box::into_raw(Box::new(host)))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Frees a host.
+///
+/// # Safety
+/// The host must have been created with [`birb_host_new`] and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn birb_host_free(host: *mut BirbHost) {
+    if !host.is_null() {
+        let _ = catch_panic(|| {
+            drop(Box::from_raw(host));
+            Ok(())
+        });
+    }
+}
+
+/// Passes bytes received from the renderer to the host.
+///
+/// Returns an error if the host is null, or if `data` is null while `len` isn’t zero.
+///
+/// # Safety
+/// The host must be null or valid, and `data` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn birb_host_receive(
+    host: *mut BirbHost,
+    data: *const u8,
+    len: usize,
+) -> BirbStatus {
+    let host = match host.as_mut() {
+        Some(host) => host,
+        None => return BirbStatus::BirbError,
+    };
+    if len == 0 {
+        return status(host, Ok(()));
+    }
+    if data.is_null() {
+        return status(host, Err("no data".into()));
+    }
+    let bytes = slice::from_raw_parts(data, len);
+    let result = catch_panic(|| {
+        let _ = host.received.send(bytes.to_vec());
+        Ok(())
+    });
+    status(host, result)
+}
+
+/// Describes and renders the root view again on the next call to [`birb_host_pump`].
+///
+/// Returns an error if the host is null.
+///
+/// # Safety
+/// The host must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn birb_host_invalidate(host: *mut BirbHost) -> BirbStatus {
+    let host = match host.as_mut() {
+        Some(host) => host,
+        None => return BirbStatus::BirbError,
+    };
+    let result = catch_panic(|| {
+        host.needs_render = true;
+        Ok(())
+    });
+    status(host, result)
+}
+
+/// Dispatches events from the renderer, renders if needed, and sends changes to the renderer.
+/// Should be called once per frame.
+///
+/// Returns an error if the host is null.
+///
+/// # Safety
+/// The host must be null or valid, and must not be used from the callbacks.
+#[no_mangle]
+pub unsafe extern "C" fn birb_host_pump(host: *mut BirbHost) -> BirbStatus {
+    let host = match host.as_mut() {
+        Some(host) => host,
+        None => return BirbStatus::BirbError,
+    };
+    host.is_pumping.set(true);
+    let result = catch_panic(|| host.pump());
+    host.is_pumping.set(false);
+    status(host, result)
+}
+
+/// Returns a description of the last error, which stays valid until the next call to another
+/// host function, or null. Also returns null if the host is null.
+///
+/// # Safety
+/// The host must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn birb_host_last_error(host: *const BirbHost) -> *const c_char {
+    match host.as_ref().and_then(|host| host.last_error.as_ref()) {
+        Some(err) => err.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Application state passed to the callbacks.
+    struct App {
+        description: CString,
+        sent: Vec<u8>,
+    }
+
+    extern "C" fn describe(user_data: *mut c_void) -> *const c_char {
+        let app = unsafe { &*(user_data as *const App) };
+        app.description.as_ptr()
+    }
+
+    extern "C" fn send(user_data: *mut c_void, data: *const u8, len: usize) {
+        let app = unsafe { &mut *(user_data as *mut App) };
+        app.sent
+            .extend_from_slice(unsafe { slice::from_raw_parts(data, len) });
+    }
+
+    fn app(description: &str) -> Box<App> {
+        Box::new(App {
+            description: CString::new(description).unwrap(),
+            sent: Vec::new(),
+        })
+    }
+
+    fn last_error(host: *const BirbHost) -> Option<String> {
+        let err = unsafe { birb_host_last_error(host) };
+        if err.is_null() {
+            None
+        } else {
+            Some(
+                unsafe { CStr::from_ptr(err) }
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        }
+    }
+
+    #[test]
+    fn host_lifecycle() {
+        let mut app = app(
+            r#"{"component": "Layer", "props": {"bounds": [0, 0, 320, 200]},
+                "subviews": [{"component": "Layer", "key": 1}]}"#,
+        );
+        let user_data = &mut *app as *mut App as *mut c_void;
+        let host = birb_host_new(describe, send, user_data);
+        assert!(!host.is_null());
+        assert!(app.sent.is_empty());
+
+        unsafe {
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbOk);
+            assert_eq!(last_error(host), None);
+            let sent = app.sent.len();
+            assert!(sent > 0);
+
+            // nothing changed, so there’s nothing to send
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbOk);
+            assert_eq!(app.sent.len(), sent);
+
+            app.description = CString::new(r#"{"component": "Layer"}"#).unwrap();
+            assert_eq!(birb_host_invalidate(host), BirbStatus::BirbOk);
+            assert_eq!(app.sent.len(), sent);
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbOk);
+            assert!(app.sent.len() > sent);
+            let sent = app.sent.len();
+
+            assert_eq!(birb_host_receive(host, ptr::null(), 0), BirbStatus::BirbOk);
+            birb_host_free(host);
+            assert_eq!(app.sent.len(), sent);
+        }
+    }
+
+    #[test]
+    fn invalid_descriptions_are_errors() {
+        let mut app = app(r#"{"component": "NoSuchComponent"}"#);
+        let host = birb_host_new(describe, send, &mut *app as *mut App as *mut c_void);
+        unsafe {
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbError);
+            assert!(last_error(host).unwrap().starts_with("invalid description"));
+
+            app.description = CString::new("{").unwrap();
+            assert_eq!(birb_host_invalidate(host), BirbStatus::BirbOk);
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbError);
+            assert!(last_error(host).unwrap().starts_with("invalid description"));
+
+            app.description = CString::new(r#"{"component": "Layer"}"#).unwrap();
+            assert_eq!(birb_host_invalidate(host), BirbStatus::BirbOk);
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbOk);
+            assert_eq!(last_error(host), None);
+            birb_host_free(host);
+        }
+    }
+
+    #[test]
+    fn invalid_handles_are_errors() {
+        let host = ptr::null_mut();
+        unsafe {
+            assert_eq!(birb_host_pump(host), BirbStatus::BirbError);
+            assert_eq!(birb_host_invalidate(host), BirbStatus::BirbError);
+            let bytes = [1, 2, 3];
+            assert_eq!(
+                birb_host_receive(host, bytes.as_ptr(), bytes.len()),
+                BirbStatus::BirbError
+            );
+            assert_eq!(last_error(host), None);
+            birb_host_free(host);
+        }
+
+        let mut app = app(r#"{"component": "Layer"}"#);
+        let host = birb_host_new(describe, send, &mut *app as *mut App as *mut c_void);
+        unsafe {
+            assert_eq!(
+                birb_host_receive(host, ptr::null(), 3),
+                BirbStatus::BirbError
+            );
+            assert_eq!(last_error(host).as_deref(), Some("no data"));
+            birb_host_free(host);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Applies random patches to the NV tree and compares it with a naive reference implementation.
+//!
+//! Run with `cargo fuzz run nv_tree_patches` in `core`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    birb::fuzzing::patch_sequence(data);
+});
@@ -0,0 +1,11 @@
+//! Renders random sequences of view trees and checks the view tree’s invariants after each diff.
+//!
+//! Run with `cargo fuzz run view_tree_diff` in `core`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    birb::fuzzing::diff_sequence(data);
+});
@@ -0,0 +1,233 @@
+//! Accessibility.
+
+use crate::impl_view;
+use crate::nv_tree::NativeView;
+use crate::view::View;
+use core::fmt;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// System accessibility preferences.
+///
+/// Backends report these using [`RawEvent::SetAccessibility`](crate::raw_events::RawEvent), and
+/// views can read them from their [`Context`](crate::Context).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AccessibilitySettings {
+    /// If true, the user prefers to have as little motion on screen as possible.
+    ///
+    /// Transitions that move or scale views will be replaced with cross-fades.
+    pub reduce_motion: bool,
+
+    /// If true, the user prefers opaque surfaces over translucent ones.
+    ///
+    /// The backgrounds of [material](crate::LayerProps::material) layers will be made opaque.
+    pub reduce_transparency: bool,
+
+    /// If true, the user prefers more contrast between foreground and background elements.
+    ///
+    /// Translucent layer borders will be made opaque.
+    pub increase_contrast: bool,
+}
+
+impl AccessibilitySettings {
+    /// Adapts a native view to these settings, before it is sent to the NV tree.
+    pub(crate) fn adapt(&self, mut view: NativeView) -> NativeView {
+        match &mut view {
+            NativeView::Layer(props) => {
+                if self.reduce_transparency && props.material && props.background.a > 0. {
+                    props.background.a = 1.;
+                }
+                if let Some((width, color)) = &mut props.border {
+                    if self.increase_contrast && *width > 0. && color.a > 0. {
+                        color.a = 1.;
+                    }
+                }
+            }
+            NativeView::Custom { .. } => (),
+        }
+        view
+    }
+}
+
+/// How urgently an announcement should be spoken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AnnouncementPriority {
+    /// Spoken when the screen reader is idle; may be dropped.
+    Low,
+    /// Spoken after the current utterance.
+    #[default]
+    Medium,
+    /// Interrupts the current utterance.
+    High,
+}
+
+/// A message for the screen reader to speak.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Announcement {
+    pub message: String,
+    pub priority: AnnouncementPriority,
+}
+
+/// Accessibility services available to views, obtained from their [`Context`](crate::Context).
+///
+/// This is a cheap handle that may be kept in view state and used in event handlers.
+#[derive(Debug, Clone)]
+pub struct Accessibility {
+    settings: Arc<Mutex<AccessibilitySettings>>,
+    announcements: Arc<Mutex<Vec<Announcement>>>,
+}
+
+impl Accessibility {
+    pub(crate) fn new(
+        settings: Arc<Mutex<AccessibilitySettings>>,
+        announcements: Arc<Mutex<Vec<Announcement>>>,
+    ) -> Accessibility {
+        Accessibility {
+            settings,
+            announcements,
+        }
+    }
+
+    /// Returns the current system accessibility settings.
+    ///
+    /// These are shared with the view tree, so a handle kept in view state sees changes made with
+    /// [`ViewTree::set_accessibility`](crate::ViewTree::set_accessibility).
+    pub fn settings(&self) -> AccessibilitySettings {
+        *self.settings.lock()
+    }
+
+    /// Asks the screen reader (e.g. VoiceOver) to speak a message.
+    ///
+    /// Use this for dynamic changes that aren’t otherwise noticeable, such as toasts or validation
+    /// errors. The announcement will be sent to the backend with the next patches.
+    pub fn announce(&self, message: impl Into<String>, priority: AnnouncementPriority) {
+        self.announcements.lock().push(Announcement {
+            message: message.into(),
+            priority,
+        });
+    }
+}
+
+/// Wraps a view whose content changes should be spoken by screen readers (a live region).
+///
+/// Whenever `label` changes, it will be announced with the given priority. The label should
+/// describe the content, e.g. the text of a validation error.
+pub struct LiveRegion<Ctx> {
+    pub key: Option<u64>,
+
+    /// The wrapped view.
+    pub content: Arc<dyn View<Ctx>>,
+
+    /// A textual description of the content.
+    pub label: String,
+
+    /// Priority of announcements.
+    pub priority: AnnouncementPriority,
+}
+
+impl<Ctx> fmt::Debug for LiveRegion<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LiveRegion")
+            .field("content", &self.content)
+            .field("label", &self.label)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for LiveRegion<Ctx> {
+    fn eq(&self, other: &LiveRegion<Ctx>) -> bool {
+        self.key == other.key
+            && self.label == other.label
+            && self.priority == other.priority
+            && self.content.eq(&*other.content)
+    }
+}
+
+impl_view! {
+    LiveRegion<Ctx>;
+    fn body(&self, _state: &()) {
+        Arc::clone(&self.content)
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::view::Fragment;
+    use crate::{LayerProps, ViewTree};
+
+    /// Keeps the accessibility handle from its context.
+    #[derive(Debug)]
+    struct Probe {
+        handle: Arc<Mutex<Option<Accessibility>>>,
+    }
+
+    impl PartialEq for Probe {
+        fn eq(&self, other: &Probe) -> bool {
+            Arc::ptr_eq(&self.handle, &other.handle)
+        }
+    }
+
+    impl_view! {
+        Probe;
+        fn new_state(&self, context) {
+            *self.handle.lock() = Some(context.accessibility().clone());
+            Arc::new(())
+        }
+        fn body(&self, _state: &()) {
+            Arc::new(Fragment::new())
+        }
+    }
+
+    #[test]
+    fn kept_handles_see_new_settings() {
+        let handle = Arc::new(Mutex::new(None));
+        let mut tree: ViewTree = ViewTree::new();
+        tree.render_root(
+            Arc::new(Probe {
+                handle: Arc::clone(&handle),
+            }),
+            (),
+        );
+        let accessibility = handle.lock().clone().expect("state was not created");
+        assert_eq!(accessibility.settings(), AccessibilitySettings::default());
+
+        let settings = AccessibilitySettings {
+            reduce_motion: true,
+            ..AccessibilitySettings::default()
+        };
+        tree.set_accessibility(settings);
+        assert_eq!(accessibility.settings(), settings);
+    }
+
+    #[test]
+    fn only_materials_are_made_opaque() {
+        let adapt = |settings: AccessibilitySettings, material| {
+            let props = LayerProps {
+                background: Color::rgba(1., 1., 1., 0.5),
+                border: Some((1., Color::rgba(0., 0., 0., 0.25))),
+                material,
+                ..LayerProps::default()
+            };
+            match settings.adapt(NativeView::Layer(Box::new(props))) {
+                NativeView::Layer(props) => (props.background.a, props.border.unwrap().1.a),
+                NativeView::Custom { .. } => unreachable!(),
+            }
+        };
+        let settings = AccessibilitySettings {
+            reduce_transparency: true,
+            increase_contrast: true,
+            ..AccessibilitySettings::default()
+        };
+        assert_eq!(adapt(settings, false), (0.5, 1.));
+        assert_eq!(adapt(settings, true), (1., 1.));
+        assert_eq!(adapt(AccessibilitySettings::default(), true), (0.5, 0.25));
+    }
+}
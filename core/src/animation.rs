@@ -0,0 +1,547 @@
+//! Animation.
+
+use crate::nv_tree::NativeView;
+use cgmath::{Matrix3, SquareMatrix, Vector2, Vector3};
+use core::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Easing curves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Applies the easing curve to a linear progress value between 0 and 1.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1. - (1. - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) / 2.
+                }
+            }
+        }
+    }
+}
+
+/// A custom transition function; see [`Transition::Custom`].
+pub type TransitionFn = dyn Fn(&mut NativeView, f64) + Send + Sync;
+
+/// A transition that is applied to native views when they appear or disappear.
+#[derive(Clone)]
+pub enum Transition {
+    /// Fades the view in or out.
+    Fade,
+    /// Scales the view from or to the given scale factor, while fading it.
+    Scale(f64),
+    /// Moves the view from or to the given offset.
+    Slide(Vector2<f64>),
+    /// A custom transition.
+    ///
+    /// The function is called with the native view as it would appear without the transition and
+    /// a visibility value, where 0 means fully hidden and 1 means fully visible.
+    Custom(Arc<TransitionFn>),
+}
+
+impl Transition {
+    /// Applies the transition to a native view.
+    ///
+    /// - `visibility`: 0 if the view should be fully hidden, 1 if it should be fully visible.
+    pub fn apply(&self, view: &mut NativeView, visibility: f64) {
+        match (self, view) {
+            (Transition::Fade, NativeView::Layer(props)) => props.opacity *= visibility,
+            (Transition::Fade, NativeView::Custom { opacity, .. }) => *opacity *= visibility,
+            (Transition::Scale(scale), view) => {
+                let factor = scale + (1. - scale) * visibility;
+                let (transform, opacity) = match view {
+                    NativeView::Layer(props) => (&mut props.transform, &mut props.opacity),
+                    NativeView::Custom {
+                        transform, opacity, ..
+                    } => (transform, opacity),
+                };
+                *transform = *transform * Matrix3::from_diagonal(Vector3::new(factor, factor, 1.));
+                *opacity *= visibility;
+            }
+            (Transition::Slide(offset), NativeView::Layer(props)) => {
+                props.bounds.origin += offset * (1. - visibility);
+            }
+            (Transition::Slide(offset), NativeView::Custom { bounds, .. }) => {
+                bounds.origin += offset * (1. - visibility);
+            }
+            (Transition::Custom(f), view) => f(view, visibility),
+        }
+    }
+
+    /// Returns the transition that should be used instead when the user prefers reduced motion.
+    ///
+    /// Transitions that move or scale views are replaced with a cross-fade.
+    pub fn for_reduced_motion(&self) -> Transition {
+        match self {
+            Transition::Scale(_) | Transition::Slide(_) => Transition::Fade,
+            transition => transition.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Transition::Fade => write!(f, "Fade"),
+            Transition::Scale(scale) => write!(f, "Scale({:?})", scale),
+            Transition::Slide(offset) => write!(f, "Slide({:?})", offset),
+            Transition::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// Transitions for when a view is inserted into or removed from its superview.
+///
+/// Transitions are applied to the native views closest to the view that declares them. A view
+/// with an exit transition will be kept alive until the transition has completed.
+#[derive(Debug, Clone)]
+pub struct Transitions {
+    /// The transition used when the view is inserted.
+    pub enter: Option<Transition>,
+    /// The transition used when the view is removed.
+    pub exit: Option<Transition>,
+    /// Transition duration.
+    pub duration: Duration,
+    /// Transition easing curve.
+    pub easing: Easing,
+}
+
+impl Transitions {
+    /// Uses the same transition for both insertion and removal.
+    pub fn symmetric(transition: Transition, duration: Duration) -> Transitions {
+        Transitions {
+            enter: Some(transition.clone()),
+            exit: Some(transition),
+            duration,
+            easing: Easing::default(),
+        }
+    }
+}
+
+/// Returns the linear progress of an animation between 0 and 1.
+pub(crate) fn progress(start: Instant, duration: Duration, now: Instant) -> f64 {
+    if duration == Duration::from_secs(0) {
+        return 1.;
+    }
+    let elapsed = now.saturating_duration_since(start);
+    (elapsed.as_secs_f64() / duration.as_secs_f64()).min(1.)
+}
+
+/// Spring parameters for physically based animations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spring {
+    pub stiffness: f64,
+    pub damping: f64,
+    /// Must be positive; smaller masses are treated as [`MIN_MASS`].
+    pub mass: f64,
+}
+
+impl Spring {
+    /// Creates a spring with the given response (the approximate duration of one oscillation, in
+    /// seconds) and damping ratio (1 for critical damping, less than 1 for bouncing).
+    pub fn with_response(response: f64, damping_ratio: f64) -> Spring {
+        let angular_frequency = 2. * std::f64::consts::PI / response.max(0.001);
+        Spring {
+            stiffness: angular_frequency * angular_frequency,
+            damping: 2. * damping_ratio * angular_frequency,
+            mass: 1.,
+        }
+    }
+
+    /// Returns the displacement from the target and the velocity after `t` seconds, starting at
+    /// the given displacement and velocity.
+    ///
+    /// This solves the damped oscillator exactly, so that it is stable for any time step and
+    /// spring parameters (including very small masses, which numerical integration would need
+    /// tiny steps for).
+    fn solve(&self, x: f64, v: f64, t: f64) -> (f64, f64) {
+        let mass = self.mass.max(MIN_MASS);
+        let (k, c) = (self.stiffness, self.damping);
+        if k <= 0. {
+            // no spring force; only damping slows the value down
+            if c <= 0. {
+                return (x + v * t, v);
+            }
+            let decay = (-c / mass * t).exp();
+            return (x + v * mass / c * (1. - decay), v * decay);
+        }
+
+        let natural_frequency = (k / mass).sqrt();
+        let damping_ratio = c / (2. * (k * mass).sqrt());
+        if (damping_ratio - 1.).abs() < CRITICAL_DAMPING_TOLERANCE {
+            let decay = (-natural_frequency * t).exp();
+            let b = v + natural_frequency * x;
+            (decay * (x + b * t), decay * (v - natural_frequency * b * t))
+        } else if damping_ratio < 1. {
+            let a = damping_ratio * natural_frequency;
+            let frequency = natural_frequency * (1. - damping_ratio * damping_ratio).sqrt();
+            let decay = (-a * t).exp();
+            let (sin, cos) = (frequency * t).sin_cos();
+            (
+                decay * (x * cos + (v + a * x) / frequency * sin),
+                decay
+                    * (v * cos
+                        - (a * v + natural_frequency * natural_frequency * x) / frequency * sin),
+            )
+        } else {
+            // the product of the two roots is k / mass; computing the slow root from it avoids
+            // cancellation when the spring is heavily overdamped
+            let fast =
+                -natural_frequency * (damping_ratio + (damping_ratio * damping_ratio - 1.).sqrt());
+            let slow = k / mass / fast;
+            let fast_part = (v - slow * x) / (fast - slow);
+            let slow_part = x - fast_part;
+            let (fast_decay, slow_decay) = ((fast * t).exp(), (slow * t).exp());
+            (
+                slow_part * slow_decay + fast_part * fast_decay,
+                slow * slow_part * slow_decay + fast * fast_part * fast_decay,
+            )
+        }
+    }
+}
+
+impl Default for Spring {
+    fn default() -> Self {
+        Spring::with_response(0.4, 1.)
+    }
+}
+
+/// Values closer to their target than this (and slower than this per second) are settled.
+const SETTLE_THRESHOLD: f64 = 1e-3;
+
+/// Springs with a damping ratio this close to 1 are treated as critically damped, where the
+/// solutions for under- and overdamped springs become numerically unstable.
+const CRITICAL_DAMPING_TOLERANCE: f64 = 1e-4;
+
+/// The longest time a spring is advanced by in one tick, in seconds.
+///
+/// Longer gaps between frames (e.g. because the app was suspended) continue the animation from
+/// where it was instead of integrating the whole gap.
+const MAX_FRAME_GAP: f64 = 0.1;
+
+/// The smallest spring mass.
+pub const MIN_MASS: f64 = 1e-3;
+
+/// A value animated by a spring.
+///
+/// The animation can be interrupted at any time: changing the target will preserve the current
+/// velocity, and the value can also be driven directly (e.g. by a gesture) and then released, in
+/// which case the spring takes over with the velocity of the gesture. This makes interactions like
+/// swipe-to-dismiss continuous instead of jumping between discrete animations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedValue {
+    value: f64,
+    velocity: f64,
+    target: f64,
+    spring: Spring,
+    /// Set if the value is being driven directly.
+    is_driven: bool,
+    last_time: Option<Instant>,
+}
+
+impl AnimatedValue {
+    /// Creates a value at rest.
+    pub fn new(value: f64, spring: Spring) -> AnimatedValue {
+        AnimatedValue {
+            value,
+            velocity: 0.,
+            target: value,
+            spring,
+            is_driven: false,
+            last_time: None,
+        }
+    }
+
+    /// The current value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The current velocity in units per second.
+    pub fn velocity(&self) -> f64 {
+        self.velocity
+    }
+
+    /// The value the spring is moving towards.
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+
+    /// Returns true if the value is being driven directly.
+    pub fn is_driven(&self) -> bool {
+        self.is_driven
+    }
+
+    /// Returns true if the value is at rest at its target.
+    pub fn is_settled(&self) -> bool {
+        !self.is_driven && self.value == self.target && self.velocity == 0.
+    }
+
+    /// Sets a new spring, keeping the current value and velocity.
+    pub fn set_spring(&mut self, spring: Spring) {
+        self.spring = spring;
+    }
+
+    /// Retargets the animation, keeping the current value and velocity.
+    pub fn set_target(&mut self, target: f64) {
+        if self.is_settled() {
+            // the animation starts at the next tick, not when the value was last ticked
+            self.last_time = None;
+        }
+        self.target = target;
+    }
+
+    /// Sets the value directly, e.g. from a gesture.
+    ///
+    /// The velocity is estimated from successive calls, and the spring will not move the value
+    /// until [`release`](Self::release) is called.
+    pub fn drive(&mut self, value: f64, now: Instant) {
+        if let (true, Some(last_time)) = (self.is_driven, self.last_time) {
+            let dt = now.saturating_duration_since(last_time).as_secs_f64();
+            if dt > 0. {
+                // smooth the velocity a bit because input events are noisy
+                let velocity = (value - self.value) / dt;
+                self.velocity = self.velocity * 0.2 + velocity * 0.8;
+            }
+        } else {
+            self.velocity = 0.;
+        }
+        self.is_driven = true;
+        self.value = value;
+        self.last_time = Some(now);
+    }
+
+    /// Drives the value by interpolating between two values using a gesture’s progress.
+    pub fn drive_progress(&mut self, from: f64, to: f64, progress: f64, now: Instant) {
+        self.drive(from + (to - from) * progress, now);
+    }
+
+    /// Stops driving the value directly and animates to the target, starting with the velocity
+    /// of the driving gesture.
+    pub fn release(&mut self, target: f64, now: Instant) {
+        self.is_driven = false;
+        self.target = target;
+        self.last_time = Some(now);
+    }
+
+    /// Advances the animation to the given point in time and returns the new value.
+    pub fn tick(&mut self, now: Instant) -> f64 {
+        if self.is_driven {
+            // the last time is that of the last drive sample, for estimating the velocity
+            return self.value;
+        }
+        let dt = match self.last_time.replace(now) {
+            Some(last_time) => now.saturating_duration_since(last_time).as_secs_f64(),
+            None => 0.,
+        };
+        let dt = dt.min(MAX_FRAME_GAP);
+
+        if dt > 0. {
+            let (displacement, velocity) =
+                self.spring
+                    .solve(self.value - self.target, self.velocity, dt);
+            self.value = self.target + displacement;
+            self.velocity = velocity;
+        }
+
+        if (self.value - self.target).abs() < SETTLE_THRESHOLD
+            && self.velocity.abs() < SETTLE_THRESHOLD
+        {
+            self.value = self.target;
+            self.velocity = 0.;
+        }
+
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    /// Ticks a value once per frame for the given duration and returns the time afterwards.
+    fn run(value: &mut AnimatedValue, mut now: Instant, duration: Duration) -> Instant {
+        let end = now + duration;
+        while now < end {
+            now += FRAME;
+            value.tick(now);
+        }
+        now
+    }
+
+    #[test]
+    fn settles_at_target() {
+        let start = Instant::now();
+        let mut value = AnimatedValue::new(0., Spring::default());
+        value.tick(start);
+        value.set_target(10.);
+        assert!(!value.is_settled());
+
+        run(&mut value, start, Duration::from_secs(3));
+        assert!(value.is_settled());
+        assert_eq!(value.value(), 10.);
+        assert_eq!(value.velocity(), 0.);
+    }
+
+    #[test]
+    fn retarget_after_idle_animates() {
+        let start = Instant::now();
+        let mut value = AnimatedValue::new(0., Spring::default());
+        value.tick(start);
+
+        let later = start + Duration::from_secs(3600);
+        value.set_target(1.);
+        assert_eq!(value.tick(later), 0.);
+        let first_frame = value.tick(later + FRAME);
+        assert!(first_frame > 0. && first_frame < 0.5, "{}", first_frame);
+    }
+
+    #[test]
+    fn retarget_preserves_velocity() {
+        let start = Instant::now();
+        let mut value = AnimatedValue::new(0., Spring::default());
+        value.tick(start);
+        value.set_target(1.);
+        let now = run(&mut value, start, Duration::from_millis(100));
+        let velocity = value.velocity();
+        assert!(velocity > 0.);
+
+        value.set_target(-1.);
+        assert_eq!(value.velocity(), velocity);
+        value.tick(now + Duration::from_millis(1));
+        assert!(value.velocity() > 0. && value.velocity() < velocity);
+    }
+
+    #[test]
+    fn frame_gaps_are_clamped() {
+        let start = Instant::now();
+        let mut value = AnimatedValue::new(0., Spring::with_response(10., 1.));
+        value.tick(start);
+        value.set_target(1.);
+        value.tick(start + FRAME);
+
+        // a long gap doesn’t finish the animation at once
+        value.tick(start + Duration::from_secs(3600));
+        assert!(value.value() < 0.5, "{}", value.value());
+    }
+
+    #[test]
+    fn release_continues_with_gesture_velocity() {
+        let start = Instant::now();
+        let mut value = AnimatedValue::new(0., Spring::default());
+        value.drive(0., start);
+        value.drive(1., start + FRAME);
+        assert!(value.is_driven());
+        assert!(value.velocity() > 0.);
+        assert_eq!(value.tick(start + FRAME * 2), 1.);
+
+        value.release(0., start + FRAME * 2);
+        // moving away from the target at first
+        assert!(value.tick(start + FRAME * 3) > 1.);
+        run(&mut value, start + FRAME * 3, Duration::from_secs(3));
+        assert!(value.is_settled());
+    }
+
+    #[test]
+    fn drive_velocity_ignores_ticks() {
+        let start = Instant::now();
+        let mut value = AnimatedValue::new(0., Spring::default());
+        value.drive(0., start);
+        assert_eq!(value.tick(start + FRAME / 2), 0.);
+        value.drive(1., start + FRAME);
+        assert_eq!(value.tick(start + FRAME), 1.);
+        value.drive(2., start + FRAME * 2);
+
+        // 62.5 units per second, smoothed
+        let frame_velocity = 1. / FRAME.as_secs_f64();
+        let expected = frame_velocity * 0.8 * 0.2 + frame_velocity * 0.8;
+        assert!(
+            (value.velocity() - expected).abs() < 1e-9,
+            "{}",
+            value.velocity()
+        );
+
+        value.tick(start + FRAME * 5 / 2);
+        value.release(2., start + FRAME * 3);
+        assert_eq!(value.velocity(), expected);
+        let released = value.tick(start + FRAME * 3 + Duration::from_millis(1));
+        let jump = released - 2.;
+        assert!(jump > 0. && jump < expected * 0.001 * 1.01, "{}", jump);
+    }
+
+    #[test]
+    fn springs_with_tiny_masses_are_stable() {
+        for &mass in &[0., 1e-9, MIN_MASS, 0.01] {
+            for &damping_ratio in &[0.1, 1.] {
+                let start = Instant::now();
+                let spring = Spring {
+                    mass,
+                    ..Spring::with_response(0.4, damping_ratio)
+                };
+                let mut value = AnimatedValue::new(0., spring);
+                value.tick(start);
+                value.set_target(1.);
+
+                let mut now = start;
+                while now < start + Duration::from_secs(5) {
+                    now += FRAME;
+                    let current = value.tick(now);
+                    assert!(current.is_finite() && value.velocity().is_finite());
+                    assert!(current.abs() < 3., "{} with mass {}", current, mass);
+                }
+                assert!(value.is_settled(), "{:?}", value);
+                assert_eq!(value.value(), 1.);
+            }
+        }
+    }
+
+    #[test]
+    fn springs_match_their_differential_equation() {
+        // an underdamped, a critically damped, and an overdamped spring, without a spring force
+        for spring in &[
+            Spring::with_response(0.4, 0.3),
+            Spring::with_response(0.4, 1.),
+            Spring::with_response(0.4, 3.),
+            Spring {
+                stiffness: 0.,
+                damping: 5.,
+                mass: 2.,
+            },
+        ] {
+            let (x, v) = (1., -2.);
+            let (x1, v1) = spring.solve(x, v, 0.);
+            assert!((x1 - x).abs() < 1e-12 && (v1 - v).abs() < 1e-12);
+
+            // compare the acceleration to the spring force
+            let (t, h) = (0.1, 1e-6);
+            let (x0, v0) = spring.solve(x, v, t - h);
+            let (x1, v1) = spring.solve(x, v, t);
+            let (_, v2) = spring.solve(x, v, t + h);
+            let acceleration = (v2 - v0) / (2. * h);
+            let force = -spring.stiffness * x1 - spring.damping * v1;
+            assert!(
+                (acceleration - force / spring.mass).abs() < 1e-3,
+                "{:?}",
+                spring
+            );
+            assert!(((x1 - x0) / h - v1).abs() < 1e-3, "{:?}", spring);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+//! Loading images in the background.
+//!
+//! An [`ImageLoader`] is passed down the view tree in the context and loads images on background
+//! threads using an app-provided function (since decoding and networking are up to the app), and
+//! keeps decoded bitmaps in an [`ImageCache`]. [`AsyncImage`] views start loading their image
+//! when they appear and render a placeholder until it has loaded, at which point they are
+//! rendered again (see [`ViewTree::set_render_waker`](crate::ViewTree::set_render_waker) for
+//! waking up the event loop).
+
+use crate::image::RgbaImage;
+use crate::view::{AnyState, State, View};
+use crate::view_tree::{Context, Observers};
+use core::any::Any;
+use core::fmt;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+/// Where an image is loaded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImageSource {
+    Path(PathBuf),
+    Url(String),
+}
+
+/// The loading state of an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImagePhase {
+    /// The image hasn’t loaded yet.
+    Loading,
+    Loaded(Arc<RgbaImage>),
+    /// The image could not be loaded. Contains the error message.
+    Failed(String),
+}
+
+/// Loads and decodes an image; called on a background thread.
+pub type LoadFn = dyn Fn(&ImageSource) -> Result<RgbaImage, String> + Send + Sync;
+
+/// Decoded images, keyed by source; least recently used images are evicted when the total size
+/// exceeds the budget.
+#[derive(Debug)]
+pub struct ImageCache {
+    images: HashMap<ImageSource, CacheEntry>,
+    budget: usize,
+    size: usize,
+    /// Incremented every time an image is used, to find the least recently used one.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    image: Arc<RgbaImage>,
+    last_used: u64,
+}
+
+/// Returns the size of an image’s pixel data in bytes.
+fn image_size(image: &RgbaImage) -> usize {
+    image.data().len()
+}
+
+impl ImageCache {
+    /// Creates an empty cache that keeps at most `budget` bytes of pixel data.
+    pub fn new(budget: usize) -> ImageCache {
+        ImageCache {
+            images: HashMap::new(),
+            budget,
+            size: 0,
+            clock: 0,
+        }
+    }
+
+    /// Returns a cached image and marks it as recently used.
+    pub fn get(&mut self, source: &ImageSource) -> Option<Arc<RgbaImage>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.images.get_mut(source).map(|entry| {
+            entry.last_used = clock;
+            Arc::clone(&entry.image)
+        })
+    }
+
+    /// Adds an image, evicting least recently used images if the cache is over budget.
+    ///
+    /// Images larger than the entire budget are not cached.
+    pub fn insert(&mut self, source: ImageSource, image: Arc<RgbaImage>) {
+        self.remove(&source);
+        let size = image_size(&image);
+        if size > self.budget {
+            return;
+        }
+        self.clock += 1;
+        self.size += size;
+        self.images.insert(
+            source,
+            CacheEntry {
+                image,
+                last_used: self.clock,
+            },
+        );
+        while self.size > self.budget {
+            let oldest = self
+                .images
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(source, _)| source.clone())
+                .unwrap();
+            self.remove(&oldest);
+        }
+    }
+
+    /// Removes an image.
+    pub fn remove(&mut self, source: &ImageSource) {
+        if let Some(entry) = self.images.remove(source) {
+            self.size -= image_size(&entry.image);
+        }
+    }
+
+    /// Returns the number of cached images.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Returns true if there are no cached images.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Returns the total size of the cached pixel data in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+struct LoaderInner {
+    cache: ImageCache,
+    /// Images that are being loaded.
+    pending: HashSet<ImageSource>,
+    /// Images that failed to load, so they aren’t loaded again on every render.
+    failed: HashMap<ImageSource, String>,
+}
+
+/// Loads images on background threads and caches them, meant to be passed down the view tree in
+/// the context.
+///
+/// Clones share the same state and cache. Views that show images from it should
+/// [`observe`](Self::observe) it, so they are rendered again when an image has finished loading;
+/// [`AsyncImage`] does this already.
+#[derive(Clone)]
+pub struct ImageLoader {
+    inner: Arc<Mutex<LoaderInner>>,
+    load: Arc<LoadFn>,
+    observers: Observers,
+}
+
+impl ImageLoader {
+    /// Creates an image loader that loads images with `load` and caches up to `cache_budget`
+    /// bytes of pixel data.
+    pub fn new<F>(cache_budget: usize, load: F) -> ImageLoader
+    where
+        F: 'static + Fn(&ImageSource) -> Result<RgbaImage, String> + Send + Sync,
+    {
+        ImageLoader {
+            inner: Arc::new(Mutex::new(LoaderInner {
+                cache: ImageCache::new(cache_budget),
+                pending: HashSet::new(),
+                failed: HashMap::new(),
+            })),
+            load: Arc::new(load),
+            observers: Observers::new(),
+        }
+    }
+
+    /// Returns the loading state of an image without loading it.
+    pub fn phase(&self, source: &ImageSource) -> ImagePhase {
+        let mut inner = self.inner.lock();
+        if let Some(image) = inner.cache.get(source) {
+            ImagePhase::Loaded(image)
+        } else if let Some(err) = inner.failed.get(source) {
+            ImagePhase::Failed(err.clone())
+        } else {
+            ImagePhase::Loading
+        }
+    }
+
+    /// Starts loading an image on a background thread, unless it’s already cached, being loaded,
+    /// or has failed to load.
+    pub fn load(&self, source: &ImageSource) {
+        {
+            let mut inner = self.inner.lock();
+            if inner.cache.get(source).is_some()
+                || inner.pending.contains(source)
+                || inner.failed.contains_key(source)
+            {
+                return;
+            }
+            inner.pending.insert(source.clone());
+        }
+
+        let loader = self.clone();
+        let source = source.clone();
+        thread::spawn(move || {
+            let result = (loader.load)(&source);
+            {
+                let mut inner = loader.inner.lock();
+                inner.pending.remove(&source);
+                match result {
+                    Ok(image) => inner.cache.insert(source, Arc::new(image)),
+                    Err(err) => {
+                        inner.failed.insert(source, err);
+                    }
+                }
+            }
+            loader.observers.notify();
+        });
+    }
+
+    /// Forgets a cached image or a failure, so the image will be loaded again.
+    pub fn invalidate(&self, source: &ImageSource) {
+        {
+            let mut inner = self.inner.lock();
+            inner.cache.remove(source);
+            inner.failed.remove(source);
+        }
+        self.observers.notify();
+    }
+
+    /// Renders the view of a context again whenever an image has finished loading or is
+    /// invalidated; see [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// Returns the number of cached images and their total size in bytes.
+    pub fn cache_size(&self) -> (usize, usize) {
+        let inner = self.inner.lock();
+        (inner.cache.len(), inner.cache.size())
+    }
+}
+
+impl fmt::Debug for ImageLoader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock();
+        f.debug_struct("ImageLoader")
+            .field("cache", &inner.cache)
+            .field("pending", &inner.pending)
+            .field("failed", &inner.failed)
+            .finish()
+    }
+}
+
+/// Context types that provide an image loader.
+pub trait ImageLoaderContext {
+    fn image_loader(&self) -> &ImageLoader;
+}
+
+impl ImageLoaderContext for ImageLoader {
+    fn image_loader(&self) -> &ImageLoader {
+        self
+    }
+}
+
+/// Renders an image in some loading state.
+pub type ImageRenderFn<Ctx> = dyn Fn(&ImagePhase) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// A view that loads an image with the context’s [`ImageLoader`] and renders it using `render`.
+///
+/// Loading starts when the view appears or its source changes. Until the image has loaded,
+/// `render` is called with [`ImagePhase::Loading`] and should return a placeholder; if loading
+/// fails, it is called with [`ImagePhase::Failed`].
+pub struct AsyncImage<Ctx> {
+    pub key: Option<u64>,
+
+    pub source: ImageSource,
+
+    /// Renders the image, or a placeholder or error state.
+    pub render: Arc<ImageRenderFn<Ctx>>,
+}
+
+impl<Ctx> fmt::Debug for AsyncImage<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncImage")
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl<Ctx> PartialEq for AsyncImage<Ctx> {
+    fn eq(&self, other: &AsyncImage<Ctx>) -> bool {
+        self.key == other.key
+            && self.source == other.source
+            && Arc::ptr_eq(&self.render, &other.render)
+    }
+}
+
+#[derive(Debug)]
+struct AsyncImageState(ImageLoader);
+
+impl<Ctx> State<Ctx> for AsyncImageState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl<Ctx: ImageLoaderContext + 'static> View<Ctx> for AsyncImage<Ctx> {
+    fn new_state(&self, context: Context<Ctx>) -> AnyState<Ctx> {
+        Arc::new(AsyncImageState(context.ctx().image_loader().clone()))
+    }
+    fn effects(&self, state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        let state = state
+            .as_any()
+            .downcast_ref::<AsyncImageState>()
+            .expect("View::effects: invalid state for AsyncImage");
+        state.0.observe(context);
+        let loader = state.0.clone();
+        let source = self.source.clone();
+        context.effect(self.source.clone(), move || {
+            loader.load(&source);
+            || ()
+        });
+    }
+    fn body(&self, state: &AnyState<Ctx>) -> Arc<dyn View<Ctx>> {
+        let state = state
+            .as_any()
+            .downcast_ref::<AsyncImageState>()
+            .expect("View::body: invalid state for AsyncImage");
+        (self.render)(&state.0.phase(&self.source))
+    }
+    fn eq(&self, other: &dyn View<Ctx>) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{Layer, LayerProps};
+    use crate::nv_tree::{NativeView, Patch};
+    use crate::rect::Rect;
+    use crate::view_tree::ViewTree;
+    use cgmath::{Point2, Vector2};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    fn path(name: &str) -> ImageSource {
+        ImageSource::Path(name.into())
+    }
+
+    /// Returns an image whose pixel data is `size` bytes large.
+    fn image(size: usize) -> Arc<RgbaImage> {
+        Arc::new(RgbaImage::new(size as u32 / 4, 1))
+    }
+
+    /// Waits up to a few seconds for the condition to become true.
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        false
+    }
+
+    /// A loader whose images are as wide as the number in their name, and which can be held
+    /// back by locking the returned mutex.
+    fn loader() -> (ImageLoader, Arc<Mutex<()>>, Arc<AtomicUsize>) {
+        let gate = Arc::new(Mutex::new(()));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let loader = {
+            let (gate, loads) = (Arc::clone(&gate), Arc::clone(&loads));
+            ImageLoader::new(1024, move |source| {
+                let _gate = gate.lock();
+                loads.fetch_add(1, Ordering::SeqCst);
+                match source {
+                    ImageSource::Path(path) => path
+                        .to_str()
+                        .and_then(|name| name.parse().ok())
+                        .map(|width| RgbaImage::new(width, 1))
+                        .ok_or_else(|| format!("cannot decode {}", path.display())),
+                    ImageSource::Url(url) => Err(format!("offline: {}", url)),
+                }
+            })
+        };
+        (loader, gate, loads)
+    }
+
+    #[test]
+    fn caches_evict_the_least_recently_used_images() {
+        let mut cache = ImageCache::new(100);
+        assert!(cache.is_empty());
+        cache.insert(path("a"), image(40));
+        cache.insert(path("b"), image(40));
+        assert_eq!((cache.len(), cache.size()), (2, 80));
+
+        // replacing an image doesn’t count it twice
+        cache.insert(path("b"), image(20));
+        assert_eq!((cache.len(), cache.size()), (2, 60));
+
+        assert!(cache.get(&path("a")).is_some());
+        cache.insert(path("c"), image(60));
+        assert!(cache.get(&path("b")).is_none());
+        assert!(cache.get(&path("a")).is_some());
+        assert_eq!((cache.len(), cache.size()), (2, 100));
+
+        cache.insert(path("d"), image(104));
+        assert!(cache.get(&path("d")).is_none());
+        assert_eq!(cache.len(), 2);
+
+        cache.remove(&path("a"));
+        cache.remove(&path("a"));
+        assert_eq!((cache.len(), cache.size()), (1, 60));
+    }
+
+    #[test]
+    fn images_are_loaded_once() {
+        let (loader, gate, loads) = loader();
+        let source = path("3");
+        let held = gate.lock();
+        loader.load(&source);
+        loader.load(&source);
+        assert_eq!(loader.phase(&source), ImagePhase::Loading);
+        drop(held);
+
+        assert!(wait_until(|| loader.phase(&source) != ImagePhase::Loading));
+        match loader.phase(&source) {
+            ImagePhase::Loaded(image) => assert_eq!(image.width(), 3),
+            phase => panic!("{:?}", phase),
+        }
+        loader.load(&source);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(loader.cache_size(), (1, 12));
+
+        loader.invalidate(&source);
+        assert_eq!(loader.cache_size(), (0, 0));
+        assert_eq!(loader.phase(&source), ImagePhase::Loading);
+        loader.load(&source);
+        assert!(wait_until(|| loader.cache_size().0 == 1));
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn failures_are_kept_until_invalidated() {
+        let (loader, _, loads) = loader();
+        let source = ImageSource::Url("https://example.com/a.png".into());
+        loader.load(&source);
+        assert!(wait_until(|| loader.phase(&source) != ImagePhase::Loading));
+        assert_eq!(
+            loader.phase(&source),
+            ImagePhase::Failed("offline: https://example.com/a.png".into())
+        );
+
+        loader.load(&source);
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        loader.invalidate(&source);
+        loader.load(&source);
+        assert!(wait_until(|| loads.load(Ordering::SeqCst) == 2));
+    }
+
+    /// Renders images as layers as wide as the image, or 1 point wide if they’re loading and
+    /// 2 points wide if they failed.
+    fn render_image(phase: &ImagePhase) -> Arc<dyn View<ImageLoader>> {
+        let width = match phase {
+            ImagePhase::Loading => 1.,
+            ImagePhase::Failed(_) => 2.,
+            ImagePhase::Loaded(image) => image.width() as f64,
+        };
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(0., 0.), Vector2::new(width, 1.)),
+                ..LayerProps::default()
+            },
+            ..Layer::default()
+        })
+    }
+
+    /// Returns the widths of updated layers.
+    fn widths(tree: &mut ViewTree<ImageLoader>) -> Vec<f64> {
+        tree.patches()
+            .filter_map(|patch| match patch {
+                Patch::Update(_, NativeView::Layer(props)) => Some(props.bounds.size.x),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn views_are_rendered_again_when_their_image_loads() {
+        let (loader, gate, _) = loader();
+        let render: Arc<ImageRenderFn<ImageLoader>> = Arc::new(render_image);
+        let view = |name: &str| -> Arc<dyn View<ImageLoader>> {
+            Arc::new(AsyncImage {
+                key: None,
+                source: path(name),
+                render: Arc::clone(&render),
+            })
+        };
+
+        let mut tree = ViewTree::new();
+        let held = gate.lock();
+        tree.render_root(view("5"), loader.clone());
+        assert_eq!(widths(&mut tree), [1.]);
+        drop(held);
+
+        assert!(wait_until(|| loader.cache_size().0 == 1));
+        assert!(wait_until(|| tree.has_render_requests()));
+        tree.tick(Instant::now());
+        assert_eq!(widths(&mut tree), [5.]);
+
+        // a new source is loaded as well
+        tree.render_root(view("nonsense"), loader.clone());
+        assert_eq!(widths(&mut tree), [1.]);
+        assert!(wait_until(|| tree.has_render_requests()));
+        tree.tick(Instant::now());
+        assert_eq!(widths(&mut tree), [2.]);
+
+        // images that are already cached are shown right away
+        tree.render_root(view("5"), loader.clone());
+        assert_eq!(widths(&mut tree), [5.]);
+    }
+}
@@ -0,0 +1,447 @@
+//! Image atlases.
+//!
+//! UIs with many small images (such as icons) would otherwise need a separate bitmap in the
+//! backend for each of them. An [`ImageAtlas`] packs them into a few large pages instead, which
+//! are uploaded to the backend with [`NVTree::sync_atlas`](crate::NVTree::sync_atlas). Layers can
+//! then show any region of a page as their [`contents`](crate::LayerProps::contents).
+
+use crate::image::RgbaImage;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Transparent pixels left around every image, so that images don’t bleed into each other when
+/// they’re scaled.
+const PADDING: u32 = 1;
+
+/// A region of an atlas page, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AtlasRegion {
+    /// The page index.
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Cap insets for nine-slice scaling, in pixels of an image.
+///
+/// When an image with cap insets is scaled, the corners keep their size, the edges only stretch
+/// along their length, and only the center stretches in both directions. This is useful for
+/// button and panel artwork with borders or rounded corners.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CapInsets {
+    pub top: u32,
+    pub left: u32,
+    pub bottom: u32,
+    pub right: u32,
+}
+
+impl CapInsets {
+    /// Creates cap insets that are the same on all sides.
+    pub fn uniform(inset: u32) -> CapInsets {
+        CapInsets {
+            top: inset,
+            left: inset,
+            bottom: inset,
+            right: inset,
+        }
+    }
+
+    /// Returns true if all insets are zero, i.e. the image is simply stretched.
+    pub fn is_zero(&self) -> bool {
+        *self == CapInsets::default()
+    }
+
+    /// Returns the insets shrunk proportionally so that opposite insets don’t overlap in an image
+    /// of the given size.
+    pub fn clamped(&self, width: u32, height: u32) -> CapInsets {
+        fn clamp(a: u32, b: u32, size: u32) -> (u32, u32) {
+            let total = a as u64 + b as u64;
+            if total <= size as u64 {
+                (a, b)
+            } else {
+                let a = (a as u64 * size as u64 / total) as u32;
+                (a, size - a)
+            }
+        }
+        let (left, right) = clamp(self.left, self.right, width);
+        let (top, bottom) = clamp(self.top, self.bottom, height);
+        CapInsets {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+}
+
+/// A row of images in a page.
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Where the next image in this shelf will go.
+    next_x: u32,
+}
+
+#[derive(Debug)]
+struct Page {
+    image: RgbaImage,
+    shelves: Vec<Shelf>,
+    /// Number of images in this page.
+    images: usize,
+    /// If true, the page has changed since it was last uploaded.
+    dirty: bool,
+}
+
+impl Page {
+    fn new(size: u32) -> Page {
+        Page {
+            image: RgbaImage::new(size, size),
+            shelves: Vec::new(),
+            images: 0,
+            dirty: true,
+        }
+    }
+
+    /// Finds space for an image of the given size (including padding).
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let size = self.image.width();
+        // use the shortest shelf that fits to waste as little space as possible
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && size - shelf.next_x >= width)
+            .min_by_key(|shelf| shelf.height);
+        if let Some(shelf) = shelf {
+            let x = shelf.next_x;
+            shelf.next_x += width;
+            return Some((x, shelf.y));
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if size - y < height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            next_x: width,
+        });
+        Some((0, y))
+    }
+}
+
+/// Packs small images into large pages; see the [module documentation](self).
+///
+/// Images are packed in rows, so similarly sized images pack best. Space is only reclaimed once
+/// all images in a page have been removed.
+#[derive(Debug)]
+pub struct ImageAtlas<K> {
+    page_size: u32,
+    max_image_size: u32,
+    pages: Vec<Page>,
+    regions: HashMap<K, AtlasRegion>,
+}
+
+impl<K: Clone + Eq + Hash> ImageAtlas<K> {
+    /// Creates an atlas with square pages of `page_size` pixels that accepts images of at most
+    /// `max_image_size` pixels in either dimension.
+    pub fn new(page_size: u32, max_image_size: u32) -> ImageAtlas<K> {
+        ImageAtlas {
+            page_size,
+            max_image_size: max_image_size.min(page_size.saturating_sub(2 * PADDING)),
+            pages: Vec::new(),
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Adds an image, replacing any image with the same key, and returns its region.
+    ///
+    /// Returns None if the image is larger than the maximum image size; such images should be
+    /// shown some other way.
+    pub fn insert(&mut self, key: K, image: &RgbaImage) -> Option<AtlasRegion> {
+        self.remove(&key);
+        if image.width() > self.max_image_size || image.height() > self.max_image_size {
+            return None;
+        }
+
+        let width = image.width() + 2 * PADDING;
+        let height = image.height() + 2 * PADDING;
+        let mut allocation = None;
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some((x, y)) = page.allocate(width, height) {
+                allocation = Some((index, x, y));
+                break;
+            }
+        }
+        let (page, x, y) = match allocation {
+            Some(allocation) => allocation,
+            None => {
+                let mut page = Page::new(self.page_size);
+                let (x, y) = page.allocate(width, height).unwrap();
+                self.pages.push(page);
+                (self.pages.len() - 1, x, y)
+            }
+        };
+
+        let region = AtlasRegion {
+            page,
+            x: x + PADDING,
+            y: y + PADDING,
+            width: image.width(),
+            height: image.height(),
+        };
+        let page = &mut self.pages[page];
+        copy_image(image, &mut page.image, region.x, region.y);
+        page.images += 1;
+        page.dirty = true;
+        self.regions.insert(key, region);
+        Some(region)
+    }
+
+    /// Returns the region of an image.
+    pub fn get(&self, key: &K) -> Option<AtlasRegion> {
+        self.regions.get(key).copied()
+    }
+
+    /// Removes an image. Its region must no longer be used.
+    pub fn remove(&mut self, key: &K) {
+        let region = match self.regions.remove(key) {
+            Some(region) => region,
+            None => return,
+        };
+        let page = &mut self.pages[region.page];
+        page.images -= 1;
+        if page.images == 0 {
+            // start over; the old pixels will be overwritten
+            page.shelves.clear();
+        }
+    }
+
+    /// Returns the number of images in the atlas.
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Returns true if there are no images in the atlas.
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Returns the number of pages.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns the contents of a page.
+    ///
+    /// # Panics
+    /// - if the page doesn’t exist
+    pub fn page(&self, page: usize) -> &RgbaImage {
+        &self.pages[page].image
+    }
+
+    /// Returns the indices of pages that have changed since they were last uploaded.
+    pub fn dirty_pages(&self) -> Vec<usize> {
+        (0..self.pages.len())
+            .filter(|index| self.pages[*index].dirty)
+            .collect()
+    }
+
+    /// Marks a page as uploaded.
+    pub fn mark_uploaded(&mut self, page: usize) {
+        self.pages[page].dirty = false;
+    }
+
+    /// Marks all pages as changed, e.g. after the backend has lost them.
+    pub fn invalidate_pages(&mut self) {
+        for page in &mut self.pages {
+            page.dirty = true;
+        }
+    }
+}
+
+/// Copies an image into another at the given position, clearing the padding around it.
+fn copy_image(image: &RgbaImage, target: &mut RgbaImage, x: u32, y: u32) {
+    let target_width = target.width() as usize;
+    let width = image.width() as usize;
+    let data = target.data_mut();
+
+    let padded_x = (x - PADDING) as usize;
+    let padded_width = width + 2 * PADDING as usize;
+    for row in 0..image.height() + 2 * PADDING {
+        let start = ((y - PADDING + row) as usize * target_width + padded_x) * 4;
+        let target_row = &mut data[start..start + padded_width * 4];
+        for pixel in target_row.iter_mut() {
+            *pixel = 0;
+        }
+        if row >= PADDING && row < image.height() + PADDING {
+            let source_start = (row - PADDING) as usize * width * 4;
+            let offset = PADDING as usize * 4;
+            target_row[offset..offset + width * 4]
+                .copy_from_slice(&image.data()[source_start..source_start + width * 4]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an image filled with one color.
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        let data = color.repeat((width * height) as usize);
+        RgbaImage::from_raw(width, height, data).unwrap()
+    }
+
+    /// Returns true if the regions overlap, including their padding.
+    fn overlap(a: AtlasRegion, b: AtlasRegion) -> bool {
+        a.page == b.page
+            && a.x < b.x + b.width + 2 * PADDING
+            && b.x < a.x + a.width + 2 * PADDING
+            && a.y < b.y + b.height + 2 * PADDING
+            && b.y < a.y + a.height + 2 * PADDING
+    }
+
+    #[test]
+    fn images_are_copied_into_padded_regions() {
+        let mut atlas = ImageAtlas::new(16, 16);
+        let red = atlas.insert("red", &solid(3, 2, [255, 0, 0, 255])).unwrap();
+        let blue = atlas
+            .insert("blue", &solid(2, 2, [0, 0, 255, 255]))
+            .unwrap();
+        let tall = atlas
+            .insert("tall", &solid(1, 5, [0, 255, 0, 255]))
+            .unwrap();
+        let short = atlas.insert("short", &solid(4, 1, [9, 9, 9, 9])).unwrap();
+
+        assert_eq!(
+            red,
+            AtlasRegion {
+                page: 0,
+                x: 1,
+                y: 1,
+                width: 3,
+                height: 2
+            }
+        );
+        assert_eq!((blue.x, blue.y), (6, 1));
+        // the first shelf is too short, so a new one is started
+        assert_eq!((tall.x, tall.y), (1, 5));
+        // the shortest shelf that fits is used
+        assert_eq!((short.x, short.y), (10, 1));
+        let regions = [red, blue, tall, short];
+        for (i, a) in regions.iter().enumerate() {
+            for b in &regions[i + 1..] {
+                assert!(!overlap(*a, *b), "{:?} and {:?} overlap", a, b);
+            }
+        }
+
+        let page = atlas.page(0);
+        assert_eq!(page.pixel(1, 1), [255, 0, 0, 255]);
+        assert_eq!(page.pixel(3, 2), [255, 0, 0, 255]);
+        assert_eq!(page.pixel(4, 1), [0, 0, 0, 0]);
+        assert_eq!(page.pixel(7, 2), [0, 0, 255, 255]);
+        assert_eq!(page.pixel(1, 9), [0, 255, 0, 255]);
+        assert_eq!(page.pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(atlas.get(&"blue"), Some(blue));
+        assert_eq!(atlas.len(), 4);
+    }
+
+    #[test]
+    fn full_pages_are_followed_by_new_ones() {
+        let mut atlas = ImageAtlas::new(8, 100);
+        assert!(atlas.insert(0, &solid(7, 1, [1; 4])).is_none());
+        assert!(atlas.insert(0, &solid(1, 7, [1; 4])).is_none());
+        assert!(atlas.is_empty());
+
+        let pages: Vec<_> = (0..5)
+            .map(|key| atlas.insert(key, &solid(6, 2, [1; 4])).unwrap().page)
+            .collect();
+        assert_eq!(pages, [0, 0, 1, 1, 2]);
+        assert_eq!(atlas.page_count(), 3);
+        assert_eq!(atlas.page(2).width(), 8);
+    }
+
+    #[test]
+    fn space_is_reclaimed_from_empty_pages() {
+        let mut atlas = ImageAtlas::new(8, 6);
+        atlas.insert("a", &solid(6, 2, [1; 4])).unwrap();
+        atlas.insert("b", &solid(6, 2, [2; 4])).unwrap();
+
+        // replacing an image doesn’t reuse its space until the page is empty
+        let replaced = atlas.insert("a", &solid(6, 2, [3; 4])).unwrap();
+        assert_eq!(replaced.page, 1);
+        assert_eq!(atlas.len(), 2);
+
+        atlas.remove(&"b");
+        atlas.remove(&"b");
+        let reused = atlas.insert("c", &solid(2, 2, [4; 4])).unwrap();
+        assert_eq!((reused.page, reused.x, reused.y), (0, 1, 1));
+        // the old pixels around the new image were cleared
+        assert_eq!(atlas.page(0).pixel(3, 1), [0, 0, 0, 0]);
+        assert_eq!(atlas.page(0).pixel(2, 2), [4; 4]);
+        assert_eq!(atlas.get(&"b"), None);
+    }
+
+    #[test]
+    fn changed_pages_are_dirty() {
+        let mut atlas = ImageAtlas::new(8, 6);
+        assert!(atlas.dirty_pages().is_empty());
+        atlas.insert(0, &solid(6, 6, [1; 4])).unwrap();
+        atlas.insert(1, &solid(6, 6, [1; 4])).unwrap();
+        assert_eq!(atlas.dirty_pages(), [0, 1]);
+
+        atlas.mark_uploaded(0);
+        atlas.mark_uploaded(1);
+        assert!(atlas.dirty_pages().is_empty());
+
+        // removing images doesn’t change pixels that are still in use
+        atlas.remove(&0);
+        assert!(atlas.dirty_pages().is_empty());
+        atlas.insert(2, &solid(1, 1, [1; 4])).unwrap();
+        assert_eq!(atlas.dirty_pages(), [0]);
+
+        atlas.invalidate_pages();
+        assert_eq!(atlas.dirty_pages(), [0, 1]);
+    }
+
+    #[test]
+    fn cap_insets_are_clamped_proportionally() {
+        assert!(CapInsets::default().is_zero());
+        assert!(!CapInsets::uniform(1).is_zero());
+        assert_eq!(CapInsets::uniform(4).clamped(10, 8), CapInsets::uniform(4));
+
+        let insets = CapInsets {
+            top: 6,
+            left: 3,
+            bottom: 2,
+            right: 9,
+        };
+        assert_eq!(
+            insets.clamped(6, 4),
+            CapInsets {
+                top: 3,
+                left: 1,
+                bottom: 1,
+                right: 5,
+            }
+        );
+        assert_eq!(
+            CapInsets::uniform(u32::MAX).clamped(5, 0),
+            CapInsets {
+                top: 0,
+                left: 2,
+                bottom: 0,
+                right: 3,
+            }
+        );
+    }
+}
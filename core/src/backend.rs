@@ -1,7 +1,11 @@
 //! Traits for backends.
 
+use crate::accessibility::Announcement;
+use crate::image::RgbaImage;
 use crate::nv_tree::NativeView;
+use crate::print::PageSetup;
 use crate::raw_events::RawEvent;
+use crate::rect::Rect;
 
 /// A backend implementation.
 pub trait Backend {
@@ -45,8 +49,104 @@ pub trait Backend {
     /// Sets the root view.
     fn set_root_view(&mut self, view: &mut Self::ViewRef) -> Result<(), Self::Error>;
 
+    /// Renders a view and its subviews offscreen into an image of the given size in pixels.
+    ///
+    /// The view’s bounds will be scaled to fit the image. Returns None if the backend does not
+    /// support offscreen rendering.
+    fn render_to_image(
+        &mut self,
+        _view: &Self::ViewRef,
+        _size: (u32, u32),
+    ) -> Result<Option<RgbaImage>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Renders a view and its subviews into a PDF document.
+    ///
+    /// Each page rectangle (in the view’s coordinate system) is drawn on its own page, inset by
+    /// the page margin. Returns None if the backend does not support PDF export.
+    fn export_pdf(
+        &mut self,
+        _view: &Self::ViewRef,
+        _setup: &PageSetup,
+        _pages: &[Rect],
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Captures the current contents of the host (e.g. the window) at device resolution.
+    ///
+    /// Returns None if the backend does not support capturing.
+    fn capture(&mut self) -> Result<Option<RgbaImage>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Creates or replaces an [atlas](crate::atlas) page, whose regions may be used as layer
+    /// contents.
+    ///
+    /// Backends that can’t draw layer contents may ignore this.
+    fn set_atlas_page(&mut self, _page: usize, _image: &RgbaImage) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Returns true if the backend knows a kind of [custom view](crate::custom_view).
+    ///
+    /// Custom views of unknown kinds are shown as empty layers.
+    fn supports_custom_view(&self, _kind: &str) -> bool {
+        false
+    }
+
+    /// Asks the screen reader to speak a message.
+    ///
+    /// Backends without screen reader support may ignore this.
+    fn announce(&mut self, _announcement: &Announcement) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Sets whether the current file drag would be accepted if the files were dropped, so the
+    /// backend can show appropriate feedback. Called after dispatching
+    /// [`FileDrag`](RawEvent::FileDrag) events.
+    ///
+    /// Backends without file drag support may ignore this.
+    fn set_drop_accepted(&mut self, _accepted: bool) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     /// Returns the next event from the queue.
     ///
     /// This method may be called frequently in quick succession.
     fn poll(&mut self) -> Result<Option<RawEvent>, Self::Error>;
 }
+
+/// A backend without views, for testing the NV tree.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct NullBackend;
+
+#[cfg(test)]
+impl Backend for NullBackend {
+    type ViewRef = ();
+    type Error = ();
+
+    fn new_view(&mut self, _view: NativeView) -> Result<(), ()> {
+        Ok(())
+    }
+    fn remove_view(&mut self, _view: ()) -> Result<(), ()> {
+        Ok(())
+    }
+    fn update_view(&mut self, _: &mut (), _view: NativeView) -> Result<(), ()> {
+        Ok(())
+    }
+    fn replace_view(&mut self, _: &mut (), _view: NativeView) -> Result<(), ()> {
+        Ok(())
+    }
+    fn set_subviews(&mut self, _: &mut (), _: usize, _: usize, _: Vec<&()>) -> Result<(), ()> {
+        Ok(())
+    }
+    fn set_root_view(&mut self, _view: &mut ()) -> Result<(), ()> {
+        Ok(())
+    }
+    fn poll(&mut self) -> Result<Option<RawEvent>, ()> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,349 @@
+//! Large scrollable canvases.
+
+use crate::layer::{Layer, LayerProps};
+use crate::rect::Rect;
+use crate::spatial::SpatialIndex;
+use crate::view::{AnyState, Fragment, View};
+use crate::view_tree::{Context, Observers};
+use cgmath::{EuclideanSpace, Point2, Vector2};
+use core::any::Any;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Scroll state of a [`Canvas`].
+///
+/// Clones share the same state, so it can be scrolled from event handlers; the canvas is rendered
+/// again when it changes.
+#[derive(Debug, Clone)]
+pub struct CanvasState {
+    scroll_offset: Arc<Mutex<Vector2<f64>>>,
+    observers: Observers,
+}
+
+impl CanvasState {
+    pub fn new() -> CanvasState {
+        CanvasState {
+            scroll_offset: Arc::new(Mutex::new(Vector2::new(0., 0.))),
+            observers: Observers::new(),
+        }
+    }
+
+    /// Renders the view of a context again whenever the state changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// The scroll offset in points; i.e. the canvas point at the top left corner of the viewport.
+    pub fn scroll_offset(&self) -> Vector2<f64> {
+        *self.scroll_offset.lock()
+    }
+
+    /// Sets the scroll offset in points.
+    pub fn set_scroll_offset(&self, offset: Vector2<f64>) {
+        {
+            let mut scroll_offset = self.scroll_offset.lock();
+            if *scroll_offset == offset {
+                return;
+            }
+            *scroll_offset = offset;
+        }
+        self.observers.notify();
+    }
+}
+
+impl Default for CanvasState {
+    fn default() -> Self {
+        CanvasState::new()
+    }
+}
+
+/// Renders a canvas item, given its key. The view will be placed in a layer with the item’s
+/// bounds, so it should be laid out relative to the origin.
+pub type ItemFn<Ctx> = dyn Fn(u64) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// A scrollable two-dimensional canvas for large amounts of content, such as diagrams.
+///
+/// Items are given as rectangles in canvas coordinates in a [`SpatialIndex`], and only items
+/// intersecting the visible area (extended by [`margin`](Self::margin)) are rendered.
+/// The canvas is split into square tiles, and every item is placed in the tile that contains its
+/// origin; tiles are materialized as they scroll into view and reclaimed as they scroll out,
+/// while scrolling only moves the tiles and leaves their contents alone.
+///
+/// The canvas extends from the origin to the far corner of the item bounds.
+pub struct Canvas<Ctx> {
+    pub key: Option<u64>,
+
+    /// Viewport bounds.
+    pub bounds: Rect,
+
+    /// Item rectangles, by item key.
+    pub items: Arc<SpatialIndex<u64>>,
+
+    /// Renders items.
+    pub item: Arc<ItemFn<Ctx>>,
+
+    /// Scroll state.
+    pub state: CanvasState,
+
+    /// Tile width and height.
+    pub tile_size: f64,
+
+    /// Distance outside the viewport in which items will also be rendered, so that scrolling
+    /// doesn’t immediately reveal empty space.
+    pub margin: f64,
+}
+
+impl<Ctx> fmt::Debug for Canvas<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Canvas")
+            .field("bounds", &self.bounds)
+            .field("items", &self.items.len())
+            .field("state", &self.state)
+            .field("tile_size", &self.tile_size)
+            .field("margin", &self.margin)
+            .finish()
+    }
+}
+
+impl<Ctx> PartialEq for Canvas<Ctx> {
+    fn eq(&self, other: &Canvas<Ctx>) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && Arc::ptr_eq(&self.items, &other.items)
+            && Arc::ptr_eq(&self.item, &other.item)
+            && Arc::ptr_eq(&self.state.scroll_offset, &other.state.scroll_offset)
+            && self.tile_size == other.tile_size
+            && self.margin == other.margin
+    }
+}
+
+impl<Ctx> Canvas<Ctx> {
+    /// The size of the canvas.
+    pub fn content_size(&self) -> Vector2<f64> {
+        match self.items.bounds() {
+            Some(bounds) => (bounds.origin + bounds.size).to_vec().map(|x| x.max(0.)),
+            None => Vector2::new(0., 0.),
+        }
+    }
+
+    fn max_scroll_offset(&self) -> Vector2<f64> {
+        (self.content_size() - self.bounds.size).map(|x| x.max(0.))
+    }
+
+    /// The visible area in canvas coordinates.
+    pub fn visible_rect(&self) -> Rect {
+        Rect::new(
+            Point2::from_vec(self.state.scroll_offset()),
+            self.bounds.size,
+        )
+    }
+
+    /// Scrolls by a delta in points, clamped to the content.
+    pub fn scroll_by(&self, delta: Vector2<f64>) {
+        let max = self.max_scroll_offset();
+        let offset = self.state.scroll_offset() + delta;
+        self.state.set_scroll_offset(Vector2::new(
+            offset.x.clamp(0., max.x),
+            offset.y.clamp(0., max.y),
+        ));
+    }
+
+    /// Scrolls a rectangle in canvas coordinates into view, if possible.
+    pub fn scroll_to_rect(&self, rect: Rect) {
+        fn axis(offset: f64, viewport: f64, min: f64, size: f64) -> f64 {
+            if min < offset || size > viewport {
+                min
+            } else if min + size > offset + viewport {
+                min + size - viewport
+            } else {
+                offset
+            }
+        }
+        let offset = self.state.scroll_offset();
+        let max = self.max_scroll_offset();
+        let x = axis(offset.x, self.bounds.size.x, rect.origin.x, rect.size.x);
+        let y = axis(offset.y, self.bounds.size.y, rect.origin.y, rect.size.y);
+        self.state
+            .set_scroll_offset(Vector2::new(x.clamp(0., max.x), y.clamp(0., max.y)));
+    }
+
+    /// Returns the tile containing a point in canvas coordinates.
+    fn tile_at(&self, point: Point2<f64>) -> (i64, i64) {
+        let size = self.tile_size.max(1.);
+        (
+            (point.x / size).floor() as i64,
+            (point.y / size).floor() as i64,
+        )
+    }
+
+    /// Returns the items to render and their rectangles, grouped by tile, in drawing order.
+    fn visible_tiles(&self) -> BTreeMap<(i64, i64), Vec<(u64, Rect)>> {
+        let area = self.visible_rect().inset(-self.margin, -self.margin);
+        let mut tiles: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for key in self.items.query(area) {
+            if let Some(rect) = self.items.get(key) {
+                tiles
+                    .entry(self.tile_at(rect.origin))
+                    .or_default()
+                    .push((key, rect));
+            }
+        }
+        for items in tiles.values_mut() {
+            items.sort_unstable_by_key(|(key, _)| *key);
+        }
+        tiles
+    }
+
+    /// Returns the key of the topmost item at a point in the canvas’s superview’s coordinate
+    /// system.
+    pub fn item_at(&self, point: Point2<f64>) -> Option<u64> {
+        if !self.bounds.contains(point) {
+            return None;
+        }
+        let point = point - self.bounds.origin.to_vec() + self.state.scroll_offset();
+        self.items
+            .query_point(point)
+            .into_iter()
+            .filter_map(|key| Some((self.tile_at(self.items.get(key)?.origin), key)))
+            .max()
+            .map(|(_, key)| key)
+    }
+}
+
+impl<Ctx: 'static> View<Ctx> for Canvas<Ctx> {
+    fn body(&self, _state: &AnyState<Ctx>) -> Arc<dyn View<Ctx>> {
+        let offset = self.state.scroll_offset();
+        let tile_size = self.tile_size.max(1.);
+
+        let mut tiles: Fragment<Ctx> = Vec::new();
+        for ((x, y), rects) in self.visible_tiles() {
+            let tile_origin = Point2::new(x as f64 * tile_size, y as f64 * tile_size);
+
+            let mut items: Fragment<Ctx> = Vec::with_capacity(rects.len());
+            for (key, rect) in rects {
+                items.push(Arc::new(Layer {
+                    key: Some(key),
+                    props: LayerProps {
+                        bounds: Rect::new(Point2::from_vec(rect.origin - tile_origin), rect.size),
+                        ..LayerProps::default()
+                    },
+                    subviews: vec![(self.item)(key)],
+                    ..Layer::default()
+                }));
+            }
+
+            let mut hasher = DefaultHasher::new();
+            (x, y).hash(&mut hasher);
+            tiles.push(Arc::new(Layer {
+                key: Some(hasher.finish()),
+                props: LayerProps {
+                    bounds: Rect::new(tile_origin - offset, Vector2::new(tile_size, tile_size)),
+                    ..LayerProps::default()
+                },
+                subviews: items,
+                ..Layer::default()
+            }));
+        }
+
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                clip_contents: true,
+                ..LayerProps::default()
+            },
+            subviews: tiles,
+            ..Layer::default()
+        })
+    }
+    fn eq(&self, other: &dyn View<Ctx>) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.state.observe(context);
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), (width, height).into())
+    }
+
+    /// A 100×100 canvas with 100-point tiles.
+    fn canvas(items: SpatialIndex<u64>) -> Canvas<()> {
+        Canvas {
+            key: None,
+            bounds: rect(0., 0., 100., 100.),
+            items: Arc::new(items),
+            item: Arc::new(|_| Arc::new(())),
+            state: CanvasState::new(),
+            tile_size: 100.,
+            margin: 0.,
+        }
+    }
+
+    /// Returns the keys of the items to render in each tile.
+    fn tile_keys(canvas: &Canvas<()>) -> Vec<((i64, i64), Vec<u64>)> {
+        canvas
+            .visible_tiles()
+            .into_iter()
+            .map(|(tile, items)| (tile, items.into_iter().map(|(key, _)| key).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn items_are_placed_in_the_tile_containing_their_origin() {
+        let mut items = SpatialIndex::new(50.);
+        items.insert(1, rect(10., 10., 20., 20.));
+        items.insert(3, rect(90., 90., 20., 20.));
+        items.insert(2, rect(80., 10., 10., 10.));
+        items.insert(4, rect(150., 10., 10., 10.));
+        let canvas = canvas(items);
+        assert_eq!(tile_keys(&canvas), [((0, 0), vec![1, 2, 3])]);
+
+        canvas.scroll_by(Vector2::new(60., 0.));
+        assert_eq!(canvas.state.scroll_offset(), Vector2::new(60., 0.));
+        assert_eq!(
+            tile_keys(&canvas),
+            [((0, 0), vec![2, 3]), ((1, 0), vec![4])]
+        );
+
+        // scrolling is clamped to the content
+        canvas.scroll_by(Vector2::new(100., 100.));
+        assert_eq!(canvas.state.scroll_offset(), Vector2::new(60., 10.));
+    }
+
+    #[test]
+    fn items_in_later_tiles_are_on_top() {
+        let mut items = SpatialIndex::new(50.);
+        items.insert(2, rect(50., 50., 60., 60.));
+        items.insert(1, rect(100., 100., 20., 20.));
+        items.insert(3, rect(10., 10., 10., 10.));
+        let canvas = canvas(items);
+        assert_eq!(canvas.item_at(Point2::new(60., 60.)), Some(2));
+        assert_eq!(canvas.item_at(Point2::new(15., 15.)), Some(3));
+        assert_eq!(canvas.item_at(Point2::new(40., 40.)), None);
+        assert_eq!(canvas.item_at(Point2::new(150., 150.)), None);
+
+        canvas.scroll_by(Vector2::new(20., 20.));
+        assert_eq!(canvas.item_at(Point2::new(85., 85.)), Some(1));
+        assert_eq!(canvas.item_at(Point2::new(75., 75.)), Some(2));
+    }
+}
@@ -1,10 +1,260 @@
 //! Color.
 
+use crate::rect::Rect;
+use cgmath::Point2;
+
 /// A color in sRGB.
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     pub r: f64,
     pub g: f64,
     pub b: f64,
     pub a: f64,
 }
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0., 0., 0., 0.);
+    pub const BLACK: Color = Color::rgb(0., 0., 0.);
+    pub const WHITE: Color = Color::rgb(1., 1., 1.);
+
+    /// Creates an opaque color.
+    pub const fn rgb(r: f64, g: f64, b: f64) -> Color {
+        Color { r, g, b, a: 1. }
+    }
+
+    /// Creates a color with an alpha value.
+    pub const fn rgba(r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Parses a hex color like in CSS: `#rgb`, `#rgba`, `#rrggbb`, or `#rrggbbaa`, with or without
+    /// the `#`. Returns None if it’s invalid.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        let digit = |i: usize, len: usize| -> Option<f64> {
+            let value = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+            Some(match len {
+                // #abc is short for #aabbcc
+                1 => value as f64 / 15.,
+                _ => value as f64 / 255.,
+            })
+        };
+        let (components, len) = match hex.len() {
+            3 => (3, 1),
+            4 => (4, 1),
+            6 => (3, 2),
+            8 => (4, 2),
+            _ => return None,
+        };
+        Some(Color {
+            r: digit(0, len)?,
+            g: digit(1, len)?,
+            b: digit(2, len)?,
+            a: if components == 4 { digit(3, len)? } else { 1. },
+        })
+    }
+
+    /// Returns the same color with a different alpha value.
+    pub const fn with_alpha(self, a: f64) -> Color {
+        Color { a, ..self }
+    }
+
+    /// Interpolates linearly between this color (at 0) and another (at 1), component-wise in
+    /// sRGB.
+    ///
+    /// Colors are mixed premultiplied, so mixing with a transparent color fades without
+    /// shifting the hue.
+    pub fn mix(self, other: Color, t: f64) -> Color {
+        let a = self.premultiplied();
+        let b = other.premultiplied();
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Color {
+            r: lerp(a.r, b.r),
+            g: lerp(a.g, b.g),
+            b: lerp(a.b, b.b),
+            a: lerp(a.a, b.a),
+        }
+        .unpremultiplied()
+    }
+
+    /// Returns the color with its color components multiplied by alpha, as most compositors
+    /// expect.
+    pub fn premultiplied(self) -> Color {
+        Color {
+            r: self.r * self.a,
+            g: self.g * self.a,
+            b: self.b * self.a,
+            a: self.a,
+        }
+    }
+
+    /// Reverses [`premultiplied`](Self::premultiplied). Fully transparent colors become
+    /// transparent black.
+    pub fn unpremultiplied(self) -> Color {
+        if self.a <= 0. {
+            return Color::TRANSPARENT;
+        }
+        Color {
+            r: self.r / self.a,
+            g: self.g / self.a,
+            b: self.b / self.a,
+            a: self.a,
+        }
+    }
+}
+
+/// A linear gradient.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gradient {
+    /// Where the gradient starts, in unit coordinates of the rectangle it fills: (0, 0) is the
+    /// top left corner and (1, 1) the bottom right corner.
+    pub start: Point2<f64>,
+    /// Where the gradient ends, in the same coordinates as the start.
+    pub end: Point2<f64>,
+    /// Color stops as (location, color) pairs in ascending order, where locations go from 0 at
+    /// the start to 1 at the end. Colors are extended beyond the first and last stop.
+    pub stops: Vec<(f64, Color)>,
+}
+
+impl Gradient {
+    /// Creates a gradient from the top to the bottom edge.
+    pub fn vertical(top: Color, bottom: Color) -> Gradient {
+        Gradient {
+            start: Point2::new(0.5, 0.),
+            end: Point2::new(0.5, 1.),
+            stops: vec![(0., top), (1., bottom)],
+        }
+    }
+
+    /// Creates a gradient from the left to the right edge.
+    pub fn horizontal(left: Color, right: Color) -> Gradient {
+        Gradient {
+            start: Point2::new(0., 0.5),
+            end: Point2::new(1., 0.5),
+            stops: vec![(0., left), (1., right)],
+        }
+    }
+
+    /// Returns the color at a location between the start (0) and the end (1).
+    pub fn color_at(&self, location: f64) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Color::TRANSPARENT,
+        };
+        if location <= first.0 {
+            return first.1;
+        }
+        for pair in self.stops.windows(2) {
+            let ((a, a_color), (b, b_color)) = (pair[0], pair[1]);
+            if location < b {
+                let t = if b > a { (location - a) / (b - a) } else { 1. };
+                return a_color.mix(b_color, t);
+            }
+        }
+        last.1
+    }
+
+    /// Returns the color at a point in a rectangle the gradient fills, projected onto the line
+    /// from the start to the end.
+    pub fn color_at_point(&self, rect: Rect, point: Point2<f64>) -> Color {
+        let unit = Point2::new(
+            (point.x - rect.origin.x) / rect.size.x,
+            (point.y - rect.origin.y) / rect.size.y,
+        );
+        let direction = self.end - self.start;
+        let length2 = direction.x * direction.x + direction.y * direction.y;
+        if length2 <= 0. || !length2.is_finite() {
+            return self.color_at(0.);
+        }
+        let offset = unit - self.start;
+        self.color_at((offset.x * direction.x + offset.y * direction.y) / length2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_are_parsed() {
+        assert_eq!(Color::from_hex("#fff"), Some(Color::WHITE));
+        assert_eq!(Color::from_hex("f00"), Some(Color::rgb(1., 0., 0.)));
+        assert_eq!(
+            Color::from_hex("#0f08"),
+            Some(Color::rgba(0., 1., 0., 8. / 15.))
+        );
+        assert_eq!(
+            Color::from_hex("#336699"),
+            Some(Color::rgb(51. / 255., 102. / 255., 153. / 255.))
+        );
+        assert_eq!(
+            Color::from_hex("33669980"),
+            Some(Color::rgba(
+                51. / 255.,
+                102. / 255.,
+                153. / 255.,
+                128. / 255.
+            ))
+        );
+        assert_eq!(Color::from_hex("#ABCDEF"), Color::from_hex("#abcdef"));
+    }
+
+    #[test]
+    fn short_hex_colors_repeat_digits() {
+        assert_eq!(Color::from_hex("#abc"), Color::from_hex("#aabbcc"));
+        assert_eq!(Color::from_hex("#abcd"), Color::from_hex("#aabbccdd"));
+        assert_eq!(Color::from_hex("#0000"), Some(Color::TRANSPARENT));
+    }
+
+    #[test]
+    fn hex_colors_of_invalid_lengths_are_rejected() {
+        for hex in &[
+            "",
+            "#",
+            "#f",
+            "#ff",
+            "#fffff",
+            "#fffffff",
+            "#fffffffff",
+            "##fff",
+        ] {
+            assert_eq!(Color::from_hex(hex), None, "{:?}", hex);
+        }
+    }
+
+    #[test]
+    fn non_hex_colors_are_rejected() {
+        for hex in &[
+            "#ggg", "#12345z", "+fff", "#-fff", " fff", "fff ", "0x123", "#ÿff", "#ffé",
+        ] {
+            assert_eq!(Color::from_hex(hex), None, "{:?}", hex);
+        }
+    }
+
+    #[test]
+    fn mixing_with_transparent_colors_keeps_the_hue() {
+        let red = Color::rgb(1., 0., 0.);
+        assert_eq!(red.mix(Color::TRANSPARENT, 0.), red);
+        assert_eq!(red.mix(Color::TRANSPARENT, 0.5), red.with_alpha(0.5));
+        assert_eq!(Color::TRANSPARENT.mix(red, 0.25), red.with_alpha(0.25));
+        assert_eq!(red.mix(Color::TRANSPARENT, 1.), Color::TRANSPARENT);
+        assert_eq!(
+            Color::TRANSPARENT.mix(Color::TRANSPARENT, 0.5),
+            Color::TRANSPARENT
+        );
+    }
+
+    #[test]
+    fn colors_are_mixed_premultiplied() {
+        let blue = Color::rgba(0., 0., 1., 0.5);
+        let red = Color::rgb(1., 0., 0.);
+        assert_eq!(blue.mix(red, 0.5), Color::rgba(2. / 3., 0., 1. / 3., 0.75));
+        assert_eq!(blue.premultiplied(), Color::rgba(0., 0., 0.5, 0.5));
+        assert_eq!(blue.premultiplied().unpremultiplied(), blue);
+    }
+}
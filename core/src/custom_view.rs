@@ -0,0 +1,346 @@
+//! Native view kinds added by other crates.
+//!
+//! Backends only have a fixed set of native views built in. Crates that add platform controls
+//! (such as a map or a video player) can use [`CustomView`]s instead, which are identified by a
+//! kind string (e.g. `com.example.map`) and carry their properties as opaque bytes that only the
+//! code registered for that kind understands. Backends look kinds up in a
+//! [`CustomViewRegistry`], where such crates register a [`CustomViewFactory`] for their kind.
+//!
+//! Backends show custom views of kinds they don’t know as empty layers, so apps can check
+//! [`Backend::supports_custom_view`](crate::backend::Backend::supports_custom_view) to render a
+//! fallback instead.
+
+use crate::animation::Transitions;
+use crate::impl_view;
+use crate::nv_tree::NativeView;
+use crate::rect::Rect;
+use crate::view::{Fragment, NativeType, View};
+use cgmath::{Matrix3, SquareMatrix};
+use core::fmt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A native view of a custom kind; see the [module documentation](self).
+///
+/// Views of different kinds are never updated into each other, but replaced.
+pub struct CustomView<Ctx = ()> {
+    pub key: Option<u64>,
+
+    /// Identifies the kind of view. Should be a reverse domain name to avoid conflicts.
+    pub kind: String,
+
+    /// Properties, in a format defined by the kind.
+    pub props: Vec<u8>,
+
+    /// Bounds of this view in the superview.
+    pub bounds: Rect,
+
+    /// View transform around the center of the bounds.
+    pub transform: Matrix3<f64>,
+
+    /// View opacity.
+    pub opacity: f64,
+
+    /// Subviews of this view.
+    pub subviews: Fragment<Ctx>,
+
+    /// Transitions for when this view is inserted or removed.
+    pub transitions: Option<Transitions>,
+}
+
+impl<Ctx> CustomView<Ctx> {
+    /// Creates a custom view of the given kind.
+    pub fn new(kind: impl Into<String>, props: Vec<u8>) -> CustomView<Ctx> {
+        CustomView {
+            key: None,
+            kind: kind.into(),
+            props,
+            bounds: Rect::zero(),
+            transform: Matrix3::identity(),
+            opacity: 1.,
+            subviews: Vec::new(),
+            transitions: None,
+        }
+    }
+}
+
+impl<Ctx> fmt::Debug for CustomView<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomView")
+            .field("kind", &self.kind)
+            .field("props", &self.props.len())
+            .field("bounds", &self.bounds)
+            .field("transform", &self.transform)
+            .field("opacity", &self.opacity)
+            .field("subviews", &self.subviews)
+            .field("transitions", &self.transitions)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for CustomView<Ctx> {
+    fn eq(&self, other: &CustomView<Ctx>) -> bool {
+        self.kind == other.kind
+            && self.props == other.props
+            && self.bounds == other.bounds
+            && self.transform == other.transform
+            && self.opacity == other.opacity
+            && self.subviews.eq(&other.subviews)
+    }
+}
+
+impl_view! {
+    CustomView<Ctx>;
+    fn body(&self, _state: &()) {
+        Arc::new(self.subviews.clone())
+    }
+    fn native_type(&self) -> Option<NativeType> {
+        Some(NativeType::Custom)
+    }
+    fn native_view(&self) -> NativeView {
+        NativeView::Custom {
+            kind: self.kind.clone(),
+            bounds: self.bounds,
+            transform: self.transform,
+            opacity: self.opacity,
+            props: self.props.clone(),
+        }
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn transitions(&self) -> Option<Transitions> {
+        self.transitions.clone()
+    }
+    fn is_same_type(&self, other: &dyn View<Ctx>) -> bool {
+        match other.as_any().downcast_ref::<Self>() {
+            Some(other) => self.kind == other.kind,
+            None => false,
+        }
+    }
+}
+
+/// Creates and updates the platform views of a custom kind, for a backend whose platform views
+/// are of type `P`.
+pub trait CustomViewFactory<P>: Send {
+    /// Creates a platform view from properties.
+    fn new_view(&mut self, props: &[u8]) -> Result<P, String>;
+
+    /// Updates a platform view with new properties.
+    fn update_view(&mut self, view: &mut P, props: &[u8]) -> Result<(), String>;
+}
+
+/// Custom view kinds known to a backend, for a backend whose platform views are of type `P`; see
+/// the [module documentation](self).
+pub struct CustomViewRegistry<P> {
+    factories: HashMap<String, Box<dyn CustomViewFactory<P>>>,
+}
+
+impl<P> CustomViewRegistry<P> {
+    pub fn new() -> CustomViewRegistry<P> {
+        CustomViewRegistry {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers a kind, replacing any factory that was registered for it before.
+    pub fn register<F>(&mut self, kind: impl Into<String>, factory: F)
+    where
+        F: 'static + CustomViewFactory<P>,
+    {
+        self.factories.insert(kind.into(), Box::new(factory));
+    }
+
+    /// Removes a kind.
+    pub fn unregister(&mut self, kind: &str) {
+        self.factories.remove(kind);
+    }
+
+    /// Returns true if a kind is registered.
+    pub fn contains(&self, kind: &str) -> bool {
+        self.factories.contains_key(kind)
+    }
+
+    /// Returns the factory of a kind.
+    pub fn get_mut(&mut self, kind: &str) -> Option<&mut (dyn CustomViewFactory<P> + 'static)> {
+        self.factories.get_mut(kind).map(|factory| &mut **factory)
+    }
+
+    /// Returns all registered kinds.
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(|kind| &**kind)
+    }
+}
+
+impl<P> Default for CustomViewRegistry<P> {
+    fn default() -> CustomViewRegistry<P> {
+        CustomViewRegistry::new()
+    }
+}
+
+impl<P> fmt::Debug for CustomViewRegistry<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.factories.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::nv_tree::{NVTree, PatchError};
+    use crate::raw_events::RawEvent;
+    use crate::view_tree::ViewTree;
+
+    const KIND: &str = "com.example.dial";
+
+    /// A platform view of a known kind with its value, or an empty placeholder layer.
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct PlatformView {
+        kind: Option<String>,
+        value: u8,
+    }
+
+    /// Creates dials, whose props are a single byte.
+    struct DialFactory;
+
+    impl CustomViewFactory<PlatformView> for DialFactory {
+        fn new_view(&mut self, props: &[u8]) -> Result<PlatformView, String> {
+            let mut view = PlatformView {
+                kind: Some(KIND.into()),
+                value: 0,
+            };
+            self.update_view(&mut view, props)?;
+            Ok(view)
+        }
+        fn update_view(&mut self, view: &mut PlatformView, props: &[u8]) -> Result<(), String> {
+            match props {
+                [value] => {
+                    view.value = *value;
+                    Ok(())
+                }
+                _ => Err("expected a single byte".into()),
+            }
+        }
+    }
+
+    /// A backend that creates custom views with a registry, and placeholders for unknown kinds.
+    #[derive(Debug, Default)]
+    struct Platform {
+        registry: CustomViewRegistry<PlatformView>,
+        views: Vec<PlatformView>,
+    }
+
+    impl Platform {
+        fn create(&mut self, view: NativeView) -> Result<PlatformView, String> {
+            match view {
+                NativeView::Custom { kind, props, .. } => match self.registry.get_mut(&kind) {
+                    Some(factory) => factory.new_view(&props),
+                    None => Ok(PlatformView::default()),
+                },
+                NativeView::Layer(_) => Ok(PlatformView::default()),
+            }
+        }
+    }
+
+    impl Backend for Platform {
+        type ViewRef = usize;
+        type Error = String;
+
+        fn new_view(&mut self, view: NativeView) -> Result<usize, String> {
+            let view = self.create(view)?;
+            self.views.push(view);
+            Ok(self.views.len() - 1)
+        }
+        fn remove_view(&mut self, _view: usize) -> Result<(), String> {
+            Ok(())
+        }
+        fn update_view(&mut self, view: &mut usize, new: NativeView) -> Result<(), String> {
+            let platform_view = &mut self.views[*view];
+            match (new, &platform_view.kind) {
+                (NativeView::Custom { kind, props, .. }, Some(current)) if kind == *current => {
+                    match self.registry.get_mut(&kind) {
+                        Some(factory) => factory.update_view(platform_view, &props),
+                        None => Err(format!("{} was unregistered", kind)),
+                    }
+                }
+                _ => Ok(()),
+            }
+        }
+        fn replace_view(&mut self, view: &mut usize, new: NativeView) -> Result<(), String> {
+            self.views[*view] = self.create(new)?;
+            Ok(())
+        }
+        fn set_subviews(
+            &mut self,
+            _: &mut usize,
+            _: usize,
+            _: usize,
+            _: Vec<&usize>,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+        fn set_root_view(&mut self, _view: &mut usize) -> Result<(), String> {
+            Ok(())
+        }
+        fn supports_custom_view(&self, kind: &str) -> bool {
+            self.registry.contains(kind)
+        }
+        fn poll(&mut self) -> Result<Option<RawEvent>, String> {
+            Ok(None)
+        }
+    }
+
+    /// Renders a custom view and applies the patches.
+    fn render(
+        views: &mut ViewTree,
+        native_views: &mut NVTree<Box<Platform>, usize>,
+        kind: &str,
+        props: Vec<u8>,
+    ) -> Result<(), PatchError<Platform>> {
+        views.render_root(Arc::new(CustomView::new(kind, props)), ());
+        native_views.patch_frame(views.patches())
+    }
+
+    #[test]
+    fn registered_kinds_are_created_and_updated() {
+        let mut platform = Platform::default();
+        platform.registry.register(KIND, DialFactory);
+        assert_eq!(platform.registry.kinds().collect::<Vec<_>>(), [KIND]);
+        assert!(platform.supports_custom_view(KIND));
+
+        let mut views = ViewTree::new();
+        let mut native_views = NVTree::new(Box::new(platform));
+        render(&mut views, &mut native_views, KIND, vec![1]).unwrap();
+        render(&mut views, &mut native_views, KIND, vec![2]).unwrap();
+        let dial = PlatformView {
+            kind: Some(KIND.into()),
+            value: 2,
+        };
+        assert_eq!(native_views.backend().views, [dial]);
+
+        // factories may reject props
+        let result = render(&mut views, &mut native_views, KIND, vec![]);
+        assert!(matches!(result, Err(PatchError::BackendError(_))));
+        assert_eq!(native_views.backend().views[0].value, 2);
+    }
+
+    #[test]
+    fn unregistered_kinds_are_rejected() {
+        let mut platform = Platform::default();
+        platform.registry.register(KIND, DialFactory);
+        platform.registry.register("com.example.map", DialFactory);
+        platform.registry.unregister("com.example.map");
+        assert!(!platform.registry.contains("com.example.map"));
+        assert!(!platform.supports_custom_view("com.example.map"));
+
+        // views of other kinds replace the view and aren’t made by the dial factory
+        let mut views = ViewTree::new();
+        let mut native_views = NVTree::new(Box::new(platform));
+        render(&mut views, &mut native_views, KIND, vec![1]).unwrap();
+        render(&mut views, &mut native_views, "com.example.map", vec![1]).unwrap();
+        assert_eq!(native_views.backend().views, [PlatformView::default()]);
+        render(&mut views, &mut native_views, "com.example.map", vec![]).unwrap();
+        assert_eq!(native_views.backend().views, [PlatformView::default()]);
+    }
+}
@@ -0,0 +1,420 @@
+//! Damage tracking for renderers that draw the NV tree themselves.
+//!
+//! Backends that composite layers on their own (rather than handing them to a platform toolkit
+//! like Cocoa) would otherwise have to redraw the entire window every frame. A [`DamageTracker`]
+//! watches the same patches as the NV tree and works out which areas of the window have changed,
+//! so mostly-static UIs only redraw and re-composite small regions.
+//!
+//! ```no_run
+//! # use birb::damage::DamageTracker;
+//! # use birb::ViewTree;
+//! # let mut views = ViewTree::<()>::new();
+//! let mut damage = DamageTracker::new();
+//!
+//! // every frame
+//! let patches: Vec<_> = views.patches().collect();
+//! let dirty = damage.frame(&patches);
+//! // nv_tree.patch_frame(patches), then redraw only the dirty rectangles
+//! ```
+
+use crate::nv_tree::{NativeView, Patch, TrackingArea};
+use crate::rect::Rect;
+use crate::view::ViewId;
+use cgmath::{Matrix3, SquareMatrix};
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+
+/// Default maximum number of rectangles returned per frame.
+const DEFAULT_MAX_RECTS: usize = 16;
+
+struct Node {
+    view: NativeView,
+    superview: Option<ViewId>,
+    subviews: Vec<ViewId>,
+}
+
+/// What needs to be redrawn once a frame has been applied.
+enum After {
+    /// Only the view itself changed.
+    View(ViewId),
+    /// The view and everything in it may have moved.
+    Subtree(ViewId),
+}
+
+/// Tracks which areas of the window change with each frame of patches; see the
+/// [module documentation](self).
+pub struct DamageTracker {
+    nodes: HashMap<ViewId, Node>,
+    root: Option<ViewId>,
+    max_rects: usize,
+}
+
+impl DamageTracker {
+    pub fn new() -> DamageTracker {
+        DamageTracker {
+            nodes: HashMap::new(),
+            root: None,
+            max_rects: DEFAULT_MAX_RECTS,
+        }
+    }
+
+    /// Sets the maximum number of rectangles returned per frame; damage is merged into fewer,
+    /// larger rectangles beyond that. Defaults to 16.
+    pub fn set_max_rects(&mut self, max_rects: usize) {
+        self.max_rects = max_rects.max(1);
+    }
+
+    /// Forgets all views, e.g. when the renderer redraws everything anyway after a resync.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root = None;
+    }
+
+    /// Tracks a frame of patches, which must be the same patches that are applied to the NV
+    /// tree, in the same order.
+    ///
+    /// Returns the areas of the window that need to be redrawn, in window coordinates. They
+    /// cover everything visible that changed, both where it was before and where it is now.
+    pub fn frame(&mut self, patches: &[Patch]) -> Vec<Rect> {
+        let mut damage = Vec::new();
+        let mut after = Vec::new();
+
+        for patch in patches {
+            match patch {
+                Patch::SetRoot(id) => {
+                    if let Some(root) = self.root {
+                        self.push_subtree(root, &mut damage);
+                    }
+                    self.root = Some(*id);
+                    after.push(After::Subtree(*id));
+                }
+                Patch::Update(id, view) => {
+                    if let Some(node) = self.nodes.get(id) {
+                        if node.view == *view {
+                            continue;
+                        }
+                        let moved = node.view.bounds() != view.bounds()
+                            || node.view.transform() != view.transform()
+                            || node.view.clips_contents() != view.clips_contents();
+                        if moved {
+                            self.push_subtree(*id, &mut damage);
+                            after.push(After::Subtree(*id));
+                        } else {
+                            self.push_view(*id, &mut damage);
+                            after.push(After::View(*id));
+                        }
+                        self.nodes.get_mut(id).unwrap().view = view.clone();
+                    } else {
+                        self.nodes.insert(
+                            *id,
+                            Node {
+                                view: view.clone(),
+                                superview: None,
+                                subviews: Vec::new(),
+                            },
+                        );
+                    }
+                }
+                Patch::Replace(id, view) => {
+                    if self.nodes.contains_key(id) {
+                        self.push_subtree(*id, &mut damage);
+                        after.push(After::View(*id));
+                        let subviews =
+                            std::mem::take(&mut self.nodes.get_mut(id).unwrap().subviews);
+                        for subview in subviews {
+                            self.remove(subview);
+                        }
+                        self.nodes.get_mut(id).unwrap().view = view.clone();
+                    }
+                }
+                Patch::SubviewRegion(id, offset, len, subviews) => {
+                    let current = match self.nodes.get(id) {
+                        Some(node) => node.subviews.clone(),
+                        None => continue,
+                    };
+                    let start = (*offset).min(current.len());
+                    let end = (offset + len).min(current.len());
+                    let old = &current[start..end];
+                    if old == &subviews[..] {
+                        continue;
+                    }
+                    let old_set: HashSet<_> = old.iter().copied().collect();
+                    let new_set: HashSet<_> = subviews.iter().copied().collect();
+                    // views that stay only need to be redrawn if their stacking order changed
+                    let old_order = old.iter().filter(|view| new_set.contains(view));
+                    let new_order = subviews.iter().filter(|view| old_set.contains(view));
+                    let reordered = !old_order.eq(new_order);
+
+                    for removed in old {
+                        if new_set.contains(removed) && !reordered {
+                            continue;
+                        }
+                        self.push_subtree(*removed, &mut damage);
+                        if let Some(node) = self.nodes.get_mut(removed) {
+                            if node.superview == Some(*id) {
+                                node.superview = None;
+                            }
+                        }
+                    }
+                    for subview in subviews {
+                        if old_set.contains(subview) && !reordered {
+                            continue;
+                        }
+                        // the subview may be moving here from somewhere else
+                        self.push_subtree(*subview, &mut damage);
+                        if let Some(node) = self.nodes.get_mut(subview) {
+                            node.superview = Some(*id);
+                        }
+                        after.push(After::Subtree(*subview));
+                    }
+                    let node = self.nodes.get_mut(id).unwrap();
+                    node.subviews.splice(start..end, subviews.iter().copied());
+                }
+                Patch::Remove(id) => {
+                    self.push_subtree(*id, &mut damage);
+                    self.remove(*id);
+                }
+                Patch::Announce(_) => (),
+            }
+        }
+
+        for after in after {
+            match after {
+                After::View(id) => self.push_view(id, &mut damage),
+                After::Subtree(id) => self.push_subtree(id, &mut damage),
+            }
+        }
+        self.merge(damage)
+    }
+
+    /// Removes a view and its subviews.
+    fn remove(&mut self, id: ViewId) {
+        if let Some(node) = self.nodes.remove(&id) {
+            for subview in node.subviews {
+                self.remove(subview);
+            }
+        }
+    }
+
+    /// Returns where a view is in the window, and the clip rectangle of its ancestors in window
+    /// coordinates. Returns None if the view isn’t in the window.
+    fn area(&self, id: ViewId) -> Option<(TrackingArea, Option<Rect>)> {
+        let mut path = Vec::new();
+        let mut current = id;
+        loop {
+            path.push(current);
+            if Some(current) == self.root {
+                break;
+            }
+            current = self.nodes.get(&current)?.superview?;
+            if path.len() > self.nodes.len() {
+                // a cycle; the NV tree will reject this frame anyway
+                return None;
+            }
+        }
+
+        let mut to_window = Matrix3::identity();
+        let mut clip: Option<Rect> = None;
+        for id in path[1..].iter().rev() {
+            let view = &self.nodes.get(id)?.view;
+            let area = TrackingArea::new(to_window, view);
+            if let Some(shape) = view.clip_shape() {
+                let rect = shape
+                    .bounding_box()
+                    .transformed(area.to_window)
+                    .bounding_box();
+                clip = Some(match clip {
+                    Some(clip) => clip.intersect(rect).unwrap_or_else(Rect::zero),
+                    None => rect,
+                });
+            }
+            to_window = area.to_window;
+        }
+        Some((TrackingArea::new(to_window, &self.nodes[&id].view), clip))
+    }
+
+    /// Adds the area of a view (but not its subviews) to the damage.
+    fn push_view(&self, id: ViewId, damage: &mut Vec<Rect>) {
+        if let Some((area, clip)) = self.area(id) {
+            let rect = area.outset_bounding_box(self.nodes[&id].view.overflow());
+            let rect = match clip {
+                Some(clip) => rect.intersect(clip),
+                None => Some(rect),
+            };
+            damage.extend(rect.filter(|rect| rect.size.x > 0. && rect.size.y > 0.));
+        }
+    }
+
+    /// Adds the area of a view and all its subviews to the damage.
+    fn push_subtree(&self, id: ViewId, damage: &mut Vec<Rect>) {
+        if let Some(node) = self.nodes.get(&id) {
+            self.push_view(id, damage);
+            for subview in &node.subviews {
+                self.push_subtree(*subview, damage);
+            }
+        }
+    }
+
+    /// Merges overlapping rectangles, and everything if there are too many.
+    fn merge(&self, mut rects: Vec<Rect>) -> Vec<Rect> {
+        let mut merged: Vec<Rect> = Vec::new();
+        while let Some(mut rect) = rects.pop() {
+            // merging may make the rectangle overlap ones that didn’t before
+            let mut i = 0;
+            while i < merged.len() {
+                if merged[i].intersects(rect) {
+                    rect = rect.union(merged.swap_remove(i));
+                    i = 0;
+                } else {
+                    i += 1;
+                }
+            }
+            merged.push(rect);
+        }
+        if merged.len() > self.max_rects {
+            let all = merged.into_iter().fold(None, |all: Option<Rect>, rect| {
+                Some(all.map_or(rect, |all| all.union(rect)))
+            });
+            return all.into_iter().collect();
+        }
+        merged
+    }
+}
+
+impl Default for DamageTracker {
+    fn default() -> DamageTracker {
+        DamageTracker::new()
+    }
+}
+
+impl fmt::Debug for DamageTracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DamageTracker")
+            .field("views", &self.nodes.len())
+            .field("root", &self.root)
+            .field("max_rects", &self.max_rects)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::layer::LayerProps;
+    use cgmath::{Point2, Vector2};
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    fn layer(bounds: Rect, background: Color) -> NativeView {
+        NativeView::Layer(Box::new(LayerProps {
+            bounds,
+            background,
+            ..LayerProps::default()
+        }))
+    }
+
+    /// Sorts damage rectangles from left to right.
+    fn sorted(mut rects: Vec<Rect>) -> Vec<Rect> {
+        rects.sort_by(|a, b| a.origin.x.total_cmp(&b.origin.x));
+        rects
+    }
+
+    /// Returns a tracker with a root view containing `a`, which contains `b`, and `c`.
+    fn tracker() -> (DamageTracker, [ViewId; 4]) {
+        let ids = [ViewId::new(), ViewId::new(), ViewId::new(), ViewId::new()];
+        let [root, a, b, c] = ids;
+        let mut damage = DamageTracker::new();
+        let initial = damage.frame(&[
+            Patch::Update(root, layer(rect(0., 0., 200., 200.), Color::WHITE)),
+            Patch::Update(a, layer(rect(10., 10., 20., 20.), Color::WHITE)),
+            Patch::Update(b, layer(rect(5., 5., 5., 5.), Color::WHITE)),
+            Patch::Update(c, layer(rect(100., 100., 20., 20.), Color::WHITE)),
+            Patch::SubviewRegion(root, 0, 0, vec![a, c]),
+            Patch::SubviewRegion(a, 0, 0, vec![b]),
+            Patch::SetRoot(root),
+        ]);
+        assert_eq!(initial, [rect(0., 0., 200., 200.)]);
+        (damage, ids)
+    }
+
+    #[test]
+    fn changed_views_are_damaged() {
+        let (mut damage, [_, a, b, c]) = tracker();
+        let unchanged = layer(rect(10., 10., 20., 20.), Color::WHITE);
+        assert_eq!(damage.frame(&[Patch::Update(a, unchanged)]), []);
+
+        let black = layer(rect(10., 10., 20., 20.), Color::BLACK);
+        assert_eq!(
+            damage.frame(&[Patch::Update(a, black)]),
+            [rect(10., 10., 20., 20.)]
+        );
+        let black = layer(rect(5., 5., 5., 5.), Color::BLACK);
+        assert_eq!(
+            damage.frame(&[Patch::Update(b, black)]),
+            [rect(15., 15., 5., 5.)]
+        );
+
+        assert_eq!(
+            damage.frame(&[Patch::Remove(c)]),
+            [rect(100., 100., 20., 20.)]
+        );
+    }
+
+    #[test]
+    fn moved_views_damage_their_old_and_new_rects() {
+        let (mut damage, [root, a, _, c]) = tracker();
+        let moved = layer(rect(50., 10., 20., 20.), Color::WHITE);
+        assert_eq!(
+            sorted(damage.frame(&[Patch::Update(a, moved)])),
+            [rect(10., 10., 20., 20.), rect(50., 10., 20., 20.)]
+        );
+
+        // overlapping damage is merged
+        let moved = layer(rect(60., 10., 20., 20.), Color::WHITE);
+        assert_eq!(
+            damage.frame(&[Patch::Update(a, moved)]),
+            [rect(50., 10., 30., 20.)]
+        );
+
+        // moving a view into another moves its subtree along
+        assert_eq!(
+            sorted(damage.frame(&[
+                Patch::SubviewRegion(root, 0, 1, vec![]),
+                Patch::SubviewRegion(c, 0, 0, vec![a]),
+            ])),
+            [rect(60., 10., 20., 20.), rect(160., 110., 20., 20.)]
+        );
+
+        // everything is merged if there is too much damage
+        damage.set_max_rects(1);
+        let moved = layer(rect(0., 0., 20., 20.), Color::WHITE);
+        assert_eq!(
+            damage.frame(&[Patch::Update(c, moved)]),
+            [rect(0., 0., 180., 130.)]
+        );
+    }
+
+    #[test]
+    fn damage_is_clipped_by_ancestors() {
+        let (mut damage, [_, a, b, _]) = tracker();
+        let clipping = NativeView::Layer(Box::new(LayerProps {
+            bounds: rect(10., 10., 20., 20.),
+            background: Color::WHITE,
+            clip_contents: true,
+            ..LayerProps::default()
+        }));
+        assert_eq!(
+            damage.frame(&[Patch::Update(a, clipping)]),
+            [rect(10., 10., 20., 20.)]
+        );
+
+        let outside = layer(rect(15., 15., 10., 10.), Color::WHITE);
+        assert_eq!(
+            sorted(damage.frame(&[Patch::Update(b, outside)])),
+            [rect(15., 15., 5., 5.), rect(25., 25., 5., 5.)]
+        );
+    }
+}
@@ -0,0 +1,537 @@
+//! Building view subtrees from declarative descriptions (with the `declarative` feature enabled).
+//!
+//! A [`Description`] can be deserialized from any self-describing serde format, such as JSON or
+//! RON, so that designers or tools can define static layouts without recompiling:
+//!
+//! ```json
+//! {
+//!     "component": "Layer",
+//!     "props": { "bounds": [0, 0, 320, 200], "background": [1, 1, 1, 1] },
+//!     "subviews": [
+//!         { "component": "Layer", "key": 1, "props": { "bounds": [10, 10, 50, 50] } }
+//!     ]
+//! }
+//! ```
+//!
+//! Components are looked up by name in a [`Registry`] of constructors, which apps extend with
+//! their own components:
+//!
+//! ```no_run
+//! # use birb::declarative::{Description, Registry};
+//! # use birb::Layer;
+//! # use std::sync::Arc;
+//! # fn load(description: Description) {
+//! let mut registry = Registry::<()>::with_builtins();
+//! registry.register("Card", |props, subviews| {
+//!     let mut layer = Layer::default();
+//!     layer.key = props.key();
+//!     layer.props.bounds = props.rect("bounds")?.unwrap_or(layer.props.bounds);
+//!     layer.props.corner_radius = 8.0.into();
+//!     layer.subviews = subviews;
+//!     Ok(Arc::new(layer))
+//! });
+//! let view = registry.load(&description).expect("invalid layout");
+//! # }
+//! ```
+
+use crate::color::{Color, Gradient};
+use crate::layer::{BorderAlignment, BorderStyle, Layer, LayerProps};
+use crate::rect::Rect;
+use crate::view::{AnyView, Fragment};
+use core::fmt;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::Arc;
+
+/// A dynamically typed property value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a property value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Number(value as f64))
+    }
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Number(value as f64))
+    }
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::Number(value))
+    }
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.into()))
+    }
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut list = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            list.push(item);
+        }
+        Ok(Value::List(list))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            entries.insert(key, value);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+/// A declarative description of a view subtree; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Description {
+    /// The name of the component in the registry.
+    pub component: String,
+
+    /// The view key.
+    #[serde(default)]
+    pub key: Option<u64>,
+
+    /// Component properties.
+    #[serde(default)]
+    pub props: BTreeMap<String, Value>,
+
+    /// Descriptions of the subviews.
+    #[serde(default)]
+    pub subviews: Vec<Description>,
+}
+
+/// Errors that may occur when loading a description.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// No component with this name has been registered.
+    UnknownComponent(String),
+    /// A property has the wrong type.
+    InvalidProp {
+        component: String,
+        prop: String,
+        expected: &'static str,
+    },
+    /// A component constructor failed.
+    Component { component: String, message: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::UnknownComponent(component) => write!(f, "unknown component {}", component),
+            LoadError::InvalidProp {
+                component,
+                prop,
+                expected,
+            } => write!(f, "{}.{}: expected {}", component, prop, expected),
+            LoadError::Component { component, message } => write!(f, "{}: {}", component, message),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// Properties of a component, passed to its constructor.
+#[derive(Debug, Clone, Copy)]
+pub struct Props<'a> {
+    component: &'a str,
+    key: Option<u64>,
+    values: &'a BTreeMap<String, Value>,
+}
+
+impl<'a> Props<'a> {
+    /// Returns the name of the component.
+    pub fn component(&self) -> &'a str {
+        self.component
+    }
+
+    /// Returns the view key, which the constructed view must use.
+    pub fn key(&self) -> Option<u64> {
+        self.key
+    }
+
+    /// Returns a property.
+    pub fn get(&self, name: &str) -> Option<&'a Value> {
+        self.values.get(name)
+    }
+
+    /// Returns an error for a property of the wrong type.
+    pub fn invalid(&self, name: &str, expected: &'static str) -> LoadError {
+        LoadError::InvalidProp {
+            component: self.component.into(),
+            prop: name.into(),
+            expected,
+        }
+    }
+
+    /// Returns an error for a failed constructor.
+    pub fn error(&self, message: impl Into<String>) -> LoadError {
+        LoadError::Component {
+            component: self.component.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Result<Option<bool>, LoadError> {
+        match self.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::Bool(value)) => Ok(Some(*value)),
+            Some(_) => Err(self.invalid(name, "a boolean")),
+        }
+    }
+
+    pub fn f64(&self, name: &str) -> Result<Option<f64>, LoadError> {
+        match self.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::Number(value)) => Ok(Some(*value)),
+            Some(_) => Err(self.invalid(name, "a number")),
+        }
+    }
+
+    pub fn str(&self, name: &str) -> Result<Option<&'a str>, LoadError> {
+        match self.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(self.invalid(name, "a string")),
+        }
+    }
+
+    fn numbers(
+        &self,
+        name: &str,
+        out: &mut [f64],
+        expected: &'static str,
+    ) -> Result<bool, LoadError> {
+        match self.get(name) {
+            None | Some(Value::Null) => Ok(false),
+            Some(Value::List(list)) if list.len() == out.len() => {
+                for (item, out) in list.iter().zip(out.iter_mut()) {
+                    match item {
+                        Value::Number(value) => *out = *value,
+                        _ => return Err(self.invalid(name, expected)),
+                    }
+                }
+                Ok(true)
+            }
+            Some(_) => Err(self.invalid(name, expected)),
+        }
+    }
+
+    /// Returns a rectangle written as `[x, y, width, height]`.
+    pub fn rect(&self, name: &str) -> Result<Option<Rect>, LoadError> {
+        let mut n = [0.; 4];
+        if self.numbers(name, &mut n, "[x, y, width, height]")? {
+            Ok(Some(Rect::new((n[0], n[1]).into(), (n[2], n[3]).into())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns a color written as `[r, g, b, a]`.
+    pub fn color(&self, name: &str) -> Result<Option<Color>, LoadError> {
+        let mut n = [0.; 4];
+        if self.numbers(name, &mut n, "[r, g, b, a]")? {
+            Ok(Some(Color {
+                r: n[0],
+                g: n[1],
+                b: n[2],
+                a: n[3],
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Constructs a view from properties and subviews.
+pub type Constructor<Ctx> =
+    Box<dyn Fn(Props, Fragment<Ctx>) -> Result<AnyView<Ctx>, LoadError> + Send + Sync>;
+
+/// Component constructors by name; see the [module documentation](self).
+pub struct Registry<Ctx = ()> {
+    constructors: HashMap<String, Constructor<Ctx>>,
+}
+
+impl<Ctx: 'static> Registry<Ctx> {
+    /// Creates an empty registry.
+    pub fn new() -> Registry<Ctx> {
+        Registry {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Creates a registry with the built-in components.
+    ///
+    /// - `Layer`: a [`Layer`] with the properties `bounds`, `background`, `background_gradient`
+    ///   (two colors, from top to bottom), `corner_radius`, `border_width`, `border_color`,
+    ///   `border_alignment` (`"inside"`, `"center"`, or `"outside"`), `border_dash`
+    ///   (`[dash, gap]`), `clip_contents`, `opacity`, `group_opacity`, `should_rasterize`, and
+    ///   `material`.
+    pub fn with_builtins() -> Registry<Ctx> {
+        let mut registry = Registry::new();
+        registry.register("Layer", layer);
+        registry
+    }
+
+    /// Registers a component, replacing any component with the same name.
+    pub fn register<F>(&mut self, name: impl Into<String>, constructor: F)
+    where
+        F: 'static + Fn(Props, Fragment<Ctx>) -> Result<AnyView<Ctx>, LoadError> + Send + Sync,
+    {
+        self.constructors.insert(name.into(), Box::new(constructor));
+    }
+
+    /// Returns true if a component is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.constructors.contains_key(name)
+    }
+
+    /// Constructs the view subtree of a description.
+    pub fn load(&self, description: &Description) -> Result<AnyView<Ctx>, LoadError> {
+        let constructor = match self.constructors.get(&description.component) {
+            Some(constructor) => constructor,
+            None => return Err(LoadError::UnknownComponent(description.component.clone())),
+        };
+        let mut subviews = Vec::with_capacity(description.subviews.len());
+        for subview in &description.subviews {
+            subviews.push(self.load(subview)?);
+        }
+        let props = Props {
+            component: &description.component,
+            key: description.key,
+            values: &description.props,
+        };
+        let view = constructor(props, subviews)?;
+        if view.key() != description.key {
+            return Err(props.error("constructed view does not have the view key"));
+        }
+        Ok(view)
+    }
+}
+
+impl<Ctx: 'static> Default for Registry<Ctx> {
+    fn default() -> Registry<Ctx> {
+        Registry::with_builtins()
+    }
+}
+
+impl<Ctx> fmt::Debug for Registry<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.constructors.keys()).finish()
+    }
+}
+
+fn layer<Ctx: 'static>(props: Props, subviews: Fragment<Ctx>) -> Result<AnyView<Ctx>, LoadError> {
+    let mut layer = LayerProps::default();
+    layer.bounds = props.rect("bounds")?.unwrap_or(layer.bounds);
+    layer.background = props.color("background")?.unwrap_or(layer.background);
+    let mut gradient = [0.; 8];
+    let expected = "[r, g, b, a, r, g, b, a]";
+    if props.numbers("background_gradient", &mut gradient, expected)? {
+        let color = |n: &[f64]| Color::rgba(n[0], n[1], n[2], n[3]);
+        layer.background_gradient = Some(Gradient::vertical(
+            color(&gradient[..4]),
+            color(&gradient[4..]),
+        ));
+    }
+    if let Some(corner_radius) = props.f64("corner_radius")? {
+        layer.corner_radius = corner_radius.into();
+    }
+    if let Some(width) = props.f64("border_width")? {
+        let color = props.color("border_color")?.unwrap_or_default();
+        layer.border = Some((width, color));
+    }
+    layer.border_alignment = match props.str("border_alignment")? {
+        None => layer.border_alignment,
+        Some("inside") => BorderAlignment::Inside,
+        Some("center") => BorderAlignment::Center,
+        Some("outside") => BorderAlignment::Outside,
+        Some(_) => {
+            let expected = "\"inside\", \"center\", or \"outside\"";
+            return Err(props.invalid("border_alignment", expected));
+        }
+    };
+    let mut dash = [0.; 2];
+    if props.numbers("border_dash", &mut dash, "[dash, gap]")? {
+        layer.border_style = BorderStyle::Dashed {
+            dash: dash[0],
+            gap: dash[1],
+        };
+    }
+    layer.clip_contents = props.bool("clip_contents")?.unwrap_or(layer.clip_contents);
+    layer.opacity = props.f64("opacity")?.unwrap_or(layer.opacity);
+    layer.group_opacity = props.bool("group_opacity")?.unwrap_or(layer.group_opacity);
+    layer.should_rasterize = props
+        .bool("should_rasterize")?
+        .unwrap_or(layer.should_rasterize);
+    layer.material = props.bool("material")?.unwrap_or(layer.material);
+    Ok(Arc::new(Layer {
+        key: props.key(),
+        props: layer,
+        subviews,
+        ..Layer::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a JSON description.
+    fn parse(json: &str) -> Description {
+        serde_json::from_str(json).expect("invalid description")
+    }
+
+    /// Loads a JSON description with the built-in components.
+    fn load(json: &str) -> Result<AnyView, LoadError> {
+        Registry::with_builtins().load(&parse(json))
+    }
+
+    fn as_layer(view: &AnyView) -> &Layer {
+        view.as_any().downcast_ref().expect("not a layer")
+    }
+
+    #[test]
+    fn descriptions_are_loaded_into_views() {
+        let view = load(
+            r#"{
+                "component": "Layer",
+                "props": {
+                    "bounds": [0, 0, 320, 200],
+                    "background": [1, 1, 1, 1],
+                    "border_width": 2,
+                    "border_dash": [4, 2],
+                    "clip_contents": true,
+                    "extra": { "ignored": [null, "yes"] }
+                },
+                "subviews": [
+                    { "component": "Layer", "key": 1, "props": { "bounds": [10, 10, 50, 50] } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let layer = as_layer(&view);
+        assert_eq!(layer.key, None);
+        assert_eq!(
+            layer.props.bounds,
+            Rect::new((0., 0.).into(), (320., 200.).into())
+        );
+        assert_eq!(layer.props.background, Color::rgba(1., 1., 1., 1.));
+        assert_eq!(layer.props.border, Some((2., Color::default())));
+        assert_eq!(
+            layer.props.border_style,
+            BorderStyle::Dashed { dash: 4., gap: 2. }
+        );
+        assert!(layer.props.clip_contents);
+        assert_eq!(layer.subviews.len(), 1);
+
+        let subview = as_layer(&layer.subviews[0]);
+        assert_eq!(subview.key, Some(1));
+        assert_eq!(
+            subview.props.bounds,
+            Rect::new((10., 10.).into(), (50., 50.).into())
+        );
+        assert!(subview.subviews.is_empty());
+    }
+
+    #[test]
+    fn malformed_descriptions_are_not_parsed() {
+        let malformed = [
+            r#"{ "component": "Layer""#,
+            r#"{ "props": {} }"#,
+            r#"{ "component": 1 }"#,
+            r#"{ "component": "Layer", "key": -1 }"#,
+            r#"{ "component": "Layer", "props": [] }"#,
+            r#"{ "component": "Layer", "subviews": [{}] }"#,
+        ];
+        for json in &malformed {
+            assert!(
+                serde_json::from_str::<Description>(json).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_components_are_errors() {
+        let error =
+            load(r#"{ "component": "Layer", "subviews": [{ "component": "Card", "key": 1 }] }"#)
+                .unwrap_err();
+        assert_eq!(error, LoadError::UnknownComponent("Card".into()));
+    }
+
+    #[test]
+    fn props_of_the_wrong_type_are_errors() {
+        let invalid = [
+            ("bounds", r#"[0, 0, 320]"#, "[x, y, width, height]"),
+            ("bounds", r#"[0, 0, "320", 200]"#, "[x, y, width, height]"),
+            ("background", r#""white""#, "[r, g, b, a]"),
+            ("opacity", r#"true"#, "a number"),
+            ("clip_contents", r#"1"#, "a boolean"),
+            (
+                "border_alignment",
+                r#""middle""#,
+                "\"inside\", \"center\", or \"outside\"",
+            ),
+        ];
+        for &(prop, value, expected) in &invalid {
+            let json = format!(
+                r#"{{ "component": "Layer", "props": {{ "{}": {} }} }}"#,
+                prop, value
+            );
+            let error = load(&json).unwrap_err();
+            let expected_error = LoadError::InvalidProp {
+                component: "Layer".into(),
+                prop: prop.into(),
+                expected,
+            };
+            assert_eq!(error, expected_error, "{}", json);
+        }
+    }
+
+    #[test]
+    fn views_must_use_the_described_key() {
+        let mut registry = Registry::<()>::new();
+        registry.register("Keyless", |_, _| Ok(Arc::new(Layer::default())));
+        let error = registry
+            .load(&parse(r#"{ "component": "Keyless", "key": 2 }"#))
+            .unwrap_err();
+        assert!(
+            matches!(error, LoadError::Component { ref component, .. } if component == "Keyless")
+        );
+    }
+}
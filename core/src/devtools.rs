@@ -0,0 +1,739 @@
+//! Developer tooling.
+
+use crate::color::Color;
+use crate::image::RgbaImage;
+use crate::nv_tree::NativeView;
+use crate::rect::CornerRadius;
+use crate::view::ViewId;
+use crate::view_tree::ViewTree;
+use core::fmt::Write as _;
+use core::mem;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// A captured frame.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// When the frame was captured.
+    pub time: Instant,
+    /// Frame contents.
+    pub image: RgbaImage,
+}
+
+/// Records a stream of frames from [`Backend::capture`](crate::backend::Backend::capture), e.g.
+/// for live previews in an inspector or for attaching a recording to feedback reports.
+///
+/// Only the most recent frames are kept.
+#[derive(Debug)]
+pub struct FrameRecorder {
+    interval: Duration,
+    max_frames: usize,
+    last_capture: Option<Instant>,
+    frames: VecDeque<Frame>,
+}
+
+impl FrameRecorder {
+    /// Creates a recorder that captures at most one frame per `interval` and keeps the last
+    /// `max_frames` frames.
+    pub fn new(interval: Duration, max_frames: usize) -> FrameRecorder {
+        FrameRecorder {
+            interval,
+            max_frames,
+            last_capture: None,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Should be called every frame. Calls `capture` if it’s time to record another frame.
+    ///
+    /// Returns true if a frame was recorded.
+    pub fn tick<E>(
+        &mut self,
+        now: Instant,
+        capture: impl FnOnce() -> Result<Option<RgbaImage>, E>,
+    ) -> Result<bool, E> {
+        if let Some(last_capture) = self.last_capture {
+            if now.saturating_duration_since(last_capture) < self.interval {
+                return Ok(false);
+            }
+        }
+        self.last_capture = Some(now);
+
+        match capture()? {
+            Some(image) => {
+                self.frames.push_back(Frame { time: now, image });
+                while self.frames.len() > self.max_frames {
+                    self.frames.pop_front();
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the most recently recorded frame.
+    pub fn latest(&self) -> Option<&Frame> {
+        self.frames.back()
+    }
+
+    /// Returns all recorded frames, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter()
+    }
+
+    /// Removes and returns all recorded frames, oldest first.
+    pub fn drain(&mut self) -> Vec<Frame> {
+        self.frames.drain(..).collect()
+    }
+}
+
+/// A property override for native views; see
+/// [`ViewTree::set_prop_override`](crate::ViewTree::set_prop_override).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropOverride {
+    Background(Color),
+    BorderColor(Color),
+    BorderWidth(f64),
+    CornerRadius(f64),
+    Opacity(f64),
+}
+
+impl PropOverride {
+    fn apply(&self, view: &mut NativeView) {
+        match view {
+            NativeView::Layer(props) => match *self {
+                PropOverride::Background(color) => props.background = color,
+                PropOverride::BorderColor(color) => {
+                    props.border = Some((props.border_width(), color));
+                }
+                PropOverride::BorderWidth(width) => {
+                    let color = props.border.map(|(_, color)| color).unwrap_or_default();
+                    props.border = Some((width, color));
+                }
+                PropOverride::CornerRadius(radius) => {
+                    props.corner_radius = CornerRadius {
+                        style: props.corner_radius.style,
+                        ..radius.into()
+                    }
+                }
+                PropOverride::Opacity(value) => props.opacity = value,
+            },
+            NativeView::Custom { opacity, .. } => {
+                if let PropOverride::Opacity(value) = *self {
+                    *opacity = value;
+                }
+            }
+        }
+    }
+}
+
+/// Border color of highlighted views.
+const HIGHLIGHT_COLOR: Color = Color::rgb(1., 0., 1.);
+
+/// Border width of highlighted views.
+const HIGHLIGHT_WIDTH: f64 = 2.;
+
+/// Native view overrides from developer tools, keyed by native view.
+#[derive(Default)]
+pub(crate) struct Overrides {
+    pub(crate) props: HashMap<ViewId, Vec<PropOverride>>,
+    pub(crate) highlighted: Vec<ViewId>,
+}
+
+impl Overrides {
+    /// Sets a property override, replacing any previous override of the same property.
+    pub(crate) fn set(&mut self, id: ViewId, prop: PropOverride) {
+        let props = self.props.entry(id).or_default();
+        props.retain(|p| mem::discriminant(p) != mem::discriminant(&prop));
+        props.push(prop);
+    }
+
+    /// Removes everything concerning a native view.
+    pub(crate) fn remove(&mut self, id: ViewId) {
+        self.props.remove(&id);
+        self.highlighted.retain(|highlighted| *highlighted != id);
+    }
+
+    /// Applies overrides to a native view.
+    pub(crate) fn apply(&self, id: ViewId, mut view: NativeView) -> NativeView {
+        if let Some(props) = self.props.get(&id) {
+            for prop in props {
+                prop.apply(&mut view);
+            }
+        }
+        if self.highlighted.contains(&id) {
+            PropOverride::BorderColor(HIGHLIGHT_COLOR).apply(&mut view);
+            PropOverride::BorderWidth(HIGHLIGHT_WIDTH).apply(&mut view);
+        }
+        view
+    }
+}
+
+/// A snapshot of a view in a view tree, for inspectors; see
+/// [`ViewTree::inspect`](crate::ViewTree::inspect).
+#[derive(Clone)]
+pub struct InspectorNode {
+    pub id: ViewId,
+    /// The name of the view type.
+    pub type_name: &'static str,
+    pub key: Option<u64>,
+    /// The native view as it was sent to the NV tree, if this is a native view.
+    pub native_view: Option<NativeView>,
+    pub subviews: Vec<InspectorNode>,
+}
+
+impl InspectorNode {
+    /// Serializes this node and its subviews as JSON (see [`DevtoolsServer`] for the format).
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        write!(
+            out,
+            "{{\"id\":\"{}\",\"type\":{},\"key\":",
+            self.id,
+            json_string(self.type_name)
+        )
+        .unwrap();
+        match self.key {
+            Some(key) => write!(out, "{}", key).unwrap(),
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"native\":");
+        match &self.native_view {
+            Some(NativeView::Layer(props)) => write!(
+                out,
+                "{{\"bounds\":[{},{},{},{}],\"background\":{},\"corner-radius\":{},\"border-width\":{},\"border-color\":{},\"clip-contents\":{},\"opacity\":{}}}",
+                json_number(props.bounds.origin.x),
+                json_number(props.bounds.origin.y),
+                json_number(props.bounds.size.x),
+                json_number(props.bounds.size.y),
+                json_color(props.background),
+                json_corner_radius(props.corner_radius),
+                json_number(props.border_width()),
+                json_color(props.border.map(|(_, color)| color).unwrap_or_default()),
+                props.clip_contents,
+                json_number(props.opacity)
+            )
+            .unwrap(),
+            Some(NativeView::Custom {
+                kind,
+                bounds,
+                opacity,
+                ..
+            }) => write!(
+                out,
+                "{{\"kind\":{},\"bounds\":[{},{},{},{}],\"opacity\":{}}}",
+                json_string(kind),
+                json_number(bounds.origin.x),
+                json_number(bounds.origin.y),
+                json_number(bounds.size.x),
+                json_number(bounds.size.y),
+                json_number(*opacity)
+            )
+            .unwrap(),
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"subviews\":[");
+        for (i, subview) in self.subviews.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            subview.write_json(out);
+        }
+        out.push_str("]}");
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_number(n: f64) -> String {
+    if n.is_finite() {
+        format!("{}", n)
+    } else {
+        "null".into()
+    }
+}
+
+/// Writes a single number if all corners have the same radius.
+fn json_corner_radius(radius: CornerRadius) -> String {
+    if radius.is_uniform() {
+        json_number(radius.top_left)
+    } else {
+        format!(
+            "[{},{},{},{}]",
+            json_number(radius.top_left),
+            json_number(radius.top_right),
+            json_number(radius.bottom_right),
+            json_number(radius.bottom_left)
+        )
+    }
+}
+
+fn json_color(color: Color) -> String {
+    format!(
+        "[{},{},{},{}]",
+        json_number(color.r),
+        json_number(color.g),
+        json_number(color.b),
+        json_number(color.a)
+    )
+}
+
+/// Serves a small developer tools protocol over a local TCP socket, through which external tools
+/// can inspect a view tree, highlight views on screen, and override native view properties live.
+///
+/// The protocol is line-based: every command is a line of text, and is answered with a line of
+/// JSON that is either `{"ok":true,...}` or `{"ok":false,"error":"..."}`. View IDs are written as
+/// UUIDs.
+///
+/// - `tree`: responds with `{"ok":true,"tree":node}` (or `null` if there is no root view), where
+///   each node is `{"id":..,"type":..,"key":..,"native":..,"subviews":[..]}`, and `native`
+///   contains the native view properties of native views (colors are `[r,g,b,a]` arrays).
+/// - `highlight <view>`: highlights a view on screen; `highlight` without a view removes the
+///   highlight.
+/// - `set <view> <prop> <value>`: overrides a property. `background` and `border-color` take
+///   colors as four numbers (`r g b a`), and `border-width`, `corner-radius` and `opacity` take
+///   a number.
+/// - `reset <view>`: removes all overrides of a view.
+pub struct DevtoolsServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+struct Client {
+    stream: TcpStream,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl DevtoolsServer {
+    /// Starts listening on an address.
+    ///
+    /// The protocol is not authenticated, so this should be a loopback address (e.g.
+    /// `127.0.0.1:0` to use any free port; see [`local_addr`](Self::local_addr)).
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<DevtoolsServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(DevtoolsServer {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Should be called every frame. Accepts new connections and handles pending commands, which
+    /// may emit patches.
+    ///
+    /// Clients that disconnect or cause I/O errors are dropped.
+    pub fn poll<Ctx>(&mut self, tree: &mut ViewTree<Ctx>) -> io::Result<()>
+    where
+        Ctx: 'static + Clone + Send,
+    {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    self.clients.push(Client {
+                        stream,
+                        input: Vec::new(),
+                        output: Vec::new(),
+                    });
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut i = 0;
+        while i < self.clients.len() {
+            if self.clients[i].poll(tree).is_ok() {
+                i += 1;
+            } else {
+                self.clients.remove(i);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    fn poll<Ctx>(&mut self, tree: &mut ViewTree<Ctx>) -> io::Result<()>
+    where
+        Ctx: 'static + Clone + Send,
+    {
+        let mut buf = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.input.extend_from_slice(&buf[..len]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        while let Some(end) = self.input.iter().position(|b| *b == b'\n') {
+            let line: Vec<_> = self.input.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let response = match handle_command(tree, line.trim()) {
+                Ok(response) => response,
+                Err(err) => format!("{{\"ok\":false,\"error\":{}}}", json_string(&err)),
+            };
+            self.output.extend_from_slice(response.as_bytes());
+            self.output.push(b'\n');
+        }
+
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.output.drain(..len);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Handles a devtools command and returns the JSON response.
+fn handle_command<Ctx>(tree: &mut ViewTree<Ctx>, line: &str) -> Result<String, String>
+where
+    Ctx: 'static + Clone + Send,
+{
+    let mut args = line.split_whitespace();
+    let command = args.next().unwrap_or("");
+
+    match command {
+        "tree" => Ok(match tree.inspect() {
+            Some(root) => format!("{{\"ok\":true,\"tree\":{}}}", root.to_json()),
+            None => "{\"ok\":true,\"tree\":null}".into(),
+        }),
+        "highlight" => {
+            let view = match args.next() {
+                Some(arg) => Some(parse_view(Some(arg))?),
+                None => None,
+            };
+            tree.set_highlighted_view(view);
+            Ok("{\"ok\":true}".into())
+        }
+        "set" => {
+            let view = parse_view(args.next())?;
+            let prop = args.next().ok_or("missing property")?;
+            let prop = match prop {
+                "background" => PropOverride::Background(parse_color(&mut args)?),
+                "border-color" => PropOverride::BorderColor(parse_color(&mut args)?),
+                "border-width" => PropOverride::BorderWidth(parse_number(args.next())?),
+                "corner-radius" => PropOverride::CornerRadius(parse_number(args.next())?),
+                "opacity" => PropOverride::Opacity(parse_number(args.next())?),
+                _ => return Err(format!("unknown property {}", json_string(prop))),
+            };
+            if tree.set_prop_override(view, prop) {
+                Ok("{\"ok\":true}".into())
+            } else {
+                Err(format!("no such view {}", view))
+            }
+        }
+        "reset" => {
+            tree.clear_prop_overrides(parse_view(args.next())?);
+            Ok("{\"ok\":true}".into())
+        }
+        _ => Err(format!("unknown command {}", json_string(command))),
+    }
+}
+
+fn parse_view(arg: Option<&str>) -> Result<ViewId, String> {
+    let arg = arg.ok_or("missing view ID")?;
+    arg.parse()
+        .map_err(|_| format!("invalid view ID {}", json_string(arg)))
+}
+
+fn parse_number(arg: Option<&str>) -> Result<f64, String> {
+    let arg = arg.ok_or("missing number")?;
+    arg.parse()
+        .map_err(|_| format!("invalid number {}", json_string(arg)))
+}
+
+fn parse_color<'a>(args: &mut impl Iterator<Item = &'a str>) -> Result<Color, String> {
+    Ok(Color {
+        r: parse_number(args.next())?,
+        g: parse_number(args.next())?,
+        b: parse_number(args.next())?,
+        a: parse_number(args.next())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{Layer, LayerProps};
+    use crate::nv_tree::Patch;
+    use crate::rect::Rect;
+    use cgmath::{Point2, Vector2};
+    use std::io::BufRead;
+    use std::sync::Arc;
+
+    /// A composite view around a keyed layer.
+    #[derive(Debug, PartialEq)]
+    struct Panel;
+
+    crate::impl_view! {
+        Panel;
+        fn body(&self, _state: &()) {
+            Arc::new(Layer {
+                key: Some(2),
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(10., 10.), Vector2::new(20., 20.)),
+                    ..LayerProps::default()
+                },
+                ..Layer::default()
+            })
+        }
+    }
+
+    /// Renders a layer containing a panel and returns the view tree.
+    fn tree() -> ViewTree {
+        let mut tree = ViewTree::new();
+        tree.render_root(
+            Arc::new(Layer {
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(0., 0.), Vector2::new(100., 50.)),
+                    background: Color::WHITE,
+                    ..LayerProps::default()
+                },
+                subviews: vec![Arc::new(Panel)],
+                ..Layer::default()
+            }),
+            (),
+        );
+        tree.patches().for_each(drop);
+        tree
+    }
+
+    /// Drains the patches and returns the updated native views.
+    fn updates(tree: &mut ViewTree) -> HashMap<ViewId, NativeView> {
+        tree.patches()
+            .filter_map(|patch| match patch {
+                Patch::Update(id, view) => Some((id, view)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the layer properties of an updated native view.
+    fn props(views: &HashMap<ViewId, NativeView>, id: ViewId) -> &LayerProps {
+        match &views[&id] {
+            NativeView::Layer(props) => props,
+            _ => panic!("not a layer"),
+        }
+    }
+
+    fn json(response: &str) -> serde_json::Value {
+        serde_json::from_str(response).expect("invalid JSON")
+    }
+
+    #[test]
+    fn inspecting_reports_the_tree_structure() {
+        let tree = tree();
+        let root = tree.inspect().unwrap();
+        assert!(root.type_name.ends_with("Layer"));
+        assert_eq!(root.key, None);
+        assert_eq!(root.native_view.as_ref().unwrap().bounds().size.x, 100.);
+        assert_eq!(root.subviews.len(), 1);
+
+        let panel = &root.subviews[0];
+        assert!(panel.type_name.ends_with("Panel"));
+        assert!(panel.native_view.is_none());
+        assert_eq!(panel.subviews.len(), 1);
+
+        let layer = &panel.subviews[0];
+        assert_eq!(layer.key, Some(2));
+        assert!(layer.native_view.is_some());
+        assert!(layer.subviews.is_empty());
+
+        let json = json(&root.to_json());
+        assert_eq!(json["id"], root.id.to_string());
+        assert_eq!(json["native"]["bounds"], serde_json::json!([0, 0, 100, 50]));
+        assert_eq!(
+            json["native"]["background"],
+            serde_json::json!([1, 1, 1, 1])
+        );
+        assert_eq!(json["subviews"][0]["native"], serde_json::Value::Null);
+        assert_eq!(json["subviews"][0]["subviews"][0]["key"], 2);
+        assert_eq!(
+            json["subviews"][0]["subviews"][0]["id"],
+            layer.id.to_string()
+        );
+
+        assert!(ViewTree::<()>::new().inspect().is_none());
+    }
+
+    #[test]
+    fn view_ids_round_trip_through_strings() {
+        let id = ViewId::new();
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert_eq!(id.to_string().len(), 36);
+        assert!("not-a-view".parse::<ViewId>().is_err());
+    }
+
+    #[test]
+    fn commands_override_and_highlight_views() {
+        let mut tree = tree();
+        let root = tree.inspect().unwrap();
+        let panel = root.subviews[0].id;
+        let layer = root.subviews[0].subviews[0].id;
+        let command = |tree: &mut ViewTree, line: &str| {
+            json(&handle_command(tree, line).unwrap_or_else(|err| err))
+        };
+
+        // overrides apply to the native views of composite views
+        let response = command(&mut tree, &format!("set {} background 1 0 0 0.5", panel));
+        assert_eq!(response["ok"], true);
+        let views = updates(&mut tree);
+        assert_eq!(
+            props(&views, layer).background,
+            Color::rgba(1., 0., 0., 0.5)
+        );
+        assert_eq!(props(&views, root.id).background, Color::WHITE);
+
+        command(&mut tree, &format!("set {} opacity 0.25", layer));
+        command(&mut tree, &format!("highlight {}", root.id));
+        let views = updates(&mut tree);
+        let layer_props = props(&views, layer);
+        assert_eq!(layer_props.background, Color::rgba(1., 0., 0., 0.5));
+        assert_eq!(layer_props.opacity, 0.25);
+        assert!(layer_props.border.is_none());
+        assert_eq!(
+            props(&views, root.id).border,
+            Some((HIGHLIGHT_WIDTH, HIGHLIGHT_COLOR))
+        );
+
+        command(&mut tree, &format!("reset {}", panel));
+        command(&mut tree, "highlight");
+        let views = updates(&mut tree);
+        let layer_props = props(&views, layer);
+        assert_eq!(layer_props.background, Color::default());
+        assert_eq!(layer_props.opacity, 1.);
+        assert!(props(&views, root.id).border.is_none());
+    }
+
+    #[test]
+    fn invalid_commands_are_errors() {
+        let mut tree = tree();
+        let root = tree.inspect().unwrap().id;
+        let missing = ViewId::new();
+        for (line, error) in &[
+            (String::new(), "unknown command \"\"".to_string()),
+            ("frobnicate".into(), "unknown command \"frobnicate\"".into()),
+            ("set".into(), "missing view ID".into()),
+            (
+                "set nonsense opacity 1".into(),
+                "invalid view ID \"nonsense\"".into(),
+            ),
+            (format!("set {}", root), "missing property".into()),
+            (format!("set {} opacity", root), "missing number".into()),
+            (
+                format!("set {} opacity lots", root),
+                "invalid number \"lots\"".into(),
+            ),
+            (
+                format!("set {} background 1 0 0", root),
+                "missing number".into(),
+            ),
+            (
+                format!("set {} shadow 1", root),
+                "unknown property \"shadow\"".into(),
+            ),
+            (
+                format!("set {} opacity 1", missing),
+                format!("no such view {}", missing),
+            ),
+            (
+                "highlight nonsense".into(),
+                "invalid view ID \"nonsense\"".into(),
+            ),
+            ("reset".into(), "missing view ID".into()),
+        ] {
+            assert_eq!(
+                handle_command(&mut tree, line).as_ref(),
+                Err(error),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(tree.patches().count(), 0);
+    }
+
+    #[test]
+    fn the_server_answers_commands() {
+        let mut tree = tree();
+        let mut server = DevtoolsServer::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        client.write_all(b"tree\nnonsense\n").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+
+        let mut reader = io::BufReader::new(client);
+        let mut lines = Vec::new();
+        for _ in 0..500 {
+            server.poll(&mut tree).unwrap();
+            let mut line = String::new();
+            if reader.read_line(&mut line).is_ok() && line.ends_with('\n') {
+                lines.push(json(&line));
+                if lines.len() == 2 {
+                    break;
+                }
+            }
+        }
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["ok"], true);
+        assert_eq!(lines[0]["tree"]["subviews"][0]["subviews"][0]["key"], 2);
+        assert_eq!(lines[1]["ok"], false);
+    }
+
+    #[test]
+    fn frame_recorders_keep_recent_frames() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut recorder = FrameRecorder::new(Duration::from_millis(100), 2);
+        let capture = || Ok::<_, ()>(Some(RgbaImage::new(1, 1)));
+
+        assert_eq!(recorder.tick(at(0), capture), Ok(true));
+        assert_eq!(recorder.tick(at(50), capture), Ok(false));
+        assert_eq!(recorder.tick(at(100), || Ok::<_, ()>(None)), Ok(false));
+        assert_eq!(recorder.tick(at(200), capture), Ok(true));
+        assert_eq!(recorder.tick(at(300), || Err("failed")), Err("failed"));
+        assert_eq!(recorder.tick(at(400), capture), Ok(true));
+
+        let times: Vec<_> = recorder.frames().map(|frame| frame.time).collect();
+        assert_eq!(times, [at(200), at(400)]);
+        assert_eq!(recorder.latest().unwrap().time, at(400));
+        assert_eq!(recorder.drain().len(), 2);
+        assert!(recorder.latest().is_none());
+    }
+}
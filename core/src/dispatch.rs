@@ -0,0 +1,2100 @@
+//! Event dispatch.
+//!
+//! Raw events from the backend are turned into [events](crate::events) and sent to the event
+//! handlers of native views. Pointer, hover, and scroll events target the topmost view under the
+//! pointer, and key, media key, game controller, and [pointing device](PointingDevice) events
+//! target the key view. Events then bubble up through the target’s native ancestors, until a
+//! handler stops propagation.
+//!
+//! A pointer event stream (from [`Began`](PointerEventPhase::Began) to
+//! [`Ended`](PointerEventPhase::Ended) or [`Canceled`](PointerEventPhase::Canceled)) keeps going
+//! to the views that received its first event, except those that declined interest. Each of these
+//! views has a priority, which starts at zero and can be raised by its handler; whenever one view
+//! has a higher priority than the others, the others are sent a `Canceled` event and drop out of
+//! the stream.
+//!
+//! [Taps](Tap) are synthesized from pointer event streams that end close to where they began.
+//!
+//! Scroll deltas are converted to points and adjusted for natural scrolling and shift-scrolling
+//! according to the dispatcher’s [`ScrollSettings`].
+//!
+//! [File drags](FileDrop) target the views under the drag like pointer events. Views are sent
+//! `Entered` and `Exited` as the drag moves over them, and should mark the event as handled if
+//! they accept the files.
+//!
+//! Observers can be added to the dispatcher to see events of any type without a tracking rect,
+//! e.g. for analytics or a global Escape key handler. For each event, [`Before`](ObserverPhase::Before)
+//! observers are called first, in the order they were added, then the views in bubbling order,
+//! and finally [`After`](ObserverPhase::After) observers, in the order they were added. Observers
+//! see each event once; the `Canceled` and `Exited` events sent to individual views as they lose
+//! a pointer stream or file drag aren’t observed.
+
+use crate::backend::Backend;
+use crate::events::{
+    Controller, ControllerInput, DeviceChange, Event, EventHandler, EventHandlers, EventType,
+    FileDrop, Hover, Key, KeyModifiers, Media, Pointer, PointerDevice, PointingDevice, Scroll, Tap,
+};
+use crate::nv_tree::NVTree;
+use crate::raw_events::{
+    ControllerId, DragEventPhase, EventId, PointerEventPhase, PointerId, RawEvent,
+};
+use crate::view::ViewId;
+use crate::view_tree::ViewTree;
+use cgmath::{InnerSpace, Point2, Vector2, Vector3};
+use core::any::Any;
+use core::ops::DerefMut;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The maximum time between pressing and releasing a pointer for it to count as a [tap](Tap).
+pub const TAP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The maximum time between two pointer presses for them to count as consecutive clicks, if the
+/// backend doesn’t count clicks itself.
+pub const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How raw scroll deltas are turned into [scroll events](Scroll), so they follow system
+/// conventions without each view correcting them; see [`EventDispatcher::set_scroll_settings`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollSettings {
+    /// If true, the user prefers natural scrolling, where content moves with the fingers. Deltas
+    /// that the platform didn’t already invert are inverted.
+    pub natural_scrolling: bool,
+
+    /// If true, vertical scrolling with a discrete device while holding shift scrolls
+    /// horizontally instead, unless the platform already converted the delta.
+    pub shift_scrolls_horizontally: bool,
+
+    /// Points scrolled per line for discrete devices (usually one mouse wheel notch).
+    pub line_height: f64,
+
+    /// Multiplies deltas of continuous devices, e.g. for a system scroll speed preference.
+    pub continuous_scale: f64,
+}
+
+impl ScrollSettings {
+    /// Converts a raw scroll delta to points; returns the delta and whether it’s inverted.
+    fn convert(
+        &self,
+        delta: (f64, f64),
+        is_discrete: bool,
+        is_direction_inverted: bool,
+        modifiers: KeyModifiers,
+    ) -> (Vector2<f64>, bool) {
+        let mut delta = Vector2::new(delta.0, delta.1);
+        if is_discrete {
+            if self.shift_scrolls_horizontally && modifiers.shift() && delta.x == 0. {
+                delta = Vector2::new(delta.y, 0.);
+            }
+            delta *= self.line_height;
+        } else {
+            delta *= self.continuous_scale;
+        }
+        if self.natural_scrolling && !is_direction_inverted {
+            (-delta, true)
+        } else {
+            (delta, is_direction_inverted)
+        }
+    }
+}
+
+impl Default for ScrollSettings {
+    fn default() -> ScrollSettings {
+        ScrollSettings {
+            natural_scrolling: false,
+            shift_scrolls_horizontally: true,
+            line_height: 40.,
+            continuous_scale: 1.,
+        }
+    }
+}
+
+/// Passed to event handlers along with the event; lets them influence how events are dispatched.
+///
+/// For pointer events, this also carries the state of the event stream the event belongs to.
+#[derive(Debug)]
+pub struct EventContext {
+    view: Option<ViewId>,
+    stream: Option<(EventId, PointerEventPhase)>,
+    captures: Vec<(PointerId, bool)>,
+    data: Option<Box<dyn Any + Send>>,
+    priority: i32,
+    handled: bool,
+    stop_propagation: bool,
+    declined: bool,
+    canceled: bool,
+}
+
+impl EventContext {
+    /// The view whose event handler is being called, or None if an observer is being called.
+    pub fn view(&self) -> Option<ViewId> {
+        self.view
+    }
+
+    /// The ID of the pointer event stream this event belongs to, if any.
+    pub fn stream_id(&self) -> Option<EventId> {
+        self.stream.map(|(id, _)| id)
+    }
+
+    /// The phase of the pointer event stream this event belongs to, if any.
+    pub fn phase(&self) -> Option<PointerEventPhase> {
+        self.stream.map(|(_, phase)| phase)
+    }
+
+    /// Returns the data this view stored for the event stream, if there is some of type `T`.
+    ///
+    /// Events that aren’t part of a pointer event stream start out without data every time.
+    pub fn data<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.data.as_mut()?.downcast_mut()
+    }
+
+    /// Stores data for this view for the rest of the event stream, replacing any previous data.
+    ///
+    /// This is useful for gesture recognizers, which can keep e.g. the start location here
+    /// instead of in a map keyed by pointer.
+    pub fn set_data<T: Any + Send>(&mut self, data: T) {
+        self.data = Some(Box::new(data));
+    }
+
+    /// This view’s priority in the event stream.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Raises this view’s priority in the event stream. Views with a lower priority will lose the
+    /// stream once this event has been dispatched.
+    ///
+    /// Does nothing if the priority is lower than the current priority.
+    pub fn raise_priority(&mut self, priority: i32) {
+        self.priority = self.priority.max(priority);
+    }
+
+    /// Marks the event as handled, so that it won’t be handled by anything else, such as default
+    /// key commands. Propagation to ancestors continues unless it’s stopped.
+    pub fn set_handled(&mut self) {
+        self.handled = true;
+    }
+
+    /// Returns true if this or an earlier handler marked the event as handled.
+    pub fn is_handled(&self) -> bool {
+        self.handled
+    }
+
+    /// Stops the event from bubbling up to ancestors of this view.
+    ///
+    /// If this is the first event of a pointer stream, the ancestors won’t receive the rest of
+    /// the stream either.
+    ///
+    /// In an observer, this stops the event from reaching the observers added after it; a
+    /// `Before` observer also stops it from reaching any views.
+    pub fn stop_propagation(&mut self) {
+        self.stop_propagation = true;
+    }
+
+    /// Declines interest in the rest of this pointer stream, so that this view won’t receive any
+    /// further events from it.
+    pub fn decline(&mut self) {
+        self.declined = true;
+    }
+
+    /// Cancels the pointer stream: no view will receive any further events from it, and all other
+    /// views receiving it are sent a `Canceled` event.
+    pub fn cancel(&mut self) {
+        self.canceled = true;
+    }
+
+    /// Captures a pointer: all subsequent pointer events from it will be sent to this view only,
+    /// regardless of where the pointer is, until it’s released or the pointer event ends.
+    ///
+    /// Useful for e.g. dragging a slider thumb outside the slider.
+    pub fn capture_pointer(&mut self, pointer: PointerId) {
+        self.captures.push((pointer, true));
+    }
+
+    /// Releases a pointer captured by this view.
+    pub fn release_pointer(&mut self, pointer: PointerId) {
+        self.captures.push((pointer, false));
+    }
+}
+
+/// A view that receives an event (stream).
+#[derive(Debug)]
+struct Recipient {
+    view: ViewId,
+    priority: i32,
+    data: Option<Box<dyn Any + Send>>,
+    /// Whether the view received the last event.
+    received: bool,
+    declined: bool,
+}
+
+impl Recipient {
+    fn new(view: ViewId) -> Recipient {
+        Recipient {
+            view,
+            priority: 0,
+            data: None,
+            received: false,
+            declined: false,
+        }
+    }
+}
+
+/// The outcome of sending an event to a list of views.
+struct Dispatched {
+    handled: bool,
+    /// The view that canceled the event stream.
+    canceled_by: Option<ViewId>,
+    /// Whether an observer stopped the event before it reached any views.
+    intercepted: bool,
+}
+
+/// Identifies an observer added to an [`EventDispatcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+/// When an observer sees events, relative to views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObserverPhase {
+    /// Before any view; the observer may stop the event from reaching views.
+    Before,
+    /// After all views, even if a view stopped propagation.
+    After,
+}
+
+/// An observer added to an [`EventDispatcher`].
+#[derive(Debug)]
+struct Observer {
+    id: ObserverId,
+    phase: ObserverPhase,
+    handlers: EventHandlers,
+}
+
+/// A pointer event stream that may turn out to be a tap.
+#[derive(Debug)]
+struct PendingTap {
+    /// Views with a tap handler that were under the pointer when it was pressed, in bubbling
+    /// order.
+    views: Vec<ViewId>,
+    /// Window location where the pointer was pressed.
+    origin: Point2<f64>,
+    start: Instant,
+}
+
+/// The last pointer press, for counting clicks.
+#[derive(Debug)]
+struct LastClick {
+    pointer: PointerId,
+    device: PointerDevice,
+    location: Point2<f64>,
+    time: Instant,
+    count: u32,
+}
+
+/// Dispatches raw events from the backend to views.
+#[derive(Debug, Default)]
+pub struct EventDispatcher {
+    /// Views that have captured a pointer.
+    captures: HashMap<PointerId, ViewId>,
+    /// Views that are receiving a pointer stream.
+    streams: HashMap<EventId, Vec<Recipient>>,
+    /// Pointer streams that may turn out to be taps.
+    taps: HashMap<EventId, PendingTap>,
+    /// Views under the current file drag.
+    drag_views: Vec<ViewId>,
+    /// Click counts of pointer streams.
+    click_counts: HashMap<EventId, u32>,
+    last_click: Option<LastClick>,
+    key_view: Option<ViewId>,
+    observers: Vec<Observer>,
+    next_observer_id: u64,
+    scroll_settings: ScrollSettings,
+    /// The pointing device that was used most recently.
+    active_device: Option<(PointerDevice, PointerId)>,
+}
+
+impl EventDispatcher {
+    pub fn new() -> EventDispatcher {
+        EventDispatcher::default()
+    }
+
+    /// The view that receives key events.
+    pub fn key_view(&self) -> Option<ViewId> {
+        self.key_view
+    }
+
+    /// Sets the view that receives key events; usually the focused view (see
+    /// [`FocusManager`](crate::focus::FocusManager)).
+    ///
+    /// If the view isn’t a native view, its topmost native view will receive key events.
+    pub fn set_key_view(&mut self, view: Option<ViewId>) {
+        self.key_view = view;
+    }
+
+    /// How raw scroll deltas are converted.
+    pub fn scroll_settings(&self) -> ScrollSettings {
+        self.scroll_settings
+    }
+
+    /// Sets how raw scroll deltas are converted, e.g. when the backend reports that the user
+    /// changed their scrolling preferences.
+    pub fn set_scroll_settings(&mut self, settings: ScrollSettings) {
+        self.scroll_settings = settings;
+    }
+
+    /// Adds an observer that will see all events for which it has a handler, in the given phase.
+    ///
+    /// Observers are called with no view and with window coordinates as the event location. They
+    /// can mark events as handled and stop propagation, but requests that only concern views,
+    /// such as raising priority, capturing pointers, declining, or canceling, are ignored.
+    pub fn add_observer(&mut self, phase: ObserverPhase, handlers: EventHandlers) -> ObserverId {
+        let id = ObserverId(self.next_observer_id);
+        self.next_observer_id += 1;
+        self.observers.push(Observer {
+            id,
+            phase,
+            handlers,
+        });
+        id
+    }
+
+    /// Removes an observer. Does nothing if it was already removed.
+    pub fn remove_observer(&mut self, id: ObserverId) {
+        self.observers.retain(|observer| observer.id != id);
+    }
+
+    /// Updates state after the view tree has changed, forgetting views that no longer exist.
+    ///
+    /// Pointer streams whose views were all removed are kept around without recipients until they
+    /// end, like canceled streams.
+    pub fn update<Ctx>(&mut self, views: &ViewTree<Ctx>)
+    where
+        Ctx: 'static + Clone + Send,
+    {
+        self.captures.retain(|_, view| views.contains(*view));
+        for recipients in self.streams.values_mut() {
+            recipients.retain(|recipient| views.contains(recipient.view));
+        }
+        for tap in self.taps.values_mut() {
+            tap.views.retain(|view| views.contains(*view));
+        }
+        self.drag_views.retain(|view| views.contains(*view));
+        if self.key_view.is_some_and(|view| !views.contains(view)) {
+            self.key_view = None;
+        }
+    }
+
+    /// The pointing device that most recently entered proximity or generated hover or pointer
+    /// events, and its unique ID.
+    pub fn active_device(&self) -> Option<(PointerDevice, PointerId)> {
+        self.active_device
+    }
+
+    /// Returns the view that has captured a pointer, if any.
+    pub fn captured_view(&self, pointer: PointerId) -> Option<ViewId> {
+        self.captures.get(&pointer).copied()
+    }
+
+    /// Dispatches a raw event that arrived at `now`, which is used to time [taps](Tap) and count
+    /// clicks.
+    ///
+    /// Returns true if a handler marked the event as handled. Raw events that aren’t meant for
+    /// views (such as root size changes) are ignored.
+    pub fn dispatch<Ctx, B, Bknd>(
+        &mut self,
+        event: &RawEvent,
+        now: Instant,
+        views: &ViewTree<Ctx>,
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+    ) -> bool
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        match *event {
+            RawEvent::Hover {
+                device,
+                root_location,
+                tilt,
+                unique_id,
+                phase,
+                modifiers,
+                ..
+            } => {
+                self.activate_device(device, unique_id, views, native_views);
+                let window_location = root_location.into();
+                let target = native_views.hit_test(window_location);
+                let mut recipients = Self::bubble_path(native_views, target);
+                self.dispatch_to(
+                    &mut recipients,
+                    None,
+                    views,
+                    native_views,
+                    Some(window_location),
+                    |handlers| handlers.hover.clone(),
+                    |_, location| Hover {
+                        id: unique_id,
+                        phase,
+                        location,
+                        window_location,
+                        tilt: Vector3::new(tilt.0, tilt.1, tilt.2),
+                        device,
+                        modifiers,
+                    },
+                    true,
+                )
+                .handled
+            }
+            RawEvent::Pointer {
+                device,
+                root_location,
+                pressure,
+                tilt,
+                event_id,
+                unique_id,
+                phase,
+                modifiers,
+                click_count,
+            } => {
+                self.activate_device(device, unique_id, views, native_views);
+                let window_location = root_location.into();
+                let click_count = if phase == PointerEventPhase::Began {
+                    let count = match click_count {
+                        0 => self.count_click(unique_id, device, window_location, now),
+                        count => count,
+                    };
+                    self.click_counts.insert(event_id, count);
+                    count
+                } else {
+                    match click_count {
+                        0 => self.click_counts.get(&event_id).copied().unwrap_or(1),
+                        count => count,
+                    }
+                };
+                let pointer = |location, phase| Pointer {
+                    id: unique_id,
+                    phase,
+                    location,
+                    window_location,
+                    pressure,
+                    tilt: Vector3::new(tilt.0, tilt.1, tilt.2),
+                    device,
+                    modifiers,
+                    click_count,
+                };
+
+                let stream = match phase {
+                    PointerEventPhase::Began => None,
+                    _ => self.streams.remove(&event_id),
+                };
+                let began = stream.is_none();
+                let mut recipients = match stream {
+                    Some(recipients) => recipients,
+                    None => {
+                        self.captures.remove(&unique_id);
+                        let target = native_views.hit_test(window_location);
+                        Self::bubble_path(native_views, target)
+                    }
+                };
+                recipients.retain(|recipient| native_views.contains(recipient.view));
+
+                if phase == PointerEventPhase::Began {
+                    let tap_views: Vec<_> = recipients
+                        .iter()
+                        .map(|r| r.view)
+                        .filter(|view| {
+                            views
+                                .event_handlers(*view)
+                                .is_some_and(|handlers| handlers.tap.is_some())
+                        })
+                        .collect();
+                    if !tap_views.is_empty() {
+                        self.taps.insert(
+                            event_id,
+                            PendingTap {
+                                views: tap_views,
+                                origin: window_location,
+                                start: now,
+                            },
+                        );
+                    }
+                }
+
+                let captured = self
+                    .captured_view(unique_id)
+                    .filter(|view| native_views.contains(*view));
+                let targets = match captured {
+                    Some(view) => {
+                        let index = match recipients.iter().position(|r| r.view == view) {
+                            Some(index) => index,
+                            None => {
+                                recipients.push(Recipient::new(view));
+                                recipients.len() - 1
+                            }
+                        };
+                        &mut recipients[index..=index]
+                    }
+                    None => &mut recipients[..],
+                };
+                let dispatched = self.dispatch_to(
+                    targets,
+                    Some((event_id, phase)),
+                    views,
+                    native_views,
+                    Some(window_location),
+                    |handlers| handlers.pointer.clone(),
+                    |_, location| pointer(location, phase),
+                    true,
+                );
+
+                // views that didn’t receive the first event don’t get the rest of the stream
+                recipients.retain(|r| !r.declined && (r.received || !began));
+                let lost_stream = match dispatched.canceled_by {
+                    Some(view) => {
+                        let mut lost = std::mem::take(&mut recipients);
+                        lost.retain(|r| r.view != view);
+                        lost
+                    }
+                    None => {
+                        let max_priority = recipients.iter().map(|r| r.priority).max();
+                        let (keep, lose) = recipients
+                            .into_iter()
+                            .partition(|r| Some(r.priority) == max_priority);
+                        recipients = keep;
+                        lose
+                    }
+                };
+                let ended =
+                    phase == PointerEventPhase::Ended || phase == PointerEventPhase::Canceled;
+                if !ended {
+                    for mut recipient in lost_stream {
+                        self.dispatch_to(
+                            std::slice::from_mut(&mut recipient),
+                            Some((event_id, PointerEventPhase::Canceled)),
+                            views,
+                            native_views,
+                            Some(window_location),
+                            |handlers| handlers.pointer.clone(),
+                            |_, location| pointer(location, PointerEventPhase::Canceled),
+                            false,
+                        );
+                    }
+                }
+
+                if ended {
+                    self.captures.remove(&unique_id);
+                    self.click_counts.remove(&event_id);
+                } else {
+                    // a canceled stream is kept around without recipients until it ends
+                    self.streams.insert(event_id, recipients);
+                }
+
+                let mut handled = dispatched.handled;
+                match self.taps.remove(&event_id) {
+                    Some(tap)
+                        if (window_location - tap.origin).magnitude() > device.tap_slop()
+                            || dispatched.canceled_by.is_some()
+                            || dispatched.intercepted =>
+                    {
+                        // no longer a tap
+                    }
+                    Some(tap)
+                        if phase == PointerEventPhase::Ended
+                            && now.duration_since(tap.start) <= TAP_TIMEOUT =>
+                    {
+                        let mut recipients: Vec<_> = tap
+                            .views
+                            .into_iter()
+                            .filter(|view| native_views.contains(*view))
+                            .map(Recipient::new)
+                            .collect();
+                        let tap_handled = self
+                            .dispatch_to(
+                                &mut recipients,
+                                None,
+                                views,
+                                native_views,
+                                Some(window_location),
+                                |handlers| handlers.tap.clone(),
+                                |_, location| Tap {
+                                    id: unique_id,
+                                    location,
+                                    window_location,
+                                    device,
+                                    modifiers,
+                                    click_count,
+                                },
+                                true,
+                            )
+                            .handled;
+                        handled |= tap_handled;
+                    }
+                    Some(tap) if !ended => {
+                        self.taps.insert(event_id, tap);
+                    }
+                    _ => {}
+                }
+                handled
+            }
+            RawEvent::Key {
+                ref chars,
+                ref chars_without_mod,
+                key_code,
+                phase,
+                modifiers,
+            } => {
+                let mut recipients = self.key_path(views, native_views);
+                self.dispatch_to(
+                    &mut recipients,
+                    None,
+                    views,
+                    native_views,
+                    None,
+                    |handlers| handlers.key.clone(),
+                    |_, _| Key {
+                        modifiers,
+                        code: key_code,
+                        phase,
+                        chars: chars.clone(),
+                        chars_ignoring_modifiers: chars_without_mod.clone(),
+                    },
+                    true,
+                )
+                .handled
+            }
+            RawEvent::Scroll {
+                root_location,
+                delta,
+                is_discrete,
+                is_direction_inverted,
+                modifiers,
+            } => {
+                let (delta, is_direction_inverted) = self.scroll_settings.convert(
+                    delta,
+                    is_discrete,
+                    is_direction_inverted,
+                    modifiers,
+                );
+                let window_location = root_location.into();
+                let target = native_views.hit_test(window_location);
+                let mut recipients = Self::bubble_path(native_views, target);
+                self.dispatch_to(
+                    &mut recipients,
+                    None,
+                    views,
+                    native_views,
+                    Some(window_location),
+                    |handlers| handlers.scroll.clone(),
+                    |_, location| Scroll {
+                        location,
+                        window_location,
+                        delta,
+                        is_discrete,
+                        is_direction_inverted,
+                        modifiers,
+                    },
+                    true,
+                )
+                .handled
+            }
+            RawEvent::FileDrag {
+                root_location,
+                phase,
+                ref paths,
+            } => {
+                let paths: Arc<[PathBuf]> = paths.clone().into();
+                self.dispatch_file_drag(root_location.into(), phase, paths, views, native_views)
+            }
+            RawEvent::MediaKey { key, phase } => {
+                let mut recipients = self.key_path(views, native_views);
+                self.dispatch_to(
+                    &mut recipients,
+                    None,
+                    views,
+                    native_views,
+                    None,
+                    |handlers| handlers.media.clone(),
+                    |_, _| Media { key, phase },
+                    true,
+                )
+                .handled
+            }
+            RawEvent::ControllerConnection {
+                controller,
+                is_connected,
+            } => {
+                let input = if is_connected {
+                    ControllerInput::Connected
+                } else {
+                    ControllerInput::Disconnected
+                };
+                self.dispatch_controller(controller, input, views, native_views)
+            }
+            RawEvent::ControllerButton {
+                controller,
+                button,
+                value,
+                is_pressed,
+            } => {
+                let input = ControllerInput::Button {
+                    button,
+                    value,
+                    is_pressed,
+                };
+                self.dispatch_controller(controller, input, views, native_views)
+            }
+            RawEvent::ControllerAxis {
+                controller,
+                axis,
+                value,
+            } => {
+                let input = ControllerInput::Axis { axis, value };
+                self.dispatch_controller(controller, input, views, native_views)
+            }
+            RawEvent::Proximity {
+                device,
+                unique_id,
+                is_entering,
+            } => {
+                let change = if is_entering {
+                    DeviceChange::EnteredProximity
+                } else {
+                    DeviceChange::LeftProximity
+                };
+                let handled =
+                    self.dispatch_pointing_device(device, unique_id, change, views, native_views);
+                let activated =
+                    is_entering && self.activate_device(device, unique_id, views, native_views);
+                handled || activated
+            }
+            RawEvent::SetRootSize { .. }
+            | RawEvent::SetAccessibility { .. }
+            | RawEvent::SetVisibility { .. }
+            | RawEvent::SetScaleFactor { .. } => false,
+        }
+    }
+
+    /// Sends a file drag event to the views under the drag, and `Exited` to views that are no
+    /// longer under it.
+    ///
+    /// Returns true if a view accepted the files.
+    fn dispatch_file_drag<Ctx, B, Bknd>(
+        &mut self,
+        window_location: Point2<f64>,
+        phase: DragEventPhase,
+        paths: Arc<[PathBuf]>,
+        views: &ViewTree<Ctx>,
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+    ) -> bool
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        let mut recipients = match phase {
+            DragEventPhase::Exited => Vec::new(),
+            _ => {
+                let target = native_views.hit_test(window_location);
+                Self::bubble_path(native_views, target)
+            }
+        };
+        let prev_views = std::mem::take(&mut self.drag_views);
+        if phase == DragEventPhase::Entered || phase == DragEventPhase::Moved {
+            self.drag_views = recipients.iter().map(|r| r.view).collect();
+        }
+
+        let file_drop = |view_phase| {
+            let paths = Arc::clone(&paths);
+            move |_, location| FileDrop {
+                phase: view_phase,
+                location,
+                window_location,
+                paths: Arc::clone(&paths),
+            }
+        };
+
+        let mut exited: Vec<_> = prev_views
+            .iter()
+            .copied()
+            .filter(|view| native_views.contains(*view))
+            .filter(|view| !recipients.iter().any(|r| r.view == *view))
+            .map(Recipient::new)
+            .collect();
+        for recipient in &mut exited {
+            self.dispatch_to(
+                std::slice::from_mut(recipient),
+                None,
+                views,
+                native_views,
+                Some(window_location),
+                |handlers| handlers.file_drop.clone(),
+                file_drop(DragEventPhase::Exited),
+                false,
+            );
+        }
+
+        let entered = file_drop(DragEventPhase::Entered);
+        let moved = file_drop(phase);
+        self.dispatch_to(
+            &mut recipients,
+            None,
+            views,
+            native_views,
+            Some(window_location),
+            |handlers| handlers.file_drop.clone(),
+            |view, location| {
+                let is_new = view.is_some_and(|view| !prev_views.contains(&view));
+                if phase == DragEventPhase::Moved && is_new {
+                    entered(view, location)
+                } else {
+                    moved(view, location)
+                }
+            },
+            true,
+        )
+        .handled
+    }
+
+    /// Sends a game controller event to the key view.
+    fn dispatch_controller<Ctx, B, Bknd>(
+        &mut self,
+        controller: ControllerId,
+        input: ControllerInput,
+        views: &ViewTree<Ctx>,
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+    ) -> bool
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        let mut recipients = self.key_path(views, native_views);
+        self.dispatch_to(
+            &mut recipients,
+            None,
+            views,
+            native_views,
+            None,
+            |handlers| handlers.controller.clone(),
+            |_, _| Controller { controller, input },
+            true,
+        )
+        .handled
+    }
+
+    /// Makes a device the active pointing device, and sends an event to the key view if it wasn’t
+    /// before.
+    ///
+    /// Returns true if a handler marked the event as handled.
+    fn activate_device<Ctx, B, Bknd>(
+        &mut self,
+        device: PointerDevice,
+        unique_id: PointerId,
+        views: &ViewTree<Ctx>,
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+    ) -> bool
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        if self.active_device == Some((device, unique_id)) {
+            return false;
+        }
+        self.active_device = Some((device, unique_id));
+        let change = DeviceChange::Activated;
+        self.dispatch_pointing_device(device, unique_id, change, views, native_views)
+    }
+
+    /// Sends a pointing device event to the key view.
+    fn dispatch_pointing_device<Ctx, B, Bknd>(
+        &mut self,
+        device: PointerDevice,
+        unique_id: PointerId,
+        change: DeviceChange,
+        views: &ViewTree<Ctx>,
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+    ) -> bool
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        let mut recipients = self.key_path(views, native_views);
+        self.dispatch_to(
+            &mut recipients,
+            None,
+            views,
+            native_views,
+            None,
+            |handlers| handlers.pointing_device.clone(),
+            |_, _| PointingDevice {
+                device,
+                unique_id,
+                change,
+            },
+            true,
+        )
+        .handled
+    }
+
+    /// Returns recipients for the key view’s topmost native view and all of its native
+    /// ancestors.
+    fn key_path<Ctx, B, Bknd>(
+        &self,
+        views: &ViewTree<Ctx>,
+        native_views: &NVTree<B, Bknd::ViewRef>,
+    ) -> Vec<Recipient>
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        let target = self
+            .key_view
+            .filter(|view| views.contains(*view))
+            .and_then(|view| views.native_roots(view).first().copied());
+        Self::bubble_path(native_views, target)
+    }
+
+    /// Counts a pointer press at `now`, returning the number of consecutive clicks it’s part of.
+    fn count_click(
+        &mut self,
+        pointer: PointerId,
+        device: PointerDevice,
+        location: Point2<f64>,
+        now: Instant,
+    ) -> u32 {
+        let count = match &self.last_click {
+            Some(last)
+                if last.pointer == pointer
+                    && last.device == device
+                    && now.duration_since(last.time) <= DOUBLE_CLICK_INTERVAL
+                    && (location - last.location).magnitude() <= device.tap_slop() =>
+            {
+                last.count + 1
+            }
+            _ => 1,
+        };
+        self.last_click = Some(LastClick {
+            pointer,
+            device,
+            location,
+            time: now,
+            count,
+        });
+        count
+    }
+
+    /// Returns recipients for the target view followed by all of its native ancestors.
+    fn bubble_path<B, Bknd>(
+        native_views: &NVTree<B, Bknd::ViewRef>,
+        target: Option<ViewId>,
+    ) -> Vec<Recipient>
+    where
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        let mut path = Vec::new();
+        let mut current = target;
+        while let Some(view) = current {
+            path.push(Recipient::new(view));
+            current = native_views.superview(view);
+        }
+        path
+    }
+
+    /// Sends an event to each recipient in order, until propagation is stopped or the stream is
+    /// canceled.
+    ///
+    /// - `stream`: the pointer event stream the event belongs to, if any
+    /// - `window_location`: the event location, if it has one
+    /// - `handler`: returns the relevant handler from a view’s handlers
+    /// - `event`: creates the event, given the receiving view (None for observers) and the
+    ///   location in its superview
+    /// - `observed`: whether observers should see the event
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch_to<Ctx, B, Bknd, T, H, E>(
+        &mut self,
+        recipients: &mut [Recipient],
+        stream: Option<(EventId, PointerEventPhase)>,
+        views: &ViewTree<Ctx>,
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+        window_location: Option<Point2<f64>>,
+        handler: H,
+        event: E,
+        observed: bool,
+    ) -> Dispatched
+    where
+        Ctx: 'static + Clone + Send,
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+        T: EventType,
+        H: Fn(&EventHandlers) -> Option<EventHandler<T>>,
+        E: Fn(Option<ViewId>, Point2<f64>) -> T,
+    {
+        let mut dispatched = Dispatched {
+            handled: false,
+            canceled_by: None,
+            intercepted: false,
+        };
+        for recipient in recipients.iter_mut() {
+            recipient.received = false;
+        }
+        if observed {
+            let (handled, stopped) = self.observe(
+                ObserverPhase::Before,
+                stream,
+                window_location,
+                &handler,
+                &event,
+                false,
+            );
+            dispatched.handled = handled;
+            dispatched.intercepted = stopped;
+        }
+        let recipients = if dispatched.intercepted {
+            &mut []
+        } else {
+            recipients
+        };
+        for recipient in recipients.iter_mut() {
+            let view = recipient.view;
+            let handler = match views.event_handlers(view).and_then(|h| handler(&h)) {
+                Some(handler) => handler,
+                None => continue,
+            };
+            let location = match window_location {
+                Some(point) => Self::location_in_superview(native_views, view, point),
+                None => Point2::new(0., 0.),
+            };
+            let context = self.call(
+                recipient,
+                stream,
+                &handler,
+                event(Some(view), location),
+                dispatched.handled,
+            );
+            dispatched.handled = context.handled;
+            if context.canceled {
+                dispatched.canceled_by = Some(view);
+                break;
+            }
+            if context.stop_propagation {
+                break;
+            }
+        }
+        if observed {
+            dispatched.handled = self
+                .observe(
+                    ObserverPhase::After,
+                    stream,
+                    window_location,
+                    &handler,
+                    &event,
+                    dispatched.handled,
+                )
+                .0;
+        }
+        dispatched
+    }
+
+    /// Sends an event to the observers in the given phase, in the order they were added, until
+    /// one stops propagation.
+    ///
+    /// Returns whether the event is handled and whether propagation was stopped.
+    fn observe<T, H, E>(
+        &self,
+        phase: ObserverPhase,
+        stream: Option<(EventId, PointerEventPhase)>,
+        window_location: Option<Point2<f64>>,
+        handler: &H,
+        event: &E,
+        mut handled: bool,
+    ) -> (bool, bool)
+    where
+        T: EventType,
+        H: Fn(&EventHandlers) -> Option<EventHandler<T>>,
+        E: Fn(Option<ViewId>, Point2<f64>) -> T,
+    {
+        let location = window_location.unwrap_or_else(|| Point2::new(0., 0.));
+        let handlers: Vec<_> = self
+            .observers
+            .iter()
+            .filter(|observer| observer.phase == phase)
+            .filter_map(|observer| handler(&observer.handlers))
+            .collect();
+        for handler in handlers {
+            let mut context = EventContext {
+                view: None,
+                stream,
+                captures: Vec::new(),
+                data: None,
+                priority: 0,
+                handled,
+                stop_propagation: false,
+                declined: false,
+                canceled: false,
+            };
+            handler.call(Event::new(event(None, location)), &mut context);
+            handled = context.handled;
+            if context.stop_propagation {
+                return (handled, true);
+            }
+        }
+        (handled, false)
+    }
+
+    /// Converts a point in window coordinates to the coordinate system of the view’s superview.
+    fn location_in_superview<B, Bknd>(
+        native_views: &mut NVTree<B, Bknd::ViewRef>,
+        view: ViewId,
+        point: Point2<f64>,
+    ) -> Point2<f64>
+    where
+        B: DerefMut<Target = Bknd>,
+        Bknd: Backend,
+    {
+        match native_views.superview(view) {
+            Some(superview) => native_views
+                .convert_from_window(superview, point)
+                .unwrap_or(point),
+            None => point,
+        }
+    }
+
+    /// Calls an event handler and applies the requests it made through the context.
+    fn call<T: EventType>(
+        &mut self,
+        recipient: &mut Recipient,
+        stream: Option<(EventId, PointerEventPhase)>,
+        handler: &EventHandler<T>,
+        event: T,
+        handled: bool,
+    ) -> EventContext {
+        let view = recipient.view;
+        let mut context = EventContext {
+            view: Some(view),
+            stream,
+            captures: Vec::new(),
+            data: recipient.data.take(),
+            priority: recipient.priority,
+            handled,
+            stop_propagation: false,
+            declined: false,
+            canceled: false,
+        };
+        handler.call(Event::new(event), &mut context);
+
+        recipient.data = context.data.take();
+        recipient.priority = context.priority;
+        recipient.received = true;
+        recipient.declined |= context.declined;
+        for (pointer, capture) in context.captures.drain(..) {
+            if capture {
+                self.captures.insert(pointer, view);
+            } else if self.captures.get(&pointer) == Some(&view) {
+                self.captures.remove(&pointer);
+            }
+        }
+        context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::NullBackend;
+    use crate::raw_events::HoverEventPhase;
+    use crate::{Layer, LayerProps, Rect, View};
+    use parking_lot::Mutex;
+
+    /// Records the events that handlers received, in order.
+    #[derive(Debug, Clone, Default)]
+    struct Log(Arc<Mutex<Vec<String>>>);
+
+    impl Log {
+        fn push(&self, entry: String) {
+            self.0.lock().push(entry);
+        }
+
+        /// Returns and clears the entries so far.
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.0.lock())
+        }
+    }
+
+    /// Returns a pointer handler that logs the view name and the event phase before calling `f`.
+    fn on_pointer<F>(log: &Log, name: &'static str, mut f: F) -> Option<EventHandler<Pointer>>
+    where
+        F: 'static + FnMut(&Pointer, &mut EventContext) + Send,
+    {
+        let log = log.clone();
+        Some(EventHandler::new(move |event: Event<Pointer>, context| {
+            log.push(format!("{} {:?}", name, event.phase()));
+            f(&event, context);
+        }))
+    }
+
+    /// Returns a layer with the given bounds in its superview and no handlers.
+    fn layer(bounds: (f64, f64, f64, f64), subviews: Vec<Arc<dyn View>>) -> Layer {
+        let (x, y, width, height) = bounds;
+        Layer {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(x, y), (width, height).into()),
+                ..LayerProps::default()
+            },
+            subviews,
+            ..Layer::default()
+        }
+    }
+
+    /// A view tree rendered into an NV tree, with a dispatcher and a clock.
+    struct Window {
+        views: ViewTree,
+        native_views: NVTree<Box<NullBackend>, ()>,
+        dispatcher: EventDispatcher,
+        now: Instant,
+    }
+
+    impl Window {
+        fn new(root: Layer) -> Window {
+            let mut views: ViewTree = ViewTree::new();
+            let mut native_views = NVTree::new(Box::new(NullBackend));
+            views.render_root(Arc::new(root), ());
+            native_views.patch_frame(views.patches()).unwrap();
+            Window {
+                views,
+                native_views,
+                dispatcher: EventDispatcher::new(),
+                now: Instant::now(),
+            }
+        }
+
+        fn dispatch(&mut self, event: RawEvent) -> bool {
+            self.dispatcher
+                .dispatch(&event, self.now, &self.views, &mut self.native_views)
+        }
+
+        fn advance(&mut self, millis: u64) {
+            self.now += Duration::from_millis(millis);
+        }
+
+        /// Returns the topmost view at a point.
+        fn view_at(&mut self, x: f64, y: f64) -> ViewId {
+            self.native_views.hit_test(Point2::new(x, y)).unwrap()
+        }
+    }
+
+    /// Returns a pointer event from the mouse (pointer 1) at a window location.
+    fn pointer(event_id: EventId, phase: PointerEventPhase, x: f64, y: f64) -> RawEvent {
+        RawEvent::Pointer {
+            device: PointerDevice::Cursor,
+            root_location: (x, y),
+            pressure: 1.,
+            tilt: (0., 0., 1.),
+            event_id,
+            unique_id: 1,
+            phase,
+            modifiers: KeyModifiers::NONE,
+            click_count: 0,
+        }
+    }
+
+    /// Two side by side views `a` and `b` in a window `root`, all of which log pointer events;
+    /// `a` captures the pointer when pressed.
+    fn capturing_window(log: &Log) -> Window {
+        let mut a = layer((0., 0., 50., 50.), vec![]);
+        a.pointer_action = on_pointer(log, "a", |event, context| {
+            if event.phase() == PointerEventPhase::Began {
+                context.capture_pointer(event.id());
+            }
+        });
+        let mut b = layer((50., 0., 50., 50.), vec![]);
+        b.pointer_action = on_pointer(log, "b", |_, _| ());
+        let mut root = layer((0., 0., 100., 100.), vec![Arc::new(a), Arc::new(b)]);
+        root.pointer_action = on_pointer(log, "root", |_, _| ());
+        Window::new(root)
+    }
+
+    #[test]
+    fn captured_pointers_stay_with_the_view() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = capturing_window(&log);
+        let a = window.view_at(10., 10.);
+
+        window.dispatch(pointer(1, Began, 10., 10.));
+        assert_eq!(log.take(), ["a Began", "root Began"]);
+        assert_eq!(window.dispatcher.captured_view(1), Some(a));
+
+        // moving over b, and out of the window, only reaches a
+        window.dispatch(pointer(1, Moved, 80., 10.));
+        window.dispatch(pointer(1, Moved, 200., 200.));
+        assert_eq!(log.take(), ["a Moved", "a Moved"]);
+        assert_eq!(window.dispatcher.captured_view(1), Some(a));
+
+        window.dispatch(pointer(1, Ended, 80., 10.));
+        assert_eq!(log.take(), ["a Ended"]);
+        assert_eq!(window.dispatcher.captured_view(1), None);
+
+        // the next stream is hit tested again
+        window.dispatch(pointer(2, Began, 80., 10.));
+        assert_eq!(log.take(), ["b Began", "root Began"]);
+        assert_eq!(window.dispatcher.captured_view(1), None);
+    }
+
+    #[test]
+    fn captures_are_released_on_cancel() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = capturing_window(&log);
+        let a = window.view_at(10., 10.);
+
+        window.dispatch(pointer(1, Began, 10., 10.));
+        assert_eq!(window.dispatcher.captured_view(1), Some(a));
+        window.dispatch(pointer(1, Canceled, 80., 10.));
+        assert_eq!(log.take(), ["a Began", "root Began", "a Canceled"]);
+        assert_eq!(window.dispatcher.captured_view(1), None);
+    }
+
+    #[test]
+    fn captures_can_be_released() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut a = layer((0., 0., 50., 50.), vec![]);
+        a.pointer_action = on_pointer(&log, "a", |event, context| match event.phase() {
+            Began => context.capture_pointer(event.id()),
+            _ => context.release_pointer(event.id()),
+        });
+        let mut root = layer((0., 0., 100., 100.), vec![Arc::new(a)]);
+        root.pointer_action = on_pointer(&log, "root", |_, _| ());
+        let mut window = Window::new(root);
+
+        window.dispatch(pointer(1, Began, 10., 10.));
+        window.dispatch(pointer(1, Moved, 60., 10.));
+        assert_eq!(window.dispatcher.captured_view(1), None);
+        // the rest of the stream goes to its recipients again
+        window.dispatch(pointer(1, Moved, 70., 10.));
+        assert_eq!(
+            log.take(),
+            ["a Began", "root Began", "a Moved", "a Moved", "root Moved"]
+        );
+    }
+
+    /// A view `inner` in a window `root`, both of which log pointer events and call their
+    /// handler.
+    fn nested_window<I, R>(log: &Log, inner: I, root: R) -> Window
+    where
+        I: 'static + FnMut(&Pointer, &mut EventContext) + Send,
+        R: 'static + FnMut(&Pointer, &mut EventContext) + Send,
+    {
+        let mut inner_layer = layer((10., 10., 20., 20.), vec![]);
+        inner_layer.pointer_action = on_pointer(log, "inner", inner);
+        let mut root_layer = layer((0., 0., 100., 100.), vec![Arc::new(inner_layer)]);
+        root_layer.pointer_action = on_pointer(log, "root", root);
+        Window::new(root_layer)
+    }
+
+    #[test]
+    fn higher_priority_cancels_other_views() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        // like a scroll view that takes over once the pointer moves
+        let mut window = nested_window(
+            &log,
+            |_, _| (),
+            |event, context| {
+                if event.phase() == Moved {
+                    context.raise_priority(1);
+                }
+            },
+        );
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        assert_eq!(log.take(), ["inner Began", "root Began"]);
+        window.dispatch(pointer(1, Moved, 16., 15.));
+        assert_eq!(log.take(), ["inner Moved", "root Moved", "inner Canceled"]);
+        window.dispatch(pointer(1, Moved, 17., 15.));
+        window.dispatch(pointer(1, Ended, 17., 15.));
+        assert_eq!(log.take(), ["root Moved", "root Ended"]);
+        assert!(window.dispatcher.streams.is_empty());
+    }
+
+    #[test]
+    fn equal_priorities_keep_sharing_the_stream() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let raise = |_: &Pointer, context: &mut EventContext| context.raise_priority(2);
+        let mut window = nested_window(&log, raise, raise);
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        window.dispatch(pointer(1, Ended, 15., 15.));
+        assert_eq!(
+            log.take(),
+            ["inner Began", "root Began", "inner Ended", "root Ended"]
+        );
+    }
+
+    #[test]
+    fn stream_data_is_dropped_when_the_stream_ends() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let counts = Log::default();
+        let count_events = {
+            let counts = counts.clone();
+            move |_: &Pointer, context: &mut EventContext| {
+                let count = context.data::<u32>().map_or(1, |count| *count + 1);
+                counts.push(count.to_string());
+                context.set_data(count);
+            }
+        };
+        let mut window = nested_window(&log, count_events, |_, _| ());
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        window.dispatch(pointer(1, Moved, 16., 15.));
+        window.dispatch(pointer(1, Ended, 16., 15.));
+        assert_eq!(counts.take(), ["1", "2", "3"]);
+        assert!(window.dispatcher.streams.is_empty());
+
+        // a canceled stream drops its data too
+        window.dispatch(pointer(2, Began, 15., 15.));
+        window.dispatch(pointer(2, Canceled, 15., 15.));
+        window.dispatch(pointer(3, Began, 15., 15.));
+        assert_eq!(counts.take(), ["1", "2", "1"]);
+    }
+
+    #[test]
+    fn canceling_in_the_middle_of_a_stream() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = nested_window(
+            &log,
+            |event, context| {
+                if event.phase() == Moved {
+                    context.cancel();
+                }
+            },
+            |_, _| (),
+        );
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        assert_eq!(log.take(), ["inner Began", "root Began"]);
+        // the view that cancels doesn’t get a Canceled event, and the root doesn’t get the move
+        window.dispatch(pointer(1, Moved, 16., 15.));
+        assert_eq!(log.take(), ["inner Moved", "root Canceled"]);
+        // the stream is kept around without recipients until it ends
+        window.dispatch(pointer(1, Moved, 17., 15.));
+        assert!(log.take().is_empty());
+        assert_eq!(window.dispatcher.streams.get(&1).map(Vec::len), Some(0));
+        window.dispatch(pointer(1, Ended, 17., 15.));
+        assert!(log.take().is_empty());
+        assert!(window.dispatcher.streams.is_empty());
+    }
+
+    /// A view `inner` in a window `root` that logs taps.
+    fn tap_window(log: &Log) -> Window {
+        let mut inner = layer((10., 10., 20., 20.), vec![]);
+        inner.tap_action = {
+            let log = log.clone();
+            Some(EventHandler::new(move |event: Event<Tap>, _: &mut _| {
+                log.push(format!("tap {}", event.click_count()));
+            }))
+        };
+        Window::new(layer((0., 0., 100., 100.), vec![Arc::new(inner)]))
+    }
+
+    #[test]
+    fn taps() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = tap_window(&log);
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        window.advance(100);
+        // moving within the slop is fine
+        window.dispatch(pointer(1, Moved, 18., 15.));
+        assert!(log.take().is_empty());
+        window.advance(100);
+        window.dispatch(pointer(1, Ended, 17., 15.));
+        assert_eq!(log.take(), ["tap 1"]);
+        assert!(window.dispatcher.taps.is_empty());
+
+        // releasing outside the view still counts if it’s close enough
+        window.advance(1000);
+        window.dispatch(pointer(2, Began, 28., 15.));
+        window.dispatch(pointer(2, Ended, 31., 15.));
+        assert_eq!(log.take(), ["tap 1"]);
+    }
+
+    #[test]
+    fn moving_too_far_is_not_a_tap() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = tap_window(&log);
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        window.dispatch(pointer(1, Moved, 25., 15.));
+        assert!(window.dispatcher.taps.is_empty());
+        // coming back doesn’t make it a tap again
+        window.dispatch(pointer(1, Moved, 15., 15.));
+        window.dispatch(pointer(1, Ended, 15., 15.));
+        assert!(log.take().is_empty());
+
+        // neither is a canceled stream
+        window.dispatch(pointer(2, Began, 15., 15.));
+        window.dispatch(pointer(2, Canceled, 15., 15.));
+        assert!(log.take().is_empty());
+        assert!(window.dispatcher.taps.is_empty());
+    }
+
+    #[test]
+    fn holding_too_long_is_not_a_tap() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = tap_window(&log);
+
+        window.dispatch(pointer(1, Began, 15., 15.));
+        window.advance(TAP_TIMEOUT.as_millis() as u64 + 1);
+        window.dispatch(pointer(1, Ended, 15., 15.));
+        assert!(log.take().is_empty());
+        assert!(window.dispatcher.taps.is_empty());
+
+        window.dispatch(pointer(2, Began, 15., 15.));
+        window.advance(TAP_TIMEOUT.as_millis() as u64);
+        window.dispatch(pointer(2, Ended, 15., 15.));
+        assert_eq!(log.take().len(), 1);
+    }
+
+    /// Presses and releases the pointer at a location, after waiting some milliseconds.
+    fn click(window: &mut Window, event_id: EventId, wait: u64, x: f64, y: f64) {
+        window.advance(wait);
+        window.dispatch(pointer(event_id, PointerEventPhase::Began, x, y));
+        window.advance(50);
+        window.dispatch(pointer(event_id, PointerEventPhase::Ended, x, y));
+    }
+
+    #[test]
+    fn click_counts() {
+        let log = Log::default();
+        let mut window = tap_window(&log);
+
+        click(&mut window, 1, 0, 15., 15.);
+        click(&mut window, 2, 200, 16., 15.);
+        click(&mut window, 3, 200, 15., 16.);
+        assert_eq!(log.take(), ["tap 1", "tap 2", "tap 3"]);
+
+        // pointer events carry the count for the whole stream
+        let counts = Log::default();
+        let mut inner = layer((10., 10., 20., 20.), vec![]);
+        inner.pointer_action = {
+            let counts = counts.clone();
+            Some(EventHandler::new(
+                move |event: Event<Pointer>, _: &mut _| {
+                    counts.push(event.click_count().to_string());
+                },
+            ))
+        };
+        let mut window = Window::new(layer((0., 0., 100., 100.), vec![Arc::new(inner)]));
+        click(&mut window, 1, 0, 15., 15.);
+        click(&mut window, 2, 200, 15., 15.);
+        assert_eq!(counts.take(), ["1", "1", "2", "2"]);
+    }
+
+    #[test]
+    fn click_counts_reset() {
+        let log = Log::default();
+        let mut window = tap_window(&log);
+        let interval = DOUBLE_CLICK_INTERVAL.as_millis() as u64;
+
+        // the interval is measured between presses
+        click(&mut window, 1, 0, 15., 15.);
+        click(&mut window, 2, interval - 50, 15., 15.);
+        click(&mut window, 3, interval - 50 + 1, 15., 15.);
+        assert_eq!(log.take(), ["tap 1", "tap 2", "tap 1"]);
+
+        // moving beyond the slop starts over
+        click(&mut window, 4, 100, 15., 15.);
+        click(&mut window, 5, 100, 20., 15.);
+        click(&mut window, 6, 100, 20., 15.);
+        assert_eq!(log.take(), ["tap 2", "tap 1", "tap 2"]);
+
+        // counts from the backend are used as is
+        window.advance(1000);
+        let mut event = pointer(7, PointerEventPhase::Began, 15., 15.);
+        if let RawEvent::Pointer { click_count, .. } = &mut event {
+            *click_count = 3;
+        }
+        window.dispatch(event);
+        window.dispatch(pointer(7, PointerEventPhase::Ended, 15., 15.));
+        assert_eq!(log.take(), ["tap 3"]);
+    }
+
+    /// Returns a view `leaf` in a view `mid` in a window `root`, all of which log pointer events
+    /// and call their handler.
+    fn three_level_window<L, M, R>(log: &Log, leaf: L, mid: M, root: R) -> Window
+    where
+        L: 'static + FnMut(&Pointer, &mut EventContext) + Send,
+        M: 'static + FnMut(&Pointer, &mut EventContext) + Send,
+        R: 'static + FnMut(&Pointer, &mut EventContext) + Send,
+    {
+        let mut leaf_layer = layer((5., 5., 10., 10.), vec![]);
+        leaf_layer.pointer_action = on_pointer(log, "leaf", leaf);
+        let mut mid_layer = layer((10., 10., 40., 40.), vec![Arc::new(leaf_layer)]);
+        mid_layer.pointer_action = on_pointer(log, "mid", mid);
+        let mut root_layer = layer((0., 0., 100., 100.), vec![Arc::new(mid_layer)]);
+        root_layer.pointer_action = on_pointer(log, "root", root);
+        Window::new(root_layer)
+    }
+
+    #[test]
+    fn handled_events_keep_bubbling() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let handled = Log::default();
+        let check_handled = {
+            let handled = handled.clone();
+            move |_: &Pointer, context: &mut EventContext| {
+                handled.push(context.is_handled().to_string());
+            }
+        };
+        let mut window = three_level_window(
+            &log,
+            |_, context| context.set_handled(),
+            check_handled.clone(),
+            check_handled,
+        );
+
+        assert!(window.dispatch(pointer(1, Began, 20., 20.)));
+        assert_eq!(log.take(), ["leaf Began", "mid Began", "root Began"]);
+        assert_eq!(handled.take(), ["true", "true"]);
+
+        // events no one handles aren’t handled
+        assert!(!window.dispatch(pointer(2, Began, 60., 60.)));
+        assert_eq!(log.take(), ["root Began"]);
+    }
+
+    #[test]
+    fn stopping_propagation() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = three_level_window(
+            &log,
+            |_, _| (),
+            |event, context| {
+                if event.phase() == Began {
+                    context.stop_propagation();
+                }
+            },
+            |_, _| (),
+        );
+
+        assert!(!window.dispatch(pointer(1, Began, 20., 20.)));
+        assert_eq!(log.take(), ["leaf Began", "mid Began"]);
+        // the root doesn’t get the rest of the stream either
+        window.dispatch(pointer(1, Moved, 21., 20.));
+        window.dispatch(pointer(1, Ended, 21., 20.));
+        assert_eq!(
+            log.take(),
+            ["leaf Moved", "mid Moved", "leaf Ended", "mid Ended"]
+        );
+    }
+
+    #[test]
+    fn declining_keeps_bubbling() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = three_level_window(
+            &log,
+            |_, context| context.decline(),
+            |event, context| {
+                if event.phase() == Moved {
+                    context.decline();
+                }
+            },
+            |_, _| (),
+        );
+
+        window.dispatch(pointer(1, Began, 20., 20.));
+        assert_eq!(log.take(), ["leaf Began", "mid Began", "root Began"]);
+        window.dispatch(pointer(1, Moved, 21., 20.));
+        assert_eq!(log.take(), ["mid Moved", "root Moved"]);
+        // declining doesn’t cancel the stream for anyone else
+        window.dispatch(pointer(1, Ended, 21., 20.));
+        assert_eq!(log.take(), ["root Ended"]);
+    }
+
+    #[test]
+    fn file_drags() {
+        use DragEventPhase::*;
+        let log = Log::default();
+        let on_drop = |name: &'static str, accepts: bool| {
+            let log = log.clone();
+            Some(EventHandler::new(
+                move |event: Event<FileDrop>, context: &mut EventContext| {
+                    log.push(format!("{} {:?}", name, event.phase()));
+                    if accepts {
+                        context.set_handled();
+                    }
+                },
+            ))
+        };
+        let mut a = layer((0., 0., 50., 50.), vec![]);
+        a.file_drop_action = on_drop("a", false);
+        let mut b = layer((50., 0., 50., 50.), vec![]);
+        b.file_drop_action = on_drop("b", true);
+        let mut root = layer((0., 0., 100., 100.), vec![Arc::new(a), Arc::new(b)]);
+        root.file_drop_action = on_drop("root", false);
+        let mut window = Window::new(root);
+
+        let drag = |phase, x, y| RawEvent::FileDrag {
+            root_location: (x, y),
+            phase,
+            paths: vec![PathBuf::from("/tmp/file.txt")],
+        };
+        assert!(!window.dispatch(drag(Entered, 10., 10.)));
+        assert_eq!(log.take(), ["a Entered", "root Entered"]);
+        assert!(!window.dispatch(drag(Moved, 20., 10.)));
+        assert_eq!(log.take(), ["a Moved", "root Moved"]);
+
+        // moving onto b exits a before entering b
+        assert!(window.dispatch(drag(Moved, 80., 10.)));
+        assert_eq!(log.take(), ["a Exited", "b Entered", "root Moved"]);
+        assert!(window.dispatch(drag(Dropped, 80., 10.)));
+        assert_eq!(log.take(), ["b Dropped", "root Dropped"]);
+        assert!(window.dispatcher.drag_views.is_empty());
+
+        // leaving the window exits every view under the drag
+        window.dispatch(drag(Entered, 10., 10.));
+        log.take();
+        assert!(!window.dispatch(drag(Exited, 10., 10.)));
+        assert_eq!(log.take(), ["a Exited", "root Exited"]);
+        assert!(window.dispatcher.drag_views.is_empty());
+    }
+
+    /// Adds an observer that logs pointer events and calls `f`.
+    fn observe<F>(window: &mut Window, log: &Log, phase: ObserverPhase, mut f: F) -> ObserverId
+    where
+        F: 'static + FnMut(&mut EventContext) + Send,
+    {
+        let name = match phase {
+            ObserverPhase::Before => "before",
+            ObserverPhase::After => "after",
+        };
+        let handlers = EventHandlers {
+            pointer: on_pointer(log, name, move |_, context| {
+                assert_eq!(context.view(), None);
+                f(context);
+            }),
+            ..EventHandlers::default()
+        };
+        window.dispatcher.add_observer(phase, handlers)
+    }
+
+    #[test]
+    fn observers_surround_views() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = three_level_window(
+            &log,
+            |_, context| context.set_handled(),
+            |_, context| context.stop_propagation(),
+            |_, _| (),
+        );
+        let handled = Log::default();
+        let check_handled = {
+            let handled = handled.clone();
+            move |context: &mut EventContext| handled.push(context.is_handled().to_string())
+        };
+        // added in reverse order to check that the phase matters, not the order
+        observe(
+            &mut window,
+            &log,
+            ObserverPhase::After,
+            check_handled.clone(),
+        );
+        observe(&mut window, &log, ObserverPhase::Before, check_handled);
+
+        assert!(window.dispatch(pointer(1, Began, 20., 20.)));
+        assert_eq!(
+            log.take(),
+            ["before Began", "leaf Began", "mid Began", "after Began"]
+        );
+        assert_eq!(handled.take(), ["false", "true"]);
+
+        // observers don’t need a view under the pointer
+        window.dispatch(pointer(2, Began, 200., 200.));
+        assert_eq!(log.take(), ["before Began", "after Began"]);
+    }
+
+    #[test]
+    fn observers_can_intercept_events() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = three_level_window(&log, |_, _| (), |_, _| (), |_, _| ());
+        let first = observe(&mut window, &log, ObserverPhase::Before, |context| {
+            context.set_handled();
+            context.stop_propagation();
+        });
+        observe(&mut window, &log, ObserverPhase::Before, |_| ());
+        observe(&mut window, &log, ObserverPhase::After, |_| ());
+
+        // later Before observers and views are skipped, but After observers still see it
+        assert!(window.dispatch(pointer(1, Began, 20., 20.)));
+        assert_eq!(log.take(), ["before Began", "after Began"]);
+        window.dispatch(pointer(1, Ended, 20., 20.));
+        log.take();
+
+        // removing the observer lets events through again
+        window.dispatcher.remove_observer(first);
+        window.dispatcher.remove_observer(first);
+        assert!(!window.dispatch(pointer(2, Began, 20., 20.)));
+        assert_eq!(
+            log.take(),
+            [
+                "before Began",
+                "leaf Began",
+                "mid Began",
+                "root Began",
+                "after Began"
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_observers_see_nothing() {
+        use PointerEventPhase::*;
+        let log = Log::default();
+        let mut window = three_level_window(
+            &log,
+            |event, context| {
+                if event.phase() == Moved {
+                    context.raise_priority(1);
+                }
+            },
+            |_, _| (),
+            |_, _| (),
+        );
+        let before = observe(&mut window, &log, ObserverPhase::Before, |_| ());
+        let after = observe(&mut window, &log, ObserverPhase::After, |_| ());
+
+        window.dispatch(pointer(1, Began, 20., 20.));
+        log.take();
+        // the Canceled events sent to views that lose the stream aren’t observed
+        window.dispatch(pointer(1, Moved, 21., 20.));
+        assert_eq!(
+            log.take(),
+            [
+                "before Moved",
+                "leaf Moved",
+                "mid Moved",
+                "root Moved",
+                "after Moved",
+                "mid Canceled",
+                "root Canceled"
+            ]
+        );
+
+        window.dispatcher.remove_observer(before);
+        window.dispatcher.remove_observer(after);
+        window.dispatch(pointer(1, Ended, 21., 20.));
+        assert_eq!(log.take(), ["leaf Ended"]);
+    }
+
+    #[test]
+    fn scroll_deltas() {
+        let shift = KeyModifiers::SHIFT;
+        let none = KeyModifiers::NONE;
+        let default = ScrollSettings::default();
+        let natural = ScrollSettings {
+            natural_scrolling: true,
+            ..default
+        };
+        let no_shift = ScrollSettings {
+            shift_scrolls_horizontally: false,
+            ..default
+        };
+        let fast = ScrollSettings {
+            line_height: 10.,
+            continuous_scale: 2.,
+            ..default
+        };
+        // settings, delta, discrete, platform-inverted, modifiers => delta, inverted
+        let cases = [
+            (default, (0., 1.), true, false, none, (0., 40.), false),
+            (default, (0.5, -2.), true, false, none, (20., -80.), false),
+            (default, (3., 4.), false, false, none, (3., 4.), false),
+            (fast, (0., 1.), true, false, none, (0., 10.), false),
+            (fast, (3., 4.), false, false, none, (6., 8.), false),
+            // shift turns vertical wheel scrolling horizontal
+            (default, (0., 1.), true, false, shift, (40., 0.), false),
+            (no_shift, (0., 1.), true, false, shift, (0., 40.), false),
+            // unless the platform already did, or it’s a trackpad
+            (default, (1., 0.), true, false, shift, (40., 0.), false),
+            (default, (1., 1.), true, false, shift, (40., 40.), false),
+            (default, (0., 4.), false, false, shift, (0., 4.), false),
+            // natural scrolling inverts deltas the platform didn’t invert
+            (natural, (0., 1.), true, false, none, (0., -40.), true),
+            (natural, (3., -4.), false, false, none, (-3., 4.), true),
+            (natural, (0., 1.), true, false, shift, (-40., 0.), true),
+            (natural, (3., -4.), false, true, none, (3., -4.), true),
+            // and platform-inverted deltas are left alone otherwise
+            (default, (3., -4.), false, true, none, (3., -4.), true),
+        ];
+        for (settings, delta, discrete, inverted, modifiers, expected, expected_inverted) in cases {
+            let converted = settings.convert(delta, discrete, inverted, modifiers);
+            assert_eq!(
+                converted,
+                (Vector2::from(expected), expected_inverted),
+                "{:?} {:?} discrete: {} inverted: {} {:?}",
+                settings,
+                delta,
+                discrete,
+                inverted,
+                modifiers
+            );
+        }
+    }
+
+    #[test]
+    fn scroll_events_use_the_settings() {
+        let deltas = Log::default();
+        let mut inner = layer((10., 10., 20., 20.), vec![]);
+        inner.scroll_action = {
+            let deltas = deltas.clone();
+            Some(EventHandler::new(move |event: Event<Scroll>, _: &mut _| {
+                let delta = event.delta();
+                let inverted = event.is_direction_inverted();
+                deltas.push(format!("{} {} {}", delta.x, delta.y, inverted));
+            }))
+        };
+        let mut window = Window::new(layer((0., 0., 100., 100.), vec![Arc::new(inner)]));
+        let scroll = RawEvent::Scroll {
+            root_location: (15., 15.),
+            delta: (0.5, 1.),
+            is_discrete: true,
+            is_direction_inverted: false,
+            modifiers: KeyModifiers::NONE,
+        };
+
+        window.dispatch(scroll.clone());
+        window.dispatcher.set_scroll_settings(ScrollSettings {
+            natural_scrolling: true,
+            line_height: 20.,
+            ..ScrollSettings::default()
+        });
+        window.dispatch(scroll);
+        assert_eq!(deltas.take(), ["20 40 false", "-10 -20 true"]);
+    }
+
+    #[test]
+    fn pointing_device_changes() {
+        let log = Log::default();
+        let mut inner = layer((10., 10., 20., 20.), vec![]);
+        inner.pointing_device_action = {
+            let log = log.clone();
+            Some(EventHandler::new(
+                move |event: Event<PointingDevice>, context: &mut EventContext| {
+                    log.push(format!(
+                        "{:?} {:?} {}",
+                        event.change(),
+                        event.device(),
+                        event.unique_id()
+                    ));
+                    context.set_handled();
+                },
+            ))
+        };
+        let mut window = Window::new(layer((0., 0., 100., 100.), vec![Arc::new(inner)]));
+        let proximity = |device, unique_id, is_entering| RawEvent::Proximity {
+            device,
+            unique_id,
+            is_entering,
+        };
+
+        // without a key view, nothing receives them
+        assert!(!window.dispatch(proximity(PointerDevice::Pen, 5, true)));
+        assert!(log.take().is_empty());
+        assert_eq!(
+            window.dispatcher.active_device(),
+            Some((PointerDevice::Pen, 5))
+        );
+
+        let inner = window.view_at(15., 15.);
+        window.dispatcher.set_key_view(Some(inner));
+        // flipping the pen over
+        assert!(window.dispatch(proximity(PointerDevice::Pen, 5, false)));
+        assert!(window.dispatch(proximity(PointerDevice::Eraser, 6, true)));
+        assert_eq!(
+            log.take(),
+            [
+                "LeftProximity Pen 5",
+                "EnteredProximity Eraser 6",
+                "Activated Eraser 6"
+            ]
+        );
+        assert_eq!(
+            window.dispatcher.active_device(),
+            Some((PointerDevice::Eraser, 6))
+        );
+
+        // the mouse becomes active when it’s used, but only once
+        window.dispatch(pointer(1, PointerEventPhase::Began, 50., 50.));
+        window.dispatch(pointer(1, PointerEventPhase::Ended, 50., 50.));
+        assert_eq!(log.take(), ["Activated Cursor 1"]);
+        window.dispatch(RawEvent::Hover {
+            device: PointerDevice::Cursor,
+            root_location: (60., 60.),
+            tilt: (0., 0., 1.),
+            event_id: 2,
+            unique_id: 1,
+            phase: HoverEventPhase::Moved,
+            modifiers: KeyModifiers::NONE,
+        });
+        assert!(log.take().is_empty());
+
+        // re-entering doesn’t activate a device that is already active
+        window.dispatch(proximity(PointerDevice::Eraser, 6, true));
+        window.dispatch(proximity(PointerDevice::Eraser, 6, true));
+        assert_eq!(
+            log.take(),
+            [
+                "EnteredProximity Eraser 6",
+                "Activated Eraser 6",
+                "EnteredProximity Eraser 6"
+            ]
+        );
+    }
+
+    #[test]
+    fn hover_targets_the_topmost_view() {
+        let log = Log::default();
+        let hover = |name: &'static str| {
+            let log = log.clone();
+            Some(EventHandler::new(move |event: Event<Hover>, _: &mut _| {
+                log.push(format!("{} {:?}", name, event.phase()));
+            }))
+        };
+        let mut inner = layer((10., 10., 20., 20.), vec![]);
+        inner.hover_action = hover("inner");
+        let mut root = layer((0., 0., 100., 100.), vec![Arc::new(inner)]);
+        root.hover_action = hover("root");
+        let mut window = Window::new(root);
+
+        let hover_at = |phase, x, y| RawEvent::Hover {
+            device: PointerDevice::Cursor,
+            root_location: (x, y),
+            tilt: (0., 0., 1.),
+            event_id: 1,
+            unique_id: 1,
+            phase,
+            modifiers: KeyModifiers::NONE,
+        };
+        window.dispatch(hover_at(HoverEventPhase::Entered, 15., 15.));
+        window.dispatch(hover_at(HoverEventPhase::Moved, 50., 50.));
+        assert_eq!(log.take(), ["inner Entered", "root Entered", "root Moved"]);
+    }
+}
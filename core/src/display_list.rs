@@ -0,0 +1,353 @@
+//! Display lists for renderers that draw the NV tree themselves.
+//!
+//! A [`DisplayList`] describes what the NV tree looks like in terms of simple drawing primitives,
+//! so software, GPU, and remote renderers only need to know how to draw those, and not how native
+//! views are laid out or kept in sync. It mirrors the structure of the NV tree: each
+//! [`DisplayNode`] corresponds to a native view, with a transform, opacity, and clip that apply to
+//! its items and children.
+//!
+//! ```no_run
+//! # use birb::display_list::{DisplayItem, DisplayNode};
+//! # use birb::NVTree;
+//! # use birb::backend::Backend;
+//! # use core::ops::DerefMut;
+//! # fn draw<B, Bk>(nv_tree: &NVTree<B, Bk::ViewRef>)
+//! # where B: DerefMut<Target = Bk>, Bk: Backend {
+//! fn visit(node: &DisplayNode) {
+//!     for item in &node.items {
+//!         match item {
+//!             DisplayItem::RoundedRect { .. } => { /* fill */ }
+//!             _ => (),
+//!         }
+//!     }
+//!     node.children.iter().for_each(visit);
+//! }
+//! if let Some(root) = &nv_tree.display_list().root {
+//!     visit(root);
+//! }
+//! # }
+//! ```
+
+use crate::atlas::{AtlasRegion, CapInsets};
+use crate::color::{Color, Gradient};
+use crate::layer::{BorderStyle, ContentsGravity};
+use crate::nv_tree::{NativeView, TrackingArea};
+use crate::rect::{CornerRadius, Rect};
+use crate::shape::Shape;
+use crate::view::ViewId;
+use cgmath::{Matrix3, SquareMatrix, Vector2};
+
+/// A drawing primitive, in the coordinate system of its node.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DisplayItem {
+    /// Fills a rectangle with rounded corners.
+    RoundedRect {
+        rect: Rect,
+        corner_radius: CornerRadius,
+        color: Color,
+    },
+    /// Fills a rectangle with rounded corners with a gradient, whose start and end are relative
+    /// to the rectangle.
+    Gradient {
+        rect: Rect,
+        corner_radius: CornerRadius,
+        gradient: Gradient,
+    },
+    /// Strokes the inside of a rectangle with rounded corners.
+    ///
+    /// Borders that are centered on or outside the layer bounds are already outset accordingly.
+    Border {
+        rect: Rect,
+        corner_radius: CornerRadius,
+        width: f64,
+        color: Color,
+        style: BorderStyle,
+    },
+    /// Draws a region of an atlas page scaled to fill a rectangle, with nine-slice scaling, and
+    /// repeated in both directions if tiled. Nothing is drawn outside `bounds`.
+    Image {
+        rect: Rect,
+        region: AtlasRegion,
+        insets: CapInsets,
+        bounds: Rect,
+        tiled: bool,
+    },
+    /// A native view of a custom kind; see [`custom_view`](crate::custom_view).
+    Custom {
+        rect: Rect,
+        kind: String,
+        props: Vec<u8>,
+    },
+}
+
+/// A node of a display list, corresponding to a native view.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayNode {
+    /// The native view this node was created from.
+    pub view: ViewId,
+
+    /// Maps the coordinate system of this node (with the origin at the top left corner of the
+    /// view) to that of its parent.
+    pub transform: Matrix3<f64>,
+
+    /// Opacity of this node and its children.
+    pub opacity: f64,
+
+    /// If true, the node and its children should be composited as a group before the opacity is
+    /// applied; otherwise, the opacity applies to each item separately.
+    pub group_opacity: bool,
+
+    /// Clips the children of this node, in the coordinate system of this node.
+    pub clip: Option<Shape>,
+
+    /// If true, the renderer may cache this node and its children as a bitmap.
+    pub should_rasterize: bool,
+
+    /// Items to draw, back to front, below the children.
+    pub items: Vec<DisplayItem>,
+
+    /// Children, back to front.
+    pub children: Vec<DisplayNode>,
+}
+
+/// A display list of an NV tree; see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayList {
+    /// The root node, if the tree has a root view.
+    pub root: Option<DisplayNode>,
+}
+
+impl DisplayList {
+    /// Builds a display list from a root view and a way to look up views and their subviews, at
+    /// a scale factor that determines the natural size of layer contents.
+    pub(crate) fn build<'a, F>(root: Option<ViewId>, scale_factor: f64, view: F) -> DisplayList
+    where
+        F: Fn(ViewId) -> Option<(&'a NativeView, &'a [ViewId])>,
+    {
+        DisplayList {
+            root: root.and_then(|root| build_node(root, scale_factor, &view)),
+        }
+    }
+}
+
+fn build_node<'a, F>(id: ViewId, scale_factor: f64, view: &F) -> Option<DisplayNode>
+where
+    F: Fn(ViewId) -> Option<(&'a NativeView, &'a [ViewId])>,
+{
+    let (native_view, subviews) = view(id)?;
+    let local = Rect::new((0., 0.).into(), native_view.bounds().size);
+    let transform = TrackingArea::new(Matrix3::identity(), native_view).to_window;
+    let children = subviews
+        .iter()
+        .filter_map(|subview| build_node(*subview, scale_factor, view))
+        .collect();
+
+    let node = match native_view {
+        NativeView::Layer(props) => {
+            let mut items = Vec::new();
+            if props.background.a > 0. {
+                items.push(DisplayItem::RoundedRect {
+                    rect: local,
+                    corner_radius: props.corner_radius,
+                    color: props.background,
+                });
+            }
+            if let Some(gradient) = &props.background_gradient {
+                items.push(DisplayItem::Gradient {
+                    rect: local,
+                    corner_radius: props.corner_radius,
+                    gradient: gradient.clone(),
+                });
+            }
+            if let Some(region) = props.contents {
+                let size = Vector2::new(region.width as f64, region.height as f64) / scale_factor;
+                items.push(DisplayItem::Image {
+                    rect: props.contents_gravity.contents_rect(local, size),
+                    region,
+                    insets: props.contents_insets,
+                    bounds: local,
+                    tiled: props.contents_gravity == ContentsGravity::Tile,
+                });
+            }
+            if let Some((width, color)) = props.border {
+                if width > 0. && color.a > 0. {
+                    let overflow = props.border_alignment.overflow(width);
+                    items.push(DisplayItem::Border {
+                        rect: local.outset(overflow, overflow),
+                        corner_radius: props.corner_radius.clamped(local.size).outset(overflow),
+                        width,
+                        color,
+                        style: props.border_style,
+                    });
+                }
+            }
+            DisplayNode {
+                view: id,
+                transform,
+                opacity: props.opacity,
+                group_opacity: props.group_opacity,
+                clip: props.clip_shape(),
+                should_rasterize: props.should_rasterize,
+                items,
+                children,
+            }
+        }
+        NativeView::Custom {
+            kind,
+            opacity,
+            props,
+            ..
+        } => DisplayNode {
+            view: id,
+            transform,
+            opacity: *opacity,
+            group_opacity: true,
+            clip: None,
+            should_rasterize: false,
+            items: vec![DisplayItem::Custom {
+                rect: local,
+                kind: kind.clone(),
+                props: props.clone(),
+            }],
+            children,
+        },
+    };
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::NullBackend;
+    use crate::layer::{BorderAlignment, LayerProps};
+    use crate::nv_tree::{NVTree, Patch};
+    use cgmath::Point2;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    fn layer(props: LayerProps) -> NativeView {
+        NativeView::Layer(Box::new(props))
+    }
+
+    #[test]
+    fn nodes_mirror_the_tree() {
+        let [root, a, b, c] = [ViewId::new(), ViewId::new(), ViewId::new(), ViewId::new()];
+        let root_props = LayerProps {
+            bounds: rect(0., 0., 100., 100.),
+            background: Color::WHITE,
+            corner_radius: 10.0.into(),
+            clip_contents: true,
+            ..LayerProps::default()
+        };
+        let gradient = Gradient::vertical(Color::WHITE, Color::BLACK);
+        let mut tree: NVTree<Box<NullBackend>, ()> = NVTree::new(Box::new(NullBackend));
+        tree.patch_frame([
+            Patch::Update(root, layer(root_props.clone())),
+            Patch::Update(
+                a,
+                layer(LayerProps {
+                    bounds: rect(10., 10., 50., 50.),
+                    background: Color::BLACK,
+                    border: Some((2., Color::WHITE)),
+                    border_alignment: BorderAlignment::Outside,
+                    opacity: 0.5,
+                    group_opacity: false,
+                    ..LayerProps::default()
+                }),
+            ),
+            Patch::Update(
+                b,
+                layer(LayerProps {
+                    bounds: rect(5., 5., 10., 10.),
+                    background_gradient: Some(gradient.clone()),
+                    ..LayerProps::default()
+                }),
+            ),
+            Patch::Update(
+                c,
+                layer(LayerProps {
+                    bounds: rect(60., 60., 20., 20.),
+                    ..LayerProps::default()
+                }),
+            ),
+            Patch::SubviewRegion(root, 0, 0, vec![a, c]),
+            Patch::SubviewRegion(a, 0, 0, vec![b]),
+            Patch::SetRoot(root),
+        ])
+        .unwrap();
+
+        let list = tree.display_list();
+        let root_node = list.root.unwrap();
+        assert_eq!(root_node.view, root);
+        assert_eq!(root_node.transform, Matrix3::identity());
+        assert_eq!(root_node.clip, root_props.clip_shape());
+        assert!(root_node.clip.is_some());
+        assert_eq!(
+            root_node.items,
+            [DisplayItem::RoundedRect {
+                rect: rect(0., 0., 100., 100.),
+                corner_radius: 10.0.into(),
+                color: Color::WHITE,
+            }]
+        );
+        let children: Vec<_> = root_node.children.iter().map(|node| node.view).collect();
+        assert_eq!(children, [a, c]);
+
+        // items are in the coordinate system of their node, and outset borders extend beyond it
+        let a_node = &root_node.children[0];
+        assert_eq!(
+            a_node.transform,
+            Matrix3::new(1., 0., 0., 0., 1., 0., 10., 10., 1.)
+        );
+        assert_eq!((a_node.opacity, a_node.group_opacity), (0.5, false));
+        assert_eq!(a_node.clip, None);
+        assert_eq!(
+            a_node.items,
+            [
+                DisplayItem::RoundedRect {
+                    rect: rect(0., 0., 50., 50.),
+                    corner_radius: CornerRadius::default(),
+                    color: Color::BLACK,
+                },
+                DisplayItem::Border {
+                    rect: rect(-2., -2., 54., 54.),
+                    corner_radius: CornerRadius::default().outset(2.),
+                    width: 2.,
+                    color: Color::WHITE,
+                    style: BorderStyle::default(),
+                },
+            ]
+        );
+
+        let b_node = &a_node.children[0];
+        assert_eq!(b_node.view, b);
+        assert_eq!(
+            b_node.items,
+            [DisplayItem::Gradient {
+                rect: rect(0., 0., 10., 10.),
+                corner_radius: CornerRadius::default(),
+                gradient,
+            }]
+        );
+        assert!(b_node.children.is_empty());
+
+        // transparent layers draw nothing
+        let c_node = &root_node.children[1];
+        assert!(c_node.items.is_empty());
+        assert_eq!((c_node.opacity, c_node.group_opacity), (1., true));
+    }
+
+    #[test]
+    fn trees_without_a_root_are_empty() {
+        let mut tree: NVTree<Box<NullBackend>, ()> = NVTree::new(Box::new(NullBackend));
+        assert_eq!(tree.display_list(), DisplayList::default());
+        tree.patch_frame([Patch::Update(ViewId::new(), layer(LayerProps::default()))])
+            .unwrap();
+        assert_eq!(tree.display_list().root, None);
+    }
+}
@@ -0,0 +1,248 @@
+//! Side effects of views; see [`Context::effect`](crate::Context::effect).
+
+use core::any::Any;
+use core::fmt;
+use core::mem;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// A cleanup function returned by an effect’s setup.
+type Cleanup = Box<dyn FnOnce() + Send>;
+
+/// An effect declared in `View::effects`.
+struct KeyedEffect {
+    /// The key the effect was set up with.
+    key: Box<dyn Any + Send>,
+    cleanup: Cleanup,
+}
+
+#[derive(Default)]
+struct EffectsInner {
+    /// Effects set up in `View::new_state`, which last as long as the state.
+    permanent: Vec<Cleanup>,
+    /// Effects set up in `View::effects`, in order of declaration.
+    keyed: Vec<KeyedEffect>,
+    /// The index of the next keyed effect, while `View::effects` is being called.
+    next_index: Option<usize>,
+}
+
+/// The effects of a view, shared with the contexts given to the view.
+#[derive(Clone, Default)]
+pub(crate) struct Effects(Arc<Mutex<EffectsInner>>);
+
+impl fmt::Debug for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.0.lock();
+        f.debug_struct("Effects")
+            .field("permanent", &inner.permanent.len())
+            .field("keyed", &inner.keyed.len())
+            .finish()
+    }
+}
+
+impl Effects {
+    /// Sets up an effect, or does nothing if the effect at the same position already has the same
+    /// key.
+    ///
+    /// Setup and cleanup functions are called without holding the lock, so they may use the
+    /// context themselves.
+    pub(crate) fn effect<K, C>(&self, key: K, setup: impl FnOnce() -> C)
+    where
+        K: PartialEq + Send + 'static,
+        C: FnOnce() + Send + 'static,
+    {
+        let index = {
+            let mut inner = self.0.lock();
+            let index = inner.next_index;
+            inner.next_index = index.map(|index| index + 1);
+            index
+        };
+
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let cleanup = Box::new(setup());
+                self.0.lock().permanent.push(cleanup);
+                return;
+            }
+        };
+
+        let previous = {
+            let mut inner = self.0.lock();
+            match inner.keyed.get(index) {
+                Some(effect) if effect.key.downcast_ref::<K>() == Some(&key) => return,
+                Some(_) => Some(inner.keyed.remove(index)),
+                None => None,
+            }
+        };
+        if let Some(previous) = previous {
+            (previous.cleanup)();
+        }
+
+        let cleanup = Box::new(setup());
+        let mut inner = self.0.lock();
+        let index = index.min(inner.keyed.len());
+        inner.keyed.insert(
+            index,
+            KeyedEffect {
+                key: Box::new(key),
+                cleanup,
+            },
+        );
+    }
+
+    /// Starts a call to `View::effects`, which declares keyed effects in order.
+    pub(crate) fn begin(&self) {
+        self.0.lock().next_index = Some(0);
+    }
+
+    /// Finishes a call to `View::effects`, cleaning up keyed effects that weren’t declared again.
+    pub(crate) fn end(&self) {
+        let removed = {
+            let mut inner = self.0.lock();
+            let declared = inner.next_index.take().unwrap_or(0);
+            if declared < inner.keyed.len() {
+                inner.keyed.split_off(declared)
+            } else {
+                Vec::new()
+            }
+        };
+        for effect in removed.into_iter().rev() {
+            (effect.cleanup)();
+        }
+    }
+
+    /// Cleans up all effects, most recently set up first.
+    pub(crate) fn clean_up(&self) {
+        let (permanent, keyed) = {
+            let mut inner = self.0.lock();
+            inner.next_index = None;
+            (mem::take(&mut inner.permanent), mem::take(&mut inner.keyed))
+        };
+        for effect in keyed.into_iter().rev() {
+            (effect.cleanup)();
+        }
+        for cleanup in permanent.into_iter().rev() {
+            cleanup();
+        }
+    }
+
+    /// Returns the number of effects that are set up.
+    pub(crate) fn len(&self) -> usize {
+        let inner = self.0.lock();
+        inner.permanent.len() + inner.keyed.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layer::Layer;
+    use crate::view::{AnyState, View};
+    use crate::view_tree::{Context, ViewTree};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    /// Subscribes to something for as long as it exists, and watches `dependency` and, if
+    /// `watch_extra` is set, something else.
+    #[derive(Debug)]
+    struct Subscriber {
+        log: Log,
+        dependency: u32,
+        watch_extra: bool,
+    }
+
+    impl PartialEq for Subscriber {
+        fn eq(&self, other: &Subscriber) -> bool {
+            Arc::ptr_eq(&self.log, &other.log)
+                && self.dependency == other.dependency
+                && self.watch_extra == other.watch_extra
+        }
+    }
+
+    /// Returns an effect setup function that logs its setup and cleanup.
+    fn logged(log: &Log, name: String) -> impl FnOnce() -> Box<dyn FnOnce() + Send> {
+        let log = Arc::clone(log);
+        move || {
+            log.lock().push(format!("set up {}", name));
+            Box::new(move || log.lock().push(format!("clean up {}", name)))
+        }
+    }
+
+    crate::impl_view! {
+        Subscriber: ();
+        fn new_state(&self, context) {
+            context.effect((), logged(&self.log, "subscription".into()));
+            Arc::new(())
+        }
+        fn body(&self, _state: &()) {
+            Arc::new(())
+        }
+        fn effects(&self, _state: &AnyState<()>, context: &Context<()>) {
+            let dependency = self.dependency;
+            context.effect(dependency, logged(&self.log, dependency.to_string()));
+            if self.watch_extra {
+                context.effect("extra", logged(&self.log, "extra".into()));
+            }
+        }
+    }
+
+    /// Renders a subscriber, or nothing, and returns what happened.
+    fn render(tree: &mut ViewTree, log: &Log, subscriber: Option<(u32, bool)>) -> Vec<String> {
+        let subviews = subscriber
+            .map(|(dependency, watch_extra)| {
+                let view: Arc<dyn View> = Arc::new(Subscriber {
+                    log: Arc::clone(log),
+                    dependency,
+                    watch_extra,
+                });
+                view
+            })
+            .into_iter()
+            .collect();
+        tree.render_root(
+            Arc::new(Layer {
+                subviews,
+                ..Layer::default()
+            }),
+            (),
+        );
+        log.lock().drain(..).collect()
+    }
+
+    #[test]
+    fn effects_are_cleaned_up_when_dependencies_change() {
+        let log = Log::default();
+        let mut tree = ViewTree::new();
+        assert_eq!(
+            render(&mut tree, &log, Some((1, true))),
+            ["set up subscription", "set up 1", "set up extra"]
+        );
+        assert!(render(&mut tree, &log, Some((1, true))).is_empty());
+        assert_eq!(
+            render(&mut tree, &log, Some((2, true))),
+            ["clean up 1", "set up 2"]
+        );
+        assert_eq!(
+            render(&mut tree, &log, Some((2, false))),
+            ["clean up extra"]
+        );
+        assert_eq!(
+            render(&mut tree, &log, Some((3, true))),
+            ["clean up 2", "set up 3", "set up extra"]
+        );
+    }
+
+    #[test]
+    fn effects_are_cleaned_up_when_views_are_removed() {
+        let log = Log::default();
+        let mut tree = ViewTree::new();
+        render(&mut tree, &log, Some((1, true)));
+        assert_eq!(
+            render(&mut tree, &log, None),
+            ["clean up extra", "clean up 1", "clean up subscription"]
+        );
+        assert!(render(&mut tree, &log, None).is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+//! Embedding view trees in other view trees.
+//!
+//! A view tree can be mounted inside another one, e.g. to host plugins or to isolate a heavy
+//! editor that renders at its own pace. Each tree has its own context type and is rendered on
+//! its own, but they share one NV tree: the host tree marks where embedded trees go with
+//! [`Embed`] views, and a [`Compositor`] rewrites the patches of all trees so that the root view
+//! of each embedded tree becomes the first subview of its `Embed`’s native view.
+//!
+//! ```no_run
+//! # use birb::backend::Backend;
+//! # use birb::embed::Compositor;
+//! # use birb::{NVTree, ViewTree};
+//! # use core::ops::DerefMut;
+//! # use std::fmt::Debug;
+//! # fn run<B, Bk>(nv_tree: &mut NVTree<B, Bk::ViewRef>)
+//! # where B: DerefMut<Target = Bk>, Bk: Backend + Debug, Bk::Error: Debug {
+//! # let mut host = ViewTree::<()>::new();
+//! # let mut editor = ViewTree::<()>::new();
+//! const EDITOR: u64 = 1;
+//! let mut compositor = Compositor::new();
+//!
+//! // whenever the host tree has rendered
+//! let points = host.embed_points();
+//! let patches = compositor.host_frame(host.patches(), &points);
+//! nv_tree.patch_frame(patches).expect("invalid patches");
+//!
+//! // whenever the embedded tree has rendered
+//! let patches = compositor.embedded_frame(EDITOR, editor.patches());
+//! nv_tree.patch_frame(patches).expect("invalid patches");
+//! # }
+//! ```
+//!
+//! Patches must be applied in the order the compositor returns them. Each tree still needs its
+//! own [`EventDispatcher`](crate::dispatch::EventDispatcher).
+//!
+//! While the host tree has no `Embed` view for an embedded tree, the embedded tree’s native views
+//! stay in the NV tree without being shown (which its leak check will report), so that it can
+//! keep rendering; use [`Compositor::unmount`] to remove them.
+
+use crate::impl_view;
+use crate::nv_tree::Patch;
+use crate::view::{View, ViewId};
+use core::fmt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Identifies an embedded view tree; chosen by the app.
+pub type EmbedId = u64;
+
+/// Marks where an embedded view tree is mounted in the host tree.
+///
+/// The content should be a single native view (such as an empty layer that sets the bounds of
+/// the embedded tree); the root view of the embedded tree will be inserted as its first subview.
+pub struct Embed<Ctx> {
+    pub key: Option<u64>,
+
+    /// The embedded tree.
+    pub id: EmbedId,
+
+    /// The native view the embedded tree is mounted in.
+    pub content: Arc<dyn View<Ctx>>,
+}
+
+impl<Ctx> fmt::Debug for Embed<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Embed")
+            .field("id", &self.id)
+            .field("content", &self.content)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for Embed<Ctx> {
+    fn eq(&self, other: &Embed<Ctx>) -> bool {
+        self.key == other.key && self.id == other.id && self.content.eq(&*other.content)
+    }
+}
+
+impl_view! {
+    Embed<Ctx>;
+    fn body(&self, _state: &()) {
+        Arc::clone(&self.content)
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[derive(Debug, Default)]
+struct Mount {
+    /// The root view of the embedded tree, once it has been rendered.
+    root: Option<ViewId>,
+    /// The native view in the host tree that the embedded tree is mounted in, if any.
+    host: Option<ViewId>,
+    /// The host view whose first subview the root view currently is, if any.
+    attached_to: Option<ViewId>,
+}
+
+/// Combines the patches of a host tree and embedded trees for a single NV tree; see the
+/// [module documentation](self).
+#[derive(Debug, Default)]
+pub struct Compositor {
+    mounts: HashMap<EmbedId, Mount>,
+}
+
+impl Compositor {
+    pub fn new() -> Compositor {
+        Compositor::default()
+    }
+
+    /// Rewrites a frame of patches from the host tree.
+    ///
+    /// `points` are the mount points of the host tree after the frame, as returned by
+    /// [`ViewTree::embed_points`](crate::ViewTree::embed_points).
+    pub fn host_frame<I>(&mut self, patches: I, points: &HashMap<EmbedId, ViewId>) -> Vec<Patch>
+    where
+        I: IntoIterator<Item = Patch>,
+    {
+        let mut out = Vec::new();
+
+        // detach embedded trees from mount points that are going away, before the host tree
+        // removes them (and everything in them)
+        for (id, mount) in &mut self.mounts {
+            if mount.host.is_some() && mount.host != points.get(id).copied() {
+                if let Some(host) = mount.attached_to.take() {
+                    out.push(Patch::SubviewRegion(host, 0, 1, Vec::new()));
+                }
+                mount.host = None;
+            }
+        }
+
+        let attached: HashMap<_, _> = self
+            .mounts
+            .values()
+            .filter_map(|mount| Some((mount.attached_to?, mount.root?)))
+            .collect();
+        for patch in patches {
+            match patch {
+                // the host tree doesn’t know about the embedded root view
+                Patch::SubviewRegion(host, offset, len, subviews)
+                    if attached.contains_key(&host) =>
+                {
+                    out.push(Patch::SubviewRegion(host, offset + 1, len, subviews));
+                }
+                // replacing a view removes its subviews
+                Patch::Replace(host, view) if attached.contains_key(&host) => {
+                    out.push(Patch::SubviewRegion(host, 0, 1, Vec::new()));
+                    out.push(Patch::Replace(host, view));
+                    out.push(Patch::SubviewRegion(host, 0, 0, vec![attached[&host]]));
+                }
+                patch => out.push(patch),
+            }
+        }
+
+        for (id, host) in points {
+            let mount = self.mounts.entry(*id).or_default();
+            mount.host = Some(*host);
+            if let (Some(root), None) = (mount.root, mount.attached_to) {
+                out.push(Patch::SubviewRegion(*host, 0, 0, vec![root]));
+                mount.attached_to = Some(*host);
+            }
+        }
+        out
+    }
+
+    /// Rewrites a frame of patches from an embedded tree.
+    pub fn embedded_frame<I>(&mut self, id: EmbedId, patches: I) -> Vec<Patch>
+    where
+        I: IntoIterator<Item = Patch>,
+    {
+        let mount = self.mounts.entry(id).or_default();
+        let mut out = Vec::new();
+        for patch in patches {
+            match patch {
+                // the root view is mounted in the host tree instead
+                Patch::SetRoot(root) => {
+                    if let Some(host) = mount.host {
+                        let len = if mount.attached_to.is_some() { 1 } else { 0 };
+                        out.push(Patch::SubviewRegion(host, 0, len, vec![root]));
+                        mount.attached_to = Some(host);
+                    }
+                    mount.root = Some(root);
+                }
+                patch => out.push(patch),
+            }
+        }
+        out
+    }
+
+    /// Removes an embedded tree from the NV tree, e.g. after it has been dropped.
+    pub fn unmount(&mut self, id: EmbedId) -> Vec<Patch> {
+        let mut out = Vec::new();
+        if let Some(mount) = self.mounts.remove(&id) {
+            if let Some(host) = mount.attached_to {
+                out.push(Patch::SubviewRegion(host, 0, 1, Vec::new()));
+            }
+            if let Some(root) = mount.root {
+                out.push(Patch::Remove(root));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::NullBackend;
+    use crate::layer::Layer;
+    use crate::nv_tree::NVTree;
+    use crate::view_tree::ViewTree;
+
+    /// Shared native views of a host tree and an embedded tree.
+    struct Trees {
+        host: ViewTree,
+        editor: ViewTree,
+        compositor: Compositor,
+        native_views: NVTree<Box<NullBackend>, ()>,
+    }
+
+    const EDITOR: EmbedId = 1;
+
+    impl Trees {
+        fn new() -> Trees {
+            Trees {
+                host: ViewTree::new(),
+                editor: ViewTree::new(),
+                compositor: Compositor::new(),
+                native_views: NVTree::new(Box::new(NullBackend)),
+            }
+        }
+
+        /// Renders the host tree, with the editor mounted in its second subview if `mounted`
+        /// is set.
+        fn render_host(&mut self, mounted: bool) {
+            let mut subviews: Vec<Arc<dyn View>> = vec![Arc::new(Layer::default())];
+            if mounted {
+                subviews.push(Arc::new(Embed {
+                    key: Some(1),
+                    id: EDITOR,
+                    content: Arc::new(Layer::default()),
+                }));
+            }
+            self.host.render_root(
+                Arc::new(Layer {
+                    subviews,
+                    ..Layer::default()
+                }),
+                (),
+            );
+            let points = self.host.embed_points();
+            let patches = self.compositor.host_frame(self.host.patches(), &points);
+            self.native_views.patch_frame(patches).unwrap();
+        }
+
+        /// Renders the editor and returns its root view.
+        fn render_editor(&mut self) -> ViewId {
+            self.editor.render_root(Arc::new(Layer::default()), ());
+            let patches: Vec<_> = self.editor.patches().collect();
+            let root = patches.iter().find_map(|patch| match patch {
+                Patch::SetRoot(root) => Some(*root),
+                _ => None,
+            });
+            let patches = self.compositor.embedded_frame(EDITOR, patches);
+            self.native_views.patch_frame(patches).unwrap();
+            root.expect("editor has no root view")
+        }
+
+        fn mount_point(&self) -> Option<ViewId> {
+            self.host.embed_points().get(&EDITOR).copied()
+        }
+    }
+
+    #[test]
+    fn embedded_trees_can_be_remounted() {
+        let mut trees = Trees::new();
+        trees.render_host(true);
+        let root = trees.render_editor();
+        let point = trees.mount_point().unwrap();
+        assert_eq!(trees.native_views.superview(root), Some(point));
+
+        trees.render_host(false);
+        assert_eq!(trees.mount_point(), None);
+        assert!(!trees.native_views.contains(point));
+        assert!(trees.native_views.contains(root));
+        assert_eq!(trees.native_views.superview(root), None);
+
+        // the editor keeps rendering while it’s not mounted
+        trees.editor.render_root(
+            Arc::new(Layer {
+                subviews: vec![Arc::new(Layer::default())],
+                ..Layer::default()
+            }),
+            (),
+        );
+        let patches = trees
+            .compositor
+            .embedded_frame(EDITOR, trees.editor.patches());
+        trees.native_views.patch_frame(patches).unwrap();
+
+        trees.render_host(true);
+        let point = trees.mount_point().unwrap();
+        assert_eq!(trees.native_views.superview(root), Some(point));
+
+        let patches = trees.compositor.unmount(EDITOR);
+        trees.native_views.patch_frame(patches).unwrap();
+        assert!(!trees.native_views.contains(root));
+        assert_eq!(trees.native_views.memory_stats().stale_entries, 0);
+
+        // unmounting again does nothing
+        assert!(trees.compositor.unmount(EDITOR).is_empty());
+    }
+
+    #[test]
+    fn embedded_trees_can_render_before_being_mounted() {
+        let mut trees = Trees::new();
+        let root = trees.render_editor();
+        assert_eq!(trees.native_views.superview(root), None);
+
+        trees.render_host(true);
+        let point = trees.mount_point().unwrap();
+        assert_eq!(trees.native_views.superview(root), Some(point));
+
+        // the host tree doesn’t know about the embedded root view when it changes the mount point
+        let content = Layer {
+            subviews: vec![Arc::new(Layer::default())],
+            ..Layer::default()
+        };
+        trees.host.render_root(
+            Arc::new(Layer {
+                subviews: vec![
+                    Arc::new(Layer::default()),
+                    Arc::new(Embed {
+                        key: Some(1),
+                        id: EDITOR,
+                        content: Arc::new(content),
+                    }),
+                ],
+                ..Layer::default()
+            }),
+            (),
+        );
+        let points = trees.host.embed_points();
+        let patches = trees.compositor.host_frame(trees.host.patches(), &points);
+        assert!(patches.iter().any(|patch| matches!(
+            patch,
+            Patch::SubviewRegion(host, 1, 0, subviews) if *host == point && subviews.len() == 1
+        )));
+        trees.native_views.patch_frame(patches).unwrap();
+        assert_eq!(trees.native_views.superview(root), Some(point));
+        assert_eq!(trees.native_views.memory_stats().stale_entries, 0);
+    }
+}
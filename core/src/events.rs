@@ -1,15 +1,47 @@
 //! Events.
 
+use crate::dispatch::EventContext;
+use crate::raw_events::{
+    ControllerId, DragEventPhase, HoverEventPhase, KeyEventPhase, PointerEventPhase, PointerId,
+};
 use cgmath::{Point2, Vector2, Vector3};
 use core::fmt;
+use core::mem;
+use core::ops::{self, Deref};
 use parking_lot::Mutex;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 /// An event.
 pub struct Event<Type> {
     data: Type,
     // TODO: phase/capture/priority stuff
-    // TODO: some way to access data
+}
+
+impl<Type> Event<Type> {
+    pub(crate) fn new(data: Type) -> Event<Type> {
+        Event { data }
+    }
+
+    /// Returns the event data.
+    pub fn data(&self) -> &Type {
+        &self.data
+    }
+}
+
+/// Wraps event data, e.g. to call a handler in tests (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl<Type> From<Type> for Event<Type> {
+    fn from(data: Type) -> Event<Type> {
+        Event::new(data)
+    }
+}
+
+impl<Type> Deref for Event<Type> {
+    type Target = Type;
+    fn deref(&self) -> &Type {
+        &self.data
+    }
 }
 
 /// List of event types.
@@ -20,6 +52,11 @@ pub enum EventTypeId {
     Pointer = 1,
     Key = 2,
     Scroll = 3,
+    Tap = 4,
+    Media = 5,
+    Controller = 6,
+    FileDrop = 7,
+    PointingDevice = 8,
 }
 
 /// Internal trait for individual event types.
@@ -76,6 +113,16 @@ impl PointerDevice {
             PointerDevice::Cursor => false,
         }
     }
+
+    /// The distance in points a pointer may move between pressing down and lifting up while
+    /// still counting as a tap.
+    pub fn tap_slop(&self) -> f64 {
+        if self.is_volatile() {
+            10.
+        } else {
+            4.
+        }
+    }
 }
 
 /// A hover event.
@@ -84,24 +131,115 @@ pub struct Hover {
     /// Unique ID of the pointer, or zero. If nonzero, can be expected to persist forever.
     ///
     /// This value will be computed from e.g. hardware IDs in wacom pens.
-    id: u64,
+    pub(crate) id: PointerId,
+
+    /// Event phase.
+    pub(crate) phase: HoverEventPhase,
 
     /// Event location in the parent coordinate system.
-    location: Point2<f64>,
+    pub(crate) location: Point2<f64>,
 
     /// Event location in the window coordinate system.
-    window_location: Point2<f64>,
+    pub(crate) window_location: Point2<f64>,
 
     /// Pointer tilt, expressed as a unit vector. Will point from the tip of the pen to the far end
     /// of the pen.
     ///
     /// The Z axis points outwards from the screen.
-    tilt: Vector3<f64>,
+    pub(crate) tilt: Vector3<f64>,
 
     /// The device type that emitted this hover event.
     ///
     /// Touch devices will never emit hover events.
-    device: PointerDevice,
+    pub(crate) device: PointerDevice,
+
+    /// Modifier keys that are being pressed.
+    pub(crate) modifiers: KeyModifiers,
+}
+
+impl Hover {
+    /// Unique ID of the pointer, or zero.
+    pub fn id(&self) -> PointerId {
+        self.id
+    }
+
+    /// Event phase.
+    pub fn phase(&self) -> HoverEventPhase {
+        self.phase
+    }
+
+    /// Event location in the coordinate system of the superview of the view receiving the event.
+    pub fn location(&self) -> Point2<f64> {
+        self.location
+    }
+
+    /// Event location in the window coordinate system.
+    pub fn window_location(&self) -> Point2<f64> {
+        self.window_location
+    }
+
+    /// Pointer tilt; see [`Pointer::tilt`].
+    pub fn tilt(&self) -> Vector3<f64> {
+        self.tilt
+    }
+
+    /// The device type that emitted this hover event.
+    pub fn device(&self) -> PointerDevice {
+        self.device
+    }
+
+    /// Modifier keys that are being pressed.
+    pub fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Hover {
+    /// Creates a hover event from a cursor without modifiers, at a location that is used both in
+    /// the parent and the window coordinate system.
+    pub fn new(phase: HoverEventPhase, location: Point2<f64>) -> Hover {
+        Hover {
+            id: 0,
+            phase,
+            location,
+            window_location: location,
+            tilt: Vector3::new(0., 0., 1.),
+            device: PointerDevice::Cursor,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    /// Sets the unique ID of the pointer.
+    pub fn with_id(mut self, id: PointerId) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the location in the window coordinate system.
+    pub fn with_window_location(mut self, window_location: Point2<f64>) -> Self {
+        self.window_location = window_location;
+        self
+    }
+
+    /// Sets the pointer tilt.
+    pub fn with_tilt(mut self, tilt: Vector3<f64>) -> Self {
+        self.tilt = tilt;
+        self
+    }
+
+    /// Sets the device type.
+    pub fn with_device(mut self, device: PointerDevice) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Sets the modifier keys.
+    pub fn with_modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
 }
 
 impl EventType for Hover {
@@ -125,25 +263,144 @@ pub struct Pointer {
     /// Unique ID of the pointer, or zero. If nonzero, can be expected to persist forever.
     ///
     /// This value will be computed from e.g. hardware IDs in wacom pens.
-    id: u64,
+    pub(crate) id: PointerId,
+
+    /// Event phase.
+    pub(crate) phase: PointerEventPhase,
 
     /// Event location in the parent coordinate system.
-    location: Point2<f64>,
+    pub(crate) location: Point2<f64>,
 
     /// Event location in the window coordinate system.
-    window_location: Point2<f64>,
+    pub(crate) window_location: Point2<f64>,
 
     /// Pointer pressure, between 0 and 1.
-    pressure: f64,
+    pub(crate) pressure: f64,
 
     /// Pointer tilt, expressed as a unit vector. Will point from the tip of the pen to the far end
     /// of the pen.
     ///
     /// The Z axis points outwards from the screen.
-    tilt: Vector3<f64>,
+    pub(crate) tilt: Vector3<f64>,
 
     /// The device type that emitted this pointer event.
-    device: PointerDevice,
+    pub(crate) device: PointerDevice,
+
+    /// Modifier keys that are being pressed.
+    pub(crate) modifiers: KeyModifiers,
+
+    /// The number of consecutive clicks.
+    pub(crate) click_count: u32,
+}
+
+impl Pointer {
+    /// Unique ID of the pointer, or zero.
+    pub fn id(&self) -> PointerId {
+        self.id
+    }
+
+    /// Event phase.
+    pub fn phase(&self) -> PointerEventPhase {
+        self.phase
+    }
+
+    /// Event location in the coordinate system of the superview of the view receiving the event.
+    pub fn location(&self) -> Point2<f64> {
+        self.location
+    }
+
+    /// Event location in the window coordinate system.
+    pub fn window_location(&self) -> Point2<f64> {
+        self.window_location
+    }
+
+    /// Pointer pressure, between 0 and 1.
+    pub fn pressure(&self) -> f64 {
+        self.pressure
+    }
+
+    /// Pointer tilt, expressed as a unit vector pointing from the tip of the pen to the far end.
+    pub fn tilt(&self) -> Vector3<f64> {
+        self.tilt
+    }
+
+    /// The device type that emitted this pointer event.
+    pub fn device(&self) -> PointerDevice {
+        self.device
+    }
+
+    /// Modifier keys that are being pressed.
+    pub fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+
+    /// The number of consecutive clicks this pointer event stream is part of: 1 for a single
+    /// click, 2 for a double click, and so on.
+    pub fn click_count(&self) -> u32 {
+        self.click_count
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Pointer {
+    /// Creates a single-click pointer event from a cursor without modifiers, at a location that is
+    /// used both in the parent and the window coordinate system.
+    pub fn new(phase: PointerEventPhase, location: Point2<f64>) -> Pointer {
+        Pointer {
+            id: 0,
+            phase,
+            location,
+            window_location: location,
+            pressure: 1.,
+            tilt: Vector3::new(0., 0., 1.),
+            device: PointerDevice::Cursor,
+            modifiers: KeyModifiers::NONE,
+            click_count: 1,
+        }
+    }
+
+    /// Sets the unique ID of the pointer.
+    pub fn with_id(mut self, id: PointerId) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the location in the window coordinate system.
+    pub fn with_window_location(mut self, window_location: Point2<f64>) -> Self {
+        self.window_location = window_location;
+        self
+    }
+
+    /// Sets the pointer pressure.
+    pub fn with_pressure(mut self, pressure: f64) -> Self {
+        self.pressure = pressure;
+        self
+    }
+
+    /// Sets the pointer tilt.
+    pub fn with_tilt(mut self, tilt: Vector3<f64>) -> Self {
+        self.tilt = tilt;
+        self
+    }
+
+    /// Sets the device type.
+    pub fn with_device(mut self, device: PointerDevice) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Sets the modifier keys.
+    pub fn with_modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    /// Sets the number of consecutive clicks.
+    pub fn with_click_count(mut self, click_count: u32) -> Self {
+        self.click_count = click_count;
+        self
+    }
 }
 
 impl EventType for Pointer {
@@ -164,9 +421,83 @@ impl From<Event<Pointer>> for Pointer {
 /// A key event.
 #[derive(Debug)]
 pub struct Key {
-    modifiers: KeyModifiers,
-    code: KeyCode,
-    // TODO
+    pub(crate) modifiers: KeyModifiers,
+    pub(crate) code: KeyCode,
+    pub(crate) phase: KeyEventPhase,
+    pub(crate) chars: String,
+    pub(crate) chars_ignoring_modifiers: String,
+}
+
+impl Key {
+    /// Modifier keys that are being pressed.
+    pub fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+
+    /// The keyboard key.
+    pub fn code(&self) -> KeyCode {
+        self.code
+    }
+
+    /// Event phase.
+    pub fn phase(&self) -> KeyEventPhase {
+        self.phase
+    }
+
+    /// Returns true if the key was repeated due to being held down.
+    pub fn is_repeat(&self) -> bool {
+        self.phase == KeyEventPhase::Repeat
+    }
+
+    /// The characters the key produces in the current keyboard layout, with modifiers applied
+    /// (e.g. “+” for shift and = on a US layout). Empty for keys that don’t produce characters,
+    /// such as arrow keys.
+    ///
+    /// Use this to match shortcuts by character, since the key code for a character differs
+    /// between layouts.
+    pub fn chars(&self) -> &str {
+        &self.chars
+    }
+
+    /// The characters the key would produce in the current keyboard layout if no modifier keys
+    /// were pressed.
+    pub fn chars_ignoring_modifiers(&self) -> &str {
+        &self.chars_ignoring_modifiers
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Key {
+    /// Creates a key event without modifiers that doesn’t produce any characters.
+    pub fn new(code: KeyCode, phase: KeyEventPhase) -> Key {
+        Key {
+            modifiers: KeyModifiers::NONE,
+            code,
+            phase,
+            chars: String::new(),
+            chars_ignoring_modifiers: String::new(),
+        }
+    }
+
+    /// Sets the characters the key produces, both with and without modifiers.
+    pub fn with_chars(mut self, chars: &str) -> Self {
+        self.chars = chars.into();
+        self.chars_ignoring_modifiers = chars.into();
+        self
+    }
+
+    /// Sets the characters the key would produce if no modifier keys were pressed.
+    pub fn with_chars_ignoring_modifiers(mut self, chars: &str) -> Self {
+        self.chars_ignoring_modifiers = chars.into();
+        self
+    }
+
+    /// Sets the modifier keys.
+    pub fn with_modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
 }
 
 impl EventType for Key {
@@ -188,8 +519,10 @@ impl From<Event<Key>> for Key {
 ///
 /// This should not be emulated using keyboard events because cases like cross-application
 /// drag-and-drop would not cause key events to be fired beforehand.
+///
+/// Modifiers can be combined like bit flags, e.g. `KeyModifiers::PRIMARY | KeyModifiers::SHIFT`.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct KeyModifiers {
     /// Whether any shift key is pressed.
     shift: bool,
@@ -204,21 +537,206 @@ pub struct KeyModifiers {
     command: bool,
 }
 
+impl KeyModifiers {
+    /// No modifiers.
+    pub const NONE: KeyModifiers = KeyModifiers::new(false, false, false, false);
+    /// Only shift.
+    pub const SHIFT: KeyModifiers = KeyModifiers::new(true, false, false, false);
+    /// Only control.
+    pub const CONTROL: KeyModifiers = KeyModifiers::new(false, true, false, false);
+    /// Only option or alt.
+    pub const OPTION: KeyModifiers = KeyModifiers::new(false, false, true, false);
+    /// Only command or meta.
+    pub const COMMAND: KeyModifiers = KeyModifiers::new(false, false, false, true);
+
+    /// The modifier used for most shortcuts on the current platform: command on macOS, and
+    /// control elsewhere.
+    #[cfg(target_os = "macos")]
+    pub const PRIMARY: KeyModifiers = KeyModifiers::COMMAND;
+    /// The modifier used for most shortcuts on the current platform: command on macOS, and
+    /// control elsewhere.
+    #[cfg(not(target_os = "macos"))]
+    pub const PRIMARY: KeyModifiers = KeyModifiers::CONTROL;
+
+    /// Creates a modifier state from whether each modifier is pressed.
+    pub const fn new(shift: bool, control: bool, option: bool, command: bool) -> KeyModifiers {
+        KeyModifiers {
+            shift,
+            control,
+            option,
+            command,
+        }
+    }
+
+    /// Returns true if no modifiers are pressed.
+    pub fn is_empty(&self) -> bool {
+        *self == KeyModifiers::NONE
+    }
+
+    /// Returns true if all of the given modifiers are pressed (and possibly others).
+    pub fn contains(&self, other: KeyModifiers) -> bool {
+        *self & other == other
+    }
+
+    /// Whether the primary modifier is pressed; see [`PRIMARY`](Self::PRIMARY).
+    pub fn primary(&self) -> bool {
+        self.contains(KeyModifiers::PRIMARY)
+    }
+
+    /// Whether any shift key is pressed.
+    pub fn shift(&self) -> bool {
+        self.shift
+    }
+
+    /// Whether any control key is pressed.
+    pub fn control(&self) -> bool {
+        self.control
+    }
+
+    /// Whether any option key or alt key is pressed.
+    pub fn option(&self) -> bool {
+        self.option
+    }
+
+    /// Whether any command key or meta key is pressed.
+    pub fn command(&self) -> bool {
+        self.command
+    }
+}
+
+impl ops::BitOr for KeyModifiers {
+    type Output = KeyModifiers;
+    fn bitor(self, other: KeyModifiers) -> KeyModifiers {
+        KeyModifiers {
+            shift: self.shift || other.shift,
+            control: self.control || other.control,
+            option: self.option || other.option,
+            command: self.command || other.command,
+        }
+    }
+}
+
+impl ops::BitOrAssign for KeyModifiers {
+    fn bitor_assign(&mut self, other: KeyModifiers) {
+        *self = *self | other;
+    }
+}
+
+impl ops::BitAnd for KeyModifiers {
+    type Output = KeyModifiers;
+    fn bitand(self, other: KeyModifiers) -> KeyModifiers {
+        KeyModifiers {
+            shift: self.shift && other.shift,
+            control: self.control && other.control,
+            option: self.option && other.option,
+            command: self.command && other.command,
+        }
+    }
+}
+
+impl ops::BitAndAssign for KeyModifiers {
+    fn bitand_assign(&mut self, other: KeyModifiers) {
+        *self = *self & other;
+    }
+}
+
 /// A scroll event.
 #[derive(Debug)]
 pub struct Scroll {
     /// Event location in the parent coordinate system.
-    location: Point2<f64>,
+    pub(crate) location: Point2<f64>,
+
+    /// Event location in the window coordinate system.
+    pub(crate) window_location: Point2<f64>,
+
+    /// Scroll delta in points.
+    pub(crate) delta: Vector2<f64>,
+
+    /// If true, the scrolling device is discrete (e.g. a mouse wheel that scrolls in increments)
+    /// and may benefit from smooth scrolling.
+    pub(crate) is_discrete: bool,
+
+    /// If true, the delta was inverted for natural scrolling.
+    pub(crate) is_direction_inverted: bool,
+
+    /// The modifier keys that were pressed.
+    pub(crate) modifiers: KeyModifiers,
+}
+
+impl Scroll {
+    /// Event location in the coordinate system of the superview of the view receiving the event.
+    pub fn location(&self) -> Point2<f64> {
+        self.location
+    }
 
     /// Event location in the window coordinate system.
-    window_location: Point2<f64>,
+    pub fn window_location(&self) -> Point2<f64> {
+        self.window_location
+    }
 
     /// Scroll delta in points.
-    delta: Vector2<f64>,
+    pub fn delta(&self) -> Vector2<f64> {
+        self.delta
+    }
 
     /// If true, the scrolling device is discrete (e.g. a mouse wheel that scrolls in increments)
     /// and may benefit from smooth scrolling.
-    is_discrete: bool,
+    pub fn is_discrete(&self) -> bool {
+        self.is_discrete
+    }
+
+    /// If true, the delta was inverted for natural scrolling, so content moves in the same
+    /// direction as the user’s fingers. Views that don’t scroll content (like sliders) may want
+    /// to invert it back.
+    pub fn is_direction_inverted(&self) -> bool {
+        self.is_direction_inverted
+    }
+
+    /// The modifier keys that were pressed, e.g. to zoom instead of scrolling.
+    pub fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Scroll {
+    /// Creates a scroll event from a continuous device without modifiers, at a location that is
+    /// used both in the parent and the window coordinate system.
+    pub fn new(location: Point2<f64>, delta: Vector2<f64>) -> Scroll {
+        Scroll {
+            location,
+            window_location: location,
+            delta,
+            is_discrete: false,
+            is_direction_inverted: false,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    /// Sets the location in the window coordinate system.
+    pub fn with_window_location(mut self, window_location: Point2<f64>) -> Self {
+        self.window_location = window_location;
+        self
+    }
+
+    /// Sets whether the scrolling device is discrete.
+    pub fn with_discrete(mut self, is_discrete: bool) -> Self {
+        self.is_discrete = is_discrete;
+        self
+    }
+
+    /// Sets whether the delta was inverted for natural scrolling.
+    pub fn with_direction_inverted(mut self, is_direction_inverted: bool) -> Self {
+        self.is_direction_inverted = is_direction_inverted;
+        self
+    }
+
+    /// Sets the modifier keys.
+    pub fn with_modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
 }
 
 impl EventType for Scroll {
@@ -236,8 +754,457 @@ impl From<Event<Scroll>> for Scroll {
     }
 }
 
+/// A tap event, synthesized from a pointer that was pressed and released in place.
+///
+/// A tap is sent after the pointer is released, if it didn’t move further than the device’s
+/// [tap slop](PointerDevice::tap_slop), was released within [`TAP_TIMEOUT`], and the pointer event
+/// stream wasn’t canceled. Taps target the views that received the pointer-down and bubble up
+/// like other events.
+///
+/// [`TAP_TIMEOUT`]: crate::dispatch::TAP_TIMEOUT
+#[derive(Debug)]
+pub struct Tap {
+    /// Unique ID of the pointer, or zero.
+    pub(crate) id: PointerId,
+
+    /// Event location in the parent coordinate system.
+    pub(crate) location: Point2<f64>,
+
+    /// Event location in the window coordinate system.
+    pub(crate) window_location: Point2<f64>,
+
+    /// The device type that emitted the pointer events.
+    pub(crate) device: PointerDevice,
+
+    /// Modifier keys that were being pressed when the pointer was released.
+    pub(crate) modifiers: KeyModifiers,
+
+    /// The number of consecutive clicks.
+    pub(crate) click_count: u32,
+}
+
+impl Tap {
+    /// Unique ID of the pointer, or zero.
+    pub fn id(&self) -> PointerId {
+        self.id
+    }
+
+    /// Event location in the coordinate system of the superview of the view receiving the event.
+    pub fn location(&self) -> Point2<f64> {
+        self.location
+    }
+
+    /// Event location in the window coordinate system.
+    pub fn window_location(&self) -> Point2<f64> {
+        self.window_location
+    }
+
+    /// The device type that emitted the pointer events.
+    pub fn device(&self) -> PointerDevice {
+        self.device
+    }
+
+    /// Modifier keys that were being pressed when the pointer was released.
+    pub fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+
+    /// The number of consecutive clicks this tap is part of: 1 for a single tap, 2 for a double
+    /// tap, and so on.
+    pub fn click_count(&self) -> u32 {
+        self.click_count
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Tap {
+    /// Creates a single tap from a cursor without modifiers, at a location that is used both in
+    /// the parent and the window coordinate system.
+    pub fn new(location: Point2<f64>) -> Tap {
+        Tap {
+            id: 0,
+            location,
+            window_location: location,
+            device: PointerDevice::Cursor,
+            modifiers: KeyModifiers::NONE,
+            click_count: 1,
+        }
+    }
+
+    /// Sets the unique ID of the pointer.
+    pub fn with_id(mut self, id: PointerId) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the location in the window coordinate system.
+    pub fn with_window_location(mut self, window_location: Point2<f64>) -> Self {
+        self.window_location = window_location;
+        self
+    }
+
+    /// Sets the device type.
+    pub fn with_device(mut self, device: PointerDevice) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Sets the modifier keys.
+    pub fn with_modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    /// Sets the number of consecutive clicks.
+    pub fn with_click_count(mut self, click_count: u32) -> Self {
+        self.click_count = click_count;
+        self
+    }
+}
+
+impl EventType for Tap {
+    fn location(&self) -> Option<Point2<f64>> {
+        Some(self.location)
+    }
+    fn type_id() -> EventTypeId {
+        EventTypeId::Tap
+    }
+}
+
+impl From<Event<Tap>> for Tap {
+    fn from(this: Event<Tap>) -> Self {
+        this.data
+    }
+}
+
+/// A file drag event, for files being dragged onto the window from e.g. the file manager.
+///
+/// Views receive `Entered` when the drag moves over them, `Moved` while it stays over them,
+/// and then either `Exited` or `Dropped`. To accept the files, mark `Entered` and `Moved` events
+/// as [handled](crate::dispatch::EventContext::set_handled); the backend will then show that the
+/// files can be dropped.
+#[derive(Debug)]
+pub struct FileDrop {
+    pub(crate) phase: DragEventPhase,
+    pub(crate) location: Point2<f64>,
+    pub(crate) window_location: Point2<f64>,
+    pub(crate) paths: Arc<[PathBuf]>,
+}
+
+impl FileDrop {
+    /// Event phase.
+    pub fn phase(&self) -> DragEventPhase {
+        self.phase
+    }
+
+    /// Event location in the coordinate system of the superview of the view receiving the event.
+    pub fn location(&self) -> Point2<f64> {
+        self.location
+    }
+
+    /// Event location in the window coordinate system.
+    pub fn window_location(&self) -> Point2<f64> {
+        self.window_location
+    }
+
+    /// Paths of the dragged files.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl FileDrop {
+    /// Creates a file drag event at a location that is used both in the parent and the window
+    /// coordinate system.
+    pub fn new(phase: DragEventPhase, location: Point2<f64>, paths: Vec<PathBuf>) -> FileDrop {
+        FileDrop {
+            phase,
+            location,
+            window_location: location,
+            paths: paths.into(),
+        }
+    }
+
+    /// Sets the location in the window coordinate system.
+    pub fn with_window_location(mut self, window_location: Point2<f64>) -> Self {
+        self.window_location = window_location;
+        self
+    }
+}
+
+impl EventType for FileDrop {
+    fn location(&self) -> Option<Point2<f64>> {
+        Some(self.location)
+    }
+    fn type_id() -> EventTypeId {
+        EventTypeId::FileDrop
+    }
+}
+
+impl From<Event<FileDrop>> for FileDrop {
+    fn from(this: Event<FileDrop>) -> Self {
+        this.data
+    }
+}
+
+/// A media key event.
+///
+/// Like key events, media key events are sent to the key view.
+#[derive(Debug)]
+pub struct Media {
+    pub(crate) key: MediaKey,
+    pub(crate) phase: KeyEventPhase,
+}
+
+impl Media {
+    /// The media key.
+    pub fn key(&self) -> MediaKey {
+        self.key
+    }
+
+    /// Event phase.
+    pub fn phase(&self) -> KeyEventPhase {
+        self.phase
+    }
+
+    /// Returns true if the key was repeated due to being held down.
+    pub fn is_repeat(&self) -> bool {
+        self.phase == KeyEventPhase::Repeat
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Media {
+    /// Creates a media key event.
+    pub fn new(key: MediaKey, phase: KeyEventPhase) -> Media {
+        Media { key, phase }
+    }
+}
+
+impl EventType for Media {
+    fn location(&self) -> Option<Point2<f64>> {
+        None
+    }
+    fn type_id() -> EventTypeId {
+        EventTypeId::Media
+    }
+}
+
+impl From<Event<Media>> for Media {
+    fn from(this: Event<Media>) -> Self {
+        this.data
+    }
+}
+
+/// Media keys, as found on keyboards, headphones, and remotes.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKey {
+    PlayPause = 0,
+    Next = 1,
+    Previous = 2,
+    FastForward = 3,
+    Rewind = 4,
+    VolumeUp = 5,
+    VolumeDown = 6,
+    Mute = 7,
+}
+
+/// A game controller event.
+///
+/// Like key events, game controller events are sent to the key view.
+#[derive(Debug)]
+pub struct Controller {
+    pub(crate) controller: ControllerId,
+    pub(crate) input: ControllerInput,
+}
+
+impl Controller {
+    /// The controller that generated this event.
+    pub fn controller(&self) -> ControllerId {
+        self.controller
+    }
+
+    /// What happened.
+    pub fn input(&self) -> ControllerInput {
+        self.input
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl Controller {
+    /// Creates a game controller event.
+    pub fn new(controller: ControllerId, input: ControllerInput) -> Controller {
+        Controller { controller, input }
+    }
+}
+
+impl EventType for Controller {
+    fn location(&self) -> Option<Point2<f64>> {
+        None
+    }
+    fn type_id() -> EventTypeId {
+        EventTypeId::Controller
+    }
+}
+
+impl From<Event<Controller>> for Controller {
+    fn from(this: Event<Controller>) -> Self {
+        this.data
+    }
+}
+
+/// Game controller input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControllerInput {
+    /// The controller was connected.
+    Connected,
+    /// The controller was disconnected.
+    Disconnected,
+    /// A button changed.
+    Button {
+        button: ControllerButton,
+        /// How far the button is pressed, between 0 and 1. Buttons that aren’t pressure-sensitive
+        /// are always either 0 or 1.
+        value: f64,
+        /// Whether the button counts as pressed.
+        is_pressed: bool,
+    },
+    /// A thumbstick axis changed.
+    Axis {
+        axis: ControllerAxis,
+        /// The axis value, between -1 and 1.
+        value: f64,
+    },
+}
+
+/// Game controller buttons, named after their positions on a standard gamepad.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControllerButton {
+    /// The bottom face button.
+    A = 0,
+    /// The right face button.
+    B = 1,
+    /// The left face button.
+    X = 2,
+    /// The top face button.
+    Y = 3,
+    LeftShoulder = 4,
+    RightShoulder = 5,
+    LeftTrigger = 6,
+    RightTrigger = 7,
+    DpadUp = 8,
+    DpadDown = 9,
+    DpadLeft = 10,
+    DpadRight = 11,
+    /// Pressing down the left thumbstick.
+    LeftThumbstick = 12,
+    /// Pressing down the right thumbstick.
+    RightThumbstick = 13,
+    Menu = 14,
+    Options = 15,
+}
+
+/// Game controller thumbstick axes.
+///
+/// Like the window coordinate system, positive x points right and positive y points down.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControllerAxis {
+    LeftX = 0,
+    LeftY = 1,
+    RightX = 2,
+    RightY = 3,
+}
+
+/// A pointing device event, e.g. when the user brings a pen close to a tablet or flips it over to
+/// use the eraser, so apps can switch tools before the device touches the surface.
+///
+/// Like key events, pointing device events are sent to the key view.
+#[derive(Debug)]
+pub struct PointingDevice {
+    pub(crate) device: PointerDevice,
+    pub(crate) unique_id: PointerId,
+    pub(crate) change: DeviceChange,
+}
+
+impl PointingDevice {
+    /// The kind of device.
+    pub fn device(&self) -> PointerDevice {
+        self.device
+    }
+
+    /// The unique ID of the device, as in pointer and hover events; may be zero.
+    ///
+    /// If nonzero, it identifies the physical device (e.g. one of several pens), and is stable.
+    pub fn unique_id(&self) -> PointerId {
+        self.unique_id
+    }
+
+    /// What happened.
+    pub fn change(&self) -> DeviceChange {
+        self.change
+    }
+}
+
+/// Constructors for testing event handlers (with the `testing` feature enabled).
+#[cfg(feature = "testing")]
+impl PointingDevice {
+    /// Creates a pointing device event.
+    pub fn new(
+        device: PointerDevice,
+        unique_id: PointerId,
+        change: DeviceChange,
+    ) -> PointingDevice {
+        PointingDevice {
+            device,
+            unique_id,
+            change,
+        }
+    }
+}
+
+impl EventType for PointingDevice {
+    fn location(&self) -> Option<Point2<f64>> {
+        None
+    }
+    fn type_id() -> EventTypeId {
+        EventTypeId::PointingDevice
+    }
+}
+
+impl From<Event<PointingDevice>> for PointingDevice {
+    fn from(this: Event<PointingDevice>) -> Self {
+        this.data
+    }
+}
+
+/// Pointing device changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceChange {
+    /// The device entered the proximity of a tablet.
+    EnteredProximity,
+    /// The device left the proximity of a tablet.
+    LeftProximity,
+    /// The device became the active pointing device, i.e. the one that most recently entered
+    /// proximity or generated hover or pointer events.
+    Activated,
+}
+
+/// An event handler function; see [`EventHandler`].
+pub type HandlerFn<Type> = dyn FnMut(Event<Type>, &mut EventContext) + Send;
+
 /// An event handler.
-pub struct EventHandler<Type>(Arc<Mutex<dyn FnMut(Event<Type>) + Send>>);
+///
+/// Handlers are called with the event and a context through which they can influence further
+/// dispatch.
+pub struct EventHandler<Type>(Arc<Mutex<HandlerFn<Type>>>);
 
 impl<T> Clone for EventHandler<T> {
     fn clone(&self) -> Self {
@@ -246,9 +1213,13 @@ impl<T> Clone for EventHandler<T> {
 }
 
 impl<T: EventType> EventHandler<T> {
-    pub fn new<F: 'static + FnMut(Event<T>) + Send>(handler: F) -> Self {
+    pub fn new<F: 'static + FnMut(Event<T>, &mut EventContext) + Send>(handler: F) -> Self {
         EventHandler(Arc::new(Mutex::new(handler)))
     }
+
+    pub(crate) fn call(&self, event: Event<T>, context: &mut EventContext) {
+        (self.0.lock())(event, context)
+    }
 }
 
 impl<T: EventType> fmt::Debug for EventHandler<T> {
@@ -257,6 +1228,40 @@ impl<T: EventType> fmt::Debug for EventHandler<T> {
     }
 }
 
+/// The event handlers of a view.
+#[derive(Debug, Clone, Default)]
+pub struct EventHandlers {
+    pub pointer: Option<EventHandler<Pointer>>,
+    pub hover: Option<EventHandler<Hover>>,
+    pub key: Option<EventHandler<Key>>,
+    pub scroll: Option<EventHandler<Scroll>>,
+    pub tap: Option<EventHandler<Tap>>,
+    pub media: Option<EventHandler<Media>>,
+    pub controller: Option<EventHandler<Controller>>,
+    pub file_drop: Option<EventHandler<FileDrop>>,
+    pub pointing_device: Option<EventHandler<PointingDevice>>,
+}
+
+impl EventHandlers {
+    /// Returns the number of handlers that are set.
+    pub fn count(&self) -> usize {
+        [
+            self.pointer.is_some(),
+            self.hover.is_some(),
+            self.key.is_some(),
+            self.scroll.is_some(),
+            self.tap.is_some(),
+            self.media.is_some(),
+            self.controller.is_some(),
+            self.file_drop.is_some(),
+            self.pointing_device.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+    }
+}
+
 /// Keyboard layout-independent identifiers for keyboard keys.
 ///
 /// Some obscure keys may be missing.
@@ -377,3 +1382,16 @@ pub enum KeyCode {
     NumpadEnter = 0x81,
     NumpadComma = 0x82,
 }
+
+impl KeyCode {
+    /// Returns the key code with the given value, if there is one.
+    pub fn from_u8(code: u8) -> Option<KeyCode> {
+        match code {
+            // these ranges are exactly the discriminants of KeyCode, which is repr(u8)
+            0x1..=0x1A | 0x20..=0x63 | 0x70..=0x82 => {
+                Some(unsafe { mem::transmute::<u8, KeyCode>(code) })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! Watching files and directories for changes (with the `file-watcher` feature enabled).
+//!
+//! Changes are detected on a background thread using the platform’s file system notifications
+//! (FSEvents on macOS, inotify on Linux, and so on) and queued until
+//! [`FileWatcher::dispatch_events`] is called on the UI thread, so listeners can safely change
+//! state and request a re-render. Views usually watch files in an effect:
+//!
+//! ```no_run
+//! # use birb::file_watcher::FileWatcher;
+//! # use birb::Context;
+//! # fn effects(context: &Context<FileWatcher>, path: std::path::PathBuf) {
+//! let watcher = context.ctx().clone();
+//! context.effect(path.clone(), move || {
+//!     let id = watcher
+//!         .watch(&path, false, |event| println!("{:?}", event))
+//!         .ok();
+//!     move || {
+//!         if let Some(id) = id {
+//!             watcher.unwatch(id);
+//!         }
+//!     }
+//! });
+//! # }
+//! ```
+
+use core::fmt;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What happened to the files in a [`FileEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileEventKind {
+    Created,
+    Modified,
+    Removed,
+    /// Anything else, e.g. access or changes the platform doesn’t describe further.
+    Other,
+}
+
+/// A change to watched files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEvent {
+    pub kind: FileEventKind,
+    /// The affected paths; for renames, the old path comes first.
+    pub paths: Vec<PathBuf>,
+}
+
+impl FileEvent {
+    fn from_notify(event: notify::Event) -> FileEvent {
+        use notify::EventKind;
+        let kind = match event.kind {
+            EventKind::Create(_) => FileEventKind::Created,
+            EventKind::Modify(_) => FileEventKind::Modified,
+            EventKind::Remove(_) => FileEventKind::Removed,
+            EventKind::Any | EventKind::Access(_) | EventKind::Other => FileEventKind::Other,
+        };
+        FileEvent {
+            kind,
+            paths: event.paths,
+        }
+    }
+}
+
+/// Errors that may occur when watching a path.
+#[derive(Debug)]
+pub enum WatchError {
+    /// The path could not be resolved, e.g. because it doesn’t exist.
+    Io(io::Error),
+    /// The platform’s file system notifications failed.
+    Backend(notify::Error),
+}
+
+impl From<io::Error> for WatchError {
+    fn from(err: io::Error) -> WatchError {
+        WatchError::Io(err)
+    }
+}
+
+impl From<notify::Error> for WatchError {
+    fn from(err: notify::Error) -> WatchError {
+        WatchError::Backend(err)
+    }
+}
+
+/// A file event listener.
+pub type FileListener = dyn Fn(&FileEvent) + Send + Sync;
+
+/// Identifies a watch; see [`FileWatcher::watch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchId(u64);
+
+struct Watch {
+    path: PathBuf,
+    recursive: bool,
+    listener: Arc<FileListener>,
+}
+
+impl Watch {
+    /// Returns true if a change to the path concerns this watch.
+    fn matches(&self, path: &Path) -> bool {
+        if self.recursive {
+            path.starts_with(&self.path)
+        } else {
+            path == self.path || path.parent() == Some(&self.path)
+        }
+    }
+}
+
+/// Returns the mode a path needs to be watched in, given its number of non-recursive and
+/// recursive watches.
+fn watch_mode(counts: (usize, usize)) -> Option<RecursiveMode> {
+    match counts {
+        (_, recursive) if recursive > 0 => Some(RecursiveMode::Recursive),
+        (non_recursive, _) if non_recursive > 0 => Some(RecursiveMode::NonRecursive),
+        _ => None,
+    }
+}
+
+struct WatcherInner {
+    watcher: RecommendedWatcher,
+    watches: HashMap<WatchId, Watch>,
+    /// Number of non-recursive and recursive watches per path, since the platform watcher only
+    /// watches each path once.
+    watched_paths: HashMap<PathBuf, (usize, usize)>,
+    next_id: u64,
+    generation: usize,
+}
+
+impl WatcherInner {
+    /// Changes how the platform watcher watches a path.
+    fn set_watch_mode(
+        &mut self,
+        path: &Path,
+        mode: Option<RecursiveMode>,
+        new_mode: Option<RecursiveMode>,
+    ) -> Result<(), WatchError> {
+        if mode == new_mode {
+            return Ok(());
+        }
+        if mode.is_some() {
+            self.watcher.unwatch(path)?;
+        }
+        if let Some(new_mode) = new_mode {
+            self.watcher.watch(path, new_mode)?;
+        }
+        Ok(())
+    }
+}
+
+/// Events that arrived on the background thread but haven’t been dispatched yet.
+#[derive(Default)]
+struct Queue {
+    events: Vec<FileEvent>,
+    waker: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+/// Watches files and directories, meant to be passed down the view tree in the context.
+///
+/// This is a cheap handle; clones share the same state. Listeners are only ever called from
+/// [`dispatch_events`](Self::dispatch_events), which should be called on the UI thread whenever
+/// the [waker](Self::set_waker) is called or once per frame.
+#[derive(Clone)]
+pub struct FileWatcher {
+    inner: Arc<Mutex<WatcherInner>>,
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl FileWatcher {
+    /// Creates a file watcher using the recommended backend for the platform.
+    pub fn new() -> Result<FileWatcher, WatchError> {
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        let watcher = {
+            let queue = Arc::clone(&queue);
+            notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                // errors from the background thread have no listener to go to
+                if let Ok(event) = result {
+                    let waker = {
+                        let mut queue = queue.lock();
+                        queue.events.push(FileEvent::from_notify(event));
+                        queue.waker.clone()
+                    };
+                    if let Some(waker) = waker {
+                        waker();
+                    }
+                }
+            })?
+        };
+        Ok(FileWatcher {
+            inner: Arc::new(Mutex::new(WatcherInner {
+                watcher,
+                watches: HashMap::new(),
+                watched_paths: HashMap::new(),
+                next_id: 0,
+                generation: 0,
+            })),
+            queue,
+        })
+    }
+
+    /// Starts watching a file or directory.
+    ///
+    /// For directories, changes to the directory’s entries are reported as well, and if
+    /// `recursive` is true, changes to anything inside. The listener will be called from
+    /// [`dispatch_events`](Self::dispatch_events).
+    pub fn watch<F>(
+        &self,
+        path: impl AsRef<Path>,
+        recursive: bool,
+        listener: F,
+    ) -> Result<WatchId, WatchError>
+    where
+        F: 'static + Fn(&FileEvent) + Send + Sync,
+    {
+        // platforms report canonical paths
+        let path = fs::canonicalize(path)?;
+
+        let mut inner = self.inner.lock();
+        let counts = inner.watched_paths.get(&path).copied().unwrap_or((0, 0));
+        let new_counts = if recursive {
+            (counts.0, counts.1 + 1)
+        } else {
+            (counts.0 + 1, counts.1)
+        };
+        inner.set_watch_mode(&path, watch_mode(counts), watch_mode(new_counts))?;
+        inner.watched_paths.insert(path.clone(), new_counts);
+
+        let id = WatchId(inner.next_id);
+        inner.next_id += 1;
+        inner.watches.insert(
+            id,
+            Watch {
+                path,
+                recursive,
+                listener: Arc::new(listener),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Stops a watch. Does nothing if it was already stopped.
+    pub fn unwatch(&self, id: WatchId) {
+        let mut inner = self.inner.lock();
+        let watch = match inner.watches.remove(&id) {
+            Some(watch) => watch,
+            None => return,
+        };
+        let counts = inner.watched_paths[&watch.path];
+        let new_counts = if watch.recursive {
+            (counts.0, counts.1 - 1)
+        } else {
+            (counts.0 - 1, counts.1)
+        };
+        // the path may have been removed, which already ends the platform’s watch
+        let _ = inner.set_watch_mode(&watch.path, watch_mode(counts), watch_mode(new_counts));
+        if new_counts == (0, 0) {
+            inner.watched_paths.remove(&watch.path);
+        } else {
+            inner.watched_paths.insert(watch.path, new_counts);
+        }
+    }
+
+    /// Sets a function that will be called on the background thread whenever events arrive, e.g.
+    /// to wake up the UI thread’s event loop so it calls [`dispatch_events`](Self::dispatch_events).
+    pub fn set_waker<F>(&self, waker: F)
+    where
+        F: 'static + Fn() + Send + Sync,
+    {
+        self.queue.lock().waker = Some(Arc::new(waker));
+    }
+
+    /// Calls listeners for all events that have arrived since the last call. Should be called on
+    /// the UI thread.
+    ///
+    /// Returns true if any listener was called.
+    pub fn dispatch_events(&self) -> bool {
+        let events = mem::take(&mut self.queue.lock().events);
+        let mut dispatched = false;
+        for event in events {
+            // clone the listeners so they can add and remove watches
+            let listeners: Vec<_> = self
+                .inner
+                .lock()
+                .watches
+                .values()
+                .filter(|watch| event.paths.iter().any(|path| watch.matches(path)))
+                .map(|watch| Arc::clone(&watch.listener))
+                .collect();
+            for listener in listeners {
+                listener(&event);
+                dispatched = true;
+            }
+        }
+        if dispatched {
+            self.inner.lock().generation += 1;
+        }
+        dispatched
+    }
+
+    /// A counter that is incremented every time events are dispatched to listeners.
+    pub fn generation(&self) -> usize {
+        self.inner.lock().generation
+    }
+}
+
+impl fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock();
+        f.debug_struct("FileWatcher")
+            .field(
+                "watched_paths",
+                &inner.watched_paths.keys().collect::<Vec<_>>(),
+            )
+            .field("pending_events", &self.queue.lock().events.len())
+            .finish()
+    }
+}
+
+/// Context types that provide a file watcher.
+pub trait FileWatcherContext {
+    fn file_watcher(&self) -> &FileWatcher;
+}
+
+impl FileWatcherContext for FileWatcher {
+    fn file_watcher(&self) -> &FileWatcher {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Creates an empty directory for a test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("birb-watcher-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    /// Queues an event as if it came from the background thread.
+    fn push(watcher: &FileWatcher, kind: FileEventKind, path: &Path) {
+        watcher.queue.lock().events.push(FileEvent {
+            kind,
+            paths: vec![path.to_path_buf()],
+        });
+    }
+
+    /// A listener that records its events.
+    fn recorder() -> (
+        Arc<Mutex<Vec<FileEvent>>>,
+        impl Fn(&FileEvent) + Send + Sync,
+    ) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener_events = Arc::clone(&events);
+        (events, move |event: &FileEvent| {
+            listener_events.lock().push(event.clone())
+        })
+    }
+
+    #[test]
+    fn paths_are_watched_as_needed() {
+        assert_eq!(watch_mode((0, 0)), None);
+        assert_eq!(watch_mode((2, 0)), Some(RecursiveMode::NonRecursive));
+        assert_eq!(watch_mode((0, 1)), Some(RecursiveMode::Recursive));
+        assert_eq!(watch_mode((1, 1)), Some(RecursiveMode::Recursive));
+
+        let dir = temp_dir("counts");
+        let watcher = FileWatcher::new().unwrap();
+        let a = watcher.watch(&dir, false, |_| ()).unwrap();
+        let b = watcher.watch(&dir, true, |_| ()).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(watcher.inner.lock().watched_paths[&dir], (1, 1));
+
+        watcher.unwatch(b);
+        watcher.unwatch(b);
+        assert_eq!(watcher.inner.lock().watched_paths[&dir], (1, 0));
+        watcher.unwatch(a);
+        assert!(watcher.inner.lock().watched_paths.is_empty());
+
+        let missing = watcher.watch(dir.join("missing"), false, |_| ());
+        let _ = fs::remove_dir_all(&dir);
+        assert!(matches!(missing, Err(WatchError::Io(_))));
+    }
+
+    #[test]
+    fn events_go_to_matching_listeners() {
+        let dir = temp_dir("listeners");
+        fs::create_dir(dir.join("sub")).unwrap();
+        let watcher = FileWatcher::new().unwrap();
+        let (shallow_events, listener) = recorder();
+        let shallow = watcher.watch(&dir, false, listener).unwrap();
+        let (deep_events, listener) = recorder();
+        watcher.watch(&dir, true, listener).unwrap();
+
+        assert!(!watcher.dispatch_events());
+        assert_eq!(watcher.generation(), 0);
+
+        push(&watcher, FileEventKind::Created, &dir.join("a"));
+        push(&watcher, FileEventKind::Modified, &dir.join("sub/b"));
+        push(&watcher, FileEventKind::Removed, Path::new("/elsewhere"));
+        assert!(watcher.dispatch_events());
+        assert_eq!(watcher.generation(), 1);
+
+        let created = FileEvent {
+            kind: FileEventKind::Created,
+            paths: vec![dir.join("a")],
+        };
+        let modified = FileEvent {
+            kind: FileEventKind::Modified,
+            paths: vec![dir.join("sub/b")],
+        };
+        assert_eq!(*deep_events.lock(), [created.clone(), modified]);
+        assert_eq!(*shallow_events.lock(), [created]);
+
+        // events only concerning other paths don’t count
+        watcher.unwatch(shallow);
+        push(&watcher, FileEventKind::Other, Path::new("/elsewhere"));
+        let dispatched = watcher.dispatch_events();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(!dispatched);
+        assert_eq!(watcher.generation(), 1);
+        assert_eq!(shallow_events.lock().len(), 1);
+    }
+
+    #[test]
+    fn listeners_may_stop_their_own_watch() {
+        let dir = temp_dir("unwatch");
+        let watcher = FileWatcher::new().unwrap();
+        let id = Arc::new(Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let listener = {
+            let (watcher, id, calls) = (watcher.clone(), Arc::clone(&id), Arc::clone(&calls));
+            move |_: &FileEvent| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                if let Some(id) = id.lock().take() {
+                    watcher.unwatch(id);
+                }
+            }
+        };
+        *id.lock() = Some(watcher.watch(&dir, false, listener).unwrap());
+
+        push(&watcher, FileEventKind::Modified, &dir);
+        push(&watcher, FileEventKind::Modified, &dir);
+        let dispatched = watcher.dispatch_events();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(dispatched);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(watcher.inner.lock().watches.is_empty());
+    }
+
+    #[test]
+    fn changes_on_disk_wake_and_reach_listeners() {
+        let dir = temp_dir("disk");
+        let watcher = FileWatcher::new().unwrap();
+        let wakes = Arc::new(AtomicUsize::new(0));
+        {
+            let wakes = Arc::clone(&wakes);
+            watcher.set_waker(move || {
+                wakes.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        let (events, listener) = recorder();
+        watcher.watch(&dir, false, listener).unwrap();
+
+        let file = dir.join("file.txt");
+        fs::write(&file, "hello").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            watcher.dispatch_events();
+            if events
+                .lock()
+                .iter()
+                .any(|event| event.paths.contains(&file))
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(wakes.load(Ordering::SeqCst) > 0);
+        let events = events.lock();
+        let event = events.iter().find(|event| event.paths.contains(&file));
+        assert!(
+            matches!(event, Some(event) if event.kind != FileEventKind::Removed),
+            "{:?}",
+            events
+        );
+        assert!(watcher.generation() > 0);
+    }
+}
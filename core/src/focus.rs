@@ -0,0 +1,398 @@
+//! Keyboard focus.
+//!
+//! Views declare whether they can receive keyboard focus with [`View::focus`]. A view may also
+//! be a focus group (e.g. a toolbar or a list): the group is a single tab stop, and arrow keys
+//! move focus between the focusable views inside it, like Full Keyboard Access on macOS.
+//!
+//! [`View::focus`]: crate::View::focus
+
+use crate::view::ViewId;
+use core::fmt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Directions in which focus can be moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FocusDirection {
+    /// The next tab stop (Tab).
+    Next,
+    /// The previous tab stop (Shift+Tab).
+    Previous,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// A custom arrow-key navigation function for a focus group.
+///
+/// Called with the index of the focused member, the number of members, and the direction, and
+/// returns the index of the member that should be focused next (or None to not move focus).
+pub type FocusNavigationFn = dyn Fn(usize, usize, FocusDirection) -> Option<usize> + Send + Sync;
+
+/// How arrow keys move focus inside a focus group.
+#[derive(Clone)]
+pub enum GroupNavigation {
+    /// Left and right arrow keys move focus.
+    Horizontal,
+    /// Up and down arrow keys move focus.
+    Vertical,
+    /// Members are laid out in a grid with the given number of columns.
+    Grid(usize),
+    /// Custom navigation.
+    Custom(Arc<FocusNavigationFn>),
+}
+
+impl fmt::Debug for GroupNavigation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupNavigation::Horizontal => write!(f, "Horizontal"),
+            GroupNavigation::Vertical => write!(f, "Vertical"),
+            GroupNavigation::Grid(columns) => write!(f, "Grid({})", columns),
+            GroupNavigation::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// A focus group, which is a single tab stop containing several focusable views.
+#[derive(Debug, Clone)]
+pub struct FocusGroup {
+    /// Arrow-key navigation inside the group.
+    pub navigation: GroupNavigation,
+
+    /// If true, moving past the last member moves to the first one and vice versa.
+    pub wraps: bool,
+}
+
+impl FocusGroup {
+    /// Returns the index of the member that should be focused when moving in a direction.
+    fn navigate(&self, index: usize, count: usize, direction: FocusDirection) -> Option<usize> {
+        let step = match (&self.navigation, direction) {
+            (GroupNavigation::Custom(f), direction) => {
+                return f(index, count, direction).filter(|i| *i < count)
+            }
+            (GroupNavigation::Horizontal, FocusDirection::Left)
+            | (GroupNavigation::Vertical, FocusDirection::Up)
+            | (GroupNavigation::Grid(_), FocusDirection::Left) => -1,
+            (GroupNavigation::Horizontal, FocusDirection::Right)
+            | (GroupNavigation::Vertical, FocusDirection::Down)
+            | (GroupNavigation::Grid(_), FocusDirection::Right) => 1,
+            (GroupNavigation::Grid(columns), FocusDirection::Up) => -(*columns.max(&1) as isize),
+            (GroupNavigation::Grid(columns), FocusDirection::Down) => *columns.max(&1) as isize,
+            _ => return None,
+        };
+        let target = index as isize + step;
+        if target >= 0 && (target as usize) < count {
+            Some(target as usize)
+        } else if self.wraps {
+            Some(target.rem_euclid(count as isize) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// How a view participates in keyboard focus.
+#[derive(Debug, Clone)]
+pub enum Focus {
+    /// The view can be focused.
+    Focusable,
+    /// The view is a focus group for the focusable views inside it.
+    Group(FocusGroup),
+}
+
+/// Focusable views in tree order, along with their closest focus groups.
+///
+/// Obtained from [`ViewTree::focus_order`](crate::ViewTree::focus_order).
+#[derive(Debug, Clone, Default)]
+pub struct FocusOrder {
+    /// Focusable views and their closest focus group.
+    pub views: Vec<(ViewId, Option<ViewId>)>,
+
+    /// Focus groups.
+    pub groups: HashMap<ViewId, FocusGroup>,
+}
+
+/// A tab stop: either a single view or a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TabStop {
+    View(ViewId),
+    Group(ViewId),
+}
+
+impl FocusOrder {
+    fn group_of(&self, view: ViewId) -> Option<ViewId> {
+        self.views
+            .iter()
+            .find(|(id, _)| *id == view)
+            .and_then(|(_, group)| *group)
+    }
+
+    fn members(&self, group: ViewId) -> Vec<ViewId> {
+        self.views
+            .iter()
+            .filter(|(_, g)| *g == Some(group))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn tab_stops(&self) -> Vec<TabStop> {
+        let mut stops = Vec::new();
+        for (id, group) in &self.views {
+            let stop = match group {
+                Some(group) => TabStop::Group(*group),
+                None => TabStop::View(*id),
+            };
+            if !stops.contains(&stop) {
+                stops.push(stop);
+            }
+        }
+        stops
+    }
+}
+
+/// Keeps track of the focused view and moves focus in response to keyboard navigation.
+///
+/// Key events should be targeted at the focused view.
+#[derive(Debug, Clone, Default)]
+pub struct FocusManager {
+    focused: Option<ViewId>,
+
+    /// The last focused member of each group, which will be focused again when tabbing into the
+    /// group.
+    last_focused: HashMap<ViewId, ViewId>,
+}
+
+impl FocusManager {
+    pub fn new() -> FocusManager {
+        FocusManager::default()
+    }
+
+    /// The focused view.
+    pub fn focused(&self) -> Option<ViewId> {
+        self.focused
+    }
+
+    /// Focuses a view, e.g. when it is clicked. Returns false if the view is not focusable.
+    pub fn focus(&mut self, order: &FocusOrder, view: ViewId) -> bool {
+        if !order.views.iter().any(|(id, _)| *id == view) {
+            return false;
+        }
+        self.set_focused(order, Some(view));
+        true
+    }
+
+    /// Removes focus.
+    pub fn blur(&mut self) {
+        self.focused = None;
+    }
+
+    /// Updates state after the view tree has changed, removing focus from views that no longer
+    /// exist or are no longer focusable.
+    pub fn update(&mut self, order: &FocusOrder) {
+        if let Some(focused) = self.focused {
+            if !order.views.iter().any(|(id, _)| *id == focused) {
+                self.focused = None;
+            }
+        }
+        self.last_focused
+            .retain(|group, view| order.group_of(*view) == Some(*group));
+    }
+
+    /// Moves focus in a direction and returns the newly focused view.
+    ///
+    /// Returns None if focus did not move, e.g. if an arrow key was pressed outside a group. In
+    /// that case, the key event should be handled as usual.
+    pub fn move_focus(&mut self, order: &FocusOrder, direction: FocusDirection) -> Option<ViewId> {
+        let target = match direction {
+            FocusDirection::Next | FocusDirection::Previous => self.next_tab_stop(order, direction),
+            _ => self.navigate_group(order, direction),
+        }?;
+        self.set_focused(order, Some(target));
+        Some(target)
+    }
+
+    fn set_focused(&mut self, order: &FocusOrder, view: Option<ViewId>) {
+        self.focused = view;
+        if let Some(view) = view {
+            if let Some(group) = order.group_of(view) {
+                self.last_focused.insert(group, view);
+            }
+        }
+    }
+
+    fn next_tab_stop(&self, order: &FocusOrder, direction: FocusDirection) -> Option<ViewId> {
+        let stops = order.tab_stops();
+        if stops.is_empty() {
+            return None;
+        }
+
+        let current = self.focused.and_then(|focused| {
+            let stop = match order.group_of(focused) {
+                Some(group) => TabStop::Group(group),
+                None => TabStop::View(focused),
+            };
+            stops.iter().position(|s| *s == stop)
+        });
+        let index = match (current, direction) {
+            (Some(i), FocusDirection::Previous) => (i + stops.len() - 1) % stops.len(),
+            (Some(i), _) => (i + 1) % stops.len(),
+            (None, FocusDirection::Previous) => stops.len() - 1,
+            (None, _) => 0,
+        };
+
+        match stops[index] {
+            TabStop::View(id) => Some(id),
+            TabStop::Group(group) => {
+                let members = order.members(group);
+                self.last_focused
+                    .get(&group)
+                    .copied()
+                    .filter(|id| members.contains(id))
+                    .or_else(|| members.first().copied())
+            }
+        }
+    }
+
+    fn navigate_group(&self, order: &FocusOrder, direction: FocusDirection) -> Option<ViewId> {
+        let focused = self.focused?;
+        let group_id = order.group_of(focused)?;
+        let group = order.groups.get(&group_id)?;
+        let members = order.members(group_id);
+        let index = members.iter().position(|id| *id == focused)?;
+        let target = group.navigate(index, members.len(), direction)?;
+        Some(members[target])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `a`, a horizontal toolbar `[b, c, d]`, `e`, and a wrapping 2-column grid `[f, g, h, i]`.
+    fn order() -> (FocusOrder, [ViewId; 9], [ViewId; 2]) {
+        let views = [(); 9].map(|_| ViewId::new());
+        let [a, b, c, d, e, f, g, h, i] = views;
+        let groups = [ViewId::new(), ViewId::new()];
+        let [toolbar, grid] = groups;
+        let mut order = FocusOrder {
+            views: vec![
+                (a, None),
+                (b, Some(toolbar)),
+                (c, Some(toolbar)),
+                (d, Some(toolbar)),
+                (e, None),
+                (f, Some(grid)),
+                (g, Some(grid)),
+                (h, Some(grid)),
+                (i, Some(grid)),
+            ],
+            groups: HashMap::new(),
+        };
+        order.groups.insert(
+            toolbar,
+            FocusGroup {
+                navigation: GroupNavigation::Horizontal,
+                wraps: false,
+            },
+        );
+        order.groups.insert(
+            grid,
+            FocusGroup {
+                navigation: GroupNavigation::Grid(2),
+                wraps: true,
+            },
+        );
+        (order, views, groups)
+    }
+
+    /// Moves focus in each direction in turn and returns where it went.
+    fn moves(
+        focus: &mut FocusManager,
+        order: &FocusOrder,
+        directions: &[FocusDirection],
+    ) -> Vec<Option<ViewId>> {
+        directions
+            .iter()
+            .map(|direction| focus.move_focus(order, *direction))
+            .collect()
+    }
+
+    #[test]
+    fn tab_order_treats_groups_as_one_stop() {
+        use FocusDirection::*;
+        let (order, [a, b, _, _, e, f, ..], _) = order();
+        let mut focus = FocusManager::new();
+
+        let next = moves(&mut focus, &order, &[Next, Next, Next, Next, Next]);
+        assert_eq!(next, [Some(a), Some(b), Some(e), Some(f), Some(a)]);
+
+        let previous = moves(
+            &mut focus,
+            &order,
+            &[Previous, Previous, Previous, Previous],
+        );
+        assert_eq!(previous, [Some(f), Some(e), Some(b), Some(a)]);
+
+        focus.blur();
+        assert_eq!(focus.move_focus(&order, Previous), Some(f));
+    }
+
+    #[test]
+    fn tabbing_into_a_group_focuses_its_last_focused_member() {
+        use FocusDirection::*;
+        let (order, [a, b, c, _, e, ..], _) = order();
+        let mut focus = FocusManager::new();
+        assert!(focus.focus(&order, b));
+        assert_eq!(focus.move_focus(&order, Right), Some(c));
+        assert_eq!(
+            moves(&mut focus, &order, &[Next, Previous]),
+            [Some(e), Some(c)]
+        );
+        assert_eq!(
+            moves(&mut focus, &order, &[Previous, Next]),
+            [Some(a), Some(c)]
+        );
+    }
+
+    #[test]
+    fn arrows_move_inside_groups() {
+        use FocusDirection::*;
+        let (order, [a, b, c, d, _, f, g, h, i], _) = order();
+        let mut focus = FocusManager::new();
+
+        // the toolbar is horizontal and doesn’t wrap
+        focus.focus(&order, b);
+        let toolbar = moves(&mut focus, &order, &[Left, Right, Right, Right, Up, Down]);
+        assert_eq!(toolbar, [None, Some(c), Some(d), None, None, None]);
+        assert_eq!(focus.focused(), Some(d));
+        assert_eq!(focus.move_focus(&order, Left), Some(c));
+
+        // the grid wraps around at both ends
+        focus.focus(&order, f);
+        let grid = moves(&mut focus, &order, &[Down, Down, Left, Up, Right, Right]);
+        assert_eq!(grid, [Some(h), Some(f), Some(i), Some(g), Some(h), Some(i)]);
+
+        // arrows don’t move focus outside groups
+        focus.focus(&order, a);
+        assert_eq!(
+            moves(&mut focus, &order, &[Left, Right, Up, Down]),
+            [None; 4]
+        );
+        assert_eq!(focus.focused(), Some(a));
+    }
+
+    #[test]
+    fn focus_is_removed_from_views_that_are_gone() {
+        use FocusDirection::*;
+        let (mut order, [a, b, c, ..], [toolbar, _]) = order();
+        let mut focus = FocusManager::new();
+        focus.focus(&order, c);
+        assert!(!focus.focus(&order, toolbar));
+
+        order.views.retain(|(id, _)| *id != c);
+        focus.update(&order);
+        assert_eq!(focus.focused(), None);
+        assert_eq!(moves(&mut focus, &order, &[Next, Next]), [Some(a), Some(b)]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Locale-aware formatting of numbers, dates, and relative times.
+//!
+//! These are simple built-in conventions for common locales; [`Localization`] has convenience
+//! methods that use its current locale, so text formatted in a view body will update when the
+//! locale changes.
+
+use crate::locale::{Locale, Localization};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Separators used when formatting numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NumberSymbols {
+    /// The decimal separator.
+    pub decimal: char,
+
+    /// The digit group (thousands) separator.
+    pub group: char,
+}
+
+impl NumberSymbols {
+    /// Returns the number symbols for a locale.
+    pub fn for_locale(locale: &Locale) -> NumberSymbols {
+        let (decimal, group) = match (locale.language(), locale.region()) {
+            ("de", Some("CH")) | ("it", Some("CH")) => ('.', '’'),
+            ("de", _)
+            | ("es", _)
+            | ("it", _)
+            | ("nl", _)
+            | ("da", _)
+            | ("tr", _)
+            | ("id", _)
+            | ("pt", Some("BR")) => (',', '.'),
+            ("fr", _)
+            | ("ru", _)
+            | ("uk", _)
+            | ("pl", _)
+            | ("cs", _)
+            | ("sv", _)
+            | ("nb", _)
+            | ("fi", _)
+            | ("pt", _) => (',', '\u{202f}'),
+            _ => ('.', ','),
+        };
+        NumberSymbols { decimal, group }
+    }
+}
+
+/// Formats a number with digit grouping and a fixed number of fraction digits.
+///
+/// e.g. `1234.5` with two fraction digits is `1,234.50` in `en` and `1.234,50` in `de`.
+pub fn format_number(locale: &Locale, value: f64, fraction_digits: usize) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let symbols = NumberSymbols::for_locale(locale);
+    let formatted = format!("{:.*}", fraction_digits, value.abs());
+    let (integer, fraction) = match formatted.find('.') {
+        Some(index) => (&formatted[..index], Some(&formatted[index + 1..])),
+        None => (&formatted[..], None),
+    };
+
+    let mut out = String::new();
+    // don’t output -0
+    if value < 0. && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        out.push('-');
+    }
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            out.push(symbols.group);
+        }
+        out.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        out.push(symbols.decimal);
+        out.push_str(fraction);
+    }
+    out
+}
+
+/// A calendar date in the proleptic Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+
+    /// The month, from 1 to 12.
+    pub month: u8,
+
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+}
+
+impl Date {
+    /// Returns the UTC date at the given point in time.
+    ///
+    /// Time zones are not handled here; add the UTC offset to the time beforehand if required.
+    pub fn from_system_time(time: SystemTime) -> Date {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            // round down to whole seconds
+            Err(err) => {
+                let before = err.duration();
+                -(before.as_secs() as i64) - if before.subsec_nanos() > 0 { 1 } else { 0 }
+            }
+        };
+        Date::from_days_since_epoch(secs.div_euclid(86400))
+    }
+
+    /// Returns the date a number of days after 1970-01-01.
+    pub fn from_days_since_epoch(days: i64) -> Date {
+        // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+}
+
+/// Formats a date numerically in the usual order for the locale.
+///
+/// e.g. `3/14/2020` in `en-US`, `14/03/2020` in `en-GB`, `14.03.2020` in `de`, and
+/// `2020-03-14` in `sv`.
+pub fn format_date(locale: &Locale, date: Date) -> String {
+    let Date { year, month, day } = date;
+    match (locale.language(), locale.region()) {
+        ("en", None) | ("en", Some("US")) | ("en", Some("PH")) => {
+            format!("{}/{}/{}", month, day, year)
+        }
+        ("zh", _) | ("ja", _) => format!("{}/{}/{}", year, month, day),
+        ("sv", _) | ("lt", _) | ("en", Some("CA")) => format!("{}-{:02}-{:02}", year, month, day),
+        ("ko", _) => format!("{}. {}. {}.", year, month, day),
+        ("hu", _) => format!("{}. {:02}. {:02}.", year, month, day),
+        ("de", _)
+        | ("ru", _)
+        | ("uk", _)
+        | ("pl", _)
+        | ("cs", _)
+        | ("fi", _)
+        | ("nb", _)
+        | ("da", _)
+        | ("tr", _) => format!("{:02}.{:02}.{}", day, month, year),
+        ("nl", _) => format!("{:02}-{:02}-{}", day, month, year),
+        _ => format!("{:02}/{:02}/{}", day, month, year),
+    }
+}
+
+/// Formats a relative time, such as “3 minutes ago” or “in 2 days”.
+///
+/// - `seconds`: the offset from now; negative values are in the past.
+///
+/// Strings are looked up in the localization’s catalog using the keys
+/// `relative-time-{unit}-past` and `relative-time-{unit}-future` (where unit is one of
+/// `seconds`, `minutes`, `hours`, or `days`) with a `{count}` argument, and fall back to English.
+pub fn format_relative_time(localization: &Localization, seconds: i64) -> String {
+    let abs = seconds.unsigned_abs();
+    let (unit, count) = if abs < 60 {
+        ("seconds", abs)
+    } else if abs < 3600 {
+        ("minutes", abs / 60)
+    } else if abs < 86400 {
+        ("hours", abs / 3600)
+    } else {
+        ("days", abs / 86400)
+    };
+    let is_past = seconds < 0;
+
+    let key = format!(
+        "relative-time-{}-{}",
+        unit,
+        if is_past { "past" } else { "future" }
+    );
+    let count_str = format_number(&localization.locale(), count as f64, 0);
+    if let Some(string) = localization.try_format(&key, &[("count", &count_str)]) {
+        return string;
+    }
+
+    let unit = if count == 1 {
+        &unit[..unit.len() - 1]
+    } else {
+        unit
+    };
+    if is_past {
+        format!("{} {} ago", count_str, unit)
+    } else {
+        format!("in {} {}", count_str, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::MapCatalog;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn dates_around_the_epoch() {
+        let date = |year, month, day| Date { year, month, day };
+        let secs = Duration::from_secs;
+        assert_eq!(Date::from_system_time(UNIX_EPOCH), date(1970, 1, 1));
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH + Duration::from_nanos(1)),
+            date(1970, 1, 1)
+        );
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH - Duration::from_nanos(1)),
+            date(1969, 12, 31)
+        );
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH - secs(1)),
+            date(1969, 12, 31)
+        );
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH - secs(86400)),
+            date(1969, 12, 31)
+        );
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH - secs(86401)),
+            date(1969, 12, 30)
+        );
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH + secs(86399)),
+            date(1970, 1, 1)
+        );
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH + secs(86400)),
+            date(1970, 1, 2)
+        );
+        // 2020-03-14
+        assert_eq!(Date::from_days_since_epoch(18335), date(2020, 3, 14));
+        assert_eq!(Date::from_days_since_epoch(11016), date(2000, 2, 29));
+    }
+
+    #[test]
+    fn dates_in_locales() {
+        let date = Date {
+            year: 2020,
+            month: 3,
+            day: 4,
+        };
+        let format = |tag| format_date(&Locale::new(tag), date);
+        assert_eq!(format("en-US"), "3/4/2020");
+        assert_eq!(format("en-GB"), "04/03/2020");
+        assert_eq!(format("de-DE"), "04.03.2020");
+        assert_eq!(format("sv"), "2020-03-04");
+        assert_eq!(format("ko"), "2020. 3. 4.");
+    }
+
+    #[test]
+    fn numbers() {
+        let format = |tag, value, digits| format_number(&Locale::new(tag), value, digits);
+        assert_eq!(format("en", 1234.5, 2), "1,234.50");
+        assert_eq!(format("de", 1234.5, 2), "1.234,50");
+        assert_eq!(format("de-CH", 1234567., 0), "1’234’567");
+        assert_eq!(format("fr", 1234.5, 1), "1\u{202f}234,5");
+        assert_eq!(format("en", 123., 0), "123");
+        assert_eq!(format("en", -1234., 0), "-1,234");
+        assert_eq!(format("en", -0.001, 2), "0.00");
+        assert_eq!(format("en", f64::INFINITY, 2), "inf");
+    }
+
+    #[test]
+    fn relative_times() {
+        let en = Localization::new(Locale::new("en"), Arc::new(MapCatalog::new()));
+        assert_eq!(format_relative_time(&en, -1), "1 second ago");
+        assert_eq!(format_relative_time(&en, -59), "59 seconds ago");
+        assert_eq!(format_relative_time(&en, 60), "in 1 minute");
+        assert_eq!(format_relative_time(&en, 7200), "in 2 hours");
+        assert_eq!(format_relative_time(&en, -86400 * 3), "3 days ago");
+
+        let de = Locale::new("de");
+        let mut catalog = MapCatalog::new();
+        catalog.insert(&de, "relative-time-days-past", "vor {count} Tagen");
+        let de = Localization::new(de, Arc::new(catalog));
+        assert_eq!(format_relative_time(&de, -86400 * 1200), "vor 1.200 Tagen");
+        // falls back to English
+        assert_eq!(format_relative_time(&de, 60), "in 1 minute");
+    }
+}
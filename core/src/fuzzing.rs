@@ -0,0 +1,576 @@
+//! Helpers for fuzzing the view tree (with the `fuzzing` feature enabled).
+//!
+//! The fuzz targets in `core/fuzz` decode their input into
+//!
+//! - a sequence of view trees with [`diff_sequence`], which renders them one after another and
+//!   checks after every render that the view tree is still consistent, that its patches apply
+//!   cleanly to a [`ModelBackend`], and that neither tree leaks entries for removed views;
+//! - a sequence of patches with [`patch_sequence`], which applies them both to an NV tree and to a
+//!   naive [`ReferenceTree`] and checks that both end up with the same structure.
+
+use crate::backend::Backend;
+use crate::impl_view;
+use crate::nv_tree::{NativeView, PatchError};
+use crate::raw_events::RawEvent;
+use crate::view::{AnyView, Fragment, View, ViewId};
+use crate::{Layer, LayerProps, NVTree, Patch, Rect, ViewTree};
+use cgmath::{Point2, Vector2};
+use core::mem;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Maximum nesting depth of generated views.
+const MAX_DEPTH: usize = 6;
+
+/// Maximum number of subviews of a generated view.
+const MAX_SUBVIEWS: u8 = 4;
+
+/// Number of distinct views that generated patches refer to.
+const PATCH_VIEWS: usize = 8;
+
+/// Renders a sequence of view trees decoded from fuzzer input.
+///
+/// # Panics
+/// Panics if the view tree becomes inconsistent, if its patches don’t apply to the NV tree, if
+/// the resulting backend views don’t match the view tree, or if either tree fails its leak check.
+pub fn diff_sequence(data: &[u8]) {
+    let mut input = Input { data };
+    let mut tree: ViewTree = ViewTree::new();
+    let mut backend = ModelBackend::default();
+    let mut nv_tree = NVTree::new(&mut backend);
+    tree.set_leak_check(true);
+    nv_tree.set_leak_check(true);
+
+    let mut frame = 0;
+    while !input.data.is_empty() {
+        tree.render_root(input.root(), ());
+        if let Err(err) = tree.check_invariants() {
+            panic!("frame {}: inconsistent view tree: {}", frame, err);
+        }
+        if let Err(err) = nv_tree.patch_frame(tree.patches()) {
+            panic!("frame {}: could not apply patches: {:?}", frame, err);
+        }
+        let hierarchy = match nv_tree.backend().hierarchy() {
+            Ok(hierarchy) => hierarchy,
+            Err(err) => panic!("frame {}: inconsistent backend views: {:?}", frame, err),
+        };
+        assert_eq!(
+            hierarchy,
+            tree.model_hierarchy(),
+            "frame {}: backend views don’t match the view tree",
+            frame
+        );
+        frame += 1;
+    }
+}
+
+/// Applies frames of patches decoded from fuzzer input to an NV tree and to a [`ReferenceTree`].
+///
+/// # Panics
+/// Panics if only one of them rejects a frame, or if their structures differ afterwards.
+pub fn patch_sequence(data: &[u8]) {
+    let mut input = Input { data };
+    let ids: Vec<_> = (0..PATCH_VIEWS).map(|_| ViewId::new()).collect();
+    let mut reference = ReferenceTree::default();
+    let mut backend = ModelBackend::default();
+    let mut nv_tree = NVTree::new(&mut backend);
+
+    let mut frame = 0;
+    while !input.data.is_empty() {
+        let patches = input.patches(&ids, &reference);
+        let expected = reference.apply_frame(&patches);
+        let result = nv_tree.patch_frame(patches);
+        let same_result = match (&expected, &result) {
+            (Ok(()), Ok(())) => true,
+            (Err(PatchError::NoSuchView(a)), Err(PatchError::NoSuchView(b))) => a == b,
+            (Err(PatchError::Cycle(a)), Err(PatchError::Cycle(b))) => a == b,
+            _ => false,
+        };
+        assert!(
+            same_result,
+            "frame {}: expected {:?}, got {:?}",
+            frame, expected, result
+        );
+        assert_eq!(
+            nv_tree.reference_tree(),
+            reference,
+            "frame {}: NV tree doesn’t match the reference tree",
+            frame
+        );
+        frame += 1;
+    }
+}
+
+/// Fuzzer input, decoded into views.
+///
+/// Reads zeros once it runs out of data, so decoding always terminates.
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((byte, rest)) => {
+                self.data = rest;
+                *byte
+            }
+            None => 0,
+        }
+    }
+
+    /// Decodes a root view, which must be a native view.
+    fn root(&mut self) -> AnyView {
+        self.layer(0, None)
+    }
+
+    fn view(&mut self, depth: usize, key: Option<u64>) -> AnyView {
+        let choice = if depth >= MAX_DEPTH {
+            0
+        } else {
+            self.byte() % 5
+        };
+        match choice {
+            0 | 1 => self.layer(depth, key),
+            2 => Arc::new(Wrapper {
+                key,
+                body: self.view(depth + 1, None),
+            }),
+            3 => Arc::new(OtherWrapper {
+                key,
+                body: self.view(depth + 1, None),
+            }),
+            _ => Arc::new(self.subviews(depth + 1)),
+        }
+    }
+
+    fn layer(&mut self, depth: usize, key: Option<u64>) -> AnyView {
+        // the bounds identify the layer when comparing hierarchies
+        let tag = f64::from(self.byte());
+        Arc::new(Layer {
+            key,
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(tag, 0.), Vector2::new(1., 1.)),
+                ..LayerProps::default()
+            },
+            subviews: self.subviews(depth + 1),
+            ..Layer::default()
+        })
+    }
+
+    fn subviews(&mut self, depth: usize) -> Fragment {
+        let count = if depth >= MAX_DEPTH {
+            0
+        } else {
+            self.byte() % (MAX_SUBVIEWS + 1)
+        };
+        let mut keys = Vec::new();
+        let mut subviews = Vec::new();
+        for _ in 0..count {
+            // keys must be unique among siblings
+            let byte = self.byte();
+            let key = Some(u64::from(byte % 8)).filter(|key| byte >= 128 && !keys.contains(key));
+            keys.extend(key);
+            subviews.push(self.view(depth, key));
+        }
+        subviews
+    }
+
+    /// Decodes a frame of patches for views with the given IDs.
+    ///
+    /// Patches may refer to views that don’t exist or make a view its own subview, but never give
+    /// a view two superviews or make it its own ancestor, since the NV tree doesn’t check for that.
+    fn patches(&mut self, ids: &[ViewId], tree: &ReferenceTree) -> Vec<Patch> {
+        let mut tree = tree.clone();
+        let count = 1 + self.byte() % 4;
+        let mut patches = Vec::new();
+        for _ in 0..count {
+            let patch = self.patch(ids, &tree);
+            let _ = tree.apply(&patch);
+            patches.push(patch);
+        }
+        patches
+    }
+
+    fn patch(&mut self, ids: &[ViewId], tree: &ReferenceTree) -> Patch {
+        let id = ids[usize::from(self.byte()) % ids.len()];
+        match self.byte() % 5 {
+            0 => Patch::SetRoot(id),
+            1 => Patch::Update(id, self.native_view()),
+            2 => Patch::Replace(id, self.native_view()),
+            3 => Patch::Remove(id),
+            _ => {
+                let current = tree.nodes.get(&id).map_or(0, |node| node.subviews.len());
+                let offset = usize::from(self.byte()) % (current + 1);
+                let len = usize::from(self.byte()) % (current - offset + 1);
+                let count = self.byte() % MAX_SUBVIEWS;
+                let mut subviews = Vec::new();
+                for _ in 0..count {
+                    let subview = ids[usize::from(self.byte()) % ids.len()];
+                    let is_invalid = subview == id || !tree.nodes.contains_key(&subview);
+                    if is_invalid
+                        || (!subviews.contains(&subview)
+                            && tree.can_adopt(id, offset..offset + len, subview))
+                    {
+                        subviews.push(subview);
+                    }
+                }
+                Patch::SubviewRegion(id, offset, len, subviews)
+            }
+        }
+    }
+
+    fn native_view(&mut self) -> NativeView {
+        let tag = f64::from(self.byte());
+        Layer::<()> {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(tag, 0.), Vector2::new(1., 1.)),
+                ..LayerProps::default()
+            },
+            ..Layer::default()
+        }
+        .native_view()
+    }
+}
+
+/// A naive implementation of NV tree patches, for checking the NV tree against.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReferenceTree {
+    pub root: Option<ViewId>,
+    pub nodes: HashMap<ViewId, ReferenceNode>,
+}
+
+/// A view in a [`ReferenceTree`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceNode {
+    pub bounds: Rect,
+    /// Subviews, including those that have been removed but not yet removed from this list.
+    pub subviews: Vec<ViewId>,
+}
+
+impl ReferenceTree {
+    /// Applies all patches of a frame, or none of them if one of them is invalid.
+    pub fn apply_frame(&mut self, patches: &[Patch]) -> Result<(), PatchError<ModelBackend>> {
+        let mut tree = self.clone();
+        for patch in patches {
+            tree.apply(patch)?;
+        }
+        *self = tree;
+        Ok(())
+    }
+
+    fn apply(&mut self, patch: &Patch) -> Result<(), PatchError<ModelBackend>> {
+        match patch {
+            Patch::SetRoot(id) => {
+                self.node_mut(*id)?;
+                self.root = Some(*id);
+            }
+            Patch::Update(id, view) => {
+                let node = self.nodes.entry(*id).or_insert_with(|| ReferenceNode {
+                    bounds: view.bounds(),
+                    subviews: Vec::new(),
+                });
+                node.bounds = view.bounds();
+            }
+            Patch::Replace(id, view) => {
+                let node = self.node_mut(*id)?;
+                node.bounds = view.bounds();
+                for subview in mem::take(&mut node.subviews) {
+                    self.remove(subview);
+                }
+            }
+            Patch::SubviewRegion(id, offset, len, new_subviews) => {
+                self.node_mut(*id)?;
+                for subview in new_subviews {
+                    if subview == id {
+                        return Err(PatchError::Cycle(*id));
+                    }
+                    self.node_mut(*subview)?;
+                }
+                let node = self.node_mut(*id)?;
+                let mut subviews = Vec::new();
+                for (i, subview) in node.subviews.iter().enumerate() {
+                    if i == *offset {
+                        subviews.extend_from_slice(new_subviews);
+                    }
+                    if i < *offset || i >= offset + len {
+                        subviews.push(*subview);
+                    }
+                }
+                if *offset >= node.subviews.len() {
+                    subviews.extend_from_slice(new_subviews);
+                }
+                node.subviews = subviews;
+            }
+            Patch::Remove(id) => {
+                self.node_mut(*id)?;
+                self.remove(*id);
+            }
+            Patch::Announce(_) => (),
+        }
+        Ok(())
+    }
+
+    fn node_mut(&mut self, id: ViewId) -> Result<&mut ReferenceNode, PatchError<ModelBackend>> {
+        self.nodes.get_mut(&id).ok_or(PatchError::NoSuchView(id))
+    }
+
+    fn remove(&mut self, id: ViewId) {
+        if let Some(node) = self.nodes.remove(&id) {
+            for subview in node.subviews {
+                self.remove(subview);
+            }
+        }
+    }
+
+    fn superview(&self, id: ViewId) -> Option<ViewId> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.subviews.contains(&id))
+            .map(|(superview, _)| *superview)
+    }
+
+    /// Returns true if the view may become a subview of `id` in place of the subviews in
+    /// `region`, i.e. if it isn’t a subview anywhere else and isn’t an ancestor of `id`.
+    fn can_adopt(&self, id: ViewId, region: core::ops::Range<usize>, subview: ViewId) -> bool {
+        match self.superview(subview) {
+            Some(superview) if superview == id => {
+                let index = self.nodes[&id]
+                    .subviews
+                    .iter()
+                    .position(|s| *s == subview)
+                    .unwrap();
+                if !region.contains(&index) {
+                    return false;
+                }
+            }
+            Some(_) => return false,
+            None => (),
+        }
+        let mut ancestor = Some(id);
+        while let Some(id) = ancestor {
+            if id == subview {
+                return false;
+            }
+            ancestor = self.superview(id);
+        }
+        true
+    }
+}
+
+macro_rules! wrapper_view {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        struct $name {
+            key: Option<u64>,
+            body: AnyView,
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                self.key == other.key && self.body.eq(&*other.body)
+            }
+        }
+
+        impl_view! {
+            $name: ();
+            fn body(&self, _state: &()) {
+                Arc::clone(&self.body)
+            }
+            fn key(&self) -> Option<u64> {
+                self.key
+            }
+        }
+    };
+}
+
+wrapper_view!(
+    /// A composite view.
+    Wrapper
+);
+wrapper_view!(
+    /// Another type of composite view, so views can change type.
+    OtherWrapper
+);
+
+/// A native view hierarchy, for comparing what a backend displays with what it should display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelView {
+    pub bounds: Rect,
+    pub subviews: Vec<ModelView>,
+}
+
+/// A backend that only keeps a model of its views, and checks that it’s being used correctly.
+#[derive(Debug, Default)]
+pub struct ModelBackend {
+    views: Vec<Option<ModelBackendView>>,
+    root: Option<usize>,
+}
+
+#[derive(Debug)]
+struct ModelBackendView {
+    bounds: Rect,
+    subviews: Vec<usize>,
+}
+
+/// Incorrect use of a [`ModelBackend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelError {
+    /// The view was removed or never existed.
+    NoSuchView(usize),
+    /// A subview region was out of bounds.
+    InvalidRegion(usize, usize, usize),
+    /// The view would be its own subview.
+    Cycle(usize),
+    /// The view is a subview more than once.
+    Duplicate(usize),
+}
+
+impl ModelBackend {
+    fn view_mut(&mut self, view: usize) -> Result<&mut ModelBackendView, ModelError> {
+        match self.views.get_mut(view) {
+            Some(Some(view)) => Ok(view),
+            _ => Err(ModelError::NoSuchView(view)),
+        }
+    }
+
+    /// Returns the hierarchy below the root view.
+    pub fn hierarchy(&self) -> Result<Option<ModelView>, ModelError> {
+        match self.root {
+            Some(root) => Ok(Some(self.model_view(root, &mut HashSet::new())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn model_view(&self, id: usize, seen: &mut HashSet<usize>) -> Result<ModelView, ModelError> {
+        if !seen.insert(id) {
+            return Err(ModelError::Duplicate(id));
+        }
+        let view = match self.views.get(id) {
+            Some(Some(view)) => view,
+            _ => return Err(ModelError::NoSuchView(id)),
+        };
+        let mut subviews = Vec::with_capacity(view.subviews.len());
+        for subview in &view.subviews {
+            subviews.push(self.model_view(*subview, seen)?);
+        }
+        Ok(ModelView {
+            bounds: view.bounds,
+            subviews,
+        })
+    }
+}
+
+impl Backend for ModelBackend {
+    type ViewRef = usize;
+    type Error = ModelError;
+
+    fn new_view(&mut self, view: NativeView) -> Result<usize, ModelError> {
+        self.views.push(Some(ModelBackendView {
+            bounds: view.bounds(),
+            subviews: Vec::new(),
+        }));
+        Ok(self.views.len() - 1)
+    }
+
+    fn remove_view(&mut self, view: usize) -> Result<(), ModelError> {
+        self.view_mut(view)?;
+        self.views[view] = None;
+        if self.root == Some(view) {
+            self.root = None;
+        }
+        Ok(())
+    }
+
+    fn update_view(&mut self, view: &mut usize, new_view: NativeView) -> Result<(), ModelError> {
+        self.view_mut(*view)?.bounds = new_view.bounds();
+        Ok(())
+    }
+
+    fn replace_view(&mut self, view: &mut usize, new_view: NativeView) -> Result<(), ModelError> {
+        let view = self.view_mut(*view)?;
+        view.bounds = new_view.bounds();
+        view.subviews.clear();
+        Ok(())
+    }
+
+    fn set_subviews(
+        &mut self,
+        view: &mut usize,
+        region_start: usize,
+        region_len: usize,
+        subviews: Vec<&usize>,
+    ) -> Result<(), ModelError> {
+        for subview in &subviews {
+            if **subview == *view {
+                return Err(ModelError::Cycle(*view));
+            }
+            self.view_mut(**subview)?;
+        }
+        let id = *view;
+        let view = self.view_mut(id)?;
+        if region_start + region_len > view.subviews.len() {
+            return Err(ModelError::InvalidRegion(id, region_start, region_len));
+        }
+        view.subviews.splice(
+            region_start..region_start + region_len,
+            subviews.into_iter().copied(),
+        );
+        Ok(())
+    }
+
+    fn set_root_view(&mut self, view: &mut usize) -> Result<(), ModelError> {
+        self.view_mut(*view)?;
+        self.root = Some(*view);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Option<RawEvent>, ModelError> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of inputs to run through each harness.
+    const INPUTS: u64 = 2000;
+
+    /// Generates deterministic pseudo-random inputs of up to 256 bytes, so that failures are
+    /// reproducible without the fuzzer.
+    fn seeded_inputs(seed: u64) -> impl Iterator<Item = (u64, Vec<u8>)> {
+        // xorshift64*
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        };
+        (0..INPUTS).map(move |i| {
+            let len = (next() % 256) as usize;
+            (i, (0..len).map(|_| next() as u8).collect())
+        })
+    }
+
+    #[test]
+    fn diff_sequence_seeded() {
+        for (i, input) in seeded_inputs(0x5eed_2685) {
+            // the panic message is printed by the panic hook; this adds the failing input
+            if std::panic::catch_unwind(|| diff_sequence(&input)).is_err() {
+                panic!("input {} failed: {:?}", i, input);
+            }
+        }
+    }
+
+    #[test]
+    fn patch_sequence_seeded() {
+        for (i, input) in seeded_inputs(0x5eed_2686) {
+            if std::panic::catch_unwind(|| patch_sequence(&input)).is_err() {
+                panic!("input {} failed: {:?}", i, input);
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Hot reloading of view code during development.
+//!
+//! The app’s views are built as a separate dynamic library (with `crate-type = ["dylib"]`) that
+//! exports a function returning the root view using [`hot_reload_entry`]:
+//!
+//! ```text
+//! fn root() -> Arc<dyn View<AppContext>> {
+//!     ...
+//! }
+//!
+//! birb::hot_reload_entry!(root: AppContext);
+//! ```
+//!
+//! A dev-mode host (with the `hot-reload` feature enabled) then loads the library with a
+//! `HotReloadHost` and renders its root view. Whenever the library has been recompiled, the host
+//! loads the new build and renders the view tree again; views keep their state if their types
+//! still match (see `ViewTree::reload_root`).
+//!
+//! ```text
+//! let mut host = HotReloadHost::new("target/debug/libapp_views.dylib")?;
+//! host.render(&mut view_tree, context.clone());
+//! loop {
+//!     // (poll events and apply patches)
+//!     if let Err(err) = host.poll(&mut view_tree, context.clone()) {
+//!         eprintln!("could not reload views: {:?}", err);
+//!     }
+//! }
+//! ```
+//!
+//! The library must be built with the same compiler, birb version, and birb features as the host
+//! (otherwise, type IDs of birb types won’t match), and the context type of the entry must match
+//! the host’s. Libraries are never unloaded, since views, states, and event handlers created by them
+//! may still be alive.
+
+/// The name of the symbol exported by [`hot_reload_entry`].
+pub const ENTRY_SYMBOL: &str = "birb_hot_reload_root";
+
+/// Exports a function that returns the root view from a hot-reloadable view library.
+///
+/// Takes the path of a function with no arguments that returns an `Arc<dyn View<Ctx>>`, and the
+/// context type (which defaults to `()`).
+#[macro_export]
+macro_rules! hot_reload_entry {
+    ($root:path) => {
+        $crate::hot_reload_entry!($root: ());
+    };
+    ($root:path: $ctx:ty) => {
+        #[no_mangle]
+        pub fn birb_hot_reload_root() -> $crate::AnyView<$ctx> {
+            $root()
+        }
+    };
+}
+
+#[cfg(feature = "hot-reload")]
+pub use self::host::*;
+
+#[cfg(feature = "hot-reload")]
+mod host {
+    use super::ENTRY_SYMBOL;
+    use crate::view::AnyView;
+    use crate::view_tree::ViewTree;
+    use core::mem;
+    use libloading::Library;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::process::{self, Command, ExitStatus};
+    use std::time::SystemTime;
+
+    /// Errors that may occur when loading a view library.
+    #[derive(Debug)]
+    pub enum HotReloadError {
+        /// The library could not be read or loaded.
+        Io(io::Error),
+        /// The library does not export a root view (see
+        /// [`hot_reload_entry`](crate::hot_reload_entry)).
+        MissingEntry,
+        /// The build command failed.
+        BuildFailed(ExitStatus),
+    }
+
+    impl From<io::Error> for HotReloadError {
+        fn from(err: io::Error) -> HotReloadError {
+            HotReloadError::Io(err)
+        }
+    }
+
+    type RootFn<Ctx> = fn() -> AnyView<Ctx>;
+
+    /// Loads views from a dynamic library and reloads them when the library changes.
+    ///
+    /// If loading a new build fails, the previous build stays active.
+    pub struct HotReloadHost<Ctx> {
+        path: PathBuf,
+        /// Modification time of the currently loaded build.
+        modified: SystemTime,
+        root: RootFn<Ctx>,
+        /// The number of loaded builds.
+        builds: usize,
+    }
+
+    impl<Ctx: 'static + Clone + Send> HotReloadHost<Ctx> {
+        /// Loads a view library.
+        pub fn new(path: impl AsRef<Path>) -> Result<HotReloadHost<Ctx>, HotReloadError> {
+            let path = path.as_ref().to_path_buf();
+            let (root, modified) = load(&path, 0)?;
+            Ok(HotReloadHost {
+                path,
+                modified,
+                root,
+                builds: 1,
+            })
+        }
+
+        /// The number of builds that have been loaded.
+        pub fn builds(&self) -> usize {
+            self.builds
+        }
+
+        /// Returns the root view from the current build.
+        pub fn root(&self) -> AnyView<Ctx> {
+            (self.root)()
+        }
+
+        /// Renders the root view from the current build.
+        pub fn render(&self, tree: &mut ViewTree<Ctx>, context: Ctx) {
+            tree.render_root(self.root(), context);
+        }
+
+        /// Returns true if the library file has changed since it was loaded.
+        pub fn is_outdated(&self) -> bool {
+            match fs::metadata(&self.path).and_then(|meta| meta.modified()) {
+                Ok(modified) => modified != self.modified,
+                Err(_) => false,
+            }
+        }
+
+        /// Loads the current build of the library and renders the view tree again.
+        pub fn reload(
+            &mut self,
+            tree: &mut ViewTree<Ctx>,
+            context: Ctx,
+        ) -> Result<(), HotReloadError> {
+            let (root, modified) = load(&self.path, self.builds)?;
+            self.builds += 1;
+            self.root = root;
+            self.modified = modified;
+            tree.reload_root(self.root(), context);
+            Ok(())
+        }
+
+        /// Reloads if the library file has changed.
+        ///
+        /// Returns true if the view tree was rendered again.
+        pub fn poll(
+            &mut self,
+            tree: &mut ViewTree<Ctx>,
+            context: Ctx,
+        ) -> Result<bool, HotReloadError> {
+            if !self.is_outdated() {
+                return Ok(false);
+            }
+            self.reload(tree, context)?;
+            Ok(true)
+        }
+
+        /// Runs a build command (e.g. `cargo build -p app-views`) and reloads if it succeeds.
+        pub fn rebuild(
+            &mut self,
+            command: &mut Command,
+            tree: &mut ViewTree<Ctx>,
+            context: Ctx,
+        ) -> Result<(), HotReloadError> {
+            let status = command.status()?;
+            if !status.success() {
+                return Err(HotReloadError::BuildFailed(status));
+            }
+            self.reload(tree, context)
+        }
+    }
+
+    /// Loads a build of a view library.
+    ///
+    /// The library is loaded from a copy, since dynamic loaders may return the previously loaded
+    /// library when opening the same path again. It will never be unloaded, since views, states,
+    /// and event handlers created by it may outlive the host.
+    fn load<Ctx>(path: &Path, build: usize) -> Result<(RootFn<Ctx>, SystemTime), HotReloadError> {
+        let modified = fs::metadata(path)?.modified()?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let copy = std::env::temp_dir().join(format!(
+            "birb-hot-reload-{}-{}-{}",
+            process::id(),
+            build,
+            file_name
+        ));
+        fs::copy(path, &copy)?;
+        let library = Library::new(&copy);
+        // the copy is no longer needed once it’s loaded (this may fail on some platforms)
+        let _ = fs::remove_file(&copy);
+        let library = library?;
+
+        // the symbol was exported by hot_reload_entry, which has this signature (assuming the
+        // library was built for the same context type)
+        let root = match unsafe { library.get::<RootFn<Ctx>>(ENTRY_SYMBOL.as_bytes()) } {
+            Ok(root) => *root,
+            Err(_) => return Err(HotReloadError::MissingEntry),
+        };
+        mem::forget(library);
+        Ok((root, modified))
+    }
+}
@@ -0,0 +1,151 @@
+//! Images.
+
+use core::fmt;
+
+/// An 8-bit RGBA bitmap in sRGB with straight (non-premultiplied) alpha.
+///
+/// Pixels are stored row by row, starting at the top left corner.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Creates a new transparent image.
+    pub fn new(width: u32, height: u32) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    /// Creates an image from raw RGBA data.
+    ///
+    /// Returns None if the data length does not match the image size.
+    pub fn from_raw(width: u32, height: u32, data: Vec<u8>) -> Option<RgbaImage> {
+        if data.len() != width as usize * height as usize * 4 {
+            return None;
+        }
+        Some(RgbaImage {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Creates an image from raw RGBA data with premultiplied alpha, as produced by most
+    /// graphics APIs. Channels are rounded to the nearest value, and fully transparent pixels
+    /// become transparent black.
+    ///
+    /// Returns None if the data length does not match the image size.
+    pub fn from_premultiplied(width: u32, height: u32, mut data: Vec<u8>) -> Option<RgbaImage> {
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = pixel[3];
+            if alpha == 0 {
+                pixel[..3].copy_from_slice(&[0; 3]);
+            } else if alpha != 255 {
+                for channel in &mut pixel[..3] {
+                    let value = (*channel as u32 * 255 + alpha as u32 / 2) / alpha as u32;
+                    *channel = value.min(255) as u8;
+                }
+            }
+        }
+        RgbaImage::from_raw(width, height, data)
+    }
+
+    /// Image width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Image height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the pixel at the given position.
+    ///
+    /// # Panics
+    /// - if the position is out of bounds
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    /// Raw RGBA data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Mutable raw RGBA data.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Returns the raw RGBA data.
+    pub fn into_raw(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl fmt::Debug for RgbaImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RgbaImage({}x{})", self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts a single premultiplied pixel.
+    fn unpremultiply(pixel: [u8; 4]) -> [u8; 4] {
+        RgbaImage::from_premultiplied(1, 1, pixel.to_vec())
+            .unwrap()
+            .pixel(0, 0)
+    }
+
+    #[test]
+    fn opaque_pixels_are_unchanged() {
+        assert_eq!(unpremultiply([12, 34, 56, 255]), [12, 34, 56, 255]);
+    }
+
+    #[test]
+    fn transparent_pixels_become_transparent_black() {
+        assert_eq!(unpremultiply([0, 0, 0, 0]), [0, 0, 0, 0]);
+        assert_eq!(unpremultiply([10, 20, 30, 0]), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn channels_are_rounded_to_the_nearest_value() {
+        assert_eq!(unpremultiply([64, 1, 2, 128]), [128, 2, 4, 128]);
+        // 255 / 3 = 85 exactly, 255 * 2 / 3 = 170
+        assert_eq!(unpremultiply([1, 2, 3, 3]), [85, 170, 255, 3]);
+        // 127.5 rounds up, 1.004 rounds down
+        assert_eq!(unpremultiply([1, 100, 0, 2]), [128, 255, 0, 2]);
+        assert_eq!(unpremultiply([100, 1, 0, 200]), [128, 1, 0, 200]);
+        assert_eq!(unpremultiply([1, 0, 0, 254]), [1, 0, 0, 254]);
+    }
+
+    #[test]
+    fn channels_above_alpha_are_clamped() {
+        assert_eq!(unpremultiply([200, 100, 50, 100]), [255, 255, 128, 100]);
+    }
+
+    #[test]
+    fn data_must_match_the_size() {
+        assert!(RgbaImage::from_premultiplied(2, 2, vec![0; 15]).is_none());
+        assert!(RgbaImage::from_premultiplied(2, 2, vec![0; 17]).is_none());
+        let image = RgbaImage::from_premultiplied(2, 1, vec![0, 0, 0, 0, 50, 50, 50, 100]);
+        assert_eq!(image.unwrap().data(), [0, 0, 0, 0, 128, 128, 128, 100]);
+    }
+}
@@ -0,0 +1,1280 @@
+//! Running the backend in a separate renderer process.
+//!
+//! The app process gives its NV tree an [`IpcBackend`], which sends all backend calls over a
+//! local IPC channel (such as a Unix domain socket) to a renderer process. There, an
+//! [`IpcRenderer`] performs them on the actual backend and sends its events back. Hence, crashes in
+//! the platform toolkit or graphics drivers only take down the renderer, and the renderer can run
+//! in a sandbox. Layer properties are [delta-encoded](crate::remote), so animations only send the
+//! properties that change.
+//!
+//! Calls are buffered and only sent when the app polls for events (or calls
+//! [`flush`](IpcBackend::flush)), so an event loop that polls once per frame sends each frame in
+//! one go. Backend errors in the renderer are also only reported when polling; the app should then
+//! [resync](crate::NVTree::resync) its NV tree.
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> Result<(), birb::ipc::IpcError> {
+//! use birb::ipc::IpcBackend;
+//! use birb::NVTree;
+//! use std::os::unix::net::UnixStream;
+//!
+//! let stream = UnixStream::connect("/tmp/renderer.sock")?;
+//! let mut backend = IpcBackend::new(stream.try_clone()?, stream);
+//! let mut nv_tree = NVTree::new(&mut backend);
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+//!
+//! Renderer-only backend features such as offscreen rendering, PDF export, and capturing aren’t
+//! available through the IPC backend.
+//!
+//! Both sides read on a background thread that keeps its half of the connection open, so to
+//! disconnect, shut the connection down explicitly (e.g. with `UnixStream::shutdown`).
+
+use crate::accessibility::{AccessibilitySettings, Announcement};
+use crate::backend::Backend;
+use crate::events::{
+    ControllerAxis, ControllerButton, KeyCode, KeyModifiers, MediaKey, PointerDevice,
+};
+use crate::image::RgbaImage;
+use crate::nv_tree::NativeView;
+use crate::raw_events::{
+    DragEventPhase, HoverEventPhase, KeyEventPhase, PointerEventPhase, RawEvent,
+};
+use crate::remote::{self, DecodeError};
+use core::fmt;
+use crossbeam::channel::{self, Receiver, TryRecvError};
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::thread;
+
+// app → renderer
+const TAG_NEW_VIEW: u8 = 0;
+const TAG_UPDATE_VIEW: u8 = 1;
+const TAG_REPLACE_VIEW: u8 = 2;
+const TAG_REMOVE_VIEW: u8 = 3;
+const TAG_SET_SUBVIEWS: u8 = 4;
+const TAG_SET_ROOT_VIEW: u8 = 5;
+const TAG_SET_ATLAS_PAGE: u8 = 6;
+const TAG_ANNOUNCE: u8 = 7;
+const TAG_SET_DROP_ACCEPTED: u8 = 8;
+const TAG_UPDATE_VIEW_FULL: u8 = 9;
+
+// renderer → app
+const TAG_EVENT: u8 = 0;
+const TAG_ERROR: u8 = 1;
+
+/// The maximum length of a message, which leaves room for an RGBA atlas page of 8192×8192 pixels.
+///
+/// Longer messages are rejected before allocating memory for them.
+const MAX_MESSAGE_LEN: usize = 1 << 29;
+
+/// Errors of the IPC backend and renderer.
+#[derive(Debug)]
+pub enum IpcError {
+    Io(io::Error),
+    /// A message could not be decoded; the connection is unusable.
+    Decode(DecodeError),
+    /// The other process closed the connection.
+    Disconnected,
+    /// The renderer’s backend failed. Contains the error message.
+    Renderer(String),
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpcError::Io(err) => write!(f, "{}", err),
+            IpcError::Decode(err) => write!(f, "invalid message: {}", err),
+            IpcError::Disconnected => write!(f, "disconnected"),
+            IpcError::Renderer(err) => write!(f, "renderer error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl From<io::Error> for IpcError {
+    fn from(err: io::Error) -> IpcError {
+        IpcError::Io(err)
+    }
+}
+
+impl From<DecodeError> for IpcError {
+    fn from(err: DecodeError) -> IpcError {
+        IpcError::Decode(err)
+    }
+}
+
+/// Reads length-prefixed messages on a background thread, since backends must be polled without
+/// blocking. The channel disconnects when the stream ends.
+fn spawn_reader<R>(mut reader: R) -> Receiver<io::Result<Vec<u8>>>
+where
+    R: 'static + Read + Send,
+{
+    let (send, recv) = channel::unbounded();
+    thread::spawn(move || loop {
+        let mut len = [0; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => {
+                let _ = send.send(Err(err));
+                break;
+            }
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_MESSAGE_LEN {
+            let _ = send.send(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message of {} bytes is too long", len),
+            )));
+            break;
+        }
+        let mut message = vec![0; len];
+        let result = reader.read_exact(&mut message).map(|_| message);
+        let failed = result.is_err();
+        if send.send(result).is_err() || failed {
+            break;
+        }
+    });
+    recv
+}
+
+/// Takes the next message, if there is one.
+fn try_recv(messages: &Receiver<io::Result<Vec<u8>>>) -> Result<Option<Vec<u8>>, IpcError> {
+    match messages.try_recv() {
+        Ok(message) => Ok(Some(message?)),
+        Err(TryRecvError::Empty) => Ok(None),
+        Err(TryRecvError::Disconnected) => Err(IpcError::Disconnected),
+    }
+}
+
+/// Writes a length-prefixed message.
+fn write_message<W: Write>(writer: &mut W, message: &[u8]) -> io::Result<()> {
+    writer.write_all(&(message.len() as u32).to_le_bytes())?;
+    writer.write_all(message)
+}
+
+/// A reference to a view in the renderer process.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct IpcViewRef(u64);
+
+/// A backend that forwards everything to a renderer process; see the
+/// [module documentation](self).
+pub struct IpcBackend<W: Write> {
+    writer: BufWriter<W>,
+    messages: Receiver<io::Result<Vec<u8>>>,
+    /// The last state sent for every view, for delta encoding.
+    views: HashMap<u64, NativeView>,
+    next_view: u64,
+}
+
+impl<W: Write> IpcBackend<W> {
+    /// Creates an IPC backend that reads events from `reader` and writes backend calls to
+    /// `writer`, which are usually the two halves of the same connection.
+    pub fn new<R>(reader: R, writer: W) -> IpcBackend<W>
+    where
+        R: 'static + Read + Send,
+    {
+        IpcBackend {
+            writer: BufWriter::new(writer),
+            messages: spawn_reader(reader),
+            views: HashMap::new(),
+            next_view: 0,
+        }
+    }
+
+    /// Sends all buffered backend calls to the renderer.
+    pub fn flush(&mut self) -> Result<(), IpcError> {
+        Ok(self.writer.flush()?)
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), IpcError> {
+        Ok(write_message(&mut self.writer, message)?)
+    }
+
+    fn send_view(&mut self, tag: u8, view: &IpcViewRef, nv: NativeView) -> Result<(), IpcError> {
+        let mut message = vec![tag];
+        remote::write_varint(&mut message, view.0);
+        remote::write_view(&mut message, &nv);
+        self.views.insert(view.0, nv);
+        self.send(&message)
+    }
+}
+
+impl<W: Write> fmt::Debug for IpcBackend<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IpcBackend")
+            .field("views", &self.views.len())
+            .finish()
+    }
+}
+
+impl<W: Write> Backend for IpcBackend<W> {
+    type ViewRef = IpcViewRef;
+    type Error = IpcError;
+
+    fn new_view(&mut self, view: NativeView) -> Result<IpcViewRef, IpcError> {
+        let view_ref = IpcViewRef(self.next_view);
+        self.next_view += 1;
+        self.send_view(TAG_NEW_VIEW, &view_ref, view)?;
+        Ok(view_ref)
+    }
+
+    fn remove_view(&mut self, view: IpcViewRef) -> Result<(), IpcError> {
+        self.views.remove(&view.0);
+        let mut message = vec![TAG_REMOVE_VIEW];
+        remote::write_varint(&mut message, view.0);
+        self.send(&message)
+    }
+
+    fn update_view(&mut self, view: &mut IpcViewRef, nv: NativeView) -> Result<(), IpcError> {
+        // views this backend didn’t create (e.g. refs from before a reconnect) are sent in full
+        let prev = match self.views.get(&view.0) {
+            Some(prev) if remote::is_same_type(prev, &nv) => prev,
+            _ => return self.send_view(TAG_UPDATE_VIEW_FULL, view, nv),
+        };
+        let mut message = vec![TAG_UPDATE_VIEW];
+        remote::write_varint(&mut message, view.0);
+        remote::write_delta(&mut message, prev, &nv);
+        self.views.insert(view.0, nv);
+        self.send(&message)
+    }
+
+    fn replace_view(&mut self, view: &mut IpcViewRef, nv: NativeView) -> Result<(), IpcError> {
+        self.send_view(TAG_REPLACE_VIEW, view, nv)
+    }
+
+    fn set_subviews(
+        &mut self,
+        view: &mut IpcViewRef,
+        region_start: usize,
+        region_len: usize,
+        subviews: Vec<&IpcViewRef>,
+    ) -> Result<(), IpcError> {
+        let mut message = vec![TAG_SET_SUBVIEWS];
+        remote::write_varint(&mut message, view.0);
+        remote::write_varint(&mut message, region_start as u64);
+        remote::write_varint(&mut message, region_len as u64);
+        remote::write_varint(&mut message, subviews.len() as u64);
+        for subview in subviews {
+            remote::write_varint(&mut message, subview.0);
+        }
+        self.send(&message)
+    }
+
+    fn set_root_view(&mut self, view: &mut IpcViewRef) -> Result<(), IpcError> {
+        let mut message = vec![TAG_SET_ROOT_VIEW];
+        remote::write_varint(&mut message, view.0);
+        self.send(&message)
+    }
+
+    fn set_atlas_page(&mut self, page: usize, image: &RgbaImage) -> Result<(), IpcError> {
+        let mut message = vec![TAG_SET_ATLAS_PAGE];
+        remote::write_varint(&mut message, page as u64);
+        remote::write_u32(&mut message, image.width());
+        remote::write_u32(&mut message, image.height());
+        message.extend_from_slice(image.data());
+        self.send(&message)
+    }
+
+    fn announce(&mut self, announcement: &Announcement) -> Result<(), IpcError> {
+        let mut message = vec![TAG_ANNOUNCE];
+        remote::write_announcement(&mut message, announcement);
+        self.send(&message)
+    }
+
+    fn set_drop_accepted(&mut self, accepted: bool) -> Result<(), IpcError> {
+        self.send(&[TAG_SET_DROP_ACCEPTED, accepted as u8])
+    }
+
+    fn poll(&mut self) -> Result<Option<RawEvent>, IpcError> {
+        self.flush()?;
+        let message = match try_recv(&self.messages)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let mut input = &message[..];
+        match remote::read_u8(&mut input)? {
+            TAG_EVENT => Ok(Some(read_event(&mut input)?)),
+            TAG_ERROR => Err(IpcError::Renderer(remote::read_string(&mut input)?)),
+            _ => Err(DecodeError::Invalid.into()),
+        }
+    }
+}
+
+/// Performs backend calls from an [`IpcBackend`] in the renderer process; see the
+/// [module documentation](self).
+pub struct IpcRenderer<B: Backend, W: Write> {
+    backend: B,
+    writer: BufWriter<W>,
+    messages: Receiver<io::Result<Vec<u8>>>,
+    /// The last received state of every view, for delta encoding.
+    views: HashMap<u64, NativeView>,
+    /// Backend views; missing if the backend failed to create them.
+    view_refs: HashMap<u64, B::ViewRef>,
+}
+
+impl<B: Backend, W: Write> IpcRenderer<B, W>
+where
+    B::Error: fmt::Debug,
+{
+    /// Creates a renderer that reads backend calls from `reader` and writes events to `writer`.
+    pub fn new<R>(backend: B, reader: R, writer: W) -> IpcRenderer<B, W>
+    where
+        R: 'static + Read + Send,
+    {
+        IpcRenderer {
+            backend,
+            writer: BufWriter::new(writer),
+            messages: spawn_reader(reader),
+            views: HashMap::new(),
+            view_refs: HashMap::new(),
+        }
+    }
+
+    /// Returns the backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backend.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Performs all backend calls received so far and sends all pending events to the app.
+    /// Should be called regularly on the thread the backend runs on, e.g. once per frame.
+    ///
+    /// Backend errors are sent to the app. Returns false once the app has closed the connection.
+    pub fn pump(&mut self) -> Result<bool, IpcError> {
+        let mut is_connected = true;
+        loop {
+            match try_recv(&self.messages) {
+                Ok(Some(message)) => self.perform(&message)?,
+                Ok(None) => break,
+                Err(IpcError::Disconnected) => {
+                    is_connected = false;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        loop {
+            match self.backend.poll() {
+                Ok(Some(event)) => {
+                    let mut message = vec![TAG_EVENT];
+                    write_event(&mut message, &event);
+                    write_message(&mut self.writer, &message)?;
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    // a backend that keeps failing (e.g. because its display connection is gone)
+                    // would never stop polling, so it’s reported once per pump
+                    let mut message = vec![TAG_ERROR];
+                    remote::write_str(&mut message, &format!("{:?}", err));
+                    write_message(&mut self.writer, &message)?;
+                    break;
+                }
+            }
+        }
+        self.writer.flush()?;
+        Ok(is_connected)
+    }
+
+    /// Performs a backend call. Returns an error only if the message is invalid.
+    fn perform(&mut self, message: &[u8]) -> Result<(), IpcError> {
+        let mut input = message;
+        let input = &mut input;
+        let result = match remote::read_u8(input)? {
+            TAG_NEW_VIEW => {
+                let id = remote::read_varint(input)?;
+                let view = remote::read_view(input)?;
+                self.views.insert(id, view.clone());
+                self.backend.new_view(view).map(|view_ref| {
+                    self.view_refs.insert(id, view_ref);
+                })
+            }
+            TAG_UPDATE_VIEW => {
+                let id = remote::read_varint(input)?;
+                let view = self.views.get_mut(&id).ok_or(DecodeError::Invalid)?;
+                remote::read_delta(input, view)?;
+                let view = view.clone();
+                match self.view_refs.get_mut(&id) {
+                    Some(view_ref) => self.backend.update_view(view_ref, view),
+                    None => Ok(()),
+                }
+            }
+            TAG_UPDATE_VIEW_FULL => {
+                let id = remote::read_varint(input)?;
+                let view = remote::read_view(input)?;
+                self.views.insert(id, view.clone());
+                match self.view_refs.get_mut(&id) {
+                    Some(view_ref) => self.backend.update_view(view_ref, view),
+                    None => Ok(()),
+                }
+            }
+            TAG_REPLACE_VIEW => {
+                let id = remote::read_varint(input)?;
+                let view = remote::read_view(input)?;
+                self.views.insert(id, view.clone());
+                match self.view_refs.get_mut(&id) {
+                    Some(view_ref) => self.backend.replace_view(view_ref, view),
+                    None => Ok(()),
+                }
+            }
+            TAG_REMOVE_VIEW => {
+                let id = remote::read_varint(input)?;
+                self.views.remove(&id);
+                match self.view_refs.remove(&id) {
+                    Some(view_ref) => self.backend.remove_view(view_ref),
+                    None => Ok(()),
+                }
+            }
+            TAG_SET_SUBVIEWS => {
+                let id = remote::read_varint(input)?;
+                let start = remote::read_varint(input)? as usize;
+                let len = remote::read_varint(input)? as usize;
+                let count = remote::read_varint(input)? as usize;
+                let mut subviews = Vec::new();
+                for _ in 0..count {
+                    subviews.push(remote::read_varint(input)?);
+                }
+                // views that failed to be created are left out
+                let view_refs = &mut self.view_refs;
+                let mut view_ref = view_refs.remove(&id);
+                let result = match &mut view_ref {
+                    Some(view_ref) => {
+                        let subviews = subviews.iter().filter_map(|id| view_refs.get(id));
+                        self.backend
+                            .set_subviews(view_ref, start, len, subviews.collect())
+                    }
+                    None => Ok(()),
+                };
+                if let Some(view_ref) = view_ref {
+                    view_refs.insert(id, view_ref);
+                }
+                result
+            }
+            TAG_SET_ROOT_VIEW => {
+                let id = remote::read_varint(input)?;
+                match self.view_refs.get_mut(&id) {
+                    Some(view_ref) => self.backend.set_root_view(view_ref),
+                    None => Ok(()),
+                }
+            }
+            TAG_SET_ATLAS_PAGE => {
+                let page = remote::read_varint(input)? as usize;
+                let width = remote::read_u32(input)?;
+                let height = remote::read_u32(input)?;
+                let len = width as usize * height as usize * 4;
+                let data = remote::read_bytes(input, len)?.to_vec();
+                let image = RgbaImage::from_raw(width, height, data).ok_or(DecodeError::Invalid)?;
+                self.backend.set_atlas_page(page, &image)
+            }
+            TAG_ANNOUNCE => {
+                let announcement = remote::read_announcement(input)?;
+                self.backend.announce(&announcement)
+            }
+            TAG_SET_DROP_ACCEPTED => {
+                let accepted = remote::read_bool(input)?;
+                self.backend.set_drop_accepted(accepted)
+            }
+            _ => return Err(DecodeError::Invalid.into()),
+        };
+        if let Err(err) = result {
+            let mut message = vec![TAG_ERROR];
+            remote::write_str(&mut message, &format!("{:?}", err));
+            write_message(&mut self.writer, &message)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Backend, W: Write> fmt::Debug for IpcRenderer<B, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IpcRenderer")
+            .field("views", &self.views.len())
+            .finish()
+    }
+}
+
+fn write_point(out: &mut Vec<u8>, (x, y): (f64, f64)) {
+    remote::write_f64(out, x);
+    remote::write_f64(out, y);
+}
+
+fn write_modifiers(out: &mut Vec<u8>, modifiers: KeyModifiers) {
+    out.push(
+        modifiers.shift() as u8
+            | (modifiers.control() as u8) << 1
+            | (modifiers.option() as u8) << 2
+            | (modifiers.command() as u8) << 3,
+    );
+}
+
+fn write_event(out: &mut Vec<u8>, event: &RawEvent) {
+    match event {
+        RawEvent::Hover {
+            device,
+            root_location,
+            tilt,
+            event_id,
+            unique_id,
+            phase,
+            modifiers,
+        } => {
+            out.push(0);
+            out.push(*device as u8);
+            write_point(out, *root_location);
+            write_point(out, (tilt.0, tilt.1));
+            remote::write_f64(out, tilt.2);
+            remote::write_varint(out, *event_id as u64);
+            out.extend_from_slice(&unique_id.to_le_bytes());
+            out.push(*phase as u8);
+            write_modifiers(out, *modifiers);
+        }
+        RawEvent::Pointer {
+            device,
+            root_location,
+            pressure,
+            tilt,
+            event_id,
+            unique_id,
+            phase,
+            modifiers,
+            click_count,
+        } => {
+            out.push(1);
+            out.push(*device as u8);
+            write_point(out, *root_location);
+            remote::write_f64(out, *pressure);
+            write_point(out, (tilt.0, tilt.1));
+            remote::write_f64(out, tilt.2);
+            remote::write_varint(out, *event_id as u64);
+            out.extend_from_slice(&unique_id.to_le_bytes());
+            out.push(*phase as u8);
+            write_modifiers(out, *modifiers);
+            remote::write_u32(out, *click_count);
+        }
+        RawEvent::Key {
+            chars,
+            chars_without_mod,
+            key_code,
+            phase,
+            modifiers,
+        } => {
+            out.push(2);
+            remote::write_str(out, chars);
+            remote::write_str(out, chars_without_mod);
+            out.push(*key_code as u8);
+            out.push(*phase as u8);
+            write_modifiers(out, *modifiers);
+        }
+        RawEvent::Scroll {
+            root_location,
+            delta,
+            is_discrete,
+            is_direction_inverted,
+            modifiers,
+        } => {
+            out.push(3);
+            write_point(out, *root_location);
+            write_point(out, *delta);
+            out.push(*is_discrete as u8);
+            out.push(*is_direction_inverted as u8);
+            write_modifiers(out, *modifiers);
+        }
+        RawEvent::FileDrag {
+            root_location,
+            phase,
+            paths,
+        } => {
+            out.push(4);
+            write_point(out, *root_location);
+            out.push(*phase as u8);
+            remote::write_varint(out, paths.len() as u64);
+            for path in paths {
+                // non-UTF-8 paths can’t be sent portably and are rare enough
+                remote::write_str(out, &path.to_string_lossy());
+            }
+        }
+        RawEvent::MediaKey { key, phase } => {
+            out.push(5);
+            out.push(*key as u8);
+            out.push(*phase as u8);
+        }
+        RawEvent::ControllerConnection {
+            controller,
+            is_connected,
+        } => {
+            out.push(6);
+            remote::write_varint(out, *controller);
+            out.push(*is_connected as u8);
+        }
+        RawEvent::ControllerButton {
+            controller,
+            button,
+            value,
+            is_pressed,
+        } => {
+            out.push(7);
+            remote::write_varint(out, *controller);
+            out.push(*button as u8);
+            remote::write_f64(out, *value);
+            out.push(*is_pressed as u8);
+        }
+        RawEvent::ControllerAxis {
+            controller,
+            axis,
+            value,
+        } => {
+            out.push(8);
+            remote::write_varint(out, *controller);
+            out.push(*axis as u8);
+            remote::write_f64(out, *value);
+        }
+        RawEvent::SetRootSize { size } => {
+            out.push(9);
+            write_point(out, *size);
+        }
+        RawEvent::SetAccessibility { settings } => {
+            out.push(10);
+            out.push(
+                settings.reduce_motion as u8
+                    | (settings.reduce_transparency as u8) << 1
+                    | (settings.increase_contrast as u8) << 2,
+            );
+        }
+        RawEvent::SetVisibility { is_visible } => {
+            out.push(11);
+            out.push(*is_visible as u8);
+        }
+        RawEvent::Proximity {
+            device,
+            unique_id,
+            is_entering,
+        } => {
+            out.push(13);
+            out.push(*device as u8);
+            out.extend_from_slice(&unique_id.to_le_bytes());
+            out.push(*is_entering as u8);
+        }
+        RawEvent::SetScaleFactor { scale_factor } => {
+            out.push(12);
+            remote::write_f64(out, *scale_factor);
+        }
+    }
+}
+
+fn read_point(input: &mut &[u8]) -> Result<(f64, f64), DecodeError> {
+    Ok((remote::read_f64(input)?, remote::read_f64(input)?))
+}
+
+fn read_tilt(input: &mut &[u8]) -> Result<(f64, f64, f64), DecodeError> {
+    let (x, y) = read_point(input)?;
+    Ok((x, y, remote::read_f64(input)?))
+}
+
+fn read_u128(input: &mut &[u8]) -> Result<u128, DecodeError> {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(remote::read_bytes(input, 16)?);
+    Ok(u128::from_le_bytes(bytes))
+}
+
+/// Reads an enum value encoded as its index in `variants`.
+fn read_enum<T: Copy>(input: &mut &[u8], variants: &[T]) -> Result<T, DecodeError> {
+    let index = remote::read_u8(input)? as usize;
+    variants.get(index).copied().ok_or(DecodeError::Invalid)
+}
+
+fn read_device(input: &mut &[u8]) -> Result<PointerDevice, DecodeError> {
+    use PointerDevice::*;
+    read_enum(input, &[Touch, Pen, Eraser, Cursor])
+}
+
+fn read_key_phase(input: &mut &[u8]) -> Result<KeyEventPhase, DecodeError> {
+    use KeyEventPhase::*;
+    read_enum(input, &[Pressed, Released, Repeat])
+}
+
+fn read_modifiers(input: &mut &[u8]) -> Result<KeyModifiers, DecodeError> {
+    let bits = remote::read_u8(input)?;
+    Ok(KeyModifiers::new(
+        bits & 1 != 0,
+        bits & 2 != 0,
+        bits & 4 != 0,
+        bits & 8 != 0,
+    ))
+}
+
+fn read_event(input: &mut &[u8]) -> Result<RawEvent, DecodeError> {
+    let event = match remote::read_u8(input)? {
+        0 => RawEvent::Hover {
+            device: read_device(input)?,
+            root_location: read_point(input)?,
+            tilt: read_tilt(input)?,
+            event_id: remote::read_varint(input)? as usize,
+            unique_id: read_u128(input)?,
+            phase: {
+                use HoverEventPhase::*;
+                read_enum(input, &[Entered, Moved, Stationary, Left])?
+            },
+            modifiers: read_modifiers(input)?,
+        },
+        1 => RawEvent::Pointer {
+            device: read_device(input)?,
+            root_location: read_point(input)?,
+            pressure: remote::read_f64(input)?,
+            tilt: read_tilt(input)?,
+            event_id: remote::read_varint(input)? as usize,
+            unique_id: read_u128(input)?,
+            phase: {
+                use PointerEventPhase::*;
+                read_enum(input, &[Began, Moved, Stationary, Ended, Canceled])?
+            },
+            modifiers: read_modifiers(input)?,
+            click_count: remote::read_u32(input)?,
+        },
+        2 => RawEvent::Key {
+            chars: remote::read_string(input)?,
+            chars_without_mod: remote::read_string(input)?,
+            key_code: KeyCode::from_u8(remote::read_u8(input)?).ok_or(DecodeError::Invalid)?,
+            phase: read_key_phase(input)?,
+            modifiers: read_modifiers(input)?,
+        },
+        3 => RawEvent::Scroll {
+            root_location: read_point(input)?,
+            delta: read_point(input)?,
+            is_discrete: remote::read_bool(input)?,
+            is_direction_inverted: remote::read_bool(input)?,
+            modifiers: read_modifiers(input)?,
+        },
+        4 => RawEvent::FileDrag {
+            root_location: read_point(input)?,
+            phase: {
+                use DragEventPhase::*;
+                read_enum(input, &[Entered, Moved, Exited, Dropped])?
+            },
+            paths: {
+                let count = remote::read_varint(input)?;
+                let mut paths = Vec::new();
+                for _ in 0..count {
+                    paths.push(PathBuf::from(remote::read_string(input)?));
+                }
+                paths
+            },
+        },
+        5 => RawEvent::MediaKey {
+            key: {
+                use MediaKey::*;
+                let keys = [
+                    PlayPause,
+                    Next,
+                    Previous,
+                    FastForward,
+                    Rewind,
+                    VolumeUp,
+                    VolumeDown,
+                    Mute,
+                ];
+                read_enum(input, &keys)?
+            },
+            phase: read_key_phase(input)?,
+        },
+        6 => RawEvent::ControllerConnection {
+            controller: remote::read_varint(input)?,
+            is_connected: remote::read_bool(input)?,
+        },
+        7 => RawEvent::ControllerButton {
+            controller: remote::read_varint(input)?,
+            button: {
+                use ControllerButton::*;
+                let buttons = [
+                    A,
+                    B,
+                    X,
+                    Y,
+                    LeftShoulder,
+                    RightShoulder,
+                    LeftTrigger,
+                    RightTrigger,
+                    DpadUp,
+                    DpadDown,
+                    DpadLeft,
+                    DpadRight,
+                    LeftThumbstick,
+                    RightThumbstick,
+                    Menu,
+                    Options,
+                ];
+                read_enum(input, &buttons)?
+            },
+            value: remote::read_f64(input)?,
+            is_pressed: remote::read_bool(input)?,
+        },
+        8 => RawEvent::ControllerAxis {
+            controller: remote::read_varint(input)?,
+            axis: {
+                use ControllerAxis::*;
+                read_enum(input, &[LeftX, LeftY, RightX, RightY])?
+            },
+            value: remote::read_f64(input)?,
+        },
+        9 => RawEvent::SetRootSize {
+            size: read_point(input)?,
+        },
+        10 => {
+            let bits = remote::read_u8(input)?;
+            RawEvent::SetAccessibility {
+                settings: AccessibilitySettings {
+                    reduce_motion: bits & 1 != 0,
+                    reduce_transparency: bits & 2 != 0,
+                    increase_contrast: bits & 4 != 0,
+                },
+            }
+        }
+        11 => RawEvent::SetVisibility {
+            is_visible: remote::read_bool(input)?,
+        },
+        12 => RawEvent::SetScaleFactor {
+            scale_factor: remote::read_f64(input)?,
+        },
+        13 => RawEvent::Proximity {
+            device: read_device(input)?,
+            unique_id: read_u128(input)?,
+            is_entering: remote::read_bool(input)?,
+        },
+        _ => return Err(DecodeError::Invalid),
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::layer::LayerProps;
+    use crate::nv_tree::{NVTree, Patch};
+    use crate::rect::Rect;
+    use crate::view::ViewId;
+    use cgmath::{Point2, Vector2};
+    use crossbeam::channel::Sender;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    /// The writing end of an in-memory pipe.
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _ = self.0.send(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The reading end of an in-memory pipe; ends when the writer is dropped.
+    struct PipeReader {
+        bytes: Receiver<Vec<u8>>,
+        buffer: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.pos == self.buffer.len() {
+                match self.bytes.recv() {
+                    Ok(bytes) => {
+                        self.buffer = bytes;
+                        self.pos = 0;
+                    }
+                    Err(_) => return Ok(0),
+                }
+            }
+            let len = buf.len().min(self.buffer.len() - self.pos);
+            buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+
+    fn pipe() -> (PipeReader, PipeWriter) {
+        let (send, recv) = channel::unbounded();
+        let reader = PipeReader {
+            bytes: recv,
+            buffer: Vec::new(),
+            pos: 0,
+        };
+        (reader, PipeWriter(send))
+    }
+
+    /// A backend that keeps its view tree in memory and can be made to fail.
+    #[derive(Default)]
+    struct MemoryBackend {
+        views: HashMap<usize, NativeView>,
+        subviews: HashMap<usize, Vec<usize>>,
+        root: Option<usize>,
+        next_view: usize,
+        events: VecDeque<RawEvent>,
+        fail: bool,
+        fail_polls: bool,
+    }
+
+    impl MemoryBackend {
+        /// Returns the views of the root view’s subtree in tree order.
+        fn tree(&self) -> Vec<NativeView> {
+            let mut views = Vec::new();
+            let mut stack: Vec<_> = self.root.into_iter().collect();
+            while let Some(id) = stack.pop() {
+                views.push(self.views[&id].clone());
+                if let Some(subviews) = self.subviews.get(&id) {
+                    stack.extend(subviews.iter().rev());
+                }
+            }
+            views
+        }
+
+        fn result(&self) -> Result<(), &'static str> {
+            if self.fail {
+                Err("failed")
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Backend for MemoryBackend {
+        type ViewRef = usize;
+        type Error = &'static str;
+
+        fn new_view(&mut self, view: NativeView) -> Result<usize, &'static str> {
+            self.result()?;
+            let id = self.next_view;
+            self.next_view += 1;
+            self.views.insert(id, view);
+            Ok(id)
+        }
+        fn remove_view(&mut self, view: usize) -> Result<(), &'static str> {
+            self.views.remove(&view);
+            self.subviews.remove(&view);
+            self.result()
+        }
+        fn update_view(&mut self, id: &mut usize, view: NativeView) -> Result<(), &'static str> {
+            self.result()?;
+            self.views.insert(*id, view);
+            Ok(())
+        }
+        fn replace_view(&mut self, id: &mut usize, view: NativeView) -> Result<(), &'static str> {
+            self.subviews.remove(id);
+            self.update_view(id, view)
+        }
+        fn set_subviews(
+            &mut self,
+            id: &mut usize,
+            start: usize,
+            len: usize,
+            subviews: Vec<&usize>,
+        ) -> Result<(), &'static str> {
+            self.result()?;
+            let current = self.subviews.entry(*id).or_default();
+            let end = (start + len).min(current.len());
+            let start = start.min(end);
+            current.splice(start..end, subviews.into_iter().copied());
+            Ok(())
+        }
+        fn set_root_view(&mut self, id: &mut usize) -> Result<(), &'static str> {
+            self.result()?;
+            self.root = Some(*id);
+            Ok(())
+        }
+        fn poll(&mut self) -> Result<Option<RawEvent>, &'static str> {
+            if self.fail_polls {
+                return Err("disconnected");
+            }
+            Ok(self.events.pop_front())
+        }
+    }
+
+    type Renderer = IpcRenderer<MemoryBackend, PipeWriter>;
+
+    /// Returns an IPC backend connected to a renderer.
+    fn connect() -> (IpcBackend<PipeWriter>, Renderer) {
+        let (app_reader, renderer_writer) = pipe();
+        let (renderer_reader, app_writer) = pipe();
+        let backend = IpcBackend::new(app_reader, app_writer);
+        let renderer = IpcRenderer::new(MemoryBackend::default(), renderer_reader, renderer_writer);
+        (backend, renderer)
+    }
+
+    /// Pumps the renderer until its backend satisfies a condition, since messages arrive on a
+    /// background thread.
+    fn pump_until(renderer: &mut Renderer, condition: impl Fn(&MemoryBackend) -> bool) {
+        for _ in 0..1000 {
+            assert!(renderer.pump().unwrap());
+            if condition(renderer.backend()) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("renderer backend never reached the expected state");
+    }
+
+    /// Pumps the renderer and polls the backend until the backend returns something.
+    fn poll_until_some(
+        backend: &mut IpcBackend<PipeWriter>,
+        renderer: &mut Renderer,
+    ) -> Result<RawEvent, IpcError> {
+        for _ in 0..1000 {
+            assert!(renderer.pump().unwrap());
+            if let Some(event) = backend.poll()? {
+                return Ok(event);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("no message from the renderer");
+    }
+
+    fn layer(x: f64, opacity: f64) -> NativeView {
+        NativeView::Layer(Box::new(LayerProps {
+            bounds: Rect::new(Point2::new(x, 0.), Vector2::new(10., 10.)),
+            background: Color::rgba(1., 0., 0., 1.),
+            opacity,
+            ..LayerProps::default()
+        }))
+    }
+
+    #[test]
+    fn events_round_trip() {
+        use crate::raw_events::*;
+        let modifiers = KeyModifiers::new(true, false, true, true);
+        let events = [
+            RawEvent::Hover {
+                device: PointerDevice::Pen,
+                root_location: (1.5, -2.),
+                tilt: (0.25, 0.5, 0.75),
+                event_id: 3,
+                unique_id: u128::MAX - 1,
+                phase: HoverEventPhase::Stationary,
+                modifiers,
+            },
+            RawEvent::Pointer {
+                device: PointerDevice::Eraser,
+                root_location: (10., 20.),
+                pressure: 0.3,
+                tilt: (0., 0.5, 0.5),
+                event_id: 1 << 40,
+                unique_id: 12345,
+                phase: PointerEventPhase::Canceled,
+                modifiers: KeyModifiers::NONE,
+                click_count: 2,
+            },
+            RawEvent::Key {
+                chars: "é".into(),
+                chars_without_mod: "e".into(),
+                key_code: KeyCode::E,
+                phase: KeyEventPhase::Repeat,
+                modifiers,
+            },
+            RawEvent::Key {
+                chars: String::new(),
+                chars_without_mod: String::new(),
+                key_code: KeyCode::Escape,
+                phase: KeyEventPhase::Released,
+                modifiers: KeyModifiers::NONE,
+            },
+            RawEvent::Scroll {
+                root_location: (5., 6.),
+                delta: (-1., 3.5),
+                is_discrete: true,
+                is_direction_inverted: true,
+                modifiers,
+            },
+            RawEvent::FileDrag {
+                root_location: (7., 8.),
+                phase: DragEventPhase::Dropped,
+                paths: vec!["/tmp/a.txt".into(), "/tmp/b c.png".into()],
+            },
+            RawEvent::MediaKey {
+                key: MediaKey::Mute,
+                phase: KeyEventPhase::Pressed,
+            },
+            RawEvent::ControllerConnection {
+                controller: 9,
+                is_connected: true,
+            },
+            RawEvent::ControllerButton {
+                controller: 9,
+                button: ControllerButton::Options,
+                value: 0.75,
+                is_pressed: true,
+            },
+            RawEvent::ControllerAxis {
+                controller: u64::MAX,
+                axis: ControllerAxis::RightY,
+                value: -1.,
+            },
+            RawEvent::SetRootSize { size: (800., 600.) },
+            RawEvent::SetAccessibility {
+                settings: AccessibilitySettings {
+                    reduce_motion: true,
+                    reduce_transparency: false,
+                    increase_contrast: true,
+                },
+            },
+            RawEvent::SetVisibility { is_visible: false },
+            RawEvent::Proximity {
+                device: PointerDevice::Eraser,
+                unique_id: u128::MAX,
+                is_entering: true,
+            },
+            RawEvent::Proximity {
+                device: PointerDevice::Pen,
+                unique_id: 0,
+                is_entering: false,
+            },
+            RawEvent::SetScaleFactor { scale_factor: 1.5 },
+        ];
+
+        let mut data = Vec::new();
+        for event in &events {
+            write_event(&mut data, event);
+        }
+        let mut input = &data[..];
+        for event in &events {
+            assert_eq!(&read_event(&mut input).unwrap(), event);
+        }
+        assert!(input.is_empty());
+
+        // every variant is covered
+        let mut tags: Vec<_> = events
+            .iter()
+            .map(|event| {
+                let mut data = Vec::new();
+                write_event(&mut data, event);
+                data[0]
+            })
+            .collect();
+        tags.sort();
+        tags.dedup();
+        assert_eq!(tags, (0..14).collect::<Vec<_>>());
+
+        assert_eq!(read_event(&mut &[14][..]), Err(DecodeError::Invalid));
+        assert!(read_event(&mut &data[..10]).is_err());
+    }
+
+    #[test]
+    fn patches_reach_the_renderer() {
+        let (backend, mut renderer) = connect();
+        let mut tree = NVTree::new(Box::new(backend));
+        let (root, a, b) = (ViewId::new(), ViewId::new(), ViewId::new());
+
+        tree.patch_frame(vec![
+            Patch::Update(root, layer(0., 1.)),
+            Patch::Update(a, layer(10., 1.)),
+            Patch::Update(b, layer(20., 1.)),
+            Patch::SubviewRegion(root, 0, 0, vec![a, b]),
+            Patch::SetRoot(root),
+        ])
+        .unwrap();
+        tree.backend_mut().flush().unwrap();
+        pump_until(&mut renderer, |backend| backend.tree().len() == 3);
+        assert!(renderer.backend().tree() == [layer(0., 1.), layer(10., 1.), layer(20., 1.)]);
+
+        // delta updates, reordering, and removal
+        tree.patch_frame(vec![
+            Patch::Update(a, layer(10., 0.5)),
+            Patch::SubviewRegion(root, 0, 2, vec![b, a]),
+        ])
+        .unwrap();
+        tree.backend_mut().flush().unwrap();
+        pump_until(&mut renderer, |backend| {
+            backend.tree() == [layer(0., 1.), layer(20., 1.), layer(10., 0.5)]
+        });
+        tree.patch_frame(vec![
+            Patch::SubviewRegion(root, 0, 1, vec![]),
+            Patch::Remove(b),
+        ])
+        .unwrap();
+        tree.backend_mut().flush().unwrap();
+        pump_until(&mut renderer, |backend| backend.views.len() == 2);
+        assert!(renderer.backend().tree() == [layer(0., 1.), layer(10., 0.5)]);
+
+        // events go the other way
+        let event = RawEvent::SetRootSize { size: (30., 40.) };
+        renderer.backend_mut().events.push_back(event.clone());
+        assert_eq!(
+            poll_until_some(tree.backend_mut(), &mut renderer).unwrap(),
+            event
+        );
+
+        // as do backend errors
+        renderer.backend_mut().fail = true;
+        tree.patch_frame(vec![Patch::Update(a, layer(10., 0.25))])
+            .unwrap();
+        match poll_until_some(tree.backend_mut(), &mut renderer) {
+            Err(IpcError::Renderer(message)) => assert!(message.contains("failed")),
+            result => panic!("expected a renderer error, got {:?}", result),
+        }
+
+        // after a resync, the renderer has all views again
+        renderer.backend_mut().fail = false;
+        tree.resync().unwrap();
+        tree.backend_mut().flush().unwrap();
+        pump_until(&mut renderer, |backend| {
+            backend.tree() == [layer(0., 1.), layer(10., 0.25)]
+        });
+    }
+
+    #[test]
+    fn failing_polls_are_reported_once_per_pump() {
+        let (mut backend, mut renderer) = connect();
+        renderer.backend_mut().fail_polls = true;
+        for _ in 0..3 {
+            assert!(renderer.pump().unwrap());
+        }
+
+        let mut errors = 0;
+        for _ in 0..1000 {
+            match backend.poll() {
+                Err(IpcError::Renderer(message)) => {
+                    assert!(message.contains("disconnected"));
+                    errors += 1;
+                }
+                Ok(None) if errors == 3 => break,
+                Ok(None) => thread::sleep(Duration::from_millis(1)),
+                result => panic!("expected a renderer error, got {:?}", result),
+            }
+        }
+        assert_eq!(errors, 3);
+        thread::sleep(Duration::from_millis(10));
+        assert!(backend.poll().unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_views_are_sent_in_full() {
+        let mut backend = IpcBackend::new(io::empty(), Vec::new());
+        backend
+            .update_view(&mut IpcViewRef(7), layer(0., 1.))
+            .unwrap();
+        backend.flush().unwrap();
+        let sent = backend.writer.get_ref();
+        assert_eq!(sent[4], TAG_UPDATE_VIEW_FULL);
+
+        // and the renderer takes them as new state
+        let (reader, _writer) = pipe();
+        let (_, renderer_writer) = pipe();
+        let mut renderer = IpcRenderer::new(MemoryBackend::default(), reader, renderer_writer);
+        renderer.perform(&sent[4..]).unwrap();
+        assert!(renderer.views.get(&7) == Some(&layer(0., 1.)));
+    }
+}
@@ -1,47 +1,252 @@
-use crate::color::Color;
-use crate::events::{EventHandler, Hover, Key, Pointer, Scroll};
+use crate::animation::Transitions;
+use crate::atlas::{AtlasRegion, CapInsets};
+use crate::color::{Color, Gradient};
+use crate::events::{
+    Controller, EventHandler, EventHandlers, FileDrop, Hover, Key, Media, Pointer, PointingDevice,
+    Scroll, Tap,
+};
 use crate::impl_view;
-use crate::rect::Rect;
-use crate::view::{Fragment, Layout, NativeType, View};
-use cgmath::{Matrix3, SquareMatrix};
+use crate::layout::Layout;
+use crate::nv_tree::NativeView;
+use crate::rect::{CornerRadius, Rect};
+use crate::shape::Shape;
+use crate::view::{Fragment, NativeType, View};
+use cgmath::{Matrix3, SquareMatrix, Vector2};
 use core::fmt;
 
 /// A native view that contains graphical content and may have subviews.
-pub struct Layer<Ctx> {
+pub struct Layer<Ctx = ()> {
     pub key: Option<u64>,
 
+    /// Properties of the native layer.
+    pub props: LayerProps,
+
+    /// Subviews of this layer.
+    pub subviews: Fragment<Ctx>,
+
+    /// Layout handler for this layer.
+    pub layout: Box<dyn Layout>,
+
+    /// Transitions for when this layer is inserted or removed.
+    pub transitions: Option<Transitions>,
+
+    // event handlers
+    pub pointer_action: Option<EventHandler<Pointer>>,
+    pub hover_action: Option<EventHandler<Hover>>,
+    pub key_action: Option<EventHandler<Key>>,
+    pub scroll_action: Option<EventHandler<Scroll>>,
+    pub tap_action: Option<EventHandler<Tap>>,
+    pub media_action: Option<EventHandler<Media>>,
+    pub controller_action: Option<EventHandler<Controller>>,
+    pub file_drop_action: Option<EventHandler<FileDrop>>,
+    pub pointing_device_action: Option<EventHandler<PointingDevice>>,
+}
+
+/// Properties of a layer, shared by [`Layer`] and the native view it creates.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerProps {
     /// Layer bounds.
     pub bounds: Rect,
 
     /// Background color, with which the layer bounds will be filled--respecting the corner radius.
     pub background: Color,
 
-    /// Corner radius.
-    pub corner_radius: f64,
+    /// A gradient drawn over the background color, also respecting the corner radius.
+    pub background_gradient: Option<Gradient>,
+
+    /// If true, the background is a translucent material that content behind the layer shows
+    /// through, like that of a sidebar or a popover. It is made opaque when the user prefers
+    /// [reduced transparency](crate::accessibility::AccessibilitySettings::reduce_transparency).
+    pub material: bool,
+
+    /// Corner radii, e.g. `8.0.into()` for the same radius on all corners.
+    pub corner_radius: CornerRadius,
+
+    /// If true (the default), hit testing respects the corner radius, so points in the cut-off
+    /// corners (e.g. of a circular button) don’t hit the layer.
+    pub rounded_hit_testing: bool,
+
+    /// If false, hit testing skips the layer and its subviews, so events go to whatever is behind
+    /// them; e.g. for hidden content that is kept alive. True by default.
+    pub hit_testing: bool,
 
     /// Border (width, color).
     pub border: Option<(f64, Color)>,
 
-    /// Whether contents will be clipped to the layer’s bounds.
+    /// Where the border is drawn relative to the edge of the layer bounds.
+    pub border_alignment: BorderAlignment,
+
+    /// Whether the border is solid or dashed.
+    pub border_style: BorderStyle,
+
+    /// Whether contents will be clipped to the layer’s bounds (respecting the corner radius).
     pub clip_contents: bool,
 
+    /// If set, subviews are clipped to this shape in the layer’s coordinate system instead,
+    /// regardless of `clip_contents`.
+    pub clip_shape: Option<Shape>,
+
     /// Layer affine transform.
     pub transform: Matrix3<f64>,
 
-    /// Layer opacity.
+    /// Layer opacity, which also applies to all subviews (on top of their own opacity).
     pub opacity: f64,
 
-    /// Subviews of this layer.
-    pub subviews: Fragment<Ctx>,
+    /// If true (the default), the layer and its subviews are composited together before the
+    /// opacity is applied, so they fade as one, without overlapping subviews showing through each
+    /// other. If false, the opacity is applied to each of them separately, which is cheaper for
+    /// backends but only looks the same if nothing overlaps.
+    pub group_opacity: bool,
 
-    /// Layout handler for this layer.
-    pub layout: Box<dyn Layout>,
+    /// If true, the backend may cache the composited contents of this layer and its subviews as
+    /// a bitmap.
+    ///
+    /// Useful for expensive static subtrees that are transformed or faded frequently, but wasteful
+    /// if the contents change often.
+    pub should_rasterize: bool,
 
-    // event handlers
-    pub pointer_action: Option<EventHandler<Pointer>>,
-    pub hover_action: Option<EventHandler<Hover>>,
-    pub key_action: Option<EventHandler<Key>>,
-    pub scroll_action: Option<EventHandler<Scroll>>,
+    /// A region of an [atlas](crate::atlas) page to draw as the layer’s contents, drawn above the
+    /// background.
+    ///
+    /// The page must have been uploaded with [`NVTree::sync_atlas`](crate::NVTree::sync_atlas).
+    pub contents: Option<AtlasRegion>,
+
+    /// Cap insets for [nine-slice scaling](CapInsets) of the contents, in pixels of the region.
+    ///
+    /// Insets larger than the region are shrunk to fit.
+    pub contents_insets: CapInsets,
+
+    /// How the contents are placed in the layer bounds. Cap insets only apply if they’re resized
+    /// to fill the bounds (the default).
+    pub contents_gravity: ContentsGravity,
+}
+
+impl Default for LayerProps {
+    fn default() -> LayerProps {
+        LayerProps {
+            bounds: Rect::zero(),
+            background: Color::default(),
+            background_gradient: None,
+            material: false,
+            corner_radius: CornerRadius::default(),
+            rounded_hit_testing: true,
+            hit_testing: true,
+            border: None,
+            border_alignment: BorderAlignment::default(),
+            border_style: BorderStyle::default(),
+            clip_contents: false,
+            clip_shape: None,
+            transform: Matrix3::identity(),
+            opacity: 1.,
+            group_opacity: true,
+            should_rasterize: false,
+            contents: None,
+            contents_insets: CapInsets::default(),
+            contents_gravity: ContentsGravity::default(),
+        }
+    }
+}
+
+impl LayerProps {
+    /// Returns the border width, or zero if there is no border.
+    pub fn border_width(&self) -> f64 {
+        self.border.map_or(0., |(width, _)| width)
+    }
+
+    /// Returns the shape subviews are clipped to in the layer’s own coordinate system (with the
+    /// origin at its top left corner), or None if they aren’t clipped.
+    pub fn clip_shape(&self) -> Option<Shape> {
+        match &self.clip_shape {
+            Some(shape) => Some(shape.clone()),
+            None if self.clip_contents => Some(Shape::RoundedRect {
+                rect: Rect::new((0., 0.).into(), self.bounds.size),
+                corner_radius: self.corner_radius,
+            }),
+            None => None,
+        }
+    }
+}
+
+/// Where a layer border is drawn relative to the edge of the layer bounds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BorderAlignment {
+    /// The border is drawn inside the bounds.
+    #[default]
+    Inside,
+    /// The border is centered on the edge of the bounds.
+    Center,
+    /// The border is drawn outside the bounds, like an outline. Rounded corners are enlarged to
+    /// match.
+    Outside,
+}
+
+impl BorderAlignment {
+    /// Returns how far a border of the given width extends beyond the bounds.
+    pub fn overflow(self, width: f64) -> f64 {
+        match self {
+            BorderAlignment::Inside => 0.,
+            BorderAlignment::Center => width / 2.,
+            BorderAlignment::Outside => width,
+        }
+    }
+}
+
+/// The line style of a layer border.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BorderStyle {
+    #[default]
+    Solid,
+    /// Dashes of a length separated by gaps of another, running clockwise from the top left
+    /// corner.
+    Dashed { dash: f64, gap: f64 },
+}
+
+/// How layer contents are placed in the layer bounds.
+///
+/// The natural size of contents is one pixel of the atlas region per device pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContentsGravity {
+    /// Scaled to fill the bounds.
+    #[default]
+    Resize,
+    /// Scaled to fit inside the bounds while keeping the aspect ratio, and centered.
+    ResizeAspect,
+    /// Scaled to cover the bounds while keeping the aspect ratio, centered, and cropped.
+    ResizeAspectFill,
+    /// Centered at the natural size, and cropped if larger than the bounds.
+    Center,
+    /// Repeated at the natural size, starting at the top left corner.
+    Tile,
+}
+
+impl ContentsGravity {
+    /// Returns where contents of the given natural size are drawn in the bounds (or, if tiled,
+    /// where the first tile is drawn). The result may extend beyond the bounds, but contents are
+    /// never drawn outside them.
+    pub fn contents_rect(self, bounds: Rect, size: Vector2<f64>) -> Rect {
+        let centered = |size: Vector2<f64>| Rect::new(bounds.center() - size / 2., size);
+        let aspect_scale = |fill: bool| {
+            let x = bounds.size.x / size.x;
+            let y = bounds.size.y / size.y;
+            if fill {
+                x.max(y)
+            } else {
+                x.min(y)
+            }
+        };
+        match self {
+            _ if size.x <= 0. || size.y <= 0. => bounds,
+            ContentsGravity::Resize => bounds,
+            ContentsGravity::ResizeAspect => centered(size * aspect_scale(false)),
+            ContentsGravity::ResizeAspectFill => centered(size * aspect_scale(true)),
+            ContentsGravity::Center => centered(size),
+            ContentsGravity::Tile => Rect::new(bounds.origin, size),
+        }
+    }
 }
 
 struct DebugifyOption<'a, T>(&'a Option<T>);
@@ -58,18 +263,24 @@ impl<'a, T> fmt::Debug for DebugifyOption<'a, T> {
 impl<Ctx> fmt::Debug for Layer<Ctx> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Layer")
-            .field("bounds", &self.bounds)
-            .field("background", &self.background)
-            .field("corner_radius", &self.corner_radius)
-            .field("border", &self.border)
-            .field("clip_contents", &self.clip_contents)
-            .field("transform", &self.transform)
-            .field("opacity", &self.opacity)
+            .field("props", &self.props)
             .field("subviews", &self.subviews)
+            .field("transitions", &self.transitions)
             .field("pointer_down_action", &DebugifyOption(&self.pointer_action))
             .field("pointer_hover_action", &DebugifyOption(&self.hover_action))
             .field("key_down_action", &DebugifyOption(&self.key_action))
             .field("scroll_action", &DebugifyOption(&self.scroll_action))
+            .field("tap_action", &DebugifyOption(&self.tap_action))
+            .field("media_action", &DebugifyOption(&self.media_action))
+            .field(
+                "controller_action",
+                &DebugifyOption(&self.controller_action),
+            )
+            .field("file_drop_action", &DebugifyOption(&self.file_drop_action))
+            .field(
+                "pointing_device_action",
+                &DebugifyOption(&self.pointing_device_action),
+            )
             .finish()
     }
 }
@@ -80,33 +291,26 @@ impl<Ctx> Default for Layer<Ctx> {
     fn default() -> Self {
         Layer {
             key: None,
-            bounds: Rect::zero(),
-            background: Color::default(),
-            corner_radius: 0.,
-            border: None,
-            clip_contents: false,
-            transform: Matrix3::identity(),
-            opacity: 1.,
+            props: LayerProps::default(),
             subviews: Vec::new(),
             pointer_action: None,
             hover_action: None,
             key_action: None,
             scroll_action: None,
+            tap_action: None,
+            media_action: None,
+            controller_action: None,
+            file_drop_action: None,
+            pointing_device_action: None,
             layout: Box::new(()),
+            transitions: None,
         }
     }
 }
 
 impl<Ctx: 'static> PartialEq for Layer<Ctx> {
     fn eq(&self, other: &Layer<Ctx>) -> bool {
-        self.bounds == other.bounds
-            && self.background == other.background
-            && self.corner_radius == other.corner_radius
-            && self.border == other.border
-            && self.clip_contents == other.clip_contents
-            && self.transform == other.transform
-            && self.opacity == other.opacity
-            && self.subviews.eq(&other.subviews)
+        self.props == other.props && self.subviews.eq(&other.subviews)
         // TODO: cmp event handlers?
     }
 }
@@ -119,7 +323,33 @@ impl_view! {
     fn native_type(&self) -> Option<NativeType> {
         Some(NativeType::Layer)
     }
+    fn native_view(&self) -> NativeView {
+        let mut props = self.props.clone();
+        props.contents_insets = match props.contents {
+            Some(region) if props.contents_gravity == ContentsGravity::Resize => {
+                props.contents_insets.clamped(region.width, region.height)
+            }
+            _ => CapInsets::default(),
+        };
+        NativeView::Layer(Box::new(props))
+    }
+    fn event_handlers(&self) -> EventHandlers {
+        EventHandlers {
+            pointer: self.pointer_action.clone(),
+            hover: self.hover_action.clone(),
+            key: self.key_action.clone(),
+            scroll: self.scroll_action.clone(),
+            tap: self.tap_action.clone(),
+            media: self.media_action.clone(),
+            controller: self.controller_action.clone(),
+            file_drop: self.file_drop_action.clone(),
+            pointing_device: self.pointing_device_action.clone(),
+        }
+    }
     fn key(&self) -> Option<u64> {
         self.key
     }
+    fn transitions(&self) -> Option<Transitions> {
+        self.transitions.clone()
+    }
 }
@@ -0,0 +1,498 @@
+//! Layout.
+
+use crate::rect::Rect;
+use cgmath::{Vector2, Zero};
+use core::any::Any;
+use core::fmt;
+use std::f64;
+
+/// A layout delegate for a native view.
+pub trait Layout: Any + fmt::Debug + Send + Sync {
+    /// Performs layout.
+    ///
+    /// - `bounds`: the (strongly) suggested bounds from the superview.
+    /// - `context`: the layout context. Used to access subview layout.
+    fn layout(&self, bounds: Rect, mut context: LayoutContext) -> LayoutResult {
+        LayoutResult::new(bounds, context.subviews().map(|_| bounds).collect())
+    }
+}
+
+pub struct LayoutContext<'a> {
+    /// Layout results of all subviews from the previous layout pass, if there was one.
+    subviews: Vec<Option<&'a LayoutResult>>,
+}
+
+impl<'a> LayoutContext<'a> {
+    /// Creates a layout context given the previous layout results of all subviews.
+    pub fn new(subviews: Vec<Option<&'a LayoutResult>>) -> LayoutContext<'a> {
+        LayoutContext { subviews }
+    }
+
+    pub fn subviews(&mut self) -> impl Iterator<Item = SubviewLayout<'a>> + '_ {
+        self.subviews
+            .iter()
+            .map(|result| SubviewLayout { result: *result })
+    }
+}
+
+pub struct SubviewLayout<'a> {
+    result: Option<&'a LayoutResult>,
+}
+
+impl<'a> SubviewLayout<'a> {
+    /// The subview’s minimum size.
+    /// May be zero if it hasn’t been computed yet (e.g. on first render).
+    pub fn min_size(&self) -> Vector2<f64> {
+        self.result
+            .map_or(Vector2::zero(), |result| result.min_size)
+    }
+
+    /// The subview’s maximum size.
+    /// May be infinite if it hasn’t been computed yet (see `min_size`).
+    pub fn max_size(&self) -> Vector2<f64> {
+        self.result
+            .map_or(unbounded_size(), |result| result.max_size)
+    }
+
+    /// The subview’s layout priorities.
+    pub fn priorities(&self) -> LayoutPriorities {
+        self.result
+            .map_or(LayoutPriorities::default(), |result| result.priorities)
+    }
+
+    /// Returns the subview’s size constraints along an axis.
+    pub fn constraint(&self, axis: Axis) -> LengthConstraint {
+        let priorities = self.priorities();
+        LengthConstraint {
+            min: axis.of(self.min_size()),
+            max: axis.of(self.max_size()),
+            hugging: priorities.hugging,
+            compression_resistance: priorities.compression_resistance,
+        }
+    }
+}
+
+pub struct LayoutResult {
+    /// Own view bounds.
+    pub bounds: Rect,
+
+    /// Bounds for all subviews, in order.
+    pub subview_bounds: Vec<Rect>,
+
+    /// Minimum size of this view.
+    pub min_size: Vector2<f64>,
+
+    /// Maximum size of this view. Components may be infinite.
+    pub max_size: Vector2<f64>,
+
+    /// Priorities used by the superview when this view can’t be given a size within its
+    /// constraints.
+    pub priorities: LayoutPriorities,
+
+    /// If true, will consider the layout bounds a pointer tracking rectangle.
+    pub track_pointer: bool,
+
+    /// If true, will clip all pointer tracking rectangles of child views to this view.
+    pub clip_pointer: bool,
+}
+
+impl LayoutResult {
+    /// Creates a new layout result without any constraints.
+    pub fn new(bounds: Rect, subview_bounds: Vec<Rect>) -> LayoutResult {
+        LayoutResult {
+            bounds,
+            subview_bounds,
+            min_size: Vector2::zero(),
+            max_size: unbounded_size(),
+            priorities: LayoutPriorities::default(),
+            track_pointer: false,
+            clip_pointer: false,
+        }
+    }
+}
+
+/// Identity layout.
+///
+/// Use this to use the default layout handler, which copies the bounds given by the superview to
+/// all subviews and itself.
+impl Layout for () {}
+
+/// Lengths below this are considered to be zero when distributing space.
+const EPSILON: f64 = 1e-9;
+
+fn unbounded_size() -> Vector2<f64> {
+    Vector2::new(f64::INFINITY, f64::INFINITY)
+}
+
+/// A layout axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    /// Returns the component of the vector along this axis.
+    pub fn of(self, vector: Vector2<f64>) -> f64 {
+        match self {
+            Axis::Horizontal => vector.x,
+            Axis::Vertical => vector.y,
+        }
+    }
+
+    /// Returns the other axis.
+    pub fn cross(self) -> Axis {
+        match self {
+            Axis::Horizontal => Axis::Vertical,
+            Axis::Vertical => Axis::Horizontal,
+        }
+    }
+
+    /// Creates a vector from a length along this axis and a length along the cross axis.
+    pub fn vector(self, main: f64, cross: f64) -> Vector2<f64> {
+        match self {
+            Axis::Horizontal => Vector2::new(main, cross),
+            Axis::Vertical => Vector2::new(cross, main),
+        }
+    }
+}
+
+/// A layout priority; higher priorities win.
+///
+/// Modeled after Auto Layout priorities, so values range from 0 to 1000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(pub u16);
+
+impl Priority {
+    /// A constraint with this priority will never be broken.
+    pub const REQUIRED: Priority = Priority(1000);
+    pub const HIGH: Priority = Priority(750);
+    pub const DEFAULT: Priority = Priority(500);
+    pub const LOW: Priority = Priority(250);
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::DEFAULT
+    }
+}
+
+/// Layout priorities of a view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LayoutPriorities {
+    /// How strongly the view resists being made larger than its minimum size.
+    ///
+    /// When there’s excess space, views with the lowest hugging priority will grow first.
+    pub hugging: Priority,
+
+    /// How strongly the view resists being made smaller than its minimum size.
+    ///
+    /// When there isn’t enough space, views with the lowest compression resistance will shrink
+    /// first.
+    pub compression_resistance: Priority,
+}
+
+/// Constraints for the length of a view along one axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthConstraint {
+    pub min: f64,
+    pub max: f64,
+    pub hugging: Priority,
+    pub compression_resistance: Priority,
+}
+
+/// Resolves lengths for a list of views that have to share the available length.
+///
+/// All views start out at their minimum length. If there is space left over, views with the lowest
+/// hugging priority are grown first (up to their maximum length), and if there isn’t enough space,
+/// views with the lowest compression resistance are shrunk first (down to zero). Views with equal
+/// priority share the difference equally. Required priorities are never broken, so the resulting
+/// lengths may not add up to `available`.
+pub fn resolve_lengths(available: f64, constraints: &[LengthConstraint]) -> Vec<f64> {
+    let mut lengths: Vec<f64> = constraints.iter().map(|c| c.min.max(0.)).collect();
+    let total: f64 = lengths.iter().sum();
+
+    if total < available {
+        let mut excess = available - total;
+        for priority in sorted_priorities(constraints.iter().map(|c| c.hugging)) {
+            if priority == Priority::REQUIRED || excess <= 0. {
+                break;
+            }
+            let group: Vec<_> = (0..constraints.len())
+                .filter(|i| constraints[*i].hugging == priority)
+                .collect();
+            excess = fill(&mut lengths, &group, excess, |i, len| {
+                (constraints[i].max - len).max(0.)
+            });
+        }
+    } else if total > available {
+        let mut deficit = total - available;
+        for priority in sorted_priorities(constraints.iter().map(|c| c.compression_resistance)) {
+            if priority == Priority::REQUIRED || deficit <= 0. {
+                break;
+            }
+            let group: Vec<_> = (0..constraints.len())
+                .filter(|i| constraints[*i].compression_resistance == priority)
+                .collect();
+            let mut shrink = vec![0.; lengths.len()];
+            deficit = fill(&mut shrink, &group, deficit, |i, amount| {
+                lengths[i] - amount
+            });
+            for &i in &group {
+                lengths[i] -= shrink[i];
+            }
+        }
+    }
+
+    lengths
+}
+
+/// Returns all distinct priorities in ascending order.
+fn sorted_priorities(priorities: impl Iterator<Item = Priority>) -> Vec<Priority> {
+    let mut priorities: Vec<_> = priorities.collect();
+    priorities.sort();
+    priorities.dedup();
+    priorities
+}
+
+/// Distributes `amount` equally among the given indices in `values`, without adding more to any
+/// value than `capacity(index, value)` allows. Returns the amount that could not be distributed.
+fn fill(
+    values: &mut [f64],
+    indices: &[usize],
+    mut amount: f64,
+    capacity: impl Fn(usize, f64) -> f64,
+) -> f64 {
+    let mut open: Vec<usize> = indices
+        .iter()
+        .copied()
+        .filter(|i| capacity(*i, values[*i]) > 0.)
+        .collect();
+
+    while amount > EPSILON && !open.is_empty() {
+        let share = amount / open.len() as f64;
+        for &i in &open {
+            let added = share.min(capacity(i, values[i]));
+            values[i] += added;
+            amount -= added;
+        }
+        open.retain(|i| capacity(*i, values[*i]) > 0.);
+    }
+
+    amount.max(0.)
+}
+
+/// A layout that arranges subviews in a line along an axis.
+///
+/// Subview lengths are resolved using [`resolve_lengths`], so subview min/max sizes and priorities
+/// are respected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stack {
+    /// The axis along which subviews are laid out.
+    pub axis: Axis,
+
+    /// Spacing between subviews.
+    pub spacing: f64,
+}
+
+impl Layout for Stack {
+    fn layout(&self, bounds: Rect, mut context: LayoutContext) -> LayoutResult {
+        let axis = self.axis;
+        let cross_axis = axis.cross();
+        let subviews: Vec<_> = context.subviews().collect();
+
+        let spacing = self.spacing * subviews.len().saturating_sub(1) as f64;
+        let constraints: Vec<_> = subviews.iter().map(|s| s.constraint(axis)).collect();
+        let lengths = resolve_lengths(axis.of(bounds.size) - spacing, &constraints);
+
+        let cross_length = cross_axis.of(bounds.size);
+        let mut cursor = 0.;
+        let mut subview_bounds = Vec::with_capacity(subviews.len());
+        let mut min_main = spacing;
+        let mut min_cross: f64 = 0.;
+        for (subview, length) in subviews.iter().zip(lengths) {
+            let cross = cross_length.min(cross_axis.of(subview.max_size()));
+            subview_bounds.push(Rect::new(
+                bounds.origin + axis.vector(cursor, 0.),
+                axis.vector(length, cross),
+            ));
+            cursor += length + self.spacing;
+            min_main += axis.of(subview.min_size());
+            min_cross = min_cross.max(cross_axis.of(subview.min_size()));
+        }
+
+        let mut result = LayoutResult::new(bounds, subview_bounds);
+        result.min_size = axis.vector(min_main, min_cross);
+        result
+    }
+}
+
+/// How content with a fixed aspect ratio is sized within bounds of a different aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AspectRatioMode {
+    /// The content is made as large as possible while staying inside the bounds.
+    Fit,
+    /// The content is made as small as possible while covering the bounds entirely.
+    Fill,
+}
+
+/// A layout that sizes its subviews to a fixed aspect ratio and centers them within its bounds.
+///
+/// Useful for images, video, and thumbnails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatio {
+    /// The aspect ratio (width divided by height).
+    pub ratio: f64,
+
+    /// How to size subviews.
+    pub mode: AspectRatioMode,
+}
+
+impl AspectRatio {
+    /// Returns the largest (`Fit`) or smallest (`Fill`) rectangle with this aspect ratio, centered
+    /// in the given bounds.
+    pub fn rect_in(&self, bounds: Rect) -> Rect {
+        if self.ratio <= 0. || !self.ratio.is_finite() {
+            return bounds;
+        }
+
+        let bounds_ratio = bounds.size.x / bounds.size.y;
+        let fit_width = match self.mode {
+            AspectRatioMode::Fit => bounds_ratio > self.ratio,
+            AspectRatioMode::Fill => bounds_ratio < self.ratio,
+        };
+        let size = if fit_width {
+            Vector2::new(bounds.size.y * self.ratio, bounds.size.y)
+        } else {
+            Vector2::new(bounds.size.x, bounds.size.x / self.ratio)
+        };
+
+        Rect::new(bounds.center() - size / 2., size)
+    }
+}
+
+impl Layout for AspectRatio {
+    fn layout(&self, bounds: Rect, mut context: LayoutContext) -> LayoutResult {
+        let rect = self.rect_in(bounds);
+        let mut result = LayoutResult::new(bounds, context.subviews().map(|_| rect).collect());
+        result.clip_pointer = self.mode == AspectRatioMode::Fill;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Point2;
+
+    fn constraint(min: f64, max: f64) -> LengthConstraint {
+        LengthConstraint {
+            min,
+            max,
+            hugging: Priority::DEFAULT,
+            compression_resistance: Priority::DEFAULT,
+        }
+    }
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    #[test]
+    fn excess_is_shared_equally() {
+        let constraints = [
+            constraint(30., f64::INFINITY),
+            constraint(0., f64::INFINITY),
+        ];
+        assert_eq!(resolve_lengths(100., &constraints), [65., 35.]);
+    }
+
+    #[test]
+    fn max_lengths_are_respected() {
+        let constraints = [constraint(0., 10.), constraint(0., f64::INFINITY)];
+        assert_eq!(resolve_lengths(100., &constraints), [10., 90.]);
+
+        // views can’t grow past their maximum, even if there’s space left over
+        let constraints = [constraint(0., 10.), constraint(0., 20.)];
+        assert_eq!(resolve_lengths(100., &constraints), [10., 20.]);
+    }
+
+    #[test]
+    fn lowest_hugging_priority_grows_first() {
+        let mut a = constraint(10., f64::INFINITY);
+        a.hugging = Priority::LOW;
+        let mut b = constraint(10., f64::INFINITY);
+        b.hugging = Priority::HIGH;
+        assert_eq!(resolve_lengths(100., &[a, b]), [90., 10.]);
+
+        // once the lower priority views are full, the next priority grows
+        a.max = 50.;
+        assert_eq!(resolve_lengths(100., &[a, b]), [50., 50.]);
+    }
+
+    #[test]
+    fn lowest_compression_resistance_shrinks_first() {
+        let mut a = constraint(30., f64::INFINITY);
+        a.compression_resistance = Priority::LOW;
+        let b = constraint(90., f64::INFINITY);
+        assert_eq!(resolve_lengths(100., &[a, b]), [10., 90.]);
+
+        // views only shrink down to zero before the next priority has to give way
+        assert_eq!(resolve_lengths(50., &[a, b]), [0., 50.]);
+    }
+
+    #[test]
+    fn required_priorities_are_never_broken() {
+        let mut a = constraint(60., 60.);
+        a.hugging = Priority::REQUIRED;
+        a.compression_resistance = Priority::REQUIRED;
+        assert_eq!(resolve_lengths(100., &[a, a]), [60., 60.]);
+        assert_eq!(resolve_lengths(200., &[a, a]), [60., 60.]);
+    }
+
+    #[test]
+    fn stack_layout() {
+        let mut fixed = LayoutResult::new(rect(0., 0., 0., 0.), Vec::new());
+        fixed.min_size = Vector2::new(20., 5.);
+        fixed.max_size = Vector2::new(20., 10.);
+        fixed.priorities.hugging = Priority::REQUIRED;
+        let flexible = LayoutResult::new(rect(0., 0., 0., 0.), Vec::new());
+
+        let stack = Stack {
+            axis: Axis::Horizontal,
+            spacing: 10.,
+        };
+        let context = LayoutContext::new(vec![Some(&fixed), Some(&flexible), None]);
+        let result = stack.layout(rect(5., 5., 120., 30.), context);
+        assert_eq!(
+            result.subview_bounds,
+            [
+                rect(5., 5., 20., 10.),
+                rect(35., 5., 40., 30.),
+                rect(85., 5., 40., 30.),
+            ]
+        );
+        assert_eq!(result.min_size, Vector2::new(40., 5.));
+    }
+
+    #[test]
+    fn aspect_ratio_rect() {
+        let bounds = rect(0., 0., 200., 100.);
+        let fit = AspectRatio {
+            ratio: 1.,
+            mode: AspectRatioMode::Fit,
+        };
+        assert_eq!(fit.rect_in(bounds), rect(50., 0., 100., 100.));
+        let fill = AspectRatio {
+            ratio: 1.,
+            mode: AspectRatioMode::Fill,
+        };
+        assert_eq!(fill.rect_in(bounds), rect(0., -50., 200., 200.));
+
+        let invalid = AspectRatio {
+            ratio: 0.,
+            mode: AspectRatioMode::Fit,
+        };
+        assert_eq!(invalid.rect_in(bounds), bounds);
+    }
+}
@@ -12,9 +12,9 @@
 //! view but a virtual representation of a view, and should hence be very cheap to create, starting
 //! with constructors: views should not do anything on their own when created other than storing
 //! their properties. When the view is realized, it will be asked to create a state object which
-//! will persist over the lifetime of the view—side effects and other things should be taken care
-//! of here. Finally, the view body is derived from its properties and its state and declares the
-//! view’s subviews.
+//! will persist over the lifetime of the view. Side effects such as subscriptions are declared
+//! with [`Context::effect`], which also takes care of cleaning them up. Finally, the view body is
+//! derived from its properties and its state and declares the view’s subviews.
 //!
 //! ## Events
 //! When events arrive at a window, they will first target a specific view and then bubble up.
@@ -48,6 +48,10 @@
 //! request a layout frame, so that in the next frame, layout is performed again; this time with the
 //! superview aware of its minimum size.
 //!
+//! Views may also report a maximum size and layout priorities. When a superview can’t satisfy the
+//! constraints of all of its subviews, it should use these to decide deterministically which
+//! subviews grow or shrink first (see [`layout::resolve_lengths`]).
+//!
 //! ## Contexts
 //! Contexts are used to propagate lateral parameters (e.g. a UI theme) down the view tree without
 //! having to copy it into the view props every single time. They should be cheap to create and
@@ -75,18 +79,60 @@
 //! - Surfaces
 //! - at least one type of pointer events
 
+pub mod accessibility;
+pub mod animation;
+pub mod async_image;
+pub mod atlas;
 pub mod backend;
+pub mod canvas;
 pub mod color;
+pub mod custom_view;
+pub mod damage;
+#[cfg(feature = "declarative")]
+pub mod declarative;
+pub mod devtools;
+pub mod dispatch;
+pub mod display_list;
+mod effects;
+pub mod embed;
 pub mod events;
+#[cfg(feature = "file-watcher")]
+pub mod file_watcher;
+pub mod focus;
+pub mod format;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod hot_reload;
+pub mod image;
+pub mod ipc;
 mod layer;
+pub mod layout;
+pub mod locale;
+pub mod navigation;
+pub mod notifications;
 mod nv_tree;
+mod pdf;
+pub mod print;
+pub mod progress;
+pub mod raster;
 pub mod raw_events;
 mod rect;
+pub mod remote;
+pub mod scheduler;
+pub mod session;
+pub mod settings;
+mod shape;
+pub mod snapshot;
+pub mod spatial;
+pub mod table;
+pub mod tabs;
 #[macro_use]
 mod view;
 mod view_tree;
 
-pub use nv_tree::{NVTree, NativeView, Patch};
-pub use rect::Rect;
-pub use view::{State, View};
-pub use view_tree::{Context, ViewTree};
+pub use layer::{BorderAlignment, BorderStyle, ContentsGravity, Layer, LayerProps};
+pub use nv_tree::{NVTree, NVTreeStats, NativeView, Patch};
+pub use rect::{CornerRadius, CornerStyle, Quad, Rect};
+pub use shape::Shape;
+pub use view::{AnyState, AnyView, Fragment, ParseViewIdError, State, View, ViewId};
+pub use view_tree::{Context, Observers, RenderRequester, ViewTree, ViewTreeStats};
@@ -0,0 +1,427 @@
+//! Localization.
+
+use crate::format::{self, Date};
+use crate::view::View;
+use crate::view_tree::{Context, Observers};
+use crate::{AnyState, State};
+use core::any::Any;
+use core::fmt;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A locale, identified by a BCP 47 language tag such as `en-US`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Locale {
+    tag: String,
+}
+
+impl Locale {
+    /// Creates a locale from a language tag. Underscores are accepted as separators as well.
+    pub fn new(tag: &str) -> Locale {
+        Locale {
+            tag: tag.replace('_', "-"),
+        }
+    }
+
+    /// The full language tag.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// The language subtag (e.g. `en`).
+    pub fn language(&self) -> &str {
+        self.tag.split('-').next().unwrap_or("")
+    }
+
+    /// The region subtag (e.g. `US`), if there is one.
+    pub fn region(&self) -> Option<&str> {
+        self.tag.split('-').skip(1).find(|part| {
+            part.len() == 2 || (part.len() == 3 && part.chars().all(|c| c.is_ascii_digit()))
+        })
+    }
+
+    /// Returns this locale and all of its less specific fallbacks, most specific first.
+    ///
+    /// e.g. `zh-Hant-TW`, `zh-Hant`, `zh`
+    pub fn fallbacks(&self) -> Vec<Locale> {
+        let parts: Vec<_> = self.tag.split('-').collect();
+        (1..=parts.len())
+            .rev()
+            .map(|len| Locale {
+                tag: parts[..len].join("-"),
+            })
+            .collect()
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::new("en")
+    }
+}
+
+/// A source of localized strings, such as a Fluent bundle or a gettext catalog.
+pub trait StringCatalog: Send + Sync {
+    /// Looks up the string for a key in a specific locale (without fallbacks), substituting
+    /// arguments.
+    ///
+    /// Returns None if there is no such string.
+    fn lookup(&self, locale: &Locale, key: &str, args: &[(&str, &str)]) -> Option<String>;
+}
+
+/// A simple in-memory string catalog.
+///
+/// Strings may contain `{name}` placeholders, which will be replaced with arguments.
+#[derive(Debug, Clone, Default)]
+pub struct MapCatalog {
+    strings: HashMap<String, HashMap<String, String>>,
+}
+
+impl MapCatalog {
+    pub fn new() -> MapCatalog {
+        MapCatalog::default()
+    }
+
+    /// Adds a string.
+    pub fn insert(&mut self, locale: &Locale, key: &str, value: &str) {
+        self.strings
+            .entry(locale.tag().to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+}
+
+impl StringCatalog for MapCatalog {
+    fn lookup(&self, locale: &Locale, key: &str, args: &[(&str, &str)]) -> Option<String> {
+        let mut string = self.strings.get(locale.tag())?.get(key)?.clone();
+        for (name, value) in args {
+            string = string.replace(&format!("{{{}}}", name), value);
+        }
+        Some(string)
+    }
+}
+
+struct LocalizationInner {
+    locale: Locale,
+    catalog: Arc<dyn StringCatalog>,
+}
+
+/// Shared localization state, meant to be passed down the view tree in the context.
+///
+/// Clones share the same state. Views that display localized strings should
+/// [`observe`](Self::observe) it, so they are rendered again when the locale or the catalog
+/// changes; [`LocalizedText`] does this already.
+#[derive(Clone)]
+pub struct Localization {
+    inner: Arc<RwLock<LocalizationInner>>,
+    observers: Observers,
+}
+
+impl Localization {
+    /// Creates a new localization state.
+    pub fn new(locale: Locale, catalog: Arc<dyn StringCatalog>) -> Localization {
+        Localization {
+            inner: Arc::new(RwLock::new(LocalizationInner { locale, catalog })),
+            observers: Observers::new(),
+        }
+    }
+
+    /// The current locale.
+    pub fn locale(&self) -> Locale {
+        self.inner.read().locale.clone()
+    }
+
+    /// Changes the locale at runtime.
+    pub fn set_locale(&self, locale: Locale) {
+        self.inner.write().locale = locale;
+        self.observers.notify();
+    }
+
+    /// Replaces the string catalog at runtime.
+    pub fn set_catalog(&self, catalog: Arc<dyn StringCatalog>) {
+        self.inner.write().catalog = catalog;
+        self.observers.notify();
+    }
+
+    /// Renders the view of a context again whenever the locale or the catalog changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// Returns the localized string for a key.
+    ///
+    /// Falls back to less specific locales, and then to the key itself.
+    pub fn get(&self, key: &str) -> String {
+        self.format(key, &[])
+    }
+
+    /// Returns the localized string for a key, substituting arguments.
+    ///
+    /// Falls back to less specific locales, and then to the key itself.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        self.try_format(key, args)
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// Returns the localized string for a key, substituting arguments.
+    ///
+    /// Falls back to less specific locales, and returns None if the key is not in the catalog.
+    pub fn try_format(&self, key: &str, args: &[(&str, &str)]) -> Option<String> {
+        let inner = self.inner.read();
+        inner
+            .locale
+            .fallbacks()
+            .iter()
+            .find_map(|locale| inner.catalog.lookup(locale, key, args))
+    }
+
+    /// Formats a number for the current locale; see [`format::format_number`].
+    pub fn format_number(&self, value: f64, fraction_digits: usize) -> String {
+        format::format_number(&self.locale(), value, fraction_digits)
+    }
+
+    /// Formats a date for the current locale; see [`format::format_date`].
+    pub fn format_date(&self, date: Date) -> String {
+        format::format_date(&self.locale(), date)
+    }
+
+    /// Formats a relative time for the current locale; see [`format::format_relative_time`].
+    pub fn format_relative_time(&self, seconds: i64) -> String {
+        format::format_relative_time(self, seconds)
+    }
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Localization::new(Locale::default(), Arc::new(MapCatalog::new()))
+    }
+}
+
+impl fmt::Debug for Localization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Localization")
+            .field("locale", &self.locale())
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+/// Context types that provide localization.
+pub trait LocalizationContext {
+    fn localization(&self) -> &Localization;
+}
+
+impl LocalizationContext for Localization {
+    fn localization(&self) -> &Localization {
+        self
+    }
+}
+
+/// Renders a view for a localized string.
+pub type LocalizedRenderFn<Ctx> = dyn Fn(String) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// A view that resolves a localized string from the context and renders it using `render`.
+///
+/// The view observes the localization, so the string is resolved again when the locale changes.
+pub struct LocalizedText<Ctx> {
+    pub key: Option<u64>,
+
+    /// The string key in the catalog.
+    pub string_key: String,
+
+    /// Arguments for placeholders in the string.
+    pub args: Vec<(String, String)>,
+
+    /// Renders the resolved string (e.g. as a text view).
+    pub render: Arc<LocalizedRenderFn<Ctx>>,
+}
+
+impl<Ctx> fmt::Debug for LocalizedText<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LocalizedText")
+            .field("string_key", &self.string_key)
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+impl<Ctx> PartialEq for LocalizedText<Ctx> {
+    fn eq(&self, other: &LocalizedText<Ctx>) -> bool {
+        self.key == other.key
+            && self.string_key == other.string_key
+            && self.args == other.args
+            && Arc::ptr_eq(&self.render, &other.render)
+    }
+}
+
+#[derive(Debug)]
+struct LocalizedTextState(Localization);
+
+impl<Ctx> State<Ctx> for LocalizedTextState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl<Ctx: LocalizationContext + 'static> View<Ctx> for LocalizedText<Ctx> {
+    fn new_state(&self, context: Context<Ctx>) -> AnyState<Ctx> {
+        Arc::new(LocalizedTextState(context.ctx().localization().clone()))
+    }
+    fn body(&self, state: &AnyState<Ctx>) -> Arc<dyn View<Ctx>> {
+        let state = state
+            .as_any()
+            .downcast_ref::<LocalizedTextState>()
+            .expect("View::body: invalid state for LocalizedText");
+        let args: Vec<_> = self
+            .args
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        (self.render)(state.0.format(&self.string_key, &args))
+    }
+    fn effects(&self, state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        let state = state
+            .as_any()
+            .downcast_ref::<LocalizedTextState>()
+            .expect("View::effects: invalid state for LocalizedText");
+        state.0.observe(context);
+    }
+    fn eq(&self, other: &dyn View<Ctx>) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{Layer, LayerProps};
+    use crate::nv_tree::{NativeView, Patch};
+    use crate::rect::Rect;
+    use crate::view_tree::ViewTree;
+    use cgmath::{Point2, Vector2};
+    use parking_lot::Mutex;
+    use std::time::Instant;
+
+    fn catalog() -> MapCatalog {
+        let mut catalog = MapCatalog::new();
+        let (en, de, de_at) = (Locale::new("en"), Locale::new("de"), Locale::new("de-AT"));
+        catalog.insert(&en, "greeting", "Hello, {name}!");
+        catalog.insert(&en, "month", "January");
+        catalog.insert(&de, "greeting", "Hallo, {name}!");
+        catalog.insert(&de, "month", "Januar");
+        catalog.insert(&de_at, "month", "Jänner");
+        catalog
+    }
+
+    #[test]
+    fn locales_have_fallbacks() {
+        let locale = Locale::new("zh_Hant_TW");
+        assert_eq!(locale.tag(), "zh-Hant-TW");
+        assert_eq!(locale.language(), "zh");
+        assert_eq!(locale.region(), Some("TW"));
+        assert_eq!(
+            locale.fallbacks(),
+            [
+                Locale::new("zh-Hant-TW"),
+                Locale::new("zh-Hant"),
+                Locale::new("zh")
+            ]
+        );
+        assert_eq!(Locale::new("es-419").region(), Some("419"));
+        assert_eq!(Locale::new("sr-Latn").region(), None);
+    }
+
+    #[test]
+    fn strings_fall_back_to_less_specific_locales() {
+        let localization = Localization::new(Locale::new("de-AT"), Arc::new(catalog()));
+        assert_eq!(localization.get("month"), "Jänner");
+        assert_eq!(
+            localization.format("greeting", &[("name", "Welt")]),
+            "Hallo, Welt!"
+        );
+        assert_eq!(localization.try_format("missing", &[]), None);
+        assert_eq!(localization.get("missing"), "missing");
+
+        localization.set_locale(Locale::new("fr"));
+        assert_eq!(localization.get("month"), "month");
+    }
+
+    /// Renders localized text as a layer and logs the resolved strings.
+    fn text(log: &Arc<Mutex<Vec<String>>>) -> Arc<dyn View<Localization>> {
+        let log = Arc::clone(log);
+        Arc::new(LocalizedText {
+            key: None,
+            string_key: "greeting".into(),
+            args: vec![("name".into(), "Birb".into())],
+            render: Arc::new(move |string| {
+                let width = string.chars().count() as f64;
+                log.lock().push(string);
+                Arc::new(Layer {
+                    props: LayerProps {
+                        bounds: Rect::new(Point2::new(0., 0.), Vector2::new(width, 1.)),
+                        ..LayerProps::default()
+                    },
+                    ..Layer::default()
+                })
+            }),
+        })
+    }
+
+    /// Returns the widths of updated layers.
+    fn widths(tree: &mut ViewTree<Localization>) -> Vec<f64> {
+        tree.patches()
+            .filter_map(|patch| match patch {
+                Patch::Update(_, NativeView::Layer(props)) => Some(props.bounds.size.x),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changing_the_locale_renders_views_again() {
+        let localization = Localization::new(Locale::new("en"), Arc::new(catalog()));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut tree = ViewTree::new();
+        tree.render_root(text(&log), localization.clone());
+        assert_eq!(widths(&mut tree), [12.]);
+        assert_eq!(*log.lock(), ["Hello, Birb!"]);
+
+        assert!(!tree.has_render_requests());
+        localization.set_locale(Locale::new("de-CH"));
+        assert!(tree.has_render_requests());
+        tree.tick(Instant::now());
+        assert_eq!(*log.lock(), ["Hello, Birb!", "Hallo, Birb!"]);
+        // same length, so the layer is unchanged
+        assert!(widths(&mut tree).is_empty());
+
+        let mut catalog = catalog();
+        catalog.insert(&Locale::new("de-CH"), "greeting", "Grüezi, {name}!");
+        localization.set_catalog(Arc::new(catalog));
+        tree.tick(Instant::now());
+        assert_eq!(log.lock().last().unwrap(), "Grüezi, Birb!");
+        assert_eq!(widths(&mut tree), [13.]);
+        assert!(!tree.has_render_requests());
+
+        // removed views stop observing
+        tree.render_root(Arc::new(Layer::default()), localization.clone());
+        tree.tick(Instant::now());
+        localization.set_locale(Locale::new("en"));
+        assert!(!tree.has_render_requests());
+        assert_eq!(log.lock().len(), 3);
+    }
+}
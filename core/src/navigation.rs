@@ -0,0 +1,443 @@
+//! Navigation stacks.
+//!
+//! Pages are keyed by values in a [`NavigationPath`], which a [`PageFn`] turns into the page
+//! contents and title. Titles are returned along with the contents rather than set from within a
+//! page, since views can only pass values down the tree.
+
+use crate::animation::{Easing, Transition, Transitions};
+use crate::dispatch::EventContext;
+use crate::events::{Event, EventHandler, Key, KeyCode, Scroll};
+use crate::impl_view;
+use crate::layer::Layer;
+use crate::raw_events::KeyEventPhase;
+use crate::view::{AnyState, Fragment, State, View};
+use crate::view_tree::{Context, Observers};
+use cgmath::Vector2;
+use core::any::Any;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::sync::Arc;
+use std::time::Duration;
+
+struct PathInner<K> {
+    pages: Vec<K>,
+    /// The depth before the last change, for finding out whether a page was pushed or popped.
+    prev_depth: usize,
+}
+
+/// The pages in a [`NavigationStack`].
+///
+/// Clones share the same pages, so the path can be changed from anywhere (e.g. an event handler
+/// in a page); the stack is rendered again when it changes. The first page is the root page, which
+/// can’t be popped.
+pub struct NavigationPath<K> {
+    inner: Arc<Mutex<PathInner<K>>>,
+    observers: Observers,
+}
+
+impl<K> Clone for NavigationPath<K> {
+    fn clone(&self) -> Self {
+        NavigationPath {
+            inner: Arc::clone(&self.inner),
+            observers: self.observers.clone(),
+        }
+    }
+}
+
+impl<K: Clone> NavigationPath<K> {
+    /// Creates a path with a root page.
+    pub fn new(root: K) -> NavigationPath<K> {
+        NavigationPath {
+            inner: Arc::new(Mutex::new(PathInner {
+                pages: vec![root],
+                prev_depth: 1,
+            })),
+            observers: Observers::new(),
+        }
+    }
+
+    /// Pushes a page onto the stack.
+    pub fn push(&self, page: K) {
+        {
+            let mut inner = self.inner.lock();
+            inner.prev_depth = inner.pages.len();
+            inner.pages.push(page);
+        }
+        self.observers.notify();
+    }
+
+    /// Pops the topmost page off the stack, unless it’s the root page.
+    pub fn pop(&self) -> Option<K> {
+        let page = {
+            let mut inner = self.inner.lock();
+            if inner.pages.len() <= 1 {
+                return None;
+            }
+            inner.prev_depth = inner.pages.len();
+            inner.pages.pop()
+        };
+        self.observers.notify();
+        page
+    }
+
+    /// Pops all pages except the root page.
+    pub fn pop_to_root(&self) {
+        {
+            let mut inner = self.inner.lock();
+            if inner.pages.len() <= 1 {
+                return;
+            }
+            inner.prev_depth = inner.pages.len();
+            inner.pages.truncate(1);
+        }
+        self.observers.notify();
+    }
+
+    /// Returns all pages, starting with the root page.
+    pub fn pages(&self) -> Vec<K> {
+        self.inner.lock().pages.clone()
+    }
+
+    /// Returns the topmost page.
+    pub fn top(&self) -> K {
+        self.inner.lock().pages.last().unwrap().clone()
+    }
+
+    /// Returns the number of pages, including the root page.
+    pub fn depth(&self) -> usize {
+        self.inner.lock().pages.len()
+    }
+
+    /// Returns all pages, and whether the last change popped pages.
+    fn pages_and_is_pop(&self) -> (Vec<K>, bool) {
+        let inner = self.inner.lock();
+        (inner.pages.clone(), inner.pages.len() < inner.prev_depth)
+    }
+
+    /// Returns true if there is a page to go back to.
+    pub fn can_go_back(&self) -> bool {
+        self.depth() > 1
+    }
+
+    /// Renders the view of a context again whenever the path changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// Handles a key press by popping a page on Escape.
+    ///
+    /// Returns true if the key was handled.
+    pub fn handle_key(&self, code: KeyCode) -> bool {
+        code == KeyCode::Escape && self.pop().is_some()
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for NavigationPath<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("NavigationPath")
+            .field(&self.inner.lock().pages)
+            .finish()
+    }
+}
+
+/// A rendered navigation page.
+pub struct NavigationPage<Ctx> {
+    /// The page title, shown in the header.
+    pub title: String,
+
+    /// The page contents.
+    pub content: Arc<dyn View<Ctx>>,
+}
+
+/// Renders the page for a key in a [`NavigationPath`].
+pub type PageFn<K, Ctx> = dyn Fn(&K) -> NavigationPage<Ctx> + Send + Sync;
+
+/// Renders a navigation header, given the title of the topmost page and whether there is a page
+/// to go back to.
+pub type HeaderFn<Ctx> = dyn Fn(&str, bool) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// A stack of pages, of which only the topmost one is visible.
+///
+/// Pushed pages slide in from the right, and popped pages fade out while the page below slides
+/// in from the left. Pressing Escape or swiping right with two fingers on a trackpad inside a page
+/// goes back, unless a view in the page handles the event first.
+pub struct NavigationStack<K, Ctx> {
+    pub key: Option<u64>,
+
+    /// The pages.
+    pub path: NavigationPath<K>,
+
+    /// Renders pages.
+    pub page: Arc<PageFn<K, Ctx>>,
+
+    /// Renders the header, if there should be one. The header will be placed before the page.
+    pub header: Option<Arc<HeaderFn<Ctx>>>,
+
+    /// Duration of page transitions.
+    pub transition_duration: Duration,
+}
+
+impl<K: fmt::Debug, Ctx> fmt::Debug for NavigationStack<K, Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NavigationStack")
+            .field("path", &self.path)
+            .field("transition_duration", &self.transition_duration)
+            .finish()
+    }
+}
+
+impl<K, Ctx> PartialEq for NavigationStack<K, Ctx> {
+    fn eq(&self, other: &NavigationStack<K, Ctx>) -> bool {
+        self.key == other.key
+            && Arc::ptr_eq(&self.path.inner, &other.path.inner)
+            && Arc::ptr_eq(&self.page, &other.page)
+            && match (&self.header, &other.header) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+            && self.transition_duration == other.transition_duration
+    }
+}
+
+/// Distance that pages slide in page transitions.
+const SLIDE_DISTANCE: f64 = 80.;
+
+/// Distance that has to be swiped horizontally to go back.
+const BACK_SWIPE_DISTANCE: f64 = 120.;
+
+/// Tracks horizontal scrolling towards the previous page.
+///
+/// Scroll events don’t say when a gesture ends, so after going back, the swipe only counts again
+/// once the user scrolls in another direction; otherwise, momentum would pop several pages.
+#[derive(Debug)]
+struct BackSwipe {
+    /// The distance swiped so far, or None after going back.
+    distance: Option<f64>,
+}
+
+impl BackSwipe {
+    /// Adds a scroll event, given as the distance the fingers moved. Returns true if the swipe is
+    /// far enough to go back.
+    fn update(&mut self, delta: Vector2<f64>) -> bool {
+        if delta.x.abs() <= delta.y.abs() || delta.x < 0. {
+            self.distance = Some(0.);
+            return false;
+        }
+        match &mut self.distance {
+            Some(distance) if *distance + delta.x >= BACK_SWIPE_DISTANCE => {
+                self.distance = None;
+                true
+            }
+            Some(distance) => {
+                *distance += delta.x;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NavigationStackState {
+    back_swipe: Arc<Mutex<BackSwipe>>,
+}
+
+impl<Ctx> State<Ctx> for NavigationStackState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Slides a view while fading it.
+fn slide_fade(offset: Vector2<f64>) -> Transition {
+    Transition::Custom(Arc::new(move |view, visibility| {
+        Transition::Fade.apply(view, visibility);
+        Transition::Slide(offset).apply(view, visibility);
+    }))
+}
+
+impl<K, Ctx> View<Ctx> for NavigationStack<K, Ctx>
+where
+    K: 'static + Clone + Hash + fmt::Debug + Send + Sync,
+    Ctx: 'static,
+{
+    fn new_state(&self, _context: Context<Ctx>) -> AnyState<Ctx> {
+        Arc::new(NavigationStackState {
+            back_swipe: Arc::new(Mutex::new(BackSwipe { distance: Some(0.) })),
+        })
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.path.observe(context);
+    }
+    fn body(&self, state: &AnyState<Ctx>) -> Arc<dyn View<Ctx>> {
+        let state = state
+            .as_any()
+            .downcast_ref::<NavigationStackState>()
+            .expect("View::body: invalid state for NavigationStack");
+        let (pages, is_pop) = self.path.pages_and_is_pop();
+        let depth = pages.len();
+
+        let top = pages.last().unwrap();
+        let page = (self.page)(top);
+
+        let enter_offset = if is_pop {
+            Vector2::new(-SLIDE_DISTANCE, 0.)
+        } else {
+            Vector2::new(SLIDE_DISTANCE, 0.)
+        };
+
+        // key pages by their position and identity so that changing pages transitions
+        let mut hasher = DefaultHasher::new();
+        depth.hash(&mut hasher);
+        top.hash(&mut hasher);
+
+        let mut body: Fragment<Ctx> = Vec::new();
+        if let Some(header) = &self.header {
+            body.push(header(&page.title, depth > 1));
+        }
+
+        let path = self.path.clone();
+        let key_action = EventHandler::new(move |event: Event<Key>, context: &mut EventContext| {
+            if event.phase() == KeyEventPhase::Pressed && path.handle_key(event.code()) {
+                context.set_handled();
+            }
+        });
+        let path = self.path.clone();
+        let back_swipe = Arc::clone(&state.back_swipe);
+        let scroll_action =
+            EventHandler::new(move |event: Event<Scroll>, context: &mut EventContext| {
+                if event.is_discrete() || !path.can_go_back() {
+                    return;
+                }
+                let delta = if event.is_direction_inverted() {
+                    event.delta()
+                } else {
+                    -event.delta()
+                };
+                if back_swipe.lock().update(delta) {
+                    path.pop();
+                    context.set_handled();
+                }
+            });
+
+        body.push(Arc::new(NavigationPageView {
+            key: Some(hasher.finish()),
+            content: Arc::new(Layer {
+                subviews: vec![page.content],
+                key_action: Some(key_action),
+                scroll_action: Some(scroll_action),
+                ..Layer::default()
+            }),
+            enter_offset,
+            transition_duration: self.transition_duration,
+        }));
+        Arc::new(body)
+    }
+    fn eq(&self, other: &dyn View<Ctx>) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+/// A page in a navigation stack, with page transitions.
+struct NavigationPageView<Ctx> {
+    key: Option<u64>,
+    content: Arc<dyn View<Ctx>>,
+    /// Where the page slides in from.
+    enter_offset: Vector2<f64>,
+    transition_duration: Duration,
+}
+
+impl<Ctx> fmt::Debug for NavigationPageView<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NavigationPageView")
+            .field("content", &self.content)
+            .field("enter_offset", &self.enter_offset)
+            .field("transition_duration", &self.transition_duration)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for NavigationPageView<Ctx> {
+    fn eq(&self, other: &NavigationPageView<Ctx>) -> bool {
+        self.key == other.key
+            && self.content.eq(&*other.content)
+            && self.enter_offset == other.enter_offset
+            && self.transition_duration == other.transition_duration
+    }
+}
+
+impl_view! {
+    NavigationPageView<Ctx>;
+    fn body(&self, _state: &()) {
+        Arc::clone(&self.content)
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn transitions(&self) -> Option<Transitions> {
+        Some(Transitions {
+            enter: Some(slide_fade(self.enter_offset)),
+            exit: Some(Transition::Fade),
+            duration: self.transition_duration,
+            easing: Easing::EaseOut,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_remembers_pops() {
+        let path = NavigationPath::new(0);
+        assert_eq!(path.pages_and_is_pop(), (vec![0], false));
+        path.push(1);
+        path.push(2);
+        assert_eq!(path.pages_and_is_pop(), (vec![0, 1, 2], false));
+        assert_eq!(path.pop(), Some(2));
+        // rendering again doesn’t change the direction
+        assert_eq!(path.pages_and_is_pop(), (vec![0, 1], true));
+        assert_eq!(path.pages_and_is_pop(), (vec![0, 1], true));
+        path.pop_to_root();
+        assert_eq!(path.pop(), None);
+        assert_eq!(path.pages_and_is_pop(), (vec![0], true));
+        assert!(!path.handle_key(KeyCode::Escape));
+        path.push(3);
+        assert!(path.handle_key(KeyCode::Escape));
+        assert_eq!(path.depth(), 1);
+    }
+
+    #[test]
+    fn back_swipe() {
+        let mut swipe = BackSwipe { distance: Some(0.) };
+        assert!(!swipe.update(Vector2::new(100., 10.)));
+        // vertical and leftward scrolling starts over
+        assert!(!swipe.update(Vector2::new(1., 5.)));
+        assert!(!swipe.update(Vector2::new(100., 0.)));
+        assert!(!swipe.update(Vector2::new(-10., 0.)));
+        assert!(!swipe.update(Vector2::new(100., 0.)));
+        assert!(swipe.update(Vector2::new(20., 0.)));
+        // momentum doesn’t go back again
+        assert!(!swipe.update(Vector2::new(200., 0.)));
+        assert!(!swipe.update(Vector2::new(0., 1.)));
+        assert!(swipe.update(Vector2::new(BACK_SWIPE_DISTANCE, 0.)));
+    }
+}
@@ -0,0 +1,483 @@
+//! Transient in-app notifications (toasts).
+
+use crate::animation::{Easing, Transition, Transitions};
+use crate::impl_view;
+use crate::layer::{Layer, LayerProps};
+use crate::rect::Rect;
+use crate::view::{AnyState, Fragment, View};
+use crate::view_tree::{Context, Observers};
+use cgmath::{Point2, Vector2};
+use core::fmt;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Identifies a toast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ToastId(u64);
+
+/// An action function; see [`ToastAction`].
+pub type ActionFn = dyn Fn() + Send + Sync;
+
+/// A button on a toast.
+#[derive(Clone)]
+pub struct ToastAction {
+    pub title: String,
+    pub action: Arc<ActionFn>,
+}
+
+impl fmt::Debug for ToastAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ToastAction")
+            .field("title", &self.title)
+            .finish()
+    }
+}
+
+impl PartialEq for ToastAction {
+    fn eq(&self, other: &ToastAction) -> bool {
+        self.title == other.title && Arc::ptr_eq(&self.action, &other.action)
+    }
+}
+
+/// A transient notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub message: String,
+
+    /// Actions the user can take.
+    pub actions: Vec<ToastAction>,
+
+    /// Time after which the toast will be dismissed automatically. If None, the toast stays
+    /// until it is dismissed.
+    pub duration: Option<Duration>,
+}
+
+impl Toast {
+    /// Creates a toast that will be dismissed after four seconds.
+    pub fn new(message: impl Into<String>) -> Toast {
+        Toast {
+            message: message.into(),
+            actions: Vec::new(),
+            duration: Some(Duration::from_secs(4)),
+        }
+    }
+}
+
+/// A toast that is currently being shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveToast {
+    pub id: ToastId,
+    pub toast: Toast,
+    pub posted: Instant,
+}
+
+#[derive(Debug)]
+struct NotificationsInner {
+    toasts: Vec<ActiveToast>,
+    next_id: u64,
+}
+
+/// A toast queue, meant to be passed down the view tree in the context and rendered using a
+/// [`ToastOverlay`].
+///
+/// Clones share the same queue, so toasts can be posted from anywhere; the overlay is rendered
+/// again when toasts are posted or dismissed. Call [`tick`](Self::tick) regularly to dismiss
+/// expired toasts.
+#[derive(Debug, Clone)]
+pub struct Notifications {
+    inner: Arc<Mutex<NotificationsInner>>,
+    observers: Observers,
+}
+
+impl Notifications {
+    pub fn new() -> Notifications {
+        Notifications {
+            inner: Arc::new(Mutex::new(NotificationsInner {
+                toasts: Vec::new(),
+                next_id: 0,
+            })),
+            observers: Observers::new(),
+        }
+    }
+
+    /// Posts a toast.
+    pub fn post(&self, toast: Toast) -> ToastId {
+        let id = {
+            let mut inner = self.inner.lock();
+            let id = ToastId(inner.next_id);
+            inner.next_id += 1;
+            inner.toasts.push(ActiveToast {
+                id,
+                toast,
+                posted: Instant::now(),
+            });
+            id
+        };
+        self.observers.notify();
+        id
+    }
+
+    /// Dismisses a toast.
+    pub fn dismiss(&self, id: ToastId) {
+        let changed = {
+            let mut inner = self.inner.lock();
+            let len = inner.toasts.len();
+            inner.toasts.retain(|toast| toast.id != id);
+            inner.toasts.len() != len
+        };
+        if changed {
+            self.observers.notify();
+        }
+    }
+
+    /// Runs a toast action and dismisses the toast.
+    pub fn perform_action(&self, id: ToastId, action: usize) {
+        let action = self
+            .inner
+            .lock()
+            .toasts
+            .iter()
+            .find(|toast| toast.id == id)
+            .and_then(|toast| toast.toast.actions.get(action))
+            .map(|action| Arc::clone(&action.action));
+        if let Some(action) = action {
+            self.dismiss(id);
+            action();
+        }
+    }
+
+    /// Returns all toasts that are being shown, oldest first.
+    pub fn toasts(&self) -> Vec<ActiveToast> {
+        self.inner.lock().toasts.clone()
+    }
+
+    /// Renders the view of a context again whenever toasts are posted or dismissed; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// Dismisses toasts whose time is up.
+    ///
+    /// Returns the time at which the next toast will expire, if any.
+    pub fn tick(&self, now: Instant) -> Option<Instant> {
+        let (changed, next) = {
+            let mut inner = self.inner.lock();
+            let len = inner.toasts.len();
+            inner.toasts.retain(|toast| match toast.toast.duration {
+                Some(duration) => now.saturating_duration_since(toast.posted) < duration,
+                None => true,
+            });
+            let next = inner
+                .toasts
+                .iter()
+                .filter_map(|toast| toast.toast.duration.map(|duration| toast.posted + duration))
+                .min();
+            (inner.toasts.len() != len, next)
+        };
+        if changed {
+            self.observers.notify();
+        }
+        next
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Notifications::new()
+    }
+}
+
+/// Context types that provide notifications.
+pub trait NotificationsContext {
+    fn notifications(&self) -> &Notifications;
+}
+
+impl NotificationsContext for Notifications {
+    fn notifications(&self) -> &Notifications {
+        self
+    }
+}
+
+/// Renders a toast.
+pub type ToastFn<Ctx> = dyn Fn(&ActiveToast, &Notifications) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// Shows toasts stacked from the bottom of its bounds, newest at the bottom. Should be placed
+/// above all other content.
+pub struct ToastOverlay<Ctx> {
+    pub key: Option<u64>,
+
+    /// The toasts to show.
+    pub notifications: Notifications,
+
+    /// Renders toasts.
+    pub render: Arc<ToastFn<Ctx>>,
+
+    /// The area in which toasts are shown.
+    pub bounds: Rect,
+
+    /// The size of a single toast.
+    pub toast_size: Vector2<f64>,
+
+    /// Spacing between toasts.
+    pub spacing: f64,
+
+    /// The maximum number of toasts shown at once; older ones will wait until there is room.
+    pub max_visible: usize,
+}
+
+impl<Ctx> fmt::Debug for ToastOverlay<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ToastOverlay")
+            .field("notifications", &self.notifications)
+            .field("bounds", &self.bounds)
+            .field("toast_size", &self.toast_size)
+            .field("spacing", &self.spacing)
+            .field("max_visible", &self.max_visible)
+            .finish()
+    }
+}
+
+impl<Ctx> PartialEq for ToastOverlay<Ctx> {
+    fn eq(&self, other: &ToastOverlay<Ctx>) -> bool {
+        self.key == other.key
+            && Arc::ptr_eq(&self.notifications.inner, &other.notifications.inner)
+            && Arc::ptr_eq(&self.render, &other.render)
+            && self.bounds == other.bounds
+            && self.toast_size == other.toast_size
+            && self.spacing == other.spacing
+            && self.max_visible == other.max_visible
+    }
+}
+
+impl_view! {
+    ToastOverlay<Ctx>;
+    fn body(&self, _state: &()) {
+        let toasts = self.notifications.toasts();
+        let skip = toasts.len().saturating_sub(self.max_visible);
+        let visible = &toasts[skip..];
+
+        let x = self.bounds.origin.x + (self.bounds.size.x - self.toast_size.x) / 2.;
+        let bottom = self.bounds.origin.y + self.bounds.size.y;
+        let mut body: Fragment<Ctx> = Vec::with_capacity(visible.len());
+        for (i, toast) in visible.iter().enumerate() {
+            let from_bottom = (visible.len() - i) as f64;
+            let y = bottom - from_bottom * (self.toast_size.y + self.spacing);
+            body.push(Arc::new(Layer {
+                key: Some(toast.id.0),
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(x, y), self.toast_size),
+                    ..LayerProps::default()
+                },
+                subviews: vec![(self.render)(toast, &self.notifications)],
+                transitions: Some(Transitions {
+                    enter: Some(Transition::Scale(0.9)),
+                    exit: Some(Transition::Fade),
+                    duration: Duration::from_millis(200),
+                    easing: Easing::EaseOut,
+                }),
+                ..Layer::default()
+            }));
+        }
+        Arc::new(body)
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.notifications.observe(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nv_tree::Patch;
+    use crate::view::ViewId;
+    use crate::view_tree::ViewTree;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const TOAST_SIZE: Vector2<f64> = Vector2::new(80., 20.);
+
+    fn toast(message: &str, duration: Option<u64>) -> Toast {
+        Toast {
+            duration: duration.map(Duration::from_secs),
+            ..Toast::new(message)
+        }
+    }
+
+    fn messages(notifications: &Notifications) -> Vec<String> {
+        notifications
+            .toasts()
+            .into_iter()
+            .map(|toast| toast.toast.message)
+            .collect()
+    }
+
+    /// Returns a root layer containing an overlay that shows at most two toasts, or nothing.
+    fn root(notifications: &Notifications, with_overlay: bool) -> Arc<dyn View> {
+        let mut subviews: Fragment<()> = Vec::new();
+        if with_overlay {
+            subviews.push(Arc::new(ToastOverlay {
+                key: None,
+                notifications: notifications.clone(),
+                render: Arc::new(|_: &ActiveToast, _: &Notifications| -> Arc<dyn View> {
+                    Arc::new(())
+                }),
+                bounds: Rect::new(Point2::new(0., 0.), Vector2::new(100., 100.)),
+                toast_size: TOAST_SIZE,
+                spacing: 5.,
+                max_visible: 2,
+            }));
+        }
+        Arc::new(Layer {
+            subviews,
+            ..Layer::default()
+        })
+    }
+
+    /// Applies the patches of a view tree to a map of native view bounds, and returns the
+    /// origins of the toast layers from top to bottom.
+    fn toast_origins(tree: &mut ViewTree, views: &mut HashMap<ViewId, Rect>) -> Vec<(f64, f64)> {
+        for patch in tree.patches() {
+            match patch {
+                Patch::Update(id, view) | Patch::Replace(id, view) => {
+                    views.insert(id, view.bounds());
+                }
+                Patch::Remove(id) => {
+                    views.remove(&id);
+                }
+                _ => (),
+            }
+        }
+        let mut origins: Vec<_> = views
+            .values()
+            .filter(|bounds| bounds.size == TOAST_SIZE)
+            .map(|bounds| (bounds.origin.x, bounds.origin.y))
+            .collect();
+        origins.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        origins
+    }
+
+    #[test]
+    fn toasts_are_queued_and_dismissed() {
+        let notifications = Notifications::new();
+        let a = notifications.post(toast("a", None));
+        let b = notifications.post(toast("b", None));
+        let c = notifications.post(toast("c", None));
+        assert!(a < b && b < c);
+        assert_eq!(messages(&notifications), ["a", "b", "c"]);
+
+        notifications.dismiss(b);
+        assert_eq!(messages(&notifications), ["a", "c"]);
+        notifications.dismiss(b);
+        assert_eq!(messages(&notifications), ["a", "c"]);
+
+        // clones share the queue
+        notifications.clone().dismiss(a);
+        assert_eq!(messages(&notifications), ["c"]);
+    }
+
+    #[test]
+    fn actions_run_and_dismiss_their_toast() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let action_count = Arc::clone(&count);
+        let notifications = Notifications::new();
+        let id = notifications.post(Toast {
+            actions: vec![ToastAction {
+                title: "Undo".into(),
+                action: Arc::new(move || {
+                    action_count.fetch_add(1, Ordering::SeqCst);
+                }),
+            }],
+            ..toast("deleted", None)
+        });
+        let other = notifications.post(toast("other", None));
+
+        notifications.perform_action(other, 0);
+        notifications.perform_action(id, 1);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        assert_eq!(messages(&notifications), ["deleted", "other"]);
+
+        notifications.perform_action(id, 0);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(messages(&notifications), ["other"]);
+
+        notifications.perform_action(id, 0);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn expired_toasts_are_dismissed_by_ticks() {
+        let notifications = Notifications::new();
+        notifications.post(toast("short", Some(1)));
+        notifications.post(toast("sticky", None));
+        notifications.post(toast("long", Some(2)));
+        let toasts = notifications.toasts();
+        let (short, long) = (toasts[0].posted, toasts[2].posted);
+
+        assert_eq!(
+            notifications.tick(short),
+            Some(short + Duration::from_secs(1))
+        );
+        assert_eq!(messages(&notifications), ["short", "sticky", "long"]);
+
+        let next = notifications.tick(short + Duration::from_secs(1));
+        assert_eq!(next, Some(long + Duration::from_secs(2)));
+        assert_eq!(messages(&notifications), ["sticky", "long"]);
+
+        assert_eq!(notifications.tick(long + Duration::from_secs(2)), None);
+        assert_eq!(messages(&notifications), ["sticky"]);
+    }
+
+    #[test]
+    fn overlays_stack_the_newest_toasts_from_the_bottom() {
+        let notifications = Notifications::new();
+        for message in &["a", "b", "c"] {
+            notifications.post(toast(message, None));
+        }
+        let mut tree = ViewTree::new();
+        let mut views = HashMap::new();
+        tree.render_root(root(&notifications, true), ());
+        assert_eq!(
+            toast_origins(&mut tree, &mut views),
+            [(10., 50.), (10., 75.)]
+        );
+    }
+
+    #[test]
+    fn overlays_are_rendered_again_when_toasts_change() {
+        let notifications = Notifications::new();
+        let first = notifications.post(toast("a", None));
+        let mut tree = ViewTree::new();
+        let mut views = HashMap::new();
+        tree.render_root(root(&notifications, true), ());
+        assert_eq!(toast_origins(&mut tree, &mut views), [(10., 75.)]);
+        assert!(!tree.has_render_requests());
+
+        notifications.post(toast("b", None));
+        assert!(tree.has_render_requests());
+        let now = Instant::now();
+        tree.tick(now);
+        assert_eq!(
+            toast_origins(&mut tree, &mut views),
+            [(10., 50.), (10., 75.)]
+        );
+
+        // dismissed toasts fade out before they’re removed
+        notifications.dismiss(first);
+        tree.tick(now);
+        assert_eq!(toast_origins(&mut tree, &mut views).len(), 2);
+        tree.tick(now + Duration::from_secs(1));
+        assert_eq!(toast_origins(&mut tree, &mut views), [(10., 75.)]);
+
+        // removed overlays stop observing
+        assert_eq!(notifications.observers.len(), 1);
+        tree.render_root(root(&notifications, false), ());
+        assert!(notifications.observers.is_empty());
+        notifications.post(toast("c", None));
+        assert!(!tree.has_render_requests());
+    }
+}
@@ -1,27 +1,125 @@
+use crate::accessibility::Announcement;
+use crate::atlas::ImageAtlas;
 use crate::backend::Backend;
-use crate::color::Color;
-use crate::rect::Rect;
-use crate::view::{LayoutResult, ViewId};
-use cgmath::Matrix3;
+use crate::display_list::DisplayList;
+#[cfg(feature = "fuzzing")]
+use crate::fuzzing::{ReferenceNode, ReferenceTree};
+use crate::image::RgbaImage;
+use crate::layer::LayerProps;
+use crate::layout::LayoutResult;
+use crate::pdf;
+use crate::print::{self, BreakHint, PageSetup, PrintableRegion};
+use crate::raster::SoftwareRenderer;
+use crate::rect::{CornerRadius, Rect};
+use crate::shape::Shape;
+use crate::spatial::SpatialIndex;
+use crate::view::ViewId;
+use cgmath::{EuclideanSpace, Matrix3, Point2, SquareMatrix, Vector2, Vector3};
+use core::hash::Hash;
 use core::ops::DerefMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-#[derive(Clone)]
+/// Grid cell size of the tracking rectangle index.
+const TRACKING_CELL_SIZE: f64 = 128.;
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NativeView {
-    Layer {
+    /// A layer; cap insets of the contents are already clamped to the region size, and zero
+    /// unless the contents are resized to fill the bounds.
+    Layer(Box<LayerProps>),
+    /// A native view of a kind added by another crate; see [`custom_view`](crate::custom_view).
+    Custom {
+        /// Identifies the kind of view, e.g. `com.example.map`.
+        kind: String,
         bounds: Rect,
-        background: Color,
-        corner_radius: f64,
-        border_width: f64,
-        border_color: Color,
-        clip_contents: bool,
         transform: Matrix3<f64>,
         opacity: f64,
+        /// Properties in a format defined by the kind.
+        props: Vec<u8>,
     },
 }
 
+impl NativeView {
+    /// Returns the bounds of the view in its superview.
+    pub fn bounds(&self) -> Rect {
+        match self {
+            NativeView::Layer(props) => props.bounds,
+            NativeView::Custom { bounds, .. } => *bounds,
+        }
+    }
+
+    /// Returns the view transform.
+    pub fn transform(&self) -> Matrix3<f64> {
+        match self {
+            NativeView::Layer(props) => props.transform,
+            NativeView::Custom { transform, .. } => *transform,
+        }
+    }
+
+    /// Returns true if the view clips its subviews.
+    pub fn clips_contents(&self) -> bool {
+        match self {
+            NativeView::Layer(props) => props.clip_contents || props.clip_shape.is_some(),
+            NativeView::Custom { .. } => false,
+        }
+    }
+
+    /// Returns the shape the view clips its subviews to in its own coordinate system (with the
+    /// origin at its top left corner), or None if it doesn’t clip them.
+    pub fn clip_shape(&self) -> Option<Shape> {
+        match self {
+            NativeView::Layer(props) => props.clip_shape(),
+            NativeView::Custom { .. } => None,
+        }
+    }
+
+    /// Returns how far the view draws beyond its bounds, e.g. with an outside border.
+    pub(crate) fn overflow(&self) -> f64 {
+        match self {
+            NativeView::Layer(props) => props.border_alignment.overflow(props.border_width()),
+            NativeView::Custom { .. } => 0.,
+        }
+    }
+
+    /// Returns false if hit testing should skip the view and its subviews.
+    fn is_hit_testable(&self) -> bool {
+        match self {
+            NativeView::Layer(props) => props.hit_testing,
+            NativeView::Custom { .. } => true,
+        }
+    }
+
+    /// Returns the corner radius that hit testing should respect.
+    fn hit_test_corner_radius(&self) -> CornerRadius {
+        match self {
+            NativeView::Layer(props) if props.rounded_hit_testing => props.corner_radius,
+            _ => CornerRadius::default(),
+        }
+    }
+
+    /// Returns the view with its bounds rounded to device pixels at the given scale factor.
+    ///
+    /// Edges are rounded in the superview’s coordinate system, so views stay aligned to the
+    /// window’s pixel grid as long as their ancestors are too and have no transforms other than
+    /// translations.
+    pub fn aligned_to_pixels(&self, scale_factor: f64) -> NativeView {
+        let mut view = self.clone();
+        match &mut view {
+            NativeView::Layer(props) => {
+                props.bounds = props.bounds.round_to_pixels(scale_factor);
+            }
+            NativeView::Custom { bounds, .. } => {
+                *bounds = bounds.round_to_pixels(scale_factor);
+            }
+        }
+        view
+    }
+}
+
 /// Patches for the NV tree.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Patch {
     /// Sets the root view.
     SetRoot(ViewId),
@@ -36,6 +134,8 @@ pub enum Patch {
     /// Removes a view.
     /// **Does not remove the view from the superview’s subview references.**
     Remove(ViewId),
+    /// Asks the screen reader to speak a message.
+    Announce(Announcement),
 }
 
 /// Errors that may occur when running a patch.
@@ -44,22 +144,135 @@ pub enum PatchError<B: Backend> {
     NoSuchView(ViewId),
     BackendError(B::Error),
     Cycle(ViewId),
+    /// The backend views are out of sync with the tree after a backend error; see
+    /// [`NVTree::resync`].
+    OutOfSync,
 }
 
 struct NVTNode<R> {
     view: NativeView,
-    backing_ref: R,
+    /// The backend view; None if the view was added while the backend was out of sync.
+    backing_ref: Option<R>,
     superview: Option<ViewId>,
     subviews: Vec<ViewId>,
     layout: Option<LayoutResult>,
 }
 
+/// Returns a 2D translation matrix.
+fn translation(offset: Vector2<f64>) -> Matrix3<f64> {
+    Matrix3::new(1., 0., 0., 0., 1., 0., offset.x, offset.y, 1.)
+}
+
+/// Intersects a clip rectangle with the clip rectangle of an ancestor, if any.
+fn intersect_clip(clip: Option<Rect>, rect: Rect) -> Rect {
+    match clip {
+        Some(clip) => clip.intersect(rect).unwrap_or_else(Rect::zero),
+        None => rect,
+    }
+}
+
+/// Where a view is in the window, for hit testing.
+pub(crate) struct TrackingArea {
+    /// Maps the view’s coordinate system (with the origin at its top left corner) to the window.
+    pub(crate) to_window: Matrix3<f64>,
+    /// The inverse of `to_window`, or None if the view is collapsed by its transform.
+    from_window: Option<Matrix3<f64>>,
+    size: Vector2<f64>,
+    corner_radius: CornerRadius,
+}
+
+impl TrackingArea {
+    /// Creates a tracking area for a view inside a superview.
+    ///
+    /// Layer transforms are applied about the layer’s center.
+    pub(crate) fn new(superview_to_window: Matrix3<f64>, view: &NativeView) -> TrackingArea {
+        let bounds = view.bounds();
+        let center = bounds.origin.to_vec() + bounds.size / 2.;
+        let to_window = superview_to_window
+            * translation(center)
+            * view.transform()
+            * translation(-bounds.size / 2.);
+        TrackingArea {
+            to_window,
+            from_window: to_window.invert(),
+            size: bounds.size,
+            corner_radius: view.hit_test_corner_radius(),
+        }
+    }
+
+    /// Converts a point from window coordinates to the view’s coordinate system, or returns None
+    /// if the view is collapsed by its transform.
+    fn to_local(&self, point: Point2<f64>) -> Option<Point2<f64>> {
+        let local = self.from_window? * Vector3::new(point.x, point.y, 1.);
+        Some(Point2::new(local.x, local.y))
+    }
+
+    /// Returns true if the point in window coordinates is inside the view.
+    fn contains(&self, point: Point2<f64>) -> bool {
+        match self.to_local(point) {
+            Some(local) => Rect::new(Point2::new(0., 0.), self.size)
+                .contains_rounded(local, self.corner_radius),
+            None => false,
+        }
+    }
+
+    /// Returns the bounding box in window coordinates of a shape in the view’s coordinate
+    /// system.
+    fn shape_bounding_box(&self, shape: &Shape) -> Rect {
+        shape
+            .bounding_box()
+            .transformed(self.to_window)
+            .bounding_box()
+    }
+
+    /// Returns the bounding box of the (possibly transformed) view in window coordinates.
+    pub(crate) fn bounding_box(&self) -> Rect {
+        self.outset_bounding_box(0.)
+    }
+
+    /// Returns the bounding box of the view outset by an amount in its own coordinate system,
+    /// e.g. to include what it draws beyond its bounds.
+    pub(crate) fn outset_bounding_box(&self, amount: f64) -> Rect {
+        Rect::new(Point2::new(0., 0.), self.size)
+            .outset(amount, amount)
+            .transformed(self.to_window)
+            .bounding_box()
+    }
+}
+
 /// The native-view tree; handles layout, events, and backends.
 pub struct NVTree<B, R> {
     nodes: HashMap<ViewId, NVTNode<R>>,
     backend: B,
-    // TODO: spatial index
-    tracking_rects: HashMap<ViewId, Rect>,
+    root: Option<ViewId>,
+    /// Bounding boxes of all views in the root view’s superview’s coordinate system.
+    tracking_rects: SpatialIndex<ViewId>,
+    /// Exact tracking areas of all views in `tracking_rects`.
+    tracking_areas: HashMap<ViewId, TrackingArea>,
+    /// Views whose tracking rectangles (and those of their subviews) are out of date.
+    dirty_tracking_rects: HashSet<ViewId>,
+    /// Whether a backend operation failed, so backend views no longer match the tree.
+    needs_resync: bool,
+    /// If true, bookkeeping is checked for stale entries after every frame.
+    leak_check: bool,
+    /// Device pixels per point of the screen the window is on.
+    scale_factor: f64,
+}
+
+/// Memory statistics of an NV tree; see [`NVTree::memory_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NVTreeStats {
+    /// Number of native views.
+    pub views: usize,
+    /// Number of views with a tracking area for hit testing.
+    pub tracking_areas: usize,
+    /// Number of entries in the tracking rectangle index.
+    pub tracking_rects: usize,
+    /// Number of views whose tracking rectangles are out of date.
+    pub dirty_tracking_rects: usize,
+    /// Number of entries that refer to views that no longer exist or aren’t in the tree; should
+    /// always be zero after a frame.
+    pub stale_entries: usize,
 }
 
 impl<B: DerefMut<Target = Bknd>, Bknd: Backend> NVTree<B, Bknd::ViewRef> {
@@ -67,50 +280,750 @@ impl<B: DerefMut<Target = Bknd>, Bknd: Backend> NVTree<B, Bknd::ViewRef> {
         NVTree {
             nodes: HashMap::new(),
             backend,
-            tracking_rects: HashMap::new(),
+            root: None,
+            tracking_rects: SpatialIndex::new(TRACKING_CELL_SIZE),
+            tracking_areas: HashMap::new(),
+            dirty_tracking_rects: HashSet::new(),
+            needs_resync: false,
+            leak_check: false,
+            scale_factor: 1.,
         }
     }
 
     /// Patches the view tree.
+    ///
+    /// If the backend fails, the patch is still applied to the tree, but the backend views will
+    /// be out of sync with it until [`resync`](NVTree::resync) is called. Until then, patches only
+    /// change the tree.
     pub fn patch(&mut self, patch: Patch) -> Result<(), PatchError<Bknd>> {
         match patch {
             Patch::SetRoot(id) => self.set_root(id),
-            Patch::Update(id, view) => self.update_view(id, view, None),
+            Patch::Update(id, view) => self.update_view(id, view),
             Patch::Replace(id, view) => self.replace_view(id, view),
             Patch::SubviewRegion(id, a, b, subviews) => self.subview_region(id, a, b, subviews),
-            Patch::Remove(id) => self.remove_view(id, true).map(|_| ()),
+            Patch::Remove(id) => self.remove_view(id),
+            Patch::Announce(announcement) => self
+                .backend
+                .announce(&announcement)
+                .map_err(PatchError::BackendError),
         }
     }
 
-    /// Sets a root view.
-    fn set_root(&mut self, id: ViewId) -> Result<(), PatchError<Bknd>> {
-        if let Some(node) = self.nodes.get_mut(&id) {
-            self.backend.set_root_view(&mut node.backing_ref).map_err(PatchError::BackendError)?;
-            Ok(())
-        } else {
-            Err(PatchError::NoSuchView(id))
+    /// Applies all patches of a frame, e.g. from [`ViewTree::patches`](crate::ViewTree::patches).
+    ///
+    /// The patches are checked first, so if any of them refers to a view that won’t exist or
+    /// would make a view its own (direct or indirect) subview, none of them are applied. If the
+    /// backend fails, the patches are still all applied to the tree and the first error is
+    /// returned; the backend will then need a [`resync`](NVTree::resync).
+    pub fn patch_frame<I>(&mut self, patches: I) -> Result<(), PatchError<Bknd>>
+    where
+        I: IntoIterator<Item = Patch>,
+    {
+        let patches: Vec<_> = patches.into_iter().collect();
+        self.check_patches(&patches)?;
+        let mut result = Ok(());
+        for patch in patches {
+            result = result.and(self.patch(patch));
         }
+        if self.leak_check {
+            if let Some(entry) = self.stale_entries().first() {
+                panic!("leak check: {}", entry);
+            }
+        }
+        result
     }
 
-    /// Updates or adds a view.
-    fn update_view(
+    /// Enables or disables the leak check.
+    ///
+    /// With the leak check enabled, the tree checks after every [frame](Self::patch_frame) that
+    /// none of its bookkeeping refers to removed views or views outside the root view’s tree, and
+    /// panics with a description of the first stale entry otherwise. Meant for tests.
+    pub fn set_leak_check(&mut self, leak_check: bool) {
+        self.leak_check = leak_check;
+    }
+
+    /// Returns statistics about the views and bookkeeping in the tree.
+    ///
+    /// Stale entries are only expected in the middle of a frame; e.g. a removed view stays in its
+    /// superview’s subviews until a region patch replaces it.
+    pub fn memory_stats(&self) -> NVTreeStats {
+        NVTreeStats {
+            views: self.nodes.len(),
+            tracking_areas: self.tracking_areas.len(),
+            tracking_rects: self.tracking_rects.len(),
+            dirty_tracking_rects: self.dirty_tracking_rects.len(),
+            stale_entries: self.stale_entries().len(),
+        }
+    }
+
+    /// Returns descriptions of all entries that refer to views that no longer exist or aren’t
+    /// reachable from the root.
+    fn stale_entries(&self) -> Vec<String> {
+        let mut stale = Vec::new();
+
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<_> = self.root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            let node = match self.nodes.get(&id) {
+                Some(node) => node,
+                None => {
+                    stale.push(format!("removed view {} is still in the tree", id));
+                    continue;
+                }
+            };
+            if reachable.insert(id) {
+                stack.extend(node.subviews.iter().copied());
+            }
+        }
+        for id in self.nodes.keys() {
+            if !reachable.contains(id) {
+                stale.push(format!("{} is not reachable from the root", id));
+            }
+        }
+
+        for (id, _) in self.tracking_rects.iter() {
+            if !self.nodes.contains_key(&id) {
+                stale.push(format!("tracking rectangle of removed view {}", id));
+            }
+        }
+        for id in self.tracking_areas.keys() {
+            if !self.nodes.contains_key(id) {
+                stale.push(format!("tracking area of removed view {}", id));
+            }
+        }
+        for id in &self.dirty_tracking_rects {
+            if !self.nodes.contains_key(id) {
+                stale.push(format!("dirty tracking rectangle of removed view {}", id));
+            }
+        }
+
+        stale
+    }
+
+    /// Returns the backend.
+    pub fn backend(&self) -> &Bknd {
+        &self.backend
+    }
+
+    /// Returns the backend mutably, e.g. to poll it for events.
+    ///
+    /// Changing backend views directly will get them out of sync with the tree.
+    pub fn backend_mut(&mut self) -> &mut Bknd {
+        &mut self.backend
+    }
+
+    /// Returns a [display list](crate::display_list) of the tree, for renderers that draw it
+    /// themselves.
+    pub fn display_list(&self) -> DisplayList {
+        self.display_list_from(self.root)
+    }
+
+    /// Returns a display list of a subtree.
+    fn display_list_from(&self, root: Option<ViewId>) -> DisplayList {
+        DisplayList::build(root, self.scale_factor, |id| {
+            self.nodes
+                .get(&id)
+                .map(|node| (&node.view, &node.subviews[..]))
+        })
+    }
+
+    /// Returns the structure of the tree, for comparing it with a reference implementation.
+    #[cfg(feature = "fuzzing")]
+    pub(crate) fn reference_tree(&self) -> ReferenceTree {
+        ReferenceTree {
+            root: self.root,
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(id, node)| {
+                    let node = ReferenceNode {
+                        bounds: node.view.bounds(),
+                        subviews: node.subviews.clone(),
+                    };
+                    (*id, node)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the scale factor of the screen the window is on.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Sets the scale factor (device pixels per point) of the screen the window is on, e.g. when
+    /// the window moves to another screen and the backend sends a
+    /// [`SetScaleFactor`](crate::raw_events::RawEvent::SetScaleFactor) event. Defaults to 1.
+    ///
+    /// Layout, hit testing, and the [display list](NVTree::display_list) stay in points, but
+    /// backends are given views [aligned to pixels](NativeView::aligned_to_pixels) so edges stay
+    /// sharp at fractional scale factors. All backend views are updated when this changes.
+    ///
+    /// Scale factors that aren’t positive and finite are ignored.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) -> Result<(), PatchError<Bknd>> {
+        if !scale_factor.is_finite() || scale_factor <= 0. || scale_factor == self.scale_factor {
+            return Ok(());
+        }
+        self.scale_factor = scale_factor;
+        if self.needs_resync {
+            // resync will create the backend views at the new scale factor
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for node in self.nodes.values_mut() {
+            if let Some(backing_ref) = &mut node.backing_ref {
+                let view = node.view.aligned_to_pixels(scale_factor);
+                result = result.and(self.backend.update_view(backing_ref, view));
+            }
+        }
+        self.synced(result)
+    }
+
+    /// Returns true if the backend views are out of sync with the tree after a backend error.
+    pub fn needs_resync(&self) -> bool {
+        self.needs_resync
+    }
+
+    /// Rebuilds all backend views from the tree, e.g. after a backend error.
+    ///
+    /// Existing backend views are removed first, ignoring any errors since they’re being thrown
+    /// away anyway. If this fails, the backend stays out of sync and this may be called again.
+    pub fn resync(&mut self) -> Result<(), PatchError<Bknd>> {
+        self.needs_resync = true;
+        for node in self.nodes.values_mut() {
+            if let Some(backing_ref) = node.backing_ref.take() {
+                let _ = self.backend.remove_view(backing_ref);
+            }
+        }
+        for node in self.nodes.values_mut() {
+            let backing_ref = self
+                .backend
+                .new_view(node.view.aligned_to_pixels(self.scale_factor))
+                .map_err(PatchError::BackendError)?;
+            node.backing_ref = Some(backing_ref);
+        }
+
+        let ids: Vec<_> = self.nodes.keys().copied().collect();
+        for id in ids {
+            // removed so that subview refs can be borrowed from self.nodes
+            let mut node = self.nodes.remove(&id).unwrap();
+            let nodes = &self.nodes;
+            let subview_refs: Vec<_> = node
+                .subviews
+                .iter()
+                .filter_map(|subview| nodes.get(subview)?.backing_ref.as_ref())
+                .collect();
+            let result = match &mut node.backing_ref {
+                Some(backing_ref) if !subview_refs.is_empty() => {
+                    self.backend.set_subviews(backing_ref, 0, 0, subview_refs)
+                }
+                _ => Ok(()),
+            };
+            self.nodes.insert(id, node);
+            result.map_err(PatchError::BackendError)?;
+        }
+
+        let root_ref = match self.root {
+            Some(root) => self
+                .nodes
+                .get_mut(&root)
+                .and_then(|node| node.backing_ref.as_mut()),
+            None => None,
+        };
+        if let Some(backing_ref) = root_ref {
+            self.backend
+                .set_root_view(backing_ref)
+                .map_err(PatchError::BackendError)?;
+        }
+        self.needs_resync = false;
+        Ok(())
+    }
+
+    /// Checks that patches only refer to views that will exist when they’re applied and don’t
+    /// make a view its own (direct or indirect) subview, without applying them.
+    fn check_patches(&self, patches: &[Patch]) -> Result<(), PatchError<Bknd>> {
+        // subviews of views added, changed, or removed (None) by earlier patches
+        let mut changed: HashMap<ViewId, Option<Vec<ViewId>>> = HashMap::new();
+        // superviews of views moved by earlier patches
+        let mut superviews: HashMap<ViewId, Option<ViewId>> = HashMap::new();
+        let subviews = |changed: &HashMap<_, Option<Vec<_>>>, id| match changed.get(&id) {
+            Some(subviews) => subviews.clone(),
+            None => self.nodes.get(&id).map(|node| node.subviews.clone()),
+        };
+        let superview = |superviews: &HashMap<_, Option<_>>, id| match superviews.get(&id) {
+            Some(superview) => *superview,
+            None => self.nodes.get(&id).and_then(|node| node.superview),
+        };
+        let remove_all = |changed: &mut HashMap<_, _>, mut removed: Vec<_>| {
+            while let Some(id) = removed.pop() {
+                // subviews may have been removed already
+                if let Some(subviews) = subviews(changed, id) {
+                    removed.extend(subviews);
+                    changed.insert(id, None);
+                }
+            }
+        };
+        for patch in patches {
+            match patch {
+                Patch::SetRoot(id) => {
+                    subviews(&changed, *id).ok_or(PatchError::NoSuchView(*id))?;
+                }
+                Patch::Replace(id, _) => {
+                    // replacing a view removes its subviews
+                    let removed = subviews(&changed, *id).ok_or(PatchError::NoSuchView(*id))?;
+                    changed.insert(*id, Some(Vec::new()));
+                    remove_all(&mut changed, removed);
+                }
+                Patch::Update(id, _) => {
+                    if subviews(&changed, *id).is_none() {
+                        changed.insert(*id, Some(Vec::new()));
+                        superviews.insert(*id, None);
+                    }
+                }
+                Patch::SubviewRegion(id, offset, len, new_subviews) => {
+                    let mut current = subviews(&changed, *id).ok_or(PatchError::NoSuchView(*id))?;
+                    for subview in new_subviews {
+                        subviews(&changed, *subview).ok_or(PatchError::NoSuchView(*subview))?;
+                        let mut ancestor = Some(*id);
+                        while let Some(view) = ancestor {
+                            if view == *subview {
+                                return Err(PatchError::Cycle(*id));
+                            }
+                            ancestor = superview(&superviews, view);
+                        }
+                    }
+                    let start = (*offset).min(current.len());
+                    let end = (offset + len).min(current.len());
+                    for subview in current.splice(start..end, new_subviews.iter().copied()) {
+                        if superview(&superviews, subview) == Some(*id) {
+                            superviews.insert(subview, None);
+                        }
+                    }
+                    for subview in new_subviews {
+                        superviews.insert(*subview, Some(*id));
+                    }
+                    changed.insert(*id, Some(current));
+                }
+                Patch::Remove(id) => {
+                    subviews(&changed, *id).ok_or(PatchError::NoSuchView(*id))?;
+                    remove_all(&mut changed, vec![*id]);
+                }
+                Patch::Announce(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the backing ref of a view, for operations that need the backend to be in sync.
+    fn backing_ref(
+        nodes: &HashMap<ViewId, NVTNode<Bknd::ViewRef>>,
+        needs_resync: bool,
+        id: ViewId,
+    ) -> Result<&Bknd::ViewRef, PatchError<Bknd>> {
+        let node = nodes.get(&id).ok_or(PatchError::NoSuchView(id))?;
+        match &node.backing_ref {
+            Some(backing_ref) if !needs_resync => Ok(backing_ref),
+            _ => Err(PatchError::OutOfSync),
+        }
+    }
+
+    /// Converts the result of a backend operation in a patch, marking the backend as out of sync
+    /// if it failed.
+    fn synced<T>(&mut self, result: Result<T, Bknd::Error>) -> Result<T, PatchError<Bknd>> {
+        result.map_err(|err| {
+            self.needs_resync = true;
+            PatchError::BackendError(err)
+        })
+    }
+
+    /// Renders a view and its subviews offscreen into an image of the given size in pixels, with
+    /// the view’s bounds scaled to fit the image.
+    ///
+    /// If the backend does not support offscreen rendering, the view is drawn with the
+    /// [software renderer](crate::raster::SoftwareRenderer) instead, which can’t draw atlas
+    /// images or custom items.
+    pub fn render_to_image(
         &mut self,
         id: ViewId,
-        view: NativeView,
-        bref: Option<Bknd::ViewRef>,
-    ) -> Result<(), PatchError<Bknd>> {
-        if let Some(node) = self.nodes.get_mut(&id) {
+        size: (u32, u32),
+    ) -> Result<Option<RgbaImage>, PatchError<Bknd>> {
+        let backing_ref = Self::backing_ref(&self.nodes, self.needs_resync, id)?;
+        let image = self
+            .backend
+            .render_to_image(backing_ref, size)
+            .map_err(PatchError::BackendError)?;
+        if image.is_some() {
+            return Ok(image);
+        }
+
+        // draw the display list ourselves, with the view scaled to fill the image
+        Ok(self.display_list_from(Some(id)).root.map(|mut root| {
+            let bounds = self.nodes[&id].view.bounds();
+            let scale = |pixels: u32, points: f64| {
+                if points > 0. {
+                    pixels as f64 / points
+                } else {
+                    1.
+                }
+            };
+            let (sx, sy) = (scale(size.0, bounds.size.x), scale(size.1, bounds.size.y));
+            root.transform = Matrix3::new(sx, 0., 0., 0., sy, 0., 0., 0., 1.);
+            let list = DisplayList { root: Some(root) };
+            SoftwareRenderer::new().render(&list, size.0, size.1, 1.)
+        }))
+    }
+
+    /// Captures the current contents of the host (e.g. the window) at device resolution.
+    ///
+    /// Returns None if the backend does not support capturing.
+    pub fn capture(&mut self) -> Result<Option<RgbaImage>, PatchError<Bknd>> {
+        self.backend.capture().map_err(PatchError::BackendError)
+    }
+
+    /// Uploads atlas pages that have changed to the backend, so that layers can use their regions
+    /// as [`contents`](crate::LayerProps::contents).
+    ///
+    /// Should be called before patching in layers that use new regions. Pages that fail to upload
+    /// are uploaded again on the next call.
+    pub fn sync_atlas<K>(&mut self, atlas: &mut ImageAtlas<K>) -> Result<(), PatchError<Bknd>>
+    where
+        K: Clone + Eq + Hash,
+    {
+        for page in atlas.dirty_pages() {
             self.backend
-                .update_view(&mut node.backing_ref, view.clone())
+                .set_atlas_page(page, atlas.page(page))
                 .map_err(PatchError::BackendError)?;
+            atlas.mark_uploaded(page);
+        }
+        Ok(())
+    }
+
+    /// Exports a view and its subviews as a paginated PDF document.
+    ///
+    /// - `printables`: regions with page break behavior, as returned by
+    ///   [`ViewTree::printable_regions`](crate::ViewTree::printable_regions)
+    ///
+    /// If the backend does not support PDF export, the [display list](Self::display_list) of the
+    /// view is drawn instead, without layer contents or custom views.
+    pub fn export_pdf(
+        &mut self,
+        id: ViewId,
+        setup: &PageSetup,
+        printables: &[PrintableRegion],
+    ) -> Result<Option<Vec<u8>>, PatchError<Bknd>> {
+        let bounds = match self.nodes.get(&id) {
+            Some(node) => node.view.bounds(),
+            None => return Err(PatchError::NoSuchView(id)),
+        };
+
+        let mut hints = Vec::new();
+        for printable in printables {
+            let frames: Vec<_> = printable
+                .native_views
+                .iter()
+                .filter_map(|view| self.frame_in(*view, id))
+                .collect();
+            if frames.is_empty() {
+                continue;
+            }
+            hints.push(BreakHint {
+                top: frames
+                    .iter()
+                    .map(|f| f.origin.y)
+                    .fold(f64::INFINITY, f64::min),
+                bottom: frames
+                    .iter()
+                    .map(|f| f.origin.y + f.size.y)
+                    .fold(f64::NEG_INFINITY, f64::max),
+                page_break: printable.page_break,
+            });
+        }
+
+        let content_size = setup.content_size();
+        let pages: Vec<_> = print::paginate(bounds.size.y, content_size.y, &hints)
+            .into_iter()
+            .map(|(top, bottom)| Rect::new((0., top).into(), (bounds.size.x, bottom - top).into()))
+            .collect();
+
+        let backing_ref = Self::backing_ref(&self.nodes, self.needs_resync, id)?;
+        let exported = self
+            .backend
+            .export_pdf(backing_ref, setup, &pages)
+            .map_err(PatchError::BackendError)?;
+        if exported.is_some() {
+            return Ok(exported);
+        }
+
+        // draw the display list ourselves, with the view at the origin of the pages
+        Ok(self.display_list_from(Some(id)).root.map(|mut root| {
+            root.transform = Matrix3::identity();
+            pdf::write_pdf(&root, setup, &pages)
+        }))
+    }
+
+    /// Returns the frame of a view in the coordinate system of one of its ancestors.
+    ///
+    /// Returns None if the ancestor is not actually an ancestor.
+    fn frame_in(&self, id: ViewId, ancestor: ViewId) -> Option<Rect> {
+        let mut frame = self.nodes.get(&id)?.view.bounds();
+        let mut current = id;
+        while current != ancestor {
+            current = self.nodes.get(&current)?.superview?;
+            if current != ancestor {
+                frame = frame + self.nodes.get(&current)?.view.bounds().origin;
+            }
+        }
+        Some(frame)
+    }
+
+    /// Returns the topmost view at a point in the root view’s superview’s coordinate system
+    /// (i.e. the window).
+    ///
+    /// Views are hit if the point is within their bounds and within the clip shapes of all
+    /// ancestors that clip their contents, taking layer transforms into account. Layers with
+    /// [`hit_testing`](crate::LayerProps::hit_testing) disabled and their subviews are skipped.
+    pub fn hit_test(&mut self, point: Point2<f64>) -> Option<ViewId> {
+        self.update_tracking_rects();
+        self.tracking_rects
+            .query_point(point)
+            .into_iter()
+            .filter(|id| self.tracking_areas[id].contains(point))
+            .filter(|id| self.is_hit_testable(*id))
+            .filter(|id| !self.is_clipped_at(*id, point))
+            .filter_map(|id| self.paint_order_path(id).map(|path| (path, id)))
+            .max()
+            .map(|(_, id)| id)
+    }
+
+    /// Returns true if the view exists.
+    pub(crate) fn contains(&self, id: ViewId) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    /// Returns the superview of a view.
+    pub(crate) fn superview(&self, id: ViewId) -> Option<ViewId> {
+        self.nodes.get(&id).and_then(|node| node.superview)
+    }
+
+    /// Converts a point from window coordinates to a view’s coordinate system.
+    ///
+    /// Returns None if the view is not in the root view’s tree or has been collapsed by a
+    /// transform.
+    pub(crate) fn convert_from_window(
+        &mut self,
+        id: ViewId,
+        point: Point2<f64>,
+    ) -> Option<Point2<f64>> {
+        self.update_tracking_rects();
+        self.tracking_areas.get(&id)?.to_local(point)
+    }
+
+    /// Returns false if the view or one of its ancestors disables hit testing.
+    fn is_hit_testable(&self, id: ViewId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let node = &self.nodes[&id];
+            if !node.view.is_hit_testable() {
+                return false;
+            }
+            current = node.superview;
+        }
+        true
+    }
+
+    /// Returns true if an ancestor of the view clips it away at the given point.
+    fn is_clipped_at(&self, id: ViewId, point: Point2<f64>) -> bool {
+        let mut current = self.nodes[&id].superview;
+        while let Some(id) = current {
+            let node = &self.nodes[&id];
+            if let Some(shape) = node.view.clip_shape() {
+                let local = self
+                    .tracking_areas
+                    .get(&id)
+                    .and_then(|area| area.to_local(point));
+                match local {
+                    Some(local) if shape.contains(local) => (),
+                    _ => return true,
+                }
+            }
+            current = node.superview;
+        }
+        false
+    }
+
+    /// Returns the bounding box in window coordinates of the area that the ancestors of a view
+    /// clip it to, or None if they don’t clip it.
+    fn ancestor_clip(&self, id: ViewId) -> Option<Rect> {
+        let mut clip = None;
+        let mut current = self.nodes.get(&id)?.superview;
+        while let Some(id) = current {
+            let node = &self.nodes[&id];
+            if let (Some(shape), Some(area)) =
+                (node.view.clip_shape(), self.tracking_areas.get(&id))
+            {
+                clip = Some(intersect_clip(clip, area.shape_bounding_box(&shape)));
+            }
+            current = node.superview;
+        }
+        clip
+    }
+
+    /// Returns subview indices from the root to the view, which sort in paint order.
+    ///
+    /// Returns None if the view is not in the root view’s tree.
+    fn paint_order_path(&self, id: ViewId) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut current = id;
+        while let Some(superview) = self.nodes.get(&current)?.superview {
+            let index = self.nodes[&superview]
+                .subviews
+                .iter()
+                .position(|subview| *subview == current)?;
+            path.push(index);
+            current = superview;
+        }
+        if Some(current) != self.root {
+            return None;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Recomputes tracking rectangles of views that have been marked as dirty by patches.
+    ///
+    /// Since frames depend on all ancestors, this updates entire subtrees, skipping views that
+    /// will be covered by a dirty ancestor anyway.
+    fn update_tracking_rects(&mut self) {
+        if self.dirty_tracking_rects.is_empty() {
+            return;
+        }
+        let dirty = std::mem::take(&mut self.dirty_tracking_rects);
+        for id in &dirty {
+            if !self.nodes.contains_key(id) {
+                continue;
+            }
+
+            // find out whether this view is still attached to the root
+            let mut current = *id;
+            let mut has_dirty_ancestor = false;
+            while let Some(superview) = self.nodes[&current].superview {
+                if dirty.contains(&superview) {
+                    has_dirty_ancestor = true;
+                    break;
+                }
+                current = superview;
+            }
+            if has_dirty_ancestor {
+                continue;
+            }
+            // the superview is not dirty, so its tracking area is up to date
+            let superview_to_window = if Some(current) != self.root {
+                None
+            } else {
+                match self.nodes[id].superview {
+                    Some(superview) => self
+                        .tracking_areas
+                        .get(&superview)
+                        .map(|area| area.to_window),
+                    None => Some(Matrix3::identity()),
+                }
+            };
+            let clip = self.ancestor_clip(*id);
+            self.update_subtree_tracking_rects(*id, superview_to_window, clip);
+        }
+    }
+
+    /// Sets tracking rectangles for a view and its subviews, or removes them if
+    /// `superview_to_window` is None.
+    ///
+    /// Tracking rectangles are clipped to `clip` (in window coordinates) and the clip shapes of
+    /// the view and its subviews, and removed if they’re clipped away entirely.
+    fn update_subtree_tracking_rects(
+        &mut self,
+        id: ViewId,
+        superview_to_window: Option<Matrix3<f64>>,
+        clip: Option<Rect>,
+    ) {
+        let node = match self.nodes.get(&id) {
+            Some(node) => node,
+            None => return,
+        };
+        let area = superview_to_window.map(|matrix| TrackingArea::new(matrix, &node.view));
+        let to_window = area.as_ref().map(|area| area.to_window);
+        let subview_clip = match (&area, node.view.clip_shape()) {
+            (Some(area), Some(shape)) => {
+                Some(intersect_clip(clip, area.shape_bounding_box(&shape)))
+            }
+            _ => clip,
+        };
+        let subviews = node.subviews.clone();
+        match area {
+            Some(area) => {
+                let rect = area.bounding_box();
+                match clip.map_or(Some(rect), |clip| rect.intersect(clip)) {
+                    Some(rect) => self.tracking_rects.insert(id, rect),
+                    None => {
+                        self.tracking_rects.remove(id);
+                    }
+                }
+                self.tracking_areas.insert(id, area);
+            }
+            None => {
+                self.tracking_rects.remove(id);
+                self.tracking_areas.remove(&id);
+            }
+        }
+        for subview in subviews {
+            self.update_subtree_tracking_rects(subview, to_window, subview_clip);
+        }
+    }
+
+    /// Sets a root view.
+    fn set_root(&mut self, id: ViewId) -> Result<(), PatchError<Bknd>> {
+        let in_sync = !self.needs_resync;
+        let node = self.nodes.get_mut(&id).ok_or(PatchError::NoSuchView(id))?;
+        let result = match node.backing_ref.as_mut().filter(|_| in_sync) {
+            Some(backing_ref) => self.backend.set_root_view(backing_ref),
+            None => Ok(()),
+        };
+        if let Some(prev) = self.root.replace(id) {
+            self.dirty_tracking_rects.insert(prev);
+        }
+        self.dirty_tracking_rects.insert(id);
+        self.synced(result)
+    }
+
+    /// Updates or adds a view.
+    fn update_view(&mut self, id: ViewId, view: NativeView) -> Result<(), PatchError<Bknd>> {
+        let in_sync = !self.needs_resync;
+        let result = if let Some(node) = self.nodes.get_mut(&id) {
+            let result = match node.backing_ref.as_mut().filter(|_| in_sync) {
+                Some(backing_ref) => {
+                    let aligned = view.aligned_to_pixels(self.scale_factor);
+                    self.backend.update_view(backing_ref, aligned)
+                }
+                None => Ok(()),
+            };
+            if node.view.bounds() != view.bounds()
+                || node.view.transform() != view.transform()
+                || node.view.hit_test_corner_radius() != view.hit_test_corner_radius()
+                || node.view.clip_shape() != view.clip_shape()
+            {
+                self.dirty_tracking_rects.insert(id);
+            }
             node.view = view;
+            result
         } else {
-            let backing_ref = if let Some(bref) = bref {
-                bref
+            let (backing_ref, result) = if in_sync {
+                match self
+                    .backend
+                    .new_view(view.aligned_to_pixels(self.scale_factor))
+                {
+                    Ok(backing_ref) => (Some(backing_ref), Ok(())),
+                    Err(err) => (None, Err(err)),
+                }
             } else {
-                self.backend
-                    .new_view(view.clone())
-                    .map_err(PatchError::BackendError)?
+                (None, Ok(()))
             };
             self.nodes.insert(
                 id,
@@ -122,42 +1035,69 @@ impl<B: DerefMut<Target = Bknd>, Bknd: Backend> NVTree<B, Bknd::ViewRef> {
                     layout: None,
                 },
             );
-        }
-        Ok(())
+            self.dirty_tracking_rects.insert(id);
+            result
+        };
+        self.synced(result)
     }
 
+    /// Replaces a view with one of a different type, removing its subviews.
     fn replace_view(&mut self, id: ViewId, view: NativeView) -> Result<(), PatchError<Bknd>> {
-        let backing_ref = self
-            .remove_view(id, false)?
-            .expect("remove_view should have returned a backing ref if dispatch is false");
-        self.update_view(id, view, Some(backing_ref))
+        let subviews = match self.nodes.get_mut(&id) {
+            Some(node) => std::mem::take(&mut node.subviews),
+            None => return Err(PatchError::NoSuchView(id)),
+        };
+        let mut result = Ok(());
+        for subview in subviews {
+            if self.nodes.contains_key(&subview) {
+                result = result.and(self.remove_view(subview));
+            }
+        }
+
+        let in_sync = !self.needs_resync;
+        let node = self.nodes.get_mut(&id).unwrap();
+        let replaced = match node.backing_ref.as_mut().filter(|_| in_sync) {
+            Some(backing_ref) => {
+                let aligned = view.aligned_to_pixels(self.scale_factor);
+                self.backend.replace_view(backing_ref, aligned)
+            }
+            None => Ok(()),
+        };
+        node.view = view;
+        self.dirty_tracking_rects.insert(id);
+        result.and(self.synced(replaced))
     }
 
+    /// Removes a view and its subviews, skipping subviews that were already removed.
+    ///
     /// Does not remove the view from the superview’s subviews list.
-    fn remove_view(
-        &mut self,
-        id: ViewId,
-        dispatch: bool,
-    ) -> Result<Option<Bknd::ViewRef>, PatchError<Bknd>> {
-        if let Some(node) = self.nodes.remove(&id) {
-            for id in node.subviews {
-                self.remove_view(id, true)?;
-            }
-            if dispatch {
-                self.backend
-                    .remove_view(node.backing_ref)
-                    .map_err(PatchError::BackendError)?;
-                Ok(None)
-            } else {
-                Ok(Some(node.backing_ref))
+    fn remove_view(&mut self, id: ViewId) -> Result<(), PatchError<Bknd>> {
+        let node = self.nodes.remove(&id).ok_or(PatchError::NoSuchView(id))?;
+        self.tracking_rects.remove(id);
+        self.tracking_areas.remove(&id);
+        self.dirty_tracking_rects.remove(&id);
+        let mut result = Ok(());
+        for subview in node.subviews {
+            if self.nodes.contains_key(&subview) {
+                result = result.and(self.remove_view(subview));
             }
-        } else {
-            Err(PatchError::NoSuchView(id))
         }
+        let removed = match node.backing_ref {
+            Some(backing_ref) if !self.needs_resync => self.backend.remove_view(backing_ref),
+            Some(backing_ref) => {
+                // the backend is out of sync anyway, so this is just cleanup
+                let _ = self.backend.remove_view(backing_ref);
+                Ok(())
+            }
+            None => Ok(()),
+        };
+        result.and(self.synced(removed))
     }
 
-    /// # Panics
-    /// - if the view is its own direct descendant
+    /// Replaces a region of a view’s subviews.
+    ///
+    /// Nothing is changed if a view doesn’t exist or would become its own (direct or indirect)
+    /// subview.
     fn subview_region(
         &mut self,
         id: ViewId,
@@ -165,69 +1105,491 @@ impl<B: DerefMut<Target = Bknd>, Bknd: Backend> NVTree<B, Bknd::ViewRef> {
         len: usize,
         subviews: Vec<ViewId>,
     ) -> Result<(), PatchError<Bknd>> {
+        if !self.nodes.contains_key(&id) {
+            return Err(PatchError::NoSuchView(id));
+        }
+        for subview in &subviews {
+            if !self.nodes.contains_key(subview) {
+                return Err(PatchError::NoSuchView(*subview));
+            }
+            let mut ancestor = Some(id);
+            while let Some(view) = ancestor {
+                if view == *subview {
+                    return Err(PatchError::Cycle(id));
+                }
+                ancestor = self.nodes.get(&view).and_then(|node| node.superview);
+            }
+        }
+
+        // views that are no longer subviews have no superview until they’re added elsewhere
+        let prev_subviews = &self.nodes[&id].subviews;
+        let start = offset.min(prev_subviews.len());
+        let end = (offset + len).min(prev_subviews.len());
+        let replaced = prev_subviews[start..end].to_vec();
+        for subview in replaced {
+            if let Some(node) = self.nodes.get_mut(&subview) {
+                if node.superview == Some(id) {
+                    node.superview = None;
+                    self.dirty_tracking_rects.insert(subview);
+                }
+            }
+        }
+
         // set the superview property of all subviews
         for subview in &subviews {
-            let node = match self.nodes.get_mut(subview) {
-                Some(node) => node,
-                None => return Err(PatchError::NoSuchView(*subview)),
-            };
-            node.superview = Some(id);
+            self.nodes.get_mut(subview).unwrap().superview = Some(id);
+            self.dirty_tracking_rects.insert(*subview);
         }
 
         // remove the superview node because we need to alias self.nodes when sending a message to
         // the backend
-        let mut superview_node = match self.nodes.remove(&id) {
-            Some(node) => node,
-            None => return Err(PatchError::NoSuchView(id)),
-        };
+        let mut superview_node = self.nodes.remove(&id).unwrap();
 
         // send a message to the backend
-        {
-            let superview_ref = &mut superview_node.backing_ref;
+        let in_sync = !self.needs_resync;
+        let result = match superview_node.backing_ref.as_mut().filter(|_| in_sync) {
+            Some(superview_ref) => {
+                // all views have backing refs while the backend is in sync
+                let nodes = &self.nodes;
+                let subview_refs = subviews
+                    .iter()
+                    .filter_map(|id| nodes[id].backing_ref.as_ref())
+                    .collect();
+                self.backend
+                    .set_subviews(superview_ref, offset, len, subview_refs)
+            }
+            None => Ok(()),
+        };
 
-            let mut subview_refs = Vec::with_capacity(subviews.len());
-            for id in &subviews {
-                match self.nodes.get(&id) {
-                    Some(node) => subview_refs.push(&node.backing_ref),
-                    None => {
-                        // there are two ways to get here:
-                        // either we have weird data races, considering we checked existence of all
-                        // these subviews in the loop at the beginning of this function, or the
-                        // superview is in the subviews list.
-                        // We’ll assume that the second case has happened because safety invariants.
-                        return Err(PatchError::Cycle(*id));
-                    }
-                }
+        // update our own subview list
+        let start = offset.min(superview_node.subviews.len());
+        let end = (offset + len).min(superview_node.subviews.len());
+        superview_node
+            .subviews
+            .splice(start..end, subviews.iter().copied());
+
+        self.nodes.insert(id, superview_node);
+        self.synced(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_events::RawEvent;
+
+    /// A backend that counts its views and can be made to fail.
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct FlakyBackend {
+        views: usize,
+        fail: bool,
+    }
+
+    impl FlakyBackend {
+        fn result(&self) -> Result<(), ()> {
+            if self.fail {
+                Err(())
+            } else {
+                Ok(())
             }
+        }
+    }
 
-            self.backend
-                .set_subviews(superview_ref, offset, len, subview_refs)
-                .map_err(PatchError::BackendError)?;
+    impl Backend for FlakyBackend {
+        type ViewRef = ();
+        type Error = ();
+
+        fn new_view(&mut self, _view: NativeView) -> Result<(), ()> {
+            self.result()?;
+            self.views += 1;
+            Ok(())
+        }
+        fn remove_view(&mut self, _view: ()) -> Result<(), ()> {
+            self.views -= 1;
+            self.result()
+        }
+        fn update_view(&mut self, _: &mut (), _view: NativeView) -> Result<(), ()> {
+            self.result()
+        }
+        fn replace_view(&mut self, _: &mut (), _view: NativeView) -> Result<(), ()> {
+            self.result()
+        }
+        fn set_subviews(&mut self, _: &mut (), _: usize, _: usize, _: Vec<&()>) -> Result<(), ()> {
+            self.result()
         }
+        fn set_root_view(&mut self, _view: &mut ()) -> Result<(), ()> {
+            self.result()
+        }
+        fn poll(&mut self) -> Result<Option<RawEvent>, ()> {
+            Ok(None)
+        }
+    }
 
-        // update our own subview list
-        {
-            // superview_node.subviews[offset..len] = subviews[..len]
-            for (i, j) in (offset..len).zip(0..subviews.len()) {
-                superview_node.subviews[i] = subviews[j];
-            }
-            if subviews.len() < len {
-                // superview_node.subviews[offset + subviews.len()..len] = []
-                for _ in subviews.len()..len {
-                    superview_node.subviews.remove(offset + subviews.len());
-                }
-            }
-            if subviews.len() > len {
-                // superview_node.subviews[offset + len] <- subviews[len..]
-                for i in len..subviews.len() {
-                    superview_node
-                        .subviews
-                        .insert(offset + subviews.len(), subviews[i]);
-                }
+    type Tree = NVTree<Box<FlakyBackend>, ()>;
+
+    fn layer(x: f64, y: f64, width: f64, height: f64) -> NativeView {
+        NativeView::Layer(Box::new(LayerProps {
+            bounds: Rect::new(Point2::new(x, y), Vector2::new(width, height)),
+            ..LayerProps::default()
+        }))
+    }
+
+    /// Returns a layer with some of its properties changed.
+    fn with(view: NativeView, f: impl FnOnce(&mut LayerProps)) -> NativeView {
+        match view {
+            NativeView::Layer(mut props) => {
+                f(&mut props);
+                NativeView::Layer(props)
             }
+            view => view,
+        }
+    }
+
+    /// Returns a tree with a root view containing `a`, which contains `b`.
+    fn nested_tree() -> (Tree, [ViewId; 3]) {
+        let ids = [ViewId::new(), ViewId::new(), ViewId::new()];
+        let [root, a, b] = ids;
+        let mut tree = NVTree::new(Box::default());
+        tree.patch_frame([
+            Patch::Update(root, layer(0., 0., 100., 100.)),
+            Patch::Update(a, layer(10., 10., 50., 50.)),
+            Patch::Update(b, layer(10., 10., 20., 20.)),
+            Patch::SubviewRegion(root, 0, 0, vec![a]),
+            Patch::SubviewRegion(a, 0, 0, vec![b]),
+            Patch::SetRoot(root),
+        ])
+        .unwrap();
+        (tree, ids)
+    }
+
+    #[test]
+    fn indirect_cycles_are_rejected() {
+        let (mut tree, [root, a, b]) = nested_tree();
+
+        let cycle = Patch::SubviewRegion(b, 0, 0, vec![root]);
+        assert_eq!(tree.patch_frame([cycle.clone()]), Err(PatchError::Cycle(b)));
+        assert_eq!(tree.patch(cycle), Err(PatchError::Cycle(b)));
+
+        // within one frame
+        let (c, d) = (ViewId::new(), ViewId::new());
+        let frame = [
+            Patch::Update(c, layer(0., 0., 10., 10.)),
+            Patch::Update(d, layer(0., 0., 10., 10.)),
+            Patch::SubviewRegion(a, 1, 0, vec![c]),
+            Patch::SubviewRegion(c, 0, 0, vec![d]),
+            Patch::SubviewRegion(d, 0, 0, vec![a]),
+        ];
+        assert_eq!(tree.patch_frame(frame), Err(PatchError::Cycle(d)));
+        assert!(!tree.contains(c));
+        assert_eq!(tree.nodes[&a].subviews, [b]);
+        assert_eq!(tree.nodes[&b].subviews, []);
+
+        // hit testing still terminates
+        assert_eq!(tree.hit_test(Point2::new(25., 25.)), Some(b));
+    }
+
+    #[test]
+    fn views_can_adopt_their_former_superview() {
+        let (mut tree, [root, a, b]) = nested_tree();
+
+        // b moves up into the root, then a moves into b
+        tree.patch_frame([
+            Patch::SubviewRegion(a, 0, 1, vec![]),
+            Patch::SubviewRegion(root, 0, 1, vec![b]),
+            Patch::SubviewRegion(b, 0, 0, vec![a]),
+        ])
+        .unwrap();
+        assert_eq!(tree.superview(b), Some(root));
+        assert_eq!(tree.superview(a), Some(b));
+        assert_eq!(tree.nodes[&a].subviews, []);
+        assert_eq!(tree.hit_test(Point2::new(25., 25.)), Some(a));
+
+        // and back, one patch at a time
+        tree.patch(Patch::SubviewRegion(b, 0, 1, vec![])).unwrap();
+        assert_eq!(tree.superview(a), None);
+        tree.patch(Patch::SubviewRegion(root, 0, 1, vec![a]))
+            .unwrap();
+        tree.patch(Patch::SubviewRegion(a, 0, 0, vec![b])).unwrap();
+        assert_eq!(tree.superview(b), Some(a));
+        assert_eq!(tree.memory_stats().stale_entries, 0);
+    }
+
+    #[test]
+    fn invalid_frames_are_not_applied() {
+        let (mut tree, [root, a, b]) = nested_tree();
+        let c = ViewId::new();
+        let missing = ViewId::new();
+
+        let frame = [
+            Patch::Update(c, layer(0., 0., 10., 10.)),
+            Patch::SubviewRegion(root, 1, 0, vec![c]),
+            Patch::Remove(b),
+            Patch::SubviewRegion(a, 0, 1, vec![missing]),
+        ];
+        assert_eq!(
+            tree.patch_frame(frame),
+            Err(PatchError::NoSuchView(missing))
+        );
+        assert!(!tree.contains(c));
+        assert!(tree.contains(b));
+        assert_eq!(tree.nodes[&root].subviews, [a]);
+        assert_eq!(tree.backend().views, 3);
+    }
+
+    #[test]
+    fn backend_errors_are_recovered_by_resyncing() {
+        let (mut tree, [root, a, b]) = nested_tree();
+        let c = ViewId::new();
+
+        // the tree keeps up with the view tree even if the backend fails
+        tree.backend_mut().fail = true;
+        let frame = [
+            Patch::Update(c, layer(60., 60., 10., 10.)),
+            Patch::SubviewRegion(root, 1, 0, vec![c]),
+            Patch::Remove(b),
+            Patch::SubviewRegion(a, 0, 1, vec![]),
+        ];
+        assert_eq!(tree.patch_frame(frame), Err(PatchError::BackendError(())));
+        assert!(tree.needs_resync());
+        assert!(!tree.contains(b));
+        assert_eq!(tree.nodes[&root].subviews, [a, c]);
+        assert!(tree.nodes[&c].backing_ref.is_none());
+        assert_eq!(tree.hit_test(Point2::new(65., 65.)), Some(c));
+
+        // later frames only change the tree
+        tree.backend_mut().fail = false;
+        tree.patch_frame([Patch::Update(c, layer(70., 70., 10., 10.))])
+            .unwrap();
+        assert!(tree.needs_resync());
+        assert!(matches!(
+            tree.render_to_image(root, (1, 1)),
+            Err(PatchError::OutOfSync)
+        ));
+
+        // resyncing fails while the backend does
+        tree.backend_mut().fail = true;
+        assert_eq!(tree.resync(), Err(PatchError::BackendError(())));
+        assert!(tree.needs_resync());
+
+        tree.backend_mut().fail = false;
+        tree.resync().unwrap();
+        assert!(!tree.needs_resync());
+        assert_eq!(tree.backend().views, 3);
+        assert!(tree.nodes.values().all(|node| node.backing_ref.is_some()));
+        assert_eq!(tree.memory_stats().stale_entries, 0);
+    }
+
+    /// Returns the tracking rects and transforms of all views.
+    fn tracking_state(tree: &mut Tree) -> Vec<(ViewId, Option<Rect>, Option<Matrix3<f64>>)> {
+        tree.update_tracking_rects();
+        let mut state: Vec<_> = tree
+            .nodes
+            .keys()
+            .map(|id| {
+                let area = tree.tracking_areas.get(id).map(|area| area.to_window);
+                (*id, tree.tracking_rects.get(*id), area)
+            })
+            .collect();
+        state.sort_by_key(|(id, ..)| *id);
+        state
+    }
+
+    /// Asserts that the incrementally updated tracking rects match a full recomputation.
+    fn assert_tracking_rects_are_fresh(tree: &mut Tree) {
+        let incremental = tracking_state(tree);
+        tree.tracking_rects.clear();
+        tree.tracking_areas.clear();
+        tree.dirty_tracking_rects = tree.nodes.keys().copied().collect();
+        assert_eq!(incremental, tracking_state(tree));
+    }
+
+    #[test]
+    fn incremental_tracking_rects_match_a_full_update() {
+        let (mut tree, [root, a, b]) = nested_tree();
+        let (c, d) = (ViewId::new(), ViewId::new());
+        let clipping = |view| with(view, |props| props.clip_contents = true);
+        tree.patch_frame([
+            Patch::Update(c, clipping(layer(50., 50., 40., 40.))),
+            Patch::Update(d, layer(30., 30., 40., 40.)),
+            Patch::SubviewRegion(root, 1, 0, vec![c]),
+            Patch::SubviewRegion(c, 0, 0, vec![d]),
+        ])
+        .unwrap();
+        assert_tracking_rects_are_fresh(&mut tree);
+        assert_eq!(
+            tree.tracking_rects.get(d),
+            Some(Rect::new(Point2::new(80., 80.), Vector2::new(10., 10.)))
+        );
+
+        // moving and transforming views updates their subtrees
+        tree.patch_frame([
+            Patch::Update(a, layer(20., 20., 50., 50.)),
+            Patch::Update(
+                c,
+                with(clipping(layer(50., 50., 40., 40.)), |props| {
+                    props.transform = Matrix3::from_diagonal(Vector3::new(2., 2., 1.));
+                }),
+            ),
+        ])
+        .unwrap();
+        assert_tracking_rects_are_fresh(&mut tree);
+
+        // moved subtrees are updated in their new place, and removed ones are forgotten
+        tree.patch_frame([
+            Patch::SubviewRegion(a, 0, 1, vec![]),
+            Patch::SubviewRegion(c, 0, 1, vec![b]),
+            Patch::Remove(d),
+            Patch::Update(b, layer(-10., 0., 20., 20.)),
+        ])
+        .unwrap();
+        assert_tracking_rects_are_fresh(&mut tree);
+        assert_eq!(tree.tracking_rects.get(d), None);
+
+        // views that are detached from the root have none
+        tree.patch_frame([
+            Patch::SubviewRegion(root, 1, 1, vec![]),
+            Patch::Update(c, layer(0., 0., 40., 40.)),
+            Patch::Update(
+                root,
+                with(layer(0., 0., 100., 100.), |props| {
+                    props.transform = Matrix3::from_diagonal(Vector3::new(0.5, 0.5, 1.));
+                }),
+            ),
+        ])
+        .unwrap();
+        assert_tracking_rects_are_fresh(&mut tree);
+        assert_eq!(tree.tracking_rects.get(b), None);
+        assert!(tree.tracking_rects.get(a).is_some());
+    }
+
+    #[test]
+    fn hit_testing_respects_layer_transforms() {
+        let (root, rotated, scaled, inner) =
+            (ViewId::new(), ViewId::new(), ViewId::new(), ViewId::new());
+        let mut tree: Tree = NVTree::new(Box::default());
+        // rotated by 90° about its center, so it spans x 90–110 and y 10–110
+        let quarter_turn = Matrix3::new(0., 1., 0., -1., 0., 0., 0., 0., 1.);
+        // scaled by 2 about its center, so it spans 10–50 and 140–180
+        let double = Matrix3::from_diagonal(Vector3::new(2., 2., 1.));
+        tree.patch_frame([
+            Patch::Update(root, layer(0., 0., 200., 200.)),
+            Patch::Update(
+                rotated,
+                with(layer(50., 50., 100., 20.), |props| {
+                    props.transform = quarter_turn
+                }),
+            ),
+            Patch::Update(
+                scaled,
+                with(layer(20., 150., 20., 20.), |props| props.transform = double),
+            ),
+            Patch::Update(inner, layer(10., 10., 10., 10.)),
+            Patch::SubviewRegion(root, 0, 0, vec![rotated, scaled]),
+            Patch::SubviewRegion(scaled, 0, 0, vec![inner]),
+            Patch::SetRoot(root),
+        ])
+        .unwrap();
+
+        assert_eq!(tree.hit_test(Point2::new(100., 15.)), Some(rotated));
+        assert_eq!(tree.hit_test(Point2::new(105., 100.)), Some(rotated));
+        assert_eq!(tree.hit_test(Point2::new(60., 60.)), Some(root));
+        assert_eq!(tree.hit_test(Point2::new(140., 65.)), Some(root));
+
+        assert_eq!(tree.hit_test(Point2::new(12., 142.)), Some(scaled));
+        assert_eq!(tree.hit_test(Point2::new(45., 150.)), Some(scaled));
+        assert_eq!(tree.hit_test(Point2::new(55., 150.)), Some(root));
+        // the subview is scaled along with it, and spans 30–50 and 160–180
+        assert_eq!(tree.hit_test(Point2::new(32., 162.)), Some(inner));
+        assert_eq!(tree.hit_test(Point2::new(48., 178.)), Some(inner));
+        assert_eq!(tree.hit_test(Point2::new(28., 158.)), Some(scaled));
+
+        assert_eq!(
+            tree.convert_from_window(scaled, Point2::new(30., 160.)),
+            Some(Point2::new(10., 10.))
+        );
+        assert_eq!(
+            tree.convert_from_window(rotated, Point2::new(110., 10.)),
+            Some(Point2::new(0., 0.))
+        );
+    }
+
+    #[test]
+    fn images_are_rendered_without_backend_support() {
+        use crate::color::Color;
+
+        let [root, a, b] = [ViewId::new(), ViewId::new(), ViewId::new()];
+        let red = Color::rgb(1., 0., 0.);
+        let blue = Color::rgb(0., 0., 1.);
+        let mut tree: Tree = NVTree::new(Box::default());
+        tree.patch_frame([
+            Patch::Update(root, layer(0., 0., 200., 200.)),
+            Patch::Update(a, with(layer(20., 20., 100., 50.), |a| a.background = red)),
+            Patch::Update(b, with(layer(50., 0., 50., 50.), |b| b.background = blue)),
+            Patch::SubviewRegion(root, 0, 0, vec![a]),
+            Patch::SubviewRegion(a, 0, 0, vec![b]),
+            Patch::SetRoot(root),
+        ])
+        .unwrap();
+
+        // the view is moved to the origin and scaled to fill the image
+        let image = tree.render_to_image(a, (4, 2)).unwrap().unwrap();
+        assert_eq!((image.width(), image.height()), (4, 2));
+        for y in 0..2 {
+            assert_eq!(image.pixel(0, y), [255, 0, 0, 255]);
+            assert_eq!(image.pixel(1, y), [255, 0, 0, 255]);
+            assert_eq!(image.pixel(2, y), [0, 0, 255, 255]);
+            assert_eq!(image.pixel(3, y), [0, 0, 255, 255]);
         }
 
-        self.nodes.insert(id, superview_node);
-        Ok(())
+        let image = tree.render_to_image(root, (2, 2)).unwrap().unwrap();
+        assert_eq!(image.pixel(1, 1), [0, 0, 0, 0]);
+
+        assert!(matches!(
+            tree.render_to_image(ViewId::new(), (1, 1)),
+            Err(PatchError::NoSuchView(_))
+        ));
+    }
+
+    #[test]
+    fn hit_testing_respects_rounded_corners() {
+        let (root, card) = (ViewId::new(), ViewId::new());
+        let rounded = |rounded_hit_testing| {
+            with(layer(50., 50., 100., 100.), |props| {
+                props.corner_radius = CornerRadius::uniform(20.);
+                props.rounded_hit_testing = rounded_hit_testing;
+            })
+        };
+        let mut tree: Tree = NVTree::new(Box::default());
+        tree.patch_frame([
+            Patch::Update(root, layer(0., 0., 200., 200.)),
+            Patch::Update(card, rounded(true)),
+            Patch::SubviewRegion(root, 0, 0, vec![card]),
+            Patch::SetRoot(root),
+        ])
+        .unwrap();
+
+        // the corner arcs are centered at 70 and 130, so the cutouts end about 5.86 points in
+        for (x, y) in [
+            (52., 52.),
+            (55., 55.),
+            (148., 52.),
+            (145., 145.),
+            (52., 148.),
+        ] {
+            assert_eq!(tree.hit_test(Point2::new(x, y)), Some(root), "{} {}", x, y);
+        }
+        for (x, y) in [
+            (57., 57.),
+            (143., 57.),
+            (143., 143.),
+            (57., 143.),
+            (51., 100.),
+        ] {
+            assert_eq!(tree.hit_test(Point2::new(x, y)), Some(card), "{} {}", x, y);
+        }
+
+        tree.patch_frame([Patch::Update(card, rounded(false))])
+            .unwrap();
+        assert_eq!(tree.hit_test(Point2::new(52., 52.)), Some(card));
     }
 }
@@ -0,0 +1,566 @@
+//! A minimal PDF writer for exporting display lists, used when the backend can’t export PDFs.
+//!
+//! Layer backgrounds, gradients, borders, and clip shapes are drawn as vector graphics. Layer
+//! contents and custom views aren’t drawn, since their pixels or drawing code live in the backend.
+//! Group opacity is approximated by applying the opacity to each item separately, and the alpha
+//! of gradient stops is ignored.
+
+use crate::color::{Color, Gradient};
+use crate::display_list::{DisplayItem, DisplayNode};
+use crate::layer::BorderStyle;
+use crate::print::PageSetup;
+use crate::rect::{CornerRadius, Rect};
+use crate::shape::Shape;
+use cgmath::{Matrix3, Point2};
+use core::fmt::Write;
+use std::collections::HashMap;
+
+/// Control point distance for approximating a quarter circle with a cubic Bézier curve.
+const KAPPA: f64 = 0.552_284_749_8;
+
+/// Writes a PDF document with the given regions of a display node on their own pages.
+///
+/// The regions are in the coordinate system of the node and are drawn inset by the page margin.
+pub(crate) fn write_pdf(node: &DisplayNode, setup: &PageSetup, pages: &[Rect]) -> Vec<u8> {
+    let mut resources = Resources::default();
+    let contents: Vec<_> = pages
+        .iter()
+        .map(|page| {
+            let mut content = Content {
+                ops: String::new(),
+                resources: &mut resources,
+            };
+            content.page(node, setup, *page);
+            content.ops
+        })
+        .collect();
+
+    // objects: catalog, page tree, resources, then a page and its contents for each page, then
+    // shadings
+    let mut objects = Vec::new();
+    let page_ids: Vec<_> = (0..contents.len()).map(|i| 4 + 2 * i).collect();
+    let shading_id = |i: usize| 4 + 2 * contents.len() + i;
+
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    let kids: Vec<_> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    objects.push(format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        page_ids.len()
+    ));
+
+    let mut ext_g_states = String::new();
+    let mut alphas: Vec<_> = resources.alphas.iter().collect();
+    alphas.sort_by_key(|(_, index)| **index);
+    for (alpha, index) in alphas {
+        let _ = write!(
+            ext_g_states,
+            " /GS{} << /ca {} /CA {} >>",
+            index, alpha, alpha
+        );
+    }
+    let shadings: Vec<_> = (0..resources.shadings.len())
+        .map(|i| format!(" /Sh{} {} 0 R", i, shading_id(i)))
+        .collect();
+    objects.push(format!(
+        "<< /ExtGState <<{} >> /Shading <<{} >> >>",
+        ext_g_states,
+        shadings.concat()
+    ));
+
+    for (i, ops) in contents.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources 3 0 R /Contents {} 0 R >>",
+            num(setup.page_size.x),
+            num(setup.page_size.y),
+            page_ids[i] + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            ops.len(),
+            ops
+        ));
+    }
+    objects.extend(resources.shadings);
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).bytes());
+    }
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+    for offset in offsets {
+        out.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .bytes(),
+    );
+    out
+}
+
+/// Resources shared by all pages.
+#[derive(Default)]
+struct Resources {
+    /// Graphics states for each alpha value (formatted), by index.
+    alphas: HashMap<String, usize>,
+    /// Shading dictionaries.
+    shadings: Vec<String>,
+}
+
+/// The content stream of a page.
+struct Content<'a> {
+    ops: String,
+    resources: &'a mut Resources,
+}
+
+impl<'a> Content<'a> {
+    fn page(&mut self, node: &DisplayNode, setup: &PageSetup, page: Rect) {
+        // birb coordinates have the y axis pointing down
+        self.op(format_args!(
+            "1 0 0 -1 {} {} cm",
+            num(setup.margin),
+            num(setup.page_size.y - setup.margin)
+        ));
+        self.op(format_args!(
+            "0 0 {} {} re W n",
+            num(page.size.x),
+            num(page.size.y)
+        ));
+        self.op(format_args!(
+            "1 0 0 1 {} {} cm",
+            num(-page.origin.x),
+            num(-page.origin.y)
+        ));
+        self.node(node, 1.);
+    }
+
+    fn node(&mut self, node: &DisplayNode, opacity: f64) {
+        let opacity = opacity * node.opacity;
+        if opacity <= 0. {
+            return;
+        }
+        self.op(format_args!("q"));
+        self.op(format_args!("{} cm", matrix(node.transform)));
+        for item in &node.items {
+            self.item(item, opacity);
+        }
+        if let Some(clip) = &node.clip {
+            shape_path(&mut self.ops, clip);
+            self.op(format_args!("W n"));
+        }
+        for child in &node.children {
+            self.node(child, opacity);
+        }
+        self.op(format_args!("Q"));
+    }
+
+    fn item(&mut self, item: &DisplayItem, opacity: f64) {
+        match item {
+            DisplayItem::RoundedRect {
+                rect,
+                corner_radius,
+                color,
+            } => {
+                self.op(format_args!("q"));
+                self.alpha(color.a * opacity);
+                self.op(format_args!("{} rg", rgb(*color)));
+                rounded_rect_path(&mut self.ops, *rect, *corner_radius);
+                self.op(format_args!("f Q"));
+            }
+            DisplayItem::Gradient {
+                rect,
+                corner_radius,
+                gradient,
+            } => {
+                self.op(format_args!("q"));
+                self.alpha(opacity);
+                rounded_rect_path(&mut self.ops, *rect, *corner_radius);
+                self.op(format_args!("W n"));
+                // gradient coordinates are relative to the rectangle
+                self.op(format_args!(
+                    "{} 0 0 {} {} {} cm",
+                    num(rect.size.x),
+                    num(rect.size.y),
+                    num(rect.origin.x),
+                    num(rect.origin.y)
+                ));
+                let shading = self.shading(gradient);
+                self.op(format_args!("/Sh{} sh Q", shading));
+            }
+            DisplayItem::Border {
+                rect,
+                corner_radius,
+                width,
+                color,
+                style,
+            } => {
+                // strokes are centered on the path
+                let middle = rect.inset(width / 2., width / 2.);
+                self.op(format_args!("q"));
+                self.alpha(color.a * opacity);
+                self.op(format_args!("{} RG {} w", rgb(*color), num(*width)));
+                if let BorderStyle::Dashed { dash, gap } = style {
+                    self.op(format_args!("[{} {}] 0 d", num(*dash), num(*gap)));
+                }
+                rounded_rect_path(&mut self.ops, middle, corner_radius.inset(width / 2.));
+                self.op(format_args!("S Q"));
+            }
+            DisplayItem::Image { .. } | DisplayItem::Custom { .. } => (),
+        }
+    }
+
+    /// Sets the fill and stroke alpha.
+    fn alpha(&mut self, alpha: f64) {
+        let alpha = num(alpha.clamp(0., 1.));
+        let next = self.resources.alphas.len();
+        let index = *self.resources.alphas.entry(alpha).or_insert(next);
+        self.op(format_args!("/GS{} gs", index));
+    }
+
+    /// Adds an axial shading for a gradient in the unit square, and returns its index.
+    fn shading(&mut self, gradient: &Gradient) -> usize {
+        // the gradient is piecewise linear between these locations
+        let mut locations = vec![0., 1.];
+        for (location, _) in &gradient.stops {
+            if *location > 0. && *location < 1. {
+                locations.push(*location);
+            }
+        }
+        locations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        locations.dedup();
+
+        let functions: Vec<_> = locations
+            .windows(2)
+            .map(|pair| {
+                format!(
+                    "<< /FunctionType 2 /Domain [0 1] /C0 [{}] /C1 [{}] /N 1 >>",
+                    rgb(gradient.color_at(pair[0])),
+                    rgb(gradient.color_at(pair[1]))
+                )
+            })
+            .collect();
+        let bounds: Vec<_> = locations[1..locations.len() - 1]
+            .iter()
+            .map(|location| num(*location))
+            .collect();
+        let encode = vec!["0 1"; functions.len()];
+
+        let shading = format!(
+            "<< /ShadingType 2 /ColorSpace /DeviceRGB /Coords [{} {} {} {}] /Extend [true true] \
+             /Function << /FunctionType 3 /Domain [0 1] /Functions [{}] /Bounds [{}] \
+             /Encode [{}] >> >>",
+            num(gradient.start.x),
+            num(gradient.start.y),
+            num(gradient.end.x),
+            num(gradient.end.y),
+            functions.join(" "),
+            bounds.join(" "),
+            encode.join(" ")
+        );
+        self.resources.shadings.push(shading);
+        self.resources.shadings.len() - 1
+    }
+
+    fn op(&mut self, op: core::fmt::Arguments) {
+        let _ = self.ops.write_fmt(op);
+        self.ops.push('\n');
+    }
+}
+
+/// Formats a number compactly.
+fn num(value: f64) -> String {
+    if !value.is_finite() {
+        return "0".to_string();
+    }
+    let formatted = format!("{:.4}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "" | "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+fn rgb(color: Color) -> String {
+    format!("{} {} {}", num(color.r), num(color.g), num(color.b))
+}
+
+/// Formats an affine transform as the operands of `cm`.
+fn matrix(m: Matrix3<f64>) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        num(m.x.x),
+        num(m.x.y),
+        num(m.y.x),
+        num(m.y.y),
+        num(m.z.x),
+        num(m.z.y)
+    )
+}
+
+fn move_to(out: &mut String, point: Point2<f64>) {
+    let _ = writeln!(out, "{} {} m", num(point.x), num(point.y));
+}
+
+fn line_to(out: &mut String, point: Point2<f64>) {
+    let _ = writeln!(out, "{} {} l", num(point.x), num(point.y));
+}
+
+fn curve_to(out: &mut String, c1: Point2<f64>, c2: Point2<f64>, point: Point2<f64>) {
+    let _ = writeln!(
+        out,
+        "{} {} {} {} {} {} c",
+        num(c1.x),
+        num(c1.y),
+        num(c2.x),
+        num(c2.y),
+        num(point.x),
+        num(point.y)
+    );
+}
+
+/// Appends a rounded rectangle, running clockwise from the top left corner.
+fn rounded_rect_path(out: &mut String, rect: Rect, radius: CornerRadius) {
+    let radius = radius.clamped(rect.size);
+    let (left, top) = (rect.origin.x, rect.origin.y);
+    let (right, bottom) = (left + rect.size.x, top + rect.size.y);
+    let k = 1. - KAPPA;
+
+    move_to(out, Point2::new(left + radius.top_left, top));
+    line_to(out, Point2::new(right - radius.top_right, top));
+    if radius.top_right > 0. {
+        let r = radius.top_right;
+        curve_to(
+            out,
+            Point2::new(right - r * k, top),
+            Point2::new(right, top + r * k),
+            Point2::new(right, top + r),
+        );
+    }
+    line_to(out, Point2::new(right, bottom - radius.bottom_right));
+    if radius.bottom_right > 0. {
+        let r = radius.bottom_right;
+        curve_to(
+            out,
+            Point2::new(right, bottom - r * k),
+            Point2::new(right - r * k, bottom),
+            Point2::new(right - r, bottom),
+        );
+    }
+    line_to(out, Point2::new(left + radius.bottom_left, bottom));
+    if radius.bottom_left > 0. {
+        let r = radius.bottom_left;
+        curve_to(
+            out,
+            Point2::new(left + r * k, bottom),
+            Point2::new(left, bottom - r * k),
+            Point2::new(left, bottom - r),
+        );
+    }
+    line_to(out, Point2::new(left, top + radius.top_left));
+    if radius.top_left > 0. {
+        let r = radius.top_left;
+        curve_to(
+            out,
+            Point2::new(left, top + r * k),
+            Point2::new(left + r * k, top),
+            Point2::new(left + r, top),
+        );
+    }
+    out.push_str("h\n");
+}
+
+/// Appends the path of a shape.
+fn shape_path(out: &mut String, shape: &Shape) {
+    match shape {
+        Shape::RoundedRect {
+            rect,
+            corner_radius,
+        } => rounded_rect_path(out, *rect, *corner_radius),
+        Shape::Ellipse(rect) => {
+            let (rx, ry) = (rect.size.x / 2., rect.size.y / 2.);
+            let (cx, cy) = (rect.origin.x + rx, rect.origin.y + ry);
+            let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+            move_to(out, Point2::new(cx, cy - ry));
+            curve_to(
+                out,
+                Point2::new(cx + kx, cy - ry),
+                Point2::new(cx + rx, cy - ky),
+                Point2::new(cx + rx, cy),
+            );
+            curve_to(
+                out,
+                Point2::new(cx + rx, cy + ky),
+                Point2::new(cx + kx, cy + ry),
+                Point2::new(cx, cy + ry),
+            );
+            curve_to(
+                out,
+                Point2::new(cx - kx, cy + ry),
+                Point2::new(cx - rx, cy + ky),
+                Point2::new(cx - rx, cy),
+            );
+            curve_to(
+                out,
+                Point2::new(cx - rx, cy - ky),
+                Point2::new(cx - kx, cy - ry),
+                Point2::new(cx, cy - ry),
+            );
+            out.push_str("h\n");
+        }
+        Shape::Polygon(points) => {
+            if let Some((first, rest)) = points.split_first() {
+                move_to(out, *first);
+                for point in rest {
+                    line_to(out, *point);
+                }
+                out.push_str("h\n");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::ViewId;
+    use cgmath::{SquareMatrix, Vector2};
+
+    fn node(items: Vec<DisplayItem>, children: Vec<DisplayNode>) -> DisplayNode {
+        DisplayNode {
+            view: ViewId::new(),
+            transform: Matrix3::identity(),
+            opacity: 1.,
+            group_opacity: false,
+            clip: None,
+            should_rasterize: false,
+            items,
+            children,
+        }
+    }
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    fn setup() -> PageSetup {
+        PageSetup {
+            page_size: Vector2::new(200., 300.),
+            margin: 10.,
+        }
+    }
+
+    /// Returns the text of a PDF, checking that the cross-reference table points at its objects.
+    fn checked_text(pdf: &[u8]) -> String {
+        let text = String::from_utf8(pdf.to_vec()).unwrap();
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+
+        let startxref = text.rfind("startxref\n").unwrap();
+        let xref: usize = text[startxref + 10..]
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(text[xref..].starts_with("xref\n"));
+        let entries = text[xref..].lines().skip(3);
+        for (i, entry) in entries.take_while(|line| line.ends_with(" n ")).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+        }
+        text
+    }
+
+    #[test]
+    fn one_page_per_region() {
+        let root = node(Vec::new(), Vec::new());
+        let pages = [rect(0., 0., 180., 280.), rect(0., 280., 180., 100.)];
+        let text = checked_text(&write_pdf(&root, &setup(), &pages));
+        assert!(text.contains("/Type /Pages /Kids [4 0 R 6 0 R] /Count 2"));
+        assert!(text.contains("/MediaBox [0 0 200 300]"));
+        // flipped, inset by the margin, clipped, and moved to the region
+        assert!(text.contains("1 0 0 -1 10 290 cm\n0 0 180 100 re W n\n1 0 0 1 0 -280 cm\n"));
+    }
+
+    #[test]
+    fn items_are_drawn() {
+        let child = DisplayNode {
+            transform: Matrix3::new(1., 0., 0., 0., 1., 0., 5., 6., 1.),
+            opacity: 0.5,
+            ..node(
+                vec![DisplayItem::Border {
+                    rect: rect(0., 0., 20., 20.),
+                    corner_radius: CornerRadius::uniform(4.),
+                    width: 2.,
+                    color: Color::BLACK,
+                    style: BorderStyle::Dashed { dash: 4., gap: 2. },
+                }],
+                Vec::new(),
+            )
+        };
+        let root = DisplayNode {
+            clip: Some(Shape::Ellipse(rect(0., 0., 50., 30.))),
+            ..node(
+                vec![DisplayItem::RoundedRect {
+                    rect: rect(0., 0., 50., 30.),
+                    corner_radius: CornerRadius::default(),
+                    color: Color::rgba(1., 0., 0., 0.25),
+                }],
+                vec![child],
+            )
+        };
+        let text = checked_text(&write_pdf(&root, &setup(), &[rect(0., 0., 50., 30.)]));
+
+        assert!(text.contains("/GS0 << /ca 0.25 /CA 0.25 >>"));
+        assert!(text.contains("/GS0 gs\n1 0 0 rg\n0 0 m\n50 0 l\n50 30 l\n0 30 l\n0 0 l\nh\nf Q"));
+        assert!(text.contains("h\nW n\n"));
+        assert!(text.contains("1 0 0 1 5 6 cm"));
+        assert!(text.contains("/GS1 << /ca 0.5 /CA 0.5 >>"));
+        assert!(text.contains("0 0 0 RG 2 w\n[4 2] 0 d\n4 1 m\n"));
+    }
+
+    #[test]
+    fn gradients_are_stitched() {
+        let gradient = Gradient {
+            start: Point2::new(0., 0.),
+            end: Point2::new(0., 1.),
+            stops: vec![
+                (0., Color::BLACK),
+                (0.5, Color::rgb(1., 0., 0.)),
+                (1., Color::WHITE),
+            ],
+        };
+        let root = node(
+            vec![DisplayItem::Gradient {
+                rect: rect(10., 20., 30., 40.),
+                corner_radius: CornerRadius::default(),
+                gradient,
+            }],
+            Vec::new(),
+        );
+        let text = checked_text(&write_pdf(&root, &setup(), &[rect(0., 0., 50., 80.)]));
+
+        assert!(text.contains("/Shading << /Sh0 6 0 R >>"));
+        assert!(text.contains("30 0 0 40 10 20 cm\n/Sh0 sh Q"));
+        assert!(text.contains("/Coords [0 0 0 1]"));
+        assert!(text.contains("/C0 [0 0 0] /C1 [1 0 0]"));
+        assert!(text.contains("/C0 [1 0 0] /C1 [1 1 1]"));
+        assert!(text.contains("/Bounds [0.5] /Encode [0 1 0 1]"));
+    }
+
+    #[test]
+    fn numbers_are_compact() {
+        assert_eq!(num(1.), "1");
+        assert_eq!(num(-0.5), "-0.5");
+        assert_eq!(num(1.23456), "1.2346");
+        assert_eq!(num(-0.00001), "0");
+        assert_eq!(num(f64::NAN), "0");
+    }
+}
@@ -0,0 +1,212 @@
+//! Printing and PDF export.
+
+use crate::impl_view;
+use crate::view::{View, ViewId};
+use cgmath::Vector2;
+use core::fmt;
+use std::sync::Arc;
+
+/// Page break behavior around a view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PageBreak {
+    /// Pages may break anywhere.
+    Auto,
+    /// Always starts a new page before the view.
+    Before,
+    /// Always starts a new page after the view.
+    After,
+    /// Avoids breaking pages inside the view, unless it doesn’t fit on a single page.
+    AvoidInside,
+}
+
+/// Wraps a view to control page breaks when the view tree is printed or exported as a PDF.
+pub struct Printable<Ctx> {
+    pub key: Option<u64>,
+
+    /// The wrapped view.
+    pub content: Arc<dyn View<Ctx>>,
+
+    /// Page break behavior.
+    pub page_break: PageBreak,
+}
+
+impl<Ctx> fmt::Debug for Printable<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Printable")
+            .field("content", &self.content)
+            .field("page_break", &self.page_break)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for Printable<Ctx> {
+    fn eq(&self, other: &Printable<Ctx>) -> bool {
+        self.key == other.key
+            && self.page_break == other.page_break
+            && self.content.eq(&*other.content)
+    }
+}
+
+impl_view! {
+    Printable<Ctx>;
+    fn body(&self, _state: &()) {
+        Arc::clone(&self.content)
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+/// Page layout for printing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSetup {
+    /// Page size in points.
+    pub page_size: Vector2<f64>,
+
+    /// Page margin in points, on all sides.
+    pub margin: f64,
+}
+
+impl PageSetup {
+    /// A4 paper with a 2 cm margin.
+    pub fn a4() -> PageSetup {
+        PageSetup {
+            page_size: Vector2::new(595.28, 841.89),
+            margin: 56.69,
+        }
+    }
+
+    /// US Letter paper with a 1 inch margin.
+    pub fn letter() -> PageSetup {
+        PageSetup {
+            page_size: Vector2::new(612., 792.),
+            margin: 72.,
+        }
+    }
+
+    /// The size of the printable area of a page.
+    pub fn content_size(&self) -> Vector2<f64> {
+        self.page_size - Vector2::new(2. * self.margin, 2. * self.margin)
+    }
+}
+
+/// The native views of a [`Printable`] view, as collected by the view tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintableRegion {
+    /// The closest native views of the printable view.
+    pub native_views: Vec<ViewId>,
+
+    /// Page break behavior.
+    pub page_break: PageBreak,
+}
+
+/// A vertical range of content with page break behavior.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakHint {
+    pub top: f64,
+    pub bottom: f64,
+    pub page_break: PageBreak,
+}
+
+/// Splits content into pages and returns the (top, bottom) range of each page.
+///
+/// Returns no pages if the content height isn’t finite (e.g. from an unconstrained layout) or the
+/// page height isn’t positive.
+pub fn paginate(content_height: f64, page_height: f64, hints: &[BreakHint]) -> Vec<(f64, f64)> {
+    let mut pages = Vec::new();
+    if !content_height.is_finite() || page_height.is_nan() || page_height <= 0. {
+        return pages;
+    }
+
+    let mut start = 0.;
+    while start < content_height {
+        let mut end = start + page_height;
+
+        // forced page breaks
+        for hint in hints {
+            let forced = match hint.page_break {
+                PageBreak::Before => Some(hint.top),
+                PageBreak::After => Some(hint.bottom),
+                _ => None,
+            };
+            if let Some(forced) = forced {
+                if forced > start && forced < end {
+                    end = forced;
+                }
+            }
+        }
+
+        // move the break above regions that would be cut in half
+        for hint in hints {
+            if hint.page_break == PageBreak::AvoidInside
+                && hint.top > start
+                && hint.top < end
+                && hint.bottom > end
+                && hint.bottom - hint.top <= page_height
+            {
+                end = hint.top;
+            }
+        }
+
+        pages.push((start, end.min(content_height)));
+        start = end;
+    }
+
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint(top: f64, bottom: f64, page_break: PageBreak) -> BreakHint {
+        BreakHint {
+            top,
+            bottom,
+            page_break,
+        }
+    }
+
+    #[test]
+    fn content_is_split_into_pages() {
+        assert_eq!(
+            paginate(250., 100., &[]),
+            [(0., 100.), (100., 200.), (200., 250.)]
+        );
+        assert_eq!(paginate(100., 100., &[]), [(0., 100.)]);
+        assert_eq!(paginate(0., 100., &[]), []);
+        assert_eq!(paginate(50., f64::INFINITY, &[]), [(0., 50.)]);
+    }
+
+    #[test]
+    fn page_breaks_follow_hints() {
+        let hints = [
+            hint(30., 40., PageBreak::Before),
+            hint(60., 70., PageBreak::After),
+            hint(150., 190., PageBreak::AvoidInside),
+            hint(160., 180., PageBreak::Auto),
+        ];
+        assert_eq!(
+            paginate(200., 100., &hints),
+            [(0., 30.), (30., 70.), (70., 150.), (150., 200.)]
+        );
+
+        // regions taller than a page are broken anyway
+        let hints = [hint(50., 180., PageBreak::AvoidInside)];
+        assert_eq!(paginate(200., 100., &hints), [(0., 100.), (100., 200.)]);
+    }
+
+    #[test]
+    fn invalid_heights_have_no_pages() {
+        for &(content_height, page_height) in &[
+            (f64::INFINITY, 100.),
+            (f64::NAN, 100.),
+            (f64::NEG_INFINITY, 100.),
+            (100., f64::NAN),
+            (100., 0.),
+            (100., -10.),
+        ] {
+            assert_eq!(paginate(content_height, page_height, &[]), []);
+        }
+    }
+}
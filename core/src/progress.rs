@@ -0,0 +1,366 @@
+//! Progress indicators.
+
+use crate::color::Color;
+use crate::impl_view;
+use crate::layer::{Layer, LayerProps};
+use crate::rect::Rect;
+use crate::view::{AnyState, Fragment, State, View};
+use crate::view_tree::Context;
+use cgmath::{Matrix3, Point2, Rad, Vector2};
+use core::any::Any;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A determinate progress bar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressBar {
+    pub key: Option<u64>,
+
+    /// Bar bounds.
+    pub bounds: Rect,
+
+    /// Progress, between 0 and 1.
+    pub value: f64,
+
+    /// Color of the unfilled part of the bar.
+    pub track_color: Color,
+
+    /// Color of the filled part of the bar.
+    pub fill_color: Color,
+}
+
+impl_view! {
+    ProgressBar;
+    fn body(&self, _state: &()) {
+        let value = if self.value.is_nan() { 0. } else { self.value.clamp(0., 1.) };
+        let radius = self.bounds.size.y / 2.;
+        let fill = Layer {
+            props: LayerProps {
+                bounds: Rect::new(
+                    Point2::new(0., 0.),
+                    Vector2::new(self.bounds.size.x * value, self.bounds.size.y),
+                ),
+                background: self.fill_color,
+                corner_radius: radius.into(),
+                ..LayerProps::default()
+            },
+            ..Layer::default()
+        };
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                background: self.track_color,
+                corner_radius: radius.into(),
+                clip_contents: true,
+                ..LayerProps::default()
+            },
+            subviews: vec![Arc::new(fill)],
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+/// An indeterminate activity spinner: a ring of ticks with a highlight that goes around.
+///
+/// The spinner animates on every [`ViewTree::tick`](crate::ViewTree::tick).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spinner {
+    pub key: Option<u64>,
+
+    /// Spinner bounds; the spinner will be centered in them.
+    pub bounds: Rect,
+
+    /// Tick color.
+    pub color: Color,
+
+    /// Number of ticks.
+    pub tick_count: usize,
+
+    /// Duration of one revolution.
+    pub period: Duration,
+}
+
+impl Spinner {
+    /// Creates a spinner with the standard appearance.
+    pub fn new(bounds: Rect, color: Color) -> Spinner {
+        Spinner {
+            key: None,
+            bounds,
+            color,
+            tick_count: 12,
+            period: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SpinnerState {
+    start: Instant,
+}
+
+impl<Ctx> State<Ctx> for SpinnerState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl<Ctx: 'static> View<Ctx> for Spinner {
+    fn new_state(&self, _context: Context<Ctx>) -> AnyState<Ctx> {
+        Arc::new(SpinnerState {
+            start: Instant::now(),
+        })
+    }
+    fn body(&self, state: &AnyState<Ctx>) -> Arc<dyn View<Ctx>> {
+        let state = state
+            .as_any()
+            .downcast_ref::<SpinnerState>()
+            .expect("View::body: invalid state for Spinner");
+        let count = self.tick_count.max(1);
+        let period = self.period.as_secs_f64().max(0.001);
+        let phase = (state.start.elapsed().as_secs_f64() / period).fract();
+        let head = (phase * count as f64).floor() as usize;
+
+        let size = self.bounds.size.x.min(self.bounds.size.y);
+        let center = Point2::new(self.bounds.size.x / 2., self.bounds.size.y / 2.);
+        let tick_size = Vector2::new(size * 0.08, size * 0.25);
+        let ring_radius = (size - tick_size.y) / 2.;
+
+        let mut ticks: Fragment<Ctx> = Vec::with_capacity(count);
+        for i in 0..count {
+            let angle = 2. * PI * i as f64 / count as f64;
+            // clockwise from the top
+            let tick_center = center + Vector2::new(angle.sin(), -angle.cos()) * ring_radius;
+            // ticks fade out behind the head
+            let age = (head + count - i) % count;
+            let opacity = 1. - 0.8 * age as f64 / count as f64;
+            ticks.push(Arc::new(Layer {
+                props: LayerProps {
+                    bounds: Rect::new(tick_center - tick_size / 2., tick_size),
+                    background: self.color,
+                    corner_radius: (tick_size.x / 2.).into(),
+                    // layers are transformed about their center
+                    transform: Matrix3::from_angle_z(Rad(angle)),
+                    opacity,
+                    ..LayerProps::default()
+                },
+                ..Layer::default()
+            }));
+        }
+
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                ..LayerProps::default()
+            },
+            subviews: ticks,
+            ..Layer::default()
+        })
+    }
+    fn eq(&self, other: &dyn View<Ctx>) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn is_animated(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nv_tree::{NativeView, Patch};
+    use crate::view_tree::ViewTree;
+
+    const TRACK: Color = Color::rgb(0.5, 0.5, 0.5);
+    const FILL: Color = Color::rgb(0., 0.5, 1.);
+
+    fn bounds(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    fn bar(value: f64) -> Arc<dyn View> {
+        Arc::new(ProgressBar {
+            key: None,
+            bounds: bounds(10., 10., 200., 8.),
+            value,
+            track_color: TRACK,
+            fill_color: FILL,
+        })
+    }
+
+    /// Drains the patches and returns the properties of all updated layers.
+    fn updated_layers(tree: &mut ViewTree) -> Vec<LayerProps> {
+        tree.patches()
+            .filter_map(|patch| match patch {
+                Patch::Update(_, NativeView::Layer(props)) => Some(*props),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the width of the fill of a progress bar showing a value.
+    fn fill_width(value: f64) -> f64 {
+        let mut tree = ViewTree::new();
+        tree.render_root(bar(value), ());
+        let layers = updated_layers(&mut tree);
+        let fill = layers.iter().find(|layer| layer.background == FILL);
+        fill.unwrap().bounds.size.x
+    }
+
+    #[test]
+    fn progress_bars_fill_their_track() {
+        let mut tree = ViewTree::new();
+        tree.render_root(bar(0.25), ());
+        let layers = updated_layers(&mut tree);
+        assert_eq!(layers.len(), 2);
+        let track = layers
+            .iter()
+            .find(|layer| layer.background == TRACK)
+            .unwrap();
+        let fill = layers
+            .iter()
+            .find(|layer| layer.background == FILL)
+            .unwrap();
+        assert_eq!(track.bounds, bounds(10., 10., 200., 8.));
+        assert!(track.clip_contents);
+        assert_eq!(track.corner_radius, 4.0.into());
+        assert_eq!(fill.bounds, bounds(0., 0., 50., 8.));
+        assert_eq!(fill.corner_radius, 4.0.into());
+
+        // only the fill changes with the value
+        tree.render_root(bar(0.75), ());
+        let layers = updated_layers(&mut tree);
+        let track = layers
+            .iter()
+            .find(|layer| layer.background == TRACK)
+            .unwrap();
+        let fill = layers
+            .iter()
+            .find(|layer| layer.background == FILL)
+            .unwrap();
+        assert_eq!(track.bounds, bounds(10., 10., 200., 8.));
+        assert_eq!(fill.bounds, bounds(0., 0., 150., 8.));
+    }
+
+    #[test]
+    fn progress_values_are_clamped() {
+        assert_eq!(fill_width(0.), 0.);
+        assert_eq!(fill_width(1.), 200.);
+        assert_eq!(fill_width(1.5), 200.);
+        assert_eq!(fill_width(-1.), 0.);
+        assert_eq!(fill_width(f64::NAN), 0.);
+        assert_eq!(fill_width(f64::INFINITY), 200.);
+    }
+
+    #[test]
+    fn spinners_draw_a_ring_of_ticks() {
+        let mut tree = ViewTree::new();
+        let spinner = Spinner::new(bounds(0., 0., 100., 50.), FILL);
+        tree.render_root(Arc::new(spinner), ());
+        let layers = updated_layers(&mut tree);
+        assert_eq!(layers.len(), 13);
+        assert_eq!(layers[0].bounds, bounds(0., 0., 100., 50.));
+
+        let ticks = &layers[1..];
+        // the ring fits into the smaller dimension and is centered
+        let centers: Vec<_> = ticks.iter().map(|tick| tick.bounds.center()).collect();
+        let top = centers[0];
+        assert!(
+            (top.x - 50.).abs() < 1e-9 && (top.y - 6.25).abs() < 1e-9,
+            "{:?}",
+            top
+        );
+        let right = centers[3];
+        assert!((right.x - 68.75).abs() < 1e-9 && (right.y - 25.).abs() < 1e-9);
+        for tick in ticks {
+            assert_eq!(tick.bounds.size, Vector2::new(4., 12.5));
+            assert_eq!(tick.background, FILL);
+        }
+
+        // one tick is highlighted, and the others fade out behind it
+        let highlighted = ticks.iter().filter(|tick| tick.opacity == 1.).count();
+        assert_eq!(highlighted, 1);
+        assert!(ticks.iter().all(|tick| tick.opacity > 0.2 - 1e-9));
+    }
+
+    #[test]
+    fn spinners_animate_on_every_tick() {
+        let mut tree = ViewTree::new();
+        let spinner = Spinner {
+            tick_count: 0,
+            period: Duration::from_millis(1),
+            ..Spinner::new(bounds(0., 0., 20., 20.), FILL)
+        };
+        tree.render_root(Arc::new(spinner), ());
+        let views: Vec<_> = tree
+            .patches()
+            .filter_map(|patch| match patch {
+                Patch::Update(id, _) => Some(id),
+                _ => None,
+            })
+            .collect();
+        // at least one tick is drawn
+        assert_eq!(views.len(), 2);
+
+        // the structure stays the same, so views are only updated
+        assert!(tree.tick(Instant::now()));
+        for patch in tree.patches() {
+            match patch {
+                Patch::Update(id, _) => assert!(views.contains(&id)),
+                Patch::SubviewRegion(id, _, _, subviews) => {
+                    assert!(views.contains(&id));
+                    assert!(subviews.iter().all(|id| views.contains(id)));
+                }
+                _ => panic!("expected only updates"),
+            }
+        }
+
+        // the highlight goes around
+        let mut tree = ViewTree::new();
+        let spinner = Spinner {
+            tick_count: 4,
+            period: Duration::from_millis(20),
+            ..Spinner::new(bounds(0., 0., 20., 20.), FILL)
+        };
+        tree.render_root(Arc::new(spinner), ());
+        let head = |layers: Vec<LayerProps>| {
+            let mut ticks = layers.into_iter().filter(|layer| layer.background == FILL);
+            ticks
+                .find(|tick| tick.opacity == 1.)
+                .map(|tick| tick.bounds)
+        };
+        let first = head(updated_layers(&mut tree)).unwrap();
+        let mut moved = false;
+        for _ in 0..100 {
+            std::thread::sleep(Duration::from_millis(2));
+            tree.tick(Instant::now());
+            if matches!(head(updated_layers(&mut tree)), Some(head) if head != first) {
+                moved = true;
+                break;
+            }
+        }
+        assert!(moved);
+
+        // without animated views, ticks have nothing to do
+        tree.render_root(bar(0.5), ());
+        tree.patches().for_each(drop);
+        assert!(!tree.tick(Instant::now()));
+        assert_eq!(tree.patches().count(), 0);
+    }
+}
@@ -0,0 +1,849 @@
+//! A software renderer for display lists.
+//!
+//! [`SoftwareRenderer`] draws a [`DisplayList`] into an [`RgbaImage`] entirely on the CPU. It’s
+//! meant as a fallback for environments without GPU access, and since its output only depends on
+//! its input, as a target for pixel-exact snapshot tests.
+//!
+//! Edges are anti-aliased, colors are blended in sRGB like most compositors do, and images are
+//! sampled with nearest-neighbor filtering. Custom items are not drawn.
+//!
+//! ```no_run
+//! # use birb::raster::SoftwareRenderer;
+//! # use birb::NVTree;
+//! # use birb::backend::Backend;
+//! # use core::ops::DerefMut;
+//! # fn draw<B, Bk>(nv_tree: &NVTree<B, Bk::ViewRef>)
+//! # where B: DerefMut<Target = Bk>, Bk: Backend {
+//! let renderer = SoftwareRenderer::new();
+//! // at 2x scale
+//! let image = renderer.render(&nv_tree.display_list(), 640, 480, 2.);
+//! # }
+//! ```
+
+use crate::atlas::{AtlasRegion, CapInsets};
+use crate::color::Color;
+use crate::display_list::{DisplayItem, DisplayList, DisplayNode};
+use crate::image::RgbaImage;
+use crate::layer::BorderStyle;
+use crate::rect::{CornerRadius, Rect};
+use crate::shape::Shape;
+use cgmath::{Matrix3, Point2, SquareMatrix, Vector3};
+use core::fmt;
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_2;
+
+/// A color with premultiplied alpha.
+type Pixel = [f32; 4];
+
+/// A rectangle of pixels; the maximum is exclusive.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    x0: i64,
+    y0: i64,
+    x1: i64,
+    y1: i64,
+}
+
+impl Bounds {
+    /// Returns the pixels that a rectangle in pixel coordinates touches.
+    fn covering(rect: Rect) -> Bounds {
+        Bounds {
+            x0: rect.origin.x.floor() as i64,
+            y0: rect.origin.y.floor() as i64,
+            x1: (rect.origin.x + rect.size.x).ceil() as i64,
+            y1: (rect.origin.y + rect.size.y).ceil() as i64,
+        }
+    }
+
+    fn intersect(self, other: Bounds) -> Option<Bounds> {
+        let bounds = Bounds {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        };
+        if bounds.x0 < bounds.x1 && bounds.y0 < bounds.y1 {
+            Some(bounds)
+        } else {
+            None
+        }
+    }
+
+    fn width(&self) -> usize {
+        (self.x1 - self.x0) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.y1 - self.y0) as usize
+    }
+
+    /// Returns the index of a pixel in a buffer covering these bounds, row by row.
+    fn index(&self, x: i64, y: i64) -> usize {
+        (y - self.y0) as usize * self.width() + (x - self.x0) as usize
+    }
+}
+
+/// Pixels being drawn into.
+struct Canvas {
+    bounds: Bounds,
+    pixels: Vec<Pixel>,
+}
+
+impl Canvas {
+    fn new(bounds: Bounds) -> Canvas {
+        Canvas {
+            bounds,
+            pixels: vec![[0.; 4]; bounds.width() * bounds.height()],
+        }
+    }
+
+    /// Draws another canvas with the same bounds on top of this one.
+    fn composite(&mut self, layer: &Canvas, opacity: f32) {
+        for (dest, src) in self.pixels.iter_mut().zip(&layer.pixels) {
+            blend(dest, *src, opacity);
+        }
+    }
+}
+
+/// Coverage of a clip for each pixel of a canvas.
+type Mask = Vec<f32>;
+
+/// Draws `src` over `dest` with the given coverage.
+fn blend(dest: &mut Pixel, src: Pixel, coverage: f32) {
+    let inverse = 1. - src[3] * coverage;
+    for i in 0..4 {
+        dest[i] = src[i] * coverage + dest[i] * inverse;
+    }
+}
+
+fn premultiply(color: Color) -> Pixel {
+    let a = color.a.clamp(0., 1.) as f32;
+    [
+        color.r as f32 * a,
+        color.g as f32 * a,
+        color.b as f32 * a,
+        a,
+    ]
+}
+
+/// Returns how much of a pixel is covered by a shape, given the signed distance in pixels from
+/// its center to the shape’s edge.
+fn coverage(distance: f64) -> f32 {
+    (0.5 - distance).clamp(0., 1.) as f32
+}
+
+/// Returns how far along the edge of a rectangle with rounded corners the point on it closest to
+/// a point is, running clockwise from the end of the top left corner.
+///
+/// Corners are treated as circular, which is close enough for laying out dashes.
+fn perimeter_position(point: Point2<f64>, rect: Rect, radius: CornerRadius) -> f64 {
+    let (x0, y0, x1, y1) = (rect.origin.x, rect.origin.y, rect.max_x(), rect.max_y());
+    let (tl, tr, br, bl) = (
+        radius.top_left,
+        radius.top_right,
+        radius.bottom_right,
+        radius.bottom_left,
+    );
+    let arc = |radius: f64| radius * FRAC_PI_2;
+    let top = rect.size.x - tl - tr;
+    let right = rect.size.y - tr - br;
+    let bottom = rect.size.x - br - bl;
+
+    // position along a corner, given the angle from where it starts
+    let corner = |start: f64, radius: f64, angle: f64| start + angle.clamp(0., FRAC_PI_2) * radius;
+    if point.x > x1 - tr && point.y < y0 + tr {
+        let (dx, dy) = (point.x - (x1 - tr), point.y - (y0 + tr));
+        return corner(top, tr, dx.atan2(-dy));
+    }
+    let start = top + arc(tr);
+    if point.x > x1 - br && point.y > y1 - br {
+        let (dx, dy) = (point.x - (x1 - br), point.y - (y1 - br));
+        return corner(start + right, br, dy.atan2(dx));
+    }
+    let start = start + right + arc(br);
+    if point.x < x0 + bl && point.y > y1 - bl {
+        let (dx, dy) = (point.x - (x0 + bl), point.y - (y1 - bl));
+        return corner(start + bottom, bl, (-dx).atan2(dy));
+    }
+    let start = start + bottom + arc(bl);
+    let left = rect.size.y - bl - tl;
+    if point.x < x0 + tl && point.y < y0 + tl {
+        let (dx, dy) = (point.x - (x0 + tl), point.y - (y0 + tl));
+        return corner(start + left, tl, (-dy).atan2(-dx));
+    }
+
+    // otherwise, the point is closest to one of the straight edges
+    let distances = [point.y - y0, x1 - point.x, y1 - point.y, point.x - x0];
+    let closest = (0..4)
+        .min_by(|a, b| distances[*a].abs().total_cmp(&distances[*b].abs()))
+        .unwrap();
+    match closest {
+        0 => (point.x - x0 - tl).clamp(0., top),
+        1 => top + arc(tr) + (point.y - y0 - tr).clamp(0., right),
+        2 => top + arc(tr) + right + arc(br) + (x1 - br - point.x).clamp(0., bottom),
+        _ => start + (y1 - bl - point.y).clamp(0., left),
+    }
+}
+
+/// Returns the signed distance from a position along a line to the nearest dash of a pattern
+/// that starts with a dash at zero; negative inside.
+fn dash_distance(position: f64, dash: f64, gap: f64) -> f64 {
+    if dash <= 0. {
+        return f64::INFINITY;
+    } else if gap <= 0. {
+        return f64::NEG_INFINITY;
+    }
+    let period = dash + gap;
+    let phase = position.rem_euclid(period);
+    if phase < dash {
+        -phase.min(dash - phase)
+    } else {
+        (phase - dash).min(period - phase)
+    }
+}
+
+/// Maps a position along one axis of a nine-slice image to a pixel of the image.
+fn nine_slice(pos: f64, size: f64, image_size: u32, start_cap: u32, end_cap: u32) -> u32 {
+    let image_size_f = image_size as f64;
+    let (start, end) = (start_cap as f64, end_cap as f64);
+    // caps shrink if they don’t fit
+    let caps = start + end;
+    let shrink = if caps > size && caps > 0. {
+        size / caps
+    } else {
+        1.
+    };
+    let (dest_start, dest_end) = (start * shrink, end * shrink);
+
+    let image_pos = if pos < dest_start {
+        pos / shrink
+    } else if pos >= size - dest_end {
+        image_size_f - (size - pos) / shrink
+    } else {
+        let center = size - dest_start - dest_end;
+        start + (pos - dest_start) / center * (image_size_f - start - end)
+    };
+    (image_pos.max(0.) as u32).min(image_size.saturating_sub(1))
+}
+
+/// Draws display lists on the CPU; see the [module documentation](self).
+pub struct SoftwareRenderer {
+    pages: HashMap<usize, RgbaImage>,
+}
+
+impl SoftwareRenderer {
+    pub fn new() -> SoftwareRenderer {
+        SoftwareRenderer {
+            pages: HashMap::new(),
+        }
+    }
+
+    /// Creates or replaces an [atlas](crate::atlas) page, whose regions may be drawn by image
+    /// items.
+    ///
+    /// Image items referring to pages that don’t exist are not drawn.
+    pub fn set_atlas_page(&mut self, page: usize, image: RgbaImage) {
+        self.pages.insert(page, image);
+    }
+
+    /// Removes an atlas page.
+    pub fn remove_atlas_page(&mut self, page: usize) {
+        self.pages.remove(&page);
+    }
+
+    /// Draws a display list into a new image of the given size in pixels, with `scale` pixels per
+    /// point.
+    pub fn render(&self, list: &DisplayList, width: u32, height: u32, scale: f64) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        self.render_into(list, &mut image, scale, None);
+        image
+    }
+
+    /// Draws a display list into an existing image, with `scale` pixels per point.
+    ///
+    /// If `region` (in window coordinates) is given, only pixels touching it are redrawn, e.g. to
+    /// redraw only the areas reported by a [`DamageTracker`](crate::damage::DamageTracker);
+    /// everything else is left as it is. Redrawn pixels are cleared first.
+    pub fn render_into(
+        &self,
+        list: &DisplayList,
+        target: &mut RgbaImage,
+        scale: f64,
+        region: Option<Rect>,
+    ) {
+        let image_bounds = Bounds {
+            x0: 0,
+            y0: 0,
+            x1: target.width() as i64,
+            y1: target.height() as i64,
+        };
+        let bounds = match region {
+            Some(region) => {
+                let region = Rect::new(
+                    Point2::new(region.origin.x * scale, region.origin.y * scale),
+                    region.size * scale,
+                );
+                Bounds::covering(region).intersect(image_bounds)
+            }
+            None => image_bounds.intersect(image_bounds),
+        };
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        let mut canvas = Canvas::new(bounds);
+        if let Some(root) = &list.root {
+            let to_pixels = Matrix3::new(scale, 0., 0., 0., scale, 0., 0., 0., 1.);
+            self.draw_node(root, to_pixels, &mut canvas, None);
+        }
+
+        let width = target.width() as usize;
+        let data = target.data_mut();
+        for y in bounds.y0..bounds.y1 {
+            for x in bounds.x0..bounds.x1 {
+                let pixel = canvas.pixels[bounds.index(x, y)];
+                let offset = (y as usize * width + x as usize) * 4;
+                let alpha = pixel[3];
+                for i in 0..4 {
+                    let value = if i == 3 {
+                        alpha
+                    } else if alpha > 0. {
+                        pixel[i] / alpha
+                    } else {
+                        0.
+                    };
+                    data[offset + i] = (value.clamp(0., 1.) * 255. + 0.5) as u8;
+                }
+            }
+        }
+    }
+
+    fn draw_node(
+        &self,
+        node: &DisplayNode,
+        superview_to_pixels: Matrix3<f64>,
+        canvas: &mut Canvas,
+        mask: Option<&Mask>,
+    ) {
+        if node.opacity <= 0. {
+            return;
+        }
+        let to_pixels = superview_to_pixels * node.transform;
+        if node.opacity < 1. && node.group_opacity {
+            // the node and its children fade as a group
+            let mut layer = Canvas::new(canvas.bounds);
+            self.draw_contents(node, to_pixels, &mut layer, mask);
+            canvas.composite(&layer, node.opacity as f32);
+        } else if node.opacity < 1. {
+            // everything fades separately, like with a uniformly translucent clip
+            let opacity = node.opacity as f32;
+            let faded: Mask = match mask {
+                Some(mask) => mask.iter().map(|coverage| coverage * opacity).collect(),
+                None => vec![opacity; canvas.bounds.width() * canvas.bounds.height()],
+            };
+            self.draw_contents(node, to_pixels, canvas, Some(&faded));
+        } else {
+            self.draw_contents(node, to_pixels, canvas, mask);
+        }
+    }
+
+    fn draw_contents(
+        &self,
+        node: &DisplayNode,
+        to_pixels: Matrix3<f64>,
+        canvas: &mut Canvas,
+        mask: Option<&Mask>,
+    ) {
+        for item in &node.items {
+            self.draw_item(item, to_pixels, canvas, mask);
+        }
+        if node.children.is_empty() {
+            return;
+        }
+
+        let clipped: Mask;
+        let mask = match &node.clip {
+            Some(clip) => {
+                clipped = clip_mask(clip, to_pixels, canvas.bounds, mask);
+                Some(&clipped)
+            }
+            None => mask,
+        };
+        for child in &node.children {
+            self.draw_node(child, to_pixels, canvas, mask);
+        }
+    }
+
+    fn draw_item(
+        &self,
+        item: &DisplayItem,
+        to_pixels: Matrix3<f64>,
+        canvas: &mut Canvas,
+        mask: Option<&Mask>,
+    ) {
+        match item {
+            DisplayItem::RoundedRect {
+                rect,
+                corner_radius,
+                color,
+            } => {
+                let color = premultiply(*color);
+                paint(canvas, mask, to_pixels, *rect, |point, scale| {
+                    let distance = rect.rounded_distance(point, *corner_radius);
+                    (coverage(distance * scale), color)
+                });
+            }
+            DisplayItem::Gradient {
+                rect,
+                corner_radius,
+                gradient,
+            } => {
+                paint(canvas, mask, to_pixels, *rect, |point, scale| {
+                    let distance = rect.rounded_distance(point, *corner_radius);
+                    let color = premultiply(gradient.color_at_point(*rect, point));
+                    (coverage(distance * scale), color)
+                });
+            }
+            DisplayItem::Border {
+                rect,
+                corner_radius,
+                width,
+                color,
+                style,
+            } => {
+                let color = premultiply(*color);
+                let inner = rect.inset(*width, *width);
+                let inner_radius = corner_radius.inset(*width);
+                // dashes are laid out along the middle of the border
+                let middle = rect.inset(width / 2., width / 2.);
+                let middle_radius = corner_radius.inset(width / 2.).clamped(middle.size);
+                paint(canvas, mask, to_pixels, *rect, |point, scale| {
+                    let outer = coverage(rect.rounded_distance(point, *corner_radius) * scale);
+                    let inner = if inner.size.x > 0. && inner.size.y > 0. {
+                        coverage(inner.rounded_distance(point, inner_radius) * scale)
+                    } else {
+                        0.
+                    };
+                    let dash = match *style {
+                        BorderStyle::Solid => 1.,
+                        BorderStyle::Dashed { dash, gap } => {
+                            let position = perimeter_position(point, middle, middle_radius);
+                            coverage(dash_distance(position, dash, gap) * scale)
+                        }
+                    };
+                    ((outer - inner) * dash, color)
+                });
+            }
+            DisplayItem::Image {
+                rect,
+                region,
+                insets,
+                bounds,
+                tiled,
+            } => {
+                let page = match self.pages.get(&region.page) {
+                    Some(page) => page,
+                    None => return,
+                };
+                let region = match clamp_region(*region, page) {
+                    Some(region) => region,
+                    None => return,
+                };
+                let insets: CapInsets = insets.clamped(region.width, region.height);
+                let area = match (tiled, rect.intersect(*bounds)) {
+                    (true, _) => *bounds,
+                    (false, Some(area)) => area,
+                    (false, None) => return,
+                };
+                paint(canvas, mask, to_pixels, area, |point, scale| {
+                    let distance = area.rounded_distance(point, CornerRadius::default());
+                    let mut offset = point - rect.origin;
+                    if *tiled {
+                        offset.x = offset.x.rem_euclid(rect.size.x);
+                        offset.y = offset.y.rem_euclid(rect.size.y);
+                    }
+                    let u = nine_slice(
+                        offset.x,
+                        rect.size.x,
+                        region.width,
+                        insets.left,
+                        insets.right,
+                    );
+                    let v = nine_slice(
+                        offset.y,
+                        rect.size.y,
+                        region.height,
+                        insets.top,
+                        insets.bottom,
+                    );
+                    let [r, g, b, a] = page.pixel(region.x + u, region.y + v);
+                    let color = Color {
+                        r: r as f64 / 255.,
+                        g: g as f64 / 255.,
+                        b: b as f64 / 255.,
+                        a: a as f64 / 255.,
+                    };
+                    (coverage(distance * scale), premultiply(color))
+                });
+            }
+            DisplayItem::Custom { .. } => (),
+        }
+    }
+}
+
+/// Returns the part of a region that is inside its page, or None if it’s empty.
+fn clamp_region(region: AtlasRegion, page: &RgbaImage) -> Option<AtlasRegion> {
+    let width = region.width.min(page.width().saturating_sub(region.x));
+    let height = region.height.min(page.height().saturating_sub(region.y));
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some(AtlasRegion {
+        width,
+        height,
+        ..region
+    })
+}
+
+/// Returns the pixels touched by a rectangle in the coordinate system of a node, including a
+/// pixel of margin for anti-aliasing.
+fn pixel_bounds(to_pixels: Matrix3<f64>, rect: Rect) -> Bounds {
+    let corners = [
+        Vector3::new(rect.origin.x, rect.origin.y, 1.),
+        Vector3::new(rect.origin.x + rect.size.x, rect.origin.y, 1.),
+        Vector3::new(rect.origin.x, rect.origin.y + rect.size.y, 1.),
+        Vector3::new(rect.origin.x + rect.size.x, rect.origin.y + rect.size.y, 1.),
+    ];
+    let mut min = Point2::new(f64::INFINITY, f64::INFINITY);
+    let mut max = Point2::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for corner in &corners {
+        let corner = to_pixels * corner;
+        min.x = min.x.min(corner.x);
+        min.y = min.y.min(corner.y);
+        max.x = max.x.max(corner.x);
+        max.y = max.y.max(corner.y);
+    }
+    Bounds::covering(Rect::new(min, max - min).inset(-1., -1.))
+}
+
+/// Returns the number of pixels per unit in the coordinate system of a node, averaged over both
+/// axes, for converting distances to pixels.
+fn pixel_scale(to_pixels: Matrix3<f64>) -> f64 {
+    (to_pixels.x.x * to_pixels.y.y - to_pixels.x.y * to_pixels.y.x)
+        .abs()
+        .sqrt()
+}
+
+/// Calls `shader` for each pixel that a rectangle in the coordinate system of a node may cover,
+/// with the position of the pixel center in that coordinate system and the number of pixels per
+/// unit, and draws the returned color with the returned coverage.
+fn paint<F>(
+    canvas: &mut Canvas,
+    mask: Option<&Mask>,
+    to_pixels: Matrix3<f64>,
+    rect: Rect,
+    shader: F,
+) where
+    F: Fn(Point2<f64>, f64) -> (f32, Pixel),
+{
+    let from_pixels = match to_pixels.invert() {
+        Some(from_pixels) => from_pixels,
+        // collapsed by a transform
+        None => return,
+    };
+    let bounds = match pixel_bounds(to_pixels, rect).intersect(canvas.bounds) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let scale = pixel_scale(to_pixels);
+
+    for y in bounds.y0..bounds.y1 {
+        for x in bounds.x0..bounds.x1 {
+            let index = canvas.bounds.index(x, y);
+            let mask_coverage = mask.map_or(1., |mask| mask[index]);
+            if mask_coverage <= 0. {
+                continue;
+            }
+            let point = from_pixels * Vector3::new(x as f64 + 0.5, y as f64 + 0.5, 1.);
+            let (coverage, color) = shader(Point2::new(point.x, point.y), scale);
+            if coverage > 0. {
+                blend(&mut canvas.pixels[index], color, coverage * mask_coverage);
+            }
+        }
+    }
+}
+
+/// Returns the mask of a clip inside another clip.
+fn clip_mask(clip: &Shape, to_pixels: Matrix3<f64>, bounds: Bounds, parent: Option<&Mask>) -> Mask {
+    let mut mask = vec![0.; bounds.width() * bounds.height()];
+    let from_pixels = match to_pixels.invert() {
+        Some(from_pixels) => from_pixels,
+        None => return mask,
+    };
+    let clip_bounds = match pixel_bounds(to_pixels, clip.bounding_box()).intersect(bounds) {
+        Some(clip_bounds) => clip_bounds,
+        None => return mask,
+    };
+    let scale = pixel_scale(to_pixels);
+
+    for y in clip_bounds.y0..clip_bounds.y1 {
+        for x in clip_bounds.x0..clip_bounds.x1 {
+            let index = bounds.index(x, y);
+            let point = from_pixels * Vector3::new(x as f64 + 0.5, y as f64 + 0.5, 1.);
+            let distance = clip.distance(Point2::new(point.x, point.y));
+            mask[index] = coverage(distance * scale) * parent.map_or(1., |parent| parent[index]);
+        }
+    }
+    mask
+}
+
+impl Default for SoftwareRenderer {
+    fn default() -> SoftwareRenderer {
+        SoftwareRenderer::new()
+    }
+}
+
+impl fmt::Debug for SoftwareRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pages: Vec<_> = self.pages.keys().collect();
+        pages.sort();
+        f.debug_struct("SoftwareRenderer")
+            .field("atlas_pages", &pages)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::ViewId;
+    use cgmath::Vector2;
+
+    fn node(items: Vec<DisplayItem>, children: Vec<DisplayNode>) -> DisplayNode {
+        DisplayNode {
+            view: ViewId::new(),
+            transform: Matrix3::identity(),
+            opacity: 1.,
+            group_opacity: false,
+            clip: None,
+            should_rasterize: false,
+            items,
+            children,
+        }
+    }
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    fn fill(rect: Rect, color: Color) -> DisplayItem {
+        DisplayItem::RoundedRect {
+            rect,
+            corner_radius: CornerRadius::default(),
+            color,
+        }
+    }
+
+    fn render(root: DisplayNode, width: u32, height: u32) -> RgbaImage {
+        let list = DisplayList { root: Some(root) };
+        SoftwareRenderer::new().render(&list, width, height, 1.)
+    }
+
+    #[test]
+    fn coverage_at_edges() {
+        assert_eq!(coverage(-1.), 1.);
+        assert_eq!(coverage(-0.5), 1.);
+        assert_eq!(coverage(0.), 0.5);
+        assert_eq!(coverage(0.25), 0.25);
+        assert_eq!(coverage(0.5), 0.);
+        assert_eq!(coverage(2.), 0.);
+    }
+
+    #[test]
+    fn anti_aliased_edges() {
+        let red = Color::rgb(1., 0., 0.);
+        let image = render(node(vec![fill(rect(1.5, 0., 3., 4.), red)], vec![]), 6, 4);
+        assert_eq!(image.pixel(0, 2), [0, 0, 0, 0]);
+        // pixels on the edges are half covered, but keep their color
+        assert_eq!(image.pixel(1, 2), [255, 0, 0, 128]);
+        assert_eq!(image.pixel(2, 2), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(3, 2), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(4, 2), [255, 0, 0, 128]);
+        assert_eq!(image.pixel(5, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rounded_corners() {
+        let item = DisplayItem::RoundedRect {
+            rect: rect(0., 0., 10., 10.),
+            corner_radius: CornerRadius::uniform(4.),
+            color: Color::rgb(0., 0., 1.),
+        };
+        let image = render(node(vec![item], vec![]), 10, 10);
+        for (x, y) in [(0, 0), (9, 0), (9, 9), (0, 9)] {
+            assert_eq!(image.pixel(x, y)[3], 0, "corner at {x}, {y}");
+        }
+        // partially covered along the curve
+        let curve = image.pixel(1, 1)[3];
+        assert!(curve > 0 && curve < 255);
+        for (x, y) in [(5, 0), (9, 5), (5, 9), (0, 5), (5, 5)] {
+            assert_eq!(image.pixel(x, y), [0, 0, 255, 255], "edge at {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn dash_distances() {
+        assert_eq!(dash_distance(2., 4., 2.), -2.);
+        assert_eq!(dash_distance(4., 4., 2.), 0.);
+        assert_eq!(dash_distance(5., 4., 2.), 1.);
+        assert_eq!(dash_distance(7., 4., 2.), -1.);
+        assert_eq!(dash_distance(-1., 4., 2.), 1.);
+        assert_eq!(dash_distance(1., 0., 2.), f64::INFINITY);
+        assert_eq!(dash_distance(1., 4., 0.), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn borders() {
+        let border = |style| DisplayItem::Border {
+            rect: rect(0., 0., 20., 10.),
+            corner_radius: CornerRadius::default(),
+            width: 2.,
+            color: Color::rgb(0., 1., 0.),
+            style,
+        };
+
+        let solid = render(node(vec![border(BorderStyle::Solid)], vec![]), 20, 10);
+        assert_eq!(solid.pixel(2, 0), [0, 255, 0, 255]);
+        assert_eq!(solid.pixel(6, 1), [0, 255, 0, 255]);
+        assert_eq!(solid.pixel(0, 5), [0, 255, 0, 255]);
+        // the inside is empty
+        assert_eq!(solid.pixel(2, 2), [0, 0, 0, 0]);
+        assert_eq!(solid.pixel(10, 5), [0, 0, 0, 0]);
+
+        // dashes start where the middle of the border starts, at x = 1
+        let style = BorderStyle::Dashed { dash: 4., gap: 4. };
+        let dashed = render(node(vec![border(style)], vec![]), 20, 10);
+        assert_eq!(dashed.pixel(2, 0), [0, 255, 0, 255]);
+        assert_eq!(dashed.pixel(3, 1), [0, 255, 0, 255]);
+        assert_eq!(dashed.pixel(6, 0), [0, 0, 0, 0]);
+        assert_eq!(dashed.pixel(7, 1), [0, 0, 0, 0]);
+        assert_eq!(dashed.pixel(10, 0), [0, 255, 0, 255]);
+        assert_eq!(dashed.pixel(10, 5), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn nine_slice_positions() {
+        // caps keep their size
+        assert_eq!(nine_slice(1.5, 30., 10, 2, 2), 1);
+        assert_eq!(nine_slice(29.5, 30., 10, 2, 2), 9);
+        assert_eq!(nine_slice(28.5, 30., 10, 2, 2), 8);
+        // the center stretches
+        assert_eq!(nine_slice(2.5, 30., 10, 2, 2), 2);
+        assert_eq!(nine_slice(15., 30., 10, 2, 2), 5);
+        assert_eq!(nine_slice(27.5, 30., 10, 2, 2), 7);
+        // caps that don’t fit shrink
+        assert_eq!(nine_slice(0.5, 2., 10, 2, 2), 1);
+        assert_eq!(nine_slice(1.5, 2., 10, 2, 2), 9);
+        // and positions never leave the image
+        assert_eq!(nine_slice(-1., 30., 10, 2, 2), 0);
+        assert_eq!(nine_slice(31., 30., 10, 2, 2), 9);
+    }
+
+    #[test]
+    fn nine_slice_images() {
+        // red corners, green edges and a blue center
+        let (r, g, b) = ([255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]);
+        let data = [r, g, r, g, b, g, r, g, r].concat();
+        let mut renderer = SoftwareRenderer::new();
+        renderer.set_atlas_page(0, RgbaImage::from_raw(3, 3, data).unwrap());
+
+        let item = DisplayItem::Image {
+            rect: rect(0., 0., 9., 6.),
+            region: AtlasRegion {
+                page: 0,
+                x: 0,
+                y: 0,
+                width: 3,
+                height: 3,
+            },
+            insets: CapInsets::uniform(1),
+            bounds: rect(0., 0., 9., 6.),
+            tiled: false,
+        };
+        let list = DisplayList {
+            root: Some(node(vec![item], vec![])),
+        };
+        let image = renderer.render(&list, 9, 6, 1.);
+        for (x, y) in [(0, 0), (8, 0), (8, 5), (0, 5)] {
+            assert_eq!(image.pixel(x, y), r, "corner at {x}, {y}");
+        }
+        for (x, y) in [(1, 0), (4, 0), (7, 0), (8, 1), (8, 4), (4, 5), (0, 2)] {
+            assert_eq!(image.pixel(x, y), g, "edge at {x}, {y}");
+        }
+        for (x, y) in [(1, 1), (4, 2), (7, 4)] {
+            assert_eq!(image.pixel(x, y), b, "center at {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn clips() {
+        let blue = fill(rect(0., 0., 10., 10.), Color::rgb(0., 0., 1.));
+        let red = fill(rect(0., 0., 10., 10.), Color::rgb(1., 0., 0.));
+        let mut root = node(vec![blue], vec![node(vec![red], vec![])]);
+        root.clip = Some(Shape::RoundedRect {
+            rect: rect(0., 0., 5.5, 10.),
+            corner_radius: CornerRadius::default(),
+        });
+        let image = render(root, 10, 10);
+        // children are clipped, with anti-aliased edges
+        assert_eq!(image.pixel(2, 5), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(5, 5), [128, 0, 128, 255]);
+        // but the node’s own items aren’t
+        assert_eq!(image.pixel(7, 5), [0, 0, 255, 255]);
+
+        // nested clips intersect
+        let red = fill(rect(0., 0., 10., 10.), Color::rgb(1., 0., 0.));
+        let mut inner = node(vec![], vec![node(vec![red], vec![])]);
+        inner.clip = Some(Shape::RoundedRect {
+            rect: rect(0., 3., 10., 7.),
+            corner_radius: CornerRadius::default(),
+        });
+        let mut outer = node(vec![], vec![inner]);
+        outer.clip = Some(Shape::RoundedRect {
+            rect: rect(0., 0., 5., 10.),
+            corner_radius: CornerRadius::default(),
+        });
+        let image = render(outer, 10, 10);
+        assert_eq!(image.pixel(2, 5), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(2, 1), [0, 0, 0, 0]);
+        assert_eq!(image.pixel(7, 5), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn group_opacity() {
+        let overlapping = |group_opacity| {
+            let green = fill(rect(0., 0., 6., 4.), Color::rgb(0., 1., 0.));
+            let red = fill(rect(4., 0., 6., 4.), Color::rgb(1., 0., 0.));
+            let mut root = node(
+                vec![],
+                vec![node(vec![green], vec![]), node(vec![red], vec![])],
+            );
+            root.opacity = 0.5;
+            root.group_opacity = group_opacity;
+            render(root, 10, 4)
+        };
+
+        // as a group, only the topmost child shows where they overlap
+        let group = overlapping(true);
+        assert_eq!(group.pixel(2, 2), [0, 255, 0, 128]);
+        assert_eq!(group.pixel(5, 2), [255, 0, 0, 128]);
+        assert_eq!(group.pixel(8, 2), [255, 0, 0, 128]);
+
+        // separately, the bottom child shows through the top one
+        let separate = overlapping(false);
+        assert_eq!(separate.pixel(2, 2), [0, 255, 0, 128]);
+        assert_eq!(separate.pixel(5, 2), [170, 85, 0, 191]);
+        assert_eq!(separate.pixel(8, 2), [255, 0, 0, 128]);
+    }
+}
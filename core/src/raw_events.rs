@@ -1,6 +1,10 @@
 //! Raw events for backends.
 
-use crate::events::{KeyCode, KeyModifiers, PointerDevice};
+use crate::accessibility::AccessibilitySettings;
+use crate::events::{
+    ControllerAxis, ControllerButton, KeyCode, KeyModifiers, MediaKey, PointerDevice,
+};
+use std::path::PathBuf;
 
 /// Type for event IDs.
 pub type EventId = usize;
@@ -8,6 +12,9 @@ pub type EventId = usize;
 /// Type for unique pointer IDs.
 pub type PointerId = u128;
 
+/// Type for game controller IDs.
+pub type ControllerId = u64;
+
 /// Hover event phases.
 ///
 /// This enum has an ordering: Entered < Moved = Stationary < Left, and events are guaranteed
@@ -55,6 +62,23 @@ pub enum PointerEventPhase {
     Canceled = 4,
 }
 
+/// File drag event phases.
+///
+/// This enum has an ordering: Entered < Moved < Exited = Dropped, and events are guaranteed to be
+/// generated in this order for a given drag.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DragEventPhase {
+    /// The drag has entered the window (or, for views, the view).
+    Entered = 0,
+    /// The drag has moved.
+    Moved = 1,
+    /// The drag has left the window (or the view), or was canceled.
+    Exited = 2,
+    /// The files were dropped.
+    Dropped = 3,
+}
+
 /// Keyboard event phases.
 ///
 /// This enum has an ordering: Down < Repeat < Up, and events are guaranteed to be generated in this
@@ -119,6 +143,11 @@ pub enum RawEvent {
         phase: PointerEventPhase,
         /// The modifier keys that are currently being pressed.
         modifiers: KeyModifiers,
+        /// The number of consecutive clicks (e.g. 2 for a double click) as counted by the
+        /// platform, which should use the system double-click interval; or zero if the backend
+        /// doesn’t count clicks, in which case they will be counted by the
+        /// [`EventDispatcher`](crate::dispatch::EventDispatcher).
+        click_count: u32,
     },
     Key {
         /// The characters that are being input.
@@ -135,14 +164,110 @@ pub enum RawEvent {
     Scroll {
         /// The location in the root view.
         root_location: (f64, f64),
-        /// The scroll delta.
+        /// The scroll delta as reported by the device: in lines for discrete devices, and in
+        /// points for continuous ones. The dispatcher adjusts it according to its
+        /// [`ScrollSettings`](crate::dispatch::ScrollSettings).
         delta: (f64, f64),
         /// Whether the device scrolls in discrete increments (like most mouse wheels) or
         /// continuously (like a trackpad).
         is_discrete: bool,
+        /// Whether the platform already inverted the delta for natural scrolling (so that content
+        /// moves with the fingers), in which case the dispatcher won’t invert it again.
+        is_direction_inverted: bool,
+        /// The modifier keys that are currently being pressed.
+        modifiers: KeyModifiers,
+    },
+    /// Files are being dragged onto the window, e.g. from the file manager.
+    ///
+    /// Backends aren’t required to support file drags.
+    FileDrag {
+        /// The location in the root view.
+        root_location: (f64, f64),
+        /// The phase of this drag event.
+        phase: DragEventPhase,
+        /// Paths of the dragged files.
+        paths: Vec<PathBuf>,
+    },
+    /// A media key event.
+    ///
+    /// Backends aren’t required to support media keys.
+    MediaKey {
+        /// The media key.
+        key: MediaKey,
+        /// The phase of this key event.
+        phase: KeyEventPhase,
+    },
+    /// A game controller was connected or disconnected.
+    ///
+    /// Backends aren’t required to support game controllers.
+    ControllerConnection {
+        /// An ID that identifies the controller while it’s connected.
+        controller: ControllerId,
+        /// Whether the controller was connected or disconnected.
+        is_connected: bool,
+    },
+    /// A game controller button changed.
+    ControllerButton {
+        /// The controller ID.
+        controller: ControllerId,
+        /// The button.
+        button: ControllerButton,
+        /// How far the button is pressed, between 0 and 1.
+        value: f64,
+        /// Whether the button counts as pressed.
+        is_pressed: bool,
+    },
+    /// A game controller thumbstick axis changed.
+    ControllerAxis {
+        /// The controller ID.
+        controller: ControllerId,
+        /// The axis.
+        axis: ControllerAxis,
+        /// The axis value, between -1 and 1.
+        value: f64,
     },
     SetRootSize {
         /// The new size of the root view.
         size: (f64, f64),
     },
+    /// The system accessibility settings changed. Should be passed on to
+    /// [`ViewTree::set_accessibility`].
+    ///
+    /// [`ViewTree::set_accessibility`]: crate::ViewTree::set_accessibility
+    SetAccessibility {
+        /// The new system accessibility settings.
+        settings: AccessibilitySettings,
+    },
+    /// The window was hidden (i.e. fully occluded by other windows or minimized) or became
+    /// visible again.
+    ///
+    /// While the window is hidden, apps should save work: [`ViewTree::set_visible`] suspends
+    /// ticks, and the event loop may poll less often and skip re-renders that only refresh
+    /// what’s on screen (such as clocks). Backends aren’t required to report visibility.
+    ///
+    /// [`ViewTree::set_visible`]: crate::ViewTree::set_visible
+    SetVisibility {
+        /// Whether any part of the window is visible.
+        is_visible: bool,
+    },
+    /// A pen or eraser entered or left the proximity of a tablet, e.g. because the user flipped
+    /// the pen over to use the eraser.
+    ///
+    /// Backends aren’t required to report proximity.
+    Proximity {
+        /// The kind of device.
+        device: PointerDevice,
+        /// The unique ID of the device, as in pointer events; may be zero.
+        unique_id: PointerId,
+        /// Whether the device entered proximity (or left it).
+        is_entering: bool,
+    },
+    /// The window moved to a screen with a different scale factor, or the screen’s scale factor
+    /// changed. Should be passed on to [`NVTree::set_scale_factor`].
+    ///
+    /// [`NVTree::set_scale_factor`]: crate::NVTree::set_scale_factor
+    SetScaleFactor {
+        /// Device pixels per point.
+        scale_factor: f64,
+    },
 }
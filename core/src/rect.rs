@@ -1,10 +1,14 @@
 //! Rectangles.
 
-use cgmath::{EuclideanSpace, Point2, Vector2, Zero};
+use cgmath::{EuclideanSpace, Matrix3, Point2, Vector2, Vector3, Zero};
 use std::{f64, ops};
 
+/// How far continuous corners extend along the edges, relative to their radius.
+const CONTINUOUS_CORNER_EXTENT: f64 = 1.528;
+
 /// A rectangle.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     /// Rectangle origin.
     pub origin: Point2<f64>,
@@ -40,6 +44,38 @@ impl Rect {
             && point.y < self.origin.y + self.size.y
     }
 
+    /// Returns true if the point is inside the rectangle with rounded corners.
+    pub fn contains_rounded(&self, point: Point2<f64>, corner_radius: CornerRadius) -> bool {
+        if !self.contains(point) {
+            return false;
+        }
+        corner_radius.is_zero() || self.rounded_distance(point, corner_radius) <= 0.
+    }
+
+    /// Returns the signed distance from a point to the edge of the rectangle with rounded
+    /// corners; negative inside. Approximate for [continuous](CornerStyle::Continuous) corners.
+    pub fn rounded_distance(&self, point: Point2<f64>, corner_radius: CornerRadius) -> f64 {
+        let half_size = self.size / 2.;
+        let offset = point - self.center();
+        let corners = corner_radius.clamped(self.size);
+        let radius = match (offset.x >= 0., offset.y >= 0.) {
+            (false, false) => corners.top_left,
+            (true, false) => corners.top_right,
+            (true, true) => corners.bottom_right,
+            (false, true) => corners.bottom_left,
+        };
+        // continuous corners are approximated by superellipses that start further along the edges
+        let (radius, exponent) = match corners.style {
+            CornerStyle::Circular => (radius, 2.),
+            CornerStyle::Continuous => (radius * CONTINUOUS_CORNER_EXTENT, 5.),
+        };
+        let radius = radius.max(0.).min(half_size.x).min(half_size.y);
+        let qx = offset.x.abs() - half_size.x + radius;
+        let qy = offset.y.abs() - half_size.y + radius;
+        let outside = (qx.max(0.).powf(exponent) + qy.max(0.).powf(exponent)).powf(1. / exponent);
+        outside + qx.max(qy).min(0.) - radius
+    }
+
     /// Returns true if the two rectangles intersect.
     pub fn intersects(&self, rect: Rect) -> bool {
         let own_opposite = self.origin + self.size;
@@ -76,6 +112,95 @@ impl Rect {
         }
     }
 
+    /// Returns a new rectangle outset by the specified amount; the opposite of
+    /// [`inset`](Rect::inset).
+    pub fn outset(&self, horiz: f64, vert: f64) -> Rect {
+        self.inset(-horiz, -vert)
+    }
+
+    /// Returns the same rectangle with a non-negative size, moving the origin to the top left
+    /// corner where the size was negative.
+    pub fn normalized(&self) -> Rect {
+        let min = Point2::new(
+            self.origin.x.min(self.max_x()),
+            self.origin.y.min(self.max_y()),
+        );
+        Rect::new(min, Vector2::new(self.size.x.abs(), self.size.y.abs()))
+    }
+
+    /// Returns the smallest rectangle containing both rectangles, which may have negative sizes.
+    pub fn union(&self, rect: Rect) -> Rect {
+        let (a, b) = (self.normalized(), rect.normalized());
+        let min = Point2::new(a.origin.x.min(b.origin.x), a.origin.y.min(b.origin.y));
+        let max = Point2::new(a.max_x().max(b.max_x()), a.max_y().max(b.max_y()));
+        Rect::new(min, max - min)
+    }
+
+    /// Returns true if the other rectangle is entirely inside this one; either may have negative
+    /// sizes.
+    pub fn contains_rect(&self, rect: Rect) -> bool {
+        let (outer, rect) = (self.normalized(), rect.normalized());
+        rect.origin.x >= outer.origin.x
+            && rect.origin.y >= outer.origin.y
+            && rect.max_x() <= outer.max_x()
+            && rect.max_y() <= outer.max_y()
+    }
+
+    /// Returns the x coordinate of the right edge.
+    pub fn max_x(&self) -> f64 {
+        self.origin.x + self.size.x
+    }
+
+    /// Returns the y coordinate of the bottom edge.
+    pub fn max_y(&self) -> f64 {
+        self.origin.y + self.size.y
+    }
+
+    /// Returns the top left corner, i.e. the origin.
+    pub fn top_left(&self) -> Point2<f64> {
+        self.origin
+    }
+
+    /// Returns the top right corner.
+    pub fn top_right(&self) -> Point2<f64> {
+        Point2::new(self.max_x(), self.origin.y)
+    }
+
+    /// Returns the bottom left corner.
+    pub fn bottom_left(&self) -> Point2<f64> {
+        Point2::new(self.origin.x, self.max_y())
+    }
+
+    /// Returns the bottom right corner.
+    pub fn bottom_right(&self) -> Point2<f64> {
+        Point2::new(self.max_x(), self.max_y())
+    }
+
+    /// Returns the rectangle with its edges rounded to device pixels at the given scale factor
+    /// (device pixels per point).
+    pub fn round_to_pixels(&self, scale_factor: f64) -> Rect {
+        let round = |value: f64| (value * scale_factor).round() / scale_factor;
+        let min = Point2::new(round(self.origin.x), round(self.origin.y));
+        let max = Point2::new(round(self.max_x()), round(self.max_y()));
+        Rect::new(min, max - min)
+    }
+
+    /// Returns the rectangle transformed by a 2D affine transform.
+    pub fn transformed(&self, transform: Matrix3<f64>) -> Quad {
+        let corner = |point: Point2<f64>| {
+            let point = transform * Vector3::new(point.x, point.y, 1.);
+            Point2::new(point.x, point.y)
+        };
+        Quad {
+            corners: [
+                corner(self.top_left()),
+                corner(self.top_right()),
+                corner(self.bottom_right()),
+                corner(self.bottom_left()),
+            ],
+        }
+    }
+
     /// Returns a new rectangle with the given origin.
     pub fn with_origin(&self, origin: Point2<f64>) -> Rect {
         Rect {
@@ -102,3 +227,319 @@ impl ops::Add<Point2<f64>> for Rect {
         }
     }
 }
+
+/// A quadrilateral, e.g. a transformed [`Rect`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quad {
+    /// Corners in order around the edge, starting with the corner that was the top left corner
+    /// of the rectangle.
+    pub corners: [Point2<f64>; 4],
+}
+
+impl Quad {
+    /// Returns the smallest axis-aligned rectangle containing the quadrilateral.
+    pub fn bounding_box(&self) -> Rect {
+        let mut min = Point2::new(f64::INFINITY, f64::INFINITY);
+        let mut max = Point2::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in &self.corners {
+            min.x = min.x.min(corner.x);
+            min.y = min.y.min(corner.y);
+            max.x = max.x.max(corner.x);
+            max.y = max.y.max(corner.y);
+        }
+        Rect::new(min, max - min)
+    }
+
+    /// Returns true if the point is inside the quadrilateral, which must be convex (as
+    /// rectangles transformed by affine transforms are).
+    pub fn contains(&self, point: Point2<f64>) -> bool {
+        let mut sign = 0.;
+        for i in 0..4 {
+            let a = self.corners[i];
+            let b = self.corners[(i + 1) % 4];
+            let cross = (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x);
+            if cross != 0. {
+                if sign * cross < 0. {
+                    return false;
+                }
+                sign = cross;
+            }
+        }
+        sign != 0.
+    }
+}
+
+/// The shape of rounded corners.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CornerStyle {
+    /// Corners are quarter circles.
+    #[default]
+    Circular,
+    /// Corners curve continuously into the edges, like a squircle; they start further along the
+    /// edges than circular corners of the same radius, but look about as round.
+    Continuous,
+}
+
+/// Radii of the corners of a rounded rectangle, and their shape.
+///
+/// Converts from a single radius for all corners, e.g. `layer.corner_radius = 8.0.into()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CornerRadius {
+    pub top_left: f64,
+    pub top_right: f64,
+    pub bottom_right: f64,
+    pub bottom_left: f64,
+    pub style: CornerStyle,
+}
+
+impl CornerRadius {
+    /// Creates circular corners that all have the same radius.
+    pub fn uniform(radius: f64) -> CornerRadius {
+        CornerRadius {
+            top_left: radius,
+            top_right: radius,
+            bottom_right: radius,
+            bottom_left: radius,
+            style: CornerStyle::Circular,
+        }
+    }
+
+    /// Returns the same radii with continuous corners.
+    pub fn continuous(self) -> CornerRadius {
+        CornerRadius {
+            style: CornerStyle::Continuous,
+            ..self
+        }
+    }
+
+    /// Returns true if no corner is rounded.
+    pub fn is_zero(&self) -> bool {
+        self.max() <= 0.
+    }
+
+    /// Returns true if all corners have the same radius.
+    pub fn is_uniform(&self) -> bool {
+        self.top_left == self.top_right
+            && self.top_left == self.bottom_right
+            && self.top_left == self.bottom_left
+    }
+
+    /// Returns the largest radius.
+    pub fn max(&self) -> f64 {
+        self.top_left
+            .max(self.top_right)
+            .max(self.bottom_right)
+            .max(self.bottom_left)
+    }
+
+    /// Returns the radii reduced by an amount, e.g. for the inner edge of a border, but not
+    /// below zero.
+    pub fn inset(&self, amount: f64) -> CornerRadius {
+        CornerRadius {
+            top_left: (self.top_left - amount).max(0.),
+            top_right: (self.top_right - amount).max(0.),
+            bottom_right: (self.bottom_right - amount).max(0.),
+            bottom_left: (self.bottom_left - amount).max(0.),
+            style: self.style,
+        }
+    }
+
+    /// Returns the radii increased by an amount, e.g. for the outer edge of a border outside a
+    /// rectangle. Corners that aren’t rounded stay sharp.
+    pub fn outset(&self, amount: f64) -> CornerRadius {
+        let outset = |radius: f64| if radius > 0. { radius + amount } else { 0. };
+        CornerRadius {
+            top_left: outset(self.top_left),
+            top_right: outset(self.top_right),
+            bottom_right: outset(self.bottom_right),
+            bottom_left: outset(self.bottom_left),
+            style: self.style,
+        }
+    }
+
+    /// Returns the radii scaled down proportionally so that the corners along each edge of a
+    /// rectangle of the given size don’t overlap.
+    pub fn clamped(&self, size: Vector2<f64>) -> CornerRadius {
+        let fit = |length: f64, a: f64, b: f64| {
+            if a + b > length {
+                length.max(0.) / (a + b)
+            } else {
+                1.
+            }
+        };
+        let factor = fit(size.x, self.top_left, self.top_right)
+            .min(fit(size.x, self.bottom_left, self.bottom_right))
+            .min(fit(size.y, self.top_left, self.bottom_left))
+            .min(fit(size.y, self.top_right, self.bottom_right));
+        CornerRadius {
+            top_left: self.top_left * factor,
+            top_right: self.top_right * factor,
+            bottom_right: self.bottom_right * factor,
+            bottom_left: self.bottom_left * factor,
+            style: self.style,
+        }
+    }
+}
+
+impl From<f64> for CornerRadius {
+    fn from(radius: f64) -> CornerRadius {
+        CornerRadius::uniform(radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::SquareMatrix;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), Vector2::new(width, height))
+    }
+
+    /// Returns true if a coordinate lies on a device pixel edge.
+    fn is_on_pixel_edge(value: f64, scale_factor: f64) -> bool {
+        let pixels = value * scale_factor;
+        (pixels - pixels.round()).abs() < 1e-9
+    }
+
+    #[test]
+    fn union_contains_both_rects() {
+        let a = rect(0., 0., 10., 10.);
+        let b = rect(5., -5., 10., 5.);
+        assert_eq!(a.union(b), rect(0., -5., 15., 15.));
+        assert_eq!(b.union(a), a.union(b));
+        assert_eq!(a.union(a), a);
+        assert!(a.union(b).contains_rect(a));
+        assert!(a.union(b).contains_rect(b));
+    }
+
+    #[test]
+    fn negative_sizes_are_normalized() {
+        let flipped = rect(10., 10., -4., -6.);
+        assert_eq!(flipped.normalized(), rect(6., 4., 4., 6.));
+        assert_eq!(flipped.normalized().normalized(), flipped.normalized());
+        assert_eq!(flipped.union(rect(0., 0., 2., 2.)), rect(0., 0., 10., 10.));
+        assert!(rect(0., 0., 10., 10.).contains_rect(flipped));
+        assert!(rect(10., 10., -10., -10.).contains_rect(rect(1., 1., 2., 2.)));
+        assert!(!rect(10., 10., -4., -4.).contains_rect(rect(1., 1., 2., 2.)));
+    }
+
+    #[test]
+    fn contains_rect_includes_edges() {
+        let outer = rect(0., 0., 10., 10.);
+        assert!(outer.contains_rect(outer));
+        assert!(outer.contains_rect(rect(2., 2., 3., 3.)));
+        assert!(outer.contains_rect(rect(10., 10., 0., 0.)));
+        assert!(!outer.contains_rect(rect(8., 8., 3., 3.)));
+        assert!(!outer.contains_rect(rect(-1., 2., 3., 3.)));
+    }
+
+    #[test]
+    fn outset_is_the_opposite_of_inset() {
+        let r = rect(10., 10., 20., 10.);
+        assert_eq!(r.outset(2., 3.), rect(8., 7., 24., 16.));
+        assert_eq!(r.outset(-2., -3.), r.inset(2., 3.));
+        assert_eq!(r.outset(2., 3.).inset(2., 3.), r);
+        assert_eq!(r.outset(2., 3.).center(), r.center());
+    }
+
+    #[test]
+    fn corners() {
+        let r = rect(1., 2., 3., 4.);
+        assert_eq!(r.max_x(), 4.);
+        assert_eq!(r.max_y(), 6.);
+        assert_eq!(r.top_left(), Point2::new(1., 2.));
+        assert_eq!(r.top_right(), Point2::new(4., 2.));
+        assert_eq!(r.bottom_right(), Point2::new(4., 6.));
+        assert_eq!(r.bottom_left(), Point2::new(1., 6.));
+        assert_eq!(r.center(), Point2::new(2.5, 4.));
+
+        let flipped = rect(4., 6., -3., -4.);
+        assert_eq!(flipped.top_left(), r.bottom_right());
+        assert_eq!(flipped.bottom_right(), r.top_left());
+    }
+
+    #[test]
+    fn edges_are_rounded_to_pixels() {
+        assert_eq!(
+            rect(0.3, 0.3, 10.1, 10.1).round_to_pixels(1.),
+            rect(0., 0., 10., 10.)
+        );
+        assert_eq!(
+            rect(0.3, 0.3, 10.1, 10.1).round_to_pixels(2.),
+            rect(0.5, 0.5, 10., 10.)
+        );
+        assert_eq!(
+            rect(1.2, 1.2, -0.9, -0.9).round_to_pixels(2.),
+            rect(1., 1., -0.5, -0.5)
+        );
+    }
+
+    #[test]
+    fn edges_are_rounded_to_pixels_at_non_integer_scale_factors() {
+        for &scale_factor in &[1.25, 1.5, 1.75, 2.625] {
+            let a = rect(0.3, 0.7, 3.3, 10.1);
+            let b = rect(a.max_x(), 0.7, 4.9, 10.1);
+            let (rounded_a, rounded_b) = (
+                a.round_to_pixels(scale_factor),
+                b.round_to_pixels(scale_factor),
+            );
+            for &edge in &[
+                rounded_a.origin.x,
+                rounded_a.origin.y,
+                rounded_a.max_x(),
+                rounded_a.max_y(),
+                rounded_b.max_x(),
+            ] {
+                assert!(
+                    is_on_pixel_edge(edge, scale_factor),
+                    "{} at {}",
+                    edge,
+                    scale_factor
+                );
+            }
+            // adjacent rects stay adjacent
+            assert!((rounded_a.max_x() - rounded_b.origin.x).abs() < 1e-9);
+            // and every edge moves by at most half a pixel
+            assert!((rounded_a.origin.x - a.origin.x).abs() <= 0.5 / scale_factor);
+            assert!((rounded_a.max_y() - a.max_y()).abs() <= 0.5 / scale_factor + 1e-9);
+        }
+        assert_eq!(
+            rect(0.3, 0.3, 10.1, 10.1).round_to_pixels(1.5),
+            rect(0., 0., 16. / 1.5, 16. / 1.5)
+        );
+    }
+
+    #[test]
+    fn transformed_rects() {
+        // scale by 2, then translate by (5, -2); matrices are column-major
+        let transform = Matrix3::new(2., 0., 0., 0., 2., 0., 5., -2., 1.);
+        let quad = rect(1., 1., 2., 3.).transformed(transform);
+        let expected = [(7., 0.), (11., 0.), (11., 6.), (7., 6.)];
+        assert_eq!(quad.corners, expected.map(|(x, y)| Point2::new(x, y)));
+        assert_eq!(quad.bounding_box(), rect(7., 0., 4., 6.));
+
+        // rotate by 90° clockwise (with y pointing down)
+        let transform = Matrix3::new(0., 1., 0., -1., 0., 0., 0., 0., 1.);
+        let quad = rect(1., 2., 3., 4.).transformed(transform);
+        assert_eq!(quad.corners[0], Point2::new(-2., 1.));
+        assert_eq!(quad.bounding_box(), rect(-6., 1., 4., 3.));
+        assert!(quad.contains(Point2::new(-4., 2.)));
+        assert!(!quad.contains(Point2::new(0., 0.)));
+
+        let identity = Matrix3::identity();
+        let r = rect(-3., 4., 5., 6.);
+        assert_eq!(r.transformed(identity).bounding_box(), r);
+    }
+
+    #[test]
+    fn transformed_rects_with_negative_sizes() {
+        let identity = Matrix3::identity();
+        let quad = rect(10., 10., -4., -4.).transformed(identity);
+        assert_eq!(quad.bounding_box(), rect(6., 6., 4., 4.));
+        assert!(quad.contains(Point2::new(8., 8.)));
+        assert!(!quad.contains(Point2::new(11., 8.)));
+    }
+}
@@ -0,0 +1,1009 @@
+//! Serializing the patch stream for remote and out-of-process backends.
+//!
+//! Patches are encoded in a compact binary format. Since most updates only change a few properties
+//! of a layer (e.g. its bounds or opacity during an animation), the [`PatchEncoder`] remembers the
+//! last state of every view and only encodes the properties that changed; the [`PatchDecoder`] on
+//! the other end keeps the same state to fill in the rest. Hence, all patches must be decoded in
+//! the order they were encoded, and neither side may skip any.
+//!
+//! ```
+//! use birb::remote::{PatchDecoder, PatchEncoder};
+//! # use birb::Patch;
+//! # fn patches() -> Vec<Patch> { Vec::new() }
+//!
+//! let mut encoder = PatchEncoder::new();
+//! let mut decoder = PatchDecoder::new();
+//!
+//! let mut data = Vec::new();
+//! for patch in patches() {
+//!     encoder.encode(&patch, &mut data);
+//! }
+//!
+//! let mut input = &data[..];
+//! while !input.is_empty() {
+//!     let patch = decoder.decode(&mut input).unwrap();
+//! }
+//! ```
+
+use crate::accessibility::{Announcement, AnnouncementPriority};
+use crate::atlas::{AtlasRegion, CapInsets};
+use crate::color::{Color, Gradient};
+use crate::layer::{BorderAlignment, BorderStyle, ContentsGravity};
+use crate::nv_tree::{NativeView, Patch};
+use crate::rect::{CornerRadius, CornerStyle, Rect};
+use crate::shape::Shape;
+use crate::view::ViewId;
+use cgmath::{Matrix3, Point2, Vector2};
+use core::fmt;
+use core::mem;
+use std::collections::HashMap;
+use std::str;
+
+const TAG_SET_ROOT: u8 = 0;
+const TAG_UPDATE: u8 = 1;
+const TAG_UPDATE_DELTA: u8 = 2;
+const TAG_REPLACE: u8 = 3;
+const TAG_SUBVIEW_REGION: u8 = 4;
+const TAG_REMOVE: u8 = 5;
+const TAG_ANNOUNCE: u8 = 6;
+
+const NATIVE_TYPE_LAYER: u8 = 0;
+const NATIVE_TYPE_CUSTOM: u8 = 1;
+
+/// Number of layer properties.
+const LAYER_FIELDS: u8 = 19;
+/// Number of custom view properties.
+const CUSTOM_FIELDS: u8 = 5;
+
+/// Errors that may occur when decoding a patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a patch.
+    UnexpectedEnd,
+    /// The input contains an unknown patch or native view type, or an invalid value.
+    Invalid,
+    /// A delta update refers to a view the decoder doesn’t know, which means that patches were
+    /// skipped or decoded out of order.
+    UnknownView(ViewId),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::Invalid => write!(f, "invalid patch data"),
+            DecodeError::UnknownView(id) => write!(f, "update of unknown view {}", id),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// The last state of every view that one side of the stream knows about.
+///
+/// Subviews are tracked like in the NV tree, so that views removed along with their superview
+/// (which doesn’t come with patches of its own) are forgotten as well.
+#[derive(Default)]
+struct ViewStates {
+    views: HashMap<ViewId, NativeView>,
+    subviews: HashMap<ViewId, Vec<ViewId>>,
+}
+
+impl ViewStates {
+    fn get(&self, id: ViewId) -> Option<&NativeView> {
+        self.views.get(&id)
+    }
+
+    fn get_mut(&mut self, id: ViewId) -> Option<&mut NativeView> {
+        self.views.get_mut(&id)
+    }
+
+    fn insert(&mut self, id: ViewId, view: NativeView) {
+        self.views.insert(id, view);
+    }
+
+    /// Replaces a view, which removes its subviews.
+    fn replace(&mut self, id: ViewId, view: NativeView) {
+        for subview in self.subviews.remove(&id).unwrap_or_default() {
+            self.remove(subview);
+        }
+        self.views.insert(id, view);
+    }
+
+    /// Replaces a region of a view’s subviews.
+    fn subview_region(&mut self, id: ViewId, start: usize, len: usize, subviews: &[ViewId]) {
+        let list = self.subviews.entry(id).or_default();
+        let end = start.saturating_add(len).min(list.len());
+        let start = start.min(list.len());
+        list.splice(start..end, subviews.iter().copied());
+    }
+
+    /// Removes a view and its subviews.
+    fn remove(&mut self, id: ViewId) {
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            self.views.remove(&id);
+            removed.extend(self.subviews.remove(&id).unwrap_or_default());
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.views.len()
+    }
+
+    fn clear(&mut self) {
+        self.views.clear();
+        self.subviews.clear();
+    }
+}
+
+/// Encodes patches; see the [module documentation](self).
+#[derive(Default)]
+pub struct PatchEncoder {
+    /// The last encoded state of every view.
+    views: ViewStates,
+}
+
+impl PatchEncoder {
+    pub fn new() -> PatchEncoder {
+        PatchEncoder::default()
+    }
+
+    /// Appends an encoded patch to `out`.
+    pub fn encode(&mut self, patch: &Patch, out: &mut Vec<u8>) {
+        match patch {
+            Patch::SetRoot(id) => {
+                out.push(TAG_SET_ROOT);
+                write_id(out, *id);
+            }
+            Patch::Update(id, view) => {
+                match self.views.get(*id) {
+                    Some(prev) if is_same_type(prev, view) => {
+                        out.push(TAG_UPDATE_DELTA);
+                        write_id(out, *id);
+                        write_delta(out, prev, view);
+                    }
+                    _ => {
+                        out.push(TAG_UPDATE);
+                        write_id(out, *id);
+                        write_view(out, view);
+                    }
+                }
+                self.views.insert(*id, view.clone());
+            }
+            Patch::Replace(id, view) => {
+                out.push(TAG_REPLACE);
+                write_id(out, *id);
+                write_view(out, view);
+                self.views.replace(*id, view.clone());
+            }
+            Patch::SubviewRegion(id, start, len, subviews) => {
+                out.push(TAG_SUBVIEW_REGION);
+                write_id(out, *id);
+                write_varint(out, *start as u64);
+                write_varint(out, *len as u64);
+                write_varint(out, subviews.len() as u64);
+                for subview in subviews {
+                    write_id(out, *subview);
+                }
+                self.views.subview_region(*id, *start, *len, subviews);
+            }
+            Patch::Remove(id) => {
+                out.push(TAG_REMOVE);
+                write_id(out, *id);
+                self.views.remove(*id);
+            }
+            Patch::Announce(announcement) => {
+                out.push(TAG_ANNOUNCE);
+                write_announcement(out, announcement);
+            }
+        }
+    }
+
+    /// Forgets all view states, so that every view is sent in full again. Must be done on both
+    /// sides at the same time, e.g. when reconnecting.
+    pub fn reset(&mut self) {
+        self.views.clear();
+    }
+}
+
+impl fmt::Debug for PatchEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PatchEncoder")
+            .field("views", &self.views.len())
+            .finish()
+    }
+}
+
+/// Decodes patches; see the [module documentation](self).
+#[derive(Default)]
+pub struct PatchDecoder {
+    /// The last decoded state of every view.
+    views: ViewStates,
+}
+
+impl PatchDecoder {
+    pub fn new() -> PatchDecoder {
+        PatchDecoder::default()
+    }
+
+    /// Decodes a patch from the start of `input` and advances it past the patch.
+    ///
+    /// If this fails, the decoder state no longer matches the encoder and both must be
+    /// [reset](PatchEncoder::reset).
+    pub fn decode(&mut self, input: &mut &[u8]) -> Result<Patch, DecodeError> {
+        let patch = match read_u8(input)? {
+            TAG_SET_ROOT => Patch::SetRoot(read_id(input)?),
+            TAG_UPDATE => {
+                let id = read_id(input)?;
+                let view = read_view(input)?;
+                self.views.insert(id, view.clone());
+                Patch::Update(id, view)
+            }
+            TAG_UPDATE_DELTA => {
+                let id = read_id(input)?;
+                let view = self.views.get_mut(id).ok_or(DecodeError::UnknownView(id))?;
+                read_delta(input, view)?;
+                Patch::Update(id, view.clone())
+            }
+            TAG_REPLACE => {
+                let id = read_id(input)?;
+                let view = read_view(input)?;
+                self.views.replace(id, view.clone());
+                Patch::Replace(id, view)
+            }
+            TAG_SUBVIEW_REGION => {
+                let id = read_id(input)?;
+                let start = read_varint(input)? as usize;
+                let len = read_varint(input)? as usize;
+                // the region must be addressable, since backends compute its end
+                if start.checked_add(len).is_none() {
+                    return Err(DecodeError::Invalid);
+                }
+                let count = read_varint(input)? as usize;
+                // each ID takes 16 bytes, so don’t trust the count to allocate
+                if count > input.len() / 16 {
+                    return Err(DecodeError::UnexpectedEnd);
+                }
+                let subviews: Vec<_> = (0..count)
+                    .map(|_| read_id(input))
+                    .collect::<Result<_, _>>()?;
+                self.views.subview_region(id, start, len, &subviews);
+                Patch::SubviewRegion(id, start, len, subviews)
+            }
+            TAG_REMOVE => {
+                let id = read_id(input)?;
+                self.views.remove(id);
+                Patch::Remove(id)
+            }
+            TAG_ANNOUNCE => Patch::Announce(read_announcement(input)?),
+            _ => return Err(DecodeError::Invalid),
+        };
+        Ok(patch)
+    }
+
+    /// Forgets all view states; see [`PatchEncoder::reset`].
+    pub fn reset(&mut self) {
+        self.views.clear();
+    }
+}
+
+impl fmt::Debug for PatchDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PatchDecoder")
+            .field("views", &self.views.len())
+            .finish()
+    }
+}
+
+fn write_id(out: &mut Vec<u8>, id: ViewId) {
+    out.extend_from_slice(&id.to_bytes());
+}
+
+/// Writes an unsigned LEB128 integer.
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn write_f64(out: &mut Vec<u8>, n: f64) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+pub(crate) fn write_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+pub(crate) fn write_str(out: &mut Vec<u8>, string: &str) {
+    write_varint(out, string.len() as u64);
+    out.extend_from_slice(string.as_bytes());
+}
+
+fn write_color(out: &mut Vec<u8>, color: Color) {
+    for c in &[color.r, color.g, color.b, color.a] {
+        write_f64(out, *c);
+    }
+}
+
+pub(crate) fn write_announcement(out: &mut Vec<u8>, announcement: &Announcement) {
+    out.push(match announcement.priority {
+        AnnouncementPriority::Low => 0,
+        AnnouncementPriority::Medium => 1,
+        AnnouncementPriority::High => 2,
+    });
+    write_str(out, &announcement.message);
+}
+
+/// Returns the number of properties of a native view, each of which has a bit in the mask of a
+/// delta update.
+fn field_count(view: &NativeView) -> u8 {
+    match view {
+        NativeView::Layer(_) => LAYER_FIELDS,
+        NativeView::Custom { .. } => CUSTOM_FIELDS,
+    }
+}
+
+/// Returns true if two native views have the same type, so one can be delta-encoded relative to
+/// the other.
+pub(crate) fn is_same_type(a: &NativeView, b: &NativeView) -> bool {
+    mem::discriminant(a) == mem::discriminant(b)
+}
+
+/// Writes a native view in full.
+pub(crate) fn write_view(out: &mut Vec<u8>, view: &NativeView) {
+    out.push(match view {
+        NativeView::Layer(_) => NATIVE_TYPE_LAYER,
+        NativeView::Custom { .. } => NATIVE_TYPE_CUSTOM,
+    });
+    for field in 0..field_count(view) {
+        write_field(out, view, field);
+    }
+}
+
+/// Writes the properties that changed between two native views of the same type, preceded by a
+/// bit mask of them.
+pub(crate) fn write_delta(out: &mut Vec<u8>, prev: &NativeView, view: &NativeView) {
+    let mut mask = 0_u32;
+    for field in 0..field_count(view) {
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        write_field(&mut a, prev, field);
+        write_field(&mut b, view, field);
+        if a != b {
+            mask |= 1 << field;
+        }
+    }
+    out.extend_from_slice(&mask.to_le_bytes());
+    for field in 0..field_count(view) {
+        if mask & (1 << field) != 0 {
+            write_field(out, view, field);
+        }
+    }
+}
+
+fn write_rect(out: &mut Vec<u8>, rect: Rect) {
+    for n in &[rect.origin.x, rect.origin.y, rect.size.x, rect.size.y] {
+        write_f64(out, *n);
+    }
+}
+
+fn write_corner_radius(out: &mut Vec<u8>, radius: CornerRadius) {
+    for n in &[
+        radius.top_left,
+        radius.top_right,
+        radius.bottom_right,
+        radius.bottom_left,
+    ] {
+        write_f64(out, *n);
+    }
+    out.push(match radius.style {
+        CornerStyle::Circular => 0,
+        CornerStyle::Continuous => 1,
+    });
+}
+
+fn write_border_style(out: &mut Vec<u8>, style: BorderStyle) {
+    match style {
+        BorderStyle::Solid => out.push(0),
+        BorderStyle::Dashed { dash, gap } => {
+            out.push(1);
+            write_f64(out, dash);
+            write_f64(out, gap);
+        }
+    }
+}
+
+fn write_shape(out: &mut Vec<u8>, shape: &Shape) {
+    match shape {
+        Shape::RoundedRect {
+            rect,
+            corner_radius,
+        } => {
+            out.push(0);
+            write_rect(out, *rect);
+            write_corner_radius(out, *corner_radius);
+        }
+        Shape::Ellipse(rect) => {
+            out.push(1);
+            write_rect(out, *rect);
+        }
+        Shape::Polygon(points) => {
+            out.push(2);
+            write_varint(out, points.len() as u64);
+            for point in points {
+                write_f64(out, point.x);
+                write_f64(out, point.y);
+            }
+        }
+    }
+}
+
+fn write_gradient(out: &mut Vec<u8>, gradient: &Gradient) {
+    for point in &[gradient.start, gradient.end] {
+        write_f64(out, point.x);
+        write_f64(out, point.y);
+    }
+    write_varint(out, gradient.stops.len() as u64);
+    for (location, color) in &gradient.stops {
+        write_f64(out, *location);
+        write_color(out, *color);
+    }
+}
+
+fn write_matrix(out: &mut Vec<u8>, matrix: &Matrix3<f64>) {
+    let columns: &[[f64; 3]; 3] = matrix.as_ref();
+    for n in columns.iter().flatten() {
+        write_f64(out, *n);
+    }
+}
+
+/// Writes one property of a native view.
+fn write_field(out: &mut Vec<u8>, view: &NativeView, field: u8) {
+    match view {
+        NativeView::Layer(props) => match field {
+            0 => write_rect(out, props.bounds),
+            1 => write_color(out, props.background),
+            2 => write_corner_radius(out, props.corner_radius),
+            3 => match props.border {
+                Some((width, color)) => {
+                    out.push(1);
+                    write_f64(out, width);
+                    write_color(out, color);
+                }
+                None => out.push(0),
+            },
+            4 => out.push(props.clip_contents as u8),
+            5 => write_matrix(out, &props.transform),
+            6 => write_f64(out, props.opacity),
+            7 => out.push(props.should_rasterize as u8),
+            8 => match props.contents {
+                Some(region) => {
+                    out.push(1);
+                    write_varint(out, region.page as u64);
+                    for n in &[region.x, region.y, region.width, region.height] {
+                        write_u32(out, *n);
+                    }
+                }
+                None => out.push(0),
+            },
+            9 => {
+                let insets = props.contents_insets;
+                for n in &[insets.top, insets.left, insets.bottom, insets.right] {
+                    write_u32(out, *n);
+                }
+            }
+            10 => out.push(props.rounded_hit_testing as u8),
+            11 => out.push(match props.border_alignment {
+                BorderAlignment::Inside => 0,
+                BorderAlignment::Center => 1,
+                BorderAlignment::Outside => 2,
+            }),
+            12 => write_border_style(out, props.border_style),
+            13 => out.push(props.group_opacity as u8),
+            14 => match &props.clip_shape {
+                Some(shape) => {
+                    out.push(1);
+                    write_shape(out, shape);
+                }
+                None => out.push(0),
+            },
+            15 => match &props.background_gradient {
+                Some(gradient) => {
+                    out.push(1);
+                    write_gradient(out, gradient);
+                }
+                None => out.push(0),
+            },
+            16 => out.push(match props.contents_gravity {
+                ContentsGravity::Resize => 0,
+                ContentsGravity::ResizeAspect => 1,
+                ContentsGravity::ResizeAspectFill => 2,
+                ContentsGravity::Center => 3,
+                ContentsGravity::Tile => 4,
+            }),
+            17 => out.push(props.material as u8),
+            18 => out.push(props.hit_testing as u8),
+            _ => unreachable!(),
+        },
+        NativeView::Custom {
+            kind,
+            bounds,
+            transform,
+            opacity,
+            props,
+        } => match field {
+            0 => write_str(out, kind),
+            1 => write_rect(out, *bounds),
+            2 => write_matrix(out, transform),
+            3 => write_f64(out, *opacity),
+            4 => {
+                write_varint(out, props.len() as u64);
+                out.extend_from_slice(props);
+            }
+            _ => unreachable!(),
+        },
+    }
+}
+
+pub(crate) fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+pub(crate) fn read_u8(input: &mut &[u8]) -> Result<u8, DecodeError> {
+    Ok(read_bytes(input, 1)?[0])
+}
+
+pub(crate) fn read_bool(input: &mut &[u8]) -> Result<bool, DecodeError> {
+    match read_u8(input)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DecodeError::Invalid),
+    }
+}
+
+fn read_id(input: &mut &[u8]) -> Result<ViewId, DecodeError> {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(read_bytes(input, 16)?);
+    Ok(ViewId::from_bytes(bytes))
+}
+
+pub(crate) fn read_varint(input: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut n = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(input)?;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(DecodeError::Invalid)
+}
+
+pub(crate) fn read_f64(input: &mut &[u8]) -> Result<f64, DecodeError> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(read_bytes(input, 8)?);
+    Ok(f64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u32(input: &mut &[u8]) -> Result<u32, DecodeError> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(read_bytes(input, 4)?);
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_string(input: &mut &[u8]) -> Result<String, DecodeError> {
+    let len = read_varint(input)? as usize;
+    str::from_utf8(read_bytes(input, len)?)
+        .map(|string| string.to_string())
+        .map_err(|_| DecodeError::Invalid)
+}
+
+fn read_color(input: &mut &[u8]) -> Result<Color, DecodeError> {
+    Ok(Color {
+        r: read_f64(input)?,
+        g: read_f64(input)?,
+        b: read_f64(input)?,
+        a: read_f64(input)?,
+    })
+}
+
+pub(crate) fn read_announcement(input: &mut &[u8]) -> Result<Announcement, DecodeError> {
+    let priority = match read_u8(input)? {
+        0 => AnnouncementPriority::Low,
+        1 => AnnouncementPriority::Medium,
+        2 => AnnouncementPriority::High,
+        _ => return Err(DecodeError::Invalid),
+    };
+    let message = read_string(input)?;
+    Ok(Announcement { message, priority })
+}
+
+pub(crate) fn read_view(input: &mut &[u8]) -> Result<NativeView, DecodeError> {
+    let identity = Matrix3::new(1., 0., 0., 0., 1., 0., 0., 0., 1.);
+    let mut view = match read_u8(input)? {
+        NATIVE_TYPE_LAYER => NativeView::Layer(Box::default()),
+        NATIVE_TYPE_CUSTOM => NativeView::Custom {
+            kind: String::new(),
+            bounds: Rect::zero(),
+            transform: identity,
+            opacity: 1.,
+            props: Vec::new(),
+        },
+        _ => return Err(DecodeError::Invalid),
+    };
+    for field in 0..field_count(&view) {
+        read_field(input, &mut view, field)?;
+    }
+    Ok(view)
+}
+
+pub(crate) fn read_delta(input: &mut &[u8], view: &mut NativeView) -> Result<(), DecodeError> {
+    let mask = u32::from_le_bytes([
+        read_u8(input)?,
+        read_u8(input)?,
+        read_u8(input)?,
+        read_u8(input)?,
+    ]);
+    if mask >> field_count(view) != 0 {
+        return Err(DecodeError::Invalid);
+    }
+    for field in 0..field_count(view) {
+        if mask & (1 << field) != 0 {
+            read_field(input, view, field)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_rect(input: &mut &[u8]) -> Result<Rect, DecodeError> {
+    let origin = Point2::new(read_f64(input)?, read_f64(input)?);
+    let size = Vector2::new(read_f64(input)?, read_f64(input)?);
+    Ok(Rect::new(origin, size))
+}
+
+fn read_corner_radius(input: &mut &[u8]) -> Result<CornerRadius, DecodeError> {
+    Ok(CornerRadius {
+        top_left: read_f64(input)?,
+        top_right: read_f64(input)?,
+        bottom_right: read_f64(input)?,
+        bottom_left: read_f64(input)?,
+        style: match read_u8(input)? {
+            0 => CornerStyle::Circular,
+            1 => CornerStyle::Continuous,
+            _ => return Err(DecodeError::Invalid),
+        },
+    })
+}
+
+fn read_border_style(input: &mut &[u8]) -> Result<BorderStyle, DecodeError> {
+    match read_u8(input)? {
+        0 => Ok(BorderStyle::Solid),
+        1 => Ok(BorderStyle::Dashed {
+            dash: read_f64(input)?,
+            gap: read_f64(input)?,
+        }),
+        _ => Err(DecodeError::Invalid),
+    }
+}
+
+fn read_shape(input: &mut &[u8]) -> Result<Shape, DecodeError> {
+    match read_u8(input)? {
+        0 => Ok(Shape::RoundedRect {
+            rect: read_rect(input)?,
+            corner_radius: read_corner_radius(input)?,
+        }),
+        1 => Ok(Shape::Ellipse(read_rect(input)?)),
+        2 => {
+            let len = read_varint(input)? as usize;
+            // each point takes 16 bytes, so don’t trust the length to allocate
+            if len > input.len() / 16 {
+                return Err(DecodeError::UnexpectedEnd);
+            }
+            let mut points = Vec::with_capacity(len);
+            for _ in 0..len {
+                points.push(Point2::new(read_f64(input)?, read_f64(input)?));
+            }
+            Ok(Shape::Polygon(points))
+        }
+        _ => Err(DecodeError::Invalid),
+    }
+}
+
+fn read_gradient(input: &mut &[u8]) -> Result<Gradient, DecodeError> {
+    let start = Point2::new(read_f64(input)?, read_f64(input)?);
+    let end = Point2::new(read_f64(input)?, read_f64(input)?);
+    let len = read_varint(input)? as usize;
+    // each stop takes 40 bytes, so don’t trust the length to allocate
+    if len > input.len() / 40 {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let mut stops = Vec::with_capacity(len);
+    for _ in 0..len {
+        stops.push((read_f64(input)?, read_color(input)?));
+    }
+    Ok(Gradient { start, end, stops })
+}
+
+fn read_matrix(input: &mut &[u8]) -> Result<Matrix3<f64>, DecodeError> {
+    let mut columns = [[0.; 3]; 3];
+    for n in columns.iter_mut().flatten() {
+        *n = read_f64(input)?;
+    }
+    Ok(columns.into())
+}
+
+/// Reads one property of a native view.
+fn read_field(input: &mut &[u8], view: &mut NativeView, field: u8) -> Result<(), DecodeError> {
+    match view {
+        NativeView::Layer(props) => match field {
+            0 => props.bounds = read_rect(input)?,
+            1 => props.background = read_color(input)?,
+            2 => props.corner_radius = read_corner_radius(input)?,
+            3 => {
+                props.border = if read_bool(input)? {
+                    Some((read_f64(input)?, read_color(input)?))
+                } else {
+                    None
+                };
+            }
+            4 => props.clip_contents = read_bool(input)?,
+            5 => props.transform = read_matrix(input)?,
+            6 => props.opacity = read_f64(input)?,
+            7 => props.should_rasterize = read_bool(input)?,
+            8 => {
+                props.contents = if read_bool(input)? {
+                    Some(AtlasRegion {
+                        page: read_varint(input)? as usize,
+                        x: read_u32(input)?,
+                        y: read_u32(input)?,
+                        width: read_u32(input)?,
+                        height: read_u32(input)?,
+                    })
+                } else {
+                    None
+                }
+            }
+            9 => {
+                props.contents_insets = CapInsets {
+                    top: read_u32(input)?,
+                    left: read_u32(input)?,
+                    bottom: read_u32(input)?,
+                    right: read_u32(input)?,
+                }
+            }
+            10 => props.rounded_hit_testing = read_bool(input)?,
+            11 => {
+                props.border_alignment = match read_u8(input)? {
+                    0 => BorderAlignment::Inside,
+                    1 => BorderAlignment::Center,
+                    2 => BorderAlignment::Outside,
+                    _ => return Err(DecodeError::Invalid),
+                }
+            }
+            12 => props.border_style = read_border_style(input)?,
+            13 => props.group_opacity = read_bool(input)?,
+            14 => {
+                props.clip_shape = if read_bool(input)? {
+                    Some(read_shape(input)?)
+                } else {
+                    None
+                };
+            }
+            15 => {
+                props.background_gradient = if read_bool(input)? {
+                    Some(read_gradient(input)?)
+                } else {
+                    None
+                };
+            }
+            16 => {
+                props.contents_gravity = match read_u8(input)? {
+                    0 => ContentsGravity::Resize,
+                    1 => ContentsGravity::ResizeAspect,
+                    2 => ContentsGravity::ResizeAspectFill,
+                    3 => ContentsGravity::Center,
+                    4 => ContentsGravity::Tile,
+                    _ => return Err(DecodeError::Invalid),
+                }
+            }
+            17 => props.material = read_bool(input)?,
+            18 => props.hit_testing = read_bool(input)?,
+            _ => unreachable!(),
+        },
+        NativeView::Custom {
+            kind,
+            bounds,
+            transform,
+            opacity,
+            props,
+        } => match field {
+            0 => *kind = read_string(input)?,
+            1 => *bounds = read_rect(input)?,
+            2 => *transform = read_matrix(input)?,
+            3 => *opacity = read_f64(input)?,
+            4 => {
+                let len = read_varint(input)? as usize;
+                *props = read_bytes(input, len)?.to_vec();
+            }
+            _ => unreachable!(),
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerProps;
+
+    fn layer(opacity: f64) -> NativeView {
+        NativeView::Layer(Box::new(LayerProps {
+            bounds: Rect::new(Point2::new(1., 2.), Vector2::new(30., 40.)),
+            background: Color::rgba(1., 0.5, 0., 1.),
+            border: Some((2., Color::rgba(0., 0., 0., 0.5))),
+            border_style: BorderStyle::Dashed { dash: 3., gap: 1. },
+            clip_shape: Some(Shape::Ellipse(Rect::new(
+                Point2::new(1., 1.),
+                Vector2::new(8., 8.),
+            ))),
+            opacity,
+            material: true,
+            ..LayerProps::default()
+        }))
+    }
+
+    /// Encodes patches, decodes them again, and checks that they’re the same.
+    fn round_trip(encoder: &mut PatchEncoder, decoder: &mut PatchDecoder, patches: &[Patch]) {
+        let mut data = Vec::new();
+        for patch in patches {
+            encoder.encode(patch, &mut data);
+        }
+        let mut input = &data[..];
+        for patch in patches {
+            let decoded = decoder.decode(&mut input).unwrap();
+            let same = match (patch, &decoded) {
+                (Patch::SetRoot(a), Patch::SetRoot(b)) | (Patch::Remove(a), Patch::Remove(b)) => {
+                    a == b
+                }
+                (Patch::Update(a, view_a), Patch::Update(b, view_b))
+                | (Patch::Replace(a, view_a), Patch::Replace(b, view_b)) => {
+                    a == b && view_a == view_b
+                }
+                (Patch::SubviewRegion(a, ..), Patch::SubviewRegion(b, ..)) => {
+                    // compared by their encoding below
+                    a == b
+                }
+                (Patch::Announce(a), Patch::Announce(b)) => a == b,
+                _ => false,
+            };
+            assert!(same, "patch was decoded differently");
+
+            let (mut a, mut b) = (Vec::new(), Vec::new());
+            PatchEncoder::new().encode(patch, &mut a);
+            PatchEncoder::new().encode(&decoded, &mut b);
+            assert_eq!(a, b);
+        }
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn patches_round_trip() {
+        let mut encoder = PatchEncoder::new();
+        let mut decoder = PatchDecoder::new();
+        let (root, child, grandchild, other) =
+            (ViewId::new(), ViewId::new(), ViewId::new(), ViewId::new());
+        let custom = NativeView::Custom {
+            kind: "com.example.map".into(),
+            bounds: Rect::new(Point2::new(0., 0.), Vector2::new(10., 10.)),
+            transform: Matrix3::new(2., 0., 0., 0., 2., 0., 5., 6., 1.),
+            opacity: 0.5,
+            props: vec![1, 2, 3],
+        };
+        round_trip(
+            &mut encoder,
+            &mut decoder,
+            &[
+                Patch::Update(root, layer(1.)),
+                Patch::Update(child, layer(1.)),
+                Patch::Update(grandchild, custom.clone()),
+                Patch::Update(other, layer(1.)),
+                Patch::SubviewRegion(child, 0, 0, vec![grandchild]),
+                Patch::SubviewRegion(root, 0, 0, vec![child, other]),
+                Patch::SetRoot(root),
+                // delta updates
+                Patch::Update(child, layer(0.5)),
+                Patch::Update(grandchild, layer(0.25)),
+                Patch::Announce(Announcement {
+                    message: "Hello".into(),
+                    priority: AnnouncementPriority::High,
+                }),
+            ],
+        );
+        assert_eq!(encoder.views.len(), 4);
+        assert_eq!(decoder.views.len(), 4);
+
+        // removing the child also removes the grandchild
+        round_trip(
+            &mut encoder,
+            &mut decoder,
+            &[
+                Patch::SubviewRegion(root, 0, 1, vec![]),
+                Patch::Remove(child),
+            ],
+        );
+        assert_eq!(encoder.views.len(), 2);
+        assert_eq!(decoder.views.len(), 2);
+        assert!(encoder.views.get(grandchild).is_none());
+
+        // replacing the root removes the other view
+        round_trip(
+            &mut encoder,
+            &mut decoder,
+            &[
+                Patch::Replace(root, custom),
+                Patch::Update(other, layer(1.)),
+            ],
+        );
+        assert_eq!(encoder.views.len(), 2);
+        assert_eq!(decoder.views.len(), 2);
+        assert!(!decoder.views.subviews.contains_key(&root));
+
+        encoder.reset();
+        decoder.reset();
+        assert_eq!(decoder.views.len(), 0);
+        let mut data = Vec::new();
+        PatchEncoder::new().encode(&Patch::Update(root, layer(1.)), &mut data);
+        // a delta update of a view the decoder doesn’t know
+        data[0] = TAG_UPDATE_DELTA;
+        assert_eq!(
+            decoder.decode(&mut &data[..]).err(),
+            Some(DecodeError::UnknownView(root))
+        );
+    }
+
+    #[test]
+    fn overflowing_subview_regions_are_rejected() {
+        let mut encoder = PatchEncoder::new();
+        let mut decoder = PatchDecoder::new();
+        let (root, child) = (ViewId::new(), ViewId::new());
+        round_trip(
+            &mut encoder,
+            &mut decoder,
+            &[
+                Patch::Update(root, layer(1.)),
+                Patch::Update(child, layer(1.)),
+            ],
+        );
+
+        for &(start, len) in &[(usize::MAX, 1), (1, usize::MAX), (usize::MAX, usize::MAX)] {
+            let mut data = Vec::new();
+            encoder.encode(
+                &Patch::SubviewRegion(root, start, len, vec![child]),
+                &mut data,
+            );
+            assert_eq!(
+                decoder.decode(&mut &data[..]).err(),
+                Some(DecodeError::Invalid)
+            );
+        }
+
+        // the decoder can still be used afterwards
+        round_trip(
+            &mut encoder,
+            &mut decoder,
+            &[Patch::SubviewRegion(root, 0, usize::MAX - 1, vec![child])],
+        );
+        assert_eq!(decoder.views.subviews[&root], [child]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Scheduling low-priority work.
+//!
+//! Work like prefetching, warming up caches, or expensive measurements shouldn’t delay frames. A
+//! [`Scheduler`] is passed down the view tree in the context; views queue such work with
+//! [`request_idle`](Scheduler::request_idle), and the event loop calls
+//! [`on_idle`](Scheduler::on_idle) with the time left whenever a frame finishes early:
+//!
+//! ```no_run
+//! # use birb::scheduler::Scheduler;
+//! # use std::time::{Duration, Instant};
+//! # fn render_frame() {}
+//! # let scheduler = Scheduler::new();
+//! let frame_duration = Duration::from_micros(16_667);
+//! loop {
+//!     let frame_start = Instant::now();
+//!     render_frame();
+//!     let elapsed = frame_start.elapsed();
+//!     if elapsed < frame_duration {
+//!         scheduler.on_idle(frame_duration - elapsed);
+//!     }
+//! }
+//! ```
+
+use core::fmt;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The time an idle callback may take.
+#[derive(Debug, Clone, Copy)]
+pub struct IdleDeadline {
+    deadline: Instant,
+}
+
+impl IdleDeadline {
+    /// Returns how much time is left; callbacks that do work in steps should stop once this is
+    /// zero and request another idle callback for the rest.
+    pub fn time_remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns true if there is no time left.
+    pub fn did_time_out(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+/// An idle callback.
+pub type IdleCallback = dyn FnOnce(&IdleDeadline) + Send;
+
+/// Identifies an idle callback; see [`Scheduler::request_idle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdleId(u64);
+
+struct SchedulerInner {
+    /// Idle callbacks in the order they were requested.
+    idle: VecDeque<(IdleId, Box<IdleCallback>)>,
+    next_id: u64,
+}
+
+/// Runs low-priority work in the time left over after frames, meant to be passed down the view
+/// tree in the context; see the [module documentation](self).
+///
+/// This is a cheap handle; clones share the same state.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Mutex<SchedulerInner>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler {
+            inner: Arc::new(Mutex::new(SchedulerInner {
+                idle: VecDeque::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// Queues a callback that will be called once, the next time there is idle time.
+    pub fn request_idle<F>(&self, callback: F) -> IdleId
+    where
+        F: 'static + FnOnce(&IdleDeadline) + Send,
+    {
+        let mut inner = self.inner.lock();
+        let id = IdleId(inner.next_id);
+        inner.next_id += 1;
+        inner.idle.push_back((id, Box::new(callback)));
+        id
+    }
+
+    /// Removes a queued idle callback. Does nothing if it has already been called.
+    pub fn cancel_idle(&self, id: IdleId) {
+        self.inner.lock().idle.retain(|(idle_id, _)| *idle_id != id);
+    }
+
+    /// Returns true if there are queued idle callbacks.
+    pub fn has_idle_callbacks(&self) -> bool {
+        !self.inner.lock().idle.is_empty()
+    }
+
+    /// Calls queued idle callbacks in order until the budget has been used up. Should be called by
+    /// the event loop when a frame finishes early, with the time left until the next frame.
+    ///
+    /// Callbacks that are requested while this runs are left for the next call. A callback that
+    /// overruns the budget delays the next frame, so callbacks should keep their steps short.
+    ///
+    /// Returns true if there are callbacks left.
+    pub fn on_idle(&self, budget: Duration) -> bool {
+        let deadline = IdleDeadline {
+            deadline: Instant::now() + budget,
+        };
+        // callbacks requested from here on have higher IDs
+        let limit = self.inner.lock().next_id;
+        while !deadline.did_time_out() {
+            // the lock isn’t held while calling, so callbacks may use the scheduler themselves
+            let callback = {
+                let mut inner = self.inner.lock();
+                match inner.idle.front() {
+                    Some((id, _)) if id.0 < limit => inner.idle.pop_front().unwrap().1,
+                    _ => break,
+                }
+            };
+            callback(&deadline);
+        }
+        self.has_idle_callbacks()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("idle_callbacks", &self.inner.lock().idle.len())
+            .finish()
+    }
+}
+
+/// Context types that provide a scheduler.
+pub trait SchedulerContext {
+    fn scheduler(&self) -> &Scheduler;
+}
+
+impl SchedulerContext for Scheduler {
+    fn scheduler(&self) -> &Scheduler {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Requests an idle callback that logs its name, after sleeping for some time.
+    fn request(
+        scheduler: &Scheduler,
+        log: &Arc<Mutex<Vec<&'static str>>>,
+        name: &'static str,
+        sleep: u64,
+    ) -> IdleId {
+        let log = Arc::clone(log);
+        scheduler.request_idle(move |_| {
+            thread::sleep(Duration::from_millis(sleep));
+            log.lock().push(name);
+        })
+    }
+
+    #[test]
+    fn callbacks_run_in_order_within_the_budget() {
+        let scheduler = Scheduler::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        request(&scheduler, &log, "a", 0);
+        let b = request(&scheduler, &log, "b", 0);
+        request(&scheduler, &log, "c", 30);
+        request(&scheduler, &log, "d", 0);
+        scheduler.cancel_idle(b);
+
+        assert!(scheduler.on_idle(Duration::from_secs(0)));
+        assert!(log.lock().is_empty());
+
+        // c overruns the budget, so d is left for the next call
+        assert!(scheduler.on_idle(Duration::from_millis(20)));
+        assert_eq!(*log.lock(), ["a", "c"]);
+        assert!(!scheduler.on_idle(Duration::from_millis(20)));
+        assert_eq!(*log.lock(), ["a", "c", "d"]);
+        assert!(!scheduler.has_idle_callbacks());
+    }
+
+    #[test]
+    fn callbacks_requested_while_idle_wait_for_the_next_call() {
+        let scheduler = Scheduler::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        {
+            let (scheduler2, log2) = (scheduler.clone(), Arc::clone(&log));
+            scheduler.request_idle(move |deadline| {
+                assert!(!deadline.did_time_out());
+                assert!(deadline.time_remaining() <= Duration::from_secs(1));
+                log2.lock().push("first");
+                request(&scheduler2, &log2, "again", 0);
+            });
+        }
+
+        assert!(scheduler.on_idle(Duration::from_secs(1)));
+        assert_eq!(*log.lock(), ["first"]);
+        assert!(!scheduler.on_idle(Duration::from_secs(1)));
+        assert_eq!(*log.lock(), ["first", "again"]);
+    }
+}
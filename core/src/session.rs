@@ -0,0 +1,413 @@
+//! Restoring UI state across launches.
+//!
+//! A [`SessionStore`] keeps a [snapshot](crate::snapshot) of the view states of each open window
+//! in a directory, so that the app can reopen the same windows in the same state when it’s
+//! launched again. Windows are identified by names chosen by the app, e.g. a document path or
+//! `"main"`.
+//!
+//! ```no_run
+//! # use birb::session::SessionStore;
+//! # use birb::ViewTree;
+//! # fn main() -> Result<(), birb::session::SessionError> {
+//! let session = SessionStore::new("/path/to/app/data/session");
+//!
+//! // on launch
+//! for window in session.windows()? {
+//!     let snapshot = session.load(&window)?.unwrap_or_default();
+//!     let views = ViewTree::<()>::from_snapshot(snapshot);
+//!     // (open the window)
+//! }
+//!
+//! // when quitting (or periodically, in case the app crashes)
+//! # let views = ViewTree::<()>::new();
+//! session.save("main", &views.snapshot())?;
+//!
+//! // when a window is closed for good
+//! session.remove("main")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Window frames
+//! The store also keeps a [`WindowState`] (the window frame, whether it’s full screen, and the
+//! positions of split view dividers) under an autosave name, which the app should save whenever
+//! the window is moved, resized, or laid out differently, and apply when it opens the window.
+//! Backends that can use the platform’s own mechanism do so instead (like `SwiftBirb`, which uses
+//! Cocoa’s frame autosave names), in which case only divider positions need to be stored here.
+
+use crate::rect::Rect;
+use crate::settings::{Settings, SettingsError};
+use crate::snapshot::{DecodeSnapshotError, Snapshot};
+use cgmath::{Point2, Vector2};
+use core::fmt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File extension of saved snapshots.
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+
+/// File extension of saved window states.
+const WINDOW_EXTENSION: &str = "window";
+
+/// Prefix of divider position keys in window state files.
+const DIVIDER_PREFIX: &str = "divider.";
+
+/// Errors that may occur when loading or saving a session.
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    /// A saved snapshot could not be decoded.
+    Decode(DecodeSnapshotError),
+    /// A saved window state could not be parsed. Contains the line number.
+    Parse(usize),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(err) => write!(f, "{}", err),
+            SessionError::Decode(err) => write!(f, "{}", err),
+            SessionError::Parse(line) => write!(f, "invalid window state on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SessionError::Io(err) => Some(err),
+            SessionError::Decode(err) => Some(err),
+            SessionError::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(err: io::Error) -> SessionError {
+        SessionError::Io(err)
+    }
+}
+
+impl From<SettingsError> for SessionError {
+    fn from(err: SettingsError) -> SessionError {
+        match err {
+            SettingsError::Io(err) => SessionError::Io(err),
+            SettingsError::Parse(line) => SessionError::Parse(line),
+        }
+    }
+}
+
+impl From<DecodeSnapshotError> for SessionError {
+    fn from(err: DecodeSnapshotError) -> SessionError {
+        SessionError::Decode(err)
+    }
+}
+
+/// Where a window is and how it’s laid out; see [Window frames](self#window-frames).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowState {
+    /// The frame of the window in screen coordinates, if known.
+    pub frame: Option<Rect>,
+    /// If true, the window is full screen; the frame is then the frame it returns to when it
+    /// leaves full screen.
+    pub full_screen: bool,
+    /// Positions of split view dividers, by names chosen by the app.
+    pub dividers: BTreeMap<String, f64>,
+}
+
+/// Stores snapshots and window states in a directory; see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// Creates a store in a directory, which will be created when saving if it doesn’t exist.
+    pub fn new(dir: impl Into<PathBuf>) -> SessionStore {
+        SessionStore { dir: dir.into() }
+    }
+
+    /// The directory of the store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, window: &str, extension: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}", escape_file_name(window), extension))
+    }
+
+    /// Saves the snapshot of a window, replacing the one saved before.
+    ///
+    /// The file is replaced atomically, so a crash while saving leaves the previous snapshot.
+    pub fn save(&self, window: &str, snapshot: &Snapshot) -> Result<(), SessionError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(window, SNAPSHOT_EXTENSION);
+        let temp_path = self.path(window, "tmp");
+        fs::write(&temp_path, snapshot.to_bytes())?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Loads the snapshot of a window, or returns None if none was saved.
+    pub fn load(&self, window: &str) -> Result<Option<Snapshot>, SessionError> {
+        match fs::read(self.path(window, SNAPSHOT_EXTENSION)) {
+            Ok(bytes) => Ok(Some(Snapshot::from_bytes(&bytes)?)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Removes the snapshot of a window, e.g. when the user closes it.
+    pub fn remove(&self, window: &str) -> Result<(), SessionError> {
+        match fs::remove_file(self.path(window, SNAPSHOT_EXTENSION)) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Saves the state of a window under an autosave name, replacing the one saved before.
+    pub fn save_window_state(&self, name: &str, state: &WindowState) -> Result<(), SessionError> {
+        let temp_path = self.path(name, "window.tmp");
+        let _ = fs::remove_file(&temp_path);
+        let settings = Settings::load(&temp_path)?;
+        if let Some(frame) = state.frame {
+            settings.set("x", frame.origin.x);
+            settings.set("y", frame.origin.y);
+            settings.set("width", frame.size.x);
+            settings.set("height", frame.size.y);
+        }
+        settings.set("full-screen", state.full_screen);
+        for (divider, position) in &state.dividers {
+            settings.set(&format!("{}{}", DIVIDER_PREFIX, divider), *position);
+        }
+        settings.save()?;
+        fs::rename(&temp_path, self.path(name, WINDOW_EXTENSION))?;
+        Ok(())
+    }
+
+    /// Loads the state of a window saved under an autosave name, or returns None if none was
+    /// saved.
+    pub fn load_window_state(&self, name: &str) -> Result<Option<WindowState>, SessionError> {
+        let path = self.path(name, WINDOW_EXTENSION);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let settings = Settings::load(&path)?;
+        let get = |key: &str| settings.get(key).and_then(|value| value.as_float());
+        let frame = match (get("x"), get("y"), get("width"), get("height")) {
+            (Some(x), Some(y), Some(width), Some(height)) => {
+                Some(Rect::new(Point2::new(x, y), Vector2::new(width, height)))
+            }
+            _ => None,
+        };
+        let full_screen = settings
+            .get("full-screen")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        let mut dividers = BTreeMap::new();
+        for key in settings.keys() {
+            if let Some(divider) = key.strip_prefix(DIVIDER_PREFIX) {
+                if let Some(position) = get(&key) {
+                    dividers.insert(divider.to_string(), position);
+                }
+            }
+        }
+        Ok(Some(WindowState {
+            frame,
+            full_screen,
+            dividers,
+        }))
+    }
+
+    /// Removes the state of a window saved under an autosave name.
+    pub fn remove_window_state(&self, name: &str) -> Result<(), SessionError> {
+        match fs::remove_file(self.path(name, WINDOW_EXTENSION)) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Returns the names of all windows with a saved snapshot, sorted, e.g. to reopen them on
+    /// launch.
+    pub fn windows(&self) -> Result<Vec<String>, SessionError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut windows = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SNAPSHOT_EXTENSION) {
+                continue;
+            }
+            let window = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(unescape_file_name);
+            windows.extend(window);
+        }
+        windows.sort();
+        Ok(windows)
+    }
+}
+
+/// Escapes a window name for use in a file name; everything but ASCII letters, digits, `-`, and
+/// `_` is percent-encoded.
+fn escape_file_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// Returns the window name of an escaped file name, or None if it’s not a valid escape.
+fn unescape_file_name(escaped: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut chars = escaped.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            let hex = core::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{self, PersistentState};
+    use crate::view::State;
+    use crate::view_tree::{Context, ViewTree};
+    use core::any::Any;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use std::convert::TryInto;
+    use std::process;
+    use std::sync::Arc;
+
+    /// A view with a persistent value, which it reports to the test.
+    #[derive(Debug)]
+    struct Field {
+        initial: u64,
+        shown: Arc<AtomicU64>,
+    }
+
+    impl PartialEq for Field {
+        fn eq(&self, other: &Field) -> bool {
+            self.initial == other.initial && Arc::ptr_eq(&self.shown, &other.shown)
+        }
+    }
+
+    #[derive(Debug)]
+    struct FieldState(u64);
+
+    impl PersistentState for FieldState {
+        fn encode(&self) -> Vec<u8> {
+            self.0.to_le_bytes().to_vec()
+        }
+        fn decode(saved: &[u8], _: &Context) -> Option<FieldState> {
+            Some(FieldState(u64::from_le_bytes(saved.try_into().ok()?)))
+        }
+    }
+
+    impl State for FieldState {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+        fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+            self
+        }
+        fn save(&self) -> Option<Vec<u8>> {
+            snapshot::save_persistent_state(self)
+        }
+    }
+
+    crate::impl_view! {
+        Field: ();
+        fn new_state(&self, _context) {
+            Arc::new(FieldState(self.initial))
+        }
+        fn body(&self, state: &FieldState) {
+            self.shown.store(state.0, Ordering::SeqCst);
+            Arc::new(())
+        }
+        crate::persistent_state!(FieldState);
+    }
+
+    /// Renders a field in a view tree and returns the value it shows.
+    fn render(views: &mut ViewTree, initial: u64) -> u64 {
+        let shown = Arc::new(AtomicU64::new(0));
+        let field = Field {
+            initial,
+            shown: Arc::clone(&shown),
+        };
+        views.render_root(Arc::new(field), ());
+        shown.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn snapshots_persist_across_stores() {
+        let dir = std::env::temp_dir().join(format!("birb-session-{}", process::id()));
+        let session = SessionStore::new(&dir);
+        assert_eq!(session.windows().unwrap(), Vec::<String>::new());
+        assert!(session.load("main").unwrap().is_none());
+
+        let mut views = ViewTree::new();
+        assert_eq!(render(&mut views, 42), 42);
+        session.save("main", &views.snapshot()).unwrap();
+        session.save("docs/a b.txt", &Snapshot::new()).unwrap();
+
+        // as if on the next launch
+        let session = SessionStore::new(&dir);
+        let windows = session.windows().unwrap();
+        let loaded = session.load("main");
+        session.remove("main").unwrap();
+        let removed = session.load("main");
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(windows, ["docs/a b.txt", "main"]);
+        let mut views = ViewTree::from_snapshot(loaded.unwrap().unwrap());
+        assert_eq!(render(&mut views, 0), 42);
+        assert!(removed.unwrap().is_none());
+    }
+
+    #[test]
+    fn window_states_persist() {
+        let dir = std::env::temp_dir().join(format!("birb-session-windows-{}", process::id()));
+        let session = SessionStore::new(&dir);
+        let mut state = WindowState {
+            frame: Some(Rect::new(Point2::new(10., 20.), Vector2::new(640., 480.))),
+            full_screen: true,
+            dividers: BTreeMap::new(),
+        };
+        state.dividers.insert("sidebar".into(), 220.5);
+        session.save_window_state("main", &state).unwrap();
+        session
+            .save_window_state("other", &WindowState::default())
+            .unwrap();
+
+        let loaded = session.load_window_state("main");
+        let other = session.load_window_state("other");
+        session.remove_window_state("main").unwrap();
+        let removed = session.load_window_state("main");
+        let windows = session.windows();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(loaded.unwrap(), Some(state));
+        assert_eq!(other.unwrap(), Some(WindowState::default()));
+        assert_eq!(removed.unwrap(), None);
+        assert_eq!(windows.unwrap(), Vec::<String>::new());
+    }
+}
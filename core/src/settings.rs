@@ -0,0 +1,467 @@
+//! Persisted app settings.
+//!
+//! Settings are stored as a flat list of TOML key/value pairs, e.g.:
+//!
+//! ```toml
+//! show-sidebar = true
+//! font-size = 13.0
+//! theme = "dark"
+//! ```
+
+use core::fmt;
+use parking_lot::{Mutex, RwLock};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A setting value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Value {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float; integers will be converted.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            Value::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        match self {
+            Value::Bool(value) => value.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) if value.is_finite() && value.fract() == 0. => {
+                format!("{:.1}", value)
+            }
+            Value::Float(value) if value.is_nan() => "nan".into(),
+            Value::Float(value) if value.is_infinite() => {
+                if *value > 0. { "inf" } else { "-inf" }.into()
+            }
+            Value::Float(value) => value.to_string(),
+            Value::String(value) => {
+                let mut out = String::from("\"");
+                for c in value.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+                out
+            }
+        }
+    }
+
+    fn from_toml(s: &str) -> Option<Value> {
+        match s {
+            "true" => return Some(Value::Bool(true)),
+            "false" => return Some(Value::Bool(false)),
+            "nan" | "+nan" | "-nan" => return Some(Value::Float(f64::NAN)),
+            "inf" | "+inf" => return Some(Value::Float(f64::INFINITY)),
+            "-inf" => return Some(Value::Float(f64::NEG_INFINITY)),
+            _ => (),
+        }
+        if let Some(s) = s.strip_prefix('"') {
+            let s = s.strip_suffix('"')?;
+            let mut out = String::new();
+            let mut chars = s.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    out.push(c);
+                    continue;
+                }
+                match chars.next()? {
+                    '"' => out.push('"'),
+                    '\\' => out.push('\\'),
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'u' => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        out.push(core::char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                    }
+                    _ => return None,
+                }
+            }
+            return Some(Value::String(out));
+        }
+        let number = s.replace('_', "");
+        if let Ok(value) = number.parse() {
+            Some(Value::Int(value))
+        } else {
+            number.parse().ok().map(Value::Float)
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Value {
+        Value::Int(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::String(value)
+    }
+}
+
+/// Errors that may occur when loading or saving settings.
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    /// The settings file could not be parsed. Contains the line number.
+    Parse(usize),
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(err: io::Error) -> SettingsError {
+        SettingsError::Io(err)
+    }
+}
+
+/// A settings change listener; called with the key and the new value.
+pub type SettingsListener = dyn Fn(&str, Option<&Value>) + Send + Sync;
+
+#[derive(Default)]
+struct Listeners {
+    listeners: Vec<(usize, Arc<SettingsListener>)>,
+    next_id: usize,
+}
+
+struct SettingsInner {
+    values: BTreeMap<String, Value>,
+    path: Option<PathBuf>,
+}
+
+/// A persisted key-value settings store, meant to be passed down the view tree in the context.
+///
+/// This is a cheap handle; clones share the same state. Changes are not written to disk until
+/// [`save`](Self::save) is called.
+#[derive(Clone)]
+pub struct Settings {
+    inner: Arc<RwLock<SettingsInner>>,
+    listeners: Arc<Mutex<Listeners>>,
+}
+
+/// Identifies a settings change listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(usize);
+
+impl Settings {
+    /// Creates an empty settings store that is not backed by a file.
+    pub fn in_memory() -> Settings {
+        Settings {
+            inner: Arc::new(RwLock::new(SettingsInner {
+                values: BTreeMap::new(),
+                path: None,
+            })),
+            listeners: Arc::default(),
+        }
+    }
+
+    /// Loads settings from a file. If the file does not exist, the store will be empty and the
+    /// file will be created when saving.
+    pub fn load(path: impl AsRef<Path>) -> Result<Settings, SettingsError> {
+        let path = path.as_ref();
+        let values = match fs::read_to_string(path) {
+            Ok(contents) => parse(&contents)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let settings = Settings::in_memory();
+        {
+            let mut inner = settings.inner.write();
+            inner.values = values;
+            inner.path = Some(path.to_path_buf());
+        }
+        Ok(settings)
+    }
+
+    /// Writes the settings to their file, if there is one.
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let inner = self.inner.read();
+        if let Some(path) = &inner.path {
+            let mut contents = String::new();
+            for (key, value) in &inner.values {
+                contents.push_str(&format!("{} = {}\n", format_key(key), value.to_toml()));
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value for a key.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.inner.read().values.get(key).cloned()
+    }
+
+    /// Returns all keys that have a value, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        self.inner.read().values.keys().cloned().collect()
+    }
+
+    /// Sets the value for a key and notifies listeners if it changed.
+    pub fn set(&self, key: &str, value: impl Into<Value>) {
+        let value = value.into();
+        let prev = self
+            .inner
+            .write()
+            .values
+            .insert(key.to_string(), value.clone());
+        if prev.as_ref() != Some(&value) {
+            self.notify(key, Some(&value));
+        }
+    }
+
+    /// Removes the value for a key and notifies listeners if there was one.
+    pub fn remove(&self, key: &str) {
+        let prev = self.inner.write().values.remove(key);
+        if prev.is_some() {
+            self.notify(key, None);
+        }
+    }
+
+    /// Adds a listener that will be called whenever a value changes.
+    pub fn add_listener<F>(&self, listener: F) -> ListenerId
+    where
+        F: 'static + Fn(&str, Option<&Value>) + Send + Sync,
+    {
+        let mut listeners = self.listeners.lock();
+        let id = listeners.next_id;
+        listeners.next_id += 1;
+        listeners.listeners.push((id, Arc::new(listener)));
+        ListenerId(id)
+    }
+
+    /// Removes a listener.
+    pub fn remove_listener(&self, id: ListenerId) {
+        self.listeners.lock().listeners.retain(|(i, _)| *i != id.0);
+    }
+
+    fn notify(&self, key: &str, value: Option<&Value>) {
+        // clone the list so listeners can modify settings
+        let listeners: Vec<_> = self
+            .listeners
+            .lock()
+            .listeners
+            .iter()
+            .map(|(_, listener)| Arc::clone(listener))
+            .collect();
+        for listener in listeners {
+            listener(key, value);
+        }
+    }
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.read();
+        f.debug_struct("Settings")
+            .field("path", &inner.path)
+            .field("values", &inner.values)
+            .finish()
+    }
+}
+
+/// Context types that provide settings.
+pub trait SettingsContext {
+    fn settings(&self) -> &Settings;
+}
+
+impl SettingsContext for Settings {
+    fn settings(&self) -> &Settings {
+        self
+    }
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn format_key(key: &str) -> String {
+    if is_bare_key(key) {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_toml()
+    }
+}
+
+fn parse(contents: &str) -> Result<BTreeMap<String, Value>, SettingsError> {
+    let mut values = BTreeMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = SettingsError::Parse(i + 1);
+        let (key, value) = if let Some(quoted) = line.strip_prefix('"') {
+            // quoted key; find the closing quote
+            let mut escaped = false;
+            let end = quoted
+                .char_indices()
+                .find(|(_, c)| {
+                    let is_end = !escaped && *c == '"';
+                    escaped = !escaped && *c == '\\';
+                    is_end
+                })
+                .map(|(i, _)| i + 2)
+                .ok_or(error)?;
+            let key = match Value::from_toml(&line[..end]) {
+                Some(Value::String(key)) => key,
+                _ => return Err(SettingsError::Parse(i + 1)),
+            };
+            let rest = line[end..].trim_start();
+            match rest.strip_prefix('=') {
+                Some(value) => (key, value),
+                None => return Err(SettingsError::Parse(i + 1)),
+            }
+        } else {
+            let eq = line.find('=').ok_or(error)?;
+            let key = line[..eq].trim();
+            if !is_bare_key(key) {
+                return Err(SettingsError::Parse(i + 1));
+            }
+            (key.to_string(), &line[eq + 1..])
+        };
+        let value = Value::from_toml(value.trim()).ok_or(SettingsError::Parse(i + 1))?;
+        values.insert(key, value);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn parse_values() {
+        let values = parse(
+            "# comment\n\
+             show-sidebar = true\n\
+             \n\
+             font_size = 13.5\n\
+             count = 1_000\n\
+             limit = -inf\n\
+             theme = \"dark \\\"blue\\\"\\u00E9\"\n\
+             \"a key = with spaces\" = false\n",
+        )
+        .unwrap();
+        assert_eq!(values["show-sidebar"], Value::Bool(true));
+        assert_eq!(values["font_size"], Value::Float(13.5));
+        assert_eq!(values["count"], Value::Int(1000));
+        assert_eq!(values["limit"], Value::Float(f64::NEG_INFINITY));
+        assert_eq!(values["theme"], Value::from("dark \"blue\"é"));
+        assert_eq!(values["a key = with spaces"], Value::Bool(false));
+        assert_eq!(values.len(), 6);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let line = |contents| match parse(contents) {
+            Err(SettingsError::Parse(line)) => line,
+            _ => panic!("{:?} should not parse", contents),
+        };
+        assert_eq!(line("a = 1\nb"), 2);
+        assert_eq!(line("a b = 1"), 1);
+        assert_eq!(line("a = \"unterminated"), 1);
+        assert_eq!(line("\n\"a\" 1"), 2);
+        assert_eq!(line("a = what"), 1);
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("birb-settings-{}", process::id()))
+            .join("settings.toml");
+        let settings = Settings::load(&path).unwrap();
+        assert!(settings.keys().is_empty());
+        settings.set("flag", true);
+        settings.set("size", 12.);
+        settings.set("ratio", 0.25);
+        settings.set("count", -3_i64);
+        settings.set("name", "tab\tquote\" \u{1}");
+        settings.set("weird key", f64::INFINITY);
+        settings.save().unwrap();
+
+        let loaded = Settings::load(&path).unwrap();
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(loaded.keys(), settings.keys());
+        for key in settings.keys() {
+            assert_eq!(loaded.get(&key), settings.get(&key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn listener_ids_are_not_reused() {
+        let settings = Settings::in_memory();
+        let a = settings.add_listener(|_, _| ());
+        let b = settings.add_listener(|_, _| ());
+        settings.remove_listener(b);
+        let c = settings.add_listener(|_, _| ());
+        assert_ne!(b, c);
+        settings.remove_listener(a);
+        settings.remove_listener(c);
+        assert!(settings.listeners.lock().listeners.is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+//! Shapes.
+
+use crate::rect::{CornerRadius, Rect};
+use cgmath::{InnerSpace, Point2, Vector2};
+use std::f64;
+
+/// A closed shape, e.g. to clip a layer’s subviews to.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
+    /// A rectangle with rounded corners.
+    RoundedRect {
+        rect: Rect,
+        corner_radius: CornerRadius,
+    },
+    /// The ellipse that fits in a rectangle.
+    Ellipse(Rect),
+    /// A polygon with straight edges between consecutive points and from the last point back to
+    /// the first. May be concave or self-intersecting; points inside it are determined with the
+    /// nonzero winding rule.
+    Polygon(Vec<Point2<f64>>),
+}
+
+impl Shape {
+    /// Returns the smallest axis-aligned rectangle containing the shape.
+    pub fn bounding_box(&self) -> Rect {
+        match self {
+            Shape::RoundedRect { rect, .. } | Shape::Ellipse(rect) => *rect,
+            Shape::Polygon(points) => {
+                if points.is_empty() {
+                    return Rect::zero();
+                }
+                let mut min = Point2::new(f64::INFINITY, f64::INFINITY);
+                let mut max = Point2::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+                for point in points {
+                    min.x = min.x.min(point.x);
+                    min.y = min.y.min(point.y);
+                    max.x = max.x.max(point.x);
+                    max.y = max.y.max(point.y);
+                }
+                Rect::new(min, max - min)
+            }
+        }
+    }
+
+    /// Returns true if the point is inside the shape.
+    pub fn contains(&self, point: Point2<f64>) -> bool {
+        match self {
+            Shape::RoundedRect {
+                rect,
+                corner_radius,
+            } => rect.contains_rounded(point, *corner_radius),
+            Shape::Ellipse(_) => self.distance(point) <= 0.,
+            Shape::Polygon(points) => winding_number(points, point) != 0,
+        }
+    }
+
+    /// Returns the signed distance from a point to the edge of the shape; negative inside.
+    /// Approximate for ellipses.
+    pub fn distance(&self, point: Point2<f64>) -> f64 {
+        match self {
+            Shape::RoundedRect {
+                rect,
+                corner_radius,
+            } => rect.rounded_distance(point, *corner_radius),
+            Shape::Ellipse(rect) => {
+                let radii = rect.size / 2.;
+                if radii.x <= 0. || radii.y <= 0. {
+                    return f64::INFINITY;
+                }
+                // distance in the ellipse’s unit circle space, scaled back by the gradient
+                let offset = point - rect.center();
+                let k0 = Vector2::new(offset.x / radii.x, offset.y / radii.y).magnitude();
+                let k1 = Vector2::new(offset.x / radii.x.powi(2), offset.y / radii.y.powi(2))
+                    .magnitude();
+                if k1 == 0. {
+                    return -radii.x.min(radii.y);
+                }
+                k0 * (k0 - 1.) / k1
+            }
+            Shape::Polygon(points) => {
+                let mut distance = f64::INFINITY;
+                for (i, a) in points.iter().enumerate() {
+                    let b = points[(i + 1) % points.len()];
+                    distance = distance.min(segment_distance(point, *a, b));
+                }
+                if winding_number(points, point) != 0 {
+                    -distance
+                } else {
+                    distance
+                }
+            }
+        }
+    }
+}
+
+impl From<Rect> for Shape {
+    fn from(rect: Rect) -> Shape {
+        Shape::RoundedRect {
+            rect,
+            corner_radius: CornerRadius::default(),
+        }
+    }
+}
+
+/// Returns the distance from a point to a line segment.
+fn segment_distance(point: Point2<f64>, a: Point2<f64>, b: Point2<f64>) -> f64 {
+    let edge = b - a;
+    let length2 = edge.magnitude2();
+    let t = if length2 > 0. {
+        ((point - a).dot(edge) / length2).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (point - (a + edge * t)).magnitude()
+}
+
+/// Returns how many times a polygon winds around a point.
+fn winding_number(points: &[Point2<f64>], point: Point2<f64>) -> i32 {
+    let mut winding = 0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let cross = (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x);
+        if a.y <= point.y && b.y > point.y && cross > 0. {
+            winding += 1;
+        } else if a.y > point.y && b.y <= point.y && cross < 0. {
+            winding -= 1;
+        }
+    }
+    winding
+}
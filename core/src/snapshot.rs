@@ -0,0 +1,441 @@
+//! Saving view states and restoring them in a new view tree, e.g. across launches.
+//!
+//! Views opt in by saving their state with [`State::save`](crate::State::save) and restoring it
+//! with [`View::restore_state`](crate::View::restore_state), in any encoding they like. A
+//! [`Snapshot`] collects the saved states of all views in a tree by their [`ViewPath`], which
+//! is derived from the types and keys of the views and their ancestors, so it stays the same
+//! across launches as long as the structure of the UI does. With the `serde` feature, snapshots
+//! can be serialized.
+//!
+//! A view tree hydrated from a snapshot restores the state of each view that is added with a
+//! matching path; all other views start out as usual. Open tabs, scroll offsets, and field
+//! contents thus come back as soon as the views that own them are rendered.
+//!
+//! ```no_run
+//! # use birb::snapshot::Snapshot;
+//! # use birb::ViewTree;
+//! # let load = || Snapshot::new();
+//! # let root = std::sync::Arc::new(());
+//! let mut views = ViewTree::from_snapshot(load());
+//! views.render_root(root, ());
+//!
+//! // before quitting
+//! let snapshot = views.snapshot();
+//! ```
+//!
+//! Most states only need to implement [`PersistentState`], which takes care of versioning:
+//!
+//! ```
+//! # use birb::snapshot::{self, PersistentState};
+//! # use birb::*;
+//! # use core::any::Any;
+//! # use core::sync::atomic::{AtomicUsize, Ordering};
+//! # use std::convert::TryInto;
+//! # use std::sync::Arc;
+//! #[derive(Debug, PartialEq)]
+//! struct Tabs;
+//!
+//! #[derive(Debug, Default)]
+//! struct TabsState {
+//!     selected: AtomicUsize,
+//! }
+//!
+//! impl PersistentState for TabsState {
+//!     fn encode(&self) -> Vec<u8> {
+//!         (self.selected.load(Ordering::Relaxed) as u64).to_le_bytes().to_vec()
+//!     }
+//!     fn decode(saved: &[u8], _: &Context) -> Option<TabsState> {
+//!         let selected = u64::from_le_bytes(saved.try_into().ok()?) as usize;
+//!         Some(TabsState { selected: AtomicUsize::new(selected) })
+//!     }
+//! }
+//!
+//! impl State for TabsState {
+//!     fn as_any(&self) -> &dyn Any { self }
+//!     fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> { self }
+//!     fn save(&self) -> Option<Vec<u8>> {
+//!         snapshot::save_persistent_state(self)
+//!     }
+//! }
+//!
+//! impl_view! {
+//!     Tabs: ();
+//!     fn new_state(&self, _context) {
+//!         Arc::new(TabsState::default())
+//!     }
+//!     fn body(&self, state: &TabsState) {
+//!         Arc::new(())
+//!     }
+//!     birb::persistent_state!(TabsState);
+//! }
+//! ```
+//!
+//! To restore UI state on the next launch, snapshots can be stored in a
+//! [`SessionStore`](crate::session::SessionStore).
+
+use crate::view::{AnyState, State};
+use crate::view_tree::Context;
+use core::fmt;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// A stable path to a view in a view tree.
+///
+/// Consists of one segment per view from the root, each with the type name of the view and its
+/// key among its siblings (or its index among unkeyed siblings).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ViewPath(String);
+
+impl ViewPath {
+    /// Returns the path of a root view.
+    pub(crate) fn root(type_name: &str) -> ViewPath {
+        ViewPath(type_name.into())
+    }
+
+    /// Returns the path of a subview with the given key among its siblings.
+    pub(crate) fn child(&self, type_name: &str, key: impl fmt::Display) -> ViewPath {
+        ViewPath(format!("{}/{}{}", self.0, type_name, key))
+    }
+
+    /// Returns the path as a string, with segments separated by slashes.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ViewPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Saved view states, by view path; see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    states: BTreeMap<ViewPath, Vec<u8>>,
+}
+
+impl Snapshot {
+    /// Creates an empty snapshot.
+    pub fn new() -> Snapshot {
+        Snapshot::default()
+    }
+
+    /// Returns the number of saved states.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns true if there are no saved states.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Returns the saved state of a view.
+    pub fn get(&self, path: &ViewPath) -> Option<&[u8]> {
+        self.states.get(path).map(|state| &**state)
+    }
+
+    /// Saves the state of a view, replacing any state saved at the same path.
+    pub fn insert(&mut self, path: ViewPath, state: Vec<u8>) {
+        self.states.insert(path, state);
+    }
+
+    /// Removes and returns the saved state of a view.
+    pub fn remove(&mut self, path: &ViewPath) -> Option<Vec<u8>> {
+        self.states.remove(path)
+    }
+
+    /// Iterates over all saved states, ordered by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&ViewPath, &[u8])> {
+        self.states.iter().map(|(path, state)| (path, &**state))
+    }
+}
+
+/// Identifies encoded snapshots.
+const MAGIC: &[u8; 8] = b"birbsnap";
+
+/// Errors that may occur when decoding a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeSnapshotError {
+    /// The data is not an encoded snapshot.
+    InvalidHeader,
+    /// The data ended early or contains invalid paths.
+    Corrupt,
+}
+
+impl fmt::Display for DecodeSnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeSnapshotError::InvalidHeader => write!(f, "not a snapshot"),
+            DecodeSnapshotError::Corrupt => write!(f, "corrupt snapshot"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeSnapshotError {}
+
+impl Snapshot {
+    /// Encodes the snapshot in a compact binary format that doesn’t depend on serde.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.states.len() as u32).to_le_bytes());
+        for (path, state) in &self.states {
+            bytes.extend_from_slice(&(path.0.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.0.as_bytes());
+            bytes.extend_from_slice(&(state.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(state);
+        }
+        bytes
+    }
+
+    /// Decodes a snapshot encoded with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, DecodeSnapshotError> {
+        let mut bytes = match bytes.strip_prefix(&MAGIC[..]) {
+            Some(bytes) => bytes,
+            None => return Err(DecodeSnapshotError::InvalidHeader),
+        };
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeSnapshotError> {
+            if bytes.len() < len {
+                return Err(DecodeSnapshotError::Corrupt);
+            }
+            let (taken, rest) = bytes.split_at(len);
+            *bytes = rest;
+            Ok(taken)
+        }
+        fn take_len(bytes: &mut &[u8]) -> Result<usize, DecodeSnapshotError> {
+            let mut len = [0; 4];
+            len.copy_from_slice(take(bytes, 4)?);
+            Ok(u32::from_le_bytes(len) as usize)
+        }
+
+        let mut snapshot = Snapshot::new();
+        for _ in 0..take_len(&mut bytes)? {
+            let len = take_len(&mut bytes)?;
+            let path = core::str::from_utf8(take(&mut bytes, len)?)
+                .map_err(|_| DecodeSnapshotError::Corrupt)?;
+            let len = take_len(&mut bytes)?;
+            let state = take(&mut bytes, len)?;
+            snapshot.insert(ViewPath(path.into()), state.to_vec());
+        }
+        Ok(snapshot)
+    }
+}
+
+/// A view state that is saved in snapshots, e.g. to restore it on the next launch; see the
+/// [module documentation](self).
+///
+/// The state must still forward [`State::save`] to [`save_persistent_state`], and its view must
+/// restore it with [`persistent_state`](crate::persistent_state) or
+/// [`restore_persistent_state`].
+pub trait PersistentState<Ctx = ()>: State<Ctx> + Sized {
+    /// The version of the encoding. Saved states with a different version are discarded, so
+    /// this should be changed whenever the encoding does.
+    const VERSION: u32 = 0;
+
+    /// Encodes the state. Should be small, since all saved states are kept in memory.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Decodes a saved state, or returns None if it’s invalid.
+    fn decode(saved: &[u8], context: &Context<Ctx>) -> Option<Self>;
+}
+
+/// Saves a [`PersistentState`], prefixed with its version; for use in [`State::save`].
+pub fn save_persistent_state<S, Ctx>(state: &S) -> Option<Vec<u8>>
+where
+    S: PersistentState<Ctx>,
+{
+    let mut saved = S::VERSION.to_le_bytes().to_vec();
+    saved.extend(state.encode());
+    Some(saved)
+}
+
+/// Restores a [`PersistentState`] saved with [`save_persistent_state`]; for use in
+/// [`View::restore_state`](crate::View::restore_state).
+///
+/// Returns None if the state was saved with a different version or can’t be decoded.
+pub fn restore_persistent_state<S, Ctx>(
+    saved: &[u8],
+    context: &Context<Ctx>,
+) -> Option<AnyState<Ctx>>
+where
+    S: PersistentState<Ctx>,
+{
+    if saved.len() < 4 || saved[..4] != S::VERSION.to_le_bytes() {
+        return None;
+    }
+    let state: AnyState<Ctx> = Arc::new(S::decode(&saved[4..], context)?);
+    Some(state)
+}
+
+/// Implements [`View::restore_state`](crate::View::restore_state) for a view whose state is a
+/// [`PersistentState`]; for use inside [`impl_view`](crate::impl_view).
+///
+/// Takes the state type and the context type (which defaults to `()`), e.g.
+/// `persistent_state!(TabsState: Ctx)` in views that are generic over their context.
+#[macro_export]
+macro_rules! persistent_state {
+    ($state:ty) => {
+        $crate::persistent_state!($state: ());
+    };
+    ($state:ty: $ctx:ty) => {
+        fn restore_state(
+            &self,
+            saved: &[u8],
+            context: $crate::Context<$ctx>,
+        ) -> ::core::option::Option<$crate::AnyState<$ctx>> {
+            $crate::snapshot::restore_persistent_state::<$state, $ctx>(saved, &context)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::Layer;
+    use crate::view::View;
+    use crate::view_tree::ViewTree;
+    use core::any::Any;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::convert::TryInto;
+
+    /// Shows a selected tab, and reports which one to the test.
+    #[derive(Debug)]
+    struct Tabs {
+        key: u64,
+        initial: usize,
+        shown: Arc<AtomicUsize>,
+    }
+
+    impl PartialEq for Tabs {
+        fn eq(&self, other: &Tabs) -> bool {
+            self.key == other.key
+                && self.initial == other.initial
+                && Arc::ptr_eq(&self.shown, &other.shown)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TabsState {
+        selected: usize,
+    }
+
+    impl PersistentState for TabsState {
+        fn encode(&self) -> Vec<u8> {
+            (self.selected as u64).to_le_bytes().to_vec()
+        }
+        fn decode(saved: &[u8], _: &Context) -> Option<TabsState> {
+            let selected = u64::from_le_bytes(saved.try_into().ok()?) as usize;
+            Some(TabsState { selected })
+        }
+    }
+
+    impl State for TabsState {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+        fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+            self
+        }
+        fn save(&self) -> Option<Vec<u8>> {
+            save_persistent_state(self)
+        }
+    }
+
+    crate::impl_view! {
+        Tabs: ();
+        fn new_state(&self, _context) {
+            Arc::new(TabsState { selected: self.initial })
+        }
+        fn body(&self, state: &TabsState) {
+            self.shown.store(state.selected, Ordering::SeqCst);
+            Arc::new(())
+        }
+        fn key(&self) -> Option<u64> {
+            Some(self.key)
+        }
+        crate::persistent_state!(TabsState);
+    }
+
+    /// Renders tab views with the given keys and initial selections, and returns what they show.
+    fn render(tree: &mut ViewTree, tabs: &[(u64, usize)]) -> Vec<usize> {
+        let shown: Vec<_> = tabs.iter().map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let subviews = tabs
+            .iter()
+            .zip(&shown)
+            .map(|((key, initial), shown)| {
+                let view: Arc<dyn View> = Arc::new(Tabs {
+                    key: *key,
+                    initial: *initial,
+                    shown: Arc::clone(shown),
+                });
+                view
+            })
+            .collect();
+        tree.render_root(
+            Arc::new(Layer {
+                subviews,
+                ..Layer::default()
+            }),
+            (),
+        );
+        shown
+            .iter()
+            .map(|shown| shown.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    #[test]
+    fn hydrated_trees_restore_saved_states() {
+        let mut tree = ViewTree::new();
+        assert_eq!(render(&mut tree, &[(1, 3), (2, 5)]), [3, 5]);
+        let snapshot = tree.snapshot();
+        assert_eq!(snapshot.len(), 2);
+
+        let decoded = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        let decoded: Vec<_> = decoded.iter().collect();
+        assert_eq!(decoded, snapshot.iter().collect::<Vec<_>>());
+
+        // views without a saved state start out as usual
+        let mut tree = ViewTree::from_snapshot(snapshot);
+        assert_eq!(render(&mut tree, &[(2, 0), (3, 7)]), [5, 7]);
+
+        // each state is restored only once
+        assert_eq!(render(&mut tree, &[(3, 7)]), [7]);
+        assert_eq!(render(&mut tree, &[(2, 0), (3, 7)]), [0, 7]);
+        assert_eq!(render(&mut tree, &[(1, 0), (3, 7)]), [3, 7]);
+    }
+
+    #[test]
+    fn outdated_states_are_discarded() {
+        let mut tree = ViewTree::new();
+        render(&mut tree, &[(1, 3), (2, 5)]);
+        let mut snapshot = tree.snapshot();
+        let path = snapshot.iter().next().unwrap().0.clone();
+        let mut outdated = snapshot.remove(&path).unwrap();
+        outdated[0] += 1;
+        snapshot.insert(path, outdated);
+
+        let mut tree = ViewTree::from_snapshot(snapshot);
+        let mut shown = render(&mut tree, &[(1, 0), (2, 0)]);
+        shown.sort_unstable();
+        assert_eq!(shown, [0, 5]);
+    }
+
+    #[test]
+    fn invalid_snapshots_are_rejected() {
+        let mut snapshot = Snapshot::new();
+        snapshot.insert(ViewPath::root("Tabs"), vec![1, 2, 3]);
+        let bytes = snapshot.to_bytes();
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[1..]).err(),
+            Some(DecodeSnapshotError::InvalidHeader)
+        );
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(DecodeSnapshotError::Corrupt)
+        );
+    }
+}
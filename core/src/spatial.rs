@@ -0,0 +1,197 @@
+//! Spatial indexing.
+
+use crate::rect::Rect;
+use cgmath::Point2;
+use core::hash::Hash;
+use std::collections::{HashMap, HashSet};
+
+/// Indexes rectangles by location so that those intersecting a region can be found without
+/// looking at all of them.
+///
+/// Rectangles are sorted into the cells of a uniform grid; the cell size should be about the
+/// size of typical queries.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex<K> {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<K>>,
+    rects: HashMap<K, Rect>,
+}
+
+impl<K: Copy + Eq + Hash> SpatialIndex<K> {
+    /// Creates an empty index.
+    ///
+    /// # Panics
+    /// Panics if the cell size is not positive.
+    pub fn new(cell_size: f64) -> SpatialIndex<K> {
+        assert!(cell_size > 0., "SpatialIndex: cell size must be positive");
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+            rects: HashMap::new(),
+        }
+    }
+
+    /// The grid cell size.
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    /// Returns true if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Returns the cells covered by a rectangle.
+    fn cells_for(&self, rect: Rect) -> impl Iterator<Item = (i64, i64)> {
+        let min_x = (rect.origin.x / self.cell_size).floor() as i64;
+        let min_y = (rect.origin.y / self.cell_size).floor() as i64;
+        let max_x = ((rect.origin.x + rect.size.x.max(0.)) / self.cell_size).floor() as i64;
+        let max_y = ((rect.origin.y + rect.size.y.max(0.)) / self.cell_size).floor() as i64;
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    }
+
+    /// Inserts an entry, replacing the previous rectangle if the key is already in the index.
+    pub fn insert(&mut self, key: K, rect: Rect) {
+        if let Some(prev) = self.rects.get(&key) {
+            if *prev == rect {
+                return;
+            }
+            self.remove(key);
+        }
+        for cell in self.cells_for(rect) {
+            self.cells.entry(cell).or_default().push(key);
+        }
+        self.rects.insert(key, rect);
+    }
+
+    /// Removes an entry, returning its rectangle.
+    pub fn remove(&mut self, key: K) -> Option<Rect> {
+        let rect = self.rects.remove(&key)?;
+        for cell in self.cells_for(rect) {
+            if let Some(entries) = self.cells.get_mut(&cell) {
+                entries.retain(|entry| *entry != key);
+                if entries.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+        Some(rect)
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.rects.clear();
+    }
+
+    /// Returns the rectangle of an entry.
+    pub fn get(&self, key: K) -> Option<Rect> {
+        self.rects.get(&key).copied()
+    }
+
+    /// Iterates over all entries in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (K, Rect)> + '_ {
+        self.rects.iter().map(|(key, rect)| (*key, *rect))
+    }
+
+    /// Returns all entries whose rectangles intersect the given rectangle.
+    pub fn query(&self, rect: Rect) -> Vec<K> {
+        let mut seen = HashSet::new();
+        let mut results = Vec::new();
+        for cell in self.cells_for(rect) {
+            for key in self.cells.get(&cell).into_iter().flatten() {
+                if seen.insert(*key) && self.rects[key].intersects(rect) {
+                    results.push(*key);
+                }
+            }
+        }
+        results
+    }
+
+    /// Returns all entries whose rectangles contain the given point.
+    pub fn query_point(&self, point: Point2<f64>) -> Vec<K> {
+        let cell = (
+            (point.x / self.cell_size).floor() as i64,
+            (point.y / self.cell_size).floor() as i64,
+        );
+        self.cells
+            .get(&cell)
+            .into_iter()
+            .flatten()
+            .filter(|key| self.rects[key].contains(point))
+            .copied()
+            .collect()
+    }
+
+    /// Returns the smallest rectangle containing all entries.
+    pub fn bounds(&self) -> Option<Rect> {
+        let mut rects = self.rects.values();
+        let first = *rects.next()?;
+        let mut min = first.origin;
+        let mut max = first.origin + first.size;
+        for rect in rects {
+            min.x = min.x.min(rect.origin.x);
+            min.y = min.y.min(rect.origin.y);
+            max.x = max.x.max(rect.origin.x + rect.size.x);
+            max.y = max.y.max(rect.origin.y + rect.size.y);
+        }
+        Some(Rect::new(min, max - min))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(Point2::new(x, y), (width, height).into())
+    }
+
+    fn sorted(mut keys: Vec<u64>) -> Vec<u64> {
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn queries_find_entries_in_every_cell_they_cover() {
+        let mut index = SpatialIndex::new(10.);
+        index.insert(1, rect(5., 5., 10., 10.));
+        index.insert(2, rect(-5., -5., 3., 3.));
+        index.insert(3, rect(12., 2., 2., 2.));
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.cells.len(), 5);
+
+        assert_eq!(index.query_point(Point2::new(6., 6.)), [1]);
+        assert_eq!(index.query_point(Point2::new(14., 14.)), [1]);
+        assert!(index.query_point(Point2::new(16., 14.)).is_empty());
+        assert_eq!(index.query_point(Point2::new(-4., -4.)), [2]);
+        assert_eq!(sorted(index.query(rect(11., 1., 8., 8.))), [1, 3]);
+        assert_eq!(sorted(index.query(rect(-10., -10., 40., 40.))), [1, 2, 3]);
+        assert!(index.query(rect(20., 20., 5., 5.)).is_empty());
+        assert_eq!(index.bounds(), Some(rect(-5., -5., 20., 20.)));
+    }
+
+    #[test]
+    fn moved_and_removed_entries_leave_no_trace() {
+        let mut index = SpatialIndex::new(10.);
+        index.insert(1, rect(5., 5., 10., 10.));
+        index.insert(1, rect(25., 5., 4., 4.));
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.cells.len(), 1);
+        assert_eq!(index.get(1), Some(rect(25., 5., 4., 4.)));
+        assert!(index.query(rect(0., 0., 20., 20.)).is_empty());
+        assert_eq!(index.query_point(Point2::new(26., 6.)), [1]);
+
+        assert_eq!(index.remove(1), Some(rect(25., 5., 4., 4.)));
+        assert_eq!(index.remove(1), None);
+        assert!(index.is_empty());
+        assert!(index.cells.is_empty());
+        assert!(index.query_point(Point2::new(26., 6.)).is_empty());
+        assert_eq!(index.bounds(), None);
+    }
+}
@@ -0,0 +1,682 @@
+//! Tables.
+
+use crate::color::Color;
+use crate::events::KeyCode;
+use crate::focus::Focus;
+use crate::impl_view;
+use crate::layer::{Layer, LayerProps};
+use crate::rect::Rect;
+use crate::view::{AnyState, Fragment, View};
+use crate::view_tree::{Context, Observers};
+use cgmath::{Point2, Vector2};
+use core::cmp::Ordering;
+use core::fmt;
+use parking_lot::Mutex;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Number of rows that are rendered outside the visible area on each side, so that scrolling
+/// doesn’t immediately reveal empty space.
+const OVERSCAN_ROWS: usize = 4;
+
+/// Sort direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// Describes how a table is sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SortDescriptor {
+    /// The key of the column that is sorted by.
+    pub column: u64,
+    pub direction: SortDirection,
+}
+
+#[derive(Debug, Default)]
+struct TableStateInner {
+    sort: Option<SortDescriptor>,
+    /// Keys of selected rows.
+    selection: BTreeSet<u64>,
+    /// The row from which Shift-selection extends.
+    anchor: Option<u64>,
+    /// The row that was selected last; moved by the arrow keys.
+    cursor: Option<u64>,
+    scroll_offset: f64,
+}
+
+/// Sort, selection, and scroll state of a [`Table`].
+///
+/// Clones share the same state, so it can be kept by the app (e.g. to act on the selection); the
+/// table is rendered again when it changes.
+#[derive(Debug, Clone, Default)]
+pub struct TableState {
+    inner: Arc<Mutex<TableStateInner>>,
+    observers: Observers,
+}
+
+impl TableState {
+    pub fn new() -> TableState {
+        TableState::default()
+    }
+
+    /// Renders the view of a context again whenever the state changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// The current sort order, if any.
+    pub fn sort(&self) -> Option<SortDescriptor> {
+        self.inner.lock().sort
+    }
+
+    /// Sets the sort order.
+    pub fn set_sort(&self, sort: Option<SortDescriptor>) {
+        self.inner.lock().sort = sort;
+        self.observers.notify();
+    }
+
+    /// Sorts by a column, or reverses the direction if the table is already sorted by it; as
+    /// when clicking a column header.
+    pub fn toggle_sort(&self, column: u64) {
+        let direction = match self.sort() {
+            Some(sort) if sort.column == column && sort.direction == SortDirection::Ascending => {
+                SortDirection::Descending
+            }
+            _ => SortDirection::Ascending,
+        };
+        self.set_sort(Some(SortDescriptor { column, direction }));
+    }
+
+    /// Keys of the selected rows.
+    pub fn selection(&self) -> BTreeSet<u64> {
+        self.inner.lock().selection.clone()
+    }
+
+    /// Returns true if the row is selected.
+    pub fn is_selected(&self, row: u64) -> bool {
+        self.inner.lock().selection.contains(&row)
+    }
+
+    /// Replaces the selection.
+    pub fn set_selection(&self, selection: BTreeSet<u64>) {
+        {
+            let mut inner = self.inner.lock();
+            inner.cursor = selection.iter().next_back().copied();
+            inner.anchor = inner.cursor;
+            inner.selection = selection;
+        }
+        self.observers.notify();
+    }
+
+    /// The scroll offset in points.
+    pub fn scroll_offset(&self) -> f64 {
+        self.inner.lock().scroll_offset
+    }
+
+    /// Sets the scroll offset in points.
+    pub fn set_scroll_offset(&self, offset: f64) {
+        self.inner.lock().scroll_offset = offset;
+        self.observers.notify();
+    }
+
+    /// Updates the selection for a click on a row.
+    ///
+    /// - `order`: all row keys in display order
+    /// - `shift`: extends the selection from the anchor to the row
+    /// - `command`: toggles the row without affecting other rows
+    /// - `multiple`: whether multiple rows may be selected
+    fn click(&self, row: u64, shift: bool, command: bool, multiple: bool, order: &[u64]) {
+        {
+            let mut inner = self.inner.lock();
+            let anchor = inner.anchor.filter(|anchor| order.contains(anchor));
+            if multiple && shift && anchor.is_some() {
+                let a = order.iter().position(|key| Some(*key) == anchor).unwrap();
+                let b = order.iter().position(|key| *key == row).unwrap_or(a);
+                let (start, end) = (a.min(b), a.max(b));
+                if !command {
+                    inner.selection.clear();
+                }
+                inner.selection.extend(&order[start..=end]);
+            } else if multiple && command {
+                if !inner.selection.remove(&row) {
+                    inner.selection.insert(row);
+                }
+                inner.anchor = Some(row);
+            } else {
+                inner.selection.clear();
+                inner.selection.insert(row);
+                inner.anchor = Some(row);
+            }
+            inner.cursor = Some(row);
+        }
+        self.observers.notify();
+    }
+}
+
+/// Renders a cell for a row.
+pub type CellFn<Row, Ctx> = dyn Fn(&Row) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// Compares two rows for sorting.
+pub type CompareFn<Row> = dyn Fn(&Row, &Row) -> Ordering + Send + Sync;
+
+/// A table column.
+pub struct Column<Row, Ctx> {
+    /// Identifies the column.
+    pub key: u64,
+
+    /// The column title.
+    pub title: String,
+
+    /// The column width in points.
+    pub width: f64,
+
+    /// Renders the cell for a row.
+    pub cell: Arc<CellFn<Row, Ctx>>,
+
+    /// If set, the table can be sorted by this column.
+    pub compare: Option<Arc<CompareFn<Row>>>,
+}
+
+impl<Row, Ctx> Clone for Column<Row, Ctx> {
+    fn clone(&self) -> Self {
+        Column {
+            key: self.key,
+            title: self.title.clone(),
+            width: self.width,
+            cell: Arc::clone(&self.cell),
+            compare: self.compare.clone(),
+        }
+    }
+}
+
+impl<Row, Ctx> fmt::Debug for Column<Row, Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Column")
+            .field("key", &self.key)
+            .field("title", &self.title)
+            .field("width", &self.width)
+            .field("sortable", &self.compare.is_some())
+            .finish()
+    }
+}
+
+impl<Row, Ctx> PartialEq for Column<Row, Ctx> {
+    fn eq(&self, other: &Column<Row, Ctx>) -> bool {
+        self.key == other.key
+            && self.title == other.title
+            && self.width == other.width
+            && Arc::ptr_eq(&self.cell, &other.cell)
+            && match (&self.compare, &other.compare) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+/// A column header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ColumnHeader {
+    pub key: u64,
+    pub title: String,
+    pub sortable: bool,
+
+    /// The sort direction, if the table is sorted by this column.
+    pub sort: Option<SortDirection>,
+}
+
+/// Renders a column header cell.
+pub type HeaderFn<Ctx> = dyn Fn(&ColumnHeader) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// Returns the key of a row.
+pub type RowKeyFn<Row> = dyn Fn(&Row) -> u64 + Send + Sync;
+
+/// A table with columns, sortable headers, and row selection.
+///
+/// Only rows in the visible area are rendered, so tables may have a very large number of rows.
+/// Event handling is left to the owner of the table, which should call [`click_row`],
+/// [`handle_key`], and [`scroll_by`] in response to input.
+///
+/// [`click_row`]: Self::click_row
+/// [`handle_key`]: Self::handle_key
+/// [`scroll_by`]: Self::scroll_by
+pub struct Table<Row, Ctx> {
+    pub key: Option<u64>,
+
+    /// Table bounds, including the header.
+    pub bounds: Rect,
+
+    /// The columns.
+    pub columns: Vec<Column<Row, Ctx>>,
+
+    /// The rows, in their unsorted order.
+    pub rows: Arc<Vec<Row>>,
+
+    /// Returns a unique key for each row, used for selection and diffing.
+    pub row_key: Arc<RowKeyFn<Row>>,
+
+    /// Row height in points.
+    pub row_height: f64,
+
+    /// Header height in points.
+    pub header_height: f64,
+
+    /// Renders header cells.
+    pub header: Arc<HeaderFn<Ctx>>,
+
+    /// Background color of selected rows.
+    pub selection_color: Color,
+
+    /// Whether multiple rows may be selected.
+    pub multiple_selection: bool,
+
+    /// Sort, selection, and scroll state.
+    pub state: TableState,
+}
+
+impl<Row, Ctx> fmt::Debug for Table<Row, Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("bounds", &self.bounds)
+            .field("columns", &self.columns)
+            .field("rows", &self.rows.len())
+            .field("row_height", &self.row_height)
+            .field("header_height", &self.header_height)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<Row, Ctx> PartialEq for Table<Row, Ctx> {
+    fn eq(&self, other: &Table<Row, Ctx>) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && self.columns == other.columns
+            && Arc::ptr_eq(&self.rows, &other.rows)
+            && Arc::ptr_eq(&self.row_key, &other.row_key)
+            && self.row_height == other.row_height
+            && self.header_height == other.header_height
+            && Arc::ptr_eq(&self.header, &other.header)
+            && self.selection_color == other.selection_color
+            && self.multiple_selection == other.multiple_selection
+            && Arc::ptr_eq(&self.state.inner, &other.state.inner)
+    }
+}
+
+impl<Row, Ctx> Table<Row, Ctx> {
+    /// Returns row indices in display order.
+    fn sorted_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<_> = (0..self.rows.len()).collect();
+        let sort = self.state.sort();
+        let compare = sort.and_then(|sort| {
+            self.columns
+                .iter()
+                .find(|column| column.key == sort.column)
+                .and_then(|column| column.compare.as_ref())
+        });
+        if let (Some(sort), Some(compare)) = (sort, compare) {
+            indices.sort_by(|a, b| {
+                let ordering = compare(&self.rows[*a], &self.rows[*b]);
+                match sort.direction {
+                    SortDirection::Ascending => ordering,
+                    SortDirection::Descending => ordering.reverse(),
+                }
+            });
+        }
+        indices
+    }
+
+    /// Returns the keys of all rows in display order.
+    pub fn row_order(&self) -> Vec<u64> {
+        self.sorted_indices()
+            .into_iter()
+            .map(|i| (self.row_key)(&self.rows[i]))
+            .collect()
+    }
+
+    /// Height of the area in which rows are visible.
+    fn viewport_height(&self) -> f64 {
+        (self.bounds.size.y - self.header_height).max(0.)
+    }
+
+    fn max_scroll_offset(&self) -> f64 {
+        (self.rows.len() as f64 * self.row_height - self.viewport_height()).max(0.)
+    }
+
+    /// Returns the range of rows (in display order) that should be rendered.
+    fn visible_rows(&self) -> Range<usize> {
+        if self.row_height <= 0. {
+            return 0..self.rows.len();
+        }
+        let offset = self.state.scroll_offset();
+        let first = (offset / self.row_height).floor().max(0.) as usize;
+        let last = ((offset + self.viewport_height()) / self.row_height).ceil() as usize;
+        first.saturating_sub(OVERSCAN_ROWS)..(last + OVERSCAN_ROWS).min(self.rows.len())
+    }
+
+    /// Returns the key of the row at a point in the table’s coordinate system.
+    pub fn row_at(&self, point: Point2<f64>) -> Option<u64> {
+        let y = point.y - self.bounds.origin.y - self.header_height;
+        if y < 0. || self.row_height <= 0. || point.y > self.bounds.origin.y + self.bounds.size.y {
+            return None;
+        }
+        let index = ((y + self.state.scroll_offset()) / self.row_height).floor() as usize;
+        self.sorted_indices()
+            .get(index)
+            .map(|i| (self.row_key)(&self.rows[*i]))
+    }
+
+    /// Updates the selection for a click on a row, with the usual Shift and Command semantics.
+    pub fn click_row(&self, row: u64, shift: bool, command: bool) {
+        let order = self.row_order();
+        self.state
+            .click(row, shift, command, self.multiple_selection, &order);
+    }
+
+    /// Scrolls by a delta in points, clamped to the content.
+    pub fn scroll_by(&self, delta: f64) {
+        let offset = (self.state.scroll_offset() + delta).clamp(0., self.max_scroll_offset());
+        self.state.set_scroll_offset(offset);
+    }
+
+    /// Handles keyboard navigation: the up and down arrow keys move the selection (extending it
+    /// with Shift), Home and End jump to the first and last row, and Page Up and Page Down move
+    /// by a page. The selected row is scrolled into view.
+    ///
+    /// Returns true if the key was handled.
+    pub fn handle_key(&self, code: KeyCode, shift: bool) -> bool {
+        let order = self.row_order();
+        if order.is_empty() {
+            return false;
+        }
+        let cursor = self.state.inner.lock().cursor;
+        let current = cursor.and_then(|cursor| order.iter().position(|key| *key == cursor));
+        let page = if self.row_height > 0. {
+            ((self.viewport_height() / self.row_height).floor() as usize).max(1)
+        } else {
+            1
+        };
+        let last = order.len() - 1;
+
+        let target = match (code, current) {
+            (KeyCode::UpArrow, Some(i)) => i.saturating_sub(1),
+            (KeyCode::DownArrow, Some(i)) => (i + 1).min(last),
+            (KeyCode::UpArrow, None) => last,
+            (KeyCode::DownArrow, None) => 0,
+            (KeyCode::Home, _) => 0,
+            (KeyCode::End, _) => last,
+            (KeyCode::PageUp, i) => i.unwrap_or(0).saturating_sub(page),
+            (KeyCode::PageDown, i) => (i.unwrap_or(0) + page).min(last),
+            _ => return false,
+        };
+
+        self.state.click(
+            order[target],
+            shift && current.is_some(),
+            false,
+            self.multiple_selection,
+            &order,
+        );
+        self.scroll_to_index(target);
+        true
+    }
+
+    /// Scrolls a row into view.
+    pub fn scroll_to_row(&self, row: u64) {
+        if let Some(index) = self.row_order().iter().position(|key| *key == row) {
+            self.scroll_to_index(index);
+        }
+    }
+
+    fn scroll_to_index(&self, index: usize) {
+        let top = index as f64 * self.row_height;
+        let bottom = top + self.row_height;
+        let offset = self.state.scroll_offset();
+        let viewport = self.viewport_height();
+        if top < offset {
+            self.state.set_scroll_offset(top);
+        } else if bottom > offset + viewport {
+            self.state.set_scroll_offset((bottom - viewport).max(0.));
+        }
+    }
+
+    fn render_cells<F>(&self, y: f64, height: f64, mut cell: F) -> Fragment<Ctx>
+    where
+        F: FnMut(&Column<Row, Ctx>) -> Arc<dyn View<Ctx>>,
+        Ctx: 'static,
+    {
+        let mut x = 0.;
+        let mut cells: Fragment<Ctx> = Vec::with_capacity(self.columns.len());
+        for column in &self.columns {
+            cells.push(Arc::new(Layer {
+                key: Some(column.key),
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(x, y), Vector2::new(column.width, height)),
+                    clip_contents: true,
+                    ..LayerProps::default()
+                },
+                subviews: vec![cell(column)],
+                ..Layer::default()
+            }));
+            x += column.width;
+        }
+        cells
+    }
+}
+
+impl_view! {
+    impl<Row> Table<Row, Ctx>
+    where
+        Row: 'static + Send + Sync;
+    fn body(&self, _state: &()) {
+        let sort = self.state.sort();
+        let width = self.bounds.size.x;
+
+        let header_cells = self.render_cells(0., self.header_height, |column| {
+            (self.header)(&ColumnHeader {
+                key: column.key,
+                title: column.title.clone(),
+                sortable: column.compare.is_some(),
+                sort: sort
+                    .filter(|sort| sort.column == column.key)
+                    .map(|sort| sort.direction),
+            })
+        });
+        let header: Arc<dyn View<Ctx>> = Arc::new(Layer {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(0., 0.), Vector2::new(width, self.header_height)),
+                ..LayerProps::default()
+            },
+            subviews: header_cells,
+            ..Layer::default()
+        });
+
+        let indices = self.sorted_indices();
+        let offset = self.state.scroll_offset();
+        let mut rows: Fragment<Ctx> = Vec::new();
+        for position in self.visible_rows() {
+            let row = &self.rows[indices[position]];
+            let key = (self.row_key)(row);
+            let y = position as f64 * self.row_height - offset;
+            rows.push(Arc::new(Layer {
+                key: Some(key),
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(0., y), Vector2::new(width, self.row_height)),
+                    background: if self.state.is_selected(key) {
+                        self.selection_color
+                    } else {
+                        Color::default()
+                    },
+                    ..LayerProps::default()
+                },
+                subviews: self.render_cells(0., self.row_height, |column| (column.cell)(row)),
+                ..Layer::default()
+            }));
+        }
+        let body: Arc<dyn View<Ctx>> = Arc::new(Layer {
+            props: LayerProps {
+                bounds: Rect::new(
+                    Point2::new(0., self.header_height),
+                    Vector2::new(width, self.viewport_height()),
+                ),
+                clip_contents: true,
+                ..LayerProps::default()
+            },
+            subviews: rows,
+            ..Layer::default()
+        });
+
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                clip_contents: true,
+                ..LayerProps::default()
+            },
+            subviews: vec![header, body],
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn focus(&self) -> Option<Focus> {
+        Some(Focus::Focusable)
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.state.observe(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::Fragment;
+
+    /// A table of ten rows keyed 0 to 9, showing four rows at a time, that can be sorted by
+    /// column 1.
+    fn table(multiple_selection: bool) -> Table<u64, ()> {
+        Table {
+            key: None,
+            bounds: Rect::new(Point2::new(0., 0.), Vector2::new(100., 50.)),
+            columns: vec![Column {
+                key: 1,
+                title: "Number".into(),
+                width: 100.,
+                cell: Arc::new(|_: &u64| Arc::new(Fragment::new()) as Arc<dyn View>),
+                compare: Some(Arc::new(|a: &u64, b: &u64| a.cmp(b))),
+            }],
+            rows: Arc::new((0..10).collect()),
+            row_key: Arc::new(|row: &u64| *row),
+            row_height: 10.,
+            header_height: 10.,
+            header: Arc::new(|_: &ColumnHeader| Arc::new(Fragment::new()) as Arc<dyn View>),
+            selection_color: Color::default(),
+            multiple_selection,
+            state: TableState::new(),
+        }
+    }
+
+    fn set(rows: &[u64]) -> BTreeSet<u64> {
+        rows.iter().copied().collect()
+    }
+
+    #[test]
+    fn click_selection() {
+        let table = table(true);
+        table.click_row(2, false, false);
+        assert_eq!(table.state.selection(), set(&[2]));
+        // Command toggles rows
+        table.click_row(5, false, true);
+        assert_eq!(table.state.selection(), set(&[2, 5]));
+        table.click_row(2, false, true);
+        assert_eq!(table.state.selection(), set(&[5]));
+        // Shift selects the range from the last clicked row, even if it was deselected
+        table.click_row(5, true, false);
+        assert_eq!(table.state.selection(), set(&[2, 3, 4, 5]));
+        // and keeps its anchor
+        table.click_row(0, true, false);
+        assert_eq!(table.state.selection(), set(&[0, 1, 2]));
+        // Shift-Command adds the range to the selection
+        table.click_row(0, false, true);
+        table.click_row(8, true, true);
+        assert_eq!(table.state.selection(), set(&[0, 1, 2, 3, 4, 5, 6, 7, 8]));
+        // a plain click starts over
+        table.click_row(4, false, false);
+        assert_eq!(table.state.selection(), set(&[4]));
+    }
+
+    #[test]
+    fn single_selection() {
+        let table = table(false);
+        table.click_row(2, false, false);
+        table.click_row(5, true, false);
+        assert_eq!(table.state.selection(), set(&[5]));
+        table.click_row(7, false, true);
+        assert_eq!(table.state.selection(), set(&[7]));
+        assert!(table.handle_key(KeyCode::DownArrow, true));
+        assert_eq!(table.state.selection(), set(&[8]));
+    }
+
+    #[test]
+    fn ranges_follow_the_sort_order() {
+        let table = table(true);
+        table.state.toggle_sort(1);
+        table.state.toggle_sort(1);
+        assert_eq!(
+            table.state.sort(),
+            Some(SortDescriptor {
+                column: 1,
+                direction: SortDirection::Descending,
+            })
+        );
+        assert_eq!(table.row_order(), (0..10).rev().collect::<Vec<_>>());
+        assert_eq!(table.row_at(Point2::new(5., 15.)), Some(9));
+
+        table.click_row(8, false, false);
+        table.click_row(6, true, false);
+        assert_eq!(table.state.selection(), set(&[6, 7, 8]));
+        assert!(table.handle_key(KeyCode::DownArrow, true));
+        assert_eq!(table.state.selection(), set(&[5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn keyboard_selection() {
+        let table = table(true);
+        assert!(!table.handle_key(KeyCode::LeftArrow, false));
+        assert!(table.handle_key(KeyCode::DownArrow, false));
+        assert_eq!(table.state.selection(), set(&[0]));
+        assert!(table.handle_key(KeyCode::DownArrow, true));
+        assert!(table.handle_key(KeyCode::DownArrow, true));
+        assert_eq!(table.state.selection(), set(&[0, 1, 2]));
+        assert!(table.handle_key(KeyCode::UpArrow, false));
+        assert_eq!(table.state.selection(), set(&[1]));
+
+        // the selected row is scrolled into view
+        assert!(table.handle_key(KeyCode::End, false));
+        assert_eq!(table.state.selection(), set(&[9]));
+        assert_eq!(table.state.scroll_offset(), 60.);
+        assert_eq!(table.row_at(Point2::new(5., 15.)), Some(6));
+        assert!(table.handle_key(KeyCode::PageUp, false));
+        assert_eq!(table.state.selection(), set(&[5]));
+        assert_eq!(table.state.scroll_offset(), 50.);
+        assert!(table.handle_key(KeyCode::Home, true));
+        assert_eq!(table.state.selection(), set(&[0, 1, 2, 3, 4, 5]));
+        assert_eq!(table.state.scroll_offset(), 0.);
+    }
+
+    #[test]
+    fn selection_survives_removed_anchors() {
+        let mut table = table(true);
+        table.click_row(3, false, false);
+        table.rows = Arc::new(vec![0, 1, 2, 4, 5]);
+        // the anchor is gone, so Shift acts like a plain click
+        table.click_row(5, true, false);
+        assert_eq!(table.state.selection(), set(&[5]));
+        table.click_row(1, true, false);
+        assert_eq!(table.state.selection(), set(&[1, 2, 4, 5]));
+    }
+}
@@ -0,0 +1,311 @@
+//! Tab views.
+
+use crate::events::{KeyCode, KeyModifiers};
+use crate::impl_view;
+use crate::layer::{Layer, LayerProps};
+use crate::rect::Rect;
+use crate::view::{AnyState, Fragment, View};
+use crate::view_tree::{Context, Observers};
+use core::fmt;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The selected tab in a [`Tabs`] view, identified by its key.
+///
+/// Clones share the same selection, so it can be passed to the tab bar and changed from event
+/// handlers; the tabs are rendered again when it changes.
+#[derive(Clone)]
+pub struct TabSelection {
+    selected: Arc<Mutex<u64>>,
+    observers: Observers,
+}
+
+impl TabSelection {
+    pub fn new(selected: u64) -> TabSelection {
+        TabSelection {
+            selected: Arc::new(Mutex::new(selected)),
+            observers: Observers::new(),
+        }
+    }
+
+    /// The key of the selected tab.
+    pub fn selected(&self) -> u64 {
+        *self.selected.lock()
+    }
+
+    /// Selects a tab.
+    pub fn select(&self, key: u64) {
+        {
+            let mut selected = self.selected.lock();
+            if *selected == key {
+                return;
+            }
+            *selected = key;
+        }
+        self.observers.notify();
+    }
+
+    /// Renders the view of a context again whenever the selection changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// Handles the Command+1 through Command+9 shortcuts (Control on platforms other than macOS;
+    /// see [`KeyModifiers::PRIMARY`]), which select the first nine tabs in order (and Command+9
+    /// always selects the last tab).
+    ///
+    /// - `tabs`: the keys of all tabs in order
+    ///
+    /// Returns true if the key was handled.
+    pub fn handle_shortcut(&self, code: KeyCode, modifiers: KeyModifiers, tabs: &[u64]) -> bool {
+        if modifiers != KeyModifiers::PRIMARY || tabs.is_empty() {
+            return false;
+        }
+        let index = match code {
+            KeyCode::N1 => 0,
+            KeyCode::N2 => 1,
+            KeyCode::N3 => 2,
+            KeyCode::N4 => 3,
+            KeyCode::N5 => 4,
+            KeyCode::N6 => 5,
+            KeyCode::N7 => 6,
+            KeyCode::N8 => 7,
+            KeyCode::N9 => tabs.len() - 1,
+            _ => return false,
+        };
+        match tabs.get(index) {
+            Some(key) => {
+                self.select(*key);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for TabSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TabSelection")
+            .field(&self.selected())
+            .finish()
+    }
+}
+
+/// A tab page.
+pub struct Tab<Ctx> {
+    /// Identifies the tab.
+    pub key: u64,
+
+    /// The tab title, shown in the tab bar.
+    pub title: String,
+
+    /// The tab contents.
+    pub content: Arc<dyn View<Ctx>>,
+}
+
+impl<Ctx> Clone for Tab<Ctx> {
+    fn clone(&self) -> Self {
+        Tab {
+            key: self.key,
+            title: self.title.clone(),
+            content: Arc::clone(&self.content),
+        }
+    }
+}
+
+impl<Ctx> fmt::Debug for Tab<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tab")
+            .field("key", &self.key)
+            .field("title", &self.title)
+            .field("content", &self.content)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for Tab<Ctx> {
+    fn eq(&self, other: &Tab<Ctx>) -> bool {
+        self.key == other.key && self.title == other.title && self.content.eq(&*other.content)
+    }
+}
+
+/// A tab in the tab bar.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TabItem {
+    pub key: u64,
+    pub title: String,
+    pub is_selected: bool,
+}
+
+/// Renders the tab bar.
+///
+/// The selection handle should be used to change tabs when a tab is clicked.
+pub type TabBarFn<Ctx> = dyn Fn(&[TabItem], &TabSelection) -> Arc<dyn View<Ctx>> + Send + Sync;
+
+/// A tab bar with a content area that shows the selected tab.
+pub struct Tabs<Ctx> {
+    pub key: Option<u64>,
+
+    /// The tabs.
+    pub tabs: Vec<Tab<Ctx>>,
+
+    /// The selected tab.
+    pub selection: TabSelection,
+
+    /// Renders the tab bar, which will be placed before the content.
+    pub tab_bar: Arc<TabBarFn<Ctx>>,
+
+    /// Bounds of the content area.
+    pub content_bounds: Rect,
+
+    /// If true, inactive tabs will be kept alive (but hidden, and without receiving pointer
+    /// events) so their state is preserved. Otherwise, they will be removed when switching tabs.
+    pub preserve_state: bool,
+}
+
+impl<Ctx> fmt::Debug for Tabs<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tabs")
+            .field("tabs", &self.tabs)
+            .field("selection", &self.selection)
+            .field("content_bounds", &self.content_bounds)
+            .field("preserve_state", &self.preserve_state)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for Tabs<Ctx> {
+    fn eq(&self, other: &Tabs<Ctx>) -> bool {
+        self.key == other.key
+            && self.tabs == other.tabs
+            && Arc::ptr_eq(&self.selection.selected, &other.selection.selected)
+            && Arc::ptr_eq(&self.tab_bar, &other.tab_bar)
+            && self.content_bounds == other.content_bounds
+            && self.preserve_state == other.preserve_state
+    }
+}
+
+impl_view! {
+    Tabs<Ctx>;
+    fn body(&self, _state: &()) {
+        let selected = self.selection.selected();
+        let items: Vec<_> = self
+            .tabs
+            .iter()
+            .map(|tab| TabItem {
+                key: tab.key,
+                title: tab.title.clone(),
+                is_selected: tab.key == selected,
+            })
+            .collect();
+
+        let mut body: Fragment<Ctx> = vec![(self.tab_bar)(&items, &self.selection)];
+        for tab in &self.tabs {
+            let is_selected = tab.key == selected;
+            if !is_selected && !self.preserve_state {
+                continue;
+            }
+            // every tab has its own host layer, keyed by the tab key so its state is kept
+            body.push(Arc::new(Layer {
+                key: Some(tab.key),
+                props: LayerProps {
+                    bounds: self.content_bounds,
+                    clip_contents: true,
+                    opacity: if is_selected { 1. } else { 0. },
+                    hit_testing: is_selected,
+                    ..LayerProps::default()
+                },
+                subviews: vec![Arc::clone(&tab.content)],
+                ..Layer::default()
+            }));
+        }
+        Arc::new(body)
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.selection.observe(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::NullBackend;
+    use crate::devtools::InspectorNode;
+    use crate::view::ViewId;
+    use crate::{NVTree, ViewTree};
+    use cgmath::{Point2, Vector2};
+    use std::time::Instant;
+
+    /// Returns the key of a view in the tree.
+    fn key_of(node: &InspectorNode, id: ViewId) -> Option<u64> {
+        if node.id == id {
+            return node.key;
+        }
+        node.subviews.iter().find_map(|node| key_of(node, id))
+    }
+
+    #[test]
+    fn hidden_tabs_are_not_hit() {
+        let selection = TabSelection::new(1);
+        let tab = |key| Tab {
+            key,
+            title: key.to_string(),
+            content: Arc::new(Fragment::new()) as Arc<dyn View>,
+        };
+        let tabs = Tabs {
+            key: None,
+            tabs: vec![tab(1), tab(2), tab(3)],
+            selection: selection.clone(),
+            tab_bar: Arc::new(|_: &[TabItem], _: &TabSelection| {
+                Arc::new(Fragment::new()) as Arc<dyn View>
+            }),
+            content_bounds: Rect::new(Point2::new(0., 20.), Vector2::new(100., 80.)),
+            preserve_state: true,
+        };
+
+        let mut tree: ViewTree = ViewTree::new();
+        let mut nv_tree = NVTree::new(Box::new(NullBackend));
+        // the tabs are a fragment, so they need a root layer
+        tree.render_root(
+            Arc::new(Layer {
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(0., 0.), Vector2::new(100., 100.)),
+                    ..LayerProps::default()
+                },
+                subviews: vec![Arc::new(tabs)],
+                ..Layer::default()
+            }),
+            (),
+        );
+        nv_tree.patch_frame(tree.patches()).unwrap();
+
+        let point = Point2::new(50., 50.);
+        let hit = nv_tree.hit_test(point).unwrap();
+        assert_eq!(key_of(&tree.inspect().unwrap(), hit), Some(1));
+
+        selection.select(2);
+        tree.tick(Instant::now());
+        nv_tree.patch_frame(tree.patches()).unwrap();
+        let hit = nv_tree.hit_test(point).unwrap();
+        assert_eq!(key_of(&tree.inspect().unwrap(), hit), Some(2));
+    }
+
+    #[test]
+    fn shortcuts() {
+        let selection = TabSelection::new(1);
+        let tabs = [1, 2, 3];
+        assert!(!selection.handle_shortcut(KeyCode::N2, KeyModifiers::NONE, &tabs));
+        let shift = KeyModifiers::PRIMARY | KeyModifiers::SHIFT;
+        assert!(!selection.handle_shortcut(KeyCode::N2, shift, &tabs));
+        assert!(selection.handle_shortcut(KeyCode::N2, KeyModifiers::PRIMARY, &tabs));
+        assert_eq!(selection.selected(), 2);
+        assert!(selection.handle_shortcut(KeyCode::N9, KeyModifiers::PRIMARY, &tabs));
+        assert_eq!(selection.selected(), 3);
+        assert!(!selection.handle_shortcut(KeyCode::N5, KeyModifiers::PRIMARY, &tabs));
+        assert_eq!(selection.selected(), 3);
+    }
+}
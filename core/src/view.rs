@@ -1,9 +1,11 @@
+use crate::animation::Transitions;
+use crate::events::EventHandlers;
+use crate::focus::Focus;
 use crate::nv_tree::NativeView;
-use crate::rect::Rect;
 use crate::view_tree::Context;
-use cgmath::{Vector2, Zero};
 use core::any::Any;
 use core::fmt;
+use core::str::FromStr;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -12,6 +14,7 @@ use uuid::Uuid;
 /// (this is just a UUID)
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ViewId(u32, u16, u16, [u8; 8]);
 
 impl ViewId {
@@ -20,10 +23,55 @@ impl ViewId {
         let (a, b, c, d) = uuid.as_fields();
         ViewId(a, b, c, *d)
     }
+
+    /// Returns the UUID bytes, e.g. for serialization.
+    pub(crate) fn to_bytes(self) -> [u8; 16] {
+        let ViewId(a, b, c, d) = self;
+        let mut bytes = [0; 16];
+        bytes[0..4].copy_from_slice(&a.to_be_bytes());
+        bytes[4..6].copy_from_slice(&b.to_be_bytes());
+        bytes[6..8].copy_from_slice(&c.to_be_bytes());
+        bytes[8..16].copy_from_slice(&d);
+        bytes
+    }
+
+    /// Creates a view ID from UUID bytes returned by [`to_bytes`](Self::to_bytes).
+    pub(crate) fn from_bytes(bytes: [u8; 16]) -> ViewId {
+        let mut d = [0; 8];
+        d.copy_from_slice(&bytes[8..16]);
+        ViewId(
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u16::from_be_bytes([bytes[4], bytes[5]]),
+            u16::from_be_bytes([bytes[6], bytes[7]]),
+            d,
+        )
+    }
+}
+
+/// Formats the view ID as a hyphenated UUID.
+impl fmt::Display for ViewId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ViewId(a, b, c, d) = self;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+            a, b, c, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]
+        )
+    }
 }
 
-// TODO: state might need to be Arc'd so callback closures can use it
-// or i could also use message enums and a send_message function
+/// An error returned when parsing a [`ViewId`] that isn’t a valid UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseViewIdError;
+
+impl FromStr for ViewId {
+    type Err = ParseViewIdError;
+    fn from_str(s: &str) -> Result<ViewId, ParseViewIdError> {
+        let uuid = Uuid::parse_str(s).map_err(|_| ParseViewIdError)?;
+        let (a, b, c, d) = uuid.as_fields();
+        Ok(ViewId(a, b, c, *d))
+    }
+}
 
 /// Implements the `View` trait for a given struct.
 ///
@@ -34,45 +82,91 @@ impl ViewId {
 /// ```text
 /// impl_view! {
 ///     StructName; // or StructName : ContextType
-///     fn new_state(&self) { // optional
-///         ... -> Box<dyn State>
+///     fn new_state(&self, context) { // optional
+///         ... -> AnyState
 ///     }
-///     fn body(&self, state_variable: StateType) {
-///         ... -> Box<dyn View>
+///     fn body(&self, state_variable: &StateType) {
+///         ... -> AnyView
 ///     }
 ///     (put extra items like key() here, using normal rust syntax)
 /// }
 /// ```
+///
+/// The body may also take the state as `state_variable: Arc<StateType>`, to move clones of it into
+/// event handlers (see [`State`]).
+///
+/// Without a context type, the view is implemented for every context type. Views that embed
+/// subviews of a specific context type (such as an [`AnyView`], which uses the default `()`
+/// context) must name that context type, e.g. `StructName : ()`.
+///
+/// Generic structs list their generic parameters (types and lifetimes, without bounds) in an
+/// `impl<...>` prefix, and can put trait bounds in a where-clause:
+///
+/// ```text
+/// impl_view! {
+///     impl<'a, T> StructName<'a, T> : ContextType // optional context type
+///     where
+///         T: Bounds;
+///     fn body(&self, state_variable: &StateType) {
+///         ...
+///     }
+/// }
+/// ```
+///
+/// When no context type is given, the implementation is generic over a type parameter named
+/// `Ctx`, which can also be used in the struct type and where-clause.
 #[macro_export]
 macro_rules! impl_view {
     (
         $(#[$attr:meta])*
-        $struct:ty;
-        $(fn new_state(&$ns_self:ident, $ns_ctx:ident) $new_state:tt)*
-        fn body(&$self:ident, $state_var:ident: &$state_type:ty) $body:tt
+        impl<$($gen:tt),+> $struct:ty $(: $ctx:ty)? where $($rest:tt)*
+    ) => {
+        $crate::impl_view!(__where [$(#[$attr])*] [$($gen),*] [$struct] [$($ctx)?] [] $($rest)*);
+    };
+    (
+        $(#[$attr:meta])*
+        impl<$($gen:tt),+> $struct:ty $(: $ctx:ty)?; $($rest:tt)*
+    ) => {
+        $crate::impl_view!(__impl [$(#[$attr])*] [$($gen),*] [$struct] [$($ctx)?] [] $($rest)*);
+    };
+    (
+        $(#[$attr:meta])*
+        $struct:ty $(: $ctx:ty)?; $($rest:tt)*
+    ) => {
+        $crate::impl_view!(__impl [$(#[$attr])*] [] [$struct] [$($ctx)?] [] $($rest)*);
+    };
+    (__where $attrs:tt $gens:tt $struct:tt $ctx:tt [$($wc:tt)*] ; $($rest:tt)*) => {
+        $crate::impl_view!(__impl $attrs $gens $struct $ctx [$($wc)*] $($rest)*);
+    };
+    (__where $attrs:tt $gens:tt $struct:tt $ctx:tt [$($wc:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::impl_view!(__where $attrs $gens $struct $ctx [$($wc)* $next] $($rest)*);
+    };
+    (
+        __impl [$($attr:tt)*] [$($gen:tt),*] [$struct:ty] [] [$($wc:tt)*]
+        $(fn new_state(&$ns_self:ident, $ns_ctx:ident) $new_state:tt)?
+        fn body(&$self:ident, $state_var:ident: $($state_type:tt)+) $body:tt
         $($extra:tt)*
     ) => {
-        $(#[$attr])*
-        impl<Ctx: 'static> $crate::View<Ctx> for $struct {
+        $($attr)*
+        impl<$($gen,)* Ctx: 'static> $crate::View<Ctx> for $struct where $($wc)* {
             $crate::impl_view!(__internal1);
-            $($crate::impl_view!(__internal2, Ctx, $ns_self, $ns_ctx, $new_state);)*
-            $crate::impl_view!(__internal3, Ctx, $self, $state_var, $state_type, $body, $struct);
+            $($crate::impl_view!(__internal2, Ctx, $ns_self, $ns_ctx, $new_state);)?
+            $crate::impl_view!(__internal3, Ctx, $self, $state_var, [$($state_type)+], $body, $struct);
             $crate::impl_view!(__internal4, Ctx, $struct);
             $($extra)*
         }
     };
     (
-        $(#[$attr:meta])*
-        $struct:ty : $ctx:ty;
-        $(fn new_state(&$ns_self:ident, $ns_ctx:ident) $new_state:tt)*
-        fn body(&$self:ident, $state_var:ident: &$state_type:ty) $body:tt
+        __impl [$($attr:tt)*] [$($gen:tt),*] [$struct:ty] [$ctx:ty] [$($wc:tt)*]
+        $(fn new_state(&$ns_self:ident, $ns_ctx:ident) $new_state:tt)?
+        fn body(&$self:ident, $state_var:ident: $($state_type:tt)+) $body:tt
         $($extra:tt)*
     ) => {
-        $(#[$attr])*
-        impl $crate::View<$ctx> for $struct {
+        $($attr)*
+        impl<$($gen),*> $crate::View<$ctx> for $struct where $($wc)* {
             $crate::impl_view!(__internal1);
-            $($crate::impl_view!(__internal2, $ctx, $ns_self, $ns_ctx, $new_state);)*
-            $crate::impl_view!(__internal3, $ctx, $self, $state_var, $state_type, $struct);
+            $($crate::impl_view!(__internal2, $ctx, $ns_self, $ns_ctx, $new_state);)?
+            $crate::impl_view!(__internal3, $ctx, $self, $state_var, [$($state_type)+], $body, $struct);
             $crate::impl_view!(__internal4, $ctx, $struct);
             $($extra)*
         }
@@ -85,14 +179,42 @@ macro_rules! impl_view {
     (__internal2, $ctx:ty, $ns_self:ident, $ns_ctx:ident, $new_state:tt) => {
         fn new_state(
             &$ns_self,
-            $ns_ctx:ident: $crate::Context<$ctx>,
-        ) -> Box<dyn $crate::State<$ctx>> {
+            $ns_ctx: $crate::Context<$ctx>,
+        ) -> $crate::AnyState<$ctx> {
             $new_state
         }
     };
-    (__internal3, $ctx:ty, $self:ident, $state_var:ident, $state_type:ty, $body:tt, $struct:ty) => {
-        fn body(&$self, state: &dyn ::core::any::Any) -> ::std::sync::Arc<dyn $crate::View<$ctx>> {
-            if let Some($state_var) = state.downcast_ref::<$state_type>() {
+    (
+        __internal3, $ctx:ty, $self:ident, $state_var:ident, [Arc<$state_type:ty>], $body:tt,
+        $struct:ty
+    ) => {
+        fn body(
+            &$self,
+            state: &$crate::AnyState<$ctx>,
+        ) -> ::std::sync::Arc<dyn $crate::View<$ctx>> {
+            match $crate::State::into_any(::std::sync::Arc::clone(state)).downcast::<$state_type>() {
+                Ok($state_var) => {
+                    fn _dont_complain_about_unused<T>(_: &T) {}
+                    _dont_complain_about_unused(&$state_var);
+                    $body
+                }
+                Err(_) => panic!(
+                    "View::body: invalid state for {}; expected type {}",
+                    stringify!($struct),
+                    stringify!($state_type)
+                ),
+            }
+        }
+    };
+    (
+        __internal3, $ctx:ty, $self:ident, $state_var:ident, [&$state_type:ty], $body:tt,
+        $struct:ty
+    ) => {
+        fn body(
+            &$self,
+            state: &$crate::AnyState<$ctx>,
+        ) -> ::std::sync::Arc<dyn $crate::View<$ctx>> {
+            if let Some($state_var) = state.as_any().downcast_ref::<$state_type>() {
                 fn _dont_complain_about_unused<T>(_: T) {}
                 _dont_complain_about_unused($state_var);
                 $body
@@ -125,21 +247,37 @@ macro_rules! impl_view {
 ///
 /// This trait should probably be implemented using the [`impl_view`] macro.
 ///
+/// The context type defaults to `()`, so views that don’t use contexts can just be written as
+/// `dyn View`.
+///
 /// # Panics
 /// `body` should always return a native view, eventually. Notably, care should be taken when
-/// returning non-native views such that it doesn’t cause a cycle and end up causing an infinite
-/// loop.
-pub trait View<Ctx>: Any + fmt::Debug + Send + Sync {
+/// returning non-native views such that it doesn’t cause a cycle. The view tree will panic with a
+/// description of the cycle if views end up nested more than 256 levels deep.
+pub trait View<Ctx = ()>: Any + fmt::Debug + Send + Sync {
     /// Creates a new state object for this view.
     ///
     /// Will create [`()`] by default.
-    fn new_state(&self, context: Context<Ctx>) -> Box<dyn State<Ctx>> {
+    fn new_state(&self, context: Context<Ctx>) -> AnyState<Ctx> {
         drop(context);
-        Box::new(())
+        Arc::new(())
+    }
+
+    /// Creates a state object from one saved with [`State::save`] in a
+    /// [snapshot](crate::snapshot), instead of a new one.
+    ///
+    /// Returns None by default; if this returns None (e.g. because the saved state is from an
+    /// incompatible version), [`new_state`](Self::new_state) is used instead.
+    fn restore_state(&self, saved: &[u8], context: Context<Ctx>) -> Option<AnyState<Ctx>> {
+        let _ = (saved, context);
+        None
     }
 
     /// Renders the body of this view.
-    fn body(&self, state: &dyn Any) -> Arc<dyn View<Ctx>>;
+    ///
+    /// The state is the one created by [`new_state`](Self::new_state); it can be shared with
+    /// event handlers by cloning the `Arc`.
+    fn body(&self, state: &AnyState<Ctx>) -> Arc<dyn View<Ctx>>;
 
     /// Compares this view to another; used for diffing.
     fn eq(&self, other: &dyn View<Ctx>) -> bool;
@@ -147,6 +285,11 @@ pub trait View<Ctx>: Any + fmt::Debug + Send + Sync {
     /// For downcasting.
     fn as_any(&self) -> &dyn Any;
 
+    /// The name of the view type, for debugging.
+    fn type_name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+
     /// A key used to identify this view in an array of views.
     ///
     /// Should be derived from a `key` property.
@@ -154,8 +297,34 @@ pub trait View<Ctx>: Any + fmt::Debug + Send + Sync {
         None
     }
 
+    /// Returns transitions for when this view is inserted or removed.
+    fn transitions(&self) -> Option<Transitions> {
+        None
+    }
+
+    /// If true, this view’s body will be rendered again on every
+    /// [`ViewTree::tick`](crate::ViewTree::tick), for continuous animations.
+    ///
+    /// Animated views should keep the same structure from frame to frame and only change
+    /// properties.
+    fn is_animated(&self) -> bool {
+        false
+    }
+
+    /// Returns whether this view can be focused or is a focus group.
+    fn focus(&self) -> Option<Focus> {
+        None
+    }
+
+    /// Declares the side effects of this view with [`Context::effect`].
+    ///
+    /// Called after the view is created and every time it is rendered again, before its body.
+    fn effects(&self, state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        let _ = (state, context);
+    }
+
     /// Returns a subview context.
-    fn subview_context(&self, state: &dyn Any, context: &Ctx) -> Option<Ctx> {
+    fn subview_context(&self, state: &AnyState<Ctx>, context: &Ctx) -> Option<Ctx> {
         drop(state);
         drop(context);
         None
@@ -174,6 +343,13 @@ pub trait View<Ctx>: Any + fmt::Debug + Send + Sync {
         )
     }
 
+    /// Returns the event handlers of a native view.
+    ///
+    /// Only native views receive events.
+    fn event_handlers(&self) -> EventHandlers {
+        EventHandlers::default()
+    }
+
     /// For proxy views; should not be overridden usually.
     ///
     /// Will be called iff the views have the same TypeId, so the default implementation that always
@@ -193,19 +369,43 @@ pub enum NativeType {
     TextField,
     Surface,
     VisualEffectView,
+    /// A native view of a kind added by another crate; see [`custom_view`](crate::custom_view).
+    Custom,
 }
 
 /// View state associated with a view.
 ///
-/// Will be dropped right after the view disappears.
-pub trait State<Ctx>: Any + fmt::Debug + Send {
+/// Will be dropped once the view disappears and no event handlers refer to it anymore.
+///
+/// # Sharing state with event handlers
+/// States are stored in an `Arc`, so a view’s body can move clones of its state into the event
+/// handlers of its subviews (using the `Arc<StateType>` form of [`impl_view`]). Since handlers may
+/// run on another thread while the state is also being rendered, states only ever get shared
+/// references: anything that changes after creation goes behind a `Mutex` (from `parking_lot`),
+/// `RwLock`, or an atomic. Handlers should hold locks only briefly and never while calling into
+/// the view tree.
+///
+/// The view tree doesn’t notice changes to states, so a handler that changes state should make
+/// sure the root view is rendered again afterwards; the body will then see the new values.
+pub trait State<Ctx = ()>: Any + fmt::Debug + Send + Sync {
     /// For downcasting.
     fn as_any(&self) -> &dyn Any;
 
+    /// For downcasting to a shared state, e.g. with [`Arc::downcast`].
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+
     /// Called before the component is updated from a new virtual view.
     fn will_update(&self, update: &dyn View<Ctx>) {
         drop(update);
     }
+
+    /// Saves this state for a [snapshot](crate::snapshot), so it can be restored with
+    /// [`View::restore_state`], e.g. on the next launch.
+    ///
+    /// Returns None by default, meaning that the state isn’t saved.
+    fn save(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl_view! {
@@ -221,16 +421,25 @@ impl<Ctx> State<Ctx> for () {
     fn as_any(&self) -> &dyn Any {
         self
     }
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
 }
 
-pub type Fragment<Ctx> = Vec<Arc<dyn View<Ctx>>>;
+/// A shared virtual view, as returned from [`View::body`].
+pub type AnyView<Ctx = ()> = Arc<dyn View<Ctx>>;
+
+/// A shared view state, as returned from [`View::new_state`].
+pub type AnyState<Ctx = ()> = Arc<dyn State<Ctx>>;
+
+pub type Fragment<Ctx = ()> = Vec<AnyView<Ctx>>;
 
 /// A fragment view that expands into its children.
 impl<Ctx: 'static> View<Ctx> for Fragment<Ctx> {
     fn as_any(&self) -> &dyn Any {
         self
     }
-    fn body(&self, _: &dyn Any) -> Arc<dyn View<Ctx>> {
+    fn body(&self, _: &AnyState<Ctx>) -> Arc<dyn View<Ctx>> {
         Arc::new(self.clone())
     }
     fn eq(&self, other: &dyn View<Ctx>) -> bool {
@@ -249,73 +458,3 @@ impl<Ctx: 'static> View<Ctx> for Fragment<Ctx> {
         }
     }
 }
-
-/// A layout delegate for a native view.
-pub trait Layout: Any + fmt::Debug + Send + Sync {
-    /// Performs layout.
-    ///
-    /// - `bounds`: the (strongly) suggested bounds from the superview.
-    /// - `context`: the layout context. Used to access subview layout.
-    fn layout(&self, bounds: Rect, mut context: LayoutContext) -> LayoutResult {
-        LayoutResult {
-            bounds,
-            subview_bounds: context.subviews().map(|_| bounds).collect(),
-            min_size: Vector2::zero(),
-            track_pointer: false,
-            clip_pointer: false,
-        }
-    }
-}
-
-pub struct LayoutContext<'a> {
-    // tree: &'a mut ViewTree,
-    tree: &'a mut (),
-}
-
-impl<'a> LayoutContext<'a> {
-    pub fn subviews(&mut self) -> impl Iterator<Item = SubviewLayout<'_>> {
-        // TODO
-        Vec::new().into_iter()
-    }
-}
-
-pub struct SubviewLayout<'a> {
-    context: &'a mut LayoutContext<'a>,
-}
-
-impl<'a> SubviewLayout<'a> {
-    /// Performs layout if it hasn’t been run already.
-    pub fn force_layout(&mut self) {
-        todo!()
-    }
-
-    /// The subview’s minimum size.
-    /// May be zero if it hasn’t been computed yet (e.g. on first render).
-    /// If it’s important, use `force_layout` to try and get it a frame earlier.
-    pub fn min_size(&self) -> Vector2<f64> {
-        todo!()
-    }
-}
-
-pub struct LayoutResult {
-    /// Own view bounds.
-    bounds: Rect,
-
-    /// Bounds for all subviews, in order.
-    subview_bounds: Vec<Rect>,
-
-    /// Minimum size of this view.
-    min_size: Vector2<f64>,
-
-    /// If true, will consider the layout bounds a pointer tracking rectangle.
-    track_pointer: bool,
-
-    /// If true, will clip all pointer tracking rectangles of child views to this view.
-    clip_pointer: bool,
-}
-
-/// Identity layout.
-///
-/// Use this to use the default layout handler, which copies the bounds given by the superview to
-/// all subviews and itself.
-impl Layout for () {}
@@ -1,8 +1,37 @@
-use crate::nv_tree::Patch;
-use crate::view::{Fragment, State, View, ViewId};
+use crate::accessibility::{Accessibility, AccessibilitySettings, Announcement, LiveRegion};
+use crate::animation::{self, Easing, Transition};
+use crate::devtools::{InspectorNode, Overrides, PropOverride};
+use crate::effects::Effects;
+use crate::embed::{Embed, EmbedId};
+use crate::events::EventHandlers;
+use crate::focus::{Focus, FocusOrder};
+#[cfg(feature = "fuzzing")]
+use crate::fuzzing::ModelView;
+use crate::nv_tree::{NativeView, Patch};
+use crate::print::{Printable, PrintableRegion};
+use crate::snapshot::{Snapshot, ViewPath};
+use crate::view::{AnyState, Fragment, View, ViewId};
+use core::any::Any;
+use core::{fmt, mem};
+use parking_lot::Mutex;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+/// Logs a diff decision (with the `debug-diff` feature enabled).
+#[cfg(feature = "debug-diff")]
+macro_rules! diff_log {
+    ($($arg:tt)+) => {
+        log::debug!(target: "birb::diff", $($arg)+)
+    };
+}
+
+#[cfg(not(feature = "debug-diff"))]
+macro_rules! diff_log {
+    ($($arg:tt)+) => {};
+}
 
 #[derive(Clone, Copy)]
 struct Subregion {
@@ -10,6 +39,36 @@ struct Subregion {
     len: usize,
 }
 
+/// Identifies a subview among its siblings.
+///
+/// If a subview doesn’t have a user-specified key, it’ll be auto-keyed sequentially by index
+/// ignoring user-keyed items, e.g.
+///
+/// ```text
+/// array     [A, B, C(key=1), D(key=2), E]
+/// auto-key   0  1                      2
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SubviewKey {
+    /// A user-specified key.
+    Key(u64),
+    /// An automatically assigned key.
+    AutoKey(u64),
+}
+
+/// Formats the key as the suffix of a [`ViewPath`] segment.
+impl fmt::Display for SubviewKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubviewKey::Key(key) => write!(f, ":{}", key),
+            SubviewKey::AutoKey(key) => write!(f, "#{}", key),
+        }
+    }
+}
+
+/// Views nested deeper than this in a single render are assumed to be part of a cycle.
+const MAX_DEPTH: usize = 256;
+
 /// A node in the view tree.
 struct TreeNode<Ctx> {
     /// The current view object.
@@ -18,6 +77,8 @@ struct TreeNode<Ctx> {
     is_native: bool,
     /// The immediate superview.
     superview: Option<ViewId>,
+    /// The key among the superview’s subviews; None for the root view.
+    key: Option<SubviewKey>,
     /// The closest ancestor that is a native view.
     nv_ancestor: Option<ViewId>,
     /// Subregion in the native ancestor’s subviews.
@@ -25,30 +86,238 @@ struct TreeNode<Ctx> {
     /// how many of them and where they are in the native view tree.
     nv_subregion: Subregion,
     /// The view state.
-    state: Box<dyn State<Ctx>>,
+    state: AnyState<Ctx>,
+    /// Side effects set up by the view.
+    effects: Effects,
     /// An ordered list of all subviews.
     subviews: Vec<ViewId>,
     /// The node’s inherited context.
     context: Ctx,
 }
 
+/// A transition that is currently running.
+struct ActiveTransition {
+    /// The native views that are being animated.
+    targets: Vec<ViewId>,
+    transition: Transition,
+    /// If true, the view that declared the transition is being removed and will be removed for
+    /// real once the transition completes.
+    is_exit: bool,
+    /// When the transition started; set by the first tick after it began, so that it’s timed by
+    /// the same clock as the frames it’s shown in.
+    start: Option<Instant>,
+    duration: Duration,
+    easing: Easing,
+}
+
 /// A view tree; contains a hierarchy of virtual views and manages rendering and updating.
-pub struct ViewTree<Ctx> {
+pub struct ViewTree<Ctx = ()> {
     nodes: HashMap<ViewId, TreeNode<Ctx>>,
     root: Option<ViewId>,
     patches: VecDeque<Patch>,
+    /// Running transitions, keyed by the view that declared them.
+    transitions: HashMap<ViewId, ActiveTransition>,
+    /// System accessibility settings; shared with the [`Accessibility`] handles of views.
+    accessibility: Arc<Mutex<AccessibilitySettings>>,
+    /// Pending screen reader announcements from views.
+    announcements: Arc<Mutex<Vec<Announcement>>>,
+    /// If true, views are being rendered from a new build of a hot-reloaded library.
+    reloading: bool,
+    /// Native view overrides from developer tools.
+    overrides: Overrides,
+    /// If true, bodies are rendered twice to check that they’re pure.
+    strict: bool,
+    /// If true, bookkeeping is checked for stale entries after every render and tick.
+    leak_check: bool,
+    /// When the window was hidden, if it is; ticks are suspended meanwhile.
+    hidden_since: Option<Instant>,
+    /// Saved states that haven’t been restored yet.
+    hydration: Snapshot,
+    /// Views that asked to be rendered again; shared with their contexts.
+    render_requests: Arc<Mutex<RenderRequests>>,
+}
+
+/// Views that asked to be rendered again, and how to tell the event loop about it.
+#[derive(Default)]
+struct RenderRequests {
+    views: HashSet<ViewId>,
+    waker: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+/// Asks the view tree to render a view’s body again; obtained from the view’s
+/// [`Context`](Context::render_requester).
+///
+/// This may be kept around and used from any thread, e.g. when a download finishes. Requests for
+/// views that no longer exist, or from a view tree that has been dropped, are ignored.
+#[derive(Clone)]
+pub struct RenderRequester {
+    view: ViewId,
+    requests: Weak<Mutex<RenderRequests>>,
+}
+
+impl RenderRequester {
+    /// Asks the view tree to render the view again during the next [`tick`](ViewTree::tick).
+    pub fn request_render(&self) {
+        let requests = match self.requests.upgrade() {
+            Some(requests) => requests,
+            None => return,
+        };
+        let waker = {
+            let mut requests = requests.lock();
+            if !requests.views.insert(self.view) {
+                // already pending; the event loop has been woken up before
+                return;
+            }
+            requests.waker.clone()
+        };
+        if let Some(waker) = waker {
+            waker();
+        }
+    }
+}
+
+impl fmt::Debug for RenderRequester {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RenderRequester").field(&self.view).finish()
+    }
+}
+
+struct ObserversInner {
+    requesters: Vec<(u64, RenderRequester)>,
+    next_id: u64,
+}
+
+/// The views that depend on some shared state, such as a handle that is passed down the view tree
+/// in the context.
+///
+/// Views call [`observe`](Self::observe) in [`View::effects`], and the state calls
+/// [`notify`](Self::notify) whenever it changes to have them rendered again.
+#[derive(Clone)]
+pub struct Observers {
+    inner: Arc<Mutex<ObserversInner>>,
+}
+
+impl Observers {
+    pub fn new() -> Observers {
+        Observers {
+            inner: Arc::new(Mutex::new(ObserversInner {
+                requesters: Vec::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// Renders the view of a context again whenever [`notify`](Self::notify) is called, for as
+    /// long as the view exists.
+    ///
+    /// This sets up an [effect](Context::effect), so if it’s called in [`View::effects`] with a
+    /// different set of observers than last time, the view stops observing the old ones.
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        let observers = self.clone();
+        let requester = context.render_requester();
+        let key = Arc::as_ptr(&self.inner) as usize;
+        context.effect(key, move || {
+            let id = {
+                let mut inner = observers.inner.lock();
+                let id = inner.next_id;
+                inner.next_id += 1;
+                inner.requesters.push((id, requester));
+                id
+            };
+            move || {
+                let mut inner = observers.inner.lock();
+                inner.requesters.retain(|(other, _)| *other != id);
+            }
+        });
+    }
+
+    /// Asks all observing views to render again.
+    pub fn notify(&self) {
+        let requesters: Vec<_> = self
+            .inner
+            .lock()
+            .requesters
+            .iter()
+            .map(|(_, requester)| requester.clone())
+            .collect();
+        for requester in requesters {
+            requester.request_render();
+        }
+    }
+
+    /// Returns the number of observing views.
+    pub fn len(&self) -> usize {
+        self.inner.lock().requesters.len()
+    }
+
+    /// Returns true if no views are observing.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Observers {
+    fn default() -> Self {
+        Observers::new()
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Memory statistics of a view tree; see [`ViewTree::memory_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ViewTreeStats {
+    /// Number of views, including views that are running an exit transition.
+    pub views: usize,
+    /// Number of native views.
+    pub native_views: usize,
+    /// Number of event handlers of all views.
+    pub event_handlers: usize,
+    /// Total size of all view states in bytes, not counting anything they allocate themselves.
+    pub state_bytes: usize,
+    /// Number of side effects that are set up.
+    pub effects: usize,
+    /// Number of running transitions.
+    pub transitions: usize,
+    /// Number of native views with property overrides from developer tools.
+    pub prop_overrides: usize,
+    /// Number of patches that haven’t been taken yet.
+    pub pending_patches: usize,
+    /// Number of screen reader announcements that haven’t been taken yet.
+    pub pending_announcements: usize,
+    /// Number of entries that refer to views that no longer exist or aren’t in the tree; should
+    /// always be zero.
+    pub stale_entries: usize,
 }
 
 /// A view’s context.
 #[derive(Debug)]
-pub struct Context<Ctx> {
+pub struct Context<Ctx = ()> {
     // TODO
     context: Ctx,
+    accessibility: Accessibility,
+    effects: Effects,
+    render_requester: RenderRequester,
 }
 
 impl<Ctx> Context<Ctx> {
+    /// Asks the view tree to render this view’s body again during the next
+    /// [`tick`](ViewTree::tick), e.g. after something its body depends on has changed outside of
+    /// the view tree.
     pub fn request_render(&self) {
-        todo!()
+        self.render_requester.request_render();
+    }
+
+    /// Returns a handle for requesting renders of this view later, e.g. from an event handler or
+    /// another thread.
+    pub fn render_requester(&self) -> RenderRequester {
+        self.render_requester.clone()
     }
 
     pub fn request_layout(&self) {
@@ -63,6 +332,29 @@ impl<Ctx> Context<Ctx> {
     pub fn ctx(&self) -> &Ctx {
         &self.context
     }
+
+    /// Returns accessibility settings and services.
+    pub fn accessibility(&self) -> &Accessibility {
+        &self.accessibility
+    }
+
+    /// Sets up a side effect of the view, such as a subscription or an observer.
+    ///
+    /// `setup` is called right away and returns a cleanup function, which will be called when the
+    /// view disappears.
+    ///
+    /// In [`View::effects`], effects are told apart by the order in which they’re declared, like
+    /// subviews without keys. If an effect is declared with a different key than last time, the
+    /// old effect is cleaned up and `setup` is called again; effects that are no longer declared
+    /// are cleaned up. In [`View::new_state`], keys are ignored and effects last as long as the
+    /// state.
+    pub fn effect<K, C>(&self, key: K, setup: impl FnOnce() -> C)
+    where
+        K: PartialEq + Send + 'static,
+        C: FnOnce() + Send + 'static,
+    {
+        self.effects.effect(key, setup);
+    }
 }
 
 impl<Ctx: 'static> ViewTree<Ctx>
@@ -74,7 +366,238 @@ where
             nodes: HashMap::new(),
             root: None,
             patches: VecDeque::new(),
+            transitions: HashMap::new(),
+            accessibility: Arc::new(Mutex::new(AccessibilitySettings::default())),
+            announcements: Arc::new(Mutex::new(Vec::new())),
+            reloading: false,
+            overrides: Overrides::default(),
+            strict: false,
+            leak_check: false,
+            hidden_since: None,
+            hydration: Snapshot::new(),
+            render_requests: Arc::default(),
+        }
+    }
+
+    /// Sets a function that is called whenever a view requests to be rendered again (see
+    /// [`Context::request_render`]), e.g. to wake up the UI thread so it calls
+    /// [`tick`](Self::tick).
+    ///
+    /// It may be called from any thread, and is called once until the next tick.
+    pub fn set_render_waker<F>(&self, waker: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.render_requests.lock().waker = Some(Arc::new(waker));
+    }
+
+    /// Returns true if views have requested to be rendered again since the last
+    /// [`tick`](Self::tick).
+    pub fn has_render_requests(&self) -> bool {
+        !self.render_requests.lock().views.is_empty()
+    }
+
+    /// Creates a view tree that restores view states from a snapshot; see
+    /// [`hydrate`](Self::hydrate).
+    pub fn from_snapshot(snapshot: Snapshot) -> ViewTree<Ctx> {
+        let mut tree = ViewTree::new();
+        tree.hydrate(snapshot);
+        tree
+    }
+
+    /// Restores view states from a [snapshot](crate::snapshot).
+    ///
+    /// Views added from now on whose path has a saved state are created with
+    /// [`View::restore_state`]. Each saved state is only restored once; states of views that are
+    /// never added are kept until the next call. Views that already exist are not affected.
+    pub fn hydrate(&mut self, snapshot: Snapshot) {
+        self.hydration = snapshot;
+    }
+
+    /// Saves the states of all views that opt in with [`State::save`](crate::State::save).
+    ///
+    /// Views that are being removed with an exit transition are skipped.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new();
+        if let Some(root) = self.root {
+            let path = ViewPath::root(self.nodes[&root].view.type_name());
+            self.snapshot_node(root, path, &mut snapshot);
+        }
+        snapshot
+    }
+
+    fn snapshot_node(&self, id: ViewId, path: ViewPath, snapshot: &mut Snapshot) {
+        let node = &self.nodes[&id];
+        for subview in &node.subviews {
+            if self.is_departing(*subview) {
+                continue;
+            }
+            let subview_node = &self.nodes[subview];
+            if let Some(key) = subview_node.key {
+                let path = path.child(subview_node.view.type_name(), key);
+                self.snapshot_node(*subview, path, snapshot);
+            }
+        }
+        if let Some(state) = node.state.save() {
+            snapshot.insert(path, state);
+        }
+    }
+
+    /// Returns the path of an existing view.
+    fn view_path(&self, id: ViewId) -> ViewPath {
+        let node = &self.nodes[&id];
+        match (node.superview, node.key) {
+            (Some(superview), Some(key)) => {
+                self.view_path(superview).child(node.view.type_name(), key)
+            }
+            _ => ViewPath::root(node.view.type_name()),
+        }
+    }
+
+    /// Takes the saved state of a view that is about to be added, if there is one.
+    fn take_saved_state(
+        &mut self,
+        view: &dyn View<Ctx>,
+        parent: Option<(ViewId, SubviewKey)>,
+    ) -> Option<Vec<u8>> {
+        if self.hydration.is_empty() {
+            return None;
+        }
+        let path = match parent {
+            Some((superview, key)) => self.view_path(superview).child(view.type_name(), key),
+            None => ViewPath::root(view.type_name()),
+        };
+        self.hydration.remove(&path)
+    }
+
+    /// Enables or disables strict mode.
+    ///
+    /// In strict mode, every view body is rendered twice and the two results are compared. If they
+    /// differ, rendering panics with the name of the offending view type. This catches bodies that
+    /// depend on anything other than the view’s properties and state (such as randomness, the
+    /// current time, or values behind interior mutability), which would otherwise cause views to be
+    /// updated or replaced on every render. Meant for development only.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Enables or disables the leak check.
+    ///
+    /// With the leak check enabled, the tree checks after every render and tick that none of its
+    /// bookkeeping refers to removed views, and panics with a description of the first stale entry
+    /// otherwise. This is slow, so it’s meant for tests.
+    pub fn set_leak_check(&mut self, leak_check: bool) {
+        self.leak_check = leak_check;
+    }
+
+    /// Returns statistics about the views and bookkeeping in the tree.
+    ///
+    /// View IDs are random and never reused, so an entry for a view that was removed will never be
+    /// reclaimed by a new view; these are counted as stale entries.
+    pub fn memory_stats(&self) -> ViewTreeStats {
+        let mut stats = ViewTreeStats {
+            views: self.nodes.len(),
+            transitions: self.transitions.len(),
+            prop_overrides: self.overrides.props.len(),
+            pending_patches: self.patches.len(),
+            pending_announcements: self.announcements.lock().len(),
+            stale_entries: self.stale_entries().len(),
+            ..ViewTreeStats::default()
+        };
+        for node in self.nodes.values() {
+            if node.is_native {
+                stats.native_views += 1;
+            }
+            stats.event_handlers += node.view.event_handlers().count();
+            stats.state_bytes += mem::size_of_val(&*node.state);
+            stats.effects += node.effects.len();
+        }
+        stats
+    }
+
+    /// Sets the system accessibility settings.
+    ///
+    /// These will be used for all subsequent renders and transitions.
+    pub fn set_accessibility(&mut self, settings: AccessibilitySettings) {
+        {
+            let mut current = self.accessibility.lock();
+            if settings == *current {
+                return;
+            }
+            *current = settings;
+        }
+
+        // re-emit native views, since their appearance may depend on the settings
+        self.reemit_native_views();
+    }
+
+    /// Sets whether the window is visible, e.g. in response to
+    /// [`RawEvent::SetVisibility`](crate::raw_events::RawEvent::SetVisibility).
+    ///
+    /// While the window is hidden, [`tick`](Self::tick) does nothing, so animated views aren’t
+    /// rendered and transitions are paused. They resume where they left off once the window is
+    /// visible again.
+    pub fn set_visible(&mut self, visible: bool, now: Instant) {
+        match (visible, self.hidden_since) {
+            (false, None) => self.hidden_since = Some(now),
+            (true, Some(hidden_since)) => {
+                self.hidden_since = None;
+                let hidden_for = now.saturating_duration_since(hidden_since);
+                for transition in self.transitions.values_mut() {
+                    if let Some(start) = &mut transition.start {
+                        *start += hidden_for;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns true unless the window has been hidden with [`set_visible`](Self::set_visible).
+    pub fn is_visible(&self) -> bool {
+        self.hidden_since.is_none()
+    }
+
+    /// Returns a snapshot of the view tree for an inspector.
+    pub fn inspect(&self) -> Option<InspectorNode> {
+        self.root.map(|root| self.inspect_node(root))
+    }
+
+    /// Highlights the native views of a view on screen, or removes the highlight.
+    ///
+    /// The highlight applies to the native views the view currently consists of.
+    pub fn set_highlighted_view(&mut self, view: Option<ViewId>) {
+        self.overrides.highlighted = match view {
+            Some(view) if self.nodes.contains_key(&view) => self.native_roots(view),
+            _ => Vec::new(),
+        };
+        self.reemit_native_views();
+    }
+
+    /// Overrides a property of the native views of a view, until the override is cleared.
+    ///
+    /// Meant for live design iteration from developer tools. Like highlights, overrides apply to
+    /// the native views the view currently consists of. Returns false if the view doesn’t exist.
+    pub fn set_prop_override(&mut self, view: ViewId, prop: PropOverride) -> bool {
+        if !self.nodes.contains_key(&view) {
+            return false;
+        }
+        for id in self.native_roots(view) {
+            self.overrides.set(id, prop);
+        }
+        self.reemit_native_views();
+        true
+    }
+
+    /// Removes all property overrides of a view.
+    pub fn clear_prop_overrides(&mut self, view: ViewId) {
+        if !self.nodes.contains_key(&view) {
+            return;
         }
+        for id in self.native_roots(view) {
+            self.overrides.props.remove(&id);
+        }
+        self.reemit_native_views();
     }
 
     /// Returns an iterator over available patches.
@@ -82,6 +605,10 @@ where
     /// Does not drain the queue immediately.
     /// Calling `next` will always remove a patch from the queue.
     pub fn patches(&mut self) -> impl Iterator<Item = Patch> + '_ {
+        for announcement in self.announcements.lock().drain(..) {
+            self.patches.push_back(Patch::Announce(announcement));
+        }
+
         struct PatchIterator<'a, T>(&'a mut ViewTree<T>);
         impl<'a, T> Iterator for PatchIterator<'a, T> {
             type Item = Patch;
@@ -96,13 +623,183 @@ where
     /// Renders a root view.
     pub fn render_root(&mut self, view: Arc<dyn View<Ctx>>, context: Ctx) {
         if let Some(root) = self.root {
-            self.diff(root, &view, 0, context);
+            self.diff(root, &view, 0, context, None, 0);
         } else {
             let root_id = ViewId::new();
             self.root = Some(root_id);
-            self.diff(root_id, &view, 0, context);
+            self.diff(root_id, &view, 0, context, None, 0);
             self.patches.push_back(Patch::SetRoot(root_id));
         }
+        self.check_leaks();
+    }
+
+    /// Renders a root view from a new build of a hot-reloaded library (see
+    /// [`hot_reload`](crate::hot_reload)).
+    ///
+    /// Unlike [`render_root`](Self::render_root), every view is updated, since the code of its
+    /// type may have changed. Views keep their state only if both the view type and the state
+    /// type still have the same layout; otherwise they are replaced.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_root(&mut self, view: Arc<dyn View<Ctx>>, context: Ctx) {
+        self.reloading = true;
+        self.render_root(view, context);
+        self.reloading = false;
+    }
+
+    /// Returns all [`Printable`] views in the tree and their native views, for use with
+    /// [`NVTree::export_pdf`](crate::NVTree::export_pdf).
+    pub fn printable_regions(&self) -> Vec<PrintableRegion> {
+        let mut regions = Vec::new();
+        for (id, node) in &self.nodes {
+            if let Some(printable) = node.view.as_any().downcast_ref::<Printable<Ctx>>() {
+                regions.push(PrintableRegion {
+                    native_views: self.native_roots(*id),
+                    page_break: printable.page_break,
+                });
+            }
+        }
+        regions
+    }
+
+    /// Returns the native views of all [`Embed`] views in the tree, for use with a
+    /// [`Compositor`](crate::embed::Compositor).
+    pub fn embed_points(&self) -> HashMap<EmbedId, ViewId> {
+        let mut points = HashMap::new();
+        for (id, node) in &self.nodes {
+            if let Some(embed) = node.view.as_any().downcast_ref::<Embed<Ctx>>() {
+                if let Some(native_view) = self.native_roots(*id).first() {
+                    points.insert(embed.id, *native_view);
+                }
+            }
+        }
+        points
+    }
+
+    /// Returns true if the view exists.
+    pub(crate) fn contains(&self, id: ViewId) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    /// Returns the event handlers of a view, if it exists.
+    pub(crate) fn event_handlers(&self, id: ViewId) -> Option<EventHandlers> {
+        self.nodes.get(&id).map(|node| node.view.event_handlers())
+    }
+
+    /// Returns all focusable views in tree order, along with their focus groups; for use with a
+    /// [`FocusManager`](crate::focus::FocusManager).
+    pub fn focus_order(&self) -> FocusOrder {
+        let mut order = FocusOrder::default();
+        if let Some(root) = self.root {
+            self.collect_focus_order(root, None, &mut order);
+        }
+        order
+    }
+
+    /// Advances running transitions and emits patches for them, and renders animated views and
+    /// views that [requested it](Context::request_render) again.
+    ///
+    /// Views with an exit transition are removed once their transition has completed.
+    ///
+    /// Returns true if there are transitions that are still running or animated views. While the
+    /// window is [hidden](Self::set_visible), this does nothing and returns false.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if self.hidden_since.is_some() {
+            return false;
+        }
+
+        let animated: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.view.is_animated())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &animated {
+            self.rerender(*id);
+        }
+        let requested = mem::take(&mut self.render_requests.lock().views);
+        for id in requested {
+            if !animated.contains(&id) {
+                self.rerender(id);
+            }
+        }
+
+        for transition in self.transitions.values_mut() {
+            transition.start.get_or_insert(now);
+        }
+
+        let mut finished = Vec::new();
+        for (id, transition) in &self.transitions {
+            let start = transition.start.unwrap_or(now);
+            let progress = animation::progress(start, transition.duration, now);
+            let eased = transition.easing.apply(progress);
+            let visibility = if transition.is_exit {
+                1. - eased
+            } else {
+                eased
+            };
+
+            for target in &transition.targets {
+                if let Some(node) = self.nodes.get(target) {
+                    let mut view = self.native_view(*target, &*node.view);
+                    transition.transition.apply(&mut view, visibility);
+                    self.patches.push_back(Patch::Update(*target, view));
+                }
+            }
+
+            if progress >= 1. {
+                finished.push(*id);
+            }
+        }
+
+        for id in finished {
+            let transition = self.transitions.remove(&id).unwrap();
+            if transition.is_exit {
+                self.remove_departed_view(id);
+            } else {
+                // reset to the actual view state
+                for target in transition.targets {
+                    if let Some(node) = self.nodes.get(&target) {
+                        self.patches.push_back(Patch::Update(
+                            target,
+                            self.native_view(target, &*node.view),
+                        ));
+                    }
+                }
+            }
+        }
+
+        self.check_leaks();
+        !self.transitions.is_empty() || !animated.is_empty()
+    }
+
+    /// Renders a view’s body again without changing the view.
+    fn rerender(&mut self, id: ViewId) {
+        // the view may have been removed by rendering one of its ancestors
+        let node = match self.nodes.get(&id) {
+            Some(node) => node,
+            None => return,
+        };
+        let view = Arc::clone(&node.view);
+        let nv_subregion = node.nv_subregion;
+        let context = node.context.clone();
+        let superview = node.superview;
+        let parent = superview.zip(node.key);
+        let len = self
+            .diff(id, &view, nv_subregion.pos, context, parent, 0)
+            .len();
+
+        // the view may now take up a different number of native views
+        if let Some(superview) = superview {
+            let delta = len as isize - nv_subregion.len as isize;
+            if delta != 0 {
+                let index = self.nodes[&superview]
+                    .subviews
+                    .iter()
+                    .position(|subview| *subview == id)
+                    .unwrap();
+                self.shift_nv_subregions(superview, index + 1, delta);
+            }
+        }
     }
 
     /// Diffs a view with its current state in the tree.
@@ -110,25 +807,68 @@ where
     /// - `id`: the view id, for identifying the tree node
     /// - `view`: the new view
     /// - `nv_subregion_start`: the start index for the NV subregion for this view
+    /// - `parent`: the superview, which will be linked if the view is being added, and the key
+    ///   among its subviews
+    /// - `depth`: how deeply nested the view is in this render, for detecting cycles
     ///
     /// Returns native view IDs that are descendants of this view.
+    ///
+    /// # Panics
+    /// Panics if views are nested more than [`MAX_DEPTH`] levels deep, which almost always means
+    /// that a composite view renders itself (directly or indirectly) without ever terminating in
+    /// native views.
     fn diff(
         &mut self,
         id: ViewId,
         view: &Arc<dyn View<Ctx>>,
         nv_subregion_start: usize,
         context: Ctx,
+        parent: Option<(ViewId, SubviewKey)>,
+        depth: usize,
     ) -> Vec<ViewId> {
+        // insertion transitions only apply to subviews inserted into an existing view
+        let animate_insertions = self.nodes.contains_key(&id);
+
         if let Some(node) = self.nodes.get(&id) {
             let mut is_same_type = node.view.as_any().type_id() == view.as_any().type_id();
+            if !is_same_type {
+                diff_log!(
+                    "replacing {} ({} -> {}): type changed",
+                    id,
+                    node.view.type_name(),
+                    view.type_name()
+                );
+            } else if self.reloading {
+                // a type from a different build may have the same ID but a different layout
+                is_same_type = same_layout(node.view.as_any(), view.as_any());
+                if !is_same_type {
+                    diff_log!(
+                        "replacing {} ({}): layout changed in new build",
+                        id,
+                        view.type_name()
+                    );
+                }
+            }
             if is_same_type {
                 // allow proxy views to complain if they’re not actually the same type
                 if !node.view.is_same_type(&**view) {
+                    diff_log!(
+                        "replacing {} ({}): is_same_type returned false",
+                        id,
+                        view.type_name()
+                    );
                     is_same_type = false;
+                } else if self.reloading {
+                    // the old view may be running old code, so always use the new one
+                    diff_log!("reloading {} ({})", id, view.type_name());
+                    self.reload_view(id, view, context.clone());
                 } else {
                     // same type; can be diffed
                     if !node.view.eq(&**view) {
+                        diff_log!("updating {} ({}): not equal", id, view.type_name());
                         self.update_view(id, view);
+                    } else {
+                        diff_log!("keeping {} ({}): equal", id, view.type_name());
                     }
                 }
             }
@@ -139,18 +879,54 @@ where
             }
         } else {
             // does not exist; needs to be added
-            self.add_view(id, view, nv_subregion_start, context);
+            diff_log!("adding {} ({})", id, view.type_name());
+            let saved = self.take_saved_state(&**view, parent);
+            self.add_view(id, view, nv_subregion_start, context, true, saved);
+
+            // link the superview before rendering the body so subviews can find their ancestors
+            if let Some((superview, key)) = parent {
+                let superview_node = &self.nodes[&superview];
+                let nv_ancestor = if superview_node.is_native {
+                    Some(superview)
+                } else {
+                    superview_node.nv_ancestor
+                };
+                let node = self.nodes.get_mut(&id).unwrap();
+                node.superview = Some(superview);
+                node.key = Some(key);
+                node.nv_ancestor = nv_ancestor;
+            }
+        }
+
+        if depth >= MAX_DEPTH {
+            panic!("{}", self.describe_cycle(id));
         }
 
+        // set up or update the node’s effects
+        let node = &self.nodes[&id];
+        let effects_context = self.view_context(id, &node.context, &node.effects);
+        node.effects.begin();
+        node.view.effects(&node.state, &effects_context);
+        node.effects.end();
+
         // render the node’s body
         let node = self.nodes.get_mut(&id).unwrap();
         let body = node.view.body(&node.state);
+        if self.strict {
+            check_pure_body(&*node.view, &*body, &*node.view.body(&node.state));
+        }
         let subview_subregion_start = if node.is_native {
             0
         } else {
             nv_subregion_start
         };
-        let subviews = self.diff_subviews(id, body, subview_subregion_start);
+        let subviews = self.diff_subviews(
+            id,
+            body,
+            subview_subregion_start,
+            animate_insertions,
+            depth + 1,
+        );
 
         let node = self.nodes.get_mut(&id).unwrap();
         node.nv_subregion.pos = nv_subregion_start;
@@ -172,16 +948,16 @@ where
         view: &Arc<dyn View<Ctx>>,
         nv_subregion_start: usize,
         context: Ctx,
+        emit_patch: bool,
+        saved: Option<Vec<u8>>,
     ) {
         let is_native = view.native_type().is_some();
-        let state = view.new_state(Context {
-            // TODO: proper context
-            context: context.clone(),
-        });
+        let effects = Effects::default();
+        let state = self.new_state(id, view, &context, &effects, saved.as_deref());
 
-        if is_native {
+        if emit_patch && is_native {
             self.patches
-                .push_back(Patch::Update(id, view.native_view()));
+                .push_back(Patch::Update(id, self.native_view(id, &**view)));
         }
 
         self.nodes.insert(
@@ -190,28 +966,113 @@ where
                 view: Arc::clone(view),
                 is_native,
                 superview: None,
+                key: None,
                 nv_ancestor: None,
                 nv_subregion: Subregion {
                     pos: nv_subregion_start,
                     len: 0,
                 },
                 state,
+                effects,
                 subviews: Vec::new(),
                 context,
             },
         );
     }
 
+    /// Returns the native view of a view, as it should be sent to the NV tree.
+    fn native_view(&self, id: ViewId, view: &dyn View<Ctx>) -> NativeView {
+        // not locked while calling into the view, which may read the settings itself
+        let accessibility = *self.accessibility.lock();
+        self.overrides
+            .apply(id, accessibility.adapt(view.native_view()))
+    }
+
+    /// Emits updates for all native views that aren’t being animated by a transition.
+    fn reemit_native_views(&mut self) {
+        for (id, node) in &self.nodes {
+            if node.is_native && !self.transitions.values().any(|t| t.targets.contains(id)) {
+                self.patches
+                    .push_back(Patch::Update(*id, self.native_view(*id, &*node.view)));
+            }
+        }
+    }
+
+    fn inspect_node(&self, id: ViewId) -> InspectorNode {
+        let node = &self.nodes[&id];
+        InspectorNode {
+            id,
+            type_name: node.view.type_name(),
+            key: node.view.key(),
+            native_view: if node.is_native {
+                Some(self.native_view(id, &*node.view))
+            } else {
+                None
+            },
+            subviews: node
+                .subviews
+                .iter()
+                .map(|subview| self.inspect_node(*subview))
+                .collect(),
+        }
+    }
+
+    /// Creates a new state object for a view, restoring a saved state if possible.
+    fn new_state(
+        &self,
+        id: ViewId,
+        view: &Arc<dyn View<Ctx>>,
+        context: &Ctx,
+        effects: &Effects,
+        saved: Option<&[u8]>,
+    ) -> AnyState<Ctx> {
+        if let Some(saved) = saved {
+            let restored = view.restore_state(saved, self.view_context(id, context, effects));
+            if let Some(state) = restored {
+                return state;
+            }
+        }
+        view.new_state(self.view_context(id, context, effects))
+    }
+
+    /// Creates a context for a view.
+    fn view_context(&self, id: ViewId, context: &Ctx, effects: &Effects) -> Context<Ctx> {
+        Context {
+            // TODO: proper context
+            context: context.clone(),
+            accessibility: Accessibility::new(
+                Arc::clone(&self.accessibility),
+                Arc::clone(&self.announcements),
+            ),
+            effects: effects.clone(),
+            render_requester: RenderRequester {
+                view: id,
+                requests: Arc::downgrade(&self.render_requests),
+            },
+        }
+    }
+
     /// Removes a view and its subviews.
     ///
     /// Does *not* remove the view from the superview’s `subviews` list. The view must exist.
+    /// Patches for removing native views are only emitted if `emit_patch` is true, i.e. if the NV
+    /// tree won’t remove them anyway.
     fn remove_view(&mut self, id: ViewId, emit_patch: bool) {
         let node = self.nodes.remove(&id).expect("removing nonexistent view");
+        node.effects.clean_up();
+        self.transitions.remove(&id);
+        self.overrides.remove(id);
+        if node.is_native {
+            for transition in self.transitions.values_mut() {
+                transition.targets.retain(|target| *target != id);
+            }
+        }
         if emit_patch && node.is_native {
             self.patches.push_back(Patch::Remove(id));
         }
         for subview in node.subviews {
-            self.remove_view(subview, true);
+            // the NV tree removes native subviews along with their native ancestor
+            self.remove_view(subview, emit_patch && !node.is_native);
         }
     }
 
@@ -227,26 +1088,30 @@ where
     ) {
         let current = self.nodes.get(&id).expect("replacing nonexistent view");
         let superview = current.superview;
+        let key = current.key;
         let nv_ancestor = current.nv_ancestor;
         let was_native = current.is_native;
         let is_native = view.native_type().is_some();
 
-        self.remove_view(id, false);
-        self.add_view(id, view, nv_subregion_start, context);
+        // if the view was native, replacing or removing it will also remove its native subviews
+        self.remove_view(id, !was_native);
+        let saved = self.take_saved_state(&**view, superview.zip(key));
+        self.add_view(id, view, nv_subregion_start, context, false, saved);
 
         let node = self.nodes.get_mut(&id).unwrap();
         node.is_native = is_native;
         node.superview = superview;
+        node.key = key;
         node.nv_ancestor = nv_ancestor;
 
         if was_native && is_native {
             self.patches
-                .push_back(Patch::Replace(id, view.native_view()));
+                .push_back(Patch::Replace(id, self.native_view(id, &**view)));
         } else if was_native {
             self.patches.push_back(Patch::Remove(id));
         } else if is_native {
             self.patches
-                .push_back(Patch::Update(id, view.native_view()));
+                .push_back(Patch::Update(id, self.native_view(id, &**view)));
         }
     }
 
@@ -258,11 +1123,40 @@ where
             "update_view called with incorrect type"
         );
         node.state.will_update(&**view);
+        if let (Some(old), Some(new)) = (
+            node.view.as_any().downcast_ref::<LiveRegion<Ctx>>(),
+            view.as_any().downcast_ref::<LiveRegion<Ctx>>(),
+        ) {
+            if old.label != new.label && !new.label.is_empty() {
+                self.announcements.lock().push(Announcement {
+                    message: new.label.clone(),
+                    priority: new.priority,
+                });
+            }
+        }
+        node.view = Arc::clone(view);
         if node.is_native {
             self.patches
-                .push_back(Patch::Update(id, view.native_view()));
+                .push_back(Patch::Update(id, self.native_view(id, &**view)));
         }
-        node.view = Arc::clone(view);
+    }
+
+    /// Updates an existing view with a view of the same type from a new build of a hot-reloaded
+    /// library.
+    ///
+    /// The state is replaced with a new one if its type has changed, along with its effects.
+    fn reload_view(&mut self, id: ViewId, view: &Arc<dyn View<Ctx>>, context: Ctx) {
+        let effects = Effects::default();
+        let state = self.new_state(id, view, &context, &effects, None);
+        let node = self.nodes.get_mut(&id).expect("reloading nonexistent view");
+        if same_layout(node.state.as_any(), state.as_any()) {
+            effects.clean_up();
+        } else {
+            node.effects.clean_up();
+            node.state = state;
+            node.effects = effects;
+        }
+        self.update_view(id, view);
     }
 
     /// Diffs the subview/the subviews of a node and returns the NV ids.
@@ -271,6 +1165,8 @@ where
         superview: ViewId,
         subview: Arc<dyn View<Ctx>>,
         nv_subregion_start: usize,
+        animate_insertions: bool,
+        depth: usize,
     ) -> Vec<ViewId> {
         let superview_node = &self.nodes[&superview];
         // the closest native ancestor for the subview is either
@@ -281,7 +1177,26 @@ where
             // or the superview’s native ancestor
             superview_node.nv_ancestor
         };
-        let nv_subregion = superview_node.nv_subregion;
+        // The new native subviews are sent to the native ancestor as a single region, either
+        // all native subviews of the superview if it’s native, or the superview’s region if
+        // rendering started at the superview. Regions of composite views in between are part of
+        // those and aren’t sent separately, since the NV tree would see them before the outer
+        // region and the outer region’s length would be out of date.
+        let nv_subregion = if superview_node.is_native {
+            // a native view’s own region is in its native ancestor
+            Some(Subregion {
+                pos: 0,
+                len: superview_node
+                    .subviews
+                    .iter()
+                    .map(|subview| self.native_roots(*subview).len())
+                    .sum(),
+            })
+        } else if depth == 1 {
+            Some(superview_node.nv_subregion)
+        } else {
+            None
+        };
 
         let subview_context = match superview_node
             .view
@@ -306,44 +1221,58 @@ where
 
         // This will expand an array of subviews as the superview’s (only) subviews.
         // To identify which existing subview and newly rendered subview are meant to be the same,
-        // each subview has a key.
+        // each subview has a key (see SubviewKey).
 
-        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-        enum Key {
-            /// A user-specified key.
-            Key(u64),
-            /// An automatically assigned key.
-            AutoKey(u64),
+        let mut auto_key_counter = 0;
+        let mut new_keys = Vec::with_capacity(subviews.len());
+        for view in subviews {
+            new_keys.push(view.key().map(SubviewKey::Key).unwrap_or_else(|| {
+                let k = auto_key_counter;
+                auto_key_counter += 1;
+                SubviewKey::AutoKey(k)
+            }));
         }
 
-        // If a subview doesn’t have a user-specified key, it’ll be auto-keyed sequentially by
-        // index ignoring user-keyed items, e.g.
-        //
-        // array     [A, B, C(key=1), D(key=2), E]
-        // auto-key   0  1                      2
+        // Subviews that are playing an exit transition keep their position until they’re
+        // removed, and don’t take part in keying.
+        let mut departing = Vec::new();
 
         let mut auto_key_counter = 0;
         let mut current_subviews_by_id = HashMap::new();
-        for id in &self.nodes[&superview].subviews {
-            let key = self.nodes[&id].view.key().map(Key::Key).unwrap_or_else(|| {
-                let k = auto_key_counter;
-                auto_key_counter += 1;
-                Key::AutoKey(k)
-            });
-            current_subviews_by_id.insert(key, *id);
+        let current_subviews = self.nodes[&superview].subviews.clone();
+        for (index, id) in current_subviews.into_iter().enumerate() {
+            if self.is_departing(id) {
+                departing.push((index, id));
+                continue;
+            }
+            let key = self.nodes[&id]
+                .view
+                .key()
+                .map(SubviewKey::Key)
+                .unwrap_or_else(|| {
+                    let k = auto_key_counter;
+                    auto_key_counter += 1;
+                    SubviewKey::AutoKey(k)
+                });
+            if !new_keys.contains(&key) && self.begin_exit_transition(id) {
+                departing.push((index, id));
+                continue;
+            }
+            current_subviews_by_id.insert(key, id);
         }
+        let mut departing = departing.into_iter().peekable();
 
-        let mut auto_key_counter = 0;
         let mut new_subviews = Vec::new();
         let mut nv_subviews = Vec::new();
         let mut nv_subregion_cursor = nv_subregion_start;
 
-        for view in subviews.iter().map(|view| Arc::clone(view)) {
-            let key = view.key().map(Key::Key).unwrap_or_else(|| {
-                let k = auto_key_counter;
-                auto_key_counter += 1;
-                Key::AutoKey(k)
-            });
+        for (view, key) in subviews.iter().map(Arc::clone).zip(new_keys) {
+            while let Some((_, id)) = departing.next_if(|(i, _)| *i <= new_subviews.len()) {
+                let mut nvs = self.keep_departing_view(id, nv_subregion_cursor);
+                nv_subregion_cursor += nvs.len();
+                nv_subviews.append(&mut nvs);
+                new_subviews.push(id);
+            }
 
             if let Some(subview_id) = current_subviews_by_id.remove(&key) {
                 // this new subview already has a corresponding old subview
@@ -352,6 +1281,8 @@ where
                     &view,
                     nv_subregion_cursor,
                     subview_context.clone(),
+                    Some((superview, key)),
+                    depth,
                 );
                 nv_subregion_cursor += nvs.len();
                 nv_subviews.append(&mut nvs);
@@ -359,29 +1290,50 @@ where
             } else {
                 // no existing view with the same key, needs to be created
                 let subview_id = ViewId::new();
+                diff_log!(
+                    "no subview of {} with key {:?}; creating {}",
+                    superview,
+                    key,
+                    subview_id
+                );
 
                 let mut nvs = self.diff(
                     subview_id,
                     &view,
                     nv_subregion_cursor,
                     subview_context.clone(),
+                    Some((superview, key)),
+                    depth,
                 );
+                if animate_insertions {
+                    self.begin_enter_transition(subview_id, &nvs);
+                }
                 nv_subregion_cursor += nvs.len();
                 nv_subviews.append(&mut nvs);
-
-                let subview_node = self.nodes.get_mut(&subview_id).unwrap();
-                subview_node.superview = Some(superview);
-                subview_node.nv_ancestor = nv_ancestor;
                 new_subviews.push(subview_id);
             };
         }
 
+        for (_, id) in departing {
+            let mut nvs = self.keep_departing_view(id, nv_subregion_cursor);
+            nv_subregion_cursor += nvs.len();
+            nv_subviews.append(&mut nvs);
+            new_subviews.push(id);
+        }
+
         // unused subviews need to be removed
-        for (_, id) in current_subviews_by_id {
+        for (_key, id) in current_subviews_by_id {
+            diff_log!(
+                "removing {} ({}): key {:?} no longer in {}",
+                id,
+                self.nodes[&id].view.type_name(),
+                _key,
+                superview
+            );
             self.remove_view(id, true);
         }
 
-        if let Some(nv_ancestor) = nv_ancestor {
+        if let (Some(nv_ancestor), Some(nv_subregion)) = (nv_ancestor, nv_subregion) {
             self.patches.push_back(Patch::SubviewRegion(
                 nv_ancestor,
                 nv_subregion.pos,
@@ -396,4 +1348,676 @@ where
         superview_node.nv_subregion.len = nv_subviews.len();
         nv_subviews
     }
+
+    /// Checks that the tree is internally consistent, and returns a description of the first
+    /// problem found otherwise.
+    ///
+    /// Checks that superview links match subview lists, that native subregions are contiguous, and
+    /// that all views are reachable from the root. Meant for tests and fuzzing.
+    #[cfg(feature = "fuzzing")]
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let root = match self.root {
+            Some(root) => root,
+            None if self.nodes.is_empty() => return Ok(()),
+            None => return Err("tree has views but no root".into()),
+        };
+        match self.nodes.get(&root) {
+            Some(node) if node.superview.is_some() => {
+                return Err(format!("root {} has a superview", root))
+            }
+            Some(_) => (),
+            None => return Err(format!("root {} doesn’t exist", root)),
+        }
+
+        let mut reachable = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if !reachable.insert(id) {
+                return Err(format!("{} is a subview more than once", id));
+            }
+            let node = &self.nodes[&id];
+
+            let nv_ancestor = node.superview.and_then(|superview| {
+                let superview_node = &self.nodes[&superview];
+                if superview_node.is_native {
+                    Some(superview)
+                } else {
+                    superview_node.nv_ancestor
+                }
+            });
+            if node.nv_ancestor != nv_ancestor {
+                return Err(format!(
+                    "{} has native ancestor {:?} instead of {:?}",
+                    id, node.nv_ancestor, nv_ancestor
+                ));
+            }
+
+            let start = if node.is_native {
+                0
+            } else {
+                node.nv_subregion.pos
+            };
+            let mut pos = start;
+            for subview in &node.subviews {
+                let subview_node = self
+                    .nodes
+                    .get(subview)
+                    .ok_or_else(|| format!("subview {} of {} doesn’t exist", subview, id))?;
+                if subview_node.superview != Some(id) {
+                    return Err(format!(
+                        "subview {} of {} has superview {:?}",
+                        subview, id, subview_node.superview
+                    ));
+                }
+                if subview_node.nv_subregion.pos != pos {
+                    return Err(format!(
+                        "subview {} of {} has subregion at {} instead of {}",
+                        subview, id, subview_node.nv_subregion.pos, pos
+                    ));
+                }
+                pos += subview_node.nv_subregion.len;
+                stack.push(*subview);
+            }
+
+            let len = if node.is_native { 1 } else { pos - start };
+            if node.nv_subregion.len != len {
+                return Err(format!(
+                    "{} has subregion length {} instead of {}",
+                    id, node.nv_subregion.len, len
+                ));
+            }
+        }
+
+        match self.nodes.keys().find(|id| !reachable.contains(id)) {
+            Some(id) => Err(format!(
+                "{} ({}) is not reachable from the root",
+                id,
+                self.nodes[id].view.type_name()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the native views below the root as a model hierarchy, for comparing with a
+    /// backend.
+    #[cfg(feature = "fuzzing")]
+    pub(crate) fn model_hierarchy(&self) -> Option<ModelView> {
+        let root = self.root?;
+        let root = *self.native_roots(root).first()?;
+        Some(self.model_view(root))
+    }
+
+    #[cfg(feature = "fuzzing")]
+    fn model_view(&self, id: ViewId) -> ModelView {
+        let node = &self.nodes[&id];
+        ModelView {
+            bounds: self.native_view(id, &*node.view).bounds(),
+            subviews: node
+                .subviews
+                .iter()
+                .flat_map(|subview| self.native_roots(*subview))
+                .map(|subview| self.model_view(subview))
+                .collect(),
+        }
+    }
+
+    /// Describes the view cycle that a view is part of, for when views are nested too deeply.
+    fn describe_cycle(&self, id: ViewId) -> String {
+        // type names from the innermost view outwards
+        let mut types = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let node = &self.nodes[&id];
+            types.push(node.view.type_name());
+            current = node.superview;
+        }
+
+        // find the shortest sequence of types that repeats
+        let period = (1..=types.len() / 2).find(|&period| {
+            types[..period * 2]
+                .iter()
+                .zip(&types[period..])
+                .all(|(a, b)| a == b)
+        });
+
+        match period {
+            Some(period) => {
+                let mut cycle: Vec<_> = types[..=period].to_vec();
+                cycle.reverse();
+                format!(
+                    "view cycle detected: views nested more than {} levels deep, repeating {} \
+                     (a view must not render itself again, directly or through its subviews)",
+                    MAX_DEPTH,
+                    cycle.join(" -> ")
+                )
+            }
+            None => format!(
+                "views nested more than {} levels deep (innermost view: {})",
+                MAX_DEPTH, types[0]
+            ),
+        }
+    }
+
+    /// Returns the native views closest to the given view (which may be the view itself).
+    pub(crate) fn native_roots(&self, id: ViewId) -> Vec<ViewId> {
+        let node = &self.nodes[&id];
+        if node.is_native {
+            vec![id]
+        } else {
+            let mut roots = Vec::new();
+            for subview in &node.subviews {
+                roots.append(&mut self.native_roots(*subview));
+            }
+            roots
+        }
+    }
+
+    fn collect_focus_order(&self, id: ViewId, group: Option<ViewId>, order: &mut FocusOrder) {
+        if self.is_departing(id) {
+            return;
+        }
+        let node = &self.nodes[&id];
+        let mut group = group;
+        match node.view.focus() {
+            Some(Focus::Focusable) => order.views.push((id, group)),
+            Some(Focus::Group(focus_group)) => {
+                order.groups.insert(id, focus_group);
+                group = Some(id);
+            }
+            None => (),
+        }
+        for subview in &node.subviews {
+            self.collect_focus_order(*subview, group, order);
+        }
+    }
+
+    /// Returns true if the view is playing an exit transition.
+    fn is_departing(&self, id: ViewId) -> bool {
+        self.transitions.get(&id).is_some_and(|t| t.is_exit)
+    }
+
+    /// Starts an enter transition, if the view has one.
+    fn begin_enter_transition(&mut self, id: ViewId, targets: &[ViewId]) {
+        let transitions = match self.nodes[&id].view.transitions() {
+            Some(transitions) => transitions,
+            None => return,
+        };
+        let transition = match transitions.enter {
+            Some(transition) => self.adapt_transition(transition),
+            None => return,
+        };
+
+        // start out hidden
+        for target in targets {
+            let mut view = self.native_view(*target, &*self.nodes[target].view);
+            transition.apply(&mut view, 0.);
+            self.patches.push_back(Patch::Update(*target, view));
+        }
+
+        self.transitions.insert(
+            id,
+            ActiveTransition {
+                targets: targets.to_vec(),
+                transition,
+                is_exit: false,
+                start: None,
+                duration: transitions.duration,
+                easing: transitions.easing,
+            },
+        );
+    }
+
+    /// Starts an exit transition, if the view has one. Returns false if it doesn’t.
+    fn begin_exit_transition(&mut self, id: ViewId) -> bool {
+        let transitions = match self.nodes[&id].view.transitions() {
+            Some(transitions) => transitions,
+            None => return false,
+        };
+        let transition = match transitions.exit {
+            Some(transition) => self.adapt_transition(transition),
+            None => return false,
+        };
+
+        self.transitions.insert(
+            id,
+            ActiveTransition {
+                targets: self.native_roots(id),
+                transition,
+                is_exit: true,
+                start: None,
+                duration: transitions.duration,
+                easing: transitions.easing,
+            },
+        );
+        true
+    }
+
+    /// Adapts a transition to the accessibility settings.
+    fn adapt_transition(&self, transition: Transition) -> Transition {
+        if self.accessibility.lock().reduce_motion {
+            transition.for_reduced_motion()
+        } else {
+            transition
+        }
+    }
+
+    /// Panics if the leak check is enabled and there are stale entries.
+    fn check_leaks(&self) {
+        if !self.leak_check {
+            return;
+        }
+        if let Some(entry) = self.stale_entries().first() {
+            panic!("leak check: {}", entry);
+        }
+    }
+
+    /// Returns descriptions of all entries that refer to views that no longer exist or aren’t
+    /// reachable from the root.
+    fn stale_entries(&self) -> Vec<String> {
+        let mut stale = Vec::new();
+
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<_> = self.root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            let node = match self.nodes.get(&id) {
+                Some(node) => node,
+                None => {
+                    stale.push(format!("removed view {} is still in the tree", id));
+                    continue;
+                }
+            };
+            if reachable.insert(id) {
+                stack.extend(node.subviews.iter().copied());
+            }
+        }
+        for (id, node) in &self.nodes {
+            if !reachable.contains(id) {
+                stale.push(format!(
+                    "{} ({}) is not reachable from the root",
+                    id,
+                    node.view.type_name()
+                ));
+            }
+        }
+
+        for (id, transition) in &self.transitions {
+            if !self.nodes.contains_key(id) {
+                stale.push(format!("transition of removed view {}", id));
+            }
+            for target in &transition.targets {
+                if !self.nodes.contains_key(target) {
+                    stale.push(format!(
+                        "transition of {} targets removed view {}",
+                        id, target
+                    ));
+                }
+            }
+        }
+        for id in self.overrides.props.keys() {
+            if !self.nodes.contains_key(id) {
+                stale.push(format!("property overrides of removed view {}", id));
+            }
+        }
+        for id in &self.overrides.highlighted {
+            if !self.nodes.contains_key(id) {
+                stale.push(format!("highlight of removed view {}", id));
+            }
+        }
+
+        stale
+    }
+
+    /// Moves a departing view to a new NV subregion position and returns its native views.
+    fn keep_departing_view(&mut self, id: ViewId, nv_subregion_start: usize) -> Vec<ViewId> {
+        let delta = nv_subregion_start as isize - self.nodes[&id].nv_subregion.pos as isize;
+        self.offset_nv_subregion(id, delta);
+        self.native_roots(id)
+    }
+
+    /// Removes a view whose exit transition has completed.
+    fn remove_departed_view(&mut self, id: ViewId) {
+        let node = &self.nodes[&id];
+        let superview = node.superview;
+        let nv_ancestor = node.nv_ancestor;
+        let nv_subregion = node.nv_subregion;
+
+        if let Some(nv_ancestor) = nv_ancestor {
+            self.patches.push_back(Patch::SubviewRegion(
+                nv_ancestor,
+                nv_subregion.pos,
+                nv_subregion.len,
+                Vec::new(),
+            ));
+        }
+
+        self.remove_view(id, true);
+
+        if let Some(superview) = superview {
+            let superview_node = self.nodes.get_mut(&superview).unwrap();
+            let index = superview_node
+                .subviews
+                .iter()
+                .position(|subview| *subview == id)
+                .expect("departed view is not in its superview");
+            superview_node.subviews.remove(index);
+            self.shift_nv_subregions(superview, index, -(nv_subregion.len as isize));
+        }
+    }
+
+    /// Updates NV subregions after `delta` native views were inserted (or removed, if negative)
+    /// before the subview at `index` in `superview`.
+    fn shift_nv_subregions(&mut self, mut superview: ViewId, mut index: usize, delta: isize) {
+        loop {
+            let following = self.nodes[&superview].subviews[index..].to_vec();
+            for id in following {
+                self.offset_nv_subregion(id, delta);
+            }
+
+            let node = self.nodes.get_mut(&superview).unwrap();
+            if node.is_native {
+                break;
+            }
+            node.nv_subregion.len = (node.nv_subregion.len as isize + delta) as usize;
+
+            match node.superview {
+                Some(next) => {
+                    index = self.nodes[&next]
+                        .subviews
+                        .iter()
+                        .position(|subview| *subview == superview)
+                        .unwrap()
+                        + 1;
+                    superview = next;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Offsets the NV subregion of a view and all of its descendants in the same native view.
+    fn offset_nv_subregion(&mut self, id: ViewId, delta: isize) {
+        let node = self.nodes.get_mut(&id).unwrap();
+        node.nv_subregion.pos = (node.nv_subregion.pos as isize + delta) as usize;
+        if !node.is_native {
+            for subview in node.subviews.clone() {
+                self.offset_nv_subregion(subview, delta);
+            }
+        }
+    }
+}
+
+/// Cleans up the effects of all views.
+impl<Ctx> Drop for ViewTree<Ctx> {
+    fn drop(&mut self) {
+        for node in self.nodes.values() {
+            node.effects.clean_up();
+        }
+    }
+}
+
+/// Panics if two renders of a view’s body differ (in strict mode).
+fn check_pure_body<Ctx: 'static>(
+    view: &dyn View<Ctx>,
+    first: &dyn View<Ctx>,
+    second: &dyn View<Ctx>,
+) {
+    if !first.eq(second) {
+        panic!(
+            "strict mode: the body of {} changed between two renders ({} vs. {}); bodies must only \
+             depend on the view’s properties and state. Check for interior mutability (e.g. a Cell \
+             or Mutex) that’s modified while rendering, randomness, time-dependent values, and \
+             subviews that compare closures or handles created in the body by pointer",
+            view.type_name(),
+            first.type_name(),
+            second.type_name(),
+        );
+    }
+}
+
+/// Returns true if both objects have the same type and the same layout.
+///
+/// Objects created by different builds of a hot-reloaded library may have the same type ID even if
+/// the type has changed, so this also compares sizes and alignments.
+fn same_layout(a: &dyn Any, b: &dyn Any) -> bool {
+    a.type_id() == b.type_id()
+        && mem::size_of_val(a) == mem::size_of_val(b)
+        && mem::align_of_val(a) == mem::align_of_val(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::Transitions;
+    use crate::layer::Layer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts how often its body is rendered.
+    #[derive(Debug)]
+    struct Counter {
+        observers: Observers,
+        renders: Arc<AtomicUsize>,
+    }
+
+    impl PartialEq for Counter {
+        fn eq(&self, other: &Counter) -> bool {
+            Arc::ptr_eq(&self.renders, &other.renders)
+        }
+    }
+
+    impl_view! {
+        Counter;
+        fn body(&self, _state: &()) {
+            self.renders.fetch_add(1, Ordering::SeqCst);
+            Arc::new(Fragment::new())
+        }
+        fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+            self.observers.observe(context);
+        }
+    }
+
+    #[test]
+    fn observers_render_again_on_tick() {
+        let observers = Observers::new();
+        let renders = Arc::new(AtomicUsize::new(0));
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let mut tree: ViewTree = ViewTree::new();
+        {
+            let wakes = Arc::clone(&wakes);
+            tree.set_render_waker(move || {
+                wakes.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        tree.render_root(
+            Arc::new(Counter {
+                observers: observers.clone(),
+                renders: Arc::clone(&renders),
+            }),
+            (),
+        );
+        assert_eq!(renders.load(Ordering::SeqCst), 1);
+        assert_eq!(observers.len(), 1);
+        assert!(!tree.has_render_requests());
+
+        observers.notify();
+        observers.notify();
+        assert!(tree.has_render_requests());
+        assert_eq!(wakes.load(Ordering::SeqCst), 1);
+
+        tree.tick(Instant::now());
+        assert_eq!(renders.load(Ordering::SeqCst), 2);
+        assert!(!tree.has_render_requests());
+
+        tree.render_root(Arc::new(Fragment::new()), ());
+        assert!(observers.is_empty());
+    }
+
+    #[cfg(feature = "hot-reload")]
+    mod hot_reload {
+        use super::*;
+        use crate::view::State;
+
+        /// A state that counts how often its view’s body was rendered.
+        #[derive(Debug, Default)]
+        struct Narrow(AtomicUsize);
+
+        /// The same, but with a different layout, as if the state type had changed in a new build.
+        #[derive(Debug, Default)]
+        struct Wide(Mutex<u128>);
+
+        impl State for Narrow {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+            fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+                self
+            }
+        }
+
+        impl State for Wide {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+            fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+                self
+            }
+        }
+
+        /// Logs which state it was rendered with, and how often.
+        #[derive(Debug)]
+        struct Reloadable {
+            wide: bool,
+            log: Arc<Mutex<Vec<(&'static str, usize)>>>,
+        }
+
+        impl View for Reloadable {
+            fn new_state(&self, _context: Context) -> AnyState {
+                if self.wide {
+                    Arc::new(Wide::default())
+                } else {
+                    Arc::new(Narrow::default())
+                }
+            }
+            fn body(&self, state: &AnyState) -> Arc<dyn View> {
+                let state = state.as_any();
+                let entry = if let Some(Narrow(count)) = state.downcast_ref() {
+                    ("narrow", count.fetch_add(1, Ordering::SeqCst) + 1)
+                } else if let Some(Wide(count)) = state.downcast_ref() {
+                    let mut count = count.lock();
+                    *count += 1;
+                    ("wide", *count as usize)
+                } else {
+                    panic!("invalid state");
+                };
+                self.log.lock().push(entry);
+                Arc::new(())
+            }
+            fn eq(&self, other: &dyn View) -> bool {
+                match other.as_any().downcast_ref::<Reloadable>() {
+                    Some(other) => self.wide == other.wide && Arc::ptr_eq(&self.log, &other.log),
+                    None => false,
+                }
+            }
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+        }
+
+        #[test]
+        fn reloading_keeps_states_with_the_same_layout() {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let view = |wide| -> Arc<dyn View> {
+                Arc::new(Reloadable {
+                    wide,
+                    log: Arc::clone(&log),
+                })
+            };
+            let mut tree: ViewTree = ViewTree::new();
+            tree.render_root(view(false), ());
+            assert_eq!(mem::take(&mut *log.lock()), [("narrow", 1)]);
+
+            // views are updated with the code from the new build, but keep their state
+            tree.reload_root(view(false), ());
+            tree.reload_root(view(false), ());
+            assert_eq!(mem::take(&mut *log.lock()), [("narrow", 2), ("narrow", 3)]);
+
+            // a state with a different layout can’t be kept
+            tree.reload_root(view(true), ());
+            tree.reload_root(view(true), ());
+            assert_eq!(mem::take(&mut *log.lock()), [("wide", 1), ("wide", 2)]);
+        }
+
+        #[test]
+        fn layouts_are_compared_by_type_and_size() {
+            assert!(same_layout(&Narrow::default(), &Narrow::default()));
+            assert!(!same_layout(&Narrow::default(), &Wide::default()));
+            assert!(!same_layout(&1u32, &1f32));
+            assert!(!same_layout(&[0u8; 4], &[0u8; 8]));
+        }
+    }
+
+    /// A layer containing a layer that fades in and out over 100 ms, if `with_child` is set.
+    fn fading_root(with_child: bool) -> Arc<dyn View> {
+        let mut subviews: Fragment<()> = Vec::new();
+        if with_child {
+            subviews.push(Arc::new(Layer {
+                key: Some(1),
+                transitions: Some(Transitions {
+                    easing: Easing::Linear,
+                    ..Transitions::symmetric(Transition::Fade, Duration::from_millis(100))
+                }),
+                ..Layer::default()
+            }));
+        }
+        Arc::new(Layer {
+            subviews,
+            ..Layer::default()
+        })
+    }
+
+    /// Drains the patches and returns the opacities of all updated layers.
+    fn updated_opacities(tree: &mut ViewTree) -> Vec<f64> {
+        tree.patches()
+            .filter_map(|patch| match patch {
+                Patch::Update(_, NativeView::Layer(props)) => Some(props.opacity),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transitions_are_timed_by_ticks() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut tree: ViewTree = ViewTree::new();
+        tree.render_root(fading_root(false), ());
+        tree.render_root(fading_root(true), ());
+        tree.patches().for_each(drop);
+
+        // the enter transition starts at the first tick, however late it comes
+        assert!(tree.tick(at(1000)));
+        assert_eq!(updated_opacities(&mut tree), [0.]);
+        assert!(tree.tick(at(1050)));
+        assert_eq!(updated_opacities(&mut tree), [0.5]);
+        assert!(!tree.tick(at(1100)));
+        assert_eq!(updated_opacities(&mut tree), [1., 1.]);
+        assert!(tree.transitions.is_empty());
+
+        // the exiting view stays around until its transition completes
+        tree.render_root(fading_root(false), ());
+        tree.patches().for_each(drop);
+        assert_eq!(tree.nodes.len(), 2);
+        assert!(tree.tick(at(2000)));
+        assert_eq!(updated_opacities(&mut tree), [1.]);
+        assert!(tree.tick(at(2050)));
+        assert_eq!(updated_opacities(&mut tree), [0.5]);
+        assert!(!tree.tick(at(2100)));
+        let removed: Vec<_> = tree
+            .patches()
+            .filter(|patch| matches!(patch, Patch::SubviewRegion(_, 0, 1, subviews) if subviews.is_empty()))
+            .collect();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(tree.nodes.len(), 1);
+        assert!(tree.transitions.is_empty());
+        assert!(tree.stale_entries().is_empty());
+    }
 }
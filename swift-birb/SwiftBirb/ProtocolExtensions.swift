@@ -39,17 +39,27 @@ extension SBMatrix3 {
     }
 }
 
+extension SBKeyModifiers {
+    init(_ flags: NSEvent.ModifierFlags) {
+        self.init(
+            shift: flags.contains(.shift),
+            control: flags.contains(.control),
+            option: flags.contains(.option),
+            command: flags.contains(.command)
+        )
+    }
+}
+
 extension SBNodeList {
-    /// Converts this into a Swift list and deallocates the memory.
+    /// Copies the nodes in this list.
     ///
-    /// Safety: once this function has been called, the SBNodeList must not be used, because it would access deallocated memory.
-    func intoList() -> [SBNode] {
-        var list: [SBNode] = []
-        for i in 0...count {
-            let node = nodes.assumingMemoryBound(to: SBNode.self).advanced(by: Int(i)).pointee
-            list.append(node)
+    /// The memory belongs to the caller and is only valid until the call that passed this list returns, so this must be
+    /// called before then.
+    func toList() -> [SBNode] {
+        guard let nodes = nodes, count > 0 else {
+            return []
         }
-        nodes.deallocate()
-        return list
+        let ids = nodes.assumingMemoryBound(to: Unmanaged<SBNode>.self)
+        return (0..<Int(count)).map { ids[$0].takeUnretainedValue() }
     }
 }
@@ -0,0 +1,61 @@
+//
+//  SBDock.swift
+//  SwiftBirb
+//
+//  Created by cpsdqs on 2019-10-22.
+//  Copyright © 2019 cpsdqs. All rights reserved.
+//
+
+import Cocoa
+
+/// The app’s Dock icon.
+@objc public class SBDock : NSObject {
+    private static var progressIndicator: NSProgressIndicator?
+
+    /// Sets the badge label, or removes the badge if label is nil.
+    @objc public static func setBadge(_ label: String?) {
+        NSApp.dockTile.badgeLabel = label
+    }
+
+    /// Shows a progress bar from 0 to 1 over the app icon, or removes it if progress is negative.
+    @objc public static func setProgress(_ progress: Double) {
+        let tile = NSApp.dockTile
+        if progress < 0 {
+            tile.contentView = nil
+            progressIndicator = nil
+            tile.display()
+            return
+        }
+
+        if progressIndicator == nil {
+            let content = NSImageView(frame: NSRect(origin: .zero, size: tile.size))
+            content.image = NSApp.applicationIconImage
+            let indicator = NSProgressIndicator(frame: NSRect(
+                x: tile.size.width * 0.1,
+                y: tile.size.height * 0.05,
+                width: tile.size.width * 0.8,
+                height: tile.size.height * 0.15
+            ))
+            indicator.style = .bar
+            indicator.isIndeterminate = false
+            indicator.minValue = 0
+            indicator.maxValue = 1
+            content.addSubview(indicator)
+            tile.contentView = content
+            progressIndicator = indicator
+        }
+        progressIndicator?.doubleValue = min(progress, 1)
+        tile.display()
+    }
+
+    /// Bounces the Dock icon until the app is activated (if critical) or once.
+    ///
+    /// Returns a request ID for cancelling, or 0 if the app is already active.
+    @objc public static func requestAttention(_ critical: Bool) -> Int {
+        return NSApp.requestUserAttention(critical ? .criticalRequest : .informationalRequest)
+    }
+
+    @objc public static func cancelAttentionRequest(_ request: Int) {
+        NSApp.cancelUserAttentionRequest(request)
+    }
+}
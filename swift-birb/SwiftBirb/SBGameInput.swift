@@ -0,0 +1,184 @@
+//
+//  SBGameInput.swift
+//  SwiftBirb
+//
+//  Created by cpsdqs on 2019-09-02.
+//  Copyright © 2019 cpsdqs. All rights reserved.
+//
+
+import Cocoa
+import GameController
+
+/// Collects media key and game controller events until they’re polled.
+class SBGameInput {
+    private var queue: [SBEvent] = []
+    private var controllerIds: [ObjectIdentifier: UInt64] = [:]
+    private var nextControllerId: UInt64 = 1
+    private var observers: [NSObjectProtocol] = []
+    private var mediaKeyMonitor: Any?
+
+    init() {
+        let center = NotificationCenter.default
+        observers.append(center.addObserver(forName: .GCControllerDidConnect, object: nil, queue: .main) { [weak self] notification in
+            if let controller = notification.object as? GCController {
+                self?.connect(controller)
+            }
+        })
+        observers.append(center.addObserver(forName: .GCControllerDidDisconnect, object: nil, queue: .main) { [weak self] notification in
+            if let controller = notification.object as? GCController {
+                self?.disconnect(controller)
+            }
+        })
+        for controller in GCController.controllers() {
+            connect(controller)
+        }
+
+        mediaKeyMonitor = NSEvent.addLocalMonitorForEvents(matching: .systemDefined) { [weak self] event in
+            if self?.handleMediaKey(event) == true {
+                return nil
+            }
+            return event
+        }
+    }
+
+    deinit {
+        for observer in observers {
+            NotificationCenter.default.removeObserver(observer)
+        }
+        if let monitor = mediaKeyMonitor {
+            NSEvent.removeMonitor(monitor)
+        }
+    }
+
+    /// Removes the oldest event from the queue.
+    func poll() -> SBEvent? {
+        if queue.isEmpty {
+            return nil
+        }
+        return queue.removeFirst()
+    }
+
+    private func push(_ type: SBEventTypeId, _ data: SBEventData) {
+        var event = SBEvent()
+        event.type = type
+        event.timestamp = ProcessInfo.processInfo.systemUptime
+        event.data = data
+        queue.append(event)
+    }
+
+    // MARK: - Media keys
+
+    // from IOKit/hidsystem/ev_keymap.h
+    private static let auxControlButtons: Int16 = 8
+    private static let mediaKeys: [Int: SBMediaKey] = [
+        0: SBMediaKeyVolumeUp,
+        1: SBMediaKeyVolumeDown,
+        7: SBMediaKeyMute,
+        16: SBMediaKeyPlayPause,
+        17: SBMediaKeyNext,
+        18: SBMediaKeyPrevious,
+        19: SBMediaKeyFastForward,
+        20: SBMediaKeyRewind,
+    ]
+
+    private func handleMediaKey(_ event: NSEvent) -> Bool {
+        guard event.subtype.rawValue == SBGameInput.auxControlButtons else {
+            return false
+        }
+        let keyCode = (event.data1 & 0xFFFF0000) >> 16
+        let flags = event.data1 & 0xFFFF
+        guard let key = SBGameInput.mediaKeys[keyCode] else {
+            return false
+        }
+        let isDown = (flags & 0xFF00) >> 8 == 0xA
+        let isRepeat = flags & 0x1 != 0
+
+        var data = SBEventData()
+        data.media_key.key = key
+        data.media_key.phase = !isDown ? SBKeyEventPhaseUp : isRepeat ? SBKeyEventPhaseRepeat : SBKeyEventPhaseDown
+        push(SBEventTypeIdMediaKey, data)
+        return true
+    }
+
+    // MARK: - Game controllers
+
+    private func controllerEvent(_ controller: UInt64, _ type: SBControllerInputType) -> SBControllerEvent {
+        var event = SBControllerEvent()
+        event.controller = controller
+        event.type = type
+        return event
+    }
+
+    private func pushController(_ event: SBControllerEvent) {
+        var data = SBEventData()
+        data.controller = event
+        push(SBEventTypeIdController, data)
+    }
+
+    private func connect(_ controller: GCController) {
+        let key = ObjectIdentifier(controller)
+        if controllerIds[key] != nil {
+            return
+        }
+        let id = nextControllerId
+        nextControllerId += 1
+        controllerIds[key] = id
+        pushController(controllerEvent(id, SBControllerInputTypeConnected))
+
+        guard let gamepad = controller.extendedGamepad else {
+            return
+        }
+        let buttons: [(GCControllerButtonInput?, SBControllerButton)] = [
+            (gamepad.buttonA, SBControllerButtonA),
+            (gamepad.buttonB, SBControllerButtonB),
+            (gamepad.buttonX, SBControllerButtonX),
+            (gamepad.buttonY, SBControllerButtonY),
+            (gamepad.leftShoulder, SBControllerButtonLeftShoulder),
+            (gamepad.rightShoulder, SBControllerButtonRightShoulder),
+            (gamepad.leftTrigger, SBControllerButtonLeftTrigger),
+            (gamepad.rightTrigger, SBControllerButtonRightTrigger),
+            (gamepad.dpad.up, SBControllerButtonDpadUp),
+            (gamepad.dpad.down, SBControllerButtonDpadDown),
+            (gamepad.dpad.left, SBControllerButtonDpadLeft),
+            (gamepad.dpad.right, SBControllerButtonDpadRight),
+            (gamepad.leftThumbstickButton, SBControllerButtonLeftThumbstick),
+            (gamepad.rightThumbstickButton, SBControllerButtonRightThumbstick),
+            (gamepad.buttonMenu, SBControllerButtonMenu),
+            (gamepad.buttonOptions, SBControllerButtonOptions),
+        ]
+        for (input, button) in buttons {
+            input?.valueChangedHandler = { [weak self] _, value, pressed in
+                guard let self = self else { return }
+                var event = self.controllerEvent(id, SBControllerInputTypeButton)
+                event.button = button
+                event.value = Double(value)
+                event.is_pressed = pressed
+                self.pushController(event)
+            }
+        }
+
+        // birb’s y axis points down
+        let axes: [(GCControllerAxisInput, SBControllerAxis, Double)] = [
+            (gamepad.leftThumbstick.xAxis, SBControllerAxisLeftX, 1),
+            (gamepad.leftThumbstick.yAxis, SBControllerAxisLeftY, -1),
+            (gamepad.rightThumbstick.xAxis, SBControllerAxisRightX, 1),
+            (gamepad.rightThumbstick.yAxis, SBControllerAxisRightY, -1),
+        ]
+        for (input, axis, sign) in axes {
+            input.valueChangedHandler = { [weak self] _, value in
+                guard let self = self else { return }
+                var event = self.controllerEvent(id, SBControllerInputTypeAxis)
+                event.axis = axis
+                event.value = sign * Double(value)
+                self.pushController(event)
+            }
+        }
+    }
+
+    private func disconnect(_ controller: GCController) {
+        guard let id = controllerIds.removeValue(forKey: ObjectIdentifier(controller)) else {
+            return
+        }
+        pushController(controllerEvent(id, SBControllerInputTypeDisconnected))
+    }
+}
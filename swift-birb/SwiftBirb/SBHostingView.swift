@@ -10,14 +10,52 @@ import Cocoa
 
 /// A view that hosts a birb view hierarchy.
 @objc public class SBHostingView : NSView {
+    private let gameInput = SBGameInput()
+    private var dragEvents: [(SBEvent, [String])] = []
+    private var polledPaths: [String] = []
+    private var dropAccepted = false
+    private var windowEvents: [SBEvent] = []
+    private var inputEvents: [(SBEvent, KeyStrings?)] = []
+    private var polledStrings: [UnsafeMutablePointer<CChar>] = []
+    private var pressedButtons = 0
+    private var trackingArea: NSTrackingArea?
+    private var occlusionObserver: NSObjectProtocol?
+    private var accessibilityObserver: NSObjectProtocol?
+    private var lastScaleFactor: CGFloat = 0
+    private var autosaveName: String?
+    private var fullScreenObservers: [NSObjectProtocol] = []
+
+    /// The protocol version SwiftBirb was built with; checked by the Rust side when connecting.
+    @objc public static let protocolVersion = UInt64(SB_PROTOCOL_VERSION)
+
     @objc public init() {
         super.init(frame: NSMakeRect(0, 0, 0, 0))
+        registerForDraggedTypes([.fileURL])
+        accessibilityObserver = NSWorkspace.shared.notificationCenter.addObserver(
+            forName: NSWorkspace.accessibilityDisplayOptionsDidChangeNotification,
+            object: nil,
+            queue: nil
+        ) { [weak self] _ in
+            self?.pushAccessibility()
+        }
+        pushAccessibility()
     }
 
     required init?(coder: NSCoder) {
         fatalError("init(coder:) has not been implemented")
     }
 
+    deinit {
+        if let observer = occlusionObserver {
+            NotificationCenter.default.removeObserver(observer)
+        }
+        if let observer = accessibilityObserver {
+            NSWorkspace.shared.notificationCenter.removeObserver(observer)
+        }
+        removeFullScreenObservers()
+        freePolledStrings()
+    }
+
     @objc public func createView(_ patch: SBNodePatch) -> SBNode {
         return SBNode(host: self, patch: patch)
     }
@@ -25,4 +63,569 @@ import Cocoa
     @objc public func setRootView(_ view: SBNode) {
         // TODO: this
     }
+
+    /// Adds this view to a view owned by someone else (e.g. the content view of a window created
+    /// by another toolkit), filling it.
+    @objc(attachToView:) public func attach(to parent: NSView) {
+        removeFromSuperview()
+        frame = parent.bounds
+        autoresizingMask = [.width, .height]
+        parent.addSubview(self)
+    }
+
+    /// Takes the oldest pending event.
+    ///
+    /// Returns false if there are no pending events.
+    @objc public func pollEvent(_ event: UnsafeMutablePointer<SBEvent>) -> Bool {
+        freePolledStrings()
+        if !windowEvents.isEmpty {
+            event.pointee = windowEvents.removeFirst()
+            return true
+        }
+        if !inputEvents.isEmpty {
+            let (polled, strings) = inputEvents.removeFirst()
+            var next = polled
+            if let strings = strings {
+                // owned by this view until the next poll
+                let chars = strdup(strings.chars)!
+                let charsWithoutMod = strdup(strings.charsWithoutMod)!
+                polledStrings = [chars, charsWithoutMod]
+                next.data.key.chars = chars
+                next.data.key.chars_without_mod = charsWithoutMod
+            }
+            event.pointee = next
+            return true
+        }
+        if !dragEvents.isEmpty {
+            let (next, paths) = dragEvents.removeFirst()
+            event.pointee = next
+            polledPaths = paths
+            return true
+        }
+        guard let next = gameInput.poll() else {
+            return false
+        }
+        event.pointee = next
+        return true
+    }
+
+    /// The file paths of the most recently polled file drag event.
+    @objc public func polledFilePaths() -> [String] {
+        return polledPaths
+    }
+
+    /// Sets whether the current file drag would be accepted if the files were dropped.
+    @objc public func setDropAccepted(_ accepted: Bool) {
+        dropAccepted = accepted
+    }
+
+    /// The size of a capture of this view, in pixels.
+    @objc public func captureSize() -> SBVector2 {
+        let size = convertToBacking(bounds).size
+        return SBVector2(x: Double(size.width), y: Double(size.height))
+    }
+
+    /// Captures the current contents of this view into an RGBA buffer (premultiplied alpha) of the given size in pixels.
+    ///
+    /// Returns false if the view could not be captured.
+    @objc public func capture(width: UInt64, height: UInt64, data: UnsafeMutablePointer<UInt8>) -> Bool {
+        guard let rep = bitmapImageRepForCachingDisplay(in: bounds) else {
+            return false
+        }
+        cacheDisplay(in: bounds, to: rep)
+        guard let image = rep.cgImage, let context = CGContext(
+            data: data,
+            width: Int(width),
+            height: Int(height),
+            bitsPerComponent: 8,
+            bytesPerRow: Int(width) * 4,
+            space: sRGB,
+            bitmapInfo: CGImageAlphaInfo.premultipliedLast.rawValue
+        ) else {
+            return false
+        }
+        context.draw(image, in: CGRect(x: 0, y: 0, width: CGFloat(width), height: CGFloat(height)))
+        return true
+    }
+
+    /// Renders a node offscreen into an RGBA buffer (premultiplied alpha) of the given size in pixels.
+    ///
+    /// Returns false if the node could not be rendered.
+    @objc public func renderView(_ node: SBNode, width: UInt64, height: UInt64, data: UnsafeMutablePointer<UInt8>) -> Bool {
+        guard let layer = (node.view as? NSView)?.layer else {
+            return false
+        }
+        guard let context = CGContext(
+            data: data,
+            width: Int(width),
+            height: Int(height),
+            bitsPerComponent: 8,
+            bytesPerRow: Int(width) * 4,
+            space: sRGB,
+            bitmapInfo: CGImageAlphaInfo.premultipliedLast.rawValue
+        ) else {
+            return false
+        }
+
+        // birb coordinates have the y axis pointing down
+        let bounds = layer.bounds
+        context.translateBy(x: 0, y: CGFloat(height))
+        context.scaleBy(x: CGFloat(width) / max(bounds.width, 1), y: -CGFloat(height) / max(bounds.height, 1))
+        context.translateBy(x: -bounds.origin.x, y: -bounds.origin.y)
+        layer.render(in: context)
+        return true
+    }
+
+    /// Renders regions of a node into the pages of a PDF document.
+    ///
+    /// Each page rect is drawn on its own page, inset by the margin. Returns nil if the node could not be rendered.
+    @objc public func exportPDF(_ node: SBNode, pageSize: SBVector2, margin: Double, pages: UnsafePointer<SBRect>, pageCount: UInt64) -> NSData? {
+        guard let layer = (node.view as? NSView)?.layer else {
+            return nil
+        }
+        let data = NSMutableData()
+        var mediaBox = CGRect(x: 0, y: 0, width: CGFloat(pageSize.x), height: CGFloat(pageSize.y))
+        guard let consumer = CGDataConsumer(data: data as CFMutableData),
+            let context = CGContext(consumer: consumer, mediaBox: &mediaBox, nil) else {
+            return nil
+        }
+
+        for i in 0..<Int(pageCount) {
+            let page = pages[i].cgRect
+            context.beginPDFPage(nil)
+            context.saveGState()
+            // birb coordinates have the y axis pointing down
+            context.translateBy(x: 0, y: mediaBox.height)
+            context.scaleBy(x: 1, y: -1)
+            context.translateBy(x: CGFloat(margin), y: CGFloat(margin))
+            context.clip(to: CGRect(origin: .zero, size: page.size))
+            context.translateBy(x: -page.origin.x, y: -page.origin.y)
+            layer.render(in: context)
+            context.restoreGState()
+            context.endPDFPage()
+        }
+        context.closePDF()
+
+        return data
+    }
+
+    /// Asks VoiceOver to speak a UTF-8 message.
+    ///
+    /// Priority is 0 (low), 1 (medium), or 2 (high).
+    @objc public func announce(_ message: UnsafePointer<UInt8>, length: UInt64, priority: UInt64) {
+        let buffer = UnsafeBufferPointer(start: message, count: Int(length))
+        let text = String(decoding: buffer, as: UTF8.self)
+        let level: NSAccessibilityPriorityLevel
+        switch priority {
+        case 0: level = .low
+        case 1: level = .medium
+        default: level = .high
+        }
+        NSAccessibility.post(element: NSApp.mainWindow ?? self, notification: .announcementRequested, userInfo: [
+            .announcement: text,
+            .priority: level.rawValue,
+        ])
+    }
+
+    /// Sends the system accessibility display options to the Rust side.
+    private func pushAccessibility() {
+        var event = SBEvent()
+        event.type = SBEventTypeIdAccessibility
+        event.timestamp = ProcessInfo.processInfo.systemUptime
+        let workspace = NSWorkspace.shared
+        event.data.accessibility.reduce_motion = workspace.accessibilityDisplayShouldReduceMotion
+        event.data.accessibility.reduce_transparency = workspace.accessibilityDisplayShouldReduceTransparency
+        event.data.accessibility.increase_contrast = workspace.accessibilityDisplayShouldIncreaseContrast
+        windowEvents.append(event)
+    }
+
+    // MARK: - Window visibility
+
+    public override func viewDidMoveToWindow() {
+        super.viewDidMoveToWindow()
+        if let observer = occlusionObserver {
+            NotificationCenter.default.removeObserver(observer)
+            occlusionObserver = nil
+        }
+        guard let window = window else {
+            return
+        }
+        occlusionObserver = NotificationCenter.default.addObserver(
+            forName: NSWindow.didChangeOcclusionStateNotification,
+            object: window,
+            queue: nil
+        ) { [weak self] _ in
+            self?.pushVisibility()
+        }
+        pushVisibility()
+        pushScaleFactor()
+        _ = applyAutosaveName()
+    }
+
+    public override func viewDidChangeBackingProperties() {
+        super.viewDidChangeBackingProperties()
+        pushScaleFactor()
+    }
+
+    private func pushVisibility() {
+        var event = SBEvent()
+        event.type = SBEventTypeIdVisibility
+        event.timestamp = ProcessInfo.processInfo.systemUptime
+        event.data.visibility.is_visible = window?.occlusionState.contains(.visible) ?? false
+        windowEvents.append(event)
+    }
+
+    private func pushScaleFactor() {
+        guard let window = window, window.backingScaleFactor != lastScaleFactor else {
+            return
+        }
+        lastScaleFactor = window.backingScaleFactor
+        var event = SBEvent()
+        event.type = SBEventTypeIdScaleFactor
+        event.timestamp = ProcessInfo.processInfo.systemUptime
+        event.data.scale_factor.scale_factor = Double(window.backingScaleFactor)
+        windowEvents.append(event)
+    }
+
+    // MARK: - Window restoration
+
+    /// Saves and restores the frame and full-screen state of the window under an autosave name,
+    /// or stops doing so if the name is nil. Applied again whenever this view moves to a window.
+    ///
+    /// Returns false if another window already uses the name.
+    @objc(setWindowAutosaveName:) public func setWindowAutosaveName(_ name: String?) -> Bool {
+        if name == nil {
+            window?.setFrameAutosaveName("")
+        }
+        autosaveName = name
+        return applyAutosaveName()
+    }
+
+    private func applyAutosaveName() -> Bool {
+        removeFullScreenObservers()
+        guard let window = window, let name = autosaveName else {
+            return true
+        }
+        window.setFrameUsingName(name)
+        guard window.setFrameAutosaveName(name) else {
+            return false
+        }
+
+        // Cocoa only saves the frame, so full screen is saved separately
+        let key = "SBWindowFullScreen \(name)"
+        if UserDefaults.standard.bool(forKey: key) && !window.styleMask.contains(.fullScreen) {
+            window.toggleFullScreen(nil)
+        }
+        for (name, isFullScreen) in [
+            (NSWindow.didEnterFullScreenNotification, true),
+            (NSWindow.didExitFullScreenNotification, false),
+        ] {
+            fullScreenObservers.append(NotificationCenter.default.addObserver(
+                forName: name,
+                object: window,
+                queue: nil
+            ) { _ in
+                UserDefaults.standard.set(isFullScreen, forKey: key)
+            })
+        }
+        return true
+    }
+
+    private func removeFullScreenObservers() {
+        for observer in fullScreenObservers {
+            NotificationCenter.default.removeObserver(observer)
+        }
+        fullScreenObservers = []
+    }
+
+    // MARK: - Input events
+
+    /// Strings of a key event, which are only copied to C strings when it’s polled.
+    private struct KeyStrings {
+        let chars: String
+        let charsWithoutMod: String
+    }
+
+    private func freePolledStrings() {
+        for string in polledStrings {
+            free(string)
+        }
+        polledStrings = []
+    }
+
+    /// Converts a location in the window to the coordinates of this view, with the y axis pointing
+    /// up.
+    private func location(inWindow point: NSPoint) -> SBVector2 {
+        let point = convert(point, from: nil)
+        return SBVector2(x: Double(point.x), y: Double(isFlipped ? bounds.height - point.y : point.y))
+    }
+
+    private func makeEvent(_ type: SBEventTypeId, _ event: NSEvent) -> SBEvent {
+        var sbEvent = SBEvent()
+        sbEvent.type = type
+        sbEvent.timestamp = event.timestamp
+        return sbEvent
+    }
+
+    /// The device, pressure, and tilt of a mouse or tablet event.
+    private func deviceState(_ event: NSEvent) -> (SBPointerDevice, Double, SBVector3) {
+        guard event.subtype == .tabletPoint else {
+            return (SBPointerDeviceCursor, 1, SBVector3(x: 0, y: 0, z: 1))
+        }
+        // tablet tilt is between -1 and 1 on each axis with y pointing up
+        let x = Double(event.tilt.x)
+        let y = -Double(event.tilt.y)
+        let tilt = SBVector3(x: x, y: y, z: (max(0, 1 - x * x - y * y)).squareRoot())
+        return (SBPointerDevicePen, Double(event.pressure), tilt)
+    }
+
+    private func pushHover(_ event: NSEvent, phase: SBHoverEventPhase) {
+        var sbEvent = makeEvent(SBEventTypeIdHover, event)
+        let (device, _, tilt) = deviceState(event)
+        sbEvent.data.hover.device = device
+        sbEvent.data.hover.window_location = location(inWindow: event.locationInWindow)
+        sbEvent.data.hover.tilt = tilt
+        sbEvent.data.hover.pointer_id = 0
+        sbEvent.data.hover.phase = phase
+        sbEvent.data.hover.modifiers = SBKeyModifiers(event.modifierFlags)
+        inputEvents.append((sbEvent, nil))
+    }
+
+    private func pushPointer(_ event: NSEvent, phase: SBPointerEventPhase) {
+        var sbEvent = makeEvent(SBEventTypeIdPointer, event)
+        let (device, pressure, tilt) = deviceState(event)
+        sbEvent.data.pointer.device = device
+        sbEvent.data.pointer.window_location = location(inWindow: event.locationInWindow)
+        sbEvent.data.pointer.pressure = pressure
+        sbEvent.data.pointer.tilt = tilt
+        sbEvent.data.pointer.pointer_id = 0
+        sbEvent.data.pointer.phase = phase
+        sbEvent.data.pointer.modifiers = SBKeyModifiers(event.modifierFlags)
+        sbEvent.data.pointer.click_count = UInt32(max(0, event.clickCount))
+        inputEvents.append((sbEvent, nil))
+    }
+
+    /// Pushes a pointer event for a mouse button; only the first button pressed and the last one
+    /// released begin and end the stream.
+    private func pushButton(_ event: NSEvent, isDown: Bool) {
+        if isDown {
+            pressedButtons += 1
+            pushPointer(event, phase: pressedButtons == 1 ? SBPointerEventPhaseBegan : SBPointerEventPhaseStationary)
+        } else if pressedButtons > 0 {
+            pressedButtons -= 1
+            pushPointer(event, phase: pressedButtons == 0 ? SBPointerEventPhaseEnded : SBPointerEventPhaseStationary)
+        }
+    }
+
+    private func pushKey(_ event: NSEvent, phase: SBKeyEventPhase, strings: KeyStrings) {
+        guard let keyCode = SBHostingView.keyCodes[event.keyCode] else {
+            return
+        }
+        var sbEvent = makeEvent(SBEventTypeIdKey, event)
+        sbEvent.data.key.keyCode = keyCode
+        sbEvent.data.key.phase = phase
+        sbEvent.data.key.modifiers = SBKeyModifiers(event.modifierFlags)
+        inputEvents.append((sbEvent, strings))
+    }
+
+    public override var acceptsFirstResponder: Bool {
+        return true
+    }
+
+    public override func updateTrackingAreas() {
+        super.updateTrackingAreas()
+        if let area = trackingArea {
+            removeTrackingArea(area)
+        }
+        let area = NSTrackingArea(
+            rect: .zero,
+            options: [.mouseEnteredAndExited, .mouseMoved, .activeInKeyWindow, .inVisibleRect],
+            owner: self,
+            userInfo: nil
+        )
+        addTrackingArea(area)
+        trackingArea = area
+    }
+
+    public override func mouseEntered(with event: NSEvent) {
+        pushHover(event, phase: SBHoverEventPhaseEntered)
+    }
+
+    public override func mouseMoved(with event: NSEvent) {
+        pushHover(event, phase: SBHoverEventPhaseMoved)
+    }
+
+    public override func mouseExited(with event: NSEvent) {
+        pushHover(event, phase: SBHoverEventPhaseLeft)
+    }
+
+    public override func mouseDown(with event: NSEvent) {
+        window?.makeFirstResponder(self)
+        pushButton(event, isDown: true)
+    }
+
+    public override func mouseDragged(with event: NSEvent) {
+        pushPointer(event, phase: SBPointerEventPhaseMoved)
+    }
+
+    public override func mouseUp(with event: NSEvent) {
+        pushButton(event, isDown: false)
+    }
+
+    public override func rightMouseDown(with event: NSEvent) {
+        pushButton(event, isDown: true)
+    }
+
+    public override func rightMouseDragged(with event: NSEvent) {
+        pushPointer(event, phase: SBPointerEventPhaseMoved)
+    }
+
+    public override func rightMouseUp(with event: NSEvent) {
+        pushButton(event, isDown: false)
+    }
+
+    public override func otherMouseDown(with event: NSEvent) {
+        pushButton(event, isDown: true)
+    }
+
+    public override func otherMouseDragged(with event: NSEvent) {
+        pushPointer(event, phase: SBPointerEventPhaseMoved)
+    }
+
+    public override func otherMouseUp(with event: NSEvent) {
+        pushButton(event, isDown: false)
+    }
+
+    public override func scrollWheel(with event: NSEvent) {
+        var sbEvent = makeEvent(SBEventTypeIdScroll, event)
+        sbEvent.data.scroll.window_location = location(inWindow: event.locationInWindow)
+        sbEvent.data.scroll.delta = SBVector2(x: Double(event.scrollingDeltaX), y: Double(event.scrollingDeltaY))
+        sbEvent.data.scroll.is_discrete = !event.hasPreciseScrollingDeltas
+        sbEvent.data.scroll.is_direction_inverted = event.isDirectionInvertedFromDevice
+        sbEvent.data.scroll.modifiers = SBKeyModifiers(event.modifierFlags)
+        inputEvents.append((sbEvent, nil))
+    }
+
+    public override func keyDown(with event: NSEvent) {
+        let strings = KeyStrings(
+            chars: event.characters ?? "",
+            charsWithoutMod: event.charactersIgnoringModifiers ?? ""
+        )
+        pushKey(event, phase: event.isARepeat ? SBKeyEventPhaseRepeat : SBKeyEventPhaseDown, strings: strings)
+    }
+
+    public override func keyUp(with event: NSEvent) {
+        let strings = KeyStrings(
+            chars: event.characters ?? "",
+            charsWithoutMod: event.charactersIgnoringModifiers ?? ""
+        )
+        pushKey(event, phase: SBKeyEventPhaseUp, strings: strings)
+    }
+
+    public override func flagsChanged(with event: NSEvent) {
+        // modifier keys have no characters, and only report the new state of all modifiers
+        guard let flag = SBHostingView.modifierFlags[event.keyCode] else {
+            return
+        }
+        let phase = event.modifierFlags.contains(flag) ? SBKeyEventPhaseDown : SBKeyEventPhaseUp
+        pushKey(event, phase: phase, strings: KeyStrings(chars: "", charsWithoutMod: ""))
+    }
+
+    public override func setFrameSize(_ newSize: NSSize) {
+        super.setFrameSize(newSize)
+        var event = SBEvent()
+        event.type = SBEventTypeIdResize
+        event.timestamp = ProcessInfo.processInfo.systemUptime
+        event.data.resize.size = SBVector2(x: Double(bounds.width), y: Double(bounds.height))
+        // in order with input events, since their locations are flipped using this size
+        inputEvents.append((event, nil))
+    }
+
+    /// Modifier flags of modifier keys, by virtual key code.
+    private static let modifierFlags: [UInt16: NSEvent.ModifierFlags] = [
+        0x36: .command, 0x37: .command, 0x38: .shift, 0x3C: .shift, 0x39: .capsLock,
+        0x3A: .option, 0x3D: .option, 0x3B: .control, 0x3E: .control, 0x3F: .function,
+    ]
+
+    /// Key codes by virtual key code (from Carbon’s Events.h).
+    private static let keyCodes: [UInt16: SBKeyCode] = [
+        0x00: SBKeyCodeA, 0x01: SBKeyCodeS, 0x02: SBKeyCodeD, 0x03: SBKeyCodeF,
+        0x04: SBKeyCodeH, 0x05: SBKeyCodeG, 0x06: SBKeyCodeZ, 0x07: SBKeyCodeX,
+        0x08: SBKeyCodeC, 0x09: SBKeyCodeV, 0x0A: SBKeyCodeSection, 0x0B: SBKeyCodeB,
+        0x0C: SBKeyCodeQ, 0x0D: SBKeyCodeW, 0x0E: SBKeyCodeE, 0x0F: SBKeyCodeR,
+        0x10: SBKeyCodeY, 0x11: SBKeyCodeT, 0x12: SBKeyCodeN1, 0x13: SBKeyCodeN2,
+        0x14: SBKeyCodeN3, 0x15: SBKeyCodeN4, 0x16: SBKeyCodeN6, 0x17: SBKeyCodeN5,
+        0x18: SBKeyCodeEqual, 0x19: SBKeyCodeN9, 0x1A: SBKeyCodeN7, 0x1B: SBKeyCodeMinus,
+        0x1C: SBKeyCodeN8, 0x1D: SBKeyCodeN0, 0x1E: SBKeyCodeRightBracket, 0x1F: SBKeyCodeO,
+        0x20: SBKeyCodeU, 0x21: SBKeyCodeLeftBracket, 0x22: SBKeyCodeI, 0x23: SBKeyCodeP,
+        0x24: SBKeyCodeReturn, 0x25: SBKeyCodeL, 0x26: SBKeyCodeJ, 0x27: SBKeyCodeQuote,
+        0x28: SBKeyCodeK, 0x29: SBKeyCodeSemicolon, 0x2A: SBKeyCodeBackslash, 0x2B: SBKeyCodeComma,
+        0x2C: SBKeyCodeSlash, 0x2D: SBKeyCodeN, 0x2E: SBKeyCodeM, 0x2F: SBKeyCodePeriod,
+        0x30: SBKeyCodeTab, 0x31: SBKeyCodeSpace, 0x32: SBKeyCodeGrave, 0x33: SBKeyCodeDelete,
+        0x35: SBKeyCodeEscape, 0x36: SBKeyCodeRightCommand, 0x37: SBKeyCodeCommand,
+        0x38: SBKeyCodeShift, 0x39: SBKeyCodeCapsLock, 0x3A: SBKeyCodeOption,
+        0x3B: SBKeyCodeControl, 0x3C: SBKeyCodeRightShift, 0x3D: SBKeyCodeRightOption,
+        0x3E: SBKeyCodeRightControl, 0x3F: SBKeyCodeFunction, 0x40: SBKeyCodeF17,
+        0x41: SBKeyCodeNumpadDecimal, 0x43: SBKeyCodeNumpadMultiply, 0x45: SBKeyCodeNumpadPlus,
+        0x47: SBKeyCodeNumpadClear, 0x4B: SBKeyCodeNumpadDivide, 0x4C: SBKeyCodeNumpadEnter,
+        0x4E: SBKeyCodeNumpadMinus, 0x4F: SBKeyCodeF18, 0x50: SBKeyCodeF19,
+        0x51: SBKeyCodeNumpadEqual, 0x52: SBKeyCodeNumpad0, 0x53: SBKeyCodeNumpad1,
+        0x54: SBKeyCodeNumpad2, 0x55: SBKeyCodeNumpad3, 0x56: SBKeyCodeNumpad4,
+        0x57: SBKeyCodeNumpad5, 0x58: SBKeyCodeNumpad6, 0x59: SBKeyCodeNumpad7,
+        0x5A: SBKeyCodeF20, 0x5B: SBKeyCodeNumpad8, 0x5C: SBKeyCodeNumpad9,
+        0x5E: SBKeyCodeUnderscore, 0x5F: SBKeyCodeNumpadComma, 0x60: SBKeyCodeF5,
+        0x61: SBKeyCodeF6, 0x62: SBKeyCodeF7, 0x63: SBKeyCodeF3, 0x64: SBKeyCodeF8,
+        0x65: SBKeyCodeF9, 0x67: SBKeyCodeF11, 0x69: SBKeyCodeF13, 0x6A: SBKeyCodeF16,
+        0x6B: SBKeyCodeF14, 0x6D: SBKeyCodeF10, 0x6F: SBKeyCodeF12, 0x71: SBKeyCodeF15,
+        0x72: SBKeyCodeInsert, 0x73: SBKeyCodeHome, 0x74: SBKeyCodePageUp,
+        0x75: SBKeyCodeForwardDelete, 0x76: SBKeyCodeF4, 0x77: SBKeyCodeEnd, 0x78: SBKeyCodeF2,
+        0x79: SBKeyCodePageDown, 0x7A: SBKeyCodeF1, 0x7B: SBKeyCodeLeftArrow,
+        0x7C: SBKeyCodeRightArrow, 0x7D: SBKeyCodeDownArrow, 0x7E: SBKeyCodeUpArrow,
+    ]
+
+    // MARK: - File drags
+
+    private func pushFileDrag(_ sender: NSDraggingInfo?, phase: SBDragEventPhase) {
+        var paths: [String] = []
+        var location = SBVector2(x: 0, y: 0)
+        if let sender = sender {
+            let urls = sender.draggingPasteboard.readObjects(forClasses: [NSURL.self], options: [
+                .urlReadingFileURLsOnly: true,
+            ]) as? [URL] ?? []
+            paths = urls.map { $0.path }
+            location = self.location(inWindow: sender.draggingLocation)
+        }
+
+        var event = SBEvent()
+        event.type = SBEventTypeIdFileDrag
+        event.timestamp = ProcessInfo.processInfo.systemUptime
+        event.data.file_drag.window_location = location
+        event.data.file_drag.phase = phase
+        event.data.file_drag.path_count = UInt64(paths.count)
+        dragEvents.append((event, paths))
+    }
+
+    public override var wantsPeriodicDraggingUpdates: Bool {
+        return false
+    }
+
+    public override func draggingEntered(_ sender: NSDraggingInfo) -> NSDragOperation {
+        dropAccepted = false
+        pushFileDrag(sender, phase: SBDragEventPhaseEntered)
+        return []
+    }
+
+    public override func draggingUpdated(_ sender: NSDraggingInfo) -> NSDragOperation {
+        pushFileDrag(sender, phase: SBDragEventPhaseMoved)
+        return dropAccepted ? .copy : []
+    }
+
+    public override func draggingExited(_ sender: NSDraggingInfo?) {
+        pushFileDrag(sender, phase: SBDragEventPhaseExited)
+    }
+
+    public override func performDragOperation(_ sender: NSDraggingInfo) -> Bool {
+        pushFileDrag(sender, phase: SBDragEventPhaseDropped)
+        return dropAccepted
+    }
 }
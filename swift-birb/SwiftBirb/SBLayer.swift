@@ -33,6 +33,134 @@ class SBLayer : NSView, SBRenderable {
         update(patch)
     }
 
+    /// Draws borders that Core Animation can’t, i.e. dashed ones or ones that aren’t inside the bounds.
+    var borderLayer: CAShapeLayer?
+
+    func updateBorder(_ data: SBLayerPatch) {
+        let layer = self.layer!
+        let width = CGFloat(data.border_width)
+        let isDashed = data.border_dash > 0 && data.border_gap > 0
+        var overflow: CGFloat = 0
+        if data.border_alignment == SBBorderAlignmentCenter {
+            overflow = width / 2
+        } else if data.border_alignment == SBBorderAlignmentOutside {
+            overflow = width
+        }
+
+        if !isDashed && overflow == 0 {
+            layer.borderWidth = width
+            layer.borderColor = data.border_color.cgColor
+            borderLayer?.removeFromSuperlayer()
+            borderLayer = nil
+            return
+        }
+
+        layer.borderWidth = 0
+        let borderLayer = self.borderLayer ?? CAShapeLayer()
+        if self.borderLayer == nil {
+            // above subviews, like Core Animation borders
+            borderLayer.zPosition = 1
+            borderLayer.fillColor = nil
+            layer.addSublayer(borderLayer)
+            self.borderLayer = borderLayer
+        }
+
+        CATransaction.begin()
+        CATransaction.setDisableActions(true)
+        // the stroke is centered on the path
+        let inset = width / 2 - overflow
+        let rect = layer.bounds.insetBy(dx: inset, dy: inset)
+        var radius = layer.cornerRadius > 0 ? layer.cornerRadius - inset : 0
+        radius = max(min(radius, rect.width / 2, rect.height / 2), 0)
+        borderLayer.path = CGPath(roundedRect: rect, cornerWidth: radius, cornerHeight: radius, transform: nil)
+        borderLayer.strokeColor = data.border_color.cgColor
+        borderLayer.lineWidth = width
+        borderLayer.lineDashPattern = isDashed
+            ? [NSNumber(value: data.border_dash), NSNumber(value: data.border_gap)]
+            : nil
+        CATransaction.commit()
+    }
+
+    /// Draws the background gradient, if there is one.
+    var gradientLayer: CAGradientLayer?
+
+    func updateGradient(_ data: SBLayerPatch) {
+        let layer = self.layer!
+        let gradient = data.background_gradient
+        if gradient.stop_count == 0 {
+            gradientLayer?.removeFromSuperlayer()
+            gradientLayer = nil
+            return
+        }
+
+        let gradientLayer = self.gradientLayer ?? CAGradientLayer()
+        if self.gradientLayer == nil {
+            // below subviews, above the background
+            layer.insertSublayer(gradientLayer, at: 0)
+            self.gradientLayer = gradientLayer
+        }
+
+        CATransaction.begin()
+        CATransaction.setDisableActions(true)
+        let stops = UnsafeBufferPointer(start: gradient.stops, count: Int(gradient.stop_count))
+        gradientLayer.frame = layer.bounds
+        gradientLayer.colors = stops.map { $0.color.cgColor }
+        gradientLayer.locations = stops.map { NSNumber(value: $0.location) }
+        // the layer isn’t flipped, so y is inverted
+        gradientLayer.startPoint = CGPoint(x: gradient.start.x, y: 1 - gradient.start.y)
+        gradientLayer.endPoint = CGPoint(x: gradient.end.x, y: 1 - gradient.end.y)
+        gradientLayer.cornerRadius = layer.cornerRadius
+        if #available(macOS 10.13, *) {
+            gradientLayer.maskedCorners = layer.maskedCorners
+        }
+        if #available(macOS 10.15, *) {
+            gradientLayer.cornerCurve = layer.cornerCurve
+        }
+        CATransaction.commit()
+    }
+
+    /// Returns the path of a clip shape in layer coordinates, or nil if there is none.
+    func clipPath(_ shape: SBClipShape) -> CGPath? {
+        let bounds = layer!.bounds
+        // clip shapes have the origin at the top left corner and y pointing down
+        let transform = CGAffineTransform(a: 1, b: 0, c: 0, d: -1, tx: bounds.minX, ty: bounds.maxY)
+        let rect = shape.rect.cgRect
+        let path = CGMutablePath()
+        if shape.type == SBClipShapeTypeRoundedRect {
+            let radii = shape.corner_radius
+            var radius = CGFloat(max(radii.top_left, radii.top_right, radii.bottom_right, radii.bottom_left))
+            radius = min(radius, rect.width / 2, rect.height / 2)
+            path.addRoundedRect(in: rect, cornerWidth: radius, cornerHeight: radius, transform: transform)
+        } else if shape.type == SBClipShapeTypeEllipse {
+            path.addEllipse(in: rect, transform: transform)
+        } else if shape.type == SBClipShapeTypePolygon {
+            let points = UnsafeBufferPointer(start: shape.points, count: Int(shape.point_count))
+            path.addLines(between: points.map { CGPoint(x: $0.x, y: $0.y) }, transform: transform)
+            path.closeSubpath()
+        } else {
+            return nil
+        }
+        return path
+    }
+
+    func updateClip(_ data: SBLayerPatch) {
+        let layer = self.layer!
+        guard let path = clipPath(data.clip_shape) else {
+            layer.mask = nil
+            layer.masksToBounds = data.clip_contents
+            return
+        }
+
+        CATransaction.begin()
+        CATransaction.setDisableActions(true)
+        // unlike masksToBounds, a mask also clips the layer’s own background and border
+        let mask = layer.mask as? CAShapeLayer ?? CAShapeLayer()
+        mask.path = path
+        layer.mask = mask
+        layer.masksToBounds = false
+        CATransaction.commit()
+    }
+
     @available(macOS 10.12.2, *)
     override func makeTouchBar() -> NSTouchBar? {
         // TODO: hook up to callback
@@ -41,12 +169,18 @@ class SBLayer : NSView, SBRenderable {
 
     func updateContentsScale() {
         layer!.contentsScale = window?.deepestScreen?.backingScaleFactor ?? 1
+        layer!.rasterizationScale = layer!.contentsScale
     }
 
     override func viewDidMoveToWindow() {
         updateContentsScale()
     }
 
+    override func viewDidChangeBackingProperties() {
+        super.viewDidChangeBackingProperties()
+        updateContentsScale()
+    }
+
     // MARK: SBRenderable
 
     func update(_ patch: SBNodePatch) {
@@ -56,15 +190,28 @@ class SBLayer : NSView, SBRenderable {
         let layer = self.layer!
         layer.bounds = data.bounds.cgRect
         layer.backgroundColor = data.background.cgColor
-        layer.cornerRadius = CGFloat(data.corner_radius)
+        let radii = data.corner_radius
+        // Core Animation only supports one radius, so the largest is applied to all rounded corners
+        layer.cornerRadius = CGFloat(max(radii.top_left, radii.top_right, radii.bottom_right, radii.bottom_left))
+        if #available(macOS 10.13, *) {
+            // the layer isn’t flipped, so the top corners are at MaxY
+            var maskedCorners: CACornerMask = []
+            if radii.top_left > 0 { maskedCorners.insert(.layerMinXMaxYCorner) }
+            if radii.top_right > 0 { maskedCorners.insert(.layerMaxXMaxYCorner) }
+            if radii.bottom_right > 0 { maskedCorners.insert(.layerMaxXMinYCorner) }
+            if radii.bottom_left > 0 { maskedCorners.insert(.layerMinXMinYCorner) }
+            layer.maskedCorners = maskedCorners
+        }
         if #available(macOS 10.15, *) {
-            layer.cornerCurve = .continuous
+            layer.cornerCurve = radii.continuous ? .continuous : .circular
         }
-        layer.borderWidth = CGFloat(data.border_width)
-        layer.borderColor = data.border_color.cgColor
-        layer.masksToBounds = data.clip_contents
+        updateGradient(data)
+        updateBorder(data)
+        updateClip(data)
         layer.transform = data.transform.caTransform3D
         layer.opacity = Float(data.opacity)
+        layer.allowsGroupOpacity = data.group_opacity
+        layer.shouldRasterize = data.should_rasterize
     }
 
     func addSubview(_ subview: SBNode) {
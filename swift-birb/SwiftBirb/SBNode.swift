@@ -10,11 +10,17 @@ import Cocoa
 
 /// A single birb tree node.
 @objc public class SBNode : NSObject {
-    unowned let host: SBHostingView
+    weak var host: SBHostingView?
     var type: SBNodeType
     var view: SBRenderable
     var parent: SBNode?
     var subviews: [SBNode] = []
+    private var isRemoved = false
+
+    /// False if this node was removed or its hosting view was deallocated.
+    @objc public var isAlive: Bool {
+        return host != nil && !isRemoved
+    }
 
     init(host: SBHostingView, patch: SBNodePatch) {
         self.host = host
@@ -35,17 +41,18 @@ import Cocoa
     }
 
     @objc public func setSubviews(offset: UInt64, length: UInt64, subviews: SBNodeList) {
-        let subviews = subviews.intoList()
+        let subviews = subviews.toList()
         let a = Int(offset)
         let b = Int(length)
 
-        self.subviews[a...(a + b)] = subviews[...]
+        self.subviews.replaceSubrange(a..<(a + b), with: subviews)
 
         // TODO: need to call addSubview/removeSubview on SBRenderable?
     }
 
     /// Removes this node.
     @objc func remove() {
+        isRemoved = true
         view.removeSelf()
     }
 
@@ -0,0 +1,96 @@
+//
+//  SBStatusItem.swift
+//  SwiftBirb
+//
+//  Created by cpsdqs on 2019-10-21.
+//  Copyright © 2019 cpsdqs. All rights reserved.
+//
+
+import Cocoa
+
+/// An item in the system menu bar, which may show a birb hosting view in a popover when clicked.
+@objc public class SBStatusItem : NSObject {
+    private let item: NSStatusItem
+    private var popover: NSPopover?
+
+    @objc public override init() {
+        item = NSStatusBar.system.statusItem(withLength: NSStatusItem.variableLength)
+        super.init()
+        item.button?.target = self
+        item.button?.action = #selector(buttonClicked(_:))
+    }
+
+    deinit {
+        popover?.close()
+        NSStatusBar.system.removeStatusItem(item)
+    }
+
+    @objc public func setTitle(_ title: String) {
+        item.button?.title = title
+    }
+
+    /// Sets the icon from an RGBA buffer (straight alpha) of the given size in pixels, or removes it if data is nil.
+    ///
+    /// Template images are drawn in the menu bar’s text color, using only their alpha channel.
+    @objc public func setImage(width: UInt64, height: UInt64, scale: Double, data: UnsafePointer<UInt8>?, template: Bool) {
+        guard let data = data else {
+            item.button?.image = nil
+            return
+        }
+        let bytes = Data(bytes: data, count: Int(width * height * 4))
+        guard let provider = CGDataProvider(data: bytes as CFData), let cgImage = CGImage(
+            width: Int(width),
+            height: Int(height),
+            bitsPerComponent: 8,
+            bitsPerPixel: 32,
+            bytesPerRow: Int(width) * 4,
+            space: sRGB,
+            bitmapInfo: CGBitmapInfo(rawValue: CGImageAlphaInfo.last.rawValue),
+            provider: provider,
+            decode: nil,
+            shouldInterpolate: true,
+            intent: .defaultIntent
+        ) else {
+            return
+        }
+        let size = NSSize(width: Double(width) / scale, height: Double(height) / scale)
+        let image = NSImage(cgImage: cgImage, size: size)
+        image.isTemplate = template
+        item.button?.image = image
+    }
+
+    /// Sets the view shown in a popover when the item is clicked, or removes the popover if view is nil.
+    @objc public func setPopoverContent(_ view: SBHostingView?, size: SBVector2) {
+        popover?.close()
+        guard let view = view else {
+            popover = nil
+            return
+        }
+        let controller = NSViewController()
+        controller.view = view
+        let popover = NSPopover()
+        popover.behavior = .transient
+        popover.contentViewController = controller
+        popover.contentSize = NSSize(width: size.x, height: size.y)
+        self.popover = popover
+    }
+
+    @objc public func showPopover() {
+        guard let popover = popover, let button = item.button, !popover.isShown else {
+            return
+        }
+        popover.show(relativeTo: button.bounds, of: button, preferredEdge: .minY)
+    }
+
+    @objc public func closePopover() {
+        popover?.close()
+    }
+
+    @objc private func buttonClicked(_ sender: Any?) {
+        if popover?.isShown == true {
+            closePopover()
+        } else {
+            showPopover()
+        }
+    }
+}
@@ -15,3 +15,5 @@ FOUNDATION_EXPORT double SwiftBirbVersionNumber;
 FOUNDATION_EXPORT const unsigned char SwiftBirbVersionString[];
 
 id SBHostingView_getClass(void);
+id SBStatusItem_getClass(void);
+id SBDock_getClass(void);
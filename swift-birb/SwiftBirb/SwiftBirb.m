@@ -15,3 +15,11 @@ static_assert(sizeof(float64_t) == 8, "float64_t has unexpected size");
 id SBHostingView_getClass(void) {
     return [SBHostingView class];
 }
+
+id SBStatusItem_getClass(void) {
+    return [SBStatusItem class];
+}
+
+id SBDock_getClass(void) {
+    return [SBDock class];
+}
@@ -67,13 +67,14 @@ fn build_cocoa() {
         "cargo:rustc-link-search={}/build/Build/Products/{}",
         out_dir, lib_out_path
     );
-    println!("cargo:rustc-link-lib=SwiftBirb");
 
     // also generate bindings from the header
     let bindings = bindgen::Builder::default()
         .header("./protocol.h")
         .whitelist_type("SBPatch")
         .whitelist_type("SBNodeList")
+        .whitelist_type("SBEvent")
+        .whitelist_var("SB_PROTOCOL_VERSION")
         // .default_enum_style(bindgen::EnumVariation::Rust { non_exhaustive: true, })
         .prepend_enum_name(false)
         .generate()
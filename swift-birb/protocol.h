@@ -5,6 +5,14 @@
 #include <stdint.h>
 #include <stdbool.h>
 
+/**
+ * The version of this protocol.
+ *
+ * This must be incremented whenever anything in this file changes incompatibly, so that a SwiftBirb
+ * framework built from a different version is detected instead of silently corrupting data.
+ */
+#define SB_PROTOCOL_VERSION 13
+
 #pragma mark - Basic Data Types
 
 typedef double float64_t;
@@ -49,6 +57,16 @@ typedef struct {
     float64_t a;
 } SBColor;
 
+/** Corner radii of a rounded rectangle. */
+typedef struct {
+    float64_t top_left;
+    float64_t top_right;
+    float64_t bottom_right;
+    float64_t bottom_left;
+    /** If true, corners curve continuously into the edges (like a squircle) instead of being circular. */
+    bool continuous;
+} SBCornerRadii;
+
 /**
  * A unique identifier for a view.
  *
@@ -63,6 +81,12 @@ typedef struct {
 
 #pragma mark - Events
 
+/*
+ * Locations in events are in points, relative to the bottom left corner of the hosting view, with
+ * the y axis pointing up (as in AppKit). They are flipped using the size from the most recent
+ * resize event, so resize events must be delivered in order with input events.
+ */
+
 /** Types of events. */
 typedef enum SBEventTypeId {
     SBEventTypeIdHover = 0,
@@ -70,6 +94,12 @@ typedef enum SBEventTypeId {
     SBEventTypeIdKey = 2,
     SBEventTypeIdScroll = 3,
     SBEventTypeIdResize = 4,
+    SBEventTypeIdMediaKey = 5,
+    SBEventTypeIdController = 6,
+    SBEventTypeIdFileDrag = 7,
+    SBEventTypeIdVisibility = 8,
+    SBEventTypeIdScaleFactor = 9,
+    SBEventTypeIdAccessibility = 10,
 } SBEventTypeId;
 
 /** A unique identifier for an event handler. */
@@ -136,7 +166,7 @@ typedef struct {
      * The device’s tilt, expressed as a unit vector aligned with the window coordinate system, with
      * an additional Z axis pointing outwards.
      *
-     * Devices that do not support this should always have a tilt of [0, 1, 1].
+     * Devices that do not support this should always have a tilt of [0, 0, 1].
      */
     SBVector3 tilt;
     /**
@@ -200,7 +230,7 @@ typedef struct {
      * The device’s tilt, expressed as a unit vector aligned with the window coordinate system, with
      * an additional Z axis pointing outwards.
      *
-     * Devices that do not support this should always have a tilt of [0, 1, 1].
+     * Devices that do not support this should always have a tilt of [0, 0, 1].
      */
     SBVector3 tilt;
     /**
@@ -213,6 +243,11 @@ typedef struct {
     SBPointerEventPhase phase;
     /** The modifier keys that are currently being pressed. */
     SBKeyModifiers modifiers;
+    /**
+     * The number of consecutive clicks, from NSEvent.clickCount (which uses the system
+     * double-click interval); or zero if not available.
+     */
+    uint32_t click_count;
 } SBPointerEvent;
 
 /** Key codes. */
@@ -289,7 +324,8 @@ typedef enum SBKeyCode {
     SBKeyCodeEnd = 0x4B,
     SBKeyCodePageUp = 0x4C,
     SBKeyCodePageDown = 0x4D,
-    SBKeyCodeSection = 0x4E,
+    SBKeyCodeUnderscore = 0x4E,
+    SBKeyCodeSection = 0x4F,
     SBKeyCodeF1 = 0x50,
     SBKeyCodeF2 = 0x51,
     SBKeyCodeF3 = 0x52,
@@ -343,7 +379,12 @@ typedef enum SBKeyEventPhase {
     SBKeyEventPhaseUp = 1,
 } SBKeyEventPhase;
 
-/** Keyboard events. */
+/**
+ * Keyboard events.
+ *
+ * The strings are null-terminated UTF-8 and owned by the hosting view; they stay valid until the
+ * next event is polled.
+ */
 typedef struct {
     /** The characters that are being input. */
     char* chars;
@@ -361,16 +402,161 @@ typedef struct {
 typedef struct {
     /** The location in the window. */
     SBVector2 window_location;
-    /** The scroll delta, in points. */
+    /** The scroll delta: in lines for discrete devices, and in points for continuous ones. */
     SBVector2 delta;
+    /** Whether the device scrolls in discrete increments, like most mouse wheels. */
+    bool is_discrete;
+    /** Whether the delta was already inverted for natural scrolling. */
+    bool is_direction_inverted;
+    /** The modifier keys that are currently being pressed. */
+    SBKeyModifiers modifiers;
 } SBScrollEvent;
 
+/** Media keys. */
+typedef enum SBMediaKey {
+    SBMediaKeyPlayPause = 0,
+    SBMediaKeyNext = 1,
+    SBMediaKeyPrevious = 2,
+    SBMediaKeyFastForward = 3,
+    SBMediaKeyRewind = 4,
+    SBMediaKeyVolumeUp = 5,
+    SBMediaKeyVolumeDown = 6,
+    SBMediaKeyMute = 7,
+} SBMediaKey;
+
+/** Media key events. */
+typedef struct {
+    /** The media key. */
+    SBMediaKey key;
+    /** The phase of this key event. */
+    SBKeyEventPhase phase;
+} SBMediaKeyEvent;
+
+/** Types of game controller input. */
+typedef enum SBControllerInputType {
+    SBControllerInputTypeConnected = 0,
+    SBControllerInputTypeDisconnected = 1,
+    SBControllerInputTypeButton = 2,
+    SBControllerInputTypeAxis = 3,
+} SBControllerInputType;
+
+/** Game controller buttons. */
+typedef enum SBControllerButton {
+    SBControllerButtonA = 0,
+    SBControllerButtonB = 1,
+    SBControllerButtonX = 2,
+    SBControllerButtonY = 3,
+    SBControllerButtonLeftShoulder = 4,
+    SBControllerButtonRightShoulder = 5,
+    SBControllerButtonLeftTrigger = 6,
+    SBControllerButtonRightTrigger = 7,
+    SBControllerButtonDpadUp = 8,
+    SBControllerButtonDpadDown = 9,
+    SBControllerButtonDpadLeft = 10,
+    SBControllerButtonDpadRight = 11,
+    SBControllerButtonLeftThumbstick = 12,
+    SBControllerButtonRightThumbstick = 13,
+    SBControllerButtonMenu = 14,
+    SBControllerButtonOptions = 15,
+} SBControllerButton;
+
+/**
+ * Game controller thumbstick axes.
+ *
+ * Positive y points down, like the window coordinate system.
+ */
+typedef enum SBControllerAxis {
+    SBControllerAxisLeftX = 0,
+    SBControllerAxisLeftY = 1,
+    SBControllerAxisRightX = 2,
+    SBControllerAxisRightY = 3,
+} SBControllerAxis;
+
+/** Game controller events. */
+typedef struct {
+    /** An ID that identifies the controller while it’s connected. */
+    uint64_t controller;
+    /** The type of input. */
+    SBControllerInputType type;
+    /** The button, for button input. */
+    SBControllerButton button;
+    /** The axis, for axis input. */
+    SBControllerAxis axis;
+    /** The button value between 0 and 1, or the axis value between -1 and 1. */
+    float64_t value;
+    /** Whether the button counts as pressed, for button input. */
+    bool is_pressed;
+} SBControllerEvent;
+
+/**
+ * File drag event phases.
+ *
+ * This enum has an ordering: Entered < Moved < Exited = Dropped, and events are guaranteed to be
+ * generated in this order for a given drag.
+ */
+typedef enum SBDragEventPhase {
+    SBDragEventPhaseEntered = 0,
+    SBDragEventPhaseMoved = 1,
+    SBDragEventPhaseExited = 2,
+    SBDragEventPhaseDropped = 3,
+} SBDragEventPhase;
+
+/**
+ * File drag events.
+ *
+ * The file paths are not part of the event; they can be obtained from the hosting view right
+ * after polling the event.
+ */
+typedef struct {
+    /** The location in the window. */
+    SBVector2 window_location;
+    /** The phase of this drag event. */
+    SBDragEventPhase phase;
+    /** The number of dragged files. */
+    uint64_t path_count;
+} SBFileDragEvent;
+
+/** Resize events. */
+typedef struct {
+    /** The new size of the hosting view, in points. */
+    SBVector2 size;
+} SBResizeEvent;
+
+/** Window visibility events. */
+typedef struct {
+    /** False if the window is fully occluded or minimized. */
+    bool is_visible;
+} SBVisibilityEvent;
+
+/** Backing scale factor change events. */
+typedef struct {
+    /** Device pixels per point of the screen the window is on. */
+    double scale_factor;
+} SBScaleFactorEvent;
+
+/** System accessibility settings change events. */
+typedef struct {
+    /** Whether the user prefers as little motion on screen as possible. */
+    bool reduce_motion;
+    /** Whether the user prefers opaque surfaces over translucent ones. */
+    bool reduce_transparency;
+    /** Whether the user prefers more contrast between foreground and background elements. */
+    bool increase_contrast;
+} SBAccessibilityEvent;
+
 /** Event data. */
 typedef union {
     SBHoverEvent hover;
     SBPointerEvent pointer;
     SBKeyEvent key;
     SBScrollEvent scroll;
+    SBResizeEvent resize;
+    SBMediaKeyEvent media_key;
+    SBControllerEvent controller;
+    SBFileDragEvent file_drag;
+    SBVisibilityEvent visibility;
+    SBScaleFactorEvent scale_factor;
+    SBAccessibilityEvent accessibility;
 } SBEventData;
 
 /** An event. */
@@ -400,16 +586,90 @@ typedef enum SBPatchType {
     SBPatchTypeRemove = 2,
 } SBPatchType;
 
+/** Types of clip shapes. */
+typedef enum SBClipShapeType {
+    /** Sublayers are only clipped to the bounds, and only if `clip_contents` is set. */
+    SBClipShapeTypeNone = 0,
+    SBClipShapeTypeRoundedRect = 1,
+    SBClipShapeTypeEllipse = 2,
+    SBClipShapeTypePolygon = 3,
+} SBClipShapeType;
+
+/**
+ * A shape to clip sublayers to, in the coordinate system of the layer with the origin at its top
+ * left corner and y pointing down.
+ *
+ * The points of a polygon belong to the sender and are only valid until the call the patch was
+ * passed to returns.
+ */
+typedef struct {
+    SBClipShapeType type;
+    /** The rectangle of a rounded rectangle or ellipse. */
+    SBRect rect;
+    /** The corner radii of a rounded rectangle. */
+    SBCornerRadii corner_radius;
+    /** The points of a polygon. */
+    const SBVector2* points;
+    uint64_t point_count;
+} SBClipShape;
+
+/** A color stop of a gradient. */
+typedef struct {
+    /** Location from 0 (the start of the gradient) to 1 (the end). */
+    float64_t location;
+    SBColor color;
+} SBGradientStop;
+
+/**
+ * A linear gradient, with the start and end in unit coordinates of the layer: (0, 0) is the top
+ * left corner and (1, 1) the bottom right corner.
+ *
+ * The stops belong to the sender and are only valid until the call the patch was passed to
+ * returns.
+ */
+typedef struct {
+    SBVector2 start;
+    SBVector2 end;
+    /** Color stops in ascending order. */
+    const SBGradientStop* stops;
+    /** The number of stops, or zero if there is no gradient. */
+    uint64_t stop_count;
+} SBGradient;
+
+/** Where a layer border is drawn relative to the edge of the layer bounds. */
+typedef enum SBBorderAlignment {
+    SBBorderAlignmentInside = 0,
+    SBBorderAlignmentCenter = 1,
+    SBBorderAlignmentOutside = 2,
+} SBBorderAlignment;
+
 /** Layer description. */
 typedef struct {
     SBRect bounds;
     SBColor background;
-    float64_t corner_radius;
+    /** A gradient drawn over the background color. */
+    SBGradient background_gradient;
+    SBCornerRadii corner_radius;
     float64_t border_width;
     SBColor border_color;
+    SBBorderAlignment border_alignment;
+    /** Length of border dashes, or zero if the border is solid. */
+    float64_t border_dash;
+    /** Length of the gaps between border dashes. */
+    float64_t border_gap;
     bool clip_contents;
+    /** If set, sublayers are clipped to this shape instead, regardless of `clip_contents`. */
+    SBClipShape clip_shape;
     SBMatrix3 transform;
+    /** Opacity of the layer, which also applies to its sublayers. */
     float64_t opacity;
+    /**
+     * If true, the layer and its sublayers are composited together before the opacity is applied,
+     * so overlapping sublayers don’t show through each other.
+     */
+    bool group_opacity;
+    /** If true, the composited contents of the layer may be cached as a bitmap. */
+    bool should_rasterize;
 } SBLayerPatch;
 
 /** Types of nodes. */
@@ -444,7 +704,11 @@ typedef struct {
     SBPatchData data;
 } SBPatch;
 
-/** A list of nodes. Ownership of the pointers is guaranteed (not the pointees though) */
+/**
+ * A list of SBNode pointers.
+ *
+ * The list belongs to the sender and is only valid until the call it was passed to returns.
+ */
 typedef struct {
     void* nodes;
     uint64_t count;
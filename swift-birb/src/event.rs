@@ -0,0 +1,968 @@
+//! Typed events from the hosting view.
+//!
+//! The data of an [`SBEvent`] is a C union that may only be read as the variant named by the
+//! event type, and the C enums in it may have values this crate doesn’t know (e.g. if the
+//! framework is newer). Converting it to an [`Event`] checks both in one place, so the rest of the
+//! crate never reads union fields or raw enum values.
+//!
+//! Events must come from the hosting view, which always fills in the variant named by the event
+//! type, and keeps the strings of key events alive until the next event is polled.
+
+// for matching on the C enum constants of the protocol
+#![allow(non_upper_case_globals)]
+
+use crate::protocol::*;
+use birb::accessibility::AccessibilitySettings;
+use birb::events::{
+    ControllerAxis, ControllerButton, KeyCode, KeyModifiers, MediaKey, PointerDevice,
+};
+use birb::raw_events::{
+    DragEventPhase, EventId, HoverEventPhase, KeyEventPhase, PointerEventPhase, RawEvent,
+};
+use core::convert::TryFrom;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// An event from the hosting view.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Event {
+    Hover {
+        device: PointerDevice,
+        /// The location in the hosting view, with the y axis pointing up.
+        location: (f64, f64),
+        tilt: (f64, f64, f64),
+        /// The unique ID of the pointing device; may be zero.
+        pointer_id: u64,
+        phase: HoverEventPhase,
+        modifiers: KeyModifiers,
+    },
+    Pointer {
+        device: PointerDevice,
+        /// The location in the hosting view, with the y axis pointing up.
+        location: (f64, f64),
+        pressure: f64,
+        tilt: (f64, f64, f64),
+        /// The unique ID of the pointing device; may be zero.
+        pointer_id: u64,
+        phase: PointerEventPhase,
+        modifiers: KeyModifiers,
+        click_count: u32,
+    },
+    Key {
+        chars: String,
+        chars_without_mod: String,
+        key_code: KeyCode,
+        phase: KeyEventPhase,
+        modifiers: KeyModifiers,
+    },
+    Scroll {
+        /// The location in the hosting view, with the y axis pointing up.
+        location: (f64, f64),
+        /// The scroll delta: in lines for discrete devices, and in points for continuous ones.
+        delta: (f64, f64),
+        is_discrete: bool,
+        is_direction_inverted: bool,
+        modifiers: KeyModifiers,
+    },
+    Resize {
+        /// The new size of the hosting view.
+        size: (f64, f64),
+    },
+    MediaKey {
+        key: MediaKey,
+        phase: KeyEventPhase,
+    },
+    Controller {
+        controller: u64,
+        input: ControllerInput,
+    },
+    FileDrag {
+        /// The location in the hosting view, with the y axis pointing up.
+        location: (f64, f64),
+        phase: DragEventPhase,
+        /// The number of dragged files, whose paths must be obtained from the hosting view.
+        path_count: u64,
+    },
+    Visibility {
+        is_visible: bool,
+    },
+    ScaleFactor {
+        scale_factor: f64,
+    },
+    Accessibility {
+        reduce_motion: bool,
+        reduce_transparency: bool,
+        increase_contrast: bool,
+    },
+}
+
+/// Game controller input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ControllerInput {
+    Connected,
+    Disconnected,
+    Button {
+        button: ControllerButton,
+        value: f64,
+        is_pressed: bool,
+    },
+    Axis {
+        axis: ControllerAxis,
+        value: f64,
+    },
+}
+
+/// Errors when converting an [`SBEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InvalidEvent {
+    /// The event type is unknown.
+    UnknownType(SBEventTypeId),
+    /// An enum in the event data has an unknown value; contains the name of the field.
+    UnknownValue(&'static str, u32),
+}
+
+impl TryFrom<&SBEvent> for Event {
+    type Error = InvalidEvent;
+
+    fn try_from(event: &SBEvent) -> Result<Event, InvalidEvent> {
+        // each branch only reads the union variant named by the event type
+        Ok(match event.type_ {
+            SBEventTypeIdHover => {
+                let data = unsafe { event.data.hover };
+                Event::Hover {
+                    device: pointer_device(data.device)?,
+                    location: vector2(data.window_location),
+                    tilt: vector3(data.tilt),
+                    pointer_id: data.pointer_id,
+                    phase: hover_phase(data.phase)?,
+                    modifiers: data.modifiers.into(),
+                }
+            }
+            SBEventTypeIdPointer => {
+                let data = unsafe { event.data.pointer };
+                Event::Pointer {
+                    device: pointer_device(data.device)?,
+                    location: vector2(data.window_location),
+                    pressure: data.pressure,
+                    tilt: vector3(data.tilt),
+                    pointer_id: data.pointer_id,
+                    phase: pointer_phase(data.phase)?,
+                    modifiers: data.modifiers.into(),
+                    click_count: data.click_count,
+                }
+            }
+            SBEventTypeIdKey => {
+                let data = unsafe { event.data.key };
+                let key_code = u8::try_from(data.keyCode)
+                    .ok()
+                    .and_then(KeyCode::from_u8)
+                    .ok_or(InvalidEvent::UnknownValue("key_code", data.keyCode))?;
+                Event::Key {
+                    chars: unsafe { string(data.chars) },
+                    chars_without_mod: unsafe { string(data.chars_without_mod) },
+                    key_code,
+                    phase: key_phase(data.phase)?,
+                    modifiers: data.modifiers.into(),
+                }
+            }
+            SBEventTypeIdScroll => {
+                let data = unsafe { event.data.scroll };
+                Event::Scroll {
+                    location: vector2(data.window_location),
+                    delta: vector2(data.delta),
+                    is_discrete: data.is_discrete,
+                    is_direction_inverted: data.is_direction_inverted,
+                    modifiers: data.modifiers.into(),
+                }
+            }
+            SBEventTypeIdResize => Event::Resize {
+                size: vector2(unsafe { event.data.resize.size }),
+            },
+            SBEventTypeIdMediaKey => {
+                let data = unsafe { event.data.media_key };
+                Event::MediaKey {
+                    key: media_key(data.key)?,
+                    phase: key_phase(data.phase)?,
+                }
+            }
+            SBEventTypeIdController => {
+                let data = unsafe { event.data.controller };
+                Event::Controller {
+                    controller: data.controller,
+                    input: controller_input(&data)?,
+                }
+            }
+            SBEventTypeIdFileDrag => {
+                let data = unsafe { event.data.file_drag };
+                Event::FileDrag {
+                    location: vector2(data.window_location),
+                    phase: drag_phase(data.phase)?,
+                    path_count: data.path_count,
+                }
+            }
+            SBEventTypeIdVisibility => Event::Visibility {
+                is_visible: unsafe { event.data.visibility.is_visible },
+            },
+            SBEventTypeIdScaleFactor => Event::ScaleFactor {
+                scale_factor: unsafe { event.data.scale_factor.scale_factor },
+            },
+            SBEventTypeIdAccessibility => {
+                let data = unsafe { event.data.accessibility };
+                Event::Accessibility {
+                    reduce_motion: data.reduce_motion,
+                    reduce_transparency: data.reduce_transparency,
+                    increase_contrast: data.increase_contrast,
+                }
+            }
+            type_ => return Err(InvalidEvent::UnknownType(type_)),
+        })
+    }
+}
+
+/// Converts events to raw events, flipping locations so that the y axis points down, and assigning
+/// event IDs to hover and pointer event streams.
+#[derive(Debug, Default)]
+pub(crate) struct EventConverter {
+    /// The height of the hosting view, from the most recent resize event.
+    height: f64,
+    next_event_id: EventId,
+    /// Hover streams in progress, by device and pointer ID.
+    hover_streams: HashMap<(u8, u64), EventId>,
+    /// Pointer streams in progress, by device and pointer ID.
+    pointer_streams: HashMap<(u8, u64), EventId>,
+}
+
+impl EventConverter {
+    /// Converts an event to a raw event.
+    ///
+    /// File drag events have no paths yet; they must be obtained from the hosting view.
+    pub(crate) fn convert(&mut self, event: Event) -> RawEvent {
+        match event {
+            Event::Hover {
+                device,
+                location,
+                tilt,
+                pointer_id,
+                phase,
+                modifiers,
+            } => RawEvent::Hover {
+                device,
+                root_location: self.flip(location),
+                tilt,
+                event_id: stream_id(
+                    &mut self.hover_streams,
+                    &mut self.next_event_id,
+                    (device as u8, pointer_id),
+                    phase == HoverEventPhase::Entered,
+                    phase == HoverEventPhase::Left,
+                ),
+                unique_id: pointer_id.into(),
+                phase,
+                modifiers,
+            },
+            Event::Pointer {
+                device,
+                location,
+                pressure,
+                tilt,
+                pointer_id,
+                phase,
+                modifiers,
+                click_count,
+            } => RawEvent::Pointer {
+                device,
+                root_location: self.flip(location),
+                pressure,
+                tilt,
+                event_id: stream_id(
+                    &mut self.pointer_streams,
+                    &mut self.next_event_id,
+                    (device as u8, pointer_id),
+                    phase == PointerEventPhase::Began,
+                    phase == PointerEventPhase::Ended || phase == PointerEventPhase::Canceled,
+                ),
+                unique_id: pointer_id.into(),
+                phase,
+                modifiers,
+                click_count,
+            },
+            Event::Key {
+                chars,
+                chars_without_mod,
+                key_code,
+                phase,
+                modifiers,
+            } => RawEvent::Key {
+                chars,
+                chars_without_mod,
+                key_code,
+                phase,
+                modifiers,
+            },
+            Event::Scroll {
+                location,
+                delta,
+                is_discrete,
+                is_direction_inverted,
+                modifiers,
+            } => RawEvent::Scroll {
+                root_location: self.flip(location),
+                delta,
+                is_discrete,
+                is_direction_inverted,
+                modifiers,
+            },
+            Event::Resize { size } => {
+                self.height = size.1;
+                RawEvent::SetRootSize { size }
+            }
+            Event::MediaKey { key, phase } => RawEvent::MediaKey { key, phase },
+            Event::Controller { controller, input } => match input {
+                ControllerInput::Connected => RawEvent::ControllerConnection {
+                    controller,
+                    is_connected: true,
+                },
+                ControllerInput::Disconnected => RawEvent::ControllerConnection {
+                    controller,
+                    is_connected: false,
+                },
+                ControllerInput::Button {
+                    button,
+                    value,
+                    is_pressed,
+                } => RawEvent::ControllerButton {
+                    controller,
+                    button,
+                    value,
+                    is_pressed,
+                },
+                ControllerInput::Axis { axis, value } => RawEvent::ControllerAxis {
+                    controller,
+                    axis,
+                    value,
+                },
+            },
+            Event::FileDrag {
+                location, phase, ..
+            } => RawEvent::FileDrag {
+                root_location: self.flip(location),
+                phase,
+                paths: Vec::new(),
+            },
+            Event::Visibility { is_visible } => RawEvent::SetVisibility { is_visible },
+            Event::ScaleFactor { scale_factor } => RawEvent::SetScaleFactor { scale_factor },
+            Event::Accessibility {
+                reduce_motion,
+                reduce_transparency,
+                increase_contrast,
+            } => RawEvent::SetAccessibility {
+                settings: AccessibilitySettings {
+                    reduce_motion,
+                    reduce_transparency,
+                    increase_contrast,
+                },
+            },
+        }
+    }
+
+    /// Converts a location in the hosting view to the root view, whose y axis points down.
+    fn flip(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x, self.height - y)
+    }
+}
+
+/// Returns the event ID of the stream of a device, starting a new stream if the event begins
+/// one (or if there is none, e.g. because the first event was dropped) and forgetting it if the
+/// event ends it.
+fn stream_id(
+    streams: &mut HashMap<(u8, u64), EventId>,
+    next_event_id: &mut EventId,
+    device: (u8, u64),
+    begins: bool,
+    ends: bool,
+) -> EventId {
+    let id = match streams.get(&device) {
+        Some(id) if !begins => *id,
+        _ => {
+            let id = *next_event_id;
+            *next_event_id += 1;
+            streams.insert(device, id);
+            id
+        }
+    };
+    if ends {
+        streams.remove(&device);
+    }
+    id
+}
+
+impl From<SBKeyModifiers> for KeyModifiers {
+    fn from(modifiers: SBKeyModifiers) -> KeyModifiers {
+        KeyModifiers::new(
+            modifiers.shift,
+            modifiers.control,
+            modifiers.option,
+            modifiers.command,
+        )
+    }
+}
+
+fn vector2(vector: SBVector2) -> (f64, f64) {
+    (vector.x, vector.y)
+}
+
+fn vector3(vector: SBVector3) -> (f64, f64, f64) {
+    (vector.x, vector.y, vector.z)
+}
+
+/// Copies a C string, which may be null.
+unsafe fn string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
+
+fn pointer_device(device: SBPointerDevice) -> Result<PointerDevice, InvalidEvent> {
+    Ok(match device {
+        SBPointerDeviceTouch => PointerDevice::Touch,
+        SBPointerDevicePen => PointerDevice::Pen,
+        SBPointerDeviceEraser => PointerDevice::Eraser,
+        SBPointerDeviceCursor => PointerDevice::Cursor,
+        _ => return Err(InvalidEvent::UnknownValue("device", device)),
+    })
+}
+
+fn hover_phase(phase: SBHoverEventPhase) -> Result<HoverEventPhase, InvalidEvent> {
+    Ok(match phase {
+        SBHoverEventPhaseEntered => HoverEventPhase::Entered,
+        SBHoverEventPhaseMoved => HoverEventPhase::Moved,
+        SBHoverEventPhaseStationary => HoverEventPhase::Stationary,
+        SBHoverEventPhaseLeft => HoverEventPhase::Left,
+        _ => return Err(InvalidEvent::UnknownValue("phase", phase)),
+    })
+}
+
+fn pointer_phase(phase: SBPointerEventPhase) -> Result<PointerEventPhase, InvalidEvent> {
+    Ok(match phase {
+        SBPointerEventPhaseBegan => PointerEventPhase::Began,
+        SBPointerEventPhaseMoved => PointerEventPhase::Moved,
+        SBPointerEventPhaseStationary => PointerEventPhase::Stationary,
+        SBPointerEventPhaseEnded => PointerEventPhase::Ended,
+        SBPointerEventPhaseCanceled => PointerEventPhase::Canceled,
+        _ => return Err(InvalidEvent::UnknownValue("phase", phase)),
+    })
+}
+
+fn key_phase(phase: SBKeyEventPhase) -> Result<KeyEventPhase, InvalidEvent> {
+    Ok(match phase {
+        SBKeyEventPhaseDown => KeyEventPhase::Pressed,
+        SBKeyEventPhaseRepeat => KeyEventPhase::Repeat,
+        SBKeyEventPhaseUp => KeyEventPhase::Released,
+        _ => return Err(InvalidEvent::UnknownValue("phase", phase)),
+    })
+}
+
+fn drag_phase(phase: SBDragEventPhase) -> Result<DragEventPhase, InvalidEvent> {
+    Ok(match phase {
+        SBDragEventPhaseEntered => DragEventPhase::Entered,
+        SBDragEventPhaseMoved => DragEventPhase::Moved,
+        SBDragEventPhaseExited => DragEventPhase::Exited,
+        SBDragEventPhaseDropped => DragEventPhase::Dropped,
+        _ => return Err(InvalidEvent::UnknownValue("phase", phase)),
+    })
+}
+
+fn media_key(key: SBMediaKey) -> Result<MediaKey, InvalidEvent> {
+    Ok(match key {
+        SBMediaKeyPlayPause => MediaKey::PlayPause,
+        SBMediaKeyNext => MediaKey::Next,
+        SBMediaKeyPrevious => MediaKey::Previous,
+        SBMediaKeyFastForward => MediaKey::FastForward,
+        SBMediaKeyRewind => MediaKey::Rewind,
+        SBMediaKeyVolumeUp => MediaKey::VolumeUp,
+        SBMediaKeyVolumeDown => MediaKey::VolumeDown,
+        SBMediaKeyMute => MediaKey::Mute,
+        _ => return Err(InvalidEvent::UnknownValue("key", key)),
+    })
+}
+
+/// Converts controller input; the button and axis are only checked for the input types that
+/// use them.
+fn controller_input(data: &SBControllerEvent) -> Result<ControllerInput, InvalidEvent> {
+    Ok(match data.type_ {
+        SBControllerInputTypeConnected => ControllerInput::Connected,
+        SBControllerInputTypeDisconnected => ControllerInput::Disconnected,
+        SBControllerInputTypeButton => ControllerInput::Button {
+            button: controller_button(data.button)?,
+            value: data.value,
+            is_pressed: data.is_pressed,
+        },
+        SBControllerInputTypeAxis => ControllerInput::Axis {
+            axis: controller_axis(data.axis)?,
+            value: data.value,
+        },
+        type_ => return Err(InvalidEvent::UnknownValue("type", type_)),
+    })
+}
+
+fn controller_button(button: SBControllerButton) -> Result<ControllerButton, InvalidEvent> {
+    Ok(match button {
+        SBControllerButtonA => ControllerButton::A,
+        SBControllerButtonB => ControllerButton::B,
+        SBControllerButtonX => ControllerButton::X,
+        SBControllerButtonY => ControllerButton::Y,
+        SBControllerButtonLeftShoulder => ControllerButton::LeftShoulder,
+        SBControllerButtonRightShoulder => ControllerButton::RightShoulder,
+        SBControllerButtonLeftTrigger => ControllerButton::LeftTrigger,
+        SBControllerButtonRightTrigger => ControllerButton::RightTrigger,
+        SBControllerButtonDpadUp => ControllerButton::DpadUp,
+        SBControllerButtonDpadDown => ControllerButton::DpadDown,
+        SBControllerButtonDpadLeft => ControllerButton::DpadLeft,
+        SBControllerButtonDpadRight => ControllerButton::DpadRight,
+        SBControllerButtonLeftThumbstick => ControllerButton::LeftThumbstick,
+        SBControllerButtonRightThumbstick => ControllerButton::RightThumbstick,
+        SBControllerButtonMenu => ControllerButton::Menu,
+        SBControllerButtonOptions => ControllerButton::Options,
+        _ => return Err(InvalidEvent::UnknownValue("button", button)),
+    })
+}
+
+fn controller_axis(axis: SBControllerAxis) -> Result<ControllerAxis, InvalidEvent> {
+    Ok(match axis {
+        SBControllerAxisLeftX => ControllerAxis::LeftX,
+        SBControllerAxisLeftY => ControllerAxis::LeftY,
+        SBControllerAxisRightX => ControllerAxis::RightX,
+        SBControllerAxisRightY => ControllerAxis::RightY,
+        _ => return Err(InvalidEvent::UnknownValue("axis", axis)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem;
+
+    /// Creates an event of the given type with zeroed data.
+    fn sb_event(type_: SBEventTypeId) -> SBEvent {
+        let mut event: SBEvent = unsafe { mem::zeroed() };
+        event.type_ = type_;
+        event
+    }
+
+    fn sb_modifiers(shift: bool, control: bool, option: bool, command: bool) -> SBKeyModifiers {
+        SBKeyModifiers {
+            shift,
+            control,
+            option,
+            command,
+        }
+    }
+
+    /// Converts an event, which must be valid.
+    fn convert(converter: &mut EventConverter, event: &SBEvent) -> RawEvent {
+        converter.convert(Event::try_from(event).unwrap())
+    }
+
+    fn resize(converter: &mut EventConverter, width: f64, height: f64) {
+        let mut event = sb_event(SBEventTypeIdResize);
+        event.data.resize.size = SBVector2 {
+            x: width,
+            y: height,
+        };
+        assert_eq!(
+            convert(converter, &event),
+            RawEvent::SetRootSize {
+                size: (width, height)
+            }
+        );
+    }
+
+    fn pointer(device: SBPointerDevice, pointer_id: u64, phase: SBPointerEventPhase) -> SBEvent {
+        let mut event = sb_event(SBEventTypeIdPointer);
+        event.data.pointer.device = device;
+        event.data.pointer.pointer_id = pointer_id;
+        event.data.pointer.phase = phase;
+        event
+    }
+
+    fn hover(pointer_id: u64, phase: SBHoverEventPhase) -> SBEvent {
+        let mut event = sb_event(SBEventTypeIdHover);
+        event.data.hover.device = SBPointerDeviceCursor;
+        event.data.hover.pointer_id = pointer_id;
+        event.data.hover.phase = phase;
+        event
+    }
+
+    fn event_id(event: &RawEvent) -> EventId {
+        match event {
+            RawEvent::Hover { event_id, .. } | RawEvent::Pointer { event_id, .. } => *event_id,
+            event => panic!("{:?} has no event ID", event),
+        }
+    }
+
+    #[test]
+    fn pointer_event() {
+        let mut converter = EventConverter::default();
+        resize(&mut converter, 300., 200.);
+
+        let mut event = pointer(SBPointerDevicePen, 7, SBPointerEventPhaseBegan);
+        event.data.pointer.window_location = SBVector2 { x: 10., y: 150. };
+        event.data.pointer.pressure = 0.5;
+        event.data.pointer.tilt = SBVector3 {
+            x: 0.25,
+            y: -0.5,
+            z: 0.75,
+        };
+        event.data.pointer.modifiers = sb_modifiers(true, false, false, true);
+        event.data.pointer.click_count = 2;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::Pointer {
+                device: PointerDevice::Pen,
+                root_location: (10., 50.),
+                pressure: 0.5,
+                tilt: (0.25, -0.5, 0.75),
+                event_id: 0,
+                unique_id: 7,
+                phase: PointerEventPhase::Began,
+                modifiers: KeyModifiers::SHIFT | KeyModifiers::COMMAND,
+                click_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn hover_event() {
+        let mut converter = EventConverter::default();
+        resize(&mut converter, 300., 200.);
+
+        let mut event = hover(0, SBHoverEventPhaseMoved);
+        event.data.hover.window_location = SBVector2 { x: 0., y: 200. };
+        event.data.hover.tilt = SBVector3 {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        };
+        event.data.hover.modifiers = sb_modifiers(false, true, true, false);
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::Hover {
+                device: PointerDevice::Cursor,
+                root_location: (0., 0.),
+                tilt: (0., 0., 1.),
+                event_id: 0,
+                unique_id: 0,
+                phase: HoverEventPhase::Moved,
+                modifiers: KeyModifiers::CONTROL | KeyModifiers::OPTION,
+            }
+        );
+    }
+
+    #[test]
+    fn modifiers() {
+        for &(shift, control, option, command) in &[
+            (false, false, false, false),
+            (true, false, false, false),
+            (false, true, false, false),
+            (false, false, true, false),
+            (false, false, false, true),
+            (true, true, true, true),
+        ] {
+            assert_eq!(
+                KeyModifiers::from(sb_modifiers(shift, control, option, command)),
+                KeyModifiers::new(shift, control, option, command)
+            );
+        }
+    }
+
+    #[test]
+    fn locations_are_flipped_with_the_latest_size() {
+        let mut converter = EventConverter::default();
+        let mut event = pointer(SBPointerDeviceCursor, 0, SBPointerEventPhaseMoved);
+        event.data.pointer.window_location = SBVector2 { x: 5., y: 30. };
+        let root_location = |converter: &mut EventConverter| match convert(converter, &event) {
+            RawEvent::Pointer { root_location, .. } => root_location,
+            event => panic!("unexpected event {:?}", event),
+        };
+
+        resize(&mut converter, 100., 100.);
+        assert_eq!(root_location(&mut converter), (5., 70.));
+        resize(&mut converter, 100., 40.);
+        assert_eq!(root_location(&mut converter), (5., 10.));
+    }
+
+    #[test]
+    fn locations_stay_in_points_when_the_scale_factor_changes() {
+        let mut converter = EventConverter::default();
+        resize(&mut converter, 100., 100.);
+
+        let mut scale_factor = sb_event(SBEventTypeIdScaleFactor);
+        scale_factor.data.scale_factor.scale_factor = 2.;
+        assert_eq!(
+            convert(&mut converter, &scale_factor),
+            RawEvent::SetScaleFactor { scale_factor: 2. }
+        );
+
+        let mut event = sb_event(SBEventTypeIdScroll);
+        event.data.scroll.window_location = SBVector2 { x: 20., y: 25. };
+        match convert(&mut converter, &event) {
+            RawEvent::Scroll { root_location, .. } => assert_eq!(root_location, (20., 75.)),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn pointer_streams() {
+        let mut converter = EventConverter::default();
+        let mut ids = Vec::new();
+        for &phase in &[
+            SBPointerEventPhaseBegan,
+            SBPointerEventPhaseMoved,
+            SBPointerEventPhaseStationary,
+            SBPointerEventPhaseEnded,
+            SBPointerEventPhaseBegan,
+            SBPointerEventPhaseCanceled,
+            // the beginning of this stream was dropped
+            SBPointerEventPhaseMoved,
+            SBPointerEventPhaseEnded,
+        ] {
+            let event = pointer(SBPointerDeviceTouch, 1, phase);
+            ids.push(event_id(&convert(&mut converter, &event)));
+        }
+        assert_eq!(ids, [0, 0, 0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn concurrent_pointer_streams() {
+        let mut converter = EventConverter::default();
+        let mut id = |device, pointer_id, phase| {
+            event_id(&convert(
+                &mut converter,
+                &pointer(device, pointer_id, phase),
+            ))
+        };
+        assert_eq!(id(SBPointerDeviceTouch, 1, SBPointerEventPhaseBegan), 0);
+        assert_eq!(id(SBPointerDeviceTouch, 2, SBPointerEventPhaseBegan), 1);
+        assert_eq!(id(SBPointerDevicePen, 1, SBPointerEventPhaseBegan), 2);
+        assert_eq!(id(SBPointerDeviceTouch, 1, SBPointerEventPhaseMoved), 0);
+        assert_eq!(id(SBPointerDeviceTouch, 2, SBPointerEventPhaseEnded), 1);
+        assert_eq!(id(SBPointerDevicePen, 1, SBPointerEventPhaseMoved), 2);
+    }
+
+    #[test]
+    fn hover_streams() {
+        let mut converter = EventConverter::default();
+        let mut ids = Vec::new();
+        for &phase in &[
+            SBHoverEventPhaseEntered,
+            SBHoverEventPhaseMoved,
+            SBHoverEventPhaseStationary,
+            SBHoverEventPhaseLeft,
+            SBHoverEventPhaseEntered,
+            SBHoverEventPhaseLeft,
+        ] {
+            ids.push(event_id(&convert(&mut converter, &hover(3, phase))));
+        }
+        assert_eq!(ids, [0, 0, 0, 0, 1, 1]);
+
+        // hover and pointer streams of the same device are separate
+        let event = pointer(SBPointerDeviceCursor, 3, SBPointerEventPhaseBegan);
+        let pointer_id = event_id(&convert(&mut converter, &event));
+        let hover_id = event_id(&convert(
+            &mut converter,
+            &hover(3, SBHoverEventPhaseEntered),
+        ));
+        assert_ne!(pointer_id, hover_id);
+    }
+
+    #[test]
+    fn key_event() {
+        let mut converter = EventConverter::default();
+        let chars = b"\xc3\xa5\0";
+        let chars_without_mod = b"a\0";
+        let mut event = sb_event(SBEventTypeIdKey);
+        event.data.key.chars = chars.as_ptr() as *mut c_char;
+        event.data.key.chars_without_mod = chars_without_mod.as_ptr() as *mut c_char;
+        event.data.key.keyCode = SBKeyCodeA;
+        event.data.key.phase = SBKeyEventPhaseRepeat;
+        event.data.key.modifiers = sb_modifiers(false, false, true, false);
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::Key {
+                chars: "å".into(),
+                chars_without_mod: "a".into(),
+                key_code: KeyCode::A,
+                phase: KeyEventPhase::Repeat,
+                modifiers: KeyModifiers::OPTION,
+            }
+        );
+
+        // null strings are empty
+        event.data.key.chars = core::ptr::null_mut();
+        event.data.key.chars_without_mod = core::ptr::null_mut();
+        event.data.key.keyCode = SBKeyCodeSection;
+        event.data.key.phase = SBKeyEventPhaseUp;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::Key {
+                chars: String::new(),
+                chars_without_mod: String::new(),
+                key_code: KeyCode::Section,
+                phase: KeyEventPhase::Released,
+                modifiers: KeyModifiers::OPTION,
+            }
+        );
+    }
+
+    #[test]
+    fn scroll_event() {
+        let mut converter = EventConverter::default();
+        resize(&mut converter, 100., 100.);
+        let mut event = sb_event(SBEventTypeIdScroll);
+        event.data.scroll.window_location = SBVector2 { x: 40., y: 90. };
+        event.data.scroll.delta = SBVector2 { x: -1., y: 3. };
+        event.data.scroll.is_discrete = true;
+        event.data.scroll.is_direction_inverted = true;
+        event.data.scroll.modifiers = sb_modifiers(true, false, false, false);
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::Scroll {
+                root_location: (40., 10.),
+                delta: (-1., 3.),
+                is_discrete: true,
+                is_direction_inverted: true,
+                modifiers: KeyModifiers::SHIFT,
+            }
+        );
+    }
+
+    #[test]
+    fn window_events() {
+        let mut converter = EventConverter::default();
+        resize(&mut converter, 640., 480.);
+
+        let mut event = sb_event(SBEventTypeIdVisibility);
+        event.data.visibility.is_visible = true;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::SetVisibility { is_visible: true }
+        );
+
+        let mut event = sb_event(SBEventTypeIdAccessibility);
+        event.data.accessibility.reduce_motion = true;
+        event.data.accessibility.increase_contrast = true;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::SetAccessibility {
+                settings: AccessibilitySettings {
+                    reduce_motion: true,
+                    reduce_transparency: false,
+                    increase_contrast: true,
+                },
+            }
+        );
+
+        let mut event = sb_event(SBEventTypeIdFileDrag);
+        event.data.file_drag.window_location = SBVector2 { x: 1., y: 2. };
+        event.data.file_drag.phase = SBDragEventPhaseDropped;
+        event.data.file_drag.path_count = 2;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::FileDrag {
+                root_location: (1., 478.),
+                phase: DragEventPhase::Dropped,
+                paths: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn media_key_and_controller_events() {
+        let mut converter = EventConverter::default();
+
+        let mut event = sb_event(SBEventTypeIdMediaKey);
+        event.data.media_key.key = SBMediaKeyVolumeUp;
+        event.data.media_key.phase = SBKeyEventPhaseDown;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::MediaKey {
+                key: MediaKey::VolumeUp,
+                phase: KeyEventPhase::Pressed,
+            }
+        );
+
+        let mut event = sb_event(SBEventTypeIdController);
+        event.data.controller.controller = 4;
+        event.data.controller.type_ = SBControllerInputTypeButton;
+        event.data.controller.button = SBControllerButtonX;
+        event.data.controller.value = 0.75;
+        event.data.controller.is_pressed = true;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::ControllerButton {
+                controller: 4,
+                button: ControllerButton::X,
+                value: 0.75,
+                is_pressed: true,
+            }
+        );
+
+        event.data.controller.type_ = SBControllerInputTypeAxis;
+        event.data.controller.axis = SBControllerAxisRightY;
+        event.data.controller.value = -1.;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::ControllerAxis {
+                controller: 4,
+                axis: ControllerAxis::RightY,
+                value: -1.,
+            }
+        );
+
+        event.data.controller.type_ = SBControllerInputTypeDisconnected;
+        assert_eq!(
+            convert(&mut converter, &event),
+            RawEvent::ControllerConnection {
+                controller: 4,
+                is_connected: false,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_events() {
+        let event = sb_event(0xffff);
+        assert_eq!(
+            Event::try_from(&event),
+            Err(InvalidEvent::UnknownType(0xffff))
+        );
+
+        let event = pointer(SBPointerDeviceCursor, 0, 0xffff);
+        assert_eq!(
+            Event::try_from(&event),
+            Err(InvalidEvent::UnknownValue("phase", 0xffff))
+        );
+
+        let mut event = sb_event(SBEventTypeIdKey);
+        event.data.key.keyCode = 0x1ff;
+        event.data.key.phase = SBKeyEventPhaseDown;
+        assert_eq!(
+            Event::try_from(&event),
+            Err(InvalidEvent::UnknownValue("key_code", 0x1ff))
+        );
+
+        // the button isn’t read for connection events
+        let mut event = sb_event(SBEventTypeIdController);
+        event.data.controller.type_ = SBControllerInputTypeConnected;
+        event.data.controller.button = 0xffff;
+        assert_eq!(
+            Event::try_from(&event),
+            Ok(Event::Controller {
+                controller: 0,
+                input: ControllerInput::Connected,
+            })
+        );
+    }
+}
@@ -1,21 +1,40 @@
+use crate::event::{Event, EventConverter};
 use crate::protocol::*;
+use birb::accessibility::{Announcement, AnnouncementPriority};
 use birb::backend::Backend;
-use birb::color::Color;
+use birb::color::{Color, Gradient};
+use birb::image::RgbaImage;
+use birb::print::PageSetup;
 use birb::raw_events::RawEvent;
-use birb::NativeView;
-use birb::Rect;
+use birb::{
+    BorderAlignment, BorderStyle, CornerRadius, CornerStyle, LayerProps, NativeView, Rect, Shape,
+};
 use cgmath::{Matrix3, Point2, Vector2};
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
 use core::ffi::c_void;
 use core::marker::PhantomData;
-use core::mem;
+#[cfg(feature = "raw-window-handle")]
+use core::ptr::NonNull;
+use core::{fmt, mem, slice};
+use objc::rc::autoreleasepool;
 use objc::runtime::*;
-use objc::{msg_send, sel, sel_impl};
+use objc::{class, msg_send, sel, sel_impl};
 use objc_id::Id;
+#[cfg(feature = "raw-window-handle")]
+use raw_window_handle::{
+    AppKitWindowHandle, DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle,
+    RawWindowHandle, WindowHandle,
+};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
 
-#[link(name = "SwiftBirb")]
+// not linked in tests, which only cover code that doesn’t call into SwiftBirb
+#[cfg_attr(not(test), link(name = "SwiftBirb"))]
 extern "C" {
     fn SBHostingView_getClass() -> *mut Object;
+    fn SBStatusItem_getClass() -> *mut Object;
+    fn SBDock_getClass() -> *mut Object;
 }
 
 pub mod protocol {
@@ -26,6 +45,8 @@ pub mod protocol {
     include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
 }
 
+mod event;
+
 impl Into<SBVector2> for Point2<f64> {
     fn into(self) -> SBVector2 {
         SBVector2 {
@@ -50,6 +71,17 @@ impl Into<SBRect> for Rect {
         }
     }
 }
+impl Into<SBCornerRadii> for CornerRadius {
+    fn into(self) -> SBCornerRadii {
+        SBCornerRadii {
+            top_left: self.top_left,
+            top_right: self.top_right,
+            bottom_right: self.bottom_right,
+            bottom_left: self.bottom_left,
+            continuous: self.style == CornerStyle::Continuous,
+        }
+    }
+}
 impl Into<SBColor> for Color {
     fn into(self) -> SBColor {
         SBColor {
@@ -78,14 +110,320 @@ impl Into<SBMatrix3> for Matrix3<f64> {
 
 type SomeUnsendType = *mut ();
 
+/// Proof that the current thread is the main thread, which Cocoa must only be used from.
+///
+/// Since this can’t be sent to other threads, anything holding one (such as [`SwiftBirb`]) stays
+/// on the main thread.
+#[derive(Debug, Clone, Copy)]
+pub struct MainThreadMarker(PhantomData<SomeUnsendType>);
+
+impl MainThreadMarker {
+    /// Returns a marker if the current thread is the main thread.
+    pub fn new() -> Option<MainThreadMarker> {
+        if is_main_thread() {
+            Some(MainThreadMarker(PhantomData))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a marker without checking the current thread.
+    ///
+    /// # Safety
+    /// The current thread must be the main thread.
+    pub unsafe fn new_unchecked() -> MainThreadMarker {
+        MainThreadMarker(PhantomData)
+    }
+}
+
+/// Returns true if the current thread is the main thread.
+fn is_main_thread() -> bool {
+    let is_main: BOOL = unsafe { msg_send![class!(NSThread), isMainThread] };
+    is_main == YES
+}
+
+/// Runs code that sends Objective-C messages, turning exceptions into errors.
+///
+/// Exceptions unwind through the closure, so it must not hold anything that needs to be dropped.
+///
+/// # Panics
+/// In debug builds, if this isn’t the main thread.
+unsafe fn catch<R, F: FnOnce() -> R>(f: F) -> Result<R, SBError> {
+    debug_assert!(
+        is_main_thread(),
+        "SwiftBirb must only be used on the main thread"
+    );
+    objc_exception::r#try(f).map_err(|exception| {
+        let exception: Id<Object> = Id::from_retained_ptr(exception as *mut Object);
+        let responds: BOOL = msg_send![exception, respondsToSelector: sel!(reason)];
+        let reason: *mut Object = if responds == YES {
+            msg_send![exception, reason]
+        } else {
+            msg_send![exception, description]
+        };
+        SBError::Exception(string_from_ns(reason))
+    })
+}
+
+/// Copies an NSString. Returns an empty string for nil.
+unsafe fn string_from_ns(string: *mut Object) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+    let utf8: *const c_char = msg_send![string, UTF8String];
+    CStr::from_ptr(utf8).to_string_lossy().into_owned()
+}
+
+/// Creates an NSString.
+unsafe fn ns_string(string: &str) -> Id<Object> {
+    const NS_UTF8_STRING_ENCODING: u64 = 4;
+    let ns_string: *mut Object = msg_send![class!(NSString), alloc];
+    let ns_string: *mut Object = msg_send![ns_string, initWithBytes:string.as_ptr() as *const c_void
+                                                            length:string.len()
+                                                          encoding:NS_UTF8_STRING_ENCODING];
+    Id::from_retained_ptr(ns_string)
+}
+
+/// Creates a file NSURL.
+unsafe fn file_url(path: &Path) -> Result<Id<Object>, SBError> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| SBError::System(format!("path is not UTF-8: {}", path.display())))?;
+    let path = ns_string(path);
+    let url: *mut Object = msg_send![class!(NSURL), fileURLWithPath:&*path];
+    Ok(Id::from_ptr(url))
+}
+
+/// Opens URLs, files, and apps through the system (i.e. NSWorkspace), so apps don’t have to shell
+/// out or call Cocoa themselves.
+///
+/// Like the backend, this must only be used on the main thread.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemServices(MainThreadMarker);
+
+impl SystemServices {
+    pub fn new(mtm: MainThreadMarker) -> SystemServices {
+        SystemServices(mtm)
+    }
+
+    fn workspace() -> *mut Object {
+        unsafe { msg_send![class!(NSWorkspace), sharedWorkspace] }
+    }
+
+    /// Opens a URL with its default app, e.g. web pages in the default browser.
+    pub fn open_url(&self, url: &str) -> Result<(), SBError> {
+        unsafe {
+            let url_string = ns_string(url);
+            let ns_url: *mut Object = msg_send![class!(NSURL), URLWithString:&*url_string];
+            if ns_url.is_null() {
+                return Err(SBError::System(format!("invalid URL: {}", url)));
+            }
+            if !Self::open(ns_url)? {
+                return Err(SBError::System(format!("could not open URL: {}", url)));
+            }
+            Ok(())
+        }
+    }
+
+    /// Opens a file or directory with its default app.
+    pub fn open_file(&self, path: &Path) -> Result<(), SBError> {
+        unsafe {
+            let url = file_url(path)?;
+            if !Self::open(&*url as *const Object as *mut Object)? {
+                return Err(SBError::System(format!(
+                    "could not open {}",
+                    path.display()
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    /// Shows files in Finder with the files selected.
+    pub fn reveal_in_finder(&self, paths: &[PathBuf]) -> Result<(), SBError> {
+        unsafe {
+            let urls = paths
+                .iter()
+                .map(|path| file_url(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            let url_ptrs: Vec<*const Object> = urls.iter().map(|url| &**url as *const _).collect();
+            let (url_ptrs, url_count) = (url_ptrs.as_ptr(), url_ptrs.len());
+            catch(|| {
+                let array: *mut Object =
+                    msg_send![class!(NSArray), arrayWithObjects:url_ptrs count:url_count];
+                msg_send![Self::workspace(), activateFileViewerSelectingURLs: array]
+            })
+        }
+    }
+
+    /// Launches an app by its bundle identifier (e.g. `com.apple.Safari`), or activates it if
+    /// it’s already running.
+    pub fn launch_app(&self, bundle_id: &str) -> Result<(), SBError> {
+        unsafe {
+            let bundle_id_string = ns_string(bundle_id);
+            let app_url: *mut Object = catch(
+                || msg_send![Self::workspace(), URLForApplicationWithBundleIdentifier:&*bundle_id_string],
+            )?;
+            if app_url.is_null() {
+                return Err(SBError::System(format!(
+                    "no app with bundle ID {}",
+                    bundle_id
+                )));
+            }
+            if !Self::open(app_url)? {
+                return Err(SBError::System(format!("could not launch {}", bundle_id)));
+            }
+            Ok(())
+        }
+    }
+
+    /// Opens an NSURL with NSWorkspace. Returns false if it couldn’t be opened.
+    unsafe fn open(url: *mut Object) -> Result<bool, SBError> {
+        let success: BOOL = catch(|| msg_send![Self::workspace(), openURL: url])?;
+        Ok(success == YES)
+    }
+}
+
+/// A request for attention made with [`Dock::request_attention`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttentionRequest(isize);
+
+/// The app’s Dock icon, for showing the state of long-running tasks.
+///
+/// Like the backend, this must only be used on the main thread.
+#[derive(Debug, Clone, Copy)]
+pub struct Dock(MainThreadMarker);
+
+impl Dock {
+    pub fn new(mtm: MainThreadMarker) -> Dock {
+        Dock(mtm)
+    }
+
+    /// Sets the badge label (e.g. a number of unread items), or removes the badge.
+    pub fn set_badge(&self, label: Option<&str>) -> Result<(), SBError> {
+        unsafe {
+            let label = label.map(|label| ns_string(label));
+            let label_ptr: *const Object = match &label {
+                Some(label) => &**label,
+                None => core::ptr::null(),
+            };
+            catch(|| msg_send![SBDock_getClass(), setBadge: label_ptr])
+        }
+    }
+
+    /// Shows a progress bar over the app icon, or removes it.
+    ///
+    /// Progress goes from 0 to 1.
+    pub fn set_progress(&self, progress: Option<f64>) -> Result<(), SBError> {
+        let progress = progress.map_or(-1., |progress| progress.max(0.));
+        unsafe { catch(|| msg_send![SBDock_getClass(), setProgress: progress]) }
+    }
+
+    /// Bounces the app icon if the app isn’t active: until the app is activated if `critical`,
+    /// and once otherwise.
+    ///
+    /// Returns None if the app is already active.
+    pub fn request_attention(&self, critical: bool) -> Result<Option<AttentionRequest>, SBError> {
+        let critical = if critical { YES } else { NO };
+        let request: isize =
+            unsafe { catch(|| msg_send![SBDock_getClass(), requestAttention: critical])? };
+        Ok(if request == 0 {
+            None
+        } else {
+            Some(AttentionRequest(request))
+        })
+    }
+
+    /// Stops bouncing the app icon.
+    pub fn cancel_attention(&self, request: AttentionRequest) -> Result<(), SBError> {
+        unsafe { catch(|| msg_send![SBDock_getClass(), cancelAttentionRequest: request.0]) }
+    }
+}
+
+/// An item in the system menu bar (NSStatusItem), for menu bar utility apps.
+///
+/// The item shows a title and/or an icon, and can show the views of a [`SwiftBirb`] backend in a
+/// popover when clicked. It is removed from the menu bar when dropped.
+pub struct StatusItem(Id<Object>, MainThreadMarker);
+
+impl StatusItem {
+    pub fn new(mtm: MainThreadMarker) -> Result<StatusItem, SBError> {
+        unsafe {
+            let class = SBStatusItem_getClass();
+            let id: *mut Object = catch(|| {
+                let i: *mut Object = msg_send![class, alloc];
+                msg_send![i, init]
+            })?;
+            Ok(StatusItem(Id::from_retained_ptr(id), mtm))
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) -> Result<(), SBError> {
+        unsafe {
+            let title = ns_string(title);
+            catch(|| msg_send![self.0, setTitle:&*title])
+        }
+    }
+
+    /// Sets the icon, or removes it.
+    ///
+    /// - `scale`: pixels per point (icons in the menu bar are usually 18 points tall)
+    /// - `template`: if true, the icon is drawn in the menu bar’s text color using only its alpha
+    ///   channel, which is what most menu bar icons should do
+    pub fn set_image(
+        &mut self,
+        image: Option<&RgbaImage>,
+        scale: f64,
+        template: bool,
+    ) -> Result<(), SBError> {
+        let (width, height, data) = match image {
+            Some(image) => (image.width(), image.height(), image.data().as_ptr()),
+            None => (0, 0, core::ptr::null()),
+        };
+        let template = if template { YES } else { NO };
+        unsafe {
+            catch(|| {
+                msg_send![self.0, setImageWithWidth:width as u64
+                                             height:height as u64
+                                              scale:scale
+                                               data:data
+                                           template:template]
+            })
+        }
+    }
+
+    /// Sets the backend whose views are shown in a popover when the item is clicked, and the
+    /// popover size in points, or removes the popover.
+    ///
+    /// The backend’s root view is shown, so its view tree should be rendered as usual; it can stay
+    /// up to date while the popover is closed.
+    pub fn set_popover(
+        &mut self,
+        content: Option<(&SwiftBirb, Vector2<f64>)>,
+    ) -> Result<(), SBError> {
+        let (view, size): (*const Object, SBVector2) = match content {
+            Some((backend, size)) => (&*backend.host.0, size.into()),
+            None => (core::ptr::null(), Vector2::new(0., 0.).into()),
+        };
+        unsafe { catch(|| msg_send![self.0, setPopoverContent:view size:size]) }
+    }
+
+    /// Shows the popover, if there is one.
+    pub fn show_popover(&mut self) -> Result<(), SBError> {
+        unsafe { catch(|| msg_send![self.0, showPopover]) }
+    }
+
+    pub fn close_popover(&mut self) -> Result<(), SBError> {
+        unsafe { catch(|| msg_send![self.0, closePopover]) }
+    }
+}
+
 /// SBHost (see SwiftBirb).
 ///
 /// Must only be used on the “main” thread (i.e. whichever thread connects to Cocoa).
-#[repr(C)]
-struct Host(Id<Object>, PhantomData<SomeUnsendType>);
+struct Host(Id<Object>, MainThreadMarker, EventConverter);
 
-/// This must invariably have the same memory layout as an objective-c id.
-#[repr(C)]
+/// A reference to an SBNode.
 pub struct SBViewRef {
     obj: Id<Object>,
     _phantom: PhantomData<SomeUnsendType>,
@@ -99,100 +437,500 @@ impl SBViewRef {
         }
     }
 
-    fn update(&mut self, patch: SBNodePatch) {
-        unsafe {
-            let _: () = msg_send![self.obj, updateWithPatch: patch];
-        }
+    /// Returns the SBNode object pointer.
+    fn as_ptr(&self) -> *mut Object {
+        &*self.obj as *const Object as *mut Object
     }
 
-    fn replace(&mut self, patch: SBNodePatch) {
-        unsafe {
-            let _: () = msg_send![self.obj, replaceWithPatch: patch];
+    /// Returns an error if the node was removed or its hosting view no longer exists.
+    fn check_alive(&self) -> Result<(), SBError> {
+        let is_alive: BOOL = unsafe { catch(|| msg_send![self.obj, isAlive])? };
+        if is_alive == NO {
+            return Err(SBError::ViewDeallocated);
         }
+        Ok(())
     }
 
-    fn set_subviews(&mut self, offset: u64, length: u64, subviews: protocol::SBNodeList) {
-        unsafe {
-            let _: () =
-                msg_send![self.obj, setSubviewsWithOffset:offset length:length subviews:subviews];
-        }
+    fn update(&mut self, patch: SBNodePatch) -> Result<(), SBError> {
+        self.check_alive()?;
+        unsafe { catch(|| msg_send![self.obj, updateWithPatch: patch]) }
     }
 
-    fn remove(&mut self) {
+    fn replace(&mut self, patch: SBNodePatch) -> Result<(), SBError> {
+        self.check_alive()?;
+        unsafe { catch(|| msg_send![self.obj, replaceWithPatch: patch]) }
+    }
+
+    fn set_subviews(
+        &mut self,
+        offset: u64,
+        length: u64,
+        subviews: protocol::SBNodeList,
+    ) -> Result<(), SBError> {
+        self.check_alive()?;
         unsafe {
-            let _: () = msg_send![self.obj, remove];
+            catch(
+                || msg_send![self.obj, setSubviewsWithOffset:offset length:length subviews:subviews],
+            )
         }
     }
+
+    fn remove(&mut self) -> Result<(), SBError> {
+        self.check_alive()?;
+        unsafe { catch(|| msg_send![self.obj, remove]) }
+    }
 }
 
 impl Host {
-    pub fn new() -> Host {
+    /// Creates a hosting view, after checking that SwiftBirb uses the same protocol version.
+    pub fn new(mtm: MainThreadMarker) -> Result<Host, SBError> {
         unsafe {
             let birb_host_class = SBHostingView_getClass();
-            let i: *mut Object = msg_send![birb_host_class, alloc];
-            let id = msg_send![i, init];
-            Host(Id::from_retained_ptr(id), PhantomData)
+
+            // versions from before the handshake don’t respond to this at all
+            let expected = u64::from(SB_PROTOCOL_VERSION);
+            let found: u64 = catch(|| {
+                let responds: BOOL =
+                    msg_send![birb_host_class, respondsToSelector: sel!(protocolVersion)];
+                if responds == YES {
+                    msg_send![birb_host_class, protocolVersion]
+                } else {
+                    0
+                }
+            })?;
+            if found != expected {
+                return Err(SBError::ProtocolMismatch { expected, found });
+            }
+
+            let id: *mut Object = catch(|| {
+                let i: *mut Object = msg_send![birb_host_class, alloc];
+                msg_send![i, init]
+            })?;
+            Ok(Host(
+                Id::from_retained_ptr(id),
+                mtm,
+                EventConverter::default(),
+            ))
         }
     }
 
     fn new_view(&mut self, patch: SBNodePatch) -> Result<SBViewRef, SBError> {
         unsafe {
-            let node: Id<Object> = msg_send![self.0, createView: patch];
-            Ok(SBViewRef::new(node))
+            let node: *mut Object = catch(|| msg_send![self.0, createView: patch])?;
+            Ok(SBViewRef::new(Id::from_ptr(node)))
         }
     }
 
-    fn set_root_view(&mut self, view: &SBViewRef) {
+    fn set_root_view(&mut self, view: &SBViewRef) -> Result<(), SBError> {
+        view.check_alive()?;
+        unsafe { catch(|| msg_send![self.0, setRootView:&*view.obj]) }
+    }
+
+    /// Renders a view offscreen into an image of the given size in pixels.
+    fn render_to_image(
+        &mut self,
+        view: &SBViewRef,
+        width: u32,
+        height: u32,
+    ) -> Result<Option<RgbaImage>, SBError> {
+        view.check_alive()?;
+        let mut data = vec![0_u8; width as usize * height as usize * 4];
+        let data_ptr = data.as_mut_ptr();
+        let success: BOOL = unsafe {
+            catch(|| {
+                msg_send![self.0, renderView:&*view.obj
+                                       width:width as u64
+                                      height:height as u64
+                                        data:data_ptr]
+            })?
+        };
+        if success == NO {
+            return Ok(None);
+        }
+        // CoreGraphics bitmap contexts only support premultiplied alpha
+        Ok(RgbaImage::from_premultiplied(width, height, data))
+    }
+
+    /// Captures the current contents of the hosting view at device resolution.
+    fn capture(&mut self) -> Result<Option<RgbaImage>, SBError> {
+        let size: SBVector2 = unsafe { catch(|| msg_send![self.0, captureSize])? };
+        let (width, height) = (size.x as u32, size.y as u32);
+        let mut data = vec![0_u8; width as usize * height as usize * 4];
+        let data_ptr = data.as_mut_ptr();
+        let success: BOOL = unsafe {
+            catch(|| {
+                msg_send![self.0, captureWithWidth:width as u64
+                                            height:height as u64
+                                              data:data_ptr]
+            })?
+        };
+        if success == NO {
+            return Ok(None);
+        }
+        Ok(RgbaImage::from_premultiplied(width, height, data))
+    }
+
+    /// Renders the given regions of a view into the pages of a PDF document.
+    fn export_pdf(
+        &mut self,
+        view: &SBViewRef,
+        setup: &PageSetup,
+        pages: &[Rect],
+    ) -> Result<Option<Vec<u8>>, SBError> {
+        view.check_alive()?;
+        let page_size: SBVector2 = setup.page_size.into();
+        let pages: Vec<SBRect> = pages.iter().map(|page| (*page).into()).collect();
+        let (pages_ptr, page_count) = (pages.as_ptr(), pages.len() as u64);
+        // the data is autoreleased, and may be large, so it shouldn’t wait for the run loop
+        autoreleasepool(|| unsafe {
+            let data: *mut Object = catch(|| {
+                msg_send![self.0, exportPDF:&*view.obj
+                                   pageSize:page_size
+                                     margin:setup.margin
+                                      pages:pages_ptr
+                                  pageCount:page_count]
+            })?;
+            if data.is_null() {
+                return Ok(None);
+            }
+            let bytes: *const u8 = msg_send![data, bytes];
+            let length: usize = msg_send![data, length];
+            Ok(Some(slice::from_raw_parts(bytes, length).to_vec()))
+        })
+    }
+
+    /// Posts a screen reader announcement.
+    fn announce(&mut self, announcement: &Announcement) -> Result<(), SBError> {
+        let priority: u64 = match announcement.priority {
+            AnnouncementPriority::Low => 0,
+            AnnouncementPriority::Medium => 1,
+            AnnouncementPriority::High => 2,
+        };
+        let message = announcement.message.as_bytes();
+        let (message_ptr, message_len) = (message.as_ptr(), message.len() as u64);
+        unsafe {
+            catch(|| {
+                msg_send![self.0, announce:message_ptr
+                                    length:message_len
+                                  priority:priority]
+            })
+        }
+    }
+
+    /// Takes the oldest pending event.
+    fn poll_event(&mut self) -> Result<Option<RawEvent>, SBError> {
+        loop {
+            let mut event: SBEvent = unsafe { mem::zeroed() };
+            let event_ptr = &mut event as *mut SBEvent;
+            let has_event: BOOL = unsafe { catch(|| msg_send![self.0, pollEvent: event_ptr])? };
+            if has_event == NO {
+                return Ok(None);
+            }
+            // events with unknown types or values are from a newer framework
+            let mut event = match Event::try_from(&event) {
+                Ok(event) => self.2.convert(event),
+                Err(_) => continue,
+            };
+            if let RawEvent::FileDrag { paths, .. } = &mut event {
+                *paths = self.polled_file_paths()?;
+            }
+            return Ok(Some(event));
+        }
+    }
+
+    /// Returns the file paths of the most recently polled file drag event.
+    fn polled_file_paths(&mut self) -> Result<Vec<PathBuf>, SBError> {
+        unsafe {
+            let paths: *mut Object = catch(|| msg_send![self.0, polledFilePaths])?;
+            let count: usize = msg_send![paths, count];
+            Ok((0..count)
+                .map(|i| {
+                    let path: *mut Object = msg_send![paths, objectAtIndex: i];
+                    PathBuf::from(string_from_ns(path))
+                })
+                .collect())
+        }
+    }
+
+    fn set_drop_accepted(&mut self, accepted: bool) -> Result<(), SBError> {
+        let accepted = if accepted { YES } else { NO };
+        unsafe { catch(|| msg_send![self.0, setDropAccepted: accepted]) }
+    }
+
+    /// Sets the frame autosave name of the hosting view’s window.
+    fn set_window_autosave_name(&mut self, name: Option<&str>) -> Result<bool, SBError> {
         unsafe {
-            let _: () = msg_send![self.0, setRootView:&view.obj];
+            let name = name.map(|name| ns_string(name));
+            let name_ptr: *const Object = match &name {
+                Some(name) => &**name,
+                None => core::ptr::null(),
+            };
+            let success: BOOL = catch(|| msg_send![self.0, setWindowAutosaveName: name_ptr])?;
+            Ok(success == YES)
         }
     }
 
+    /// Adds the hosting view to a parent `NSView`, filling it.
+    #[cfg(feature = "raw-window-handle")]
+    fn attach_to(&mut self, parent: *mut Object) -> Result<(), SBError> {
+        unsafe { catch(|| msg_send![self.0, attachToView: parent]) }
+    }
+
     /// Returns a reference to the SBHostingView object.
     fn object(&mut self) -> &mut Id<Object> {
         &mut self.0
     }
 }
 
-fn nv_to_patch(nv: NativeView) -> SBNodePatch {
-    match nv {
-        NativeView::Layer {
+/// Data that patches point to, which must outlive them.
+#[derive(Default)]
+struct PatchBuffers {
+    /// Points of a clip polygon.
+    points: Vec<SBVector2>,
+    /// Gradient stops.
+    stops: Vec<SBGradientStop>,
+}
+
+/// Converts a native view to a patch, storing data the patch points to in `buffers`.
+fn nv_to_patch(nv: NativeView, buffers: &mut PatchBuffers) -> SBNodePatch {
+    let props = match nv {
+        NativeView::Layer(props) => *props,
+        // the Swift host has no custom view kinds yet
+        NativeView::Custom {
+            bounds,
+            transform,
+            opacity,
+            ..
+        } => LayerProps {
             bounds,
-            background,
-            corner_radius,
-            border_width,
-            border_color,
-            clip_contents,
             transform,
             opacity,
-        } => SBNodePatch {
-            type_: SBNodeTypeLayer,
-            patch: SBNodePatchData {
-                layer: SBLayerPatch {
-                    bounds: bounds.into(),
-                    background: background.into(),
-                    border_color: border_color.into(),
-                    border_width,
-                    clip_contents,
-                    corner_radius,
-                    opacity,
-                    transform: transform.into(),
-                },
-            },
+            ..LayerProps::default()
+        },
+    };
+    SBNodePatch {
+        type_: SBNodeTypeLayer,
+        patch: SBNodePatchData {
+            layer: layer_to_sb(&props, buffers),
+        },
+    }
+}
+
+/// Converts layer properties to a layer patch, storing data the patch points to in `buffers`.
+///
+/// The Swift host doesn’t draw layer contents yet, and hit testing happens in the NV tree.
+fn layer_to_sb(props: &LayerProps, buffers: &mut PatchBuffers) -> SBLayerPatch {
+    let (border_width, border_color) = props.border.unwrap_or_default();
+    SBLayerPatch {
+        bounds: props.bounds.into(),
+        background: props.background.into(),
+        background_gradient: gradient_to_sb(props.background_gradient.as_ref(), &mut buffers.stops),
+        border_color: border_color.into(),
+        border_width,
+        border_alignment: match props.border_alignment {
+            BorderAlignment::Inside => SBBorderAlignmentInside,
+            BorderAlignment::Center => SBBorderAlignmentCenter,
+            BorderAlignment::Outside => SBBorderAlignmentOutside,
+        },
+        border_dash: match props.border_style {
+            BorderStyle::Solid => 0.,
+            BorderStyle::Dashed { dash, .. } => dash,
+        },
+        border_gap: match props.border_style {
+            BorderStyle::Solid => 0.,
+            BorderStyle::Dashed { gap, .. } => gap,
+        },
+        clip_contents: props.clip_contents,
+        clip_shape: clip_shape_to_sb(props.clip_shape.as_ref(), &mut buffers.points),
+        corner_radius: props.corner_radius.into(),
+        opacity: props.opacity,
+        group_opacity: props.group_opacity,
+        transform: props.transform.into(),
+        should_rasterize: props.should_rasterize,
+    }
+}
+
+fn clip_shape_to_sb(shape: Option<&Shape>, points: &mut Vec<SBVector2>) -> SBClipShape {
+    let mut sb_shape = SBClipShape {
+        type_: SBClipShapeTypeNone,
+        rect: Rect::zero().into(),
+        corner_radius: CornerRadius::default().into(),
+        points: core::ptr::null(),
+        point_count: 0,
+    };
+    match shape {
+        Some(Shape::RoundedRect {
+            rect,
+            corner_radius,
+        }) => {
+            sb_shape.type_ = SBClipShapeTypeRoundedRect;
+            sb_shape.rect = (*rect).into();
+            sb_shape.corner_radius = (*corner_radius).into();
+        }
+        Some(Shape::Ellipse(rect)) => {
+            sb_shape.type_ = SBClipShapeTypeEllipse;
+            sb_shape.rect = (*rect).into();
+        }
+        Some(Shape::Polygon(polygon)) => {
+            points.clear();
+            points.extend(polygon.iter().map(|point| SBVector2 {
+                x: point.x,
+                y: point.y,
+            }));
+            sb_shape.type_ = SBClipShapeTypePolygon;
+            sb_shape.points = points.as_ptr();
+            sb_shape.point_count = points.len().try_into().unwrap();
+        }
+        None => (),
+    }
+    sb_shape
+}
+
+fn gradient_to_sb(gradient: Option<&Gradient>, stops: &mut Vec<SBGradientStop>) -> SBGradient {
+    let gradient = match gradient {
+        Some(gradient) => gradient,
+        None => {
+            return SBGradient {
+                start: SBVector2 { x: 0., y: 0. },
+                end: SBVector2 { x: 0., y: 0. },
+                stops: core::ptr::null(),
+                stop_count: 0,
+            }
+        }
+    };
+    stops.clear();
+    stops.extend(
+        gradient
+            .stops
+            .iter()
+            .map(|(location, color)| SBGradientStop {
+                location: *location,
+                color: (*color).into(),
+            }),
+    );
+    SBGradient {
+        start: SBVector2 {
+            x: gradient.start.x,
+            y: gradient.start.y,
+        },
+        end: SBVector2 {
+            x: gradient.end.x,
+            y: gradient.end.y,
         },
+        stops: stops.as_ptr(),
+        stop_count: stops.len().try_into().unwrap(),
+    }
+}
+
+/// Errors from the SwiftBirb backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SBError {
+    /// SwiftBirb threw an Objective-C exception; contains its reason.
+    Exception(String),
+    /// The view was removed, or its hosting view no longer exists.
+    ViewDeallocated,
+    /// The SwiftBirb framework was built with a different protocol version than this crate
+    /// (zero if it predates protocol versions).
+    ProtocolMismatch { expected: u64, found: u64 },
+    /// A [system service](SystemServices) failed; contains a description.
+    System(String),
+    /// The parent window handle isn’t available or isn’t an AppKit handle.
+    UnsupportedWindowHandle,
+}
+
+impl fmt::Display for SBError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SBError::Exception(reason) => write!(f, "Objective-C exception: {}", reason),
+            SBError::ViewDeallocated => write!(f, "view was removed or deallocated"),
+            SBError::ProtocolMismatch { expected, found } => write!(
+                f,
+                "SwiftBirb framework uses protocol version {} but swift-birb expects {}; \
+                 rebuild both from the same revision",
+                found, expected
+            ),
+            SBError::System(message) => write!(f, "system service failed: {}", message),
+            SBError::UnsupportedWindowHandle => write!(f, "unsupported parent window handle"),
+        }
     }
 }
 
-pub enum SBError {}
+impl std::error::Error for SBError {}
 
-/// SwiftBirb backend. Must only be used on the main thread.
+/// SwiftBirb backend.
+///
+/// Must only be used on the main thread; creating one requires a [`MainThreadMarker`], and it
+/// can’t be sent to other threads. Debug builds also check this on every backend call.
 pub struct SwiftBirb {
     host: Host,
 }
 
 impl SwiftBirb {
-    pub fn new() -> SwiftBirb {
-        SwiftBirb { host: Host::new() }
+    pub fn new(mtm: MainThreadMarker) -> Result<SwiftBirb, SBError> {
+        Ok(SwiftBirb {
+            host: Host::new(mtm)?,
+        })
+    }
+
+    /// Creates a backend whose hosting view fills a view that belongs to someone else, such as
+    /// the content view of a winit or game engine window.
+    ///
+    /// Only AppKit handles are supported. The hosting view is resized along with the parent view.
+    #[cfg(feature = "raw-window-handle")]
+    pub fn with_parent(
+        mtm: MainThreadMarker,
+        parent: &impl HasWindowHandle,
+    ) -> Result<SwiftBirb, SBError> {
+        let ns_view = match parent.window_handle().map(|handle| handle.as_raw()) {
+            Ok(RawWindowHandle::AppKit(handle)) => handle.ns_view.as_ptr() as *mut Object,
+            _ => return Err(SBError::UnsupportedWindowHandle),
+        };
+        let mut backend = SwiftBirb::new(mtm)?;
+        backend.host.attach_to(ns_view)?;
+        Ok(backend)
+    }
+
+    /// Returns a marker for the main thread, which this backend lives on.
+    pub fn main_thread_marker(&self) -> MainThreadMarker {
+        self.host.1
+    }
+
+    /// Returns system services for opening URLs, files, and apps.
+    pub fn system_services(&self) -> SystemServices {
+        SystemServices::new(self.host.1)
+    }
+
+    /// Returns the app’s Dock icon.
+    pub fn dock(&self) -> Dock {
+        Dock::new(self.host.1)
+    }
+
+    /// Saves and restores the frame and full-screen state of the hosting view’s window under an
+    /// autosave name (using Cocoa’s frame autosave names), or stops doing so.
+    ///
+    /// The saved state is applied right away, or once the hosting view is added to a window.
+    /// Returns false if another window already uses the name. Split view divider positions are
+    /// not saved; see [`birb::session`].
+    pub fn set_window_autosave_name(&mut self, name: Option<&str>) -> Result<bool, SBError> {
+        self.host.set_window_autosave_name(name)
+    }
+}
+
+/// The hosting view, e.g. for creating a graphics surface in it.
+#[cfg(feature = "raw-window-handle")]
+impl HasWindowHandle for SwiftBirb {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        let ns_view = NonNull::from(&*self.host.0).cast();
+        let handle = RawWindowHandle::AppKit(AppKitWindowHandle::new(ns_view));
+        // the hosting view lives as long as the backend
+        Ok(unsafe { WindowHandle::borrow_raw(handle) })
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl HasDisplayHandle for SwiftBirb {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        Ok(DisplayHandle::appkit())
     }
 }
 
@@ -201,22 +939,22 @@ impl Backend for SwiftBirb {
     type Error = SBError;
 
     fn new_view(&mut self, view: NativeView) -> Result<SBViewRef, SBError> {
-        self.host.new_view(nv_to_patch(view))
+        let mut buffers = PatchBuffers::default();
+        self.host.new_view(nv_to_patch(view, &mut buffers))
     }
 
     fn update_view(&mut self, view: &mut SBViewRef, patch: NativeView) -> Result<(), SBError> {
-        view.update(nv_to_patch(patch));
-        Ok(())
+        let mut buffers = PatchBuffers::default();
+        view.update(nv_to_patch(patch, &mut buffers))
     }
 
     fn remove_view(&mut self, mut view: SBViewRef) -> Result<(), SBError> {
-        view.remove();
-        Ok(())
+        view.remove()
     }
 
     fn replace_view(&mut self, view: &mut SBViewRef, patch: NativeView) -> Result<(), SBError> {
-        view.replace(nv_to_patch(patch));
-        Ok(())
+        let mut buffers = PatchBuffers::default();
+        view.replace(nv_to_patch(patch, &mut buffers))
     }
 
     fn set_subviews<'a>(
@@ -229,33 +967,81 @@ impl Backend for SwiftBirb {
         let region_start = region_start.try_into().unwrap();
         let region_len = region_len.try_into().unwrap();
 
-        let subviews_count = subviews.len().try_into().unwrap();
-        // Safety: SBViewRef is memory-compatible with objc id...
-        const _: [(); mem::size_of::<Id<Object>>()] = [(); mem::size_of::<SBViewRef>()];
-        // ...hence this is a valid pointer to a list of ids.
-        // Vec capacity does not matter in this case because the pointer can be deallocated without
-        // knowing its size.
-        let subviews_ptr =
-            unsafe { mem::transmute::<*const &SBViewRef, *mut c_void>(subviews.as_ptr()) };
-
-        let node_list = SBNodeList {
-            nodes: subviews_ptr,
-            count: subviews_count,
-        };
+        let ids: Vec<*mut Object> = subviews.iter().map(|view| view.as_ptr()).collect();
+        let result = view.set_subviews(region_start, region_len, node_list(&ids));
+        drop(ids);
+        result
+    }
 
-        // this vec was converted into raw parts; must not drop it
-        mem::forget(subviews);
+    fn set_root_view(&mut self, view: &mut SBViewRef) -> Result<(), SBError> {
+        self.host.set_root_view(view)
+    }
 
-        view.set_subviews(region_start, region_len, node_list);
-        Ok(())
+    fn render_to_image(
+        &mut self,
+        view: &SBViewRef,
+        (width, height): (u32, u32),
+    ) -> Result<Option<RgbaImage>, SBError> {
+        self.host.render_to_image(view, width, height)
     }
 
-    fn set_root_view(&mut self, view: &mut SBViewRef) -> Result<(), SBError> {
-        self.host.set_root_view(view);
-        Ok(())
+    fn capture(&mut self) -> Result<Option<RgbaImage>, SBError> {
+        self.host.capture()
+    }
+
+    fn export_pdf(
+        &mut self,
+        view: &SBViewRef,
+        setup: &PageSetup,
+        pages: &[Rect],
+    ) -> Result<Option<Vec<u8>>, SBError> {
+        self.host.export_pdf(view, setup, pages)
+    }
+
+    fn announce(&mut self, announcement: &Announcement) -> Result<(), SBError> {
+        self.host.announce(announcement)
+    }
+
+    fn set_drop_accepted(&mut self, accepted: bool) -> Result<(), SBError> {
+        self.host.set_drop_accepted(accepted)
     }
 
     fn poll(&mut self) -> Result<Option<RawEvent>, SBError> {
-        todo!()
+        self.host.poll_event()
+    }
+}
+
+/// Creates a node list that points to the given SBNode objects.
+///
+/// SwiftBirb reads the list as an array of object pointers and copies it before returning, so it
+/// only has to live until the call is done.
+fn node_list(ids: &[*mut Object]) -> SBNodeList {
+    const _: [(); mem::size_of::<*mut c_void>()] = [(); mem::size_of::<*mut Object>()];
+    const _: [(); mem::align_of::<*mut c_void>()] = [(); mem::align_of::<*mut Object>()];
+    SBNodeList {
+        nodes: ids.as_ptr() as *mut c_void,
+        count: ids.len().try_into().unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_list_points_to_views() {
+        // the objects are never dereferenced
+        let ids: Vec<*mut Object> = (1..=3).map(|i| (i * 0x10) as *mut Object).collect();
+        let list = node_list(&ids);
+        assert_eq!(list.count, 3);
+        assert_eq!(list.nodes as *const *mut Object, ids.as_ptr());
+        let nodes = unsafe { slice::from_raw_parts(list.nodes as *const *mut Object, 3) };
+        assert_eq!(nodes, &ids[..]);
+    }
+
+    #[test]
+    fn empty_node_list() {
+        let list = node_list(&[]);
+        assert_eq!(list.count, 0);
     }
 }
@@ -0,0 +1,238 @@
+use birb::color::Color;
+use birb::dispatch::EventContext;
+use birb::events::{Event, EventHandler, Key, KeyCode, Tap};
+use birb::focus::Focus;
+use birb::notifications::ActionFn;
+use birb::raw_events::KeyEventPhase;
+use birb::{impl_view, AnyView, CornerRadius, Layer, LayerProps, Rect};
+use core::fmt;
+use std::sync::Arc;
+
+/// A push button.
+///
+/// Calls the action when tapped, or when Space or Return is pressed while it has keyboard focus.
+/// Disabled buttons are dimmed and can’t be focused.
+///
+/// ```
+/// use birb::Rect;
+/// use birb::focus::Focus;
+/// use birb::View;
+/// use birb_widgets::Button;
+/// use cgmath::{Point2, Vector2};
+/// use std::sync::Arc;
+///
+/// let button: Button = Button {
+///     bounds: Rect::new(Point2::new(0., 0.), Vector2::new(80., 24.)),
+///     action: Arc::new(|| println!("clicked")),
+///     ..Button::default()
+/// };
+/// assert!(matches!(button.focus(), Some(Focus::Focusable)));
+/// ```
+pub struct Button<Ctx = ()> {
+    pub key: Option<u64>,
+
+    /// Button bounds.
+    pub bounds: Rect,
+
+    /// The button label (e.g. a [`Label`](crate::Label)).
+    pub label: AnyView<Ctx>,
+
+    /// Background color.
+    pub background: Color,
+
+    /// Corner radius.
+    pub corner_radius: CornerRadius,
+
+    /// Whether the button can be used.
+    pub is_enabled: bool,
+
+    /// Called when the button is pressed.
+    pub action: Arc<ActionFn>,
+}
+
+impl<Ctx: 'static> Default for Button<Ctx> {
+    fn default() -> Self {
+        Button {
+            key: None,
+            bounds: Rect::zero(),
+            label: Arc::new(()),
+            background: Color::rgb(0.9, 0.9, 0.9),
+            corner_radius: CornerRadius::uniform(4.),
+            is_enabled: true,
+            action: Arc::new(|| ()),
+        }
+    }
+}
+
+impl<Ctx> fmt::Debug for Button<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Button")
+            .field("bounds", &self.bounds)
+            .field("label", &self.label)
+            .field("background", &self.background)
+            .field("corner_radius", &self.corner_radius)
+            .field("is_enabled", &self.is_enabled)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for Button<Ctx> {
+    fn eq(&self, other: &Button<Ctx>) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && self.label.eq(&*other.label)
+            && self.background == other.background
+            && self.corner_radius == other.corner_radius
+            && self.is_enabled == other.is_enabled
+            && Arc::ptr_eq(&self.action, &other.action)
+    }
+}
+
+impl_view! {
+    Button<Ctx>;
+    fn body(&self, _state: &()) {
+        let (tap_action, key_action) = if self.is_enabled {
+            let action = Arc::clone(&self.action);
+            let tap_action = EventHandler::new(move |_: Event<Tap>, context: &mut EventContext| {
+                action();
+                context.set_handled();
+            });
+            let action = Arc::clone(&self.action);
+            let key_action = EventHandler::new(move |event: Event<Key>, context: &mut EventContext| {
+                match event.code() {
+                    KeyCode::Space | KeyCode::Return => (),
+                    _ => return,
+                }
+                if event.phase() == KeyEventPhase::Pressed {
+                    action();
+                }
+                context.set_handled();
+            });
+            (Some(tap_action), Some(key_action))
+        } else {
+            (None, None)
+        };
+
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                background: self.background,
+                corner_radius: self.corner_radius,
+                opacity: if self.is_enabled { 1. } else { 0.5 },
+                ..LayerProps::default()
+            },
+            subviews: vec![Arc::clone(&self.label)],
+            tap_action,
+            key_action,
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn focus(&self) -> Option<Focus> {
+        if self.is_enabled {
+            Some(Focus::Focusable)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Window;
+    use birb::events::KeyModifiers;
+    use birb::View;
+    use cgmath::{Point2, Vector2};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns a button at (10, 10) with a 20×10 label layer, and its press count.
+    fn button(is_enabled: bool) -> (Arc<Button>, Arc<AtomicUsize>) {
+        let presses = Arc::new(AtomicUsize::new(0));
+        let action = {
+            let presses = Arc::clone(&presses);
+            Arc::new(move || {
+                presses.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        let label = Arc::new(Layer {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(5., 5.), Vector2::new(20., 10.)),
+                ..LayerProps::default()
+            },
+            ..Layer::default()
+        });
+        let button = Button {
+            bounds: Rect::new(Point2::new(10., 10.), Vector2::new(80., 24.)),
+            label,
+            is_enabled,
+            action,
+            ..Button::default()
+        };
+        (Arc::new(button), presses)
+    }
+
+    #[test]
+    fn buttons_render_their_label() {
+        let mut window = Window::new();
+        let (enabled, _) = button(true);
+        let layers = window.render(enabled);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].bounds.size, Vector2::new(80., 24.));
+        assert_eq!(layers[0].background, Color::rgb(0.9, 0.9, 0.9));
+        assert_eq!(layers[0].corner_radius, CornerRadius::uniform(4.));
+        assert_eq!(layers[0].opacity, 1.);
+        assert_eq!(layers[1].bounds.size, Vector2::new(20., 10.));
+
+        let (disabled, _) = button(false);
+        assert!(View::<()>::focus(&*disabled).is_none());
+        let layers = window.render(disabled);
+        assert_eq!(layers[0].opacity, 0.5);
+    }
+
+    #[test]
+    fn buttons_are_pressed_by_taps() {
+        let mut window = Window::new();
+        let (button, presses) = button(true);
+        window.render(button);
+
+        // on the label, which bubbles up to the button
+        assert!(window.tap(20., 20.));
+        assert!(window.tap(80., 30.));
+        assert!(!window.tap(100., 100.));
+        assert_eq!(presses.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn buttons_are_pressed_by_space_and_return() {
+        let mut window = Window::new();
+        let (button, presses) = button(true);
+        window.render(button);
+        window.focus(80., 30.);
+
+        assert!(window.press(KeyCode::Space, " "));
+        assert!(window.key(
+            KeyEventPhase::Released,
+            KeyCode::Space,
+            " ",
+            KeyModifiers::NONE
+        ));
+        assert!(window.press(KeyCode::Return, "\r"));
+        assert!(!window.press(KeyCode::A, "a"));
+        assert_eq!(presses.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn disabled_buttons_ignore_events() {
+        let mut window = Window::new();
+        let (button, presses) = button(false);
+        window.render(button);
+        window.focus(80., 30.);
+
+        assert!(!window.tap(80., 30.));
+        assert!(!window.press(KeyCode::Space, " "));
+        assert_eq!(presses.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,191 @@
+use birb::color::Color;
+use birb::{impl_view, CornerRadius, Fragment, Layer, LayerProps, Rect, View};
+use cgmath::{Point2, Vector2};
+use core::fmt;
+use std::sync::Arc;
+
+/// A rounded, bordered container.
+///
+/// The content is placed in a content area inset by the padding, and clipped to the card.
+///
+/// ```
+/// use birb::{AnyState, AnyView, Layer, Rect, View};
+/// use birb_widgets::Card;
+/// use cgmath::{Point2, Vector2};
+/// use std::sync::Arc;
+///
+/// let card: Card = Card {
+///     bounds: Rect::new(Point2::new(0., 0.), Vector2::new(100., 60.)),
+///     padding: 10.,
+///     content: vec![Arc::new(())],
+///     ..Card::default()
+/// };
+/// let body = card.body(&(Arc::new(()) as AnyState));
+/// let layer = body.as_any().downcast_ref::<Layer>().unwrap();
+/// let content = layer.subviews[0].as_any().downcast_ref::<Layer>().unwrap();
+/// assert_eq!(
+///     content.props.bounds,
+///     Rect::new(Point2::new(10., 10.), Vector2::new(80., 40.))
+/// );
+/// ```
+pub struct Card<Ctx = ()> {
+    pub key: Option<u64>,
+
+    /// Card bounds.
+    pub bounds: Rect,
+
+    /// Background color.
+    pub background: Color,
+
+    /// Corner radius.
+    pub corner_radius: CornerRadius,
+
+    /// Border (width, color).
+    pub border: Option<(f64, Color)>,
+
+    /// Distance between the card edges and the content area.
+    pub padding: f64,
+
+    /// Card contents, positioned in the content area.
+    pub content: Fragment<Ctx>,
+}
+
+impl<Ctx> Default for Card<Ctx> {
+    fn default() -> Self {
+        Card {
+            key: None,
+            bounds: Rect::zero(),
+            background: Color::WHITE,
+            corner_radius: CornerRadius::uniform(8.),
+            border: Some((1., Color::BLACK.with_alpha(0.1))),
+            padding: 12.,
+            content: Vec::new(),
+        }
+    }
+}
+
+impl<Ctx> fmt::Debug for Card<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Card")
+            .field("bounds", &self.bounds)
+            .field("background", &self.background)
+            .field("corner_radius", &self.corner_radius)
+            .field("border", &self.border)
+            .field("padding", &self.padding)
+            .field("content", &self.content)
+            .finish()
+    }
+}
+
+impl<Ctx: 'static> PartialEq for Card<Ctx> {
+    fn eq(&self, other: &Card<Ctx>) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && self.background == other.background
+            && self.corner_radius == other.corner_radius
+            && self.border == other.border
+            && self.padding == other.padding
+            && self.content.eq(&other.content)
+    }
+}
+
+impl_view! {
+    Card<Ctx>;
+    fn body(&self, _state: &()) {
+        let content_size = Vector2::new(
+            (self.bounds.size.x - 2. * self.padding).max(0.),
+            (self.bounds.size.y - 2. * self.padding).max(0.),
+        );
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                background: self.background,
+                corner_radius: self.corner_radius,
+                border: self.border,
+                clip_contents: true,
+                ..LayerProps::default()
+            },
+            subviews: vec![Arc::new(Layer {
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(self.padding, self.padding), content_size),
+                    ..LayerProps::default()
+                },
+                subviews: self.content.clone(),
+                ..Layer::default()
+            })],
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Window;
+    use birb::AnyView;
+
+    fn content(width: f64) -> AnyView {
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(0., 0.), Vector2::new(width, 10.)),
+                ..LayerProps::default()
+            },
+            ..Layer::default()
+        })
+    }
+
+    #[test]
+    fn cards_inset_and_clip_their_content() {
+        let mut window = Window::new();
+        let layers = window.render(Arc::new(Card {
+            bounds: Rect::new(Point2::new(10., 20.), Vector2::new(100., 60.)),
+            background: Color::BLACK,
+            padding: 8.,
+            content: vec![content(30.), content(40.)],
+            ..Card::default()
+        }));
+        assert_eq!(layers.len(), 4);
+
+        let card = &layers[0];
+        assert_eq!(
+            card.bounds,
+            Rect::new(Point2::new(10., 20.), Vector2::new(100., 60.))
+        );
+        assert_eq!(card.background, Color::BLACK);
+        assert_eq!(card.corner_radius, CornerRadius::uniform(8.));
+        assert_eq!(card.border, Some((1., Color::BLACK.with_alpha(0.1))));
+        assert!(card.clip_contents);
+
+        let content_area = &layers[1];
+        assert_eq!(
+            content_area.bounds,
+            Rect::new(Point2::new(8., 8.), Vector2::new(84., 44.))
+        );
+        assert_eq!(layers[2].bounds.size.x, 30.);
+        assert_eq!(layers[3].bounds.size.x, 40.);
+    }
+
+    #[test]
+    fn padding_never_makes_content_negative() {
+        let mut window = Window::new();
+        let layers = window.render(Arc::new(Card {
+            bounds: Rect::new(Point2::new(0., 0.), Vector2::new(20., 40.)),
+            padding: 12.,
+            ..Card::default()
+        }));
+        assert_eq!(layers[1].bounds.size, Vector2::new(0., 16.));
+
+        // changing only the card doesn’t update the content area
+        let layers = window.render(Arc::new(Card {
+            bounds: Rect::new(Point2::new(0., 0.), Vector2::new(20., 40.)),
+            padding: 12.,
+            border: None,
+            ..Card::default()
+        }));
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].border, None);
+    }
+}
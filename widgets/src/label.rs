@@ -0,0 +1,117 @@
+use crate::TextRenderFn;
+use birb::{impl_view, Layer, LayerProps, Rect};
+use core::fmt;
+use std::sync::Arc;
+
+/// A view that displays a string.
+///
+/// ```
+/// use birb::{AnyState, AnyView, Rect, View};
+/// use birb_widgets::Label;
+/// use std::sync::Arc;
+///
+/// let label: Label = Label {
+///     text: "Hello".into(),
+///     render: Arc::new(|text| {
+///         assert_eq!(text, "Hello");
+///         Arc::new(()) as AnyView
+///     }),
+///     ..Label::default()
+/// };
+/// label.body(&(Arc::new(()) as AnyState));
+/// ```
+pub struct Label<Ctx = ()> {
+    pub key: Option<u64>,
+
+    /// Label bounds.
+    pub bounds: Rect,
+
+    /// The text.
+    pub text: String,
+
+    /// Renders the text.
+    pub render: Arc<TextRenderFn<Ctx>>,
+}
+
+impl<Ctx: 'static> Default for Label<Ctx> {
+    fn default() -> Self {
+        Label {
+            key: None,
+            bounds: Rect::zero(),
+            text: String::new(),
+            render: Arc::new(|_| Arc::new(())),
+        }
+    }
+}
+
+impl<Ctx> fmt::Debug for Label<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Label")
+            .field("bounds", &self.bounds)
+            .field("text", &self.text)
+            .finish()
+    }
+}
+
+impl<Ctx> PartialEq for Label<Ctx> {
+    fn eq(&self, other: &Label<Ctx>) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && self.text == other.text
+            && Arc::ptr_eq(&self.render, &other.render)
+    }
+}
+
+impl_view! {
+    Label<Ctx>;
+    fn body(&self, _state: &()) {
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                ..LayerProps::default()
+            },
+            subviews: vec![(self.render)(&self.text)],
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{text_layer, Window};
+    use cgmath::{Point2, Vector2};
+    use parking_lot::Mutex;
+
+    #[test]
+    fn labels_render_their_text() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let render = text_layer(&log);
+        let label = |text: &str| {
+            Arc::new(Label {
+                bounds: Rect::new(Point2::new(5., 5.), Vector2::new(50., 10.)),
+                text: text.into(),
+                render: Arc::clone(&render),
+                ..Label::default()
+            })
+        };
+
+        let mut window = Window::new();
+        let layers = window.render(label("Hello"));
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].bounds, label("").bounds);
+        assert_eq!(layers[1].bounds.size.x, 5.);
+        assert_eq!(*log.lock(), ["Hello"]);
+
+        let layers = window.render(label("Hi"));
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[1].bounds.size.x, 2.);
+        assert_eq!(*log.lock(), ["Hello", "Hi"]);
+
+        // labels are plain text, so taps go elsewhere
+        assert!(!window.tap(6., 6.));
+    }
+}
@@ -0,0 +1,43 @@
+//! Common composite views, built only on birb’s public API.
+//!
+//! All views here are plain structs with public properties and a `Default` implementation, so
+//! they can be created like layers:
+//!
+//! ```
+//! use birb::color::Color;
+//! use birb::Rect;
+//! use birb_widgets::Card;
+//! use cgmath::{Point2, Vector2};
+//!
+//! let card: Card = Card {
+//!     bounds: Rect::new(Point2::new(16., 16.), Vector2::new(200., 120.)),
+//!     background: Color::WHITE,
+//!     ..Card::default()
+//! };
+//! ```
+//!
+//! Birb doesn’t have text views yet, so views that display text take a [`TextRenderFn`] that
+//! renders a string as a view.
+//!
+//! Stateful controls (like [`Toggle`] and [`TextInput`]) keep their value in a cheap shared
+//! handle, like [`TabSelection`](birb::tabs::TabSelection), and are rendered again when it
+//! changes.
+
+mod button;
+mod card;
+mod label;
+#[cfg(test)]
+mod testing;
+mod text_input;
+mod toggle;
+
+pub use button::Button;
+pub use card::Card;
+pub use label::Label;
+pub use text_input::{TextInput, TextValue};
+pub use toggle::{Toggle, ToggleValue};
+
+use birb::AnyView;
+
+/// Renders a string as a view (e.g. as a text view).
+pub type TextRenderFn<Ctx> = dyn Fn(&str) -> AnyView<Ctx> + Send + Sync;
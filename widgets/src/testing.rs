@@ -0,0 +1,185 @@
+//! A window without a backend for testing widgets, rendered and dispatched to through birb’s
+//! public API.
+
+use crate::TextRenderFn;
+use birb::backend::Backend;
+use birb::dispatch::EventDispatcher;
+use birb::events::{KeyCode, KeyModifiers, PointerDevice};
+use birb::raw_events::{EventId, KeyEventPhase, PointerEventPhase, RawEvent};
+use birb::{AnyView, Layer, LayerProps, NVTree, NativeView, Patch, Rect, ViewId, ViewTree};
+use cgmath::{Point2, Vector2};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Renders text as a layer that is as wide as the number of characters, and logs the text.
+pub fn text_layer(log: &Arc<Mutex<Vec<String>>>) -> Arc<TextRenderFn<()>> {
+    let log = Arc::clone(log);
+    Arc::new(move |text| {
+        log.lock().push(text.to_string());
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: Rect::new(
+                    Point2::new(0., 0.),
+                    Vector2::new(text.chars().count() as f64, 10.),
+                ),
+                ..LayerProps::default()
+            },
+            ..Layer::default()
+        })
+    })
+}
+
+/// A backend without views.
+#[derive(Debug)]
+pub struct NullBackend;
+
+impl Backend for NullBackend {
+    type ViewRef = ();
+    type Error = ();
+
+    fn new_view(&mut self, _view: NativeView) -> Result<(), ()> {
+        Ok(())
+    }
+    fn remove_view(&mut self, _view: ()) -> Result<(), ()> {
+        Ok(())
+    }
+    fn update_view(&mut self, _: &mut (), _view: NativeView) -> Result<(), ()> {
+        Ok(())
+    }
+    fn replace_view(&mut self, _: &mut (), _view: NativeView) -> Result<(), ()> {
+        Ok(())
+    }
+    fn set_subviews(&mut self, _: &mut (), _: usize, _: usize, _: Vec<&()>) -> Result<(), ()> {
+        Ok(())
+    }
+    fn set_root_view(&mut self, _: &mut ()) -> Result<(), ()> {
+        Ok(())
+    }
+    fn poll(&mut self) -> Result<Option<RawEvent>, ()> {
+        Ok(None)
+    }
+}
+
+/// A view tree rendered into an NV tree, with a dispatcher and a clock.
+///
+/// Views are rendered in a 200×200 root layer, since the root view must be a native view.
+pub struct Window {
+    root: Option<ViewId>,
+    pub views: ViewTree,
+    pub native_views: NVTree<Box<NullBackend>, ()>,
+    pub dispatcher: EventDispatcher,
+    now: Instant,
+    next_event: EventId,
+}
+
+impl Window {
+    pub fn new() -> Window {
+        Window {
+            root: None,
+            views: ViewTree::new(),
+            native_views: NVTree::new(Box::new(NullBackend)),
+            dispatcher: EventDispatcher::new(),
+            now: Instant::now(),
+            next_event: 0,
+        }
+    }
+
+    /// Renders a view in the root layer and returns the updated layers other than the root, in
+    /// patch order.
+    pub fn render(&mut self, view: AnyView) -> Vec<LayerProps> {
+        let root = Layer {
+            props: LayerProps {
+                bounds: Rect::new(Point2::new(0., 0.), Vector2::new(200., 200.)),
+                ..LayerProps::default()
+            },
+            subviews: vec![view],
+            ..Layer::default()
+        };
+        self.views.render_root(Arc::new(root), ());
+        self.flush()
+    }
+
+    /// Renders views that requested it again and returns the updated layers.
+    pub fn tick(&mut self) -> Vec<LayerProps> {
+        self.now += Duration::from_millis(16);
+        self.views.tick(self.now);
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Vec<LayerProps> {
+        let patches: Vec<_> = self.views.patches().collect();
+        for patch in &patches {
+            if let Patch::SetRoot(id) = patch {
+                self.root = Some(*id);
+            }
+        }
+        let layers = patches
+            .iter()
+            .filter_map(|patch| match patch {
+                Patch::Update(id, NativeView::Layer(props)) if Some(*id) != self.root => {
+                    Some((**props).clone())
+                }
+                _ => None,
+            })
+            .collect();
+        self.native_views.patch_frame(patches).unwrap();
+        self.dispatcher.update(&self.views);
+        layers
+    }
+
+    fn dispatch(&mut self, event: RawEvent) -> bool {
+        self.dispatcher
+            .dispatch(&event, self.now, &self.views, &mut self.native_views)
+    }
+
+    /// Taps at a window location with the mouse. Returns true if a handler handled the end of
+    /// the tap.
+    pub fn tap(&mut self, x: f64, y: f64) -> bool {
+        let event_id = self.next_event;
+        self.next_event += 1;
+        let pointer = |phase| RawEvent::Pointer {
+            device: PointerDevice::Cursor,
+            root_location: (x, y),
+            pressure: 1.,
+            tilt: (0., 0., 1.),
+            event_id,
+            unique_id: 1,
+            phase,
+            modifiers: KeyModifiers::NONE,
+            click_count: 0,
+        };
+        self.dispatch(pointer(PointerEventPhase::Began));
+        self.now += Duration::from_millis(50);
+        self.dispatch(pointer(PointerEventPhase::Ended))
+    }
+
+    /// Sends key events to the view at a window location.
+    pub fn focus(&mut self, x: f64, y: f64) {
+        let view = self.native_views.hit_test(Point2::new(x, y));
+        assert!(view.is_some(), "no view at ({}, {})", x, y);
+        self.dispatcher.set_key_view(view);
+    }
+
+    /// Dispatches a key event to the key view. Returns true if it was handled.
+    pub fn key(
+        &mut self,
+        phase: KeyEventPhase,
+        key_code: KeyCode,
+        chars: &str,
+        modifiers: KeyModifiers,
+    ) -> bool {
+        self.dispatch(RawEvent::Key {
+            chars: chars.into(),
+            chars_without_mod: chars.into(),
+            key_code,
+            phase,
+            modifiers,
+        })
+    }
+
+    /// Presses a key without modifiers. Returns true if it was handled.
+    pub fn press(&mut self, key_code: KeyCode, chars: &str) -> bool {
+        self.key(KeyEventPhase::Pressed, key_code, chars, KeyModifiers::NONE)
+    }
+}
@@ -0,0 +1,365 @@
+use crate::TextRenderFn;
+use birb::color::Color;
+use birb::dispatch::EventContext;
+use birb::events::{Event, EventHandler, Key, KeyCode};
+use birb::focus::Focus;
+use birb::raw_events::KeyEventPhase;
+use birb::{impl_view, AnyState, Context, CornerRadius, Layer, LayerProps, Observers, Rect};
+use cgmath::{Point2, Vector2};
+use core::fmt;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The text of a [`TextInput`].
+///
+/// Clones share the same text, so one can be kept to read it back; the text input is rendered
+/// again when it changes.
+#[derive(Clone)]
+pub struct TextValue {
+    text: Arc<Mutex<String>>,
+    observers: Observers,
+}
+
+impl TextValue {
+    pub fn new(text: impl Into<String>) -> TextValue {
+        TextValue {
+            text: Arc::new(Mutex::new(text.into())),
+            observers: Observers::new(),
+        }
+    }
+
+    /// The current text.
+    pub fn text(&self) -> String {
+        self.text.lock().clone()
+    }
+
+    /// Replaces the text.
+    pub fn set_text(&self, text: impl Into<String>) {
+        let text = text.into();
+        {
+            let mut current = self.text.lock();
+            if *current == text {
+                return;
+            }
+            *current = text;
+        }
+        self.observers.notify();
+    }
+
+    /// Renders the view of a context again whenever the text changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+
+    /// Applies a key event to the text.
+    ///
+    /// Typed characters are appended and Delete removes the last character. Keys pressed with
+    /// Command or Control are left alone so they can be used as shortcuts.
+    ///
+    /// Returns true if the key was handled.
+    pub fn handle_key(&self, key: &Key) -> bool {
+        let modifiers = key.modifiers();
+        if key.phase() == KeyEventPhase::Released || modifiers.command() || modifiers.control() {
+            return false;
+        }
+        let changed = {
+            let mut text = self.text.lock();
+            match key.code() {
+                KeyCode::Delete => text.pop().is_some(),
+                KeyCode::Return | KeyCode::Tab | KeyCode::Escape => return false,
+                _ => {
+                    let chars: String = key.chars().chars().filter(|c| !c.is_control()).collect();
+                    if chars.is_empty() {
+                        return false;
+                    }
+                    text.push_str(&chars);
+                    true
+                }
+            }
+        };
+        if changed {
+            self.observers.notify();
+        }
+        true
+    }
+}
+
+impl fmt::Debug for TextValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TextValue").field(&self.text()).finish()
+    }
+}
+
+/// A single-line text field composed of a layer and a text view.
+///
+/// Typing while it has keyboard focus edits the text (see [`TextValue::handle_key`]); the caret is
+/// always at the end of the text. While the text is empty, the placeholder is shown dimmed.
+///
+/// ```
+/// use birb::{AnyState, AnyView, Rect, View};
+/// use birb_widgets::{TextInput, TextValue};
+/// use std::sync::{Arc, Mutex};
+///
+/// let rendered = Arc::new(Mutex::new(Vec::new()));
+/// let value = TextValue::new("");
+/// let input: TextInput = TextInput {
+///     value: value.clone(),
+///     placeholder: "Name".into(),
+///     render: {
+///         let rendered = Arc::clone(&rendered);
+///         Arc::new(move |text| {
+///             rendered.lock().unwrap().push(text.to_string());
+///             Arc::new(()) as AnyView
+///         })
+///     },
+///     ..TextInput::default()
+/// };
+///
+/// let state: AnyState = Arc::new(());
+/// input.body(&state);
+/// value.set_text("Birb");
+/// input.body(&state);
+/// assert_eq!(*rendered.lock().unwrap(), ["Name", "Birb"]);
+/// ```
+pub struct TextInput<Ctx = ()> {
+    pub key: Option<u64>,
+
+    /// Text field bounds.
+    pub bounds: Rect,
+
+    /// The text.
+    pub value: TextValue,
+
+    /// Shown while the text is empty.
+    pub placeholder: String,
+
+    /// Renders the text and the placeholder.
+    pub render: Arc<TextRenderFn<Ctx>>,
+
+    /// Background color.
+    pub background: Color,
+
+    /// Border (width, color).
+    pub border: Option<(f64, Color)>,
+
+    /// Corner radius.
+    pub corner_radius: CornerRadius,
+
+    /// Distance between the edges and the text.
+    pub padding: f64,
+
+    /// Whether the text can be edited.
+    pub is_enabled: bool,
+}
+
+/// Opacity of the placeholder text.
+const PLACEHOLDER_OPACITY: f64 = 0.5;
+
+impl<Ctx: 'static> Default for TextInput<Ctx> {
+    fn default() -> Self {
+        TextInput {
+            key: None,
+            bounds: Rect::zero(),
+            value: TextValue::new(""),
+            placeholder: String::new(),
+            render: Arc::new(|_| Arc::new(())),
+            background: Color::WHITE,
+            border: Some((1., Color::BLACK.with_alpha(0.2))),
+            corner_radius: CornerRadius::uniform(4.),
+            padding: 4.,
+            is_enabled: true,
+        }
+    }
+}
+
+impl<Ctx> fmt::Debug for TextInput<Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TextInput")
+            .field("bounds", &self.bounds)
+            .field("value", &self.value)
+            .field("placeholder", &self.placeholder)
+            .field("background", &self.background)
+            .field("border", &self.border)
+            .field("corner_radius", &self.corner_radius)
+            .field("padding", &self.padding)
+            .field("is_enabled", &self.is_enabled)
+            .finish()
+    }
+}
+
+impl<Ctx> PartialEq for TextInput<Ctx> {
+    fn eq(&self, other: &TextInput<Ctx>) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && Arc::ptr_eq(&self.value.text, &other.value.text)
+            && self.placeholder == other.placeholder
+            && Arc::ptr_eq(&self.render, &other.render)
+            && self.background == other.background
+            && self.border == other.border
+            && self.corner_radius == other.corner_radius
+            && self.padding == other.padding
+            && self.is_enabled == other.is_enabled
+    }
+}
+
+impl_view! {
+    TextInput<Ctx>;
+    fn body(&self, _state: &()) {
+        let text = self.value.text();
+        let (text, opacity) = if text.is_empty() {
+            (self.placeholder.clone(), PLACEHOLDER_OPACITY)
+        } else {
+            (text, 1.)
+        };
+
+        let key_action = if self.is_enabled {
+            let value = self.value.clone();
+            Some(EventHandler::new(move |event: Event<Key>, context: &mut EventContext| {
+                if value.handle_key(&event) {
+                    context.set_handled();
+                }
+            }))
+        } else {
+            None
+        };
+
+        let content_size = Vector2::new(
+            (self.bounds.size.x - 2. * self.padding).max(0.),
+            (self.bounds.size.y - 2. * self.padding).max(0.),
+        );
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                background: self.background,
+                border: self.border,
+                corner_radius: self.corner_radius,
+                clip_contents: true,
+                opacity: if self.is_enabled { 1. } else { 0.5 },
+                ..LayerProps::default()
+            },
+            subviews: vec![Arc::new(Layer {
+                props: LayerProps {
+                    bounds: Rect::new(Point2::new(self.padding, self.padding), content_size),
+                    opacity,
+                    ..LayerProps::default()
+                },
+                subviews: vec![(self.render)(&text)],
+                ..Layer::default()
+            })],
+            key_action,
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn focus(&self) -> Option<Focus> {
+        if self.is_enabled {
+            Some(Focus::Focusable)
+        } else {
+            None
+        }
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.value.observe(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{text_layer, Window};
+    use birb::events::KeyModifiers;
+
+    /// Renders a text input with a “Name” placeholder and returns the log of rendered text.
+    fn window(value: &TextValue, is_enabled: bool) -> (Window, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut window = Window::new();
+        window.render(Arc::new(TextInput {
+            bounds: Rect::new(Point2::new(0., 0.), Vector2::new(100., 20.)),
+            value: value.clone(),
+            placeholder: "Name".into(),
+            render: text_layer(&log),
+            is_enabled,
+            ..TextInput::default()
+        }));
+        window.focus(50., 10.);
+        (window, log)
+    }
+
+    #[test]
+    fn placeholders_are_shown_while_empty() {
+        let value = TextValue::new("");
+        let (mut window, log) = window(&value, true);
+        assert_eq!(*log.lock(), ["Name"]);
+
+        value.set_text("Birbs");
+        let layers = window.tick();
+        assert_eq!(*log.lock(), ["Name", "Birbs"]);
+        // the content area is no longer dimmed
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers[1].opacity, 1.);
+        assert_eq!(layers[2].bounds.size.x, 5.);
+
+        value.set_text("Birbs");
+        assert!(!window.views.has_render_requests());
+        value.set_text("");
+        let layers = window.tick();
+        assert_eq!(layers[1].opacity, PLACEHOLDER_OPACITY);
+        assert_eq!(log.lock().last().unwrap(), "Name");
+    }
+
+    #[test]
+    fn typing_edits_the_text() {
+        let value = TextValue::new("");
+        let (mut window, log) = window(&value, true);
+
+        assert!(window.press(KeyCode::B, "B"));
+        assert!(window.press(KeyCode::I, "i"));
+        assert!(window.press(KeyCode::R, "rb"));
+        assert_eq!(value.text(), "Birb");
+        window.tick();
+        assert_eq!(log.lock().last().unwrap(), "Birb");
+
+        assert!(window.press(KeyCode::Delete, "\u{7f}"));
+        assert_eq!(value.text(), "Bir");
+        // deleting nothing is still handled
+        value.set_text("");
+        assert!(window.press(KeyCode::Delete, "\u{7f}"));
+        assert_eq!(value.text(), "");
+    }
+
+    #[test]
+    fn shortcuts_and_control_keys_are_left_alone() {
+        let value = TextValue::new("a");
+        let (mut window, _) = window(&value, true);
+
+        assert!(!window.key(
+            KeyEventPhase::Pressed,
+            KeyCode::V,
+            "v",
+            KeyModifiers::COMMAND
+        ));
+        assert!(!window.key(
+            KeyEventPhase::Pressed,
+            KeyCode::A,
+            "a",
+            KeyModifiers::CONTROL
+        ));
+        assert!(!window.key(KeyEventPhase::Released, KeyCode::B, "b", KeyModifiers::NONE));
+        assert!(!window.press(KeyCode::Return, "\r"));
+        assert!(!window.press(KeyCode::Tab, "\t"));
+        assert!(!window.press(KeyCode::Escape, "\u{1b}"));
+        assert_eq!(value.text(), "a");
+        assert!(!window.views.has_render_requests());
+    }
+
+    #[test]
+    fn disabled_inputs_ignore_typing() {
+        let value = TextValue::new("");
+        let (mut window, _) = window(&value, false);
+        assert!(!window.press(KeyCode::A, "a"));
+        assert_eq!(value.text(), "");
+    }
+}
@@ -0,0 +1,302 @@
+use birb::color::Color;
+use birb::dispatch::EventContext;
+use birb::events::{Event, EventHandler, Key, KeyCode, Tap};
+use birb::focus::Focus;
+use birb::raw_events::KeyEventPhase;
+use birb::{impl_view, AnyState, Context, Layer, LayerProps, Observers, Rect};
+use cgmath::{Point2, Vector2};
+use core::fmt;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The value of a [`Toggle`].
+///
+/// Clones share the same value, so one can be kept to read it back; the toggle is rendered again
+/// when it changes.
+#[derive(Clone)]
+pub struct ToggleValue {
+    is_on: Arc<Mutex<bool>>,
+    observers: Observers,
+}
+
+impl ToggleValue {
+    pub fn new(is_on: bool) -> ToggleValue {
+        ToggleValue {
+            is_on: Arc::new(Mutex::new(is_on)),
+            observers: Observers::new(),
+        }
+    }
+
+    /// Whether the toggle is on.
+    pub fn is_on(&self) -> bool {
+        *self.is_on.lock()
+    }
+
+    /// Turns the toggle on or off.
+    pub fn set(&self, is_on: bool) {
+        {
+            let mut value = self.is_on.lock();
+            if *value == is_on {
+                return;
+            }
+            *value = is_on;
+        }
+        self.observers.notify();
+    }
+
+    /// Flips the value.
+    pub fn toggle(&self) {
+        {
+            let mut value = self.is_on.lock();
+            *value = !*value;
+        }
+        self.observers.notify();
+    }
+
+    /// Renders the view of a context again whenever the value changes; see
+    /// [`Observers::observe`].
+    pub fn observe<Ctx>(&self, context: &Context<Ctx>) {
+        self.observers.observe(context);
+    }
+}
+
+impl fmt::Debug for ToggleValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ToggleValue").field(&self.is_on()).finish()
+    }
+}
+
+/// An on/off switch.
+///
+/// Flips its value when tapped, or when Space is pressed while it has keyboard focus.
+///
+/// ```
+/// use birb::{AnyState, Layer, Rect, View};
+/// use birb_widgets::{Toggle, ToggleValue};
+/// use cgmath::{Point2, Vector2};
+/// use std::sync::Arc;
+///
+/// let value = ToggleValue::new(false);
+/// let toggle = Toggle {
+///     bounds: Rect::new(Point2::new(0., 0.), Vector2::new(40., 24.)),
+///     value: value.clone(),
+///     ..Toggle::default()
+/// };
+///
+/// // the knob moves to the right when the toggle is on
+/// let knob_x = |toggle: &Toggle| {
+///     let body = View::<()>::body(toggle, &(Arc::new(()) as AnyState));
+///     let track = body.as_any().downcast_ref::<Layer>().unwrap();
+///     let knob = track.subviews[0].as_any().downcast_ref::<Layer>().unwrap();
+///     knob.props.bounds.origin.x
+/// };
+/// assert_eq!(knob_x(&toggle), 2.);
+/// value.toggle();
+/// assert_eq!(knob_x(&toggle), 18.);
+/// ```
+pub struct Toggle {
+    pub key: Option<u64>,
+
+    /// Toggle bounds. The knob is as tall as the bounds minus a small inset.
+    pub bounds: Rect,
+
+    /// The value.
+    pub value: ToggleValue,
+
+    /// Track color when the toggle is on.
+    pub on_color: Color,
+
+    /// Track color when the toggle is off.
+    pub off_color: Color,
+
+    /// Knob color.
+    pub knob_color: Color,
+
+    /// Whether the toggle can be used.
+    pub is_enabled: bool,
+}
+
+/// Distance between the knob and the track edges.
+const KNOB_INSET: f64 = 2.;
+
+impl Default for Toggle {
+    fn default() -> Self {
+        Toggle {
+            key: None,
+            bounds: Rect::zero(),
+            value: ToggleValue::new(false),
+            on_color: Color::rgb(0.2, 0.5, 1.),
+            off_color: Color::rgb(0.8, 0.8, 0.8),
+            knob_color: Color::WHITE,
+            is_enabled: true,
+        }
+    }
+}
+
+impl fmt::Debug for Toggle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Toggle")
+            .field("bounds", &self.bounds)
+            .field("value", &self.value)
+            .field("on_color", &self.on_color)
+            .field("off_color", &self.off_color)
+            .field("knob_color", &self.knob_color)
+            .field("is_enabled", &self.is_enabled)
+            .finish()
+    }
+}
+
+impl PartialEq for Toggle {
+    fn eq(&self, other: &Toggle) -> bool {
+        self.key == other.key
+            && self.bounds == other.bounds
+            && Arc::ptr_eq(&self.value.is_on, &other.value.is_on)
+            && self.on_color == other.on_color
+            && self.off_color == other.off_color
+            && self.knob_color == other.knob_color
+            && self.is_enabled == other.is_enabled
+    }
+}
+
+impl_view! {
+    Toggle;
+    fn body(&self, _state: &()) {
+        let is_on = self.value.is_on();
+        let knob_size = (self.bounds.size.y - 2. * KNOB_INSET).max(0.);
+        let knob_x = if is_on {
+            self.bounds.size.x - KNOB_INSET - knob_size
+        } else {
+            KNOB_INSET
+        };
+
+        let (tap_action, key_action) = if self.is_enabled {
+            let value = self.value.clone();
+            let tap_action = EventHandler::new(move |_: Event<Tap>, context: &mut EventContext| {
+                value.toggle();
+                context.set_handled();
+            });
+            let value = self.value.clone();
+            let key_action = EventHandler::new(move |event: Event<Key>, context: &mut EventContext| {
+                if event.code() != KeyCode::Space {
+                    return;
+                }
+                if event.phase() == KeyEventPhase::Pressed {
+                    value.toggle();
+                }
+                context.set_handled();
+            });
+            (Some(tap_action), Some(key_action))
+        } else {
+            (None, None)
+        };
+
+        Arc::new(Layer {
+            props: LayerProps {
+                bounds: self.bounds,
+                background: if is_on { self.on_color } else { self.off_color },
+                corner_radius: (self.bounds.size.y / 2.).into(),
+                opacity: if self.is_enabled { 1. } else { 0.5 },
+                ..LayerProps::default()
+            },
+            subviews: vec![Arc::new(Layer {
+                props: LayerProps {
+                    bounds: Rect::new(
+                        Point2::new(knob_x, KNOB_INSET),
+                        Vector2::new(knob_size, knob_size),
+                    ),
+                    background: self.knob_color,
+                    corner_radius: (knob_size / 2.).into(),
+                    ..LayerProps::default()
+                },
+                ..Layer::default()
+            })],
+            tap_action,
+            key_action,
+            ..Layer::default()
+        })
+    }
+    fn key(&self) -> Option<u64> {
+        self.key
+    }
+    fn focus(&self) -> Option<Focus> {
+        if self.is_enabled {
+            Some(Focus::Focusable)
+        } else {
+            None
+        }
+    }
+    fn effects(&self, _state: &AnyState<Ctx>, context: &Context<Ctx>) {
+        self.value.observe(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Window;
+    use birb::View;
+
+    fn toggle(value: &ToggleValue, is_enabled: bool) -> Arc<Toggle> {
+        Arc::new(Toggle {
+            bounds: Rect::new(Point2::new(0., 0.), Vector2::new(40., 24.)),
+            value: value.clone(),
+            is_enabled,
+            ..Toggle::default()
+        })
+    }
+
+    #[test]
+    fn toggles_show_their_value() {
+        let mut window = Window::new();
+        let value = ToggleValue::new(true);
+        let layers = window.render(toggle(&value, true));
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].background, Color::rgb(0.2, 0.5, 1.));
+        assert_eq!(layers[0].corner_radius, 12.0.into());
+        assert_eq!(
+            layers[1].bounds,
+            Rect::new(Point2::new(18., 2.), Vector2::new(20., 20.))
+        );
+
+        // setting the same value doesn’t render the toggle again
+        value.set(true);
+        assert!(!window.views.has_render_requests());
+        value.set(false);
+        let layers = window.tick();
+        assert_eq!(layers[0].background, Color::rgb(0.8, 0.8, 0.8));
+        assert_eq!(layers[1].bounds.origin, Point2::new(2., 2.));
+    }
+
+    #[test]
+    fn toggles_are_flipped_by_taps_and_space() {
+        let mut window = Window::new();
+        let value = ToggleValue::new(false);
+        window.render(toggle(&value, true));
+
+        assert!(window.tap(10., 10.));
+        assert!(value.is_on());
+        let layers = window.tick();
+        assert_eq!(layers[1].bounds.origin.x, 18.);
+
+        window.focus(10., 10.);
+        assert!(window.press(KeyCode::Space, " "));
+        assert!(!value.is_on());
+        assert!(!window.press(KeyCode::Return, "\r"));
+        assert!(!value.is_on());
+        assert_eq!(window.tick()[1].bounds.origin.x, 2.);
+    }
+
+    #[test]
+    fn disabled_toggles_ignore_events() {
+        let mut window = Window::new();
+        let value = ToggleValue::new(false);
+        let layers = window.render(toggle(&value, false));
+        assert_eq!(layers[0].opacity, 0.5);
+        assert!(View::<()>::focus(&*toggle(&value, false)).is_none());
+
+        window.focus(10., 10.);
+        assert!(!window.tap(10., 10.));
+        assert!(!window.press(KeyCode::Space, " "));
+        assert!(!value.is_on());
+    }
+}